@@ -0,0 +1,291 @@
+use std::collections::HashMap;
+
+use serde::Serialize;
+
+use crate::analysis::class_names::pretty_class_name;
+use crate::report::{render_blocks, Block};
+use crate::utils::{pretty_bytes_size, pretty_count};
+use crate::Heap;
+
+// the tracked instances are split in this many ranges of stack trace serial numbers
+pub const AGE_BUCKETS: usize = 4;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct AgeBucket {
+    pub first_serial_number: u32,
+    pub last_serial_number: u32,
+    pub instances: u64,
+    pub bytes: u64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ClassAge {
+    pub class_name: String,
+    pub instances: u64,
+    pub median_serial_number: u32,
+    // instances per bucket, oldest first
+    pub buckets: Vec<u64>,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct AllocationAgeReport {
+    // instances without a recorded allocation stack trace
+    pub untracked_instances: u64,
+    // oldest first
+    pub buckets: Vec<AgeBucket>,
+    // sorted by median serial number, ascending
+    pub earliest: Vec<ClassAge>,
+    // sorted by median serial number, descending
+    pub latest: Vec<ClassAge>,
+}
+
+/// Splits the instances with an allocation stack trace into `AGE_BUCKETS` ranges of trace serial numbers
+/// holding as many instances each, and keeps the `top` classes whose live instances were allocated the
+/// earliest and the latest.
+///
+/// The JVM numbers the stack traces as they are first seen, their serial numbers are only a proxy for the
+/// allocation order. Only dumps produced with allocation profiling carry stack traces on objects.
+pub fn allocation_age_report(heap: &Heap, top: usize) -> AllocationAgeReport {
+    let mut report = AllocationAgeReport::default();
+    // (serial number, class name, size)
+    let mut tracked: Vec<(u32, String, u64)> = Vec::new();
+    for instance in heap.instances_pool.values() {
+        let has_frames = heap
+            .stack_trace_by_serial_number
+            .get(&instance.stack_trace_serial_number)
+            .map(|stack_trace| !stack_trace.stack_frame_ids.is_empty())
+            .unwrap_or(false);
+        if has_frames {
+            tracked.push((
+                instance.stack_trace_serial_number,
+                heap.instance_class_name(instance),
+                heap.shallow_size(instance),
+            ));
+        } else {
+            report.untracked_instances += 1;
+        }
+    }
+    if tracked.is_empty() {
+        return report;
+    }
+    tracked.sort_unstable();
+
+    // the last serial number of each bucket, a serial number is never split between buckets
+    let mut last_serial_numbers: Vec<u32> = (1..=AGE_BUCKETS)
+        .map(|bucket| tracked[bucket * tracked.len() / AGE_BUCKETS - 1].0)
+        .collect();
+    last_serial_numbers.dedup();
+    let bucket_of =
+        |serial_number: u32| last_serial_numbers.partition_point(|last| *last < serial_number);
+
+    report.buckets = last_serial_numbers
+        .iter()
+        .map(|last| AgeBucket {
+            first_serial_number: *last,
+            last_serial_number: *last,
+            instances: 0,
+            bytes: 0,
+        })
+        .collect();
+    // class name -> (serial numbers in ascending order, instances per bucket)
+    let mut by_class: HashMap<&str, (Vec<u32>, Vec<u64>)> = HashMap::new();
+    for (serial_number, class_name, size) in &tracked {
+        let bucket_index = bucket_of(*serial_number);
+        let bucket = &mut report.buckets[bucket_index];
+        if bucket.instances == 0 {
+            bucket.first_serial_number = *serial_number;
+        }
+        bucket.instances += 1;
+        bucket.bytes += size;
+        let (serial_numbers, buckets) = by_class
+            .entry(class_name.as_str())
+            .or_insert_with(|| (Vec::new(), vec![0; last_serial_numbers.len()]));
+        serial_numbers.push(*serial_number);
+        buckets[bucket_index] += 1;
+    }
+
+    let mut classes: Vec<ClassAge> = by_class
+        .into_iter()
+        .map(|(class_name, (serial_numbers, buckets))| ClassAge {
+            class_name: class_name.to_string(),
+            instances: serial_numbers.len() as u64,
+            median_serial_number: serial_numbers[serial_numbers.len() / 2],
+            buckets,
+        })
+        .collect();
+    classes.sort_by(|a, b| {
+        a.median_serial_number
+            .cmp(&b.median_serial_number)
+            .then_with(|| b.instances.cmp(&a.instances))
+            .then_with(|| a.class_name.cmp(&b.class_name))
+    });
+    report.earliest = classes.iter().take(top).cloned().collect();
+    classes.sort_by(|a, b| {
+        b.median_serial_number
+            .cmp(&a.median_serial_number)
+            .then_with(|| b.instances.cmp(&a.instances))
+            .then_with(|| a.class_name.cmp(&b.class_name))
+    });
+    classes.truncate(top);
+    report.latest = classes;
+    report
+}
+
+impl AllocationAgeReport {
+    pub fn blocks(&self) -> Vec<Block> {
+        if self.buckets.is_empty() {
+            return vec![Block::Heading(format!(
+                "No allocation stack traces found ({} untracked instances)",
+                pretty_count(self.untracked_instances)
+            ))];
+        }
+        let rows: Vec<Vec<String>> = self
+            .buckets
+            .iter()
+            .map(|b| {
+                vec![
+                    format!("{}-{}", b.first_serial_number, b.last_serial_number),
+                    pretty_count(b.instances),
+                    pretty_bytes_size(b.bytes),
+                ]
+            })
+            .collect();
+        let mut blocks = vec![
+            Block::Heading(format!(
+                "Instances by stack trace serial number, oldest first ({} untracked instances):",
+                pretty_count(self.untracked_instances)
+            )),
+            Block::table(&["Stack traces", "Instances", "Total size"], rows),
+        ];
+        for (title, classes) in [
+            ("allocated the earliest", &self.earliest),
+            ("allocated the latest", &self.latest),
+        ] {
+            blocks.push(Block::Heading(format!(
+                "Top {} classes {}:",
+                classes.len(),
+                title
+            )));
+            blocks.push(class_block(classes));
+        }
+        blocks
+    }
+
+    pub fn render(&self) -> String {
+        render_blocks(&self.blocks())
+    }
+}
+
+fn class_block(classes: &[ClassAge]) -> Block {
+    let rows: Vec<Vec<String>> = classes
+        .iter()
+        .map(|c| {
+            vec![
+                c.median_serial_number.to_string(),
+                pretty_count(c.instances),
+                c.buckets
+                    .iter()
+                    .map(|count| pretty_count(*count))
+                    .collect::<Vec<_>>()
+                    .join(" / "),
+                pretty_class_name(&c.class_name),
+            ]
+        })
+        .collect();
+    Block::table(
+        &["Median stack trace", "Instances", "Per range", "Class name"],
+        rows,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::gc_record::ClassDumpFields;
+    use crate::parser::record::{LoadClassData, StackTraceData};
+    use crate::result_recorder::Instance;
+    use std::sync::Arc;
+
+    #[test]
+    fn classes_by_allocation_order() {
+        let mut heap = Heap::default();
+        heap.counter.id_size = 8;
+        heap.utf8_strings
+            .make_mut()
+            .insert(10, "com/acme/Config".into());
+        heap.utf8_strings
+            .make_mut()
+            .insert(11, "com/acme/Request".into());
+        for (serial_number, class_object_id, class_name_id) in [(1, 100, 10), (2, 101, 11)] {
+            heap.class_registry.make_mut().load(LoadClassData {
+                serial_number,
+                class_object_id,
+                stack_trace_serial_number: 0,
+                class_name_id,
+            });
+            heap.classes_dump.make_mut().insert(
+                class_object_id,
+                ClassDumpFields::new(
+                    class_object_id,
+                    0,
+                    0,
+                    0,
+                    0,
+                    Vec::new(),
+                    Vec::new(),
+                    Vec::new(),
+                ),
+            );
+        }
+        for serial_number in 1..=8 {
+            heap.stack_trace_by_serial_number.make_mut().insert(
+                serial_number,
+                StackTraceData {
+                    serial_number,
+                    thread_serial_number: 1,
+                    number_of_frames: 1,
+                    stack_frame_ids: vec![1],
+                },
+            );
+        }
+        // configs allocated at startup, requests later, one object without a trace
+        let objects = [
+            (1, 100, 1),
+            (2, 100, 2),
+            (3, 101, 5),
+            (4, 101, 6),
+            (5, 101, 7),
+            (6, 101, 8),
+            (7, 100, 8),
+            (8, 101, 0),
+        ];
+        for (object_id, class_object_id, stack_trace_serial_number) in objects {
+            heap.instances_pool.make_mut().insert(
+                object_id,
+                Arc::new(Instance {
+                    object_id,
+                    stack_trace_serial_number,
+                    class_object_id,
+                    data_size: 16,
+                    fields: Vec::new(),
+                    super_fields: Vec::new(),
+                }),
+            );
+        }
+
+        let report = allocation_age_report(&heap, 1);
+        assert_eq!(report.untracked_instances, 1);
+        let ranges: Vec<(u32, u32, u64)> = report
+            .buckets
+            .iter()
+            .map(|b| (b.first_serial_number, b.last_serial_number, b.instances))
+            .collect();
+        // serial number 8 is not split
+        assert_eq!(ranges, vec![(1, 1, 1), (2, 5, 2), (6, 7, 2), (8, 8, 2)]);
+        assert_eq!(report.earliest[0].class_name, "com.acme.Config");
+        assert_eq!(report.earliest[0].buckets, vec![1, 1, 0, 1]);
+        assert_eq!(report.latest[0].class_name, "com.acme.Request");
+        assert_eq!(report.latest[0].median_serial_number, 7);
+        assert!(report.render().contains("1 / 1 / 0 / 1"));
+    }
+}
@@ -0,0 +1,195 @@
+use std::collections::HashMap;
+
+use serde::Serialize;
+
+use crate::report::{render_blocks, Block};
+use crate::utils::{pretty_bytes_size, pretty_count};
+use crate::Heap;
+
+// only the innermost frames are rendered for each allocation site
+const MAX_RENDERED_FRAMES: usize = 5;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct AllocationSiteSummary {
+    pub stack_trace_serial_number: u32,
+    pub instances: u64,
+    pub bytes: u64,
+    pub frames: Vec<String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct AllocationSiteReport {
+    // instances without a recorded allocation stack trace
+    pub untracked_instances: u64,
+    pub sites: Vec<AllocationSiteSummary>,
+}
+
+/// Aggregates instances and arrays per allocating stack trace, keeping the `top` sites by shallow size.
+///
+/// Only dumps produced with allocation profiling carry non-empty stack traces on objects,
+/// all the other instances are counted as untracked.
+pub fn allocation_site_report(heap: &Heap, top: usize) -> AllocationSiteReport {
+    let mut report = AllocationSiteReport::default();
+    let mut by_serial_number: HashMap<u32, (u64, u64)> = HashMap::new();
+    for instance in heap.instances_pool.values() {
+        let has_frames = heap
+            .stack_trace_by_serial_number
+            .get(&instance.stack_trace_serial_number)
+            .map(|stack_trace| !stack_trace.stack_frame_ids.is_empty())
+            .unwrap_or(false);
+        if !has_frames {
+            report.untracked_instances += 1;
+            continue;
+        }
+        let entry = by_serial_number
+            .entry(instance.stack_trace_serial_number)
+            .or_default();
+        entry.0 += 1;
+        entry.1 += heap.shallow_size(instance);
+    }
+
+    let mut sites: Vec<(u32, (u64, u64))> = by_serial_number.into_iter().collect();
+    sites.sort_by(|(a_serial, (_, a_bytes)), (b_serial, (_, b_bytes))| {
+        b_bytes.cmp(a_bytes).then_with(|| a_serial.cmp(b_serial))
+    });
+    sites.truncate(top);
+
+    report.sites = sites
+        .into_iter()
+        .map(|(serial_number, (instances, bytes))| {
+            let frames = heap
+                .stack_frames(serial_number)
+                .unwrap_or_default()
+                .iter()
+                .take(MAX_RENDERED_FRAMES)
+                .map(|frame| format!("  {}", frame))
+                .collect();
+            AllocationSiteSummary {
+                stack_trace_serial_number: serial_number,
+                instances,
+                bytes,
+                frames,
+            }
+        })
+        .collect();
+    report
+}
+
+impl AllocationSiteReport {
+    pub fn blocks(&self) -> Vec<Block> {
+        if self.sites.is_empty() {
+            return vec![Block::Heading(format!(
+                "No allocation stack traces found ({} untracked instances)",
+                pretty_count(self.untracked_instances)
+            ))];
+        }
+        let rows: Vec<Vec<String>> = self
+            .sites
+            .iter()
+            .map(|s| {
+                vec![
+                    pretty_bytes_size(s.bytes),
+                    pretty_count(s.instances),
+                    s.stack_trace_serial_number.to_string(),
+                ]
+            })
+            .collect();
+        let mut blocks = vec![
+            Block::Heading(format!(
+                "Top {} allocation sites ({} untracked instances):",
+                self.sites.len(),
+                pretty_count(self.untracked_instances)
+            )),
+            Block::table(&["Total size", "Instances", "Stack trace"], rows),
+        ];
+        for site in &self.sites {
+            blocks.push(Block::Heading(format!(
+                "Stack trace {}",
+                site.stack_trace_serial_number
+            )));
+            blocks.push(Block::Lines(site.frames.clone()));
+        }
+        blocks
+    }
+
+    pub fn render(&self) -> String {
+        render_blocks(&self.blocks())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::record::{StackFrameData, StackTraceData};
+    use crate::result_recorder::Instance;
+    use crate::tests::add_class;
+    use std::sync::Arc;
+
+    #[test]
+    fn instances_by_allocating_stack_trace() {
+        let mut heap = Heap::default();
+        heap.counter.id_size = 8;
+        heap.utf8_strings.make_mut().insert(1, "put".into());
+        heap.utf8_strings.make_mut().insert(2, "Cache.java".into());
+        add_class(&mut heap, 100, 0, "com/acme/Cache");
+        heap.stack_frame_by_id.make_mut().insert(
+            10,
+            StackFrameData {
+                stack_frame_id: 10,
+                method_name_id: 1,
+                method_signature_id: 0,
+                source_file_name_id: 2,
+                class_serial_number: 100,
+                line_number: 42,
+            },
+        );
+        // trace 3 has no frames, e.g. when allocation profiling is off
+        for (serial_number, stack_frame_ids) in [(1, vec![10]), (2, vec![10, 10]), (3, vec![])] {
+            heap.stack_trace_by_serial_number.make_mut().insert(
+                serial_number,
+                StackTraceData {
+                    serial_number,
+                    thread_serial_number: 1,
+                    number_of_frames: stack_frame_ids.len() as u32,
+                    stack_frame_ids,
+                },
+            );
+        }
+        for (object_id, stack_trace_serial_number) in
+            [(1, 1), (2, 2), (3, 2), (4, 2), (5, 3), (6, 0)]
+        {
+            heap.instances_pool.make_mut().insert(
+                object_id,
+                Arc::new(Instance {
+                    object_id,
+                    stack_trace_serial_number,
+                    class_object_id: 100,
+                    data_size: 8,
+                    fields: Vec::new(),
+                    super_fields: Vec::new(),
+                }),
+            );
+        }
+        let instance_size = heap.shallow_size(&heap.instances_pool[&1]);
+
+        let report = allocation_site_report(&heap, 10);
+        assert_eq!(report.untracked_instances, 2);
+        let sites: Vec<(u32, u64, u64)> = report
+            .sites
+            .iter()
+            .map(|s| (s.stack_trace_serial_number, s.instances, s.bytes))
+            .collect();
+        assert_eq!(
+            sites,
+            vec![(2, 3, 3 * instance_size), (1, 1, instance_size)]
+        );
+        assert_eq!(report.sites[0].frames.len(), 2);
+        assert!(report.sites[0].frames[0].contains("com.acme.Cache.put"));
+
+        let top = allocation_site_report(&heap, 1);
+        assert_eq!(top.sites.len(), 1);
+        assert!(top
+            .render()
+            .contains("Top 1 allocation sites (2 untracked instances)"));
+    }
+}
@@ -0,0 +1,237 @@
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::analysis::class_names::{pretty_class_name, NameGrouping};
+use crate::analysis::histogram::ClassHistogram;
+use crate::errors::HprofSlurpError;
+use crate::errors::HprofSlurpError::*;
+use crate::report::{render_blocks, Block, ReportMetadata};
+use crate::utils::{pretty_bytes_size, pretty_count};
+
+// histogram of an idle OpenJDK 17 process dumping its own heap
+const JDK_BASELINE: &str = include_str!("baselines/jdk17.txt");
+// name selecting the shipped baseline instead of a file
+pub const JDK_BASELINE_NAME: &str = "jdk";
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BaselineClass {
+    pub instances: u64,
+    pub total_size: u64,
+}
+
+/// Expected instances per class of a "normal" heap, stored as `<instances> <total size> <class name>` lines.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct BaselineProfile {
+    // ordered by class name so that serialized profiles are stable
+    pub classes: BTreeMap<String, BaselineClass>,
+}
+
+impl BaselineProfile {
+    /// The baseline shipped with the tool, covering the JDK bootstrap overhead only.
+    pub fn jdk() -> Self {
+        Self::parse(JDK_BASELINE).expect("the shipped baseline is valid")
+    }
+
+    /// Loads a profile file, `jdk` selects the shipped baseline.
+    pub fn load(path: &str) -> Result<Self, HprofSlurpError> {
+        if path == JDK_BASELINE_NAME {
+            return Ok(Self::jdk());
+        }
+        Self::parse(&std::fs::read_to_string(path)?)
+    }
+
+    pub fn from_histogram(histogram: &ClassHistogram) -> Self {
+        let classes = histogram
+            .classes
+            .iter()
+            .map(|c| {
+                let class = BaselineClass {
+                    instances: c.instances,
+                    total_size: c.total_size,
+                };
+                (c.class_name.clone(), class)
+            })
+            .collect();
+        BaselineProfile { classes }
+    }
+
+    /// Merges the classes sharing a group name, to compare against a histogram grouped the same way.
+    pub fn group_names(&mut self, grouping: NameGrouping) {
+        if !grouping.is_enabled() {
+            return;
+        }
+        let mut classes: BTreeMap<String, BaselineClass> = BTreeMap::new();
+        for (name, class) in std::mem::take(&mut self.classes) {
+            let group = classes.entry(grouping.group_name(&name)).or_default();
+            group.instances += class.instances;
+            group.total_size += class.total_size;
+        }
+        self.classes = classes;
+    }
+
+    // blank lines and `#` comments are ignored
+    pub fn parse(content: &str) -> Result<Self, HprofSlurpError> {
+        let mut classes = BTreeMap::new();
+        for (number, line) in content.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let invalid = || InvalidBaselineProfile {
+                message: format!("line {}: `{}`", number + 1, line),
+            };
+            let mut parts = line.splitn(3, char::is_whitespace);
+            let instances = parts.next().and_then(|p| p.parse().ok());
+            let total_size = parts.next().and_then(|p| p.parse().ok());
+            let class_name = parts.next().map(str::trim).filter(|n| !n.is_empty());
+            match (instances, total_size, class_name) {
+                (Some(instances), Some(total_size), Some(class_name)) => {
+                    let class = BaselineClass {
+                        instances,
+                        total_size,
+                    };
+                    classes.insert(class_name.to_string(), class);
+                }
+                _ => return Err(invalid()),
+            }
+        }
+        Ok(BaselineProfile { classes })
+    }
+
+    /// Serializes the profile in the format read by `parse`, largest classes first.
+    pub fn render(&self) -> String {
+        let mut classes: Vec<(&String, &BaselineClass)> = self.classes.iter().collect();
+        classes.sort_by(|a, b| b.1.total_size.cmp(&a.1.total_size).then(a.0.cmp(b.0)));
+        classes
+            .into_iter()
+            .map(|(name, class)| format!("{} {} {}\n", class.instances, class.total_size, name))
+            .collect()
+    }
+
+    /// Like `render`, preceded by `#` comments describing where the profile comes from.
+    pub fn render_with_metadata(&self, metadata: &ReportMetadata) -> String {
+        let mut content: String = metadata
+            .fields()
+            .into_iter()
+            .map(|(field, value)| format!("# {}: {}\n", field, value))
+            .collect();
+        content.push_str(&self.render());
+        content
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct BaselineDiff {
+    pub class_name: String,
+    pub instances: u64,
+    pub baseline_instances: u64,
+    // total size above the baseline
+    pub growth: u64,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct BaselineDiffReport {
+    pub total_growth: u64,
+    pub classes: Vec<BaselineDiff>,
+}
+
+/// Subtracts the baseline from the histogram, keeping the `top` classes growing the most beyond it.
+pub fn baseline_diff_report(
+    histogram: &ClassHistogram,
+    baseline: &BaselineProfile,
+    top: usize,
+) -> BaselineDiffReport {
+    let mut classes: Vec<BaselineDiff> = histogram
+        .classes
+        .iter()
+        .filter_map(|c| {
+            let expected = baseline
+                .classes
+                .get(&c.class_name)
+                .copied()
+                .unwrap_or_default();
+            let growth = c.total_size.saturating_sub(expected.total_size);
+            (growth > 0).then(|| BaselineDiff {
+                class_name: c.class_name.clone(),
+                instances: c.instances,
+                baseline_instances: expected.instances,
+                growth,
+            })
+        })
+        .collect();
+    classes.sort_by(|a, b| {
+        b.growth
+            .cmp(&a.growth)
+            .then_with(|| a.class_name.cmp(&b.class_name))
+    });
+    let total_growth = classes.iter().map(|c| c.growth).sum();
+    classes.truncate(top);
+    BaselineDiffReport {
+        total_growth,
+        classes,
+    }
+}
+
+impl BaselineDiffReport {
+    pub fn blocks(&self) -> Vec<Block> {
+        let heading = Block::Heading(format!(
+            "Found {} above the baseline, top {} growing classes:",
+            pretty_bytes_size(self.total_growth),
+            self.classes.len()
+        ));
+        let rows: Vec<Vec<String>> = self
+            .classes
+            .iter()
+            .map(|c| {
+                vec![
+                    pretty_bytes_size(c.growth),
+                    pretty_count(c.instances),
+                    pretty_count(c.baseline_instances),
+                    pretty_class_name(&c.class_name),
+                ]
+            })
+            .collect();
+        vec![
+            heading,
+            Block::table(&["Growth", "Instances", "Baseline", "Class name"], rows),
+        ]
+    }
+
+    pub fn render(&self) -> String {
+        render_blocks(&self.blocks())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_and_render_profile() {
+        let profile = BaselineProfile::parse(
+            "# comment\n3 96 java.lang.String\n\n1 24 java.util.HashMap$Node[]\n",
+        )
+        .unwrap();
+        assert_eq!(
+            profile.classes.get("java.util.HashMap$Node[]"),
+            Some(&BaselineClass {
+                instances: 1,
+                total_size: 24
+            })
+        );
+        assert_eq!(
+            profile.render(),
+            "3 96 java.lang.String\n1 24 java.util.HashMap$Node[]\n"
+        );
+        let metadata = ReportMetadata::new(Default::default(), 20, false, Default::default());
+        let annotated = profile.render_with_metadata(&metadata);
+        assert!(annotated.starts_with("# Tool: hprof-slurp "));
+        assert_eq!(
+            BaselineProfile::parse(&annotated).unwrap().classes,
+            profile.classes
+        );
+        assert!(BaselineProfile::parse("3 java.lang.String").is_err());
+        assert!(!BaselineProfile::jdk().classes.is_empty());
+    }
+}
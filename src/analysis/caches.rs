@@ -0,0 +1,300 @@
+use serde::Serialize;
+
+use crate::graph::HeapGraph;
+use crate::parser::gc_record::FieldValue;
+use crate::report::{render_blocks, Block};
+use crate::result_recorder::Instance;
+use crate::utils::pretty_bytes_size;
+use crate::Heap;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum CacheFramework {
+    Guava,
+    Caffeine,
+    Ehcache2,
+    Ehcache3,
+}
+
+// (base class matched including subclasses, framework, field path from the cache to its backing map)
+const CACHE_CLASSES: [(&str, CacheFramework, &[&str]); 5] = [
+    (
+        "com.google.common.cache.LocalCache",
+        CacheFramework::Guava,
+        &[],
+    ),
+    (
+        "com.github.benmanes.caffeine.cache.BoundedLocalCache",
+        CacheFramework::Caffeine,
+        &["data"],
+    ),
+    (
+        "com.github.benmanes.caffeine.cache.UnboundedLocalCache",
+        CacheFramework::Caffeine,
+        &["data"],
+    ),
+    (
+        "net.sf.ehcache.store.MemoryStore",
+        CacheFramework::Ehcache2,
+        &["map"],
+    ),
+    (
+        "org.ehcache.impl.internal.store.heap.OnHeapStore",
+        CacheFramework::Ehcache3,
+        &["map", "realMap"],
+    ),
+];
+
+#[derive(Debug, Clone, Serialize)]
+pub struct CacheInstance {
+    pub object_id: u64,
+    pub class_name: String,
+    pub framework: CacheFramework,
+    // `None` if the backing map could not be found
+    pub estimated_entries: Option<u64>,
+    pub retained_size: u64,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct CacheReport {
+    pub caches: Vec<CacheInstance>,
+}
+
+/// Lists the Guava, Caffeine and Ehcache cache instances sorted by retained size.
+pub fn cache_report(heap: &Heap, graph: &HeapGraph) -> CacheReport {
+    let mut caches: Vec<CacheInstance> = CACHE_CLASSES
+        .iter()
+        .flat_map(|(base_class, framework, map_path)| {
+            heap.instances_of(base_class).map(|instance| {
+                let backing_map = map_path.iter().try_fold(instance, |current, field| {
+                    heap.field_instance(current, field)
+                });
+                CacheInstance {
+                    object_id: instance.object_id,
+                    class_name: heap.instance_class_name(instance),
+                    framework: *framework,
+                    estimated_entries: backing_map.and_then(|map| map_entry_count(heap, map)),
+                    retained_size: graph.retained_size(instance.object_id).unwrap_or_default(),
+                }
+            })
+        })
+        .collect();
+    caches.sort_by(|a, b| {
+        b.retained_size
+            .cmp(&a.retained_size)
+            .then_with(|| a.object_id.cmp(&b.object_id))
+    });
+    CacheReport { caches }
+}
+
+// Reads the size bookkeeping of ConcurrentHashMap (JDK8+), segmented maps (Guava, JDK7, Ehcache2) and HashMap
+fn map_entry_count(heap: &Heap, map: &Instance) -> Option<u64> {
+    if let Some(FieldValue::Long(base_count)) = heap.field_value(map, "baseCount") {
+        let cells: i64 = object_array_elements(heap, map, "counterCells")
+            .filter_map(|cell| match heap.field_value(cell, "value") {
+                Some(FieldValue::Long(value)) => Some(*value),
+                _ => None,
+            })
+            .sum();
+        return Some((base_count + cells).max(0) as u64);
+    }
+    if heap.field_instance(map, "segments").is_some() {
+        let count: i64 = object_array_elements(heap, map, "segments")
+            .filter_map(|segment| match heap.field_value(segment, "count") {
+                Some(FieldValue::Int(count)) => Some(*count as i64),
+                _ => None,
+            })
+            .sum();
+        return Some(count.max(0) as u64);
+    }
+    match heap.field_value(map, "size") {
+        Some(FieldValue::Int(size)) => Some((*size).max(0) as u64),
+        _ => None,
+    }
+}
+
+fn object_array_elements<'a>(
+    heap: &'a Heap,
+    instance: &Instance,
+    field_name: &str,
+) -> impl Iterator<Item = &'a Instance> + 'a {
+    let ids: &[u64] = heap
+        .field_instance(instance, field_name)
+        .and_then(|array| array.object_array())
+        .unwrap_or_default();
+    ids.iter()
+        .filter_map(|id| heap.instances_pool.get(id).map(|i| i.as_ref()))
+}
+
+impl CacheReport {
+    pub fn blocks(&self) -> Vec<Block> {
+        let mut blocks = vec![Block::Heading(format!(
+            "Found {} cache instances:",
+            self.caches.len()
+        ))];
+        if self.caches.is_empty() {
+            return blocks;
+        }
+        let rows: Vec<Vec<String>> = self
+            .caches
+            .iter()
+            .map(|c| {
+                vec![
+                    pretty_bytes_size(c.retained_size),
+                    c.estimated_entries
+                        .map(|e| e.to_string())
+                        .unwrap_or_else(|| "?".to_string()),
+                    format!("{:?}", c.framework),
+                    format!("0x{:x}", c.object_id),
+                    c.class_name.clone(),
+                ]
+            })
+            .collect();
+        blocks.push(Block::table(
+            &[
+                "Retained",
+                "Entries",
+                "Framework",
+                "Object id",
+                "Class name",
+            ],
+            rows,
+        ));
+        blocks
+    }
+
+    pub fn render(&self) -> String {
+        render_blocks(&self.blocks())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::gc_record::{ArrayValue, Values};
+    use crate::result_recorder::RootJniGlobal;
+    use crate::tests::{add_class, add_instance, reference, single};
+
+    #[test]
+    fn caches_with_their_entries() {
+        let mut heap = Heap::default();
+        heap.counter.id_size = 8;
+        for (name_id, name) in [
+            (1, "data"),
+            (2, "baseCount"),
+            (3, "counterCells"),
+            (4, "value"),
+            (5, "segments"),
+            (6, "count"),
+            (7, "map"),
+            (8, "size"),
+        ] {
+            heap.utf8_strings.make_mut().insert(name_id, name.into());
+        }
+        for (class_object_id, super_class_object_id, class_name) in [
+            (
+                100,
+                0,
+                "com/github/benmanes/caffeine/cache/BoundedLocalCache",
+            ),
+            (101, 100, "com/github/benmanes/caffeine/cache/SSMS"),
+            (102, 0, "java/util/concurrent/ConcurrentHashMap"),
+            (103, 0, "java/util/concurrent/ConcurrentHashMap$CounterCell"),
+            (104, 0, "com/google/common/cache/LocalCache"),
+            (105, 0, "com/google/common/cache/LocalCache$Segment"),
+            (106, 0, "net/sf/ehcache/store/MemoryStore"),
+            (107, 0, "java/util/HashMap"),
+            (108, 0, "org/ehcache/impl/internal/store/heap/OnHeapStore"),
+        ] {
+            add_class(
+                &mut heap,
+                class_object_id,
+                super_class_object_id,
+                class_name,
+            );
+        }
+        let object_array =
+            |elements: Vec<u64>| vec![(0, Values::Array(ArrayValue::Object(elements)))];
+        // caffeine: a concurrent map counting 3 entries plus 2 in its counter cells
+        add_instance(&mut heap, 1, 101, vec![reference(1, 2)]);
+        add_instance(
+            &mut heap,
+            2,
+            102,
+            vec![single(2, FieldValue::Long(3)), reference(3, 3)],
+        );
+        add_instance(&mut heap, 3, 0, object_array(vec![4, 5]));
+        for cell_id in [4, 5] {
+            add_instance(
+                &mut heap,
+                cell_id,
+                103,
+                vec![single(4, FieldValue::Long(1))],
+            );
+        }
+        // guava: the cache itself holds the segments
+        add_instance(&mut heap, 10, 104, vec![reference(5, 11)]);
+        add_instance(&mut heap, 11, 0, object_array(vec![12, 13]));
+        add_instance(&mut heap, 12, 105, vec![single(6, FieldValue::Int(2))]);
+        add_instance(&mut heap, 13, 105, vec![single(6, FieldValue::Int(4))]);
+        // ehcache 2 over a hash map, ehcache 3 without its map
+        add_instance(&mut heap, 20, 106, vec![reference(7, 21)]);
+        add_instance(&mut heap, 21, 107, vec![single(8, FieldValue::Int(7))]);
+        add_instance(&mut heap, 30, 108, vec![reference(7, 0)]);
+        for object_id in [1, 10, 20, 30] {
+            heap.root_jni_global.make_mut().insert(
+                object_id,
+                RootJniGlobal {
+                    object_id,
+                    jni_global_ref_id: 0,
+                },
+            );
+        }
+        let graph = HeapGraph::new(&heap);
+
+        let report = cache_report(&heap, &graph);
+        let cache = |object_id: u64| {
+            report
+                .caches
+                .iter()
+                .find(|c| c.object_id == object_id)
+                .unwrap()
+        };
+        let caffeine = cache(1);
+        assert_eq!(caffeine.framework, CacheFramework::Caffeine);
+        assert_eq!(
+            caffeine.class_name,
+            "com.github.benmanes.caffeine.cache.SSMS"
+        );
+        assert_eq!(caffeine.estimated_entries, Some(5));
+        // the cache retains its map, the counter cells and their array
+        assert_eq!(
+            caffeine.retained_size,
+            [1, 2, 3, 4, 5]
+                .iter()
+                .map(|id| graph.shallow_size(*id).unwrap())
+                .sum::<u64>()
+        );
+        assert_eq!(
+            (cache(10).framework, cache(10).estimated_entries),
+            (CacheFramework::Guava, Some(6))
+        );
+        assert_eq!(
+            (cache(20).framework, cache(20).estimated_entries),
+            (CacheFramework::Ehcache2, Some(7))
+        );
+        assert_eq!(
+            (cache(30).framework, cache(30).estimated_entries),
+            (CacheFramework::Ehcache3, None)
+        );
+        assert_eq!(report.caches.len(), 4);
+        assert!(report
+            .caches
+            .windows(2)
+            .all(|pair| pair[0].retained_size >= pair[1].retained_size));
+
+        let rendered = report.render();
+        assert!(rendered.contains("Found 4 cache instances"));
+        let unknown = rendered.lines().find(|line| line.contains("0x1e")).unwrap();
+        assert!(unknown.contains(" ? | ") && unknown.contains("Ehcache3"));
+    }
+}
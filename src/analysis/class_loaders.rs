@@ -0,0 +1,329 @@
+use std::collections::HashMap;
+
+use serde::Serialize;
+
+use crate::analysis::render_reference_chain;
+use crate::graph::HeapGraph;
+use crate::parser::gc_record::FieldValue;
+use crate::report::{render_blocks, Block};
+use crate::result_recorder::Instance;
+use crate::utils::{pretty_bytes_size, pretty_count};
+use crate::Heap;
+
+const CLASS_LOADER_CLASS: &str = "java.lang.ClassLoader";
+const THREAD_CLASS: &str = "java.lang.Thread";
+const THREAD_LOCAL_ENTRY_CLASS: &str = "java.lang.ThreadLocal$ThreadLocalMap$Entry";
+const DRIVER_MANAGER_CLASS: &str = "java.sql.DriverManager";
+const DRIVER_INFO_CLASS: &str = "java.sql.DriverInfo";
+const TOMCAT_CLASS_LOADER_CLASS: &str = "org.apache.catalina.loader.WebappClassLoaderBase";
+
+// loaders living as long as the JVM
+const BUILTIN_CLASS_LOADERS: [&str; 7] = [
+    "jdk.internal.loader.ClassLoaders$AppClassLoader",
+    "jdk.internal.loader.ClassLoaders$PlatformClassLoader",
+    "jdk.internal.loader.ClassLoaders$BootClassLoader",
+    "sun.misc.Launcher$AppClassLoader",
+    "sun.misc.Launcher$ExtClassLoader",
+    "jdk.internal.reflect.DelegatingClassLoader",
+    "sun.reflect.DelegatingClassLoader",
+];
+
+/// What keeps a class loader alive, based on its shortest path from the GC roots.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum ClassLoaderRetention {
+    // static field of a class defined by the loader itself
+    LoadedClass,
+    // `contextClassLoader` of a thread
+    ThreadContext,
+    ThreadLocal,
+    // driver registered in `java.sql.DriverManager` and never deregistered
+    JdbcDriver,
+    Other,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ClassLoaderSummary {
+    pub object_id: u64,
+    pub class_name: String,
+    pub loaded_classes: u64,
+    pub retained_size: u64,
+    // the loader reports itself as stopped (e.g. undeployed Tomcat web application)
+    pub stopped: bool,
+    pub retention: ClassLoaderRetention,
+    pub retention_chain: Vec<String>,
+    pub leak_suspect: bool,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ClassLoaderReport {
+    pub loaders: Vec<ClassLoaderSummary>,
+}
+
+impl ClassLoaderReport {
+    pub fn leak_suspects(&self) -> impl Iterator<Item = &ClassLoaderSummary> {
+        self.loaders.iter().filter(|l| l.leak_suspect)
+    }
+}
+
+/// Inspects the application class loaders to find the ones leaked by redeployments.
+///
+/// A loader is a leak suspect if it reports itself as stopped, or if several loaders of the same class
+/// exist and it is only kept alive through its own classes, a thread context, a thread local or a JDBC driver.
+pub fn class_loader_report(heap: &Heap, graph: &HeapGraph) -> ClassLoaderReport {
+    let mut loaded_classes: HashMap<u64, u64> = HashMap::new();
+    for class in heap.classes_dump.values() {
+        *loaded_classes
+            .entry(class.class_loader_object_id)
+            .or_default() += 1;
+    }
+
+    let loaders: Vec<&Instance> = heap
+        .instances_of(CLASS_LOADER_CLASS)
+        .map(|l| l.as_ref())
+        .filter(|loader| {
+            BUILTIN_CLASS_LOADERS
+                .iter()
+                .all(|builtin| !heap.is_subclass_of(loader.class_object_id, builtin))
+        })
+        .collect();
+
+    let mut loaders_per_class: HashMap<u64, u64> = HashMap::new();
+    for loader in &loaders {
+        *loaders_per_class.entry(loader.class_object_id).or_default() += 1;
+    }
+
+    let mut summaries: Vec<ClassLoaderSummary> = loaders
+        .iter()
+        .map(|loader| {
+            let path = graph
+                .path_from_roots(loader.object_id)
+                .unwrap_or_else(|| vec![loader.object_id]);
+            let retention = retention_kind(heap, loader.object_id, &path);
+            let stopped = is_stopped(heap, loader);
+            let redeployed = loaders_per_class[&loader.class_object_id] > 1;
+            ClassLoaderSummary {
+                object_id: loader.object_id,
+                class_name: heap.instance_class_name(loader),
+                loaded_classes: loaded_classes
+                    .get(&loader.object_id)
+                    .copied()
+                    .unwrap_or_default(),
+                retained_size: graph.retained_size(loader.object_id).unwrap_or_default(),
+                stopped,
+                retention,
+                retention_chain: render_reference_chain(heap, &path),
+                leak_suspect: stopped || (redeployed && retention != ClassLoaderRetention::Other),
+            }
+        })
+        .collect();
+    summaries.sort_by(|a, b| {
+        b.retained_size
+            .cmp(&a.retained_size)
+            .then_with(|| a.object_id.cmp(&b.object_id))
+    });
+    ClassLoaderReport { loaders: summaries }
+}
+
+fn retention_kind(heap: &Heap, loader_id: u64, path: &[u64]) -> ClassLoaderRetention {
+    let holders = &path[..path.len() - 1];
+    let own_class = holders.iter().any(|id| {
+        heap.classes_dump
+            .get(id)
+            .map(|class| class.class_loader_object_id == loader_id)
+            .unwrap_or(false)
+    });
+    if own_class {
+        return ClassLoaderRetention::LoadedClass;
+    }
+    let instance_of = |id: &u64, class_name: &str| {
+        heap.instances_pool
+            .get(id)
+            .map(|i| heap.is_subclass_of(i.class_object_id, class_name))
+            .unwrap_or(false)
+    };
+    if holders
+        .iter()
+        .any(|id| instance_of(id, THREAD_LOCAL_ENTRY_CLASS))
+    {
+        ClassLoaderRetention::ThreadLocal
+    } else if holders.iter().any(|id| {
+        instance_of(id, DRIVER_INFO_CLASS)
+            || heap.class_name(*id).as_deref() == Some(DRIVER_MANAGER_CLASS)
+    }) {
+        ClassLoaderRetention::JdbcDriver
+    } else if holders
+        .last()
+        .map(|id| instance_of(id, THREAD_CLASS))
+        .unwrap_or(false)
+    {
+        ClassLoaderRetention::ThreadContext
+    } else {
+        ClassLoaderRetention::Other
+    }
+}
+
+// Tomcat 8+ exposes a `LifecycleState` while Tomcat 7 only has a `started` flag
+fn is_stopped(heap: &Heap, loader: &Instance) -> bool {
+    if !heap.is_subclass_of(loader.class_object_id, TOMCAT_CLASS_LOADER_CLASS) {
+        return false;
+    }
+    if let Some(state) = heap
+        .field_instance(loader, "state")
+        .and_then(|state| heap.enum_name(state))
+    {
+        return matches!(
+            state.as_str(),
+            "STOPPING" | "STOPPED" | "DESTROYING" | "DESTROYED" | "FAILED"
+        );
+    }
+    matches!(
+        heap.field_value(loader, "started"),
+        Some(FieldValue::Bool(false))
+    )
+}
+
+impl ClassLoaderReport {
+    pub fn blocks(&self) -> Vec<Block> {
+        let mut blocks = vec![Block::Heading(format!(
+            "Found {} application class loaders ({} leak suspects):",
+            self.loaders.len(),
+            self.leak_suspects().count()
+        ))];
+        if self.loaders.is_empty() {
+            return blocks;
+        }
+        let rows: Vec<Vec<String>> = self
+            .loaders
+            .iter()
+            .map(|l| {
+                let class_name = if l.leak_suspect {
+                    format!("{} (leak suspect)", l.class_name)
+                } else {
+                    l.class_name.clone()
+                };
+                vec![
+                    pretty_bytes_size(l.retained_size),
+                    pretty_count(l.loaded_classes),
+                    format!("{:?}", l.retention),
+                    format!("0x{:x}", l.object_id),
+                    class_name,
+                ]
+            })
+            .collect();
+        blocks.push(Block::table(
+            &[
+                "Retained",
+                "Classes",
+                "Retention",
+                "Object id",
+                "Class name",
+            ],
+            rows,
+        ));
+        for suspect in self.leak_suspects() {
+            blocks.push(Block::Heading(format!(
+                "Class loader 0x{:x} is kept alive by:",
+                suspect.object_id
+            )));
+            blocks.push(Block::Lines(suspect.retention_chain.clone()));
+        }
+        blocks
+    }
+
+    pub fn render(&self) -> String {
+        render_blocks(&self.blocks())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::result_recorder::RootJniGlobal;
+    use crate::tests::{add_class, add_instance, reference, single};
+
+    // field name ids
+    const CONTEXT_CLASS_LOADER: u64 = 1;
+    const STARTED: u64 = 2;
+
+    #[test]
+    fn redeployed_and_stopped_loaders() {
+        let mut heap = Heap::default();
+        heap.counter.id_size = 8;
+        heap.utf8_strings
+            .make_mut()
+            .insert(CONTEXT_CLASS_LOADER, "contextClassLoader".into());
+        heap.utf8_strings
+            .make_mut()
+            .insert(STARTED, "started".into());
+        for (class_object_id, super_class_object_id, class_name) in [
+            (100, 0, "java/lang/ClassLoader"),
+            (101, 100, "com/acme/PluginLoader"),
+            (102, 100, "org/apache/catalina/loader/WebappClassLoaderBase"),
+            (103, 100, "jdk/internal/loader/ClassLoaders$AppClassLoader"),
+            (104, 0, "java/lang/Thread"),
+        ] {
+            add_class(
+                &mut heap,
+                class_object_id,
+                super_class_object_id,
+                class_name,
+            );
+        }
+        // the plugin class is defined by loader 1 and pinned as a sticky class
+        add_class(&mut heap, 105, 0, "com/acme/Plugin").class_loader_object_id = 1;
+        heap.root_sticky_class.make_mut().push(105);
+        // three plugin loaders: kept by their own class, by a thread context and by a JNI reference
+        for loader_id in [1, 2, 5] {
+            add_instance(&mut heap, loader_id, 101, Vec::new());
+        }
+        add_instance(&mut heap, 3, 104, vec![reference(CONTEXT_CLASS_LOADER, 2)]);
+        // an undeployed web application and the built-in application loader
+        add_instance(
+            &mut heap,
+            6,
+            102,
+            vec![single(STARTED, FieldValue::Bool(false))],
+        );
+        add_instance(&mut heap, 7, 103, Vec::new());
+        for object_id in [3, 5, 6, 7] {
+            heap.root_jni_global.make_mut().insert(
+                object_id,
+                RootJniGlobal {
+                    object_id,
+                    jni_global_ref_id: 0,
+                },
+            );
+        }
+        let graph = HeapGraph::new(&heap);
+
+        let report = class_loader_report(&heap, &graph);
+        assert_eq!(report.loaders.len(), 4);
+        let loader = |object_id: u64| {
+            report
+                .loaders
+                .iter()
+                .find(|l| l.object_id == object_id)
+                .unwrap()
+        };
+        assert_eq!(loader(1).retention, ClassLoaderRetention::LoadedClass);
+        assert_eq!(loader(1).loaded_classes, 1);
+        assert_eq!(loader(2).retention, ClassLoaderRetention::ThreadContext);
+        assert_eq!(loader(5).retention, ClassLoaderRetention::Other);
+        assert_eq!(
+            loader(6).class_name,
+            "org.apache.catalina.loader.WebappClassLoaderBase"
+        );
+        assert!(loader(6).stopped);
+        let suspects: Vec<u64> = {
+            let mut ids: Vec<u64> = report.leak_suspects().map(|l| l.object_id).collect();
+            ids.sort_unstable();
+            ids
+        };
+        assert_eq!(suspects, vec![1, 2, 6]);
+
+        let rendered = report.render();
+        assert!(rendered.contains("Found 4 application class loaders (3 leak suspects)"));
+        assert!(rendered.contains("Class loader 0x2 is kept alive by:"));
+        assert!(!rendered.contains("Class loader 0x5 is kept alive by:"));
+    }
+}
@@ -0,0 +1,517 @@
+use std::collections::hash_map::RandomState;
+use std::fmt;
+use std::hash::{BuildHasher, Hasher};
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+
+const LAMBDA_MARKER: &str = "$$Lambda";
+const PROXY_PREFIX: &str = "$Proxy";
+// `$`-separated name parts introduced by class generation libraries e.g. `Foo$$EnhancerBySpringCGLIB$$1a2b3c`
+const ENHANCER_MARKERS: &[&str] = &["CGLIB", "ByteBuddy", "HibernateProxy", "MockitoMock"];
+// packages of the JDK, their classes keep their name once anonymized
+const JDK_PACKAGES: &[&str] = &[
+    "java.",
+    "javax.",
+    "jdk.",
+    "sun.",
+    "com.sun.",
+    "org.ietf.",
+    "org.w3c.",
+    "org.xml.",
+];
+const ACCESSOR_PREFIXES: &[&str] = &[
+    "GeneratedMethodAccessor",
+    "GeneratedConstructorAccessor",
+    "GeneratedSerializationConstructorAccessor",
+];
+
+/// Which generated class names are collapsed into a stable group name, so that histograms
+/// and diffs between dumps line up.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct NameGrouping {
+    // `Foo/0x0000000800c01234` hidden classes lose their address
+    pub hidden_classes: bool,
+    // `Foo$$Lambda$123` becomes `Foo$$Lambda`
+    pub lambdas: bool,
+    // `jdk.proxy2.$Proxy12` becomes `jdk.proxy.$Proxy`
+    pub proxies: bool,
+    // `Foo$$EnhancerBySpringCGLIB$$1a2b3c` becomes `Foo$$EnhancerBySpringCGLIB`
+    pub enhancers: bool,
+    // `jdk.internal.reflect.GeneratedMethodAccessor12` becomes `jdk.internal.reflect.GeneratedMethodAccessor`
+    pub accessors: bool,
+    // `Foo$1` and `Foo$Bar` become `Foo`, not part of `all` as these classes are not generated
+    pub nested_classes: bool,
+}
+
+impl NameGrouping {
+    pub fn all() -> Self {
+        NameGrouping {
+            hidden_classes: true,
+            lambdas: true,
+            proxies: true,
+            enhancers: true,
+            accessors: true,
+            nested_classes: false,
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        *self != NameGrouping::default()
+    }
+
+    /// Returns the group name of a class name as rendered in reports, array suffixes are kept.
+    pub fn group_name(&self, class_name: &str) -> String {
+        let element = class_name.trim_end_matches("[]");
+        let dimensions = &class_name[element.len()..];
+        let mut name = element.to_string();
+        if self.hidden_classes {
+            strip_hidden_class_address(&mut name);
+        }
+        if self.lambdas {
+            if let Some(position) = name.find(LAMBDA_MARKER) {
+                name.truncate(position + LAMBDA_MARKER.len());
+            }
+        }
+        if self.proxies {
+            group_proxy(&mut name);
+        }
+        if self.enhancers {
+            strip_enhancer_suffix(&mut name);
+        }
+        if self.accessors {
+            let simple_name_start = name.rfind('.').map_or(0, |dot| dot + 1);
+            if ACCESSOR_PREFIXES
+                .iter()
+                .any(|prefix| name[simple_name_start..].starts_with(prefix))
+            {
+                let trimmed = name.trim_end_matches(|c: char| c.is_ascii_digit()).len();
+                name.truncate(trimmed);
+            }
+        }
+        if self.nested_classes {
+            strip_nested_class_names(&mut name);
+        }
+        name.push_str(dimensions);
+        name
+    }
+}
+
+impl FromStr for NameGrouping {
+    type Err = String;
+
+    /// Parses a comma separated list of `hidden`, `lambdas`, `proxies`, `enhancers`, `accessors`, `nested`, `all` or `none`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut grouping = NameGrouping::default();
+        for kind in s.split(',').map(str::trim) {
+            match kind {
+                "all" => grouping = NameGrouping::all(),
+                "none" => grouping = NameGrouping::default(),
+                "hidden" => grouping.hidden_classes = true,
+                "lambdas" => grouping.lambdas = true,
+                "proxies" => grouping.proxies = true,
+                "enhancers" => grouping.enhancers = true,
+                "accessors" => grouping.accessors = true,
+                "nested" => grouping.nested_classes = true,
+                other => return Err(format!("unknown name group `{}`", other)),
+            }
+        }
+        Ok(grouping)
+    }
+}
+
+impl fmt::Display for NameGrouping {
+    // the form parsed by `from_str`
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let kinds: Vec<&str> = [
+            (self.hidden_classes, "hidden"),
+            (self.lambdas, "lambdas"),
+            (self.proxies, "proxies"),
+            (self.enhancers, "enhancers"),
+            (self.accessors, "accessors"),
+            (self.nested_classes, "nested"),
+        ]
+        .iter()
+        .filter(|(enabled, _)| *enabled)
+        .map(|(_, kind)| *kind)
+        .collect();
+        if kinds.is_empty() {
+            f.write_str("none")
+        } else {
+            f.write_str(&kinds.join(","))
+        }
+    }
+}
+
+/// Returns the class name of a descriptor, internal or binary name with arrays suffixed by their dimensions,
+/// e.g. `[B` is `byte[]` and `[Ljava/util/Map$Entry;` is `java.util.Map$Entry[]`.
+pub fn java_type_name(name: &str) -> String {
+    let element = name.trim_start_matches('[');
+    let dimensions = name.len() - element.len();
+    let element = match (dimensions, element) {
+        (0, _) => element,
+        (_, "Z") => "boolean",
+        (_, "B") => "byte",
+        (_, "C") => "char",
+        (_, "S") => "short",
+        (_, "I") => "int",
+        (_, "J") => "long",
+        (_, "F") => "float",
+        (_, "D") => "double",
+        _ => element,
+    };
+    let element = element
+        .strip_prefix('L')
+        .and_then(|class| class.strip_suffix(';'))
+        .unwrap_or(element);
+    format!("{}{}", element.replace('/', "."), "[]".repeat(dimensions))
+}
+
+/// Returns the name of a class as written in Java sources, nested classes included,
+/// e.g. `Ljava/util/Map$Entry;` is `java.util.Map.Entry`.
+///
+/// Anonymous, local and generated classes (`Foo$1`, `Foo$$Lambda$12`) keep their `$`.
+pub fn pretty_class_name(name: &str) -> String {
+    let name = java_type_name(name);
+    let chars: Vec<char> = name.chars().collect();
+    chars
+        .iter()
+        .enumerate()
+        .map(|(i, c)| {
+            let nested = *c == '$'
+                && i > 0
+                && (chars[i - 1].is_alphanumeric() || chars[i - 1] == '_')
+                && chars
+                    .get(i + 1)
+                    .is_some_and(|next| next.is_alphabetic() || *next == '_');
+            if nested {
+                '.'
+            } else {
+                *c
+            }
+        })
+        .collect()
+}
+
+/// Returns a pseudonym of an application class name as found in the dump, e.g. `com/acme/Order$Item` becomes
+/// `p1b2c3d4e/p5f6a7b8c/C9d0e1f2a$C3b4c5d6e`, to share reports without revealing the structure of the code.
+///
+/// A package or class name part always gets the same pseudonym for a given `salt`. The JDK classes, primitive
+/// arrays, anonymous class numbers, lambda markers and hidden class addresses are kept.
+pub fn anonymized_class_name(name: &str, salt: &str) -> String {
+    let element = name.trim_start_matches('[');
+    let dimensions = &name[..name.len() - element.len()];
+    let (prefix, class, suffix) = if dimensions.is_empty() {
+        ("", element, "")
+    } else {
+        match element.strip_prefix('L').and_then(|c| c.strip_suffix(';')) {
+            Some(class) => ("L", class, ";"),
+            // primitive arrays
+            None => return name.to_string(),
+        }
+    };
+    let separator = if class.contains('/') { '/' } else { '.' };
+    let dotted = class.replace('/', ".");
+    if JDK_PACKAGES
+        .iter()
+        .any(|package| dotted.starts_with(package))
+    {
+        return name.to_string();
+    }
+    let mut stripped = class.to_string();
+    strip_hidden_class_address(&mut stripped);
+    let address = &class[stripped.len()..];
+    let (package, simple_name) = match stripped.rfind(separator) {
+        Some(position) => (&stripped[..position], &stripped[position + 1..]),
+        None => ("", stripped.as_str()),
+    };
+    let mut parts: Vec<String> = package
+        .split(separator)
+        .filter(|part| !part.is_empty())
+        .map(|part| pseudonym('p', part, salt))
+        .collect();
+    let simple_name: Vec<String> = simple_name
+        .split('$')
+        .map(|part| {
+            let generated = part.is_empty()
+                || part.chars().all(|c| c.is_ascii_digit())
+                || part == "Lambda"
+                || ENHANCER_MARKERS.iter().any(|marker| part.contains(marker));
+            if generated {
+                part.to_string()
+            } else {
+                pseudonym('C', part, salt)
+            }
+        })
+        .collect();
+    parts.push(simple_name.join("$"));
+    format!(
+        "{}{}{}{}{}",
+        dimensions,
+        prefix,
+        parts.join(&separator.to_string()),
+        address,
+        suffix
+    )
+}
+
+/// Returns a method descriptor (e.g. `(Lcom/acme/Order;I)V`) with the pseudonyms of `anonymized_class_name`
+/// in place of its class names, anything else than a method descriptor is returned as is.
+pub fn anonymized_method_descriptor(descriptor: &str, salt: &str) -> String {
+    if !descriptor.starts_with('(') {
+        return descriptor.to_string();
+    }
+    let mut anonymized = String::with_capacity(descriptor.len());
+    let mut rest = descriptor;
+    while let Some(position) = rest.find('L') {
+        let Some(end) = rest[position..].find(';') else {
+            break;
+        };
+        anonymized.push_str(&rest[..=position]);
+        anonymized.push_str(&anonymized_class_name(
+            &rest[position + 1..position + end],
+            salt,
+        ));
+        rest = &rest[position + end..];
+    }
+    anonymized.push_str(rest);
+    anonymized
+}
+
+/// Returns a salt for `anonymized_class_name` which differs between runs, so that the pseudonyms
+/// can't be reversed by hashing a dictionary of usual package and class names.
+pub fn random_salt() -> String {
+    // the std hasher keys are drawn from the OS random source
+    format!("{:016x}", RandomState::new().build_hasher().finish())
+}
+
+// FNV-1a, stable across runs and platforms unlike the std hasher
+fn pseudonym(kind: char, part: &str, salt: &str) -> String {
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for byte in salt.bytes().chain([0]).chain(part.bytes()) {
+        hash ^= byte as u64;
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+    format!("{}{:08x}", kind, (hash >> 32) as u32 ^ hash as u32)
+}
+
+/// Returns true for hidden classes, named after the class they are defined for followed by their address.
+pub fn is_hidden_class_name(class_name: &str) -> bool {
+    let mut name = class_name.trim_end_matches("[]").to_string();
+    let len = name.len();
+    strip_hidden_class_address(&mut name);
+    name.len() != len
+}
+
+// hidden classes end with `/0x<address>` in the dump, `+0x<address>` or `.0x<address>` once rendered
+fn strip_hidden_class_address(name: &mut String) {
+    if let Some(position) = name.rfind("0x") {
+        let address = &name[position + 2..];
+        let separator = name[..position].chars().last();
+        if position > 0
+            && matches!(separator, Some('/' | '+' | '.'))
+            && !address.is_empty()
+            && address.chars().all(|c| c.is_ascii_hexdigit())
+        {
+            name.truncate(position - 1);
+        }
+    }
+}
+
+fn group_proxy(name: &mut String) {
+    let (package, simple_name) = match name.rfind('.') {
+        Some(dot) => (&name[..dot], &name[dot + 1..]),
+        None => ("", name.as_str()),
+    };
+    let Some(number) = simple_name.strip_prefix(PROXY_PREFIX) else {
+        return;
+    };
+    if number.is_empty() || !number.chars().all(|c| c.is_ascii_digit()) {
+        return;
+    }
+    // JDK 16+ spreads the proxies over `jdk.proxy1`, `jdk.proxy2`... modules
+    let package = package.trim_end_matches(|c: char| c.is_ascii_digit());
+    *name = if package.is_empty() {
+        PROXY_PREFIX.to_string()
+    } else {
+        format!("{}.{}", package, PROXY_PREFIX)
+    };
+}
+
+// keeps the outer class, the `$` starting a simple name (e.g. `$Proxy12`) does not nest
+fn strip_nested_class_names(name: &mut String) {
+    let simple_name_start = name.rfind('.').map_or(0, |dot| dot + 1);
+    if let Some(position) = name[simple_name_start..]
+        .char_indices()
+        .skip(1)
+        .find(|(_, c)| *c == '$')
+        .map(|(position, _)| simple_name_start + position)
+    {
+        name.truncate(position);
+    }
+}
+
+fn strip_enhancer_suffix(name: &mut String) {
+    let mut end = 0;
+    for part in name.split('$') {
+        end += part.len();
+        if !part.is_empty() && ENHANCER_MARKERS.iter().any(|marker| part.contains(marker)) {
+            name.truncate(end);
+            return;
+        }
+        end += 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn anonymized_class_names() {
+        for kept in [
+            "java/util/HashMap$Node",
+            "[Ljava/lang/String;",
+            "[B",
+            "jdk.internal.reflect.GeneratedMethodAccessor12",
+        ] {
+            assert_eq!(anonymized_class_name(kept, ""), kept);
+        }
+        let order = anonymized_class_name("com/acme/Order", "");
+        let item = anonymized_class_name("com/acme/Order$Item", "");
+        assert!(
+            order.starts_with('p') && !order.contains("acme"),
+            "{}",
+            order
+        );
+        // the same parts share their pseudonyms
+        assert_eq!(
+            item,
+            format!("{}${}", order, &anonymized_class_name("Item", ""))
+        );
+        assert_eq!(
+            anonymized_class_name("[[Lcom/acme/Order;", ""),
+            format!("[[L{};", order)
+        );
+        assert_ne!(anonymized_class_name("com/acme/Order", "salt"), order);
+        let lambda = anonymized_class_name("com.acme.Order$$Lambda$22+0x800000035", "");
+        assert!(lambda.ends_with("$$Lambda$22+0x800000035"), "{}", lambda);
+        assert_eq!(lambda.matches('.').count(), 2);
+    }
+
+    #[test]
+    fn anonymized_method_descriptors() {
+        let order = anonymized_class_name("com/acme/Order", "salt");
+        assert_eq!(
+            anonymized_method_descriptor("([Lcom/acme/Order;ILjava/lang/String;)V", "salt"),
+            format!("([L{};ILjava/lang/String;)V", order)
+        );
+        assert_eq!(
+            anonymized_method_descriptor("()Lcom/acme/Order;", "salt"),
+            format!("()L{};", order)
+        );
+        // primitives only, or not a descriptor
+        assert_eq!(anonymized_method_descriptor("(IJ)Z", "salt"), "(IJ)Z");
+        assert_eq!(anonymized_method_descriptor("Lorem", "salt"), "Lorem");
+    }
+
+    #[test]
+    fn random_salts_differ() {
+        let salt = random_salt();
+        assert_eq!(salt.len(), 16);
+        assert_ne!(salt, random_salt());
+    }
+
+    #[test]
+    fn pretty_class_names() {
+        let cases = [
+            ("[B", "byte[]"),
+            ("[[Z", "boolean[][]"),
+            ("Ljava/util/Map$Entry;", "java.util.Map.Entry"),
+            ("[Ljava.lang.Object;", "java.lang.Object[]"),
+            ("java.util.HashMap$Node[]", "java.util.HashMap.Node[]"),
+            ("java/lang/String", "java.lang.String"),
+            ("com.acme.Main$1", "com.acme.Main$1"),
+            (
+                "java.util.stream.Collectors$$Lambda$22+0x800000035",
+                "java.util.stream.Collectors$$Lambda$22+0x800000035",
+            ),
+            ("jdk.proxy2.$Proxy12", "jdk.proxy2.$Proxy12"),
+            ("B", "B"),
+        ];
+        for (name, expected) in cases {
+            assert_eq!(pretty_class_name(name), expected, "{}", name);
+        }
+        assert_eq!(
+            java_type_name("[Ljava/util/Map$Entry;"),
+            "java.util.Map$Entry[]"
+        );
+    }
+
+    #[test]
+    fn group_generated_class_names() {
+        let grouping = NameGrouping::all();
+        let cases = [
+            (
+                "java.util.stream.Collectors$$Lambda$22+0x800000035",
+                "java.util.stream.Collectors$$Lambda",
+            ),
+            (
+                "java.lang.invoke.LambdaForm$MH.0x0000000800c01234[]",
+                "java.lang.invoke.LambdaForm$MH[]",
+            ),
+            ("jdk.proxy2.$Proxy12", "jdk.proxy.$Proxy"),
+            ("com.sun.proxy.$Proxy3", "com.sun.proxy.$Proxy"),
+            (
+                "com.acme.Service$$EnhancerBySpringCGLIB$$1a2b3c",
+                "com.acme.Service$$EnhancerBySpringCGLIB",
+            ),
+            ("com.acme.Repo$ByteBuddy$AbCd12", "com.acme.Repo$ByteBuddy"),
+            (
+                "jdk.internal.reflect.GeneratedMethodAccessor12",
+                "jdk.internal.reflect.GeneratedMethodAccessor",
+            ),
+            ("java.util.HashMap$Node[]", "java.util.HashMap$Node[]"),
+            ("com.acme.Version2", "com.acme.Version2"),
+        ];
+        for (name, group) in cases {
+            assert_eq!(grouping.group_name(name), group);
+        }
+        assert_eq!(
+            "lambdas,proxies".parse::<NameGrouping>().unwrap(),
+            NameGrouping {
+                lambdas: true,
+                proxies: true,
+                ..Default::default()
+            }
+        );
+        assert!("bogus".parse::<NameGrouping>().is_err());
+        assert_eq!(
+            "proxies,lambdas"
+                .parse::<NameGrouping>()
+                .unwrap()
+                .to_string(),
+            "lambdas,proxies"
+        );
+        assert_eq!(NameGrouping::default().to_string(), "none");
+    }
+    #[test]
+    fn group_nested_classes() {
+        let grouping: NameGrouping = "nested".parse().unwrap();
+        let cases = [
+            ("com.acme.Service$1", "com.acme.Service"),
+            ("com.acme.Service$Worker$Task[]", "com.acme.Service[]"),
+            ("com.acme.Service$$Lambda$12", "com.acme.Service"),
+            ("jdk.proxy2.$Proxy12", "jdk.proxy2.$Proxy12"),
+            ("com.acme.Service", "com.acme.Service"),
+        ];
+        for (name, group) in cases {
+            assert_eq!(grouping.group_name(name), group);
+        }
+        assert!(!NameGrouping::all().nested_classes);
+        assert_eq!(
+            "all,nested".parse::<NameGrouping>().unwrap().to_string(),
+            "hidden,lambdas,proxies,enhancers,accessors,nested"
+        );
+    }
+}
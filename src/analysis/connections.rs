@@ -0,0 +1,299 @@
+use std::collections::HashMap;
+
+use serde::Serialize;
+
+use crate::graph::HeapGraph;
+use crate::parser::gc_record::FieldValue;
+use crate::report::{render_blocks, Block};
+use crate::result_recorder::Instance;
+use crate::utils::{pretty_bytes_size, pretty_count};
+use crate::Heap;
+
+// number of open connections for a single class above which it is flagged as a leak suspect
+pub const DEFAULT_LEAK_THRESHOLD: u64 = 100;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum ConnectionKind {
+    Socket,
+    SocketChannel,
+    HikariPoolEntry,
+    CommonsPooledObject,
+}
+
+// the base classes are matched including their subclasses
+const CONNECTION_CLASSES: [(&str, ConnectionKind); 4] = [
+    ("java.net.Socket", ConnectionKind::Socket),
+    (
+        "java.nio.channels.SocketChannel",
+        ConnectionKind::SocketChannel,
+    ),
+    (
+        "com.zaxxer.hikari.pool.PoolEntry",
+        ConnectionKind::HikariPoolEntry,
+    ),
+    (
+        "org.apache.commons.pool2.impl.DefaultPooledObject",
+        ConnectionKind::CommonsPooledObject,
+    ),
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum ConnectionState {
+    // connected socket or connection borrowed from a pool
+    Open,
+    // not connected yet or sitting idle in a pool
+    Idle,
+    Closed,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ConnectionClassSummary {
+    pub class_name: String,
+    pub kind: ConnectionKind,
+    pub instances: u64,
+    pub open: u64,
+    pub idle: u64,
+    pub closed: u64,
+    pub retained_size: u64,
+    pub leak_suspect: bool,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ConnectionReport {
+    pub classes: Vec<ConnectionClassSummary>,
+}
+
+/// Counts sockets, socket channels and pooled connections by state along with the memory they retain.
+///
+/// Classes holding at least `leak_threshold` open connections are flagged as leak suspects.
+pub fn connection_report(heap: &Heap, graph: &HeapGraph, leak_threshold: u64) -> ConnectionReport {
+    let mut by_class: HashMap<u64, ConnectionClassSummary> = HashMap::new();
+    for (base_class, kind) in CONNECTION_CLASSES {
+        for instance in heap.instances_of(base_class) {
+            let summary = by_class.entry(instance.class_object_id).or_insert_with(|| {
+                ConnectionClassSummary {
+                    class_name: heap.instance_class_name(instance),
+                    kind,
+                    instances: 0,
+                    open: 0,
+                    idle: 0,
+                    closed: 0,
+                    retained_size: 0,
+                    leak_suspect: false,
+                }
+            });
+            summary.instances += 1;
+            summary.retained_size += graph.retained_size(instance.object_id).unwrap_or_default();
+            match connection_state(heap, instance, kind) {
+                ConnectionState::Open => summary.open += 1,
+                ConnectionState::Idle => summary.idle += 1,
+                ConnectionState::Closed => summary.closed += 1,
+            }
+        }
+    }
+
+    let mut classes: Vec<ConnectionClassSummary> = by_class
+        .into_values()
+        .map(|mut summary| {
+            summary.leak_suspect = summary.open >= leak_threshold;
+            summary
+        })
+        .collect();
+    classes.sort_by(|a, b| {
+        b.retained_size
+            .cmp(&a.retained_size)
+            .then_with(|| a.class_name.cmp(&b.class_name))
+    });
+    ConnectionReport { classes }
+}
+
+fn connection_state(heap: &Heap, instance: &Instance, kind: ConnectionKind) -> ConnectionState {
+    let bool_field = |name: &str| {
+        matches!(
+            heap.field_value(instance, name),
+            Some(FieldValue::Bool(true))
+        )
+    };
+    let int_field = |name: &str| match heap.field_value(instance, name) {
+        Some(FieldValue::Int(value)) => Some(*value),
+        _ => None,
+    };
+    match kind {
+        ConnectionKind::Socket => {
+            if bool_field("closed") {
+                ConnectionState::Closed
+            } else if bool_field("connected") {
+                ConnectionState::Open
+            } else {
+                ConnectionState::Idle
+            }
+        }
+        ConnectionKind::SocketChannel => {
+            // `closed` is declared by AbstractInterruptibleChannel, state 2 is `ST_CONNECTED` in SocketChannelImpl
+            if bool_field("closed") {
+                ConnectionState::Closed
+            } else if int_field("state") == Some(2) {
+                ConnectionState::Open
+            } else {
+                ConnectionState::Idle
+            }
+        }
+        // STATE_IN_USE = 1, STATE_NOT_IN_USE = 0, STATE_REMOVED = -1, STATE_RESERVED = -2
+        ConnectionKind::HikariPoolEntry => match int_field("state") {
+            Some(1) => ConnectionState::Open,
+            Some(-1) => ConnectionState::Closed,
+            _ => ConnectionState::Idle,
+        },
+        // `state` is a PooledObjectState enum
+        ConnectionKind::CommonsPooledObject => {
+            let state = heap
+                .field_instance(instance, "state")
+                .and_then(|state| heap.enum_name(state));
+            match state.as_deref() {
+                Some("ALLOCATED") => ConnectionState::Open,
+                Some("INVALID") | Some("ABANDONED") => ConnectionState::Closed,
+                _ => ConnectionState::Idle,
+            }
+        }
+    }
+}
+
+impl ConnectionReport {
+    pub fn blocks(&self) -> Vec<Block> {
+        let total: u64 = self.classes.iter().map(|c| c.instances).sum();
+        let open: u64 = self.classes.iter().map(|c| c.open).sum();
+        let mut blocks = vec![Block::Heading(format!(
+            "Found {} connection objects ({} open):",
+            pretty_count(total),
+            pretty_count(open)
+        ))];
+        if self.classes.is_empty() {
+            return blocks;
+        }
+        let rows: Vec<Vec<String>> = self
+            .classes
+            .iter()
+            .map(|c| {
+                let class_name = if c.leak_suspect {
+                    format!("{} (leak suspect)", c.class_name)
+                } else {
+                    c.class_name.clone()
+                };
+                vec![
+                    pretty_bytes_size(c.retained_size),
+                    pretty_count(c.instances),
+                    pretty_count(c.open),
+                    pretty_count(c.idle),
+                    pretty_count(c.closed),
+                    class_name,
+                ]
+            })
+            .collect();
+        blocks.push(Block::table(
+            &[
+                "Retained",
+                "Instances",
+                "Open",
+                "Idle",
+                "Closed",
+                "Class name",
+            ],
+            rows,
+        ));
+        blocks
+    }
+
+    pub fn render(&self) -> String {
+        render_blocks(&self.blocks())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::{add_class, add_instance, single};
+
+    // field name ids
+    const CLOSED: u64 = 1;
+    const CONNECTED: u64 = 2;
+    const STATE: u64 = 3;
+
+    #[test]
+    fn connections_by_class_and_state() {
+        let mut heap = Heap::default();
+        heap.counter.id_size = 8;
+        for (name_id, name) in [
+            (CLOSED, "closed"),
+            (CONNECTED, "connected"),
+            (STATE, "state"),
+        ] {
+            heap.utf8_strings.make_mut().insert(name_id, name.into());
+        }
+        add_class(&mut heap, 100, 0, "java/net/Socket");
+        add_class(&mut heap, 101, 100, "com/acme/TlsSocket");
+        add_class(&mut heap, 102, 0, "com/zaxxer/hikari/pool/PoolEntry");
+        let socket = |closed: bool, connected: bool| {
+            vec![
+                single(CLOSED, FieldValue::Bool(closed)),
+                single(CONNECTED, FieldValue::Bool(connected)),
+            ]
+        };
+        // two open sockets and a closed one, an open subclass socket
+        add_instance(&mut heap, 1, 100, socket(false, true));
+        add_instance(&mut heap, 2, 100, socket(false, true));
+        add_instance(&mut heap, 3, 100, socket(true, true));
+        add_instance(&mut heap, 4, 101, socket(false, true));
+        // in use, not in use and removed pool entries
+        for (object_id, state) in [(5, 1), (6, 0), (7, -1)] {
+            add_instance(
+                &mut heap,
+                object_id,
+                102,
+                vec![single(STATE, FieldValue::Int(state))],
+            );
+        }
+        let graph = HeapGraph::new(&heap);
+
+        let report = connection_report(&heap, &graph, 2);
+        let summary = |class_name: &str| {
+            report
+                .classes
+                .iter()
+                .find(|c| c.class_name == class_name)
+                .unwrap()
+        };
+        let sockets = summary("java.net.Socket");
+        assert_eq!(sockets.kind, ConnectionKind::Socket);
+        assert_eq!(
+            (
+                sockets.instances,
+                sockets.open,
+                sockets.idle,
+                sockets.closed
+            ),
+            (3, 2, 0, 1)
+        );
+        assert!(sockets.leak_suspect);
+        assert_eq!(
+            sockets.retained_size,
+            [1, 2, 3]
+                .iter()
+                .map(|id| graph.retained_size(*id).unwrap())
+                .sum::<u64>()
+        );
+        // subclasses get their own row
+        let tls = summary("com.acme.TlsSocket");
+        assert_eq!(
+            (tls.kind, tls.open, tls.leak_suspect),
+            (ConnectionKind::Socket, 1, false)
+        );
+        let pool = summary("com.zaxxer.hikari.pool.PoolEntry");
+        assert_eq!(pool.kind, ConnectionKind::HikariPoolEntry);
+        assert_eq!((pool.open, pool.idle, pool.closed), (1, 1, 1));
+        assert_eq!(report.classes.len(), 3);
+
+        let rendered = report.render();
+        assert!(rendered.contains("Found 7 connection objects (4 open)"));
+        assert!(rendered.contains("java.net.Socket (leak suspect)"));
+    }
+}
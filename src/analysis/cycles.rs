@@ -0,0 +1,213 @@
+use std::collections::HashMap;
+
+use serde::Serialize;
+
+use crate::analysis::class_names::pretty_class_name;
+use crate::graph::HeapGraph;
+use crate::report::{render_blocks, Block};
+use crate::utils::{pretty_bytes_size, pretty_count};
+use crate::Heap;
+
+// classes listed per cycle
+const CYCLE_CLASSES: usize = 5;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ReferenceCycle {
+    pub objects: u64,
+    pub shallow_size: u64,
+    // freed once nothing outside of the cycle references it
+    pub retained_size: u64,
+    // (class name, instances) sorted by instances
+    pub classes: Vec<(String, u64)>,
+    // lowest object id of the cycle
+    pub example_object_id: u64,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct CycleReport {
+    pub min_size: usize,
+    pub cycles: u64,
+    pub objects: u64,
+    // largest first
+    pub largest: Vec<ReferenceCycle>,
+}
+
+/// Finds the reference cycles, i.e. the strongly connected components, of at least `min_size` objects and
+/// keeps the `top` largest with the classes taking part in them.
+///
+/// Class objects are left out like in the graph metrics, every instance referencing its class.
+pub fn cycle_report(heap: &Heap, graph: &HeapGraph, min_size: usize, top: usize) -> CycleReport {
+    let components = graph.strongly_connected_components(min_size.max(2), |object_id| {
+        !heap.classes_dump.contains_key(&object_id)
+    });
+    let largest = components
+        .iter()
+        .take(top)
+        .map(|component| {
+            let mut by_class: HashMap<String, u64> = HashMap::new();
+            for object_id in component {
+                *by_class
+                    .entry(heap.object_class_name(*object_id))
+                    .or_insert(0) += 1;
+            }
+            let mut classes: Vec<(String, u64)> = by_class.into_iter().collect();
+            classes.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+            ReferenceCycle {
+                objects: component.len() as u64,
+                shallow_size: component
+                    .iter()
+                    .filter_map(|id| graph.shallow_size(*id))
+                    .sum(),
+                retained_size: graph.retained_size_of(component),
+                classes,
+                example_object_id: component[0],
+            }
+        })
+        .collect();
+    CycleReport {
+        min_size,
+        cycles: components.len() as u64,
+        objects: components.iter().map(|c| c.len() as u64).sum(),
+        largest,
+    }
+}
+
+impl CycleReport {
+    pub fn blocks(&self) -> Vec<Block> {
+        let mut blocks = vec![Block::Heading(format!(
+            "Found {} reference cycles of at least {} objects, holding {} objects:",
+            pretty_count(self.cycles),
+            self.min_size,
+            pretty_count(self.objects)
+        ))];
+        if self.largest.is_empty() {
+            return blocks;
+        }
+        let rows = self
+            .largest
+            .iter()
+            .map(|cycle| {
+                let mut classes: Vec<String> = cycle
+                    .classes
+                    .iter()
+                    .take(CYCLE_CLASSES)
+                    .map(|(class_name, count)| {
+                        format!(
+                            "{} x {}",
+                            pretty_count(*count),
+                            pretty_class_name(class_name)
+                        )
+                    })
+                    .collect();
+                if cycle.classes.len() > CYCLE_CLASSES {
+                    classes.push(format!(
+                        "{} more classes",
+                        cycle.classes.len() - CYCLE_CLASSES
+                    ));
+                }
+                vec![
+                    pretty_count(cycle.objects),
+                    pretty_bytes_size(cycle.shallow_size),
+                    pretty_bytes_size(cycle.retained_size),
+                    format!("0x{:x}", cycle.example_object_id),
+                    classes.join(", "),
+                ]
+            })
+            .collect();
+        blocks.push(Block::table(
+            &[
+                "Objects",
+                "Shallow size",
+                "Retained size",
+                "Example",
+                "Classes",
+            ],
+            rows,
+        ));
+        blocks
+    }
+
+    pub fn render(&self) -> String {
+        render_blocks(&self.blocks())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::gc_record::ClassDumpFields;
+    use crate::parser::record::LoadClassData;
+    use crate::result_recorder::RootJniGlobal;
+    use crate::tests::{add_instance, reference};
+
+    #[test]
+    fn cycles_with_their_classes() {
+        let mut heap = Heap::default();
+        heap.counter.id_size = 8;
+        heap.utf8_strings
+            .make_mut()
+            .insert(10, "com/acme/Node".into());
+        heap.utf8_strings
+            .make_mut()
+            .insert(11, "com/acme/List".into());
+        for (serial_number, class_object_id, class_name_id) in [(1, 100, 10), (2, 101, 11)] {
+            heap.class_registry.make_mut().load(LoadClassData {
+                serial_number,
+                class_object_id,
+                stack_trace_serial_number: 0,
+                class_name_id,
+            });
+            heap.classes_dump.make_mut().insert(
+                class_object_id,
+                ClassDumpFields::new(
+                    class_object_id,
+                    0,
+                    0,
+                    0,
+                    0,
+                    Vec::new(),
+                    Vec::new(),
+                    Vec::new(),
+                ),
+            );
+        }
+        // a list and its 3 doubly linked nodes, a detached node pair and a leaf
+        add_instance(&mut heap, 1, 101, vec![reference(0, 2), reference(0, 4)]);
+        add_instance(&mut heap, 2, 100, vec![reference(0, 1), reference(0, 3)]);
+        add_instance(&mut heap, 3, 100, vec![reference(0, 2), reference(0, 4)]);
+        add_instance(&mut heap, 4, 100, vec![reference(0, 3), reference(0, 7)]);
+        add_instance(&mut heap, 5, 100, vec![reference(0, 6)]);
+        add_instance(&mut heap, 6, 100, vec![reference(0, 5)]);
+        add_instance(&mut heap, 7, 100, Vec::new());
+        heap.root_jni_global.make_mut().insert(
+            1,
+            RootJniGlobal {
+                object_id: 1,
+                jni_global_ref_id: 0,
+            },
+        );
+        let graph = HeapGraph::new(&heap);
+
+        let report = cycle_report(&heap, &graph, 2, 1);
+        assert_eq!((report.cycles, report.objects), (2, 6));
+        let cycle = &report.largest[0];
+        assert_eq!((cycle.objects, cycle.example_object_id), (4, 1));
+        assert_eq!(
+            cycle.classes,
+            vec![
+                ("com.acme.Node".to_string(), 3),
+                ("com.acme.List".to_string(), 1)
+            ]
+        );
+        // the leaf only referenced from the cycle goes with it
+        assert_eq!(
+            cycle.retained_size,
+            cycle.shallow_size + graph.shallow_size(7).unwrap()
+        );
+        assert!(report
+            .render()
+            .contains("3 x com.acme.Node, 1 x com.acme.List"));
+
+        assert_eq!(cycle_report(&heap, &graph, 3, 10).cycles, 1);
+    }
+}
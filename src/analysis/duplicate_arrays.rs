@@ -0,0 +1,184 @@
+use std::collections::HashMap;
+
+use serde::Serialize;
+
+use crate::parser::gc_record::FieldType;
+use crate::report::{render_blocks, Block};
+use crate::utils::{pretty_bytes_size, pretty_count};
+use crate::Heap;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct DuplicateArray {
+    pub class_name: String,
+    pub length: u32,
+    // arrays holding the same elements
+    pub copies: u64,
+    pub wasted_bytes: u64,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct DuplicateArrayReport {
+    // arrays that are a copy of another array, the first of each content is not counted
+    pub duplicated_arrays: u64,
+    // memory the target JVM would save by sharing identical arrays
+    pub wasted_bytes: u64,
+    // payloads shared while slurping the dump
+    pub deduplicated_payloads: u64,
+    pub deduplicated_payload_bytes: u64,
+    pub arrays: Vec<DuplicateArray>,
+}
+
+/// Groups the retained primitive arrays by content and measures the memory wasted by identical copies,
+/// keeping the `top` most wasteful contents.
+pub fn duplicate_array_report(heap: &Heap, top: usize) -> DuplicateArrayReport {
+    // (element type, elements) -> (copies, shallow size, length)
+    let mut by_content: HashMap<(FieldType, &[u8]), (u64, u64, u32)> = HashMap::new();
+    for instance in heap.instances_pool.values() {
+        let Some(array) = instance.primitive_array() else {
+            continue;
+        };
+        if array.bytes().is_empty() {
+            continue;
+        }
+        by_content
+            .entry((array.element_type, array.bytes()))
+            .or_insert((0, heap.shallow_size(instance), array.number_of_elements))
+            .0 += 1;
+    }
+
+    let mut arrays: Vec<DuplicateArray> = by_content
+        .into_iter()
+        .filter(|(_, (copies, _, _))| *copies > 1)
+        .map(
+            |((element_type, _), (copies, size, length))| DuplicateArray {
+                class_name: format!("{}[]", crate::primitive_type_name(element_type)),
+                length,
+                copies,
+                wasted_bytes: (copies - 1) * size,
+            },
+        )
+        .collect();
+
+    let mut report = DuplicateArrayReport {
+        duplicated_arrays: arrays.iter().map(|a| a.copies - 1).sum(),
+        wasted_bytes: arrays.iter().map(|a| a.wasted_bytes).sum(),
+        deduplicated_payloads: heap.counter.deduplicated_primitive_arrays,
+        deduplicated_payload_bytes: heap.counter.deduplicated_primitive_array_bytes,
+        ..Default::default()
+    };
+    arrays.sort_by(|a, b| {
+        b.wasted_bytes
+            .cmp(&a.wasted_bytes)
+            .then_with(|| a.class_name.cmp(&b.class_name))
+            .then_with(|| a.length.cmp(&b.length))
+            .then_with(|| b.copies.cmp(&a.copies))
+    });
+    arrays.truncate(top);
+    report.arrays = arrays;
+    report
+}
+
+impl DuplicateArrayReport {
+    /// Drops the contents wasting less than `min_wasted_bytes` or with fewer than `min_copies`.
+    pub fn retain_at_least(&mut self, min_wasted_bytes: u64, min_copies: u64) {
+        self.arrays
+            .retain(|a| a.wasted_bytes >= min_wasted_bytes && a.copies >= min_copies);
+    }
+
+    pub fn blocks(&self) -> Vec<Block> {
+        let mut blocks = vec![
+            Block::Heading(format!(
+                "Found {} duplicated primitive arrays wasting {}:",
+                pretty_count(self.duplicated_arrays),
+                pretty_bytes_size(self.wasted_bytes)
+            )),
+            Block::Text(format!(
+                "Payloads shared while slurping: {} ({})",
+                self.deduplicated_payloads,
+                pretty_bytes_size(self.deduplicated_payload_bytes)
+            )),
+        ];
+        if self.arrays.is_empty() {
+            return blocks;
+        }
+        let rows = self
+            .arrays
+            .iter()
+            .map(|a| {
+                vec![
+                    pretty_bytes_size(a.wasted_bytes),
+                    pretty_count(a.copies),
+                    pretty_count(a.length as u64),
+                    a.class_name.clone(),
+                ]
+            })
+            .collect();
+        blocks.push(Block::table(&["Wasted", "Copies", "Length", "Array"], rows));
+        blocks
+    }
+
+    pub fn render(&self) -> String {
+        render_blocks(&self.blocks())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::gc_record::{PrimitiveArray, Values};
+    use crate::result_recorder::primitive_byte_size;
+    use crate::tests::add_instance;
+    use std::sync::Arc;
+
+    #[test]
+    fn identical_arrays_by_element_type() {
+        let mut heap = Heap::default();
+        heap.counter.id_size = 8;
+        let ints: Vec<u8> = [1i32, 2].iter().flat_map(|i| i.to_be_bytes()).collect();
+        // the int[] content is also stored in a byte[], empty arrays are never duplicates
+        for (object_id, element_type, bytes) in [
+            (1, FieldType::Byte, b"acme".to_vec()),
+            (2, FieldType::Byte, b"acme".to_vec()),
+            (3, FieldType::Byte, b"acme".to_vec()),
+            (4, FieldType::Byte, b"zeta".to_vec()),
+            (5, FieldType::Int, ints.clone()),
+            (6, FieldType::Int, ints.clone()),
+            (7, FieldType::Byte, ints),
+            (8, FieldType::Long, Vec::new()),
+            (9, FieldType::Long, Vec::new()),
+        ] {
+            let length = (bytes.len() as u64 / primitive_byte_size(&element_type)) as u32;
+            let array = PrimitiveArray::new(element_type, length, Arc::from(bytes));
+            add_instance(
+                &mut heap,
+                object_id,
+                0,
+                vec![(0, Values::PrimitiveArray(array))],
+            );
+        }
+        let array_size = heap.shallow_size(&heap.instances_pool[&1]);
+
+        let mut report = duplicate_array_report(&heap, 10);
+        assert_eq!(report.duplicated_arrays, 3);
+        assert_eq!(report.wasted_bytes, 3 * array_size);
+        let arrays: Vec<(&str, u32, u64, u64)> = report
+            .arrays
+            .iter()
+            .map(|a| (a.class_name.as_str(), a.length, a.copies, a.wasted_bytes))
+            .collect();
+        assert_eq!(
+            arrays,
+            vec![
+                ("byte[]", 4, 3, 2 * array_size),
+                ("int[]", 2, 2, array_size),
+            ]
+        );
+        assert!(report
+            .render()
+            .contains("Found 3 duplicated primitive arrays"));
+
+        report.retain_at_least(0, 3);
+        assert_eq!(report.arrays.len(), 1);
+        assert_eq!(duplicate_array_report(&heap, 1).arrays.len(), 1);
+    }
+}
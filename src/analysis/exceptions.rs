@@ -0,0 +1,141 @@
+use std::collections::HashMap;
+
+use serde::Serialize;
+
+use crate::graph::HeapGraph;
+use crate::parser::gc_record::FieldValue;
+use crate::report::{render_blocks, Block};
+use crate::utils::{pretty_bytes_size, pretty_count};
+use crate::Heap;
+
+// characters of a message kept in the report
+const MAX_MESSAGE_LENGTH: usize = 120;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ExceptionGroup {
+    pub class_name: String,
+    // `None` for exceptions without a message or whose message was not retained
+    pub message: Option<String>,
+    pub instances: u64,
+    // exceptions referenced by another exception of the group (e.g. as cause) are counted once
+    pub retained_size: u64,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ExceptionReport {
+    pub total_instances: u64,
+    // sorted by retained size
+    pub groups: Vec<ExceptionGroup>,
+}
+
+/// Groups the `java.lang.Throwable` instances by class and message, keeping the `top` groups by retained size.
+///
+/// Large numbers of live exceptions usually pile up in logging queues or suppressed lists.
+pub fn exception_report(heap: &Heap, graph: &HeapGraph, top: usize) -> ExceptionReport {
+    let mut keys: HashMap<u64, (String, Option<String>)> = HashMap::new();
+    let mut instances: HashMap<(String, Option<String>), u64> = HashMap::new();
+    for instance in heap.instances_of("java.lang.Throwable") {
+        let message = match heap.field_value(instance, "detailMessage") {
+            Some(FieldValue::Object(message_id)) => heap
+                .string_value(*message_id)
+                .map(|message| shorten(&message)),
+            _ => None,
+        };
+        let key = (heap.instance_class_name(instance), message);
+        *instances.entry(key.clone()).or_insert(0) += 1;
+        keys.insert(instance.object_id, key);
+    }
+    let retained = graph.retained_size_by_group(|object_id| keys.get(&object_id).cloned());
+
+    let total_instances = keys.len() as u64;
+    let mut groups: Vec<ExceptionGroup> = instances
+        .into_iter()
+        .map(|(key, instances)| {
+            let retained_size = retained.get(&key).copied().unwrap_or_default();
+            let (class_name, message) = key;
+            ExceptionGroup {
+                class_name,
+                message,
+                instances,
+                retained_size,
+            }
+        })
+        .collect();
+    groups.sort_by(|a, b| {
+        b.retained_size
+            .cmp(&a.retained_size)
+            .then_with(|| b.instances.cmp(&a.instances))
+            .then_with(|| a.class_name.cmp(&b.class_name))
+            .then_with(|| a.message.cmp(&b.message))
+    });
+    groups.truncate(top);
+    ExceptionReport {
+        total_instances,
+        groups,
+    }
+}
+
+// single line messages fitting in a table cell
+fn shorten(message: &str) -> String {
+    let line: String = message
+        .chars()
+        .map(|c| if c.is_control() { ' ' } else { c })
+        .collect();
+    if line.chars().count() > MAX_MESSAGE_LENGTH {
+        let kept: String = line.chars().take(MAX_MESSAGE_LENGTH).collect();
+        format!("{}...", kept)
+    } else {
+        line
+    }
+}
+
+impl ExceptionReport {
+    pub fn blocks(&self) -> Vec<Block> {
+        let mut blocks = vec![Block::Heading(format!(
+            "Found {} exceptions, top {} by class and message:",
+            pretty_count(self.total_instances),
+            self.groups.len()
+        ))];
+        if self.groups.is_empty() {
+            return blocks;
+        }
+        let rows: Vec<Vec<String>> = self
+            .groups
+            .iter()
+            .map(|g| {
+                vec![
+                    pretty_bytes_size(g.retained_size),
+                    pretty_count(g.instances),
+                    g.class_name.clone(),
+                    g.message
+                        .clone()
+                        .unwrap_or_else(|| "<no message>".to_string()),
+                ]
+            })
+            .collect();
+        blocks.push(Block::table(
+            &["Retained", "Instances", "Class name", "Message"],
+            rows,
+        ));
+        blocks
+    }
+
+    pub fn render(&self) -> String {
+        render_blocks(&self.blocks())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn shorten_multiline_messages() {
+        assert_eq!(shorten("boom\n\tat Foo"), "boom  at Foo");
+        let long = "x".repeat(MAX_MESSAGE_LENGTH + 10);
+        assert_eq!(
+            shorten(&long),
+            format!("{}...", "x".repeat(MAX_MESSAGE_LENGTH))
+        );
+    }
+}
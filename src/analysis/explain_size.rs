@@ -0,0 +1,331 @@
+use serde::Serialize;
+
+use crate::errors::HprofSlurpError;
+use crate::errors::HprofSlurpError::*;
+use crate::parser::gc_record::FieldType;
+use crate::report::{render_blocks, Block};
+use crate::result_recorder::primitive_byte_size;
+use crate::utils::{pretty_bytes_size, pretty_count};
+use crate::{align_to_8, primitive_type_name, Heap, MAX_CLASS_HIERARCHY_DEPTH};
+
+#[derive(Debug, Clone, Serialize)]
+pub struct FieldSize {
+    pub declaring_class: String,
+    pub name: String,
+    // `object` for references
+    pub field_type: String,
+    pub bytes: u64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct DeclaringClassSize {
+    pub class_name: String,
+    pub fields: u64,
+    pub bytes: u64,
+}
+
+/// Where the bytes of an instance of a class come from, as estimated by `Heap::shallow_size`.
+#[derive(Debug, Clone, Serialize)]
+pub struct ClassSizeExplanation {
+    pub class_object_id: u64,
+    pub class_name: String,
+    // instances of this class, subclasses excluded
+    pub instances: u64,
+    pub total_bytes: u64,
+    pub header_bytes: u64,
+    pub reference_bytes: u64,
+    // declaration order, from the topmost super class down, the JVM may pack them differently
+    pub fields: Vec<FieldSize>,
+    // topmost super class first
+    pub hierarchy: Vec<DeclaringClassSize>,
+    pub padding_bytes: u64,
+    pub instance_bytes: u64,
+    // a super class is missing from the dump, its fields are not counted
+    pub incomplete_hierarchy: bool,
+}
+
+/// Explains the size of the instances of the classes named `class_name`, one explanation per class loader
+/// defining it, most instantiated first.
+pub fn explain_class_size(
+    heap: &Heap,
+    class_name: &str,
+) -> Result<Vec<ClassSizeExplanation>, HprofSlurpError> {
+    let mut explanations: Vec<ClassSizeExplanation> = heap
+        .classes_dump
+        .keys()
+        .filter(|class_id| heap.class_name(**class_id).as_deref() == Some(class_name))
+        .map(|class_id| explain(heap, *class_id))
+        .collect();
+    if explanations.is_empty() {
+        return Err(ClassNotFound {
+            name: class_name.to_string(),
+        });
+    }
+    explanations.sort_by(|a, b| {
+        b.instances
+            .cmp(&a.instances)
+            .then_with(|| a.class_object_id.cmp(&b.class_object_id))
+    });
+    Ok(explanations)
+}
+
+fn explain(heap: &Heap, class_object_id: u64) -> ClassSizeExplanation {
+    // object_header = mark(id_size) + klass(4) + padding_gap(4), like `Heap::shallow_size`
+    let header_bytes = heap.counter.id_size as u64 + 4 + 4;
+    let reference_bytes = heap.reference_size();
+
+    let mut hierarchy = Vec::new();
+    let mut fields = Vec::new();
+    let mut incomplete_hierarchy = false;
+    let mut current = class_object_id;
+    while current != 0 {
+        // a missing or cyclic super class
+        let Some(class) = heap
+            .classes_dump
+            .get(&current)
+            .filter(|_| hierarchy.len() < MAX_CLASS_HIERARCHY_DEPTH)
+        else {
+            incomplete_hierarchy = true;
+            break;
+        };
+        let declaring_class = heap
+            .class_name(current)
+            .unwrap_or_else(|| format!("unknown class 0x{:x}", current));
+        let declared: Vec<FieldSize> = class
+            .instance_fields
+            .iter()
+            .map(|field| FieldSize {
+                declaring_class: declaring_class.clone(),
+                name: heap
+                    .utf8_strings
+                    .get(&field.name_id)
+                    .map_or_else(|| "?".to_string(), |name| name.to_string()),
+                field_type: primitive_type_name(field.field_type).to_string(),
+                bytes: match field.field_type {
+                    FieldType::Object => reference_bytes,
+                    field_type => primitive_byte_size(&field_type),
+                },
+            })
+            .collect();
+        hierarchy.push(DeclaringClassSize {
+            class_name: declaring_class,
+            fields: declared.len() as u64,
+            bytes: declared.iter().map(|field| field.bytes).sum(),
+        });
+        // walking up the hierarchy, the super class fields come first
+        fields.splice(0..0, declared);
+        current = class.super_class_object_id;
+    }
+    hierarchy.reverse();
+
+    let unpadded_bytes = header_bytes + fields.iter().map(|field| field.bytes).sum::<u64>();
+    let instance_bytes = align_to_8(unpadded_bytes);
+    let (instances, total_bytes) = heap
+        .instances_pool
+        .values()
+        .filter(|instance| {
+            instance.class_object_id == class_object_id && instance.array_element_type().is_none()
+        })
+        .fold((0, 0), |(instances, bytes), instance| {
+            (instances + 1, bytes + heap.shallow_size(instance))
+        });
+    ClassSizeExplanation {
+        class_object_id,
+        class_name: heap
+            .class_name(class_object_id)
+            .unwrap_or_else(|| "unknown class".to_string()),
+        instances,
+        total_bytes,
+        header_bytes,
+        reference_bytes,
+        fields,
+        hierarchy,
+        padding_bytes: instance_bytes - unpadded_bytes,
+        instance_bytes,
+        incomplete_hierarchy,
+    }
+}
+
+impl ClassSizeExplanation {
+    pub fn blocks(&self) -> Vec<Block> {
+        let mut blocks = vec![Block::Heading(format!(
+            "{} (0x{:x}): {} instances of {} bytes, {} in total:",
+            self.class_name,
+            self.class_object_id,
+            pretty_count(self.instances),
+            self.instance_bytes,
+            pretty_bytes_size(self.total_bytes)
+        ))];
+        if self.incomplete_hierarchy {
+            blocks.push(Block::Warning(
+                "A super class is missing from the dump, its fields are not counted.".to_string(),
+            ));
+        }
+        let mut rows = vec![vec![
+            self.header_bytes.to_string(),
+            "header".to_string(),
+            format!(
+                "mark word {}, class pointer 4, gap 4",
+                self.header_bytes - 8
+            ),
+        ]];
+        rows.extend(self.fields.iter().map(|field| {
+            vec![
+                field.bytes.to_string(),
+                field.field_type.clone(),
+                format!("{}.{}", field.declaring_class, field.name),
+            ]
+        }));
+        rows.push(vec![
+            self.padding_bytes.to_string(),
+            "padding".to_string(),
+            "alignment to 8 bytes".to_string(),
+        ]);
+        blocks.push(Block::table(&["Bytes", "Type", "Field"], rows));
+        blocks.push(Block::heading("Bytes per declaring class:"));
+        let rows = self
+            .hierarchy
+            .iter()
+            .map(|class| {
+                vec![
+                    class.bytes.to_string(),
+                    class.fields.to_string(),
+                    class.class_name.clone(),
+                ]
+            })
+            .collect();
+        blocks.push(Block::table(&["Bytes", "Fields", "Class"], rows));
+        blocks.push(Block::Text(format!(
+            "References take {} bytes, the JVM may reorder the fields to fill the gaps.",
+            self.reference_bytes
+        )));
+        blocks
+    }
+
+    pub fn render(&self) -> String {
+        render_blocks(&self.blocks())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::gc_record::{ClassDumpFields, FieldInfo, FieldValue, Values};
+    use crate::parser::record::LoadClassData;
+    use crate::result_recorder::Instance;
+    use std::sync::Arc;
+
+    #[test]
+    fn explain_fields_header_and_padding() {
+        let mut heap = Heap::default();
+        heap.counter.id_size = 8;
+        heap.reference_sizing.compressed_oops = true;
+        for (name_id, name) in [
+            (1, "java/lang/Object"),
+            (2, "com/acme/Base"),
+            (3, "com/acme/Order"),
+            (4, "id"),
+            (5, "customer"),
+            (6, "paid"),
+        ] {
+            heap.utf8_strings.make_mut().insert(name_id, name.into());
+        }
+        for (serial_number, class_object_id, super_class_object_id, class_name_id, fields) in [
+            (1, 100, 0, 1, vec![]),
+            (
+                2,
+                101,
+                100,
+                2,
+                vec![FieldInfo {
+                    name_id: 4,
+                    field_type: FieldType::Long,
+                }],
+            ),
+            (
+                3,
+                102,
+                101,
+                3,
+                vec![
+                    FieldInfo {
+                        name_id: 5,
+                        field_type: FieldType::Object,
+                    },
+                    FieldInfo {
+                        name_id: 6,
+                        field_type: FieldType::Bool,
+                    },
+                ],
+            ),
+        ] {
+            heap.class_registry.make_mut().load(LoadClassData {
+                serial_number,
+                class_object_id,
+                stack_trace_serial_number: 0,
+                class_name_id,
+            });
+            heap.classes_dump.make_mut().insert(
+                class_object_id,
+                ClassDumpFields::new(
+                    class_object_id,
+                    0,
+                    super_class_object_id,
+                    0,
+                    0,
+                    Vec::new(),
+                    Vec::new(),
+                    fields,
+                ),
+            );
+        }
+        for object_id in [1, 2] {
+            heap.instances_pool.make_mut().insert(
+                object_id,
+                Arc::new(Instance {
+                    object_id,
+                    stack_trace_serial_number: 0,
+                    class_object_id: 102,
+                    data_size: 17,
+                    fields: vec![(5, Values::Single(FieldValue::Object(0)))],
+                    super_fields: Vec::new(),
+                }),
+            );
+        }
+
+        let explanations = explain_class_size(&heap, "com.acme.Order").unwrap();
+        let order = &explanations[0];
+        assert_eq!(order.instances, 2);
+        // 16 header + 8 long + 4 reference + 1 boolean, padded to 32
+        assert_eq!(
+            (
+                order.header_bytes,
+                order.padding_bytes,
+                order.instance_bytes
+            ),
+            (16, 3, 32)
+        );
+        assert_eq!(order.total_bytes, 2 * order.instance_bytes);
+        let names: Vec<&str> = order.fields.iter().map(|f| f.name.as_str()).collect();
+        assert_eq!(names, ["id", "customer", "paid"]);
+        let hierarchy: Vec<(&str, u64)> = order
+            .hierarchy
+            .iter()
+            .map(|c| (c.class_name.as_str(), c.bytes))
+            .collect();
+        assert_eq!(
+            hierarchy,
+            [
+                ("java.lang.Object", 0),
+                ("com.acme.Base", 8),
+                ("com.acme.Order", 5)
+            ]
+        );
+        assert!(order.render().contains("com.acme.Base.id"));
+
+        assert!(matches!(
+            explain_class_size(&heap, "com.acme.Missing"),
+            Err(ClassNotFound { .. })
+        ));
+    }
+}
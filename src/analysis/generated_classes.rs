@@ -0,0 +1,252 @@
+use std::collections::HashMap;
+
+use serde::Serialize;
+
+use crate::analysis::class_loader_label;
+use crate::analysis::class_names::{is_hidden_class_name, NameGrouping};
+use crate::report::{render_blocks, Block};
+use crate::utils::pretty_count;
+use crate::Heap;
+
+// generated classes of a single base class and loader above which the generation is likely leaking
+pub const EXPLOSION_THRESHOLD: u64 = 500;
+
+/// The mechanism which generated a class at runtime, detected from its name.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize)]
+pub enum ClassGenerator {
+    Lambda,
+    JdkProxy,
+    Cglib,
+    ByteBuddy,
+    Hibernate,
+    Groovy,
+    JRuby,
+    // `GeneratedMethodAccessor` and friends created by the reflection inflation
+    Reflection,
+    // other hidden classes e.g. `LambdaForm$MH`
+    Hidden,
+}
+
+impl ClassGenerator {
+    pub fn detect(class_name: &str) -> Option<ClassGenerator> {
+        let simple_name = class_name.rsplit('.').next().unwrap_or(class_name);
+        if class_name.contains("$$Lambda") {
+            Some(ClassGenerator::Lambda)
+        } else if simple_name.starts_with("$Proxy") {
+            Some(ClassGenerator::JdkProxy)
+        } else if class_name.contains("CGLIB") {
+            Some(ClassGenerator::Cglib)
+        } else if class_name.contains("$ByteBuddy$") || class_name.contains("$MockitoMock$") {
+            Some(ClassGenerator::ByteBuddy)
+        } else if class_name.contains("$HibernateProxy$") {
+            Some(ClassGenerator::Hibernate)
+        } else if groovy_base_class(class_name).is_some() {
+            Some(ClassGenerator::Groovy)
+        } else if class_name.starts_with("org.jruby.gen.") || class_name.starts_with("rubyjit.") {
+            Some(ClassGenerator::JRuby)
+        } else if simple_name.starts_with("Generated") && simple_name.contains("Accessor") {
+            Some(ClassGenerator::Reflection)
+        } else if is_hidden_class_name(class_name) {
+            Some(ClassGenerator::Hidden)
+        } else {
+            None
+        }
+    }
+}
+
+// Groovy closures are named `Foo$_method_closure1`
+fn groovy_base_class(class_name: &str) -> Option<&str> {
+    let (base, closure) = class_name.split_once("$_")?;
+    let number = &closure[closure.rfind("closure")? + "closure".len()..];
+    (!number.is_empty() && number.chars().all(|c| c.is_ascii_digit())).then_some(base)
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct GeneratedClassGroup {
+    pub generator: ClassGenerator,
+    pub base_class: String,
+    pub class_loader_object_id: u64,
+    pub class_loader: String,
+    pub classes: u64,
+    pub instances: u64,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct GeneratedClassReport {
+    pub generated_classes: u64,
+    // sorted by number of classes
+    pub groups: Vec<GeneratedClassGroup>,
+}
+
+/// Counts the classes generated at runtime (lambdas, proxies, bytecode enhancement, scripting languages)
+/// per base class and class loader, keeping the `top` largest groups.
+pub fn generated_class_report(heap: &Heap, top: usize) -> GeneratedClassReport {
+    let mut instances_per_class: HashMap<u64, u64> = HashMap::new();
+    for instance in heap.instances_pool.values() {
+        *instances_per_class
+            .entry(instance.class_object_id)
+            .or_default() += 1;
+    }
+
+    let grouping = NameGrouping::all();
+    let mut groups: HashMap<(ClassGenerator, String, u64), (u64, u64)> = HashMap::new();
+    for (class_object_id, class) in &heap.classes_dump {
+        let Some(class_name) = heap.class_name(*class_object_id) else {
+            continue;
+        };
+        let Some(generator) = ClassGenerator::detect(&class_name) else {
+            continue;
+        };
+        let base_class = match groovy_base_class(&class_name) {
+            Some(base) if generator == ClassGenerator::Groovy => base.to_string(),
+            _ => grouping.group_name(&class_name),
+        };
+        let group = groups
+            .entry((generator, base_class, class.class_loader_object_id))
+            .or_default();
+        group.0 += 1;
+        group.1 += instances_per_class
+            .get(class_object_id)
+            .copied()
+            .unwrap_or_default();
+    }
+
+    let mut groups: Vec<GeneratedClassGroup> = groups
+        .into_iter()
+        .map(
+            |((generator, base_class, class_loader_object_id), (classes, instances))| {
+                GeneratedClassGroup {
+                    generator,
+                    base_class,
+                    class_loader_object_id,
+                    class_loader: class_loader_label(heap, class_loader_object_id),
+                    classes,
+                    instances,
+                }
+            },
+        )
+        .collect();
+    groups.sort_by(|a, b| {
+        b.classes
+            .cmp(&a.classes)
+            .then_with(|| a.base_class.cmp(&b.base_class))
+            .then_with(|| a.class_loader_object_id.cmp(&b.class_loader_object_id))
+    });
+    let generated_classes = groups.iter().map(|g| g.classes).sum();
+    groups.truncate(top);
+    GeneratedClassReport {
+        generated_classes,
+        groups,
+    }
+}
+
+impl GeneratedClassReport {
+    /// Groups generating at least `EXPLOSION_THRESHOLD` classes.
+    pub fn explosions(&self) -> impl Iterator<Item = &GeneratedClassGroup> {
+        self.groups
+            .iter()
+            .filter(|g| g.classes >= EXPLOSION_THRESHOLD)
+    }
+
+    pub fn blocks(&self) -> Vec<Block> {
+        let rows: Vec<Vec<String>> = self
+            .groups
+            .iter()
+            .map(|g| {
+                vec![
+                    pretty_count(g.classes),
+                    pretty_count(g.instances),
+                    format!("{:?}", g.generator),
+                    g.class_loader.clone(),
+                    g.base_class.clone(),
+                ]
+            })
+            .collect();
+        let mut blocks = vec![
+            Block::Heading(format!(
+                "Found {} generated classes, top {} groups:",
+                pretty_count(self.generated_classes),
+                self.groups.len()
+            )),
+            Block::table(
+                &[
+                    "Classes",
+                    "Instances",
+                    "Generator",
+                    "Class loader",
+                    "Base class",
+                ],
+                rows,
+            ),
+        ];
+        let explosions: Vec<&GeneratedClassGroup> = self.explosions().collect();
+        if !explosions.is_empty() {
+            blocks.push(Block::Warning(
+                "Generated class explosions (check for unbounded proxy or class generation):"
+                    .to_string(),
+            ));
+            blocks.push(Block::Lines(
+                explosions
+                    .iter()
+                    .map(|group| {
+                        format!(
+                            "  {} {:?} classes for {} in {}",
+                            group.classes, group.generator, group.base_class, group.class_loader
+                        )
+                    })
+                    .collect(),
+            ));
+        }
+        blocks
+    }
+
+    pub fn render(&self) -> String {
+        render_blocks(&self.blocks())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn detect_class_generators() {
+        let cases = [
+            (
+                "Sample$$Lambda$1+0x00007f32a9000c18",
+                Some(ClassGenerator::Lambda),
+            ),
+            ("jdk.proxy1.$Proxy3", Some(ClassGenerator::JdkProxy)),
+            (
+                "com.acme.Service$$EnhancerBySpringCGLIB$$1a2b",
+                Some(ClassGenerator::Cglib),
+            ),
+            (
+                "com.acme.Repo$ByteBuddy$AbCd",
+                Some(ClassGenerator::ByteBuddy),
+            ),
+            (
+                "com.acme.Script$_run_closure12",
+                Some(ClassGenerator::Groovy),
+            ),
+            (
+                "jdk.internal.reflect.GeneratedMethodAccessor7",
+                Some(ClassGenerator::Reflection),
+            ),
+            (
+                "java.lang.invoke.LambdaForm$MH.0x0000000800c01234",
+                Some(ClassGenerator::Hidden),
+            ),
+            ("java.util.HashMap$Node", None),
+            ("com.acme.Script$_closureHelper", None),
+        ];
+        for (class_name, generator) in cases {
+            assert_eq!(
+                ClassGenerator::detect(class_name),
+                generator,
+                "{}",
+                class_name
+            );
+        }
+    }
+}
@@ -0,0 +1,190 @@
+use serde::Serialize;
+
+use crate::analysis::class_names::pretty_class_name;
+use crate::graph::HeapGraph;
+use crate::report::{render_blocks, Block};
+use crate::utils::pretty_count;
+use crate::Heap;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct HubObject {
+    pub object_id: u64,
+    pub class_name: String,
+    pub incoming: u64,
+    pub outgoing: u64,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct GraphMetricsReport {
+    pub objects: u64,
+    pub references: u64,
+    // sorted by incoming references
+    pub most_referenced: Vec<HubObject>,
+    // sorted by outgoing references
+    pub most_referencing: Vec<HubObject>,
+    // strongly connected components of at least two objects
+    pub components: u64,
+    pub objects_in_components: u64,
+    // largest first
+    pub largest_components: Vec<u64>,
+}
+
+/// Computes the in and out degrees of the objects and the sizes of the strongly connected components,
+/// giant hubs often being god objects or registries.
+///
+/// Class objects are left out, every instance referencing its class.
+pub fn graph_metrics_report(heap: &Heap, graph: &HeapGraph, top: usize) -> GraphMetricsReport {
+    let is_instance = |object_id: u64| !heap.classes_dump.contains_key(&object_id);
+    let mut degrees: Vec<(u64, u32, u32)> = graph.degrees(is_instance).collect();
+    let references = degrees
+        .iter()
+        .map(|(_, incoming, _)| *incoming as u64)
+        .sum();
+    let hub = |(object_id, incoming, outgoing): (u64, u32, u32)| HubObject {
+        object_id,
+        class_name: heap.object_class_name(object_id),
+        incoming: incoming as u64,
+        outgoing: outgoing as u64,
+    };
+    let most_referenced: Vec<HubObject> =
+        top_degrees(&mut degrees, top, |(id, incoming, _)| (*incoming, *id))
+            .into_iter()
+            .map(hub)
+            .collect();
+    let most_referencing: Vec<HubObject> =
+        top_degrees(&mut degrees, top, |(id, _, outgoing)| (*outgoing, *id))
+            .into_iter()
+            .map(hub)
+            .collect();
+    let components = graph.strongly_connected_components(2, is_instance);
+    GraphMetricsReport {
+        objects: degrees.len() as u64,
+        references,
+        most_referenced,
+        most_referencing,
+        components: components.len() as u64,
+        objects_in_components: components.iter().map(|c| c.len() as u64).sum(),
+        largest_components: components
+            .iter()
+            .take(top)
+            .map(|c| c.len() as u64)
+            .collect(),
+    }
+}
+
+// the `top` entries with the highest degree, ties broken by ascending id, objects without reference skipped
+fn top_degrees(
+    degrees: &mut [(u64, u32, u32)],
+    top: usize,
+    key: impl Fn(&(u64, u32, u32)) -> (u32, u64),
+) -> Vec<(u64, u32, u32)> {
+    let order = |a: &(u64, u32, u32), b: &(u64, u32, u32)| {
+        let (a_degree, a_id) = key(a);
+        let (b_degree, b_id) = key(b);
+        b_degree.cmp(&a_degree).then_with(|| a_id.cmp(&b_id))
+    };
+    if top < degrees.len() {
+        degrees.select_nth_unstable_by(top, order);
+    }
+    let mut selected: Vec<(u64, u32, u32)> = degrees
+        .iter()
+        .take(top)
+        .filter(|entry| key(entry).0 > 0)
+        .copied()
+        .collect();
+    selected.sort_unstable_by(order);
+    selected
+}
+
+impl GraphMetricsReport {
+    pub fn blocks(&self) -> Vec<Block> {
+        let hub_rows = |hubs: &[HubObject]| {
+            hubs.iter()
+                .map(|hub| {
+                    vec![
+                        pretty_count(hub.incoming),
+                        pretty_count(hub.outgoing),
+                        format!("0x{:x}", hub.object_id),
+                        pretty_class_name(&hub.class_name),
+                    ]
+                })
+                .collect()
+        };
+        let headers = ["Incoming", "Outgoing", "Object id", "Class name"];
+        let mut blocks = vec![Block::Heading(format!(
+            "Found {} references between {} objects, {} objects in {} strongly connected components.",
+            pretty_count(self.references),
+            pretty_count(self.objects),
+            pretty_count(self.objects_in_components),
+            pretty_count(self.components)
+        ))];
+        if !self.most_referenced.is_empty() {
+            blocks.push(Block::heading("Most referenced objects:"));
+            blocks.push(Block::table(&headers, hub_rows(&self.most_referenced)));
+        }
+        if !self.most_referencing.is_empty() {
+            blocks.push(Block::heading("Objects with the most references:"));
+            blocks.push(Block::table(&headers, hub_rows(&self.most_referencing)));
+        }
+        if !self.largest_components.is_empty() {
+            blocks.push(Block::heading(
+                "Largest strongly connected components (objects):",
+            ));
+            blocks.push(Block::Lines(
+                self.largest_components
+                    .iter()
+                    .map(|size| format!("  {}", pretty_count(*size)))
+                    .collect(),
+            ));
+        }
+        blocks
+    }
+
+    pub fn render(&self) -> String {
+        render_blocks(&self.blocks())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::result_recorder::RootJniGlobal;
+    use crate::tests::{add_instance, reference};
+
+    #[test]
+    fn hubs_and_components() {
+        let mut heap = Heap::default();
+        heap.counter.id_size = 8;
+        // a registry referencing 3 entries pointing back at it, 5 on its own
+        add_instance(
+            &mut heap,
+            1,
+            0,
+            vec![reference(0, 2), reference(0, 3), reference(0, 4)],
+        );
+        for entry in [2, 3, 4] {
+            add_instance(&mut heap, entry, 0, vec![reference(0, 1)]);
+        }
+        add_instance(&mut heap, 5, 0, Vec::new());
+        heap.root_jni_global.make_mut().insert(
+            1,
+            RootJniGlobal {
+                object_id: 1,
+                jni_global_ref_id: 0,
+            },
+        );
+        let graph = HeapGraph::new(&heap);
+        let report = graph_metrics_report(&heap, &graph, 2);
+        assert_eq!((report.objects, report.references), (5, 6));
+        let most_referenced: Vec<(u64, u64)> = report
+            .most_referenced
+            .iter()
+            .map(|hub| (hub.object_id, hub.incoming))
+            .collect();
+        assert_eq!(most_referenced, vec![(1, 3), (2, 1)]);
+        assert_eq!(report.most_referencing[0].outgoing, 3);
+        assert_eq!((report.components, report.objects_in_components), (1, 4));
+        assert_eq!(report.largest_components, vec![4]);
+        assert!(report.render().contains("Most referenced objects:"));
+    }
+}
@@ -0,0 +1,215 @@
+use std::collections::HashMap;
+
+use serde::Serialize;
+
+use crate::analysis::class_names::pretty_class_name;
+use crate::parser::gc_record::{FieldValue, Values};
+use crate::report::{render_blocks, Block};
+use crate::utils::{pretty_bytes_size, pretty_count};
+use crate::Heap;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct HeavyField {
+    pub class_name: String,
+    pub field_name: String,
+    // non-null references found in the instances of the class
+    pub references: u64,
+    // shallow size of the referenced objects, counted once per reference
+    pub referenced_bytes: u64,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct HeaviestFieldsReport {
+    pub fields: u64,
+    pub references: u64,
+    pub referenced_bytes: u64,
+    // sorted by referenced size
+    pub heaviest: Vec<HeavyField>,
+}
+
+/// Aggregates the shallow size of the objects referenced by each (class, reference field) pair of the
+/// instances, keeping the `top` fields pointing to the most bytes.
+pub fn heaviest_fields_report(heap: &Heap, top: usize) -> HeaviestFieldsReport {
+    // (class object id, field name id) -> (references, referenced bytes)
+    let mut by_field: HashMap<(u64, u64), (u64, u64)> = HashMap::new();
+    for instance in heap.instances_pool.values() {
+        if instance.array_element_type().is_some() {
+            continue;
+        }
+        for (name_id, value) in instance.all_fields() {
+            let Values::Single(FieldValue::Object(target_id)) = value else {
+                continue;
+            };
+            let Some(target) = heap.instances_pool.get(target_id) else {
+                continue;
+            };
+            let entry = by_field
+                .entry((instance.class_object_id, *name_id))
+                .or_default();
+            entry.0 += 1;
+            entry.1 += heap.shallow_size(target);
+        }
+    }
+
+    let mut heaviest: Vec<HeavyField> = by_field
+        .into_iter()
+        .map(
+            |((class_object_id, name_id), (references, referenced_bytes))| HeavyField {
+                class_name: heap
+                    .class_name(class_object_id)
+                    .unwrap_or_else(|| "unknown class".to_string()),
+                field_name: heap
+                    .utf8_strings
+                    .get(&name_id)
+                    .map(|name| name.to_string())
+                    .unwrap_or_else(|| "unknown field".to_string()),
+                references,
+                referenced_bytes,
+            },
+        )
+        .collect();
+    let report = HeaviestFieldsReport {
+        fields: heaviest.len() as u64,
+        references: heaviest.iter().map(|f| f.references).sum(),
+        referenced_bytes: heaviest.iter().map(|f| f.referenced_bytes).sum(),
+        ..Default::default()
+    };
+    heaviest.sort_by(|a, b| {
+        b.referenced_bytes
+            .cmp(&a.referenced_bytes)
+            .then_with(|| a.class_name.cmp(&b.class_name))
+            .then_with(|| a.field_name.cmp(&b.field_name))
+    });
+    heaviest.truncate(top);
+    HeaviestFieldsReport { heaviest, ..report }
+}
+
+impl HeaviestFieldsReport {
+    pub fn blocks(&self) -> Vec<Block> {
+        let mut blocks = vec![Block::Heading(format!(
+            "Found {} reference fields pointing {} times to {}:",
+            pretty_count(self.fields),
+            pretty_count(self.references),
+            pretty_bytes_size(self.referenced_bytes)
+        ))];
+        if self.heaviest.is_empty() {
+            return blocks;
+        }
+        blocks.push(Block::Heading(format!(
+            "Top {} heaviest fields:",
+            self.heaviest.len()
+        )));
+        let rows = self
+            .heaviest
+            .iter()
+            .map(|f| {
+                vec![
+                    pretty_bytes_size(f.referenced_bytes),
+                    pretty_count(f.references),
+                    format!("{}.{}", pretty_class_name(&f.class_name), f.field_name),
+                ]
+            })
+            .collect();
+        blocks.push(Block::table(
+            &["Referenced size", "References", "Field"],
+            rows,
+        ));
+        blocks
+    }
+
+    pub fn render(&self) -> String {
+        render_blocks(&self.blocks())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::gc_record::ClassDumpFields;
+    use crate::parser::record::LoadClassData;
+    use crate::tests::{add_instance, reference};
+
+    const CUSTOMER: u64 = 1;
+    const NOTE: u64 = 2;
+
+    #[test]
+    fn heaviest_fields_by_referenced_size() {
+        let mut heap = Heap::default();
+        heap.counter.id_size = 8;
+        for (name_id, name) in [
+            (CUSTOMER, "customer"),
+            (NOTE, "note"),
+            (10, "com/acme/Order"),
+            (11, "com/acme/Customer"),
+        ] {
+            heap.utf8_strings.make_mut().insert(name_id, name.into());
+        }
+        for (serial_number, class_object_id, class_name_id) in [(1, 100, 10), (2, 101, 11)] {
+            heap.class_registry.make_mut().load(LoadClassData {
+                serial_number,
+                class_object_id,
+                stack_trace_serial_number: 0,
+                class_name_id,
+            });
+            heap.classes_dump.make_mut().insert(
+                class_object_id,
+                ClassDumpFields::new(
+                    class_object_id,
+                    0,
+                    0,
+                    0,
+                    0,
+                    Vec::new(),
+                    Vec::new(),
+                    Vec::new(),
+                ),
+            );
+        }
+        // two orders sharing a customer of 3 references, a note to a leaf customer and a null one
+        add_instance(
+            &mut heap,
+            1,
+            100,
+            vec![reference(CUSTOMER, 3), reference(NOTE, 4)],
+        );
+        add_instance(
+            &mut heap,
+            2,
+            100,
+            vec![reference(CUSTOMER, 3), reference(NOTE, 0)],
+        );
+        add_instance(
+            &mut heap,
+            3,
+            101,
+            vec![reference(NOTE, 0), reference(NOTE, 0), reference(NOTE, 0)],
+        );
+        add_instance(&mut heap, 4, 101, Vec::new());
+
+        let report = heaviest_fields_report(&heap, 10);
+        let customer_size = heap.shallow_size(&heap.instances_pool[&3]);
+        let leaf_size = heap.shallow_size(&heap.instances_pool[&4]);
+        assert_eq!((report.fields, report.references), (2, 3));
+        let heaviest: Vec<(&str, &str, u64, u64)> = report
+            .heaviest
+            .iter()
+            .map(|f| {
+                (
+                    f.class_name.as_str(),
+                    f.field_name.as_str(),
+                    f.references,
+                    f.referenced_bytes,
+                )
+            })
+            .collect();
+        assert_eq!(
+            heaviest,
+            [
+                ("com.acme.Order", "customer", 2, 2 * customer_size),
+                ("com.acme.Order", "note", 1, leaf_size)
+            ]
+        );
+        assert!(report.render().contains("com.acme.Order.customer"));
+        assert_eq!(heaviest_fields_report(&heap, 1).heaviest.len(), 1);
+    }
+}
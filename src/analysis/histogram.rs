@@ -0,0 +1,465 @@
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+
+use crate::analysis::class_names::{java_type_name, pretty_class_name, NameGrouping};
+#[cfg(feature = "graph")]
+use crate::graph::HeapGraph;
+use crate::parser::gc_record::{FieldType, FieldValue, Values};
+use crate::report::{render_blocks, Block};
+use crate::result_recorder::Instance;
+use crate::utils::{pretty_bytes_size, pretty_count};
+use crate::Heap;
+
+// classes owning the `char[]`/`byte[]` they solely reference, `AbstractStringBuilder` covers `StringBuilder` and `StringBuffer`
+const ARRAY_OWNER_CLASSES: &[&str] = &[
+    "java.lang.String",
+    "java.lang.AbstractStringBuilder",
+    "java.nio.ByteBuffer",
+];
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ClassHistogramEntry {
+    pub class_name: String,
+    pub instances: u64,
+    // shallow size of the largest instance
+    pub largest: u64,
+    pub total_size: u64,
+    // only computed on demand as it needs the reference graph
+    pub retained_size: Option<u64>,
+}
+
+/// Sort key of the histogram classes, ties are broken by class name.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum HistogramSort {
+    #[default]
+    TotalSize,
+    Instances,
+    Largest,
+    ClassName,
+    RetainedSize,
+}
+
+impl FromStr for HistogramSort {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "total-size" => Ok(HistogramSort::TotalSize),
+            "instances" => Ok(HistogramSort::Instances),
+            "largest" => Ok(HistogramSort::Largest),
+            "name" => Ok(HistogramSort::ClassName),
+            #[cfg(feature = "graph")]
+            "retained-size" => Ok(HistogramSort::RetainedSize),
+            #[cfg(not(feature = "graph"))]
+            "retained-size" => {
+                Err("sorting by retained size needs the `graph` feature".to_string())
+            }
+            other => Err(format!("unknown histogram sort `{}`", other)),
+        }
+    }
+}
+
+impl fmt::Display for HistogramSort {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            HistogramSort::TotalSize => "total-size",
+            HistogramSort::Instances => "instances",
+            HistogramSort::Largest => "largest",
+            HistogramSort::ClassName => "name",
+            HistogramSort::RetainedSize => "retained-size",
+        })
+    }
+}
+
+/// Sorting and thresholds applied to the rendered classes.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HistogramOptions {
+    pub sort: HistogramSort,
+    pub min_total_size: u64,
+    pub min_instances: u64,
+    pub names: NameGrouping,
+    // also render the histogram with the arrays attributed to their owner
+    pub effective_ownership: bool,
+    // split the `char[]`/`byte[]` by the class of their sole referrer
+    pub split_arrays_by_owner: bool,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ClassHistogram {
+    pub total_size: u64,
+    // sorted by total size unless sorted again with `sort_by`
+    pub classes: Vec<ClassHistogramEntry>,
+}
+
+/// Aggregates the shallow size of all instances and arrays per class.
+pub fn class_histogram(heap: &Heap) -> ClassHistogram {
+    aggregate(heap.instances_pool.values().map(|instance| {
+        let class_name = java_type_name(&heap.instance_class_name(instance));
+        (class_name, heap.shallow_size(instance))
+    }))
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct OwnershipHistogram {
+    pub owned_arrays: u64,
+    pub owned_bytes: u64,
+    pub histogram: ClassHistogram,
+}
+
+/// Finds the object solely referencing each `char[]`/`byte[]`, class objects for static fields.
+///
+/// The arrays referenced more than once or not at all have no owner.
+pub fn array_owners(heap: &Heap) -> HashMap<u64, u64> {
+    // referrer of each char[]/byte[], `None` once referenced twice
+    let mut referrers: HashMap<u64, Option<u64>> = heap
+        .instances_pool
+        .values()
+        .filter(|instance| {
+            matches!(
+                instance.array_element_type(),
+                Some(FieldType::Char | FieldType::Byte)
+            )
+        })
+        .map(|instance| (instance.object_id, None))
+        .collect();
+    let mut seen: HashSet<u64> = HashSet::new();
+    let mut refer = |from: u64, to: u64| {
+        if let Some(referrer) = referrers.get_mut(&to) {
+            *referrer = if seen.insert(to) { Some(from) } else { None };
+        }
+    };
+    for instance in heap.instances_pool.values() {
+        match instance.object_array() {
+            Some(ids) => ids.iter().for_each(|id| refer(instance.object_id, *id)),
+            None => instance.all_fields().for_each(|(_, value)| {
+                if let Values::Single(FieldValue::Object(id)) = value {
+                    refer(instance.object_id, *id);
+                }
+            }),
+        }
+    }
+    for class in heap.classes_dump.values() {
+        for (_, value) in &class.static_fields {
+            if let FieldValue::Object(id) = value {
+                refer(class.class_object_id, *id);
+            }
+        }
+    }
+    referrers
+        .into_iter()
+        .filter_map(|(array_id, referrer)| referrer.map(|owner| (array_id, owner)))
+        .collect()
+}
+
+/// Histogram class name of an instance, the arrays found in `array_owners` are suffixed with the class of
+/// their owner, e.g. `byte[] (owned by io.netty.buffer.UnpooledHeapByteBuf)`.
+pub fn histogram_class_name(
+    heap: &Heap,
+    instance: &Instance,
+    array_owners: Option<&HashMap<u64, u64>>,
+) -> String {
+    let class_name = java_type_name(&heap.instance_class_name(instance));
+    match array_owners.and_then(|owners| owners.get(&instance.object_id)) {
+        Some(owner_id) => format!(
+            "{} (owned by {})",
+            class_name,
+            java_type_name(&heap.object_class_name(*owner_id))
+        ),
+        None => class_name,
+    }
+}
+
+/// Like `class_histogram` with the `char[]`/`byte[]` split by the class of the object solely referencing them.
+pub fn array_owner_histogram(heap: &Heap, array_owners: &HashMap<u64, u64>) -> ClassHistogram {
+    aggregate(heap.instances_pool.values().map(|instance| {
+        (
+            histogram_class_name(heap, instance, Some(array_owners)),
+            heap.shallow_size(instance),
+        )
+    }))
+}
+
+/// Attributes each `char[]`/`byte[]` solely referenced by a `String`, `StringBuilder` or `ByteBuffer`
+/// to its owner, the owner classes then account for the arrays they hold.
+pub fn ownership_histogram(heap: &Heap) -> OwnershipHistogram {
+    let owner_class_ids: HashSet<u64> = heap
+        .classes_dump
+        .keys()
+        .filter(|class_id| {
+            ARRAY_OWNER_CLASSES
+                .iter()
+                .any(|owner| heap.is_subclass_of(**class_id, owner))
+        })
+        .copied()
+        .collect();
+    // owner object id -> bytes of the arrays it owns
+    let mut owned_sizes: HashMap<u64, u64> = HashMap::new();
+    let mut owned_arrays: HashSet<u64> = HashSet::new();
+    for (array_id, owner_id) in array_owners(heap) {
+        let owner = heap
+            .instances_pool
+            .get(&owner_id)
+            .filter(|owner| owner_class_ids.contains(&owner.class_object_id));
+        if let (Some(owner), Some(array)) = (owner, heap.instances_pool.get(&array_id)) {
+            *owned_sizes.entry(owner.object_id).or_default() += heap.shallow_size(array);
+            owned_arrays.insert(array_id);
+        }
+    }
+
+    let histogram = aggregate(
+        heap.instances_pool
+            .values()
+            .filter(|instance| !owned_arrays.contains(&instance.object_id))
+            .map(|instance| {
+                let class_name = java_type_name(&heap.instance_class_name(instance));
+                let owned = owned_sizes.get(&instance.object_id).copied();
+                (
+                    class_name,
+                    heap.shallow_size(instance) + owned.unwrap_or_default(),
+                )
+            }),
+    );
+    OwnershipHistogram {
+        owned_arrays: owned_arrays.len() as u64,
+        owned_bytes: owned_sizes.values().sum(),
+        histogram,
+    }
+}
+
+impl OwnershipHistogram {
+    pub fn render(&self, top: usize) -> String {
+        render_blocks(&self.blocks(top))
+    }
+
+    pub fn blocks(&self, top: usize) -> Vec<Block> {
+        vec![
+            Block::Heading(format!(
+                "Found {} in {} char[]/byte[] owned by strings and buffers, top {} classes by effective ownership:",
+                pretty_bytes_size(self.owned_bytes),
+                pretty_count(self.owned_arrays),
+                top
+            )),
+            ClassHistogram::entries_table(self.histogram.classes.iter().take(top)),
+        ]
+    }
+}
+
+fn aggregate(sizes: impl Iterator<Item = (String, u64)>) -> ClassHistogram {
+    let mut by_class: HashMap<String, ClassHistogramEntry> = HashMap::new();
+    for (class_name, size) in sizes {
+        let entry =
+            by_class
+                .entry(class_name)
+                .or_insert_with_key(|class_name| ClassHistogramEntry {
+                    class_name: class_name.clone(),
+                    instances: 0,
+                    largest: 0,
+                    total_size: 0,
+                    retained_size: None,
+                });
+        entry.instances += 1;
+        entry.largest = entry.largest.max(size);
+        entry.total_size += size;
+    }
+
+    let classes: Vec<ClassHistogramEntry> = by_class.into_values().collect();
+    let mut histogram = ClassHistogram {
+        total_size: classes.iter().map(|c| c.total_size).sum(),
+        classes,
+    };
+    histogram.sort_by(HistogramSort::TotalSize);
+    histogram
+}
+
+// object arrays are named after their JVM descriptor e.g. `[Ljava.lang.String;`
+impl ClassHistogram {
+    pub fn sort_by(&mut self, sort: HistogramSort) {
+        self.classes.sort_by(|a, b| {
+            let ordering = match sort {
+                HistogramSort::TotalSize => b.total_size.cmp(&a.total_size),
+                HistogramSort::Instances => b.instances.cmp(&a.instances),
+                HistogramSort::Largest => b.largest.cmp(&a.largest),
+                HistogramSort::ClassName => std::cmp::Ordering::Equal,
+                HistogramSort::RetainedSize => b.retained_size.cmp(&a.retained_size),
+            };
+            ordering.then_with(|| a.class_name.cmp(&b.class_name))
+        });
+    }
+
+    /// Merges the classes sharing a group name, the order is kept by total size.
+    pub fn group_names(&mut self, grouping: NameGrouping) {
+        if !grouping.is_enabled() {
+            return;
+        }
+        let mut by_group: HashMap<String, ClassHistogramEntry> = HashMap::new();
+        for class in self.classes.drain(..) {
+            let group_name = grouping.group_name(&class.class_name);
+            match by_group.get_mut(&group_name) {
+                Some(group) => {
+                    group.instances += class.instances;
+                    group.largest = group.largest.max(class.largest);
+                    group.total_size += class.total_size;
+                    group.retained_size = match (group.retained_size, class.retained_size) {
+                        (Some(a), Some(b)) => Some(a + b),
+                        (a, b) => a.or(b),
+                    };
+                }
+                None => {
+                    let group = ClassHistogramEntry {
+                        class_name: group_name.clone(),
+                        ..class
+                    };
+                    by_group.insert(group_name, group);
+                }
+            }
+        }
+        self.classes = by_group.into_values().collect();
+        self.sort_by(HistogramSort::TotalSize);
+    }
+
+    /// Drops the classes below the thresholds, the total size still covers all of them.
+    pub fn retain_at_least(&mut self, min_total_size: u64, min_instances: u64) {
+        self.classes
+            .retain(|c| c.total_size >= min_total_size && c.instances >= min_instances);
+    }
+
+    /// Fills the retained size of each class, instances dominated by an instance of the same class are counted once.
+    ///
+    /// `grouping` and `array_owners` must be the ones used to name the classes of the histogram.
+    #[cfg(feature = "graph")]
+    pub fn add_retained_sizes(
+        &mut self,
+        heap: &Heap,
+        graph: &HeapGraph,
+        grouping: NameGrouping,
+        array_owners: Option<&HashMap<u64, u64>>,
+    ) {
+        let retained = graph.retained_size_by_group(|object_id| {
+            heap.instances_pool.get(&object_id).map(|instance| {
+                grouping.group_name(&histogram_class_name(heap, instance, array_owners))
+            })
+        });
+        for class in &mut self.classes {
+            class.retained_size =
+                Some(retained.get(&class.class_name).copied().unwrap_or_default());
+        }
+    }
+
+    pub fn render(&self, top: usize) -> String {
+        render_blocks(&self.blocks(top))
+    }
+
+    pub fn blocks(&self, top: usize) -> Vec<Block> {
+        let mut largest: Vec<&ClassHistogramEntry> = self.classes.iter().collect();
+        largest.sort_by(|a, b| {
+            b.largest
+                .cmp(&a.largest)
+                .then_with(|| a.class_name.cmp(&b.class_name))
+        });
+        vec![
+            Block::Heading(format!(
+                "Found a total of {} of instances allocated on the heap.",
+                pretty_bytes_size(self.total_size)
+            )),
+            Block::Heading(format!("Top {} allocated classes:", top)),
+            Self::entries_table(self.classes.iter().take(top)),
+            Block::Heading(format!("Top {} largest instances:", top)),
+            Self::entries_table(largest.into_iter().take(top)),
+        ]
+    }
+
+    fn entries_table<'a>(entries: impl Iterator<Item = &'a ClassHistogramEntry>) -> Block {
+        let entries: Vec<&ClassHistogramEntry> = entries.collect();
+        let with_retained = entries.iter().any(|c| c.retained_size.is_some());
+        let rows: Vec<Vec<String>> = entries
+            .iter()
+            .map(|c| {
+                let mut row = vec![pretty_bytes_size(c.total_size)];
+                if with_retained {
+                    row.push(pretty_bytes_size(c.retained_size.unwrap_or_default()));
+                }
+                row.push(pretty_count(c.instances));
+                row.push(pretty_bytes_size(c.largest));
+                row.push(pretty_class_name(&c.class_name));
+                row
+            })
+            .collect();
+        if with_retained {
+            Block::table(
+                &[
+                    "Total size",
+                    "Retained",
+                    "Instances",
+                    "Largest",
+                    "Class name",
+                ],
+                rows,
+            )
+        } else {
+            Block::table(&["Total size", "Instances", "Largest", "Class name"], rows)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::gc_record::{ClassDumpFields, PrimitiveArray};
+    use crate::parser::record::LoadClassData;
+    use crate::tests::{add_instance, reference};
+    use std::sync::Arc;
+
+    #[test]
+    fn split_arrays_by_sole_owner() {
+        let mut heap = Heap::default();
+        heap.counter.id_size = 8;
+        heap.utf8_strings
+            .make_mut()
+            .insert(1, "io/netty/buffer/UnpooledHeapByteBuf".into());
+        heap.class_registry.make_mut().load(LoadClassData {
+            serial_number: 1,
+            class_object_id: 100,
+            stack_trace_serial_number: 0,
+            class_name_id: 1,
+        });
+        heap.classes_dump.make_mut().insert(
+            100,
+            ClassDumpFields::new(100, 0, 0, 0, 0, Vec::new(), Vec::new(), Vec::new()),
+        );
+        // buffer 1 solely owns array 10, buffers 2 and 3 share array 11, array 12 is unreferenced
+        for (object_id, array_id) in [(1, 10), (2, 11), (3, 11)] {
+            add_instance(&mut heap, object_id, 100, vec![reference(0, array_id)]);
+        }
+        for array_id in [10, 11, 12] {
+            let array = PrimitiveArray::new(FieldType::Byte, 0, Arc::from([]));
+            add_instance(
+                &mut heap,
+                array_id,
+                0,
+                vec![(0, Values::PrimitiveArray(array))],
+            );
+        }
+
+        let owners = array_owners(&heap);
+        assert_eq!(owners, HashMap::from([(10, 1)]));
+        let histogram = array_owner_histogram(&heap, &owners);
+        let mut classes: Vec<(&str, u64)> = histogram
+            .classes
+            .iter()
+            .map(|c| (c.class_name.as_str(), c.instances))
+            .collect();
+        classes.sort();
+        assert_eq!(
+            classes,
+            [
+                ("byte[]", 2),
+                ("byte[] (owned by io.netty.buffer.UnpooledHeapByteBuf)", 1),
+                ("io.netty.buffer.UnpooledHeapByteBuf", 3)
+            ]
+        );
+        assert_eq!(histogram.total_size, class_histogram(&heap).total_size);
+    }
+}
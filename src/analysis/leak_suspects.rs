@@ -0,0 +1,51 @@
+use serde::Serialize;
+
+use crate::analysis::class_loaders::{class_loader_report, ClassLoaderReport};
+use crate::analysis::connections::{connection_report, ConnectionReport, DEFAULT_LEAK_THRESHOLD};
+use crate::graph::HeapGraph;
+use crate::report::{render_blocks, Block};
+use crate::Heap;
+
+/// Gathers the findings of the analyses able to flag leak suspects, one section per analysis.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct LeakSuspectsReport {
+    pub connections: ConnectionReport,
+    pub class_loaders: ClassLoaderReport,
+}
+
+pub fn leak_suspects_report(heap: &Heap, graph: &HeapGraph) -> LeakSuspectsReport {
+    let mut connections = connection_report(heap, graph, DEFAULT_LEAK_THRESHOLD);
+    connections.classes.retain(|c| c.leak_suspect);
+    let mut class_loaders = class_loader_report(heap, graph);
+    class_loaders.loaders.retain(|l| l.leak_suspect);
+    LeakSuspectsReport {
+        connections,
+        class_loaders,
+    }
+}
+
+impl LeakSuspectsReport {
+    pub fn is_empty(&self) -> bool {
+        self.connections.classes.is_empty() && self.class_loaders.loaders.is_empty()
+    }
+
+    pub fn blocks(&self) -> Vec<Block> {
+        if self.is_empty() {
+            return vec![Block::heading("No leak suspects found.")];
+        }
+        let mut blocks = vec![Block::heading("Leak suspects:")];
+        if !self.connections.classes.is_empty() {
+            blocks.push(Block::heading("Connections:"));
+            blocks.extend(self.connections.blocks());
+        }
+        if !self.class_loaders.loaders.is_empty() {
+            blocks.push(Block::heading("Class loaders:"));
+            blocks.extend(self.class_loaders.blocks());
+        }
+        blocks
+    }
+
+    pub fn render(&self) -> String {
+        render_blocks(&self.blocks())
+    }
+}
@@ -0,0 +1,458 @@
+use std::collections::BTreeMap;
+
+use serde::Serialize;
+
+use crate::parser::gc_record::FieldValue;
+use crate::report::{render_blocks, Block};
+use crate::result_recorder::Instance;
+use crate::utils::pretty_count;
+use crate::{GcRootKind, Heap};
+
+// JVMTI thread state bits stored in `threadStatus`
+const JVMTI_THREAD_STATE_ALIVE: i32 = 0x0001;
+const JVMTI_THREAD_STATE_TERMINATED: i32 = 0x0002;
+const JVMTI_THREAD_STATE_RUNNABLE: i32 = 0x0004;
+const JVMTI_THREAD_STATE_WAITING_INDEFINITELY: i32 = 0x0010;
+const JVMTI_THREAD_STATE_WAITING_WITH_TIMEOUT: i32 = 0x0020;
+const JVMTI_THREAD_STATE_BLOCKED_ON_MONITOR_ENTER: i32 = 0x0400;
+
+/// `java.lang.Thread.State` decoded from the thread's `threadStatus`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum ThreadState {
+    New,
+    Runnable,
+    Blocked,
+    Waiting,
+    TimedWaiting,
+    Terminated,
+    Unknown,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct LockThread {
+    pub thread_object_id: u64,
+    pub thread_name: Option<String>,
+    pub state: ThreadState,
+    // frame referencing the lock, if recorded as a stack root
+    pub frame: Option<String>,
+    // parked on a `java.util.concurrent` lock through `LockSupport.park`
+    pub parked: bool,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct LockSummary {
+    pub object_id: u64,
+    pub class_name: String,
+    // object reported as a `RootMonitorUsed` by the JVM
+    pub monitor_in_use: bool,
+    // `exclusiveOwnerThread` of `java.util.concurrent` locks
+    pub owner: Option<LockThread>,
+    pub threads: Vec<LockThread>,
+}
+
+impl LockSummary {
+    pub fn blocked_threads(&self) -> usize {
+        self.threads
+            .iter()
+            .filter(|t| t.parked || t.state == ThreadState::Blocked)
+            .count()
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct LockReport {
+    pub locks: Vec<LockSummary>,
+    // threads blocked on a monitor entry whose monitor is not in the dump (HotSpot does not record `RootMonitorUsed`)
+    pub blocked_on_unknown_monitor: Vec<LockThread>,
+}
+
+/// Snapshot of the monitors and `java.util.concurrent` locks in use with the threads referencing or waiting on them.
+///
+/// Combines the `RootMonitorUsed`, stack frame and `RootThreadBlock` roots with the `threadStatus` and
+/// `parkBlocker` fields of the threads.
+pub fn lock_report(heap: &Heap) -> LockReport {
+    let mut locks: BTreeMap<u64, LockSummary> = BTreeMap::new();
+    let new_lock = |object_id: u64| LockSummary {
+        object_id,
+        class_name: heap.object_class_name(object_id),
+        monitor_in_use: false,
+        owner: None,
+        threads: Vec::new(),
+    };
+
+    for object_id in &heap.root_monitor_used {
+        locks
+            .entry(*object_id)
+            .or_insert_with(|| new_lock(*object_id))
+            .monitor_in_use = true;
+    }
+
+    // threads referencing the monitors from their stacks
+    for root in heap.gc_roots() {
+        let on_stack = matches!(
+            root.kind,
+            GcRootKind::JavaFrame | GcRootKind::JniLocal | GcRootKind::ThreadBlock
+        );
+        let (Some(lock), Some(thread_object_id)) =
+            (locks.get_mut(&root.object_id), root.thread_object_id)
+        else {
+            continue;
+        };
+        if !on_stack
+            || lock
+                .threads
+                .iter()
+                .any(|t| t.thread_object_id == thread_object_id)
+        {
+            continue;
+        }
+        lock.threads.push(LockThread {
+            thread_object_id,
+            thread_name: root.thread_name,
+            state: thread_state(heap, thread_object_id),
+            frame: root.frame.map(|frame| frame.to_string()),
+            parked: false,
+        });
+    }
+
+    // threads parked on java.util.concurrent locks
+    let mut thread_ids: Vec<u64> = heap.root_thread_object.keys().copied().collect();
+    thread_ids.sort_unstable();
+    for thread_object_id in thread_ids {
+        let blocker = match heap
+            .instances_pool
+            .get(&thread_object_id)
+            .and_then(|thread| heap.field_instance(thread, "parkBlocker"))
+        {
+            Some(blocker) => blocker,
+            None => continue,
+        };
+        let lock = locks
+            .entry(blocker.object_id)
+            .or_insert_with(|| new_lock(blocker.object_id));
+        if lock.owner.is_none() {
+            lock.owner = heap
+                .field_instance(blocker, "exclusiveOwnerThread")
+                .map(|owner| lock_thread(heap, owner.object_id));
+        }
+        lock.threads
+            .retain(|t| t.thread_object_id != thread_object_id);
+        lock.threads.push(LockThread {
+            parked: true,
+            ..lock_thread(heap, thread_object_id)
+        });
+    }
+
+    let mut blocked_on_unknown_monitor: Vec<LockThread> = Vec::new();
+    let mut threads: Vec<_> = heap.root_thread_object.values().collect();
+    threads.sort_by_key(|thread| thread.thread_object_id);
+    for thread in threads {
+        let known = locks.values().any(|lock| {
+            lock.threads
+                .iter()
+                .any(|t| t.thread_object_id == thread.thread_object_id)
+        });
+        if known || thread_state(heap, thread.thread_object_id) != ThreadState::Blocked {
+            continue;
+        }
+        blocked_on_unknown_monitor.push(LockThread {
+            frame: heap
+                .stack_frames(thread.stack_sequence_number)
+                .and_then(|frames| frames.into_iter().next())
+                .map(|frame| frame.to_string()),
+            ..lock_thread(heap, thread.thread_object_id)
+        });
+    }
+
+    let mut locks: Vec<LockSummary> = locks.into_values().collect();
+    locks.sort_by(|a, b| {
+        b.blocked_threads()
+            .cmp(&a.blocked_threads())
+            .then_with(|| b.threads.len().cmp(&a.threads.len()))
+            .then_with(|| a.object_id.cmp(&b.object_id))
+    });
+    LockReport {
+        locks,
+        blocked_on_unknown_monitor,
+    }
+}
+
+fn lock_thread(heap: &Heap, thread_object_id: u64) -> LockThread {
+    LockThread {
+        thread_object_id,
+        thread_name: heap.thread_name(thread_object_id),
+        state: thread_state(heap, thread_object_id),
+        frame: None,
+        parked: false,
+    }
+}
+
+// `threadStatus` moved to the `holder` of the thread with JDK 19
+fn thread_state(heap: &Heap, thread_object_id: u64) -> ThreadState {
+    let thread = match heap.instances_pool.get(&thread_object_id) {
+        Some(thread) => thread,
+        None => return ThreadState::Unknown,
+    };
+    let status = |instance: &Instance| match heap.field_value(instance, "threadStatus") {
+        Some(FieldValue::Int(status)) => Some(*status),
+        _ => None,
+    };
+    let status = status(thread).or_else(|| {
+        heap.field_instance(thread, "holder")
+            .and_then(|holder| status(holder))
+    });
+    match status {
+        None => ThreadState::Unknown,
+        Some(status) if status & JVMTI_THREAD_STATE_RUNNABLE != 0 => ThreadState::Runnable,
+        Some(status) if status & JVMTI_THREAD_STATE_BLOCKED_ON_MONITOR_ENTER != 0 => {
+            ThreadState::Blocked
+        }
+        Some(status) if status & JVMTI_THREAD_STATE_WAITING_INDEFINITELY != 0 => {
+            ThreadState::Waiting
+        }
+        Some(status) if status & JVMTI_THREAD_STATE_WAITING_WITH_TIMEOUT != 0 => {
+            ThreadState::TimedWaiting
+        }
+        Some(status) if status & JVMTI_THREAD_STATE_TERMINATED != 0 => ThreadState::Terminated,
+        Some(status) if status & JVMTI_THREAD_STATE_ALIVE == 0 => ThreadState::New,
+        Some(_) => ThreadState::Runnable,
+    }
+}
+
+impl LockReport {
+    pub fn blocks(&self) -> Vec<Block> {
+        let blocked: usize = self
+            .locks
+            .iter()
+            .map(|l| l.blocked_threads())
+            .sum::<usize>()
+            + self.blocked_on_unknown_monitor.len();
+        let mut blocks = vec![Block::Heading(format!(
+            "Found {} monitors and locks in use ({} blocked threads):",
+            self.locks.len(),
+            blocked
+        ))];
+        if !self.locks.is_empty() {
+            blocks.extend(self.lock_blocks());
+        }
+        if !self.blocked_on_unknown_monitor.is_empty() {
+            blocks.push(Block::Warning(
+                "Threads blocked on a monitor not recorded in the dump:".to_string(),
+            ));
+            let mut lines = Vec::new();
+            for thread in &self.blocked_on_unknown_monitor {
+                lines.push(format!("  {}", render_thread(thread)));
+                if let Some(frame) = &thread.frame {
+                    lines.push(format!("    {}", frame));
+                }
+            }
+            blocks.push(Block::Lines(lines));
+        }
+        blocks
+    }
+
+    pub fn render(&self) -> String {
+        render_blocks(&self.blocks())
+    }
+
+    fn lock_blocks(&self) -> Vec<Block> {
+        let rows: Vec<Vec<String>> = self
+            .locks
+            .iter()
+            .map(|l| {
+                vec![
+                    pretty_count(l.threads.len() as u64),
+                    pretty_count(l.blocked_threads() as u64),
+                    format!("0x{:x}", l.object_id),
+                    l.class_name.clone(),
+                ]
+            })
+            .collect();
+        let mut blocks = vec![Block::table(
+            &["Threads", "Blocked", "Object id", "Class name"],
+            rows,
+        )];
+        for lock in self.locks.iter().filter(|l| !l.threads.is_empty()) {
+            blocks.push(Block::Heading(format!(
+                "{} 0x{:x}",
+                lock.class_name, lock.object_id
+            )));
+            let mut lines = Vec::new();
+            if let Some(owner) = &lock.owner {
+                lines.push(format!("  owned by {}", render_thread(owner)));
+            }
+            for thread in &lock.threads {
+                let relation = if thread.parked {
+                    "parked"
+                } else {
+                    "referenced"
+                };
+                lines.push(format!("  {} by {}", relation, render_thread(thread)));
+                if let Some(frame) = &thread.frame {
+                    lines.push(format!("    {}", frame));
+                }
+            }
+            blocks.push(Block::Lines(lines));
+        }
+        blocks
+    }
+}
+
+fn render_thread(thread: &LockThread) -> String {
+    format!(
+        "'{}' [{:?}]",
+        thread.thread_name.as_deref().unwrap_or("unknown thread"),
+        thread.state
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::result_recorder::{RootThreadBlock, RootThreadObject};
+    use crate::tests::{add_class, add_instance, reference, single};
+
+    // field name ids
+    const THREAD_STATUS: u64 = 1;
+    const PARK_BLOCKER: u64 = 2;
+    const EXCLUSIVE_OWNER_THREAD: u64 = 3;
+
+    #[test]
+    fn monitors_and_parked_threads() {
+        let mut heap = Heap::default();
+        heap.counter.id_size = 8;
+        for (name_id, name) in [
+            (THREAD_STATUS, "threadStatus"),
+            (PARK_BLOCKER, "parkBlocker"),
+            (EXCLUSIVE_OWNER_THREAD, "exclusiveOwnerThread"),
+        ] {
+            heap.utf8_strings.make_mut().insert(name_id, name.into());
+        }
+        add_class(&mut heap, 100, 0, "java/lang/Thread");
+        add_class(&mut heap, 101, 0, "java/lang/Object");
+        add_class(
+            &mut heap,
+            102,
+            0,
+            "java/util/concurrent/locks/ReentrantLock$NonfairSync",
+        );
+        let alive = JVMTI_THREAD_STATE_ALIVE;
+        // blocked on the monitor, parked on the lock, owning the lock and blocked on a monitor not in the dump
+        for (thread_id, status, blocker) in [
+            (1, alive | JVMTI_THREAD_STATE_BLOCKED_ON_MONITOR_ENTER, None),
+            (2, alive | JVMTI_THREAD_STATE_WAITING_INDEFINITELY, Some(60)),
+            (3, alive | JVMTI_THREAD_STATE_RUNNABLE, None),
+            (4, alive | JVMTI_THREAD_STATE_BLOCKED_ON_MONITOR_ENTER, None),
+        ] {
+            let mut fields = vec![single(THREAD_STATUS, FieldValue::Int(status))];
+            fields.extend(blocker.map(|blocker_id| reference(PARK_BLOCKER, blocker_id)));
+            add_instance(&mut heap, thread_id, 100, fields);
+            heap.root_thread_object.make_mut().insert(
+                thread_id,
+                RootThreadObject {
+                    thread_object_id: thread_id,
+                    thread_sequence_number: thread_id as u32,
+                    stack_sequence_number: 0,
+                },
+            );
+        }
+        add_instance(&mut heap, 50, 101, Vec::new());
+        heap.root_monitor_used.make_mut().push(50);
+        heap.root_thread_block.make_mut().push(RootThreadBlock {
+            object_id: 50,
+            thread_serial_number: 1,
+        });
+        add_instance(
+            &mut heap,
+            60,
+            102,
+            vec![reference(EXCLUSIVE_OWNER_THREAD, 3)],
+        );
+
+        let report = lock_report(&heap);
+        assert_eq!(report.locks.len(), 2);
+        let monitor = &report.locks[0];
+        assert_eq!((monitor.object_id, monitor.monitor_in_use), (50, true));
+        assert!(monitor.owner.is_none());
+        assert_eq!(monitor.threads.len(), 1);
+        assert_eq!(
+            (
+                monitor.threads[0].thread_object_id,
+                monitor.threads[0].state,
+                monitor.threads[0].parked
+            ),
+            (1, ThreadState::Blocked, false)
+        );
+        let lock = &report.locks[1];
+        assert_eq!((lock.object_id, lock.monitor_in_use), (60, false));
+        let owner = lock.owner.as_ref().unwrap();
+        assert_eq!(
+            (owner.thread_object_id, owner.state),
+            (3, ThreadState::Runnable)
+        );
+        assert_eq!(lock.threads.len(), 1);
+        assert_eq!(
+            (
+                lock.threads[0].thread_object_id,
+                lock.threads[0].state,
+                lock.threads[0].parked
+            ),
+            (2, ThreadState::Waiting, true)
+        );
+        let unknown: Vec<u64> = report
+            .blocked_on_unknown_monitor
+            .iter()
+            .map(|t| t.thread_object_id)
+            .collect();
+        assert_eq!(unknown, vec![4]);
+
+        let rendered = report.render();
+        assert!(rendered.contains("Found 2 monitors and locks in use (3 blocked threads)"));
+        assert!(rendered.contains("parked by 'unknown thread' [Waiting]"));
+    }
+
+    #[test]
+    fn threads_blocked_on_the_same_monitor() {
+        let mut heap = Heap::default();
+        heap.counter.id_size = 8;
+        heap.utf8_strings
+            .make_mut()
+            .insert(THREAD_STATUS, "threadStatus".into());
+        add_class(&mut heap, 100, 0, "java/lang/Thread");
+        add_class(&mut heap, 101, 0, "java/lang/Object");
+        let blocked = JVMTI_THREAD_STATE_ALIVE | JVMTI_THREAD_STATE_BLOCKED_ON_MONITOR_ENTER;
+        for thread_id in [1, 2] {
+            add_instance(
+                &mut heap,
+                thread_id,
+                100,
+                vec![single(THREAD_STATUS, FieldValue::Int(blocked))],
+            );
+            heap.root_thread_object.make_mut().insert(
+                thread_id,
+                RootThreadObject {
+                    thread_object_id: thread_id,
+                    thread_sequence_number: thread_id as u32,
+                    stack_sequence_number: 0,
+                },
+            );
+            heap.root_thread_block.make_mut().push(RootThreadBlock {
+                object_id: 50,
+                thread_serial_number: thread_id as u32,
+            });
+        }
+        add_instance(&mut heap, 50, 101, Vec::new());
+        heap.root_monitor_used.make_mut().push(50);
+
+        let report = lock_report(&heap);
+        assert_eq!(report.locks.len(), 1);
+        let threads: Vec<u64> = report.locks[0]
+            .threads
+            .iter()
+            .map(|t| t.thread_object_id)
+            .collect();
+        assert_eq!(threads, vec![1, 2]);
+        assert_eq!(report.locks[0].blocked_threads(), 2);
+        assert!(report.blocked_on_unknown_monitor.is_empty());
+    }
+}
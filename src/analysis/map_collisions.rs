@@ -0,0 +1,290 @@
+use std::collections::HashSet;
+
+use serde::Serialize;
+
+use crate::analysis::class_names::pretty_class_name;
+use crate::report::{render_blocks, Block};
+use crate::result_recorder::Instance;
+use crate::utils::pretty_count;
+use crate::Heap;
+
+// the maps whose `table` holds chains of nodes linked by `next`
+const HASH_MAP_CLASSES: &[&str] = &[
+    "java.util.HashMap",
+    "java.util.concurrent.ConcurrentHashMap",
+];
+// bins holding the first node of their chain in `first` instead of being the first node
+const TREE_BIN_CLASS: &str = "java.util.concurrent.ConcurrentHashMap$TreeBin";
+// bins turned into red-black trees, the nodes still link each other with `next`
+const TREE_NODE_CLASSES: &[&str] = &[
+    "java.util.HashMap$TreeNode",
+    "java.util.concurrent.ConcurrentHashMap$TreeBin",
+];
+// chains of 8 nodes and more are counted together, HashMap turns them into trees
+pub const LONG_CHAIN: usize = 8;
+
+/// Bucket occupancy of a map table.
+#[derive(Debug, Clone, Serialize)]
+pub struct MapBuckets {
+    pub object_id: u64,
+    pub class_name: String,
+    pub capacity: u64,
+    pub entries: u64,
+    pub occupied: u64,
+    // occupied buckets if the hash codes were evenly distributed
+    pub expected_occupied: f64,
+    pub longest_chain: u64,
+    pub tree_bins: u64,
+    // buckets per chain length, from 1 to `LONG_CHAIN` and more
+    pub chain_lengths: [u64; LONG_CHAIN],
+}
+
+impl MapBuckets {
+    /// Ratio of the occupied buckets to the ones evenly distributed hash codes would occupy, 1 is ideal.
+    pub fn spread(&self) -> f64 {
+        if self.expected_occupied == 0.0 {
+            1.0
+        } else {
+            self.occupied as f64 / self.expected_occupied
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct MapCollisionReport {
+    pub maps_scanned: u64,
+    // sorted by entries
+    pub maps: Vec<MapBuckets>,
+    // over the reported maps
+    pub chain_lengths: [u64; LONG_CHAIN],
+}
+
+/// Walks the tables and node chains of the `HashMap` and `ConcurrentHashMap` instances, subclasses included,
+/// and reports the bucket occupancy of the `top` largest ones.
+///
+/// Long chains in a sparsely occupied table come from poorly distributed `hashCode` implementations.
+pub fn map_collision_report(heap: &Heap, top: usize) -> MapCollisionReport {
+    let mut maps: Vec<MapBuckets> = HASH_MAP_CLASSES
+        .iter()
+        .flat_map(|class_name| heap.instances_of(class_name))
+        .filter_map(|map| map_buckets(heap, map))
+        .collect();
+    let mut report = MapCollisionReport {
+        maps_scanned: maps.len() as u64,
+        ..Default::default()
+    };
+    maps.sort_by(|a, b| {
+        b.entries
+            .cmp(&a.entries)
+            .then_with(|| a.object_id.cmp(&b.object_id))
+    });
+    maps.truncate(top);
+    for map in &maps {
+        for (total, count) in report.chain_lengths.iter_mut().zip(map.chain_lengths) {
+            *total += count;
+        }
+    }
+    report.maps = maps;
+    report
+}
+
+// `None` for maps without a table yet or whose table was not retained
+fn map_buckets(heap: &Heap, map: &Instance) -> Option<MapBuckets> {
+    let table = heap.field_instance(map, "table")?;
+    let buckets = table.object_array().filter(|ids| !ids.is_empty())?;
+    let mut entries = 0;
+    let mut occupied = 0;
+    let mut longest_chain = 0;
+    let mut tree_bins = 0;
+    let mut chain_lengths = [0; LONG_CHAIN];
+    for bucket in buckets.iter().filter(|id| **id != 0) {
+        let Some(mut node) = heap.instances_pool.get(bucket) else {
+            continue;
+        };
+        let class_name = heap.instance_class_name(node);
+        if TREE_NODE_CLASSES.contains(&class_name.as_str()) {
+            tree_bins += 1;
+        }
+        if class_name == TREE_BIN_CLASS {
+            match heap.field_instance(node, "first") {
+                Some(first) => node = first,
+                None => continue,
+            }
+        }
+        // forwarding and reservation nodes of a resizing ConcurrentHashMap have no key
+        if heap.field_value(node, "key").is_none() {
+            continue;
+        }
+        // a chain visiting a node twice was captured while being modified
+        let mut visited = HashSet::new();
+        let mut length = 0;
+        let mut current = Some(node);
+        while let Some(next) = current.filter(|n| visited.insert(n.object_id)) {
+            length += 1;
+            current = heap.field_instance(next, "next");
+        }
+        entries += length;
+        occupied += 1;
+        longest_chain = longest_chain.max(length);
+        chain_lengths[(length as usize).min(LONG_CHAIN) - 1] += 1;
+    }
+    let capacity = buckets.len() as u64;
+    // each bucket stays empty with probability (1 - 1/capacity)^entries
+    let expected_occupied =
+        capacity as f64 * (1.0 - (1.0 - 1.0 / capacity as f64).powf(entries as f64));
+    Some(MapBuckets {
+        object_id: map.object_id,
+        class_name: heap.instance_class_name(map),
+        capacity,
+        entries,
+        occupied,
+        expected_occupied,
+        longest_chain,
+        tree_bins,
+        chain_lengths,
+    })
+}
+
+impl MapCollisionReport {
+    pub fn blocks(&self) -> Vec<Block> {
+        let mut blocks = vec![Block::Heading(format!(
+            "Scanned the tables of {} hash maps, the {} largest:",
+            pretty_count(self.maps_scanned),
+            self.maps.len()
+        ))];
+        if self.maps.is_empty() {
+            return blocks;
+        }
+        let rows: Vec<Vec<String>> = self
+            .maps
+            .iter()
+            .map(|m| {
+                vec![
+                    pretty_count(m.entries),
+                    pretty_count(m.capacity),
+                    pretty_count(m.occupied),
+                    format!("{:.2}", m.spread()),
+                    m.longest_chain.to_string(),
+                    m.tree_bins.to_string(),
+                    format!("0x{:x}", m.object_id),
+                    pretty_class_name(&m.class_name),
+                ]
+            })
+            .collect();
+        blocks.push(Block::table(
+            &[
+                "Entries",
+                "Capacity",
+                "Occupied",
+                "Spread",
+                "Longest chain",
+                "Trees",
+                "Object id",
+                "Class name",
+            ],
+            rows,
+        ));
+        let lengths: Vec<String> = self
+            .chain_lengths
+            .iter()
+            .enumerate()
+            .map(|(index, count)| {
+                let label = if index + 1 == LONG_CHAIN {
+                    format!("{}+", LONG_CHAIN)
+                } else {
+                    (index + 1).to_string()
+                };
+                format!("{}: {}", label, pretty_count(*count))
+            })
+            .collect();
+        blocks.push(Block::Text(format!(
+            "Buckets per chain length: {}",
+            lengths.join(", ")
+        )));
+        blocks
+    }
+
+    pub fn render(&self) -> String {
+        render_blocks(&self.blocks())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::gc_record::{ArrayValue, ClassDumpFields, Values};
+    use crate::parser::record::LoadClassData;
+    use crate::tests::{add_instance, reference};
+
+    // field name ids
+    const TABLE: u64 = 1;
+    const KEY: u64 = 2;
+    const NEXT: u64 = 3;
+
+    #[test]
+    fn chain_lengths_of_map_tables() {
+        let mut heap = Heap::default();
+        for (name_id, name) in [
+            (TABLE, "table"),
+            (KEY, "key"),
+            (NEXT, "next"),
+            (10, "java/util/HashMap"),
+            (11, "java/util/HashMap$Node"),
+            (12, "[Ljava/util/HashMap$Node;"),
+        ] {
+            heap.utf8_strings.make_mut().insert(name_id, name.into());
+        }
+        for (serial_number, class_object_id, class_name_id) in
+            [(1, 100, 10), (2, 101, 11), (3, 102, 12)]
+        {
+            heap.class_registry.make_mut().load(LoadClassData {
+                serial_number,
+                class_object_id,
+                stack_trace_serial_number: 0,
+                class_name_id,
+            });
+            heap.classes_dump.make_mut().insert(
+                class_object_id,
+                ClassDumpFields::new(
+                    class_object_id,
+                    0,
+                    0,
+                    0,
+                    0,
+                    Vec::new(),
+                    Vec::new(),
+                    Vec::new(),
+                ),
+            );
+        }
+        // 4 buckets: a chain of 3 nodes, a single node and 2 empty buckets
+        add_instance(&mut heap, 1, 100, vec![reference(TABLE, 2)]);
+        add_instance(
+            &mut heap,
+            2,
+            102,
+            vec![(0, Values::Array(ArrayValue::Object(vec![20, 0, 23, 0])))],
+        );
+        for (node_id, next_id) in [(20, 21), (21, 22), (22, 0), (23, 0)] {
+            add_instance(
+                &mut heap,
+                node_id,
+                101,
+                vec![reference(KEY, 0), reference(NEXT, next_id)],
+            );
+        }
+        // a map without table
+        add_instance(&mut heap, 3, 100, vec![reference(TABLE, 0)]);
+
+        let report = map_collision_report(&heap, 10);
+        assert_eq!(report.maps_scanned, 1);
+        let map = &report.maps[0];
+        assert_eq!((map.capacity, map.entries, map.occupied), (4, 4, 2));
+        assert_eq!(map.longest_chain, 3);
+        assert_eq!(map.tree_bins, 0);
+        assert_eq!(&map.chain_lengths[..3], &[1, 0, 1]);
+        // 4 evenly spread entries occupy 4 * (1 - (3/4)^4) buckets
+        assert!((map.expected_occupied - 2.734375).abs() < 1e-9);
+        assert!(report.render().contains("1: 1, 2: 0, 3: 1"));
+    }
+}
@@ -0,0 +1,203 @@
+use std::collections::{HashMap, HashSet};
+
+use serde::Serialize;
+
+use crate::analysis::class_loader_label;
+use crate::report::{render_blocks, Block};
+use crate::utils::{pretty_bytes_size, pretty_count};
+use crate::Heap;
+
+// rough HotSpot 64-bit sizes: `InstanceKlass` with its vtable/itable, a field entry, a resolved
+// constant pool slot and a `Method` with its `ConstMethod` for a small method body
+const KLASS_BYTES: u64 = 512;
+const FIELD_BYTES: u64 = 16;
+const CONSTANT_POOL_ENTRY_BYTES: u64 = 8;
+const METHOD_BYTES: u64 = 160;
+// symbols are shared across classes, only the class name is attributed to its class
+const SYMBOL_OVERHEAD_BYTES: u64 = 16;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ClassLoaderMetadata {
+    pub class_loader_object_id: u64,
+    pub class_loader: String,
+    pub classes: u64,
+    pub fields: u64,
+    pub constant_pool_entries: u64,
+    // methods seen in the recorded stack traces only, a lower bound
+    pub methods: u64,
+    pub estimated_bytes: u64,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct MetaspaceReport {
+    pub estimated_bytes: u64,
+    // sorted by estimated size
+    pub class_loaders: Vec<ClassLoaderMetadata>,
+}
+
+/// Estimates the class metadata footprint per class loader from the class dumps.
+///
+/// The dump does not record methods nor bytecode, the estimate is a floor meant to compare loaders
+/// rather than to match the `Metaspace` usage reported by the JVM.
+pub fn metaspace_report(heap: &Heap) -> MetaspaceReport {
+    let mut methods_per_class: HashMap<u64, HashSet<(u64, u64)>> = HashMap::new();
+    for frame in heap.stack_frame_by_id.values() {
+        if let Some(class) = heap.class_by_serial_number(frame.class_serial_number) {
+            methods_per_class
+                .entry(class.class_object_id)
+                .or_default()
+                .insert((frame.method_name_id, frame.method_signature_id));
+        }
+    }
+
+    let mut by_loader: HashMap<u64, ClassLoaderMetadata> = HashMap::new();
+    for (class_object_id, class) in &heap.classes_dump {
+        let fields = (class.static_fields.len() + class.instance_fields.len()) as u64;
+        let constant_pool_entries = class.const_fields.len() as u64;
+        let methods = methods_per_class
+            .get(class_object_id)
+            .map_or(0, |methods| methods.len() as u64);
+        let name_bytes = heap
+            .class_registry
+            .by_id(*class_object_id)
+            .and_then(|data| heap.utf8_strings.get(&data.class_name_id))
+            .map_or(0, |name| name.len() as u64);
+        let loader = by_loader
+            .entry(class.class_loader_object_id)
+            .or_insert_with(|| ClassLoaderMetadata {
+                class_loader_object_id: class.class_loader_object_id,
+                class_loader: class_loader_label(heap, class.class_loader_object_id),
+                classes: 0,
+                fields: 0,
+                constant_pool_entries: 0,
+                methods: 0,
+                estimated_bytes: 0,
+            });
+        loader.classes += 1;
+        loader.fields += fields;
+        loader.constant_pool_entries += constant_pool_entries;
+        loader.methods += methods;
+        loader.estimated_bytes += KLASS_BYTES
+            + fields * FIELD_BYTES
+            + constant_pool_entries * CONSTANT_POOL_ENTRY_BYTES
+            + methods * METHOD_BYTES
+            + name_bytes
+            + SYMBOL_OVERHEAD_BYTES;
+    }
+
+    let mut class_loaders: Vec<ClassLoaderMetadata> = by_loader.into_values().collect();
+    class_loaders.sort_by(|a, b| {
+        b.estimated_bytes
+            .cmp(&a.estimated_bytes)
+            .then_with(|| a.class_loader_object_id.cmp(&b.class_loader_object_id))
+    });
+    MetaspaceReport {
+        estimated_bytes: class_loaders.iter().map(|l| l.estimated_bytes).sum(),
+        class_loaders,
+    }
+}
+
+impl MetaspaceReport {
+    pub fn blocks(&self, top: usize) -> Vec<Block> {
+        let rows: Vec<Vec<String>> = self
+            .class_loaders
+            .iter()
+            .take(top)
+            .map(|l| {
+                vec![
+                    pretty_bytes_size(l.estimated_bytes),
+                    pretty_count(l.classes),
+                    pretty_count(l.fields),
+                    pretty_count(l.methods),
+                    l.class_loader.clone(),
+                ]
+            })
+            .collect();
+        vec![
+            Block::Heading(format!(
+                "Estimated at least {} of class metadata for {} class loaders:",
+                pretty_bytes_size(self.estimated_bytes),
+                self.class_loaders.len()
+            )),
+            Block::table(
+                &["Estimated", "Classes", "Fields", "Methods", "Class loader"],
+                rows,
+            ),
+        ]
+    }
+
+    pub fn render(&self, top: usize) -> String {
+        render_blocks(&self.blocks(top))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::gc_record::{FieldInfo, FieldType, FieldValue};
+    use crate::parser::record::StackFrameData;
+    use crate::tests::{add_class, add_instance};
+
+    #[test]
+    fn metadata_per_class_loader() {
+        let mut heap = Heap::default();
+        heap.counter.id_size = 8;
+        add_class(&mut heap, 100, 0, "java/lang/Object");
+        add_class(&mut heap, 101, 100, "com/acme/PluginLoader");
+        add_instance(&mut heap, 1, 101, Vec::new());
+        let field = FieldInfo {
+            name_id: 2,
+            field_type: FieldType::Int,
+        };
+        let plugin = add_class(&mut heap, 102, 100, "com/acme/Plugin");
+        plugin.class_loader_object_id = 1;
+        plugin.instance_fields.push(field.clone());
+        plugin.static_fields.push((field, FieldValue::Int(0)));
+        // two distinct methods of the plugin class seen in three frames
+        for (stack_frame_id, method_name_id) in [(1, 10), (2, 10), (3, 11)] {
+            heap.stack_frame_by_id.make_mut().insert(
+                stack_frame_id,
+                StackFrameData {
+                    stack_frame_id,
+                    method_name_id,
+                    method_signature_id: 12,
+                    source_file_name_id: 0,
+                    class_serial_number: 102,
+                    line_number: 0,
+                },
+            );
+        }
+
+        let report = metaspace_report(&heap);
+        let loaders: Vec<(&str, u64, u64, u64, u64)> = report
+            .class_loaders
+            .iter()
+            .map(|l| {
+                (
+                    l.class_loader.as_str(),
+                    l.classes,
+                    l.fields,
+                    l.methods,
+                    l.estimated_bytes,
+                )
+            })
+            .collect();
+        let bootstrap_bytes = 2 * (KLASS_BYTES + SYMBOL_OVERHEAD_BYTES)
+            + ("java/lang/Object".len() + "com/acme/PluginLoader".len()) as u64;
+        let plugin_bytes = KLASS_BYTES
+            + 2 * FIELD_BYTES
+            + 2 * METHOD_BYTES
+            + "com/acme/Plugin".len() as u64
+            + SYMBOL_OVERHEAD_BYTES;
+        assert_eq!(
+            loaders,
+            vec![
+                ("bootstrap", 2, 0, 0, bootstrap_bytes),
+                ("com.acme.PluginLoader 0x1", 1, 2, 2, plugin_bytes),
+            ]
+        );
+        assert_eq!(report.estimated_bytes, bootstrap_bytes + plugin_bytes);
+        assert!(report.render(1).contains("for 2 class loaders"));
+        assert!(!report.render(1).contains("PluginLoader"));
+    }
+}
@@ -0,0 +1,264 @@
+use crate::report::RenderOptions;
+use crate::utils::{is_pretty_bytes_size, paint_size};
+use crate::Heap;
+
+pub mod allocation_age;
+pub mod allocation_sites;
+pub mod baseline;
+#[cfg(feature = "graph")]
+pub mod caches;
+#[cfg(feature = "graph")]
+pub mod class_loaders;
+pub mod class_names;
+#[cfg(feature = "graph")]
+pub mod connections;
+#[cfg(feature = "graph")]
+pub mod cycles;
+pub mod duplicate_arrays;
+#[cfg(feature = "graph")]
+pub mod exceptions;
+pub mod explain_size;
+pub mod generated_classes;
+#[cfg(feature = "graph")]
+pub mod graph_metrics;
+pub mod heaviest_fields;
+pub mod histogram;
+#[cfg(feature = "graph")]
+pub mod leak_suspects;
+pub mod locks;
+pub mod map_collisions;
+pub mod metaspace;
+pub mod netty;
+pub mod referencing_fields;
+#[cfg(feature = "graph")]
+pub mod root_retention;
+pub mod sparse_arrays;
+#[cfg(feature = "graph")]
+pub mod static_fields;
+pub mod strings;
+pub mod structural_duplicates;
+pub mod value_types;
+pub mod what_if;
+
+// smallest reference cycle reported by default, kept without the `graph` feature for the arguments
+pub const DEFAULT_CYCLE_MIN_SIZE: usize = 10;
+
+// Render the reference chain from a GC root (first element) to an object, one line per object
+pub fn render_reference_chain(heap: &Heap, path: &[u64]) -> Vec<String> {
+    path.iter()
+        .enumerate()
+        .map(|(index, object_id)| {
+            let description = format!("0x{:x} {}", object_id, heap.object_class_name(*object_id));
+            if index == 0 {
+                format!("  GC root {}", description)
+            } else {
+                let label = heap
+                    .reference_label(path[index - 1], *object_id)
+                    .unwrap_or_else(|| "?".to_string());
+                format!("  {}-> {} {}", " ".repeat(index), label, description)
+            }
+        })
+        .collect()
+}
+
+// Describes a class loader object id, the bootstrap loader is the null id
+pub fn class_loader_label(heap: &Heap, class_loader_object_id: u64) -> String {
+    if class_loader_object_id == 0 {
+        "bootstrap".to_string()
+    } else {
+        format!(
+            "{} 0x{:x}",
+            heap.object_class_name(class_loader_object_id),
+            class_loader_object_id
+        )
+    }
+}
+
+// line width used when truncating names without an explicit width
+const DEFAULT_MAX_WIDTH: usize = 120;
+// the last column is never truncated below this width
+const MIN_LAST_COLUMN_WIDTH: usize = 16;
+
+/// Layout of the tables rendered by the reports.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct TableOptions {
+    // width the names are truncated to, only with `truncate_names`
+    pub max_width: Option<usize>,
+    // replace non ASCII characters, useful when the output is not read as UTF-8
+    pub ascii_only: bool,
+    // shorten the last column (usually class names) with an ellipsis to fit the width
+    pub truncate_names: bool,
+}
+
+impl TableOptions {
+    fn ellipsis(&self) -> &'static str {
+        if self.ascii_only {
+            "..."
+        } else {
+            "…"
+        }
+    }
+
+    fn sanitize(&self, cell: &str) -> String {
+        if self.ascii_only {
+            cell.chars()
+                .map(|c| if c.is_ascii() { c } else { '?' })
+                .collect()
+        } else {
+            cell.to_string()
+        }
+    }
+
+    // keep the end of the cell which holds the simple class name
+    fn truncate(&self, cell: String, width: usize) -> String {
+        let count = cell.chars().count();
+        if count <= width {
+            return cell;
+        }
+        let ellipsis = self.ellipsis();
+        let kept = width.saturating_sub(ellipsis.chars().count());
+        let tail: String = cell.chars().skip(count - kept).collect();
+        format!("{}{}", ellipsis, tail)
+    }
+}
+
+// Render table from rows of pre-formatted cells, all columns are right aligned except the last one
+pub fn render_table(headers: &[&str], rows: &[Vec<String>]) -> String {
+    render_table_with(&RenderOptions::default(), headers, rows)
+}
+
+pub fn render_table_with(
+    render_options: &RenderOptions,
+    headers: &[&str],
+    rows: &[Vec<String>],
+) -> String {
+    let options = &render_options.table;
+    let last = headers.len() - 1;
+    let mut rows: Vec<Vec<String>> = rows
+        .iter()
+        .map(|row| row.iter().map(|cell| options.sanitize(cell)).collect())
+        .collect();
+    if options.truncate_names {
+        let max_width = options.max_width.unwrap_or(DEFAULT_MAX_WIDTH);
+        let leading: usize = (0..last)
+            .map(|column| {
+                rows.iter()
+                    .map(|row| row[column].chars().count())
+                    .chain(std::iter::once(headers[column].chars().count()))
+                    .max()
+                    .unwrap_or_default()
+                    + " | ".len()
+            })
+            .sum();
+        let last_width = max_width.saturating_sub(leading).max(MIN_LAST_COLUMN_WIDTH);
+        for row in rows.iter_mut() {
+            let cell = std::mem::take(&mut row[last]);
+            row[last] = options.truncate(cell, last_width);
+        }
+    }
+
+    let widths: Vec<usize> = headers
+        .iter()
+        .enumerate()
+        .map(|(column, header)| {
+            rows.iter()
+                .map(|row| row[column].chars().count())
+                .chain(std::iter::once(header.chars().count()))
+                .max()
+                .unwrap_or_default()
+        })
+        .collect();
+
+    let format_row = |cells: Vec<&str>, pad_last: bool| -> String {
+        let mut line = cells
+            .iter()
+            .enumerate()
+            .map(|(column, cell)| {
+                if column == last && !pad_last {
+                    cell.to_string()
+                } else if column == last {
+                    format!("{:<width$}", cell, width = widths[column])
+                } else if is_pretty_bytes_size(cell) {
+                    paint_size(
+                        &format!("{:>width$}", cell, width = widths[column]),
+                        render_options.colors,
+                    )
+                } else {
+                    format!("{:>width$}", cell, width = widths[column])
+                }
+            })
+            .collect::<Vec<_>>()
+            .join(" | ");
+        line.push('\n');
+        line
+    };
+
+    let mut table = String::new();
+    let header = format_row(headers.to_vec(), true);
+    table.push_str(&header);
+    table.push_str(&"-".repeat(header.chars().count() - 1));
+    table.push('\n');
+    for row in &rows {
+        table.push_str(&format_row(row.iter().map(|c| c.as_str()).collect(), false));
+    }
+    table
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rows() -> Vec<Vec<String>> {
+        vec![
+            vec![
+                "1".to_string(),
+                "java.util.concurrent.ConcurrentHashMap$Node".to_string(),
+            ],
+            vec!["22".to_string(), "café.Crème".to_string()],
+        ]
+    }
+
+    #[test]
+    fn render_table_default_layout() {
+        let table = render_table_with(&RenderOptions::default(), &["Count", "Class name"], &rows());
+        let expected = "\
+Count | Class name                                 
+---------------------------------------------------
+    1 | java.util.concurrent.ConcurrentHashMap$Node
+   22 | café.Crème
+";
+        assert_eq!(table, expected);
+    }
+
+    #[test]
+    fn render_table_ascii_truncated() {
+        let options = RenderOptions {
+            table: TableOptions {
+                max_width: Some(24),
+                ascii_only: true,
+                truncate_names: true,
+            },
+            colors: false,
+        };
+        let table = render_table_with(&options, &["Count", "Class name"], &rows());
+        let expected = "\
+Count | Class name      
+------------------------
+    1 | ...tHashMap$Node
+   22 | caf?.Cr?me
+";
+        assert_eq!(table, expected);
+    }
+
+    #[test]
+    fn render_table_colored_sizes() {
+        let options = RenderOptions {
+            colors: true,
+            ..RenderOptions::default()
+        };
+        let rows = vec![vec!["1.00KiB".to_string(), "byte[]".to_string()]];
+        let table = render_table_with(&options, &["Total size", "Class name"], &rows);
+        assert!(table.ends_with("\x1b[36m   1.00KiB\x1b[0m | byte[]\n"));
+        assert!(!render_table(&["Total size", "Class name"], &rows).contains('\x1b'));
+    }
+}
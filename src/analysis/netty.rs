@@ -0,0 +1,254 @@
+use std::collections::HashMap;
+
+use serde::Serialize;
+
+use crate::parser::gc_record::FieldValue;
+use crate::report::{render_blocks, Block};
+use crate::utils::{pretty_bytes_size, pretty_count};
+use crate::Heap;
+
+const POOL_CHUNK_CLASS: &str = "io.netty.buffer.PoolChunk";
+const POOLED_BYTE_BUF_CLASS: &str = "io.netty.buffer.PooledByteBuf";
+const DIRECT_ARENA_CLASS: &str = "io.netty.buffer.PoolArena$DirectArena";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum ArenaKind {
+    // off-heap memory, only the bookkeeping objects are visible in the dump
+    Direct,
+    Heap,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ArenaSummary {
+    pub arena_object_id: u64,
+    pub kind: ArenaKind,
+    pub chunks: u64,
+    // chunks allocated for a single huge buffer outside of the pool
+    pub unpooled_chunks: u64,
+    pub chunk_bytes: u64,
+    pub used_bytes: u64,
+    pub buffers: u64,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct NettyReport {
+    pub arenas: Vec<ArenaSummary>,
+}
+
+/// Breaks down the memory reserved by Netty's pooled allocator per arena using the `PoolChunk` bookkeeping.
+pub fn netty_report(heap: &Heap) -> NettyReport {
+    let mut arenas: HashMap<u64, ArenaSummary> = HashMap::new();
+    let int_field = |instance, name| match heap.field_value(instance, name) {
+        Some(FieldValue::Int(value)) => (*value).max(0) as u64,
+        _ => 0,
+    };
+
+    for chunk in heap.instances_of(POOL_CHUNK_CLASS) {
+        let arena = match heap.field_instance(chunk, "arena") {
+            Some(arena) => arena,
+            None => continue,
+        };
+        let summary = arenas
+            .entry(arena.object_id)
+            .or_insert_with(|| ArenaSummary {
+                arena_object_id: arena.object_id,
+                kind: if heap.is_subclass_of(arena.class_object_id, DIRECT_ARENA_CLASS) {
+                    ArenaKind::Direct
+                } else {
+                    ArenaKind::Heap
+                },
+                chunks: 0,
+                unpooled_chunks: 0,
+                chunk_bytes: 0,
+                used_bytes: 0,
+                buffers: 0,
+            });
+        let chunk_size = int_field(chunk, "chunkSize");
+        let free_bytes = int_field(chunk, "freeBytes");
+        summary.chunks += 1;
+        if matches!(
+            heap.field_value(chunk, "unpooled"),
+            Some(FieldValue::Bool(true))
+        ) {
+            summary.unpooled_chunks += 1;
+        }
+        summary.chunk_bytes += chunk_size;
+        summary.used_bytes += chunk_size.saturating_sub(free_bytes);
+    }
+
+    for buffer in heap.instances_of(POOLED_BYTE_BUF_CLASS) {
+        let arena_id = heap.field_instance(buffer, "chunk").and_then(|chunk| {
+            match heap.field_value(chunk, "arena") {
+                Some(FieldValue::Object(arena_id)) => Some(*arena_id),
+                _ => None,
+            }
+        });
+        if let Some(summary) = arena_id.and_then(|id| arenas.get_mut(&id)) {
+            summary.buffers += 1;
+        }
+    }
+
+    let mut arenas: Vec<ArenaSummary> = arenas.into_values().collect();
+    arenas.sort_by(|a, b| {
+        b.chunk_bytes
+            .cmp(&a.chunk_bytes)
+            .then_with(|| a.arena_object_id.cmp(&b.arena_object_id))
+    });
+    NettyReport { arenas }
+}
+
+impl NettyReport {
+    pub fn total_bytes(&self, kind: ArenaKind) -> u64 {
+        self.arenas
+            .iter()
+            .filter(|a| a.kind == kind)
+            .map(|a| a.chunk_bytes)
+            .sum()
+    }
+
+    pub fn blocks(&self) -> Vec<Block> {
+        let mut blocks = vec![Block::Heading(format!(
+            "Found {} Netty pool arenas holding {} of direct memory and {} of heap memory:",
+            self.arenas.len(),
+            pretty_bytes_size(self.total_bytes(ArenaKind::Direct)),
+            pretty_bytes_size(self.total_bytes(ArenaKind::Heap)),
+        ))];
+        if self.arenas.is_empty() {
+            return blocks;
+        }
+        let rows: Vec<Vec<String>> = self
+            .arenas
+            .iter()
+            .map(|a| {
+                vec![
+                    pretty_bytes_size(a.chunk_bytes),
+                    pretty_bytes_size(a.used_bytes),
+                    pretty_count(a.chunks),
+                    pretty_count(a.unpooled_chunks),
+                    pretty_count(a.buffers),
+                    format!("{:?}", a.kind),
+                    format!("0x{:x}", a.arena_object_id),
+                ]
+            })
+            .collect();
+        blocks.push(Block::table(
+            &[
+                "Chunk size",
+                "Used",
+                "Chunks",
+                "Unpooled",
+                "Buffers",
+                "Kind",
+                "Arena",
+            ],
+            rows,
+        ));
+        blocks
+    }
+
+    pub fn render(&self) -> String {
+        render_blocks(&self.blocks())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::{add_class, add_instance, reference, single};
+
+    // field name ids
+    const ARENA: u64 = 1;
+    const CHUNK_SIZE: u64 = 2;
+    const FREE_BYTES: u64 = 3;
+    const UNPOOLED: u64 = 4;
+    const CHUNK: u64 = 5;
+
+    const MIB: i32 = 1024 * 1024;
+
+    #[test]
+    fn arenas_from_their_chunks() {
+        let mut heap = Heap::default();
+        heap.counter.id_size = 8;
+        for (name_id, name) in [
+            (ARENA, "arena"),
+            (CHUNK_SIZE, "chunkSize"),
+            (FREE_BYTES, "freeBytes"),
+            (UNPOOLED, "unpooled"),
+            (CHUNK, "chunk"),
+        ] {
+            heap.utf8_strings.make_mut().insert(name_id, name.into());
+        }
+        for (class_object_id, super_class_object_id, class_name) in [
+            (100, 0, "io/netty/buffer/PoolArena"),
+            (101, 100, "io/netty/buffer/PoolArena$DirectArena"),
+            (102, 100, "io/netty/buffer/PoolArena$HeapArena"),
+            (103, 0, "io/netty/buffer/PoolChunk"),
+            (104, 0, "io/netty/buffer/PooledByteBuf"),
+            (105, 104, "io/netty/buffer/PooledUnsafeDirectByteBuf"),
+        ] {
+            add_class(
+                &mut heap,
+                class_object_id,
+                super_class_object_id,
+                class_name,
+            );
+        }
+        add_instance(&mut heap, 1, 101, Vec::new());
+        add_instance(&mut heap, 2, 102, Vec::new());
+        let chunk = |arena_id: u64, chunk_size: i32, free_bytes: i32, unpooled: bool| {
+            vec![
+                reference(ARENA, arena_id),
+                single(CHUNK_SIZE, FieldValue::Int(chunk_size)),
+                single(FREE_BYTES, FieldValue::Int(free_bytes)),
+                single(UNPOOLED, FieldValue::Bool(unpooled)),
+            ]
+        };
+        // a pooled and a huge unpooled direct chunk, an unused heap chunk and a chunk without arena
+        add_instance(&mut heap, 10, 103, chunk(1, 16 * MIB, 4 * MIB, false));
+        add_instance(&mut heap, 11, 103, chunk(1, MIB, 0, true));
+        add_instance(&mut heap, 12, 103, chunk(2, 4 * MIB, 4 * MIB, false));
+        add_instance(&mut heap, 13, 103, chunk(0, MIB, 0, false));
+        for (buffer_id, class_object_id, chunk_id) in
+            [(20, 105, 10), (21, 105, 11), (22, 104, 12), (23, 104, 0)]
+        {
+            add_instance(
+                &mut heap,
+                buffer_id,
+                class_object_id,
+                vec![reference(CHUNK, chunk_id)],
+            );
+        }
+
+        let report = netty_report(&heap);
+        assert_eq!(report.arenas.len(), 2);
+        let direct = &report.arenas[0];
+        assert_eq!(
+            (direct.arena_object_id, direct.kind),
+            (1, ArenaKind::Direct)
+        );
+        assert_eq!((direct.chunks, direct.unpooled_chunks), (2, 1));
+        assert_eq!(direct.chunk_bytes, 17 * MIB as u64);
+        assert_eq!(direct.used_bytes, 13 * MIB as u64);
+        assert_eq!(direct.buffers, 2);
+        let heap_arena = &report.arenas[1];
+        assert_eq!(
+            (heap_arena.arena_object_id, heap_arena.kind),
+            (2, ArenaKind::Heap)
+        );
+        assert_eq!(
+            (
+                heap_arena.chunk_bytes,
+                heap_arena.used_bytes,
+                heap_arena.buffers
+            ),
+            (4 * MIB as u64, 0, 1)
+        );
+        assert_eq!(report.total_bytes(ArenaKind::Direct), 17 * MIB as u64);
+
+        assert!(report.render().contains(&format!(
+            "Found 2 Netty pool arenas holding {} of direct memory and {} of heap memory",
+            pretty_bytes_size(17 * MIB as u64),
+            pretty_bytes_size(4 * MIB as u64)
+        )));
+    }
+}
@@ -0,0 +1,288 @@
+use std::collections::{HashMap, HashSet};
+
+use serde::Serialize;
+
+use crate::parser::gc_record::{FieldType, FieldValue, Values};
+use crate::report::{render_blocks, Block};
+use crate::utils::{pretty_bytes_size, pretty_count};
+use crate::Heap;
+
+// target class of the section rendered without an explicit one
+pub const DEFAULT_TARGET_CLASS: &str = "byte[]";
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ReferencingField {
+    pub class_name: String,
+    // `static <name>` for static fields and `[]` for object array elements
+    pub field_name: String,
+    pub references: u64,
+    // shallow size of the referenced target instances
+    pub referenced_bytes: u64,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ReferencingFieldsReport {
+    pub target_class: String,
+    pub target_instances: u64,
+    pub total_references: u64,
+    pub fields: Vec<ReferencingField>,
+}
+
+/// Aggregates the references to instances of `target_class` (e.g. `byte[]` or `java.lang.String`)
+/// per holding (class, field) pair, keeping the `top` pairs with the most references.
+pub fn referencing_fields_report(
+    heap: &Heap,
+    target_class: &str,
+    top: usize,
+) -> ReferencingFieldsReport {
+    let targets: HashSet<u64> = heap
+        .instances_pool
+        .values()
+        .filter(|instance| match instance.array_element_type() {
+            Some(_) => heap.instance_class_name(instance) == target_class,
+            None => heap.is_subclass_of(instance.class_object_id, target_class),
+        })
+        .map(|instance| instance.object_id)
+        .collect();
+    let target_size = |object_id: &u64| {
+        heap.instances_pool
+            .get(object_id)
+            .map(|instance| heap.shallow_size(instance))
+            .unwrap_or_default()
+    };
+    let field_name = |name_id: &u64| {
+        heap.utf8_strings
+            .get(name_id)
+            .map(|name| name.to_string())
+            .unwrap_or_else(|| "unknown field".to_string())
+    };
+
+    // (holder class object id, field name) -> (references, referenced bytes)
+    let mut by_field: HashMap<(u64, String), (u64, u64)> = HashMap::new();
+    let mut record = |class_object_id: u64, name: String, target_id: &u64| {
+        let entry = by_field.entry((class_object_id, name)).or_default();
+        entry.0 += 1;
+        entry.1 += target_size(target_id);
+    };
+
+    // fields are recorded under their declaring class, resolved once per instance class
+    let mut declaring_classes: HashMap<u64, Vec<u64>> = HashMap::new();
+    for instance in heap.instances_pool.values() {
+        match instance.array_element_type() {
+            Some(FieldType::Object) => instance
+                .object_array()
+                .unwrap_or_default()
+                .iter()
+                .filter(|id| targets.contains(id))
+                .for_each(|id| record(instance.class_object_id, "[]".to_string(), id)),
+            Some(_) => (),
+            None => {
+                let declaring_classes = declaring_classes
+                    .entry(instance.class_object_id)
+                    .or_insert_with(|| heap.declaring_classes(instance.class_object_id));
+                instance
+                    .all_fields()
+                    .enumerate()
+                    .for_each(|(slot, (name_id, value))| match value {
+                        Values::Single(FieldValue::Object(id)) if targets.contains(id) => {
+                            // fields past the known hierarchy stay under the instance class
+                            let class_object_id = declaring_classes
+                                .get(slot)
+                                .copied()
+                                .unwrap_or(instance.class_object_id);
+                            record(class_object_id, field_name(name_id), id)
+                        }
+                        _ => (),
+                    })
+            }
+        }
+    }
+    for class in heap.classes_dump.values() {
+        class
+            .static_fields
+            .iter()
+            .for_each(|(field, value)| match value {
+                FieldValue::Object(id) if targets.contains(id) => record(
+                    class.class_object_id,
+                    format!("static {}", field_name(&field.name_id)),
+                    id,
+                ),
+                _ => (),
+            });
+    }
+
+    let mut fields: Vec<ReferencingField> = by_field
+        .into_iter()
+        .map(
+            |((class_object_id, field_name), (references, referenced_bytes))| ReferencingField {
+                class_name: heap
+                    .class_name(class_object_id)
+                    .unwrap_or_else(|| "unknown class".to_string()),
+                field_name,
+                references,
+                referenced_bytes,
+            },
+        )
+        .collect();
+    let total_references = fields.iter().map(|f| f.references).sum();
+    fields.sort_by(|a, b| {
+        b.references
+            .cmp(&a.references)
+            .then_with(|| a.class_name.cmp(&b.class_name))
+            .then_with(|| a.field_name.cmp(&b.field_name))
+    });
+    fields.truncate(top);
+    ReferencingFieldsReport {
+        target_class: target_class.to_string(),
+        target_instances: targets.len() as u64,
+        total_references,
+        fields,
+    }
+}
+
+impl ReferencingFieldsReport {
+    pub fn blocks(&self) -> Vec<Block> {
+        let mut blocks = vec![Block::Heading(format!(
+            "Found {} references to {} instances of {}:",
+            self.total_references, self.target_instances, self.target_class
+        ))];
+        if self.fields.is_empty() {
+            return blocks;
+        }
+        let rows: Vec<Vec<String>> = self
+            .fields
+            .iter()
+            .map(|f| {
+                vec![
+                    pretty_count(f.references),
+                    pretty_bytes_size(f.referenced_bytes),
+                    format!("{}.{}", f.class_name, f.field_name),
+                ]
+            })
+            .collect();
+        blocks.push(Block::table(
+            &["References", "Referenced size", "Field"],
+            rows,
+        ));
+        blocks
+    }
+
+    pub fn render(&self) -> String {
+        render_blocks(&self.blocks())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::gc_record::{ArrayValue, FieldInfo};
+    use crate::result_recorder::Instance;
+    use crate::tests::{add_class, add_instance, reference};
+    use std::sync::Arc;
+
+    const NAME: u64 = 1;
+    const DEFAULT: u64 = 2;
+
+    #[test]
+    fn references_per_holding_field() {
+        let mut heap = Heap::default();
+        heap.counter.id_size = 8;
+        heap.utf8_strings.make_mut().insert(NAME, "name".into());
+        heap.utf8_strings
+            .make_mut()
+            .insert(DEFAULT, "DEFAULT".into());
+        add_class(&mut heap, 100, 0, "java/lang/String");
+        add_class(&mut heap, 101, 0, "com/acme/User");
+        add_class(&mut heap, 102, 0, "[Ljava/lang/String;");
+        add_class(&mut heap, 103, 0, "com/acme/Config")
+            .static_fields
+            .push((
+                FieldInfo {
+                    name_id: DEFAULT,
+                    field_type: FieldType::Object,
+                },
+                FieldValue::Object(10),
+            ));
+        for string_id in [10, 11, 12] {
+            add_instance(&mut heap, string_id, 100, Vec::new());
+        }
+        add_instance(&mut heap, 1, 101, vec![reference(NAME, 10)]);
+        add_instance(&mut heap, 2, 101, vec![reference(NAME, 11)]);
+        add_instance(
+            &mut heap,
+            3,
+            102,
+            vec![(0, Values::Array(ArrayValue::Object(vec![10, 12, 0])))],
+        );
+        let string_size = heap.shallow_size(&heap.instances_pool[&10]);
+
+        let report = referencing_fields_report(&heap, "java.lang.String", 10);
+        assert_eq!(report.target_instances, 3);
+        assert_eq!(report.total_references, 5);
+        let rows: Vec<(&str, &str, u64, u64)> = report
+            .fields
+            .iter()
+            .map(|f| {
+                (
+                    f.class_name.as_str(),
+                    f.field_name.as_str(),
+                    f.references,
+                    f.referenced_bytes,
+                )
+            })
+            .collect();
+        assert_eq!(
+            rows,
+            vec![
+                ("[Ljava.lang.String;", "[]", 2, 2 * string_size),
+                ("com.acme.User", "name", 2, 2 * string_size),
+                ("com.acme.Config", "static DEFAULT", 1, string_size),
+            ]
+        );
+        assert!(report.render().contains("com.acme.Config.static DEFAULT"));
+
+        assert_eq!(
+            referencing_fields_report(&heap, "java.lang.String", 1)
+                .fields
+                .len(),
+            1
+        );
+    }
+
+    #[test]
+    fn super_class_fields_under_their_declaring_class() {
+        let mut heap = Heap::default();
+        heap.counter.id_size = 8;
+        heap.utf8_strings.make_mut().insert(NAME, "name".into());
+        add_class(&mut heap, 100, 0, "java/lang/String");
+        add_class(&mut heap, 101, 0, "com/acme/Named")
+            .instance_fields
+            .push(FieldInfo {
+                name_id: NAME,
+                field_type: FieldType::Object,
+            });
+        add_class(&mut heap, 102, 101, "com/acme/Admin");
+        add_instance(&mut heap, 10, 100, Vec::new());
+        add_instance(&mut heap, 1, 101, vec![reference(NAME, 10)]);
+        heap.instances_pool.make_mut().insert(
+            2,
+            Arc::new(Instance {
+                object_id: 2,
+                stack_trace_serial_number: 0,
+                class_object_id: 102,
+                data_size: 8,
+                fields: Vec::new(),
+                super_fields: vec![reference(NAME, 10)],
+            }),
+        );
+
+        let report = referencing_fields_report(&heap, "java.lang.String", 10);
+        assert_eq!(report.fields.len(), 1);
+        let field = &report.fields[0];
+        assert_eq!(
+            (field.class_name.as_str(), field.field_name.as_str()),
+            ("com.acme.Named", "name")
+        );
+        assert_eq!(field.references, 2);
+    }
+}
@@ -0,0 +1,215 @@
+use std::collections::HashMap;
+
+use serde::Serialize;
+
+use crate::graph::HeapGraph;
+use crate::report::{render_blocks, Block};
+use crate::utils::{pretty_bytes_size, pretty_count};
+use crate::{GcRootKind, Heap};
+
+#[derive(Debug, Clone, Serialize)]
+pub struct RootKindSummary {
+    pub kind: GcRootKind,
+    pub roots: u64,
+    pub retained_size: u64,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct RootRetentionReport {
+    pub kinds: Vec<RootKindSummary>,
+    // objects reachable from roots of different kinds
+    pub shared_size: u64,
+    pub unreachable_size: u64,
+}
+
+const ROOT_KINDS: [GcRootKind; 9] = [
+    GcRootKind::Unknown,
+    GcRootKind::ThreadObject,
+    GcRootKind::JniGlobal,
+    GcRootKind::JniLocal,
+    GcRootKind::JavaFrame,
+    GcRootKind::NativeStack,
+    GcRootKind::StickyClass,
+    GcRootKind::ThreadBlock,
+    GcRootKind::MonitorUsed,
+];
+
+/// Splits the heap by the kind of GC root retaining it.
+///
+/// Objects reachable from roots of several kinds are reported as shared, an object registered
+/// as several kinds of root is attributed to the first kind reported by `Heap::gc_roots`.
+pub fn root_retention_report(heap: &Heap, graph: &HeapGraph) -> RootRetentionReport {
+    let mut kind_by_root: HashMap<u64, GcRootKind> = HashMap::new();
+    for root in heap.gc_roots() {
+        kind_by_root.entry(root.object_id).or_insert(root.kind);
+    }
+
+    let mut by_kind: HashMap<GcRootKind, RootKindSummary> = HashMap::new();
+    for kind in kind_by_root.values() {
+        by_kind
+            .entry(*kind)
+            .or_insert(RootKindSummary {
+                kind: *kind,
+                roots: 0,
+                retained_size: 0,
+            })
+            .roots += 1;
+    }
+    let kinds_by_object = reaching_root_kinds(graph, &kind_by_root);
+
+    let mut report = RootRetentionReport::default();
+    // the objects only dominated by the virtual root partition the heap
+    let top_level_objects = heap
+        .classes_dump
+        .keys()
+        .chain(heap.instances_pool.keys())
+        .filter(|object_id| graph.immediate_dominator(**object_id).is_none());
+    for object_id in top_level_objects {
+        let retained_size = graph.retained_size(*object_id).unwrap_or_default();
+        let kind = kind_by_root.get(object_id).copied().or_else(|| {
+            // reachable from roots of a single kind only
+            let mask = kinds_by_object.get(object_id).copied().unwrap_or_default();
+            (mask.count_ones() == 1).then(|| ROOT_KINDS[mask.trailing_zeros() as usize])
+        });
+        match kind {
+            _ if !graph.is_reachable(*object_id) => report.unreachable_size += retained_size,
+            Some(kind) => {
+                by_kind
+                    .get_mut(&kind)
+                    .expect("kind registered for every root")
+                    .retained_size += retained_size
+            }
+            None => report.shared_size += retained_size,
+        }
+    }
+
+    let mut kinds: Vec<RootKindSummary> = by_kind.into_values().collect();
+    kinds.sort_by(|a, b| {
+        b.retained_size
+            .cmp(&a.retained_size)
+            .then_with(|| format!("{:?}", a.kind).cmp(&format!("{:?}", b.kind)))
+    });
+    report.kinds = kinds;
+    report
+}
+
+// bit set of the root kinds (indexed like `ROOT_KINDS`) from which each object is reachable
+fn reaching_root_kinds(
+    graph: &HeapGraph,
+    kind_by_root: &HashMap<u64, GcRootKind>,
+) -> HashMap<u64, u16> {
+    let mut masks: HashMap<u64, u16> = HashMap::new();
+    for (bit, kind) in ROOT_KINDS.iter().enumerate() {
+        let bit = 1 << bit;
+        let mut stack: Vec<u64> = kind_by_root
+            .iter()
+            .filter(|(_, root_kind)| *root_kind == kind)
+            .map(|(object_id, _)| *object_id)
+            .collect();
+        while let Some(object_id) = stack.pop() {
+            let mask = masks.entry(object_id).or_default();
+            if *mask & bit != 0 {
+                continue;
+            }
+            *mask |= bit;
+            stack.extend(graph.references(object_id));
+        }
+    }
+    masks
+}
+
+impl RootRetentionReport {
+    pub fn blocks(&self) -> Vec<Block> {
+        let mut blocks = vec![Block::Heading(format!(
+            "Retained size by GC root kind ({} kinds):",
+            self.kinds.len()
+        ))];
+        let mut rows: Vec<Vec<String>> = self
+            .kinds
+            .iter()
+            .map(|k| {
+                vec![
+                    pretty_bytes_size(k.retained_size),
+                    pretty_count(k.roots),
+                    format!("{:?}", k.kind),
+                ]
+            })
+            .collect();
+        rows.push(vec![
+            pretty_bytes_size(self.shared_size),
+            "-".to_string(),
+            "shared by several root kinds".to_string(),
+        ]);
+        rows.push(vec![
+            pretty_bytes_size(self.unreachable_size),
+            "-".to_string(),
+            "unreachable".to_string(),
+        ]);
+        blocks.push(Block::table(&["Retained", "Roots", "Root kind"], rows));
+        blocks
+    }
+
+    pub fn render(&self) -> String {
+        render_blocks(&self.blocks())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::{add_class, add_instance, reference};
+
+    const NEXT: u64 = 1;
+    const PREVIOUS: u64 = 2;
+
+    #[test]
+    fn retained_size_by_root_kind() {
+        let mut heap = Heap::default();
+        heap.counter.id_size = 8;
+        heap.utf8_strings.make_mut().insert(NEXT, "next".into());
+        heap.utf8_strings
+            .make_mut()
+            .insert(PREVIOUS, "previous".into());
+        add_class(&mut heap, 100, 0, "com/acme/Node");
+        add_class(&mut heap, 101, 0, "com/acme/Orphan");
+        // both roots reach node 2, node 1 is also registered as an unknown root
+        add_instance(
+            &mut heap,
+            1,
+            100,
+            vec![reference(NEXT, 2), reference(PREVIOUS, 0)],
+        );
+        add_instance(&mut heap, 2, 100, Vec::new());
+        add_instance(&mut heap, 3, 100, vec![reference(NEXT, 2)]);
+        add_instance(&mut heap, 4, 101, Vec::new());
+        heap.root_monitor_used.make_mut().push(1);
+        heap.root_unknown.make_mut().extend([1, 3]);
+        let graph = HeapGraph::new(&heap);
+        let retained = |ids: &[u64]| {
+            ids.iter()
+                .map(|id| graph.retained_size(*id).unwrap())
+                .sum::<u64>()
+        };
+
+        let report = root_retention_report(&heap, &graph);
+        let kinds: Vec<(GcRootKind, u64, u64)> = report
+            .kinds
+            .iter()
+            .map(|k| (k.kind, k.roots, k.retained_size))
+            .collect();
+        assert_eq!(
+            kinds,
+            vec![
+                (GcRootKind::MonitorUsed, 1, retained(&[1])),
+                (GcRootKind::Unknown, 1, retained(&[3])),
+            ]
+        );
+        // the node class is reached through the instances of both kinds
+        assert_eq!(report.shared_size, retained(&[2, 100]));
+        assert_eq!(report.unreachable_size, retained(&[4, 101]));
+
+        let rendered = report.render();
+        assert!(rendered.contains("(2 kinds)"));
+        assert!(rendered.contains("shared by several root kinds"));
+    }
+}
@@ -0,0 +1,229 @@
+use std::collections::{HashMap, HashSet};
+
+use serde::Serialize;
+
+use crate::analysis::class_names::pretty_class_name;
+use crate::parser::gc_record::{FieldValue, Values};
+use crate::report::{render_blocks, Block};
+use crate::utils::{pretty_bytes_size, pretty_count};
+use crate::Heap;
+
+// arrays at least half empty are reported by default
+pub const DEFAULT_MIN_NULL_RATIO: f64 = 0.5;
+
+/// Sparse object arrays sharing the field holding them, e.g. the `elementData` of `ArrayList`.
+#[derive(Debug, Clone, Serialize)]
+pub struct SparseArrayGroup {
+    // class of the object holding the arrays, the array class for arrays without a single referrer
+    pub holder_class: String,
+    pub field: Option<String>,
+    pub arrays: u64,
+    pub slots: u64,
+    pub null_slots: u64,
+    pub wasted_bytes: u64,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct SparseArrayReport {
+    pub min_null_ratio: f64,
+    pub sparse_arrays: u64,
+    pub null_slots: u64,
+    // the null slots as references in the target JVM
+    pub wasted_bytes: u64,
+    // sorted by wasted bytes
+    pub groups: Vec<SparseArrayGroup>,
+}
+
+/// Finds the object arrays whose ratio of null elements is at least `min_null_ratio` (between 0 and 1),
+/// grouped by the field holding them, keeping the `top` most wasteful fields.
+///
+/// Oversized backing arrays of collections (e.g. `ArrayList` or `HashMap` after a burst) show up by field.
+pub fn sparse_array_report(heap: &Heap, min_null_ratio: f64, top: usize) -> SparseArrayReport {
+    // sparse array id -> (slots, null slots)
+    let sparse: HashMap<u64, (u64, u64)> = heap
+        .instances_pool
+        .values()
+        .filter_map(|instance| {
+            let ids = instance.object_array()?;
+            if ids.is_empty() {
+                return None;
+            }
+            let nulls = ids.iter().filter(|id| **id == 0).count() as u64;
+            let slots = ids.len() as u64;
+            (nulls as f64 >= min_null_ratio * slots as f64)
+                .then_some((instance.object_id, (slots, nulls)))
+        })
+        .collect();
+
+    // referrer of each sparse array, `None` once referenced twice
+    let mut referrers: HashMap<u64, Option<u64>> = HashMap::new();
+    let mut seen: HashSet<u64> = HashSet::new();
+    let mut refer = |from: u64, to: u64| {
+        if sparse.contains_key(&to) {
+            let referrer = if seen.insert(to) { Some(from) } else { None };
+            referrers.insert(to, referrer);
+        }
+    };
+    for instance in heap.instances_pool.values() {
+        match instance.object_array() {
+            Some(ids) => ids.iter().for_each(|id| refer(instance.object_id, *id)),
+            None => instance.all_fields().for_each(|(_, value)| {
+                if let Values::Single(FieldValue::Object(id)) = value {
+                    refer(instance.object_id, *id);
+                }
+            }),
+        }
+    }
+    for class in heap.classes_dump.values() {
+        for (_, value) in &class.static_fields {
+            if let FieldValue::Object(id) = value {
+                refer(class.class_object_id, *id);
+            }
+        }
+    }
+
+    let reference_size = heap.reference_size();
+    let mut groups: HashMap<(String, Option<String>), SparseArrayGroup> = HashMap::new();
+    for (array_id, (slots, nulls)) in &sparse {
+        let holder = referrers.get(array_id).copied().flatten().map(|referrer| {
+            let field = heap
+                .reference_label(referrer, *array_id)
+                // the index within an array holding the array says nothing about the others
+                .filter(|label| !label.starts_with('['));
+            (heap.object_class_name(referrer), field)
+        });
+        let key = holder.unwrap_or_else(|| (heap.object_class_name(*array_id), None));
+        let group = groups
+            .entry(key)
+            .or_insert_with_key(|(holder_class, field)| SparseArrayGroup {
+                holder_class: holder_class.clone(),
+                field: field.clone(),
+                arrays: 0,
+                slots: 0,
+                null_slots: 0,
+                wasted_bytes: 0,
+            });
+        group.arrays += 1;
+        group.slots += slots;
+        group.null_slots += nulls;
+        group.wasted_bytes += nulls * reference_size;
+    }
+
+    let mut groups: Vec<SparseArrayGroup> = groups.into_values().collect();
+    let mut report = SparseArrayReport {
+        min_null_ratio,
+        sparse_arrays: sparse.len() as u64,
+        null_slots: groups.iter().map(|g| g.null_slots).sum(),
+        wasted_bytes: groups.iter().map(|g| g.wasted_bytes).sum(),
+        ..Default::default()
+    };
+    groups.sort_by(|a, b| {
+        b.wasted_bytes
+            .cmp(&a.wasted_bytes)
+            .then_with(|| a.holder_class.cmp(&b.holder_class))
+            .then_with(|| a.field.cmp(&b.field))
+    });
+    groups.truncate(top);
+    report.groups = groups;
+    report
+}
+
+impl SparseArrayReport {
+    pub fn blocks(&self) -> Vec<Block> {
+        let mut blocks = vec![Block::Heading(format!(
+            "Found {} object arrays with at least {:.0}% of null elements, {} null slots wasting {}:",
+            pretty_count(self.sparse_arrays),
+            self.min_null_ratio * 100.0,
+            pretty_count(self.null_slots),
+            pretty_bytes_size(self.wasted_bytes)
+        ))];
+        if self.groups.is_empty() {
+            return blocks;
+        }
+        let rows: Vec<Vec<String>> = self
+            .groups
+            .iter()
+            .map(|g| {
+                let holder = pretty_class_name(&g.holder_class);
+                vec![
+                    pretty_bytes_size(g.wasted_bytes),
+                    pretty_count(g.arrays),
+                    format!("{:.1}%", g.null_slots as f64 * 100.0 / g.slots as f64),
+                    match &g.field {
+                        Some(field) => format!("{}.{}", holder, field),
+                        None => holder,
+                    },
+                ]
+            })
+            .collect();
+        blocks.push(Block::table(
+            &["Wasted", "Arrays", "Null slots", "Held by"],
+            rows,
+        ));
+        blocks
+    }
+
+    pub fn render(&self) -> String {
+        render_blocks(&self.blocks())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::gc_record::ArrayValue;
+    use crate::parser::record::LoadClassData;
+    use crate::tests::{add_instance, reference};
+
+    #[test]
+    fn sparse_arrays_by_holding_field() {
+        let mut heap = Heap::default();
+        heap.counter.id_size = 8;
+        heap.utf8_strings
+            .make_mut()
+            .insert(1, "java/util/ArrayList".into());
+        heap.utf8_strings.make_mut().insert(2, "elementData".into());
+        heap.utf8_strings
+            .make_mut()
+            .insert(3, "[Ljava/lang/Object;".into());
+        for (serial_number, class_object_id, class_name_id) in [(1, 100, 1), (2, 200, 3)] {
+            heap.class_registry.make_mut().load(LoadClassData {
+                serial_number,
+                class_object_id,
+                stack_trace_serial_number: 0,
+                class_name_id,
+            });
+        }
+        // two lists holding a mostly empty array, a dense array held by nobody
+        for (list_id, array_id) in [(1, 11), (2, 12)] {
+            add_instance(&mut heap, list_id, 100, vec![reference(2, array_id)]);
+            let mut elements = vec![0; 10];
+            elements[0] = list_id;
+            add_instance(
+                &mut heap,
+                array_id,
+                200,
+                vec![(0, Values::Array(ArrayValue::Object(elements)))],
+            );
+        }
+        add_instance(
+            &mut heap,
+            13,
+            200,
+            vec![(0, Values::Array(ArrayValue::Object(vec![1, 2])))],
+        );
+
+        let report = sparse_array_report(&heap, 0.5, 10);
+        assert_eq!(report.sparse_arrays, 2);
+        assert_eq!(report.null_slots, 18);
+        assert_eq!(report.wasted_bytes, 18 * 8);
+        assert_eq!(report.groups.len(), 1);
+        let group = &report.groups[0];
+        assert_eq!(group.holder_class, "java.util.ArrayList");
+        assert_eq!(group.field.as_deref(), Some("elementData"));
+        assert_eq!((group.arrays, group.slots), (2, 20));
+        assert!(report
+            .render()
+            .contains("90.0% | java.util.ArrayList.elementData"));
+    }
+}
@@ -0,0 +1,199 @@
+use std::ops::Deref;
+
+use serde::Serialize;
+
+use crate::graph::HeapGraph;
+use crate::parser::gc_record::FieldValue;
+use crate::report::{render_blocks, Block};
+use crate::utils::{pretty_bytes_size, pretty_count};
+use crate::Heap;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct StaticFieldRetention {
+    pub class_object_id: u64,
+    pub class_name: String,
+    pub field_name: String,
+    // object referenced by the field
+    pub object_id: u64,
+    pub object_class_name: String,
+    pub retained_size: u64,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct StaticFieldReport {
+    pub sticky_classes: u64,
+    // fields whose value is also reachable without their class, nothing is attributed to them
+    pub shared_fields: u64,
+    // sorted by retained size
+    pub fields: Vec<StaticFieldRetention>,
+}
+
+/// Attributes the memory retained by the sticky class roots to their static fields, keeping the `top` fields.
+///
+/// A field is attributed the retained size of its value only if the value is dominated by the class,
+/// a value also reachable through other references is counted as shared.
+pub fn static_field_report(heap: &Heap, graph: &HeapGraph, top: usize) -> StaticFieldReport {
+    let mut sticky_classes = heap.root_sticky_class.to_vec();
+    sticky_classes.sort_unstable();
+    sticky_classes.dedup();
+
+    let mut report = StaticFieldReport {
+        sticky_classes: sticky_classes.len() as u64,
+        ..Default::default()
+    };
+    for class_object_id in sticky_classes {
+        let Some(class) = heap.classes_dump.get(&class_object_id) else {
+            continue;
+        };
+        let class_name = heap
+            .class_name(class_object_id)
+            .unwrap_or_else(|| "unknown class".to_string());
+        for (field, value) in &class.static_fields {
+            let object_id = match value {
+                FieldValue::Object(object_id) if *object_id != 0 => *object_id,
+                _ => continue,
+            };
+            if graph.immediate_dominator(object_id) != Some(class_object_id) {
+                report.shared_fields += 1;
+                continue;
+            }
+            report.fields.push(StaticFieldRetention {
+                class_object_id,
+                class_name: class_name.clone(),
+                field_name: heap.utf8_strings.get(&field.name_id).map_or_else(
+                    || format!("field#{}", field.name_id),
+                    |n| n.deref().to_string(),
+                ),
+                object_id,
+                object_class_name: heap.object_class_name(object_id),
+                retained_size: graph.retained_size(object_id).unwrap_or_default(),
+            });
+        }
+    }
+    report.fields.sort_by(|a, b| {
+        b.retained_size
+            .cmp(&a.retained_size)
+            .then_with(|| a.class_name.cmp(&b.class_name))
+            .then_with(|| a.field_name.cmp(&b.field_name))
+            .then_with(|| a.class_object_id.cmp(&b.class_object_id))
+    });
+    report.fields.truncate(top);
+    report
+}
+
+impl StaticFieldReport {
+    pub fn blocks(&self) -> Vec<Block> {
+        let mut blocks = vec![Block::Heading(format!(
+            "Top {} static fields by retained size ({} sticky classes, {} fields shared with other references):",
+            self.fields.len(),
+            pretty_count(self.sticky_classes),
+            pretty_count(self.shared_fields)
+        ))];
+        if self.fields.is_empty() {
+            return blocks;
+        }
+        let rows: Vec<Vec<String>> = self
+            .fields
+            .iter()
+            .map(|f| {
+                vec![
+                    pretty_bytes_size(f.retained_size),
+                    f.object_class_name.clone(),
+                    format!("{}.{}", f.class_name, f.field_name),
+                ]
+            })
+            .collect();
+        blocks.push(Block::table(&["Retained", "Value", "Static field"], rows));
+        blocks
+    }
+
+    pub fn render(&self) -> String {
+        render_blocks(&self.blocks())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::gc_record::{ClassDumpFields, FieldInfo, FieldType, Values};
+    use crate::parser::record::LoadClassData;
+    use crate::result_recorder::{Instance, RootJniGlobal};
+    use std::sync::Arc;
+
+    #[test]
+    fn attribute_dominated_static_fields() {
+        let mut heap = Heap::default();
+        heap.counter.id_size = 8;
+        for (name_id, name) in [
+            (100, "com/example/Registry"),
+            (200, "CACHE"),
+            (201, "SHARED"),
+        ] {
+            heap.utf8_strings.make_mut().insert(name_id, name.into());
+        }
+        heap.class_registry.make_mut().load(LoadClassData {
+            serial_number: 1,
+            class_object_id: 1000,
+            stack_trace_serial_number: 0,
+            class_name_id: 100,
+        });
+        let static_fields = [(200, 0x10), (201, 0x30)]
+            .into_iter()
+            .map(|(name_id, object_id)| {
+                (
+                    FieldInfo {
+                        name_id,
+                        field_type: FieldType::Object,
+                    },
+                    FieldValue::Object(object_id),
+                )
+            })
+            .collect();
+        heap.classes_dump.make_mut().insert(
+            1000,
+            ClassDumpFields::new(1000, 0, 0, 0, 0, Vec::new(), static_fields, Vec::new()),
+        );
+        // CACHE -> 0x10 -> 0x20, SHARED -> 0x30 <- 0x40 (JNI global)
+        for (object_id, references) in [
+            (0x10, vec![0x20]),
+            (0x20, vec![]),
+            (0x30, vec![]),
+            (0x40, vec![0x30]),
+        ] {
+            heap.instances_pool.make_mut().insert(
+                object_id,
+                Arc::new(Instance {
+                    object_id,
+                    stack_trace_serial_number: 0,
+                    class_object_id: 0,
+                    data_size: 8 * references.len() as u64,
+                    fields: references
+                        .iter()
+                        .map(|id| (0, Values::Single(FieldValue::Object(*id))))
+                        .collect(),
+                    super_fields: Vec::new(),
+                }),
+            );
+        }
+        heap.root_sticky_class.make_mut().push(1000);
+        heap.root_jni_global.make_mut().insert(
+            0x40,
+            RootJniGlobal {
+                object_id: 0x40,
+                jni_global_ref_id: 0,
+            },
+        );
+        let graph = HeapGraph::new(&heap);
+
+        let report = static_field_report(&heap, &graph, 10);
+        assert_eq!(report.sticky_classes, 1);
+        assert_eq!(report.shared_fields, 1);
+        assert_eq!(report.fields.len(), 1);
+        let field = &report.fields[0];
+        assert_eq!(field.class_name, "com.example.Registry");
+        assert_eq!(field.field_name, "CACHE");
+        assert_eq!(field.object_id, 0x10);
+        assert_eq!(field.retained_size, 24 + 16);
+        assert!(report.render().contains("com.example.Registry.CACHE"));
+    }
+}
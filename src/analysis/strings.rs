@@ -0,0 +1,208 @@
+use std::collections::HashMap;
+
+use serde::Serialize;
+
+use crate::report::{render_blocks, Block};
+use crate::utils::{pretty_bytes_size, pretty_count};
+use crate::Heap;
+
+const STRING_CLASS: &str = "java.lang.String";
+// longer values are truncated when rendered
+const MAX_RENDERED_VALUE_LEN: usize = 80;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct DuplicateString {
+    pub value: String,
+    // distinct backing arrays holding the same content
+    pub arrays: u64,
+    pub wasted_bytes: u64,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct StringTableReport {
+    pub strings: u64,
+    pub backing_arrays: u64,
+    pub backing_array_bytes: u64,
+    // backing arrays referenced by more than one `String` (e.g. substrings on JDK 6)
+    pub shared_backing_arrays: u64,
+    pub strings_sharing_arrays: u64,
+    pub unique_contents: u64,
+    pub duplicate_arrays: u64,
+    // memory reclaimable by `-XX:+UseStringDeduplication`
+    pub duplicate_bytes: u64,
+    pub top_duplicates: Vec<DuplicateString>,
+}
+
+/// Measures how `java.lang.String` instances share their backing arrays and how much
+/// memory identical contents waste, keeping the `top` most wasteful duplicated values.
+pub fn string_table_report(heap: &Heap, top: usize) -> StringTableReport {
+    let mut report = StringTableReport::default();
+
+    // backing array id -> (strings referencing it, first string)
+    let mut by_array: HashMap<u64, (u64, u64)> = HashMap::new();
+    for string in heap.instances_of(STRING_CLASS) {
+        report.strings += 1;
+        if let Some(array) = heap.field_instance(string, "value") {
+            by_array
+                .entry(array.object_id)
+                .or_insert((0, string.object_id))
+                .0 += 1;
+        }
+    }
+
+    // decoded content -> (arrays, total bytes, smallest array)
+    let mut by_content: HashMap<String, (u64, u64, u64)> = HashMap::new();
+    for (array_id, (strings, string_id)) in &by_array {
+        let size = heap
+            .instances_pool
+            .get(array_id)
+            .map(|array| heap.shallow_size(array))
+            .unwrap_or_default();
+        report.backing_arrays += 1;
+        report.backing_array_bytes += size;
+        if *strings > 1 {
+            report.shared_backing_arrays += 1;
+            report.strings_sharing_arrays += strings;
+        }
+        if let Some(value) = heap.string_value(*string_id) {
+            let entry = by_content.entry(value).or_insert((0, 0, u64::MAX));
+            entry.0 += 1;
+            entry.1 += size;
+            entry.2 = entry.2.min(size);
+        }
+    }
+
+    report.unique_contents = by_content.len() as u64;
+    let mut duplicates: Vec<DuplicateString> = by_content
+        .into_iter()
+        .filter(|(_, (arrays, _, _))| *arrays > 1)
+        .map(|(value, (arrays, total, smallest))| DuplicateString {
+            value,
+            arrays,
+            wasted_bytes: total - smallest,
+        })
+        .collect();
+    report.duplicate_arrays = duplicates.iter().map(|d| d.arrays - 1).sum();
+    report.duplicate_bytes = duplicates.iter().map(|d| d.wasted_bytes).sum();
+    duplicates.sort_by(|a, b| {
+        b.wasted_bytes
+            .cmp(&a.wasted_bytes)
+            .then_with(|| a.value.cmp(&b.value))
+    });
+    duplicates.truncate(top);
+    report.top_duplicates = duplicates;
+    report
+}
+
+impl StringTableReport {
+    pub fn blocks(&self) -> Vec<Block> {
+        let mut blocks = vec![
+            Block::Heading(format!(
+                "Found {} strings backed by {} arrays ({}):",
+                pretty_count(self.strings),
+                pretty_count(self.backing_arrays),
+                pretty_bytes_size(self.backing_array_bytes)
+            )),
+            Block::Text(format!(
+                "Shared backing arrays: {} (used by {} strings)\nUnique contents: {}\nDuplicate arrays: {} ({} reclaimable with -XX:+UseStringDeduplication)",
+                self.shared_backing_arrays,
+                self.strings_sharing_arrays,
+                self.unique_contents,
+                self.duplicate_arrays,
+                pretty_bytes_size(self.duplicate_bytes)
+            )),
+        ];
+        if self.top_duplicates.is_empty() {
+            return blocks;
+        }
+        blocks.push(Block::Heading(format!(
+            "Top {} duplicated strings:",
+            self.top_duplicates.len()
+        )));
+        let rows = self
+            .top_duplicates
+            .iter()
+            .map(|d| {
+                vec![
+                    pretty_bytes_size(d.wasted_bytes),
+                    pretty_count(d.arrays),
+                    render_value(&d.value),
+                ]
+            })
+            .collect();
+        blocks.push(Block::table(&["Wasted", "Copies", "Value"], rows));
+        blocks
+    }
+
+    pub fn render(&self) -> String {
+        render_blocks(&self.blocks())
+    }
+}
+
+fn render_value(value: &str) -> String {
+    let escaped: String = value.escape_debug().collect();
+    if escaped.chars().count() > MAX_RENDERED_VALUE_LEN {
+        let truncated: String = escaped.chars().take(MAX_RENDERED_VALUE_LEN).collect();
+        format!("\"{}...\"", truncated)
+    } else {
+        format!("\"{}\"", escaped)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::gc_record::{FieldType, PrimitiveArray, Values};
+    use crate::tests::{add_class, add_instance, reference};
+    use std::sync::Arc;
+
+    const VALUE: u64 = 1;
+
+    #[test]
+    fn shared_and_duplicated_backing_arrays() {
+        let mut heap = Heap::default();
+        heap.counter.id_size = 8;
+        heap.utf8_strings.make_mut().insert(VALUE, "value".into());
+        add_class(&mut heap, 100, 0, "java/lang/String");
+        for (array_id, content) in [(11, "acme"), (12, "acme"), (13, "zeta")] {
+            let bytes: Arc<[u8]> = Arc::from(content.as_bytes());
+            let array = PrimitiveArray::new(FieldType::Byte, bytes.len() as u32, bytes);
+            add_instance(
+                &mut heap,
+                array_id,
+                0,
+                vec![(0, Values::PrimitiveArray(array))],
+            );
+        }
+        // strings 2 and 3 share their array, string 5 has no value
+        for (string_id, array_id) in [(1, 11), (2, 12), (3, 12), (4, 13), (5, 0)] {
+            add_instance(&mut heap, string_id, 100, vec![reference(VALUE, array_id)]);
+        }
+        let array_size = heap.shallow_size(&heap.instances_pool[&11]);
+
+        let report = string_table_report(&heap, 10);
+        assert_eq!(report.strings, 5);
+        assert_eq!(report.backing_arrays, 3);
+        assert_eq!(report.backing_array_bytes, 3 * array_size);
+        assert_eq!(
+            (report.shared_backing_arrays, report.strings_sharing_arrays),
+            (1, 2)
+        );
+        assert_eq!(report.unique_contents, 2);
+        assert_eq!(report.duplicate_arrays, 1);
+        assert_eq!(report.duplicate_bytes, array_size);
+        assert_eq!(report.top_duplicates.len(), 1);
+        let acme = &report.top_duplicates[0];
+        assert_eq!((acme.value.as_str(), acme.arrays), ("acme", 2));
+        assert!(report.render().contains("\"acme\""));
+
+        assert!(string_table_report(&heap, 0).top_duplicates.is_empty());
+    }
+
+    #[test]
+    fn long_values_are_truncated() {
+        assert_eq!(render_value("a\nb"), "\"a\\nb\"");
+        let rendered = render_value(&"x".repeat(100));
+        assert_eq!(rendered.len(), MAX_RENDERED_VALUE_LEN + "\"...\"".len());
+    }
+}
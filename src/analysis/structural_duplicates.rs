@@ -0,0 +1,323 @@
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+
+use serde::Serialize;
+
+use crate::analysis::class_names::pretty_class_name;
+use crate::parser::gc_record::{ArrayValue, FieldValue, Values};
+use crate::report::{render_blocks, Block};
+use crate::result_recorder::Instance;
+use crate::utils::{pretty_bytes_size, pretty_count};
+use crate::Heap;
+
+// references are followed this deep, further objects only contribute their class
+pub const STRUCTURAL_HASH_DEPTH: u8 = 3;
+// covered by the string table report
+const STRING_CLASS: &str = "java.lang.String";
+
+/// Hashes objects by value: the class and the field values, references being replaced by the hash of
+/// the referenced objects, so that logically equal objects hash the same whatever their identity.
+///
+/// The hashes are memoized, a hasher should be reused for the objects of the same heap.
+pub struct StructuralHasher<'a> {
+    heap: &'a Heap,
+    depth: u8,
+    // (object id, remaining depth) -> hash
+    memo: HashMap<(u64, u8), u64>,
+}
+
+impl<'a> StructuralHasher<'a> {
+    pub fn new(heap: &'a Heap, depth: u8) -> Self {
+        StructuralHasher {
+            heap,
+            depth,
+            memo: HashMap::new(),
+        }
+    }
+
+    /// `None` if the object is missing or its fields were not retained.
+    pub fn hash(&mut self, object_id: u64) -> Option<u64> {
+        self.hash_within(object_id, self.depth)
+    }
+
+    fn hash_within(&mut self, object_id: u64, depth: u8) -> Option<u64> {
+        if let Some(hash) = self.memo.get(&(object_id, depth)) {
+            return Some(*hash);
+        }
+        let instance = self.heap.instances_pool.get(&object_id)?;
+        if !instance.elements_retained() || !fields_retained(instance) {
+            return None;
+        }
+        let mut hasher = DefaultHasher::new();
+        self.heap.instance_class_name(instance).hash(&mut hasher);
+        if depth > 0 {
+            for (_, value) in instance.all_fields() {
+                match value {
+                    Values::Single(value) => self.hash_value(value, depth, &mut hasher),
+                    Values::Array(ArrayValue::Object(ids)) => {
+                        ids.len().hash(&mut hasher);
+                        for id in ids {
+                            self.hash_reference(*id, depth, &mut hasher);
+                        }
+                    }
+                    Values::Array(array) => format!("{:?}", array).hash(&mut hasher),
+                    Values::PrimitiveArray(array) => array.bytes().hash(&mut hasher),
+                }
+            }
+        }
+        let hash = hasher.finish();
+        self.memo.insert((object_id, depth), hash);
+        Some(hash)
+    }
+
+    fn hash_value(&mut self, value: &FieldValue, depth: u8, hasher: &mut DefaultHasher) {
+        match value {
+            FieldValue::Object(id) => self.hash_reference(*id, depth, hasher),
+            FieldValue::Float(value) => value.to_bits().hash(hasher),
+            FieldValue::Double(value) => value.to_bits().hash(hasher),
+            FieldValue::Bool(value) => value.hash(hasher),
+            integral => integral.as_i64().hash(hasher),
+        }
+    }
+
+    // objects whose content is unknown only compare by identity
+    fn hash_reference(&mut self, object_id: u64, depth: u8, hasher: &mut DefaultHasher) {
+        if object_id == 0 {
+            0u64.hash(hasher);
+        } else {
+            match self.hash_within(object_id, depth - 1) {
+                Some(hash) => hash.hash(hasher),
+                None => object_id.hash(hasher),
+            }
+        }
+    }
+}
+
+// instances whose payload was dropped while slurping have no fields left
+fn fields_retained(instance: &Instance) -> bool {
+    instance.data_size == 0 || !instance.fields.is_empty() || !instance.super_fields.is_empty()
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct StructuralDuplicates {
+    pub class_name: String,
+    pub instances: u64,
+    pub distinct_values: u64,
+    // shallow size of the instances equal to one seen before
+    pub wasted_bytes: u64,
+    // instances sharing the most common value, with one of them
+    pub largest_group: u64,
+    pub example_object_id: u64,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct StructuralDuplicateReport {
+    pub duplicated_instances: u64,
+    pub wasted_bytes: u64,
+    // sorted by wasted bytes
+    pub classes: Vec<StructuralDuplicates>,
+}
+
+/// Groups the instances of each class by structural hash, see `StructuralHasher`, and keeps the `top` classes
+/// wasting the most bytes on instances equal to another one, candidates for caching or interning.
+///
+/// Arrays and strings are left to the duplicate arrays and string table reports.
+pub fn structural_duplicate_report(heap: &Heap, top: usize) -> StructuralDuplicateReport {
+    let mut hasher = StructuralHasher::new(heap, STRUCTURAL_HASH_DEPTH);
+    // class name -> hash -> (instances, size, example)
+    let mut by_class: HashMap<String, HashMap<u64, (u64, u64, u64)>> = HashMap::new();
+    // sorted for the examples to be stable
+    let mut instances: Vec<&Instance> = heap
+        .instances_pool
+        .values()
+        .map(|instance| instance.as_ref())
+        // instances without fields are all equal, e.g. lock objects
+        .filter(|instance| {
+            instance.array_element_type().is_none() && instance.all_fields().next().is_some()
+        })
+        .collect();
+    instances.sort_unstable_by_key(|instance| instance.object_id);
+    for instance in instances {
+        let class_name = heap.instance_class_name(instance);
+        if class_name == STRING_CLASS {
+            continue;
+        }
+        let Some(hash) = hasher.hash(instance.object_id) else {
+            continue;
+        };
+        by_class
+            .entry(class_name)
+            .or_default()
+            .entry(hash)
+            .or_insert((0, heap.shallow_size(instance), instance.object_id))
+            .0 += 1;
+    }
+
+    let mut classes: Vec<StructuralDuplicates> = by_class
+        .into_iter()
+        .filter_map(|(class_name, values)| {
+            let instances: u64 = values.values().map(|(count, _, _)| count).sum();
+            let distinct_values = values.len() as u64;
+            if instances == distinct_values {
+                return None;
+            }
+            let wasted_bytes = values
+                .values()
+                .map(|(count, size, _)| (count - 1) * size)
+                .sum();
+            let (largest_group, _, example_object_id) = values
+                .values()
+                .copied()
+                .max_by(|a, b| a.0.cmp(&b.0).then_with(|| b.2.cmp(&a.2)))
+                .unwrap_or_default();
+            Some(StructuralDuplicates {
+                class_name,
+                instances,
+                distinct_values,
+                wasted_bytes,
+                largest_group,
+                example_object_id,
+            })
+        })
+        .collect();
+    let mut report = StructuralDuplicateReport {
+        duplicated_instances: classes
+            .iter()
+            .map(|c| c.instances - c.distinct_values)
+            .sum(),
+        wasted_bytes: classes.iter().map(|c| c.wasted_bytes).sum(),
+        ..Default::default()
+    };
+    classes.sort_by(|a, b| {
+        b.wasted_bytes
+            .cmp(&a.wasted_bytes)
+            .then_with(|| a.class_name.cmp(&b.class_name))
+    });
+    classes.truncate(top);
+    report.classes = classes;
+    report
+}
+
+impl StructuralDuplicateReport {
+    pub fn blocks(&self) -> Vec<Block> {
+        let mut blocks = vec![Block::Heading(format!(
+            "Found {} instances equal to another instance of their class, wasting {}:",
+            pretty_count(self.duplicated_instances),
+            pretty_bytes_size(self.wasted_bytes)
+        ))];
+        if self.classes.is_empty() {
+            return blocks;
+        }
+        let rows: Vec<Vec<String>> = self
+            .classes
+            .iter()
+            .map(|c| {
+                vec![
+                    pretty_bytes_size(c.wasted_bytes),
+                    pretty_count(c.instances),
+                    pretty_count(c.distinct_values),
+                    format!(
+                        "{} (0x{:x})",
+                        pretty_count(c.largest_group),
+                        c.example_object_id
+                    ),
+                    pretty_class_name(&c.class_name),
+                ]
+            })
+            .collect();
+        blocks.push(Block::table(
+            &[
+                "Wasted",
+                "Instances",
+                "Distinct",
+                "Largest group",
+                "Class name",
+            ],
+            rows,
+        ));
+        blocks
+    }
+
+    pub fn render(&self) -> String {
+        render_blocks(&self.blocks())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::gc_record::ClassDumpFields;
+    use crate::parser::record::LoadClassData;
+    use crate::tests::{add_instance, reference, single};
+
+    // field name ids
+    const CURRENCY: u64 = 1;
+    const AMOUNT: u64 = 2;
+    const CODE: u64 = 3;
+
+    #[test]
+    fn equal_instances_by_value() {
+        let mut heap = Heap::default();
+        heap.counter.id_size = 8;
+        heap.utf8_strings
+            .make_mut()
+            .insert(10, "com/acme/Money".into());
+        heap.utf8_strings
+            .make_mut()
+            .insert(11, "com/acme/Currency".into());
+        for (serial_number, class_object_id, class_name_id) in [(1, 100, 10), (2, 101, 11)] {
+            heap.class_registry.make_mut().load(LoadClassData {
+                serial_number,
+                class_object_id,
+                stack_trace_serial_number: 0,
+                class_name_id,
+            });
+            heap.classes_dump.make_mut().insert(
+                class_object_id,
+                ClassDumpFields::new(
+                    class_object_id,
+                    0,
+                    0,
+                    0,
+                    0,
+                    Vec::new(),
+                    Vec::new(),
+                    Vec::new(),
+                ),
+            );
+        }
+        // two distinct but equal currencies
+        add_instance(&mut heap, 20, 101, vec![single(CODE, FieldValue::Int(978))]);
+        add_instance(&mut heap, 21, 101, vec![single(CODE, FieldValue::Int(978))]);
+        add_instance(&mut heap, 22, 101, vec![single(CODE, FieldValue::Int(840))]);
+        // 3 x 10 EUR through different currency objects, 10 USD
+        for (money_id, currency_id) in [(1, 20), (2, 21), (3, 20), (4, 22)] {
+            add_instance(
+                &mut heap,
+                money_id,
+                100,
+                vec![
+                    reference(CURRENCY, currency_id),
+                    single(AMOUNT, FieldValue::Long(10)),
+                ],
+            );
+        }
+
+        let mut hasher = StructuralHasher::new(&heap, STRUCTURAL_HASH_DEPTH);
+        assert_eq!(hasher.hash(1), hasher.hash(2));
+        assert_ne!(hasher.hash(1), hasher.hash(4));
+
+        let report = structural_duplicate_report(&heap, 10);
+        assert_eq!(report.duplicated_instances, 2 + 1);
+        let money = &report.classes[0];
+        assert_eq!(money.class_name, "com.acme.Money");
+        assert_eq!((money.instances, money.distinct_values), (4, 2));
+        assert_eq!((money.largest_group, money.example_object_id), (3, 1));
+        assert_eq!(
+            money.wasted_bytes,
+            2 * heap.shallow_size(&heap.instances_pool[&1])
+        );
+        assert!(report.render().contains("3 (0x1)"));
+    }
+}
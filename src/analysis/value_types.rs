@@ -0,0 +1,360 @@
+use std::collections::HashMap;
+
+use serde::Serialize;
+
+use crate::parser::gc_record::ArrayValue;
+use crate::report::{render_blocks, Block};
+use crate::result_recorder::Instance;
+use crate::utils::{pretty_bytes_size, pretty_count};
+use crate::Heap;
+
+// value types frequently allocated where a cached or primitive representation would do,
+// with the fields holding their internal objects
+const VALUE_TYPES: &[(&str, &[&str])] = &[
+    ("java.math.BigDecimal", &["intVal", "stringCache"]),
+    ("java.math.BigInteger", &["mag"]),
+    ("java.time.LocalDateTime", &["date", "time"]),
+    ("java.time.LocalDate", &[]),
+    ("java.time.Instant", &[]),
+    ("java.util.Date", &["cdate"]),
+    ("java.util.UUID", &[]),
+];
+
+// `BigDecimal.intCompact` marking a value held by `intVal`
+const INFLATED: i64 = i64::MIN;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ValueTypeUsage {
+    pub class_name: String,
+    pub instances: u64,
+    // the instances with their internal objects e.g. the `int[]` magnitude of a `BigInteger`
+    pub total_bytes: u64,
+    pub distinct_values: u64,
+    // bytes of the instances holding a value seen before
+    pub duplicate_bytes: u64,
+    pub most_duplicated: Option<(String, u64)>,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ValueTypeReport {
+    // sorted by duplicate bytes
+    pub types: Vec<ValueTypeUsage>,
+}
+
+/// Counts the instances of common value types (`BigDecimal`, `BigInteger`, dates, `UUID`) and
+/// the duplicated values among them, candidates for interning or caching.
+pub fn value_type_report(heap: &Heap) -> ValueTypeReport {
+    let mut types: Vec<ValueTypeUsage> = VALUE_TYPES
+        .iter()
+        .filter_map(|(class_name, internals)| {
+            // value -> instances holding it
+            let mut values: HashMap<String, u64> = HashMap::new();
+            let mut instances = 0;
+            let mut total_bytes = 0;
+            let mut duplicate_bytes = 0;
+            for instance in heap.instances_of(class_name) {
+                let size = deep_size(heap, instance, internals);
+                instances += 1;
+                total_bytes += size;
+                // values whose fields were not retained are not compared
+                let Some(value) = render_value(heap, class_name, instance) else {
+                    continue;
+                };
+                let count = values.entry(value).or_insert(0);
+                if *count > 0 {
+                    duplicate_bytes += size;
+                }
+                *count += 1;
+            }
+            if instances == 0 {
+                return None;
+            }
+            let most_duplicated = values
+                .iter()
+                .filter(|(_, count)| **count > 1)
+                .max_by(|a, b| a.1.cmp(b.1).then_with(|| b.0.cmp(a.0)))
+                .map(|(value, count)| (value.clone(), *count));
+            Some(ValueTypeUsage {
+                class_name: class_name.to_string(),
+                instances,
+                total_bytes,
+                distinct_values: values.len() as u64,
+                duplicate_bytes,
+                most_duplicated,
+            })
+        })
+        .collect();
+    types.sort_by(|a, b| {
+        b.duplicate_bytes
+            .cmp(&a.duplicate_bytes)
+            .then_with(|| b.total_bytes.cmp(&a.total_bytes))
+            .then_with(|| a.class_name.cmp(&b.class_name))
+    });
+    ValueTypeReport { types }
+}
+
+// the instance and the objects held by its internal fields, recursively
+fn deep_size(heap: &Heap, instance: &Instance, internals: &[&str]) -> u64 {
+    let mut size = heap.shallow_size(instance);
+    for field in internals {
+        if let Some(internal) = heap.field_instance(instance, field) {
+            let nested = VALUE_TYPES
+                .iter()
+                .find(|(name, _)| heap.instance_class_name(internal) == *name)
+                .map_or(&[][..], |(_, nested)| *nested);
+            // strings own their backing array
+            let nested = if heap.is_subclass_of(internal.class_object_id, "java.lang.String") {
+                &["value"][..]
+            } else {
+                nested
+            };
+            size += deep_size(heap, internal, nested);
+        }
+    }
+    size
+}
+
+fn int_field(heap: &Heap, instance: &Instance, name: &str) -> Option<i64> {
+    heap.field_value(instance, name)?.as_i64()
+}
+
+fn render_value(heap: &Heap, class_name: &str, instance: &Instance) -> Option<String> {
+    match class_name {
+        "java.math.BigDecimal" => {
+            let scale = int_field(heap, instance, "scale")?;
+            let unscaled = match int_field(heap, instance, "intCompact")? {
+                INFLATED => render_big_integer(heap, heap.field_instance(instance, "intVal")?)?,
+                compact => compact.to_string(),
+            };
+            Some(format!("{}E-{}", unscaled, scale))
+        }
+        "java.math.BigInteger" => render_big_integer(heap, instance),
+        "java.time.LocalDateTime" => {
+            let date = heap.field_instance(instance, "date")?;
+            let time = heap.field_instance(instance, "time")?;
+            Some(format!(
+                "{}T{}",
+                render_value(heap, "java.time.LocalDate", date)?,
+                render_local_time(heap, time)?
+            ))
+        }
+        "java.time.LocalDate" => Some(format!(
+            "{:04}-{:02}-{:02}",
+            int_field(heap, instance, "year")?,
+            int_field(heap, instance, "month")?,
+            int_field(heap, instance, "day")?
+        )),
+        "java.time.Instant" => Some(format!(
+            "{}s+{}ns",
+            int_field(heap, instance, "seconds")?,
+            int_field(heap, instance, "nanos")?
+        )),
+        "java.util.Date" => Some(format!("{}ms", int_field(heap, instance, "fastTime")?)),
+        "java.util.UUID" => {
+            let most = int_field(heap, instance, "mostSigBits")? as u64;
+            let least = int_field(heap, instance, "leastSigBits")? as u64;
+            Some(format!(
+                "{:08x}-{:04x}-{:04x}-{:04x}-{:012x}",
+                most >> 32,
+                (most >> 16) & 0xffff,
+                most & 0xffff,
+                least >> 48,
+                least & 0xffff_ffff_ffff
+            ))
+        }
+        _ => None,
+    }
+}
+
+fn render_local_time(heap: &Heap, time: &Instance) -> Option<String> {
+    Some(format!(
+        "{:02}:{:02}:{:02}.{:09}",
+        int_field(heap, time, "hour")?,
+        int_field(heap, time, "minute")?,
+        int_field(heap, time, "second")?,
+        int_field(heap, time, "nano")?
+    ))
+}
+
+// big-endian magnitude rendered in hexadecimal
+fn render_big_integer(heap: &Heap, instance: &Instance) -> Option<String> {
+    let signum = int_field(heap, instance, "signum")?;
+    let mag = heap.field_instance(instance, "mag")?;
+    if !mag.elements_retained() {
+        return None;
+    }
+    let digits = match mag.array_value()? {
+        ArrayValue::Int(words) => words
+            .iter()
+            .enumerate()
+            .map(|(i, word)| {
+                if i == 0 {
+                    format!("{:x}", *word as u32)
+                } else {
+                    format!("{:08x}", *word as u32)
+                }
+            })
+            .collect::<String>(),
+        _ => return None,
+    };
+    let digits = if digits.is_empty() { "0" } else { &digits };
+    Some(format!("{}0x{}", if signum < 0 { "-" } else { "" }, digits))
+}
+
+impl ValueTypeReport {
+    pub fn blocks(&self) -> Vec<Block> {
+        let rows: Vec<Vec<String>> = self
+            .types
+            .iter()
+            .map(|t| {
+                vec![
+                    pretty_bytes_size(t.duplicate_bytes),
+                    pretty_bytes_size(t.total_bytes),
+                    pretty_count(t.instances),
+                    pretty_count(t.distinct_values),
+                    t.class_name.clone(),
+                    t.most_duplicated
+                        .as_ref()
+                        .map(|(value, count)| format!("{} ({}x)", value, count))
+                        .unwrap_or_default(),
+                ]
+            })
+            .collect();
+        vec![
+            Block::Heading(format!(
+                "Found {} value types, {} held by duplicated values:",
+                self.types.len(),
+                pretty_bytes_size(self.types.iter().map(|t| t.duplicate_bytes).sum())
+            )),
+            Block::table(
+                &[
+                    "Duplicates",
+                    "Total size",
+                    "Instances",
+                    "Distinct",
+                    "Class name",
+                    "Most duplicated",
+                ],
+                rows,
+            ),
+        ]
+    }
+
+    pub fn render(&self) -> String {
+        render_blocks(&self.blocks())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::gc_record::{FieldValue, Values};
+    use crate::tests::{add_class, add_instance, reference, single};
+
+    // field name ids
+    const MOST_SIG_BITS: u64 = 1;
+    const LEAST_SIG_BITS: u64 = 2;
+    const SCALE: u64 = 3;
+    const INT_COMPACT: u64 = 4;
+    const INT_VAL: u64 = 5;
+    const SIGNUM: u64 = 6;
+    const MAG: u64 = 7;
+
+    #[test]
+    fn duplicated_uuids_and_decimals() {
+        let mut heap = Heap::default();
+        heap.counter.id_size = 8;
+        for (name_id, name) in [
+            (MOST_SIG_BITS, "mostSigBits"),
+            (LEAST_SIG_BITS, "leastSigBits"),
+            (SCALE, "scale"),
+            (INT_COMPACT, "intCompact"),
+            (INT_VAL, "intVal"),
+            (SIGNUM, "signum"),
+            (MAG, "mag"),
+        ] {
+            heap.utf8_strings.make_mut().insert(name_id, name.into());
+        }
+        add_class(&mut heap, 100, 0, "java/util/UUID");
+        add_class(&mut heap, 101, 0, "java/math/BigDecimal");
+        add_class(&mut heap, 102, 0, "java/math/BigInteger");
+        for (object_id, most, least) in [
+            (1, 0x0123_4567_89ab_cdef, 0x0fed_cba9_8765_4321),
+            (2, 0x0123_4567_89ab_cdef, 0x0fed_cba9_8765_4321),
+            (3, 0, 1),
+        ] {
+            add_instance(
+                &mut heap,
+                object_id,
+                100,
+                vec![
+                    single(MOST_SIG_BITS, FieldValue::Long(most)),
+                    single(LEAST_SIG_BITS, FieldValue::Long(least)),
+                ],
+            );
+        }
+        // 1.50 twice as a compact value and 2^32 / 100 held by a `BigInteger`
+        for object_id in [10, 12] {
+            add_instance(
+                &mut heap,
+                object_id,
+                101,
+                vec![
+                    single(SCALE, FieldValue::Int(2)),
+                    single(INT_COMPACT, FieldValue::Long(150)),
+                ],
+            );
+        }
+        add_instance(
+            &mut heap,
+            11,
+            101,
+            vec![
+                single(SCALE, FieldValue::Int(2)),
+                single(INT_COMPACT, FieldValue::Long(INFLATED)),
+                reference(INT_VAL, 20),
+            ],
+        );
+        add_instance(
+            &mut heap,
+            20,
+            102,
+            vec![single(SIGNUM, FieldValue::Int(1)), reference(MAG, 30)],
+        );
+        add_instance(
+            &mut heap,
+            30,
+            0,
+            vec![(0, Values::Array(ArrayValue::Int(vec![1, 0])))],
+        );
+        let size = |ids: &[u64]| {
+            ids.iter()
+                .map(|id| heap.shallow_size(&heap.instances_pool[id]))
+                .sum::<u64>()
+        };
+
+        let report = value_type_report(&heap);
+        assert_eq!(report.types.len(), 2);
+        let decimals = &report.types[0];
+        assert_eq!(decimals.class_name, "java.math.BigDecimal");
+        assert_eq!((decimals.instances, decimals.distinct_values), (3, 2));
+        assert_eq!(decimals.total_bytes, size(&[10, 11, 12, 20, 30]));
+        assert_eq!(decimals.duplicate_bytes, size(&[12]));
+        assert_eq!(decimals.most_duplicated, Some(("150E-2".to_string(), 2)));
+        let uuids = &report.types[1];
+        assert_eq!(uuids.class_name, "java.util.UUID");
+        assert_eq!((uuids.instances, uuids.distinct_values), (3, 2));
+        assert_eq!(uuids.duplicate_bytes, size(&[2]));
+        assert_eq!(
+            uuids.most_duplicated,
+            Some(("01234567-89ab-cdef-0fed-cba987654321".to_string(), 2))
+        );
+
+        let inflated = &heap.instances_pool[&11];
+        assert_eq!(
+            render_value(&heap, "java.math.BigDecimal", inflated).as_deref(),
+            Some("0x100000000E-2")
+        );
+        assert!(report.render().contains("Found 2 value types"));
+    }
+}
@@ -0,0 +1,228 @@
+#[cfg(feature = "graph")]
+use std::collections::HashMap;
+
+use serde::Serialize;
+
+use crate::analysis::class_names::pretty_class_name;
+#[cfg(feature = "graph")]
+use crate::graph::HeapGraph;
+use crate::report::{render_blocks, Block};
+use crate::utils::{pretty_bytes_size, pretty_count};
+use crate::Heap;
+
+/// The objects removed by a what-if analysis: the instances of classes (subclasses included) and explicit objects.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct WhatIfQuery {
+    pub classes: Vec<String>,
+    pub object_ids: Vec<u64>,
+}
+
+impl WhatIfQuery {
+    pub fn is_empty(&self) -> bool {
+        self.classes.is_empty() && self.object_ids.is_empty()
+    }
+
+    /// Returns the ids of the objects removed, sorted.
+    pub fn matching_objects(&self, heap: &Heap) -> Vec<u64> {
+        let mut object_ids: Vec<u64> = self
+            .classes
+            .iter()
+            .flat_map(|class_name| heap.instances_of(class_name))
+            .map(|instance| instance.object_id)
+            .chain(self.object_ids.iter().copied())
+            .collect();
+        object_ids.sort_unstable();
+        object_ids.dedup();
+        object_ids
+    }
+}
+
+/// Parses an object id in hexadecimal (`0x` prefix) or decimal.
+pub fn parse_object_id(s: &str) -> Result<u64, String> {
+    match s.strip_prefix("0x") {
+        Some(hex) => u64::from_str_radix(hex, 16),
+        None => s.parse(),
+    }
+    .map_err(|e| format!("invalid object id `{}`: {}", s, e))
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct FreedClass {
+    pub class_name: String,
+    pub objects: u64,
+    pub bytes: u64,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct WhatIfReport {
+    pub removed_objects: u64,
+    pub removed_bytes: u64,
+    // the removed objects and the ones only reachable through them
+    pub freed_objects: u64,
+    pub freed_bytes: u64,
+    // sorted by bytes
+    pub classes: Vec<FreedClass>,
+}
+
+/// Measures the heap which would become unreachable if the objects of the query were removed,
+/// keeping the `top` classes freeing the most bytes.
+#[cfg(feature = "graph")]
+pub fn what_if_report(
+    heap: &Heap,
+    graph: &HeapGraph,
+    query: &WhatIfQuery,
+    top: usize,
+) -> WhatIfReport {
+    let removed = query.matching_objects(heap);
+    let freed = graph.retained_set_of(&removed);
+    let mut classes: HashMap<String, FreedClass> = HashMap::new();
+    let mut report = WhatIfReport::default();
+    for object_id in &freed {
+        let size = graph.shallow_size(*object_id).unwrap_or_default();
+        if removed.binary_search(object_id).is_ok() {
+            report.removed_objects += 1;
+            report.removed_bytes += size;
+        }
+        report.freed_objects += 1;
+        report.freed_bytes += size;
+        let class = classes
+            .entry(heap.object_class_name(*object_id))
+            .or_insert_with_key(|class_name| FreedClass {
+                class_name: class_name.clone(),
+                objects: 0,
+                bytes: 0,
+            });
+        class.objects += 1;
+        class.bytes += size;
+    }
+    let mut classes: Vec<FreedClass> = classes.into_values().collect();
+    classes.sort_by(|a, b| {
+        b.bytes
+            .cmp(&a.bytes)
+            .then_with(|| a.class_name.cmp(&b.class_name))
+    });
+    classes.truncate(top);
+    report.classes = classes;
+    report
+}
+
+impl WhatIfReport {
+    pub fn blocks(&self) -> Vec<Block> {
+        let mut blocks = vec![Block::Heading(format!(
+            "Removing {} objects ({}) would free {} objects ({}):",
+            pretty_count(self.removed_objects),
+            pretty_bytes_size(self.removed_bytes),
+            pretty_count(self.freed_objects),
+            pretty_bytes_size(self.freed_bytes)
+        ))];
+        if self.classes.is_empty() {
+            return blocks;
+        }
+        let rows: Vec<Vec<String>> = self
+            .classes
+            .iter()
+            .map(|c| {
+                vec![
+                    pretty_bytes_size(c.bytes),
+                    pretty_count(c.objects),
+                    pretty_class_name(&c.class_name),
+                ]
+            })
+            .collect();
+        blocks.push(Block::table(&["Freed", "Objects", "Class name"], rows));
+        blocks
+    }
+
+    pub fn render(&self) -> String {
+        render_blocks(&self.blocks())
+    }
+}
+
+#[cfg(all(test, feature = "graph"))]
+mod tests {
+    use super::*;
+    use crate::parser::gc_record::{ClassDumpFields, FieldValue, Values};
+    use crate::parser::record::LoadClassData;
+    use crate::result_recorder::{Instance, RootJniGlobal};
+    use std::sync::Arc;
+
+    #[test]
+    fn removing_a_class_frees_what_it_holds() {
+        let mut heap = Heap::default();
+        heap.counter.id_size = 8;
+        heap.utf8_strings
+            .make_mut()
+            .insert(1, "com/acme/Cache".into());
+        heap.utf8_strings
+            .make_mut()
+            .insert(2, "com/acme/Entry".into());
+        for (serial_number, class_object_id, class_name_id) in [(1, 100, 1), (2, 200, 2)] {
+            heap.class_registry.make_mut().load(LoadClassData {
+                serial_number,
+                class_object_id,
+                stack_trace_serial_number: 0,
+                class_name_id,
+            });
+            heap.root_sticky_class.make_mut().push(class_object_id);
+            heap.classes_dump.make_mut().insert(
+                class_object_id,
+                ClassDumpFields::new(
+                    class_object_id,
+                    0,
+                    0,
+                    0,
+                    0,
+                    Vec::new(),
+                    Vec::new(),
+                    Vec::new(),
+                ),
+            );
+        }
+        // root 1 -> cache 2 -> entries 3 and 4, root 1 -> entry 4
+        for (object_id, class_object_id, references) in [
+            (1, 200, vec![2, 4]),
+            (2, 100, vec![3, 4]),
+            (3, 200, vec![]),
+            (4, 200, vec![]),
+        ] {
+            heap.instances_pool.make_mut().insert(
+                object_id,
+                Arc::new(Instance {
+                    object_id,
+                    stack_trace_serial_number: 0,
+                    class_object_id,
+                    data_size: 8 * references.len() as u64,
+                    fields: references
+                        .iter()
+                        .map(|id| (0, Values::Single(FieldValue::Object(*id))))
+                        .collect(),
+                    super_fields: Vec::new(),
+                }),
+            );
+        }
+        heap.root_jni_global.make_mut().insert(
+            1,
+            RootJniGlobal {
+                object_id: 1,
+                jni_global_ref_id: 0,
+            },
+        );
+        let graph = HeapGraph::new(&heap);
+
+        let query = WhatIfQuery {
+            classes: vec!["com.acme.Cache".to_string()],
+            object_ids: Vec::new(),
+        };
+        let report = what_if_report(&heap, &graph, &query, 10);
+        assert_eq!((report.removed_objects, report.removed_bytes), (1, 32));
+        assert_eq!((report.freed_objects, report.freed_bytes), (2, 32 + 16));
+        assert_eq!(report.classes[0].class_name, "com.acme.Cache");
+        assert!(report
+            .render()
+            .contains("Removing 1 objects (32.00bytes) would free 2 objects (48.00bytes)"));
+
+        assert_eq!(parse_object_id("0x2a"), Ok(42));
+        assert_eq!(parse_object_id("42"), Ok(42));
+        assert!(parse_object_id("0xzz").is_err());
+    }
+}
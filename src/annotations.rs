@@ -0,0 +1,181 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs::File;
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+use crate::analysis::what_if::parse_object_id;
+use crate::errors::HprofSlurpError;
+use crate::errors::HprofSlurpError::*;
+
+const ANNOTATIONS_HEADER: &str = "# hprof-slurp annotations";
+
+/// Labels attached to object ids (e.g. `suspect` or `reported`) during an investigation, kept next to the
+/// heap which is never modified.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct HeapAnnotations {
+    tags: BTreeMap<u64, BTreeSet<String>>,
+}
+
+/// Returns the sidecar path of the annotations of a dump, e.g. `dump.hprof.tags`.
+pub fn annotations_path(dump_path: &Path) -> PathBuf {
+    let mut file_name = dump_path.file_name().unwrap_or_default().to_os_string();
+    file_name.push(".tags");
+    dump_path.with_file_name(file_name)
+}
+
+impl HeapAnnotations {
+    /// Tags an object, returns false if it already had the label.
+    ///
+    /// Labels are trimmed and cannot be empty nor contain whitespace other than spaces.
+    pub fn tag(&mut self, object_id: u64, label: &str) -> Result<bool, HprofSlurpError> {
+        let label = label.trim();
+        if label.is_empty() || label.chars().any(|c| c.is_whitespace() && c != ' ') {
+            return Err(InvalidAnnotations {
+                message: format!("invalid label `{}`", label.escape_debug()),
+            });
+        }
+        Ok(self
+            .tags
+            .entry(object_id)
+            .or_default()
+            .insert(label.to_string()))
+    }
+
+    /// Removes a label from an object, returns false if it did not have it.
+    pub fn untag(&mut self, object_id: u64, label: &str) -> bool {
+        let Some(labels) = self.tags.get_mut(&object_id) else {
+            return false;
+        };
+        let removed = labels.remove(label.trim());
+        if labels.is_empty() {
+            self.tags.remove(&object_id);
+        }
+        removed
+    }
+
+    pub fn labels(&self, object_id: u64) -> impl Iterator<Item = &str> {
+        self.tags
+            .get(&object_id)
+            .into_iter()
+            .flatten()
+            .map(|label| label.as_str())
+    }
+
+    pub fn has_label(&self, object_id: u64, label: &str) -> bool {
+        self.tags
+            .get(&object_id)
+            .is_some_and(|labels| labels.contains(label))
+    }
+
+    /// Iterates over the objects tagged with `label` in ascending id order.
+    pub fn tagged<'a>(&'a self, label: &'a str) -> impl Iterator<Item = u64> + 'a {
+        self.tags
+            .iter()
+            .filter(move |(_, labels)| labels.contains(label))
+            .map(|(object_id, _)| *object_id)
+    }
+
+    /// Iterates over the tagged objects and their labels in ascending id order.
+    pub fn iter(&self) -> impl Iterator<Item = (u64, &BTreeSet<String>)> {
+        self.tags
+            .iter()
+            .map(|(object_id, labels)| (*object_id, labels))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tags.is_empty()
+    }
+
+    /// Saves the annotations as text, an object per line with its id and tab separated labels.
+    pub fn write<W: Write>(&self, writer: &mut W) -> Result<(), HprofSlurpError> {
+        writeln!(writer, "{}", ANNOTATIONS_HEADER)?;
+        for (object_id, labels) in &self.tags {
+            write!(writer, "0x{:x}", object_id)?;
+            for label in labels {
+                write!(writer, "\t{}", label)?;
+            }
+            writeln!(writer)?;
+        }
+        Ok(())
+    }
+
+    pub fn read<R: BufRead>(reader: R) -> Result<Self, HprofSlurpError> {
+        let mut annotations = HeapAnnotations::default();
+        for (number, line) in reader.lines().enumerate() {
+            let line = line?;
+            if line.trim().is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let mut columns = line.split('\t');
+            let object_id =
+                parse_object_id(columns.next().unwrap_or_default().trim()).map_err(|message| {
+                    InvalidAnnotations {
+                        message: format!("line {}: {}", number + 1, message),
+                    }
+                })?;
+            for label in columns {
+                annotations.tag(object_id, label)?;
+            }
+        }
+        Ok(annotations)
+    }
+
+    /// Saves the annotations in the sidecar of the dump, see `annotations_path`.
+    pub fn save(&self, dump_path: &Path) -> Result<(), HprofSlurpError> {
+        let path = annotations_path(dump_path);
+        let partial_path = path.with_extension("tags.partial");
+        let mut writer = BufWriter::new(File::create(&partial_path)?);
+        self.write(&mut writer)?;
+        writer.flush()?;
+        drop(writer);
+        std::fs::rename(partial_path, path)?;
+        Ok(())
+    }
+
+    /// Loads the annotations saved next to the dump, empty if there are none.
+    pub fn load(dump_path: &Path) -> Result<Self, HprofSlurpError> {
+        let path = annotations_path(dump_path);
+        if !path.exists() {
+            return Ok(HeapAnnotations::default());
+        }
+        HeapAnnotations::read(BufReader::new(File::open(path)?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tag_and_persist() {
+        let mut annotations = HeapAnnotations::default();
+        assert!(annotations.tag(0x10, "suspect").unwrap());
+        assert!(!annotations.tag(0x10, " suspect ").unwrap());
+        assert!(annotations.tag(0x10, "needs review").unwrap());
+        assert!(annotations.tag(0x20, "suspect").unwrap());
+        assert!(annotations.tag(0x30, "bad\tlabel").is_err());
+        assert!(annotations.untag(0x20, "suspect"));
+        assert!(annotations.tag(0x20, "reported").unwrap());
+        assert_eq!(
+            annotations.tagged("suspect").collect::<Vec<_>>(),
+            vec![0x10]
+        );
+        assert!(annotations.has_label(0x20, "reported"));
+
+        let mut text = Vec::new();
+        annotations.write(&mut text).unwrap();
+        let text = String::from_utf8(text).unwrap();
+        assert_eq!(
+            text,
+            "# hprof-slurp annotations\n0x10\tneeds review\tsuspect\n0x20\treported\n"
+        );
+        assert_eq!(HeapAnnotations::read(text.as_bytes()).unwrap(), annotations);
+        assert!(HeapAnnotations::read("0xzz\tsuspect\n".as_bytes()).is_err());
+
+        let dump_path = std::env::temp_dir().join("hprof-slurp-annotations.hprof");
+        assert!(HeapAnnotations::load(&dump_path).unwrap().is_empty());
+        annotations.save(&dump_path).unwrap();
+        assert_eq!(HeapAnnotations::load(&dump_path).unwrap(), annotations);
+        std::fs::remove_file(annotations_path(&dump_path)).unwrap();
+    }
+}
@@ -1,20 +1,99 @@
+use crate::analysis::class_names::{random_salt, NameGrouping};
+use crate::analysis::histogram::{HistogramOptions, HistogramSort};
+use crate::analysis::what_if::{parse_object_id, WhatIfQuery};
+use crate::analysis::TableOptions;
+use crate::class_export::ClassExportOptions;
 use crate::errors::HprofSlurpError;
 use crate::errors::HprofSlurpError::*;
+use crate::parser::record_parser::RecordLimits;
+use crate::report::{HeapExport, OutputFormat, SectionKind};
+use crate::slurp::{is_remote_path, CheckpointOptions, STDIN_PATH};
+use crate::utils::{parse_bytes_size, ColorChoice, SizeUnits};
 use clap::{crate_authors, crate_description, crate_name, crate_version};
-use clap::{Arg, Command};
-use std::path::Path;
+use clap::{Arg, ArgGroup, Command};
+use std::cmp::Ordering;
+use std::path::{Path, PathBuf};
 
 fn command() -> Command {
     Command::new(crate_name!())
         .version(crate_version!())
         .author(crate_authors!("\n"))
         .about(crate_description!())
+        .subcommand_negates_reqs(true)
+        .subcommand(
+            Command::new("index")
+                .about("write the `.hprof.idx` sidecar index of an uncompressed dump: its strings, classes and record offsets, needs the `index` feature")
+                .arg(Arg::new("indexFile").help("binary hprof file to index").required(true)),
+        )
+        .subcommand(
+            Command::new("info")
+                .about("describe a dump from its header and first records within milliseconds: format, identifier size, timestamp, file size and estimated record counts")
+                .arg(Arg::new("infoFile").help("binary hprof file to describe").required(true)),
+        )
+        .subcommand(
+            Command::new("verify")
+                .about("check the structural invariants of a dump (record offsets, heap dump segments, referenced class, string and stack frame ids, instance sizes) before a long analysis")
+                .arg(Arg::new("verifyFile").help("binary hprof file to verify").required(true)),
+        )
+        .subcommand(
+            Command::new("explain")
+                .about("explain where the bytes of an instance of a class come from: header, fields, padding and super class contributions, with its instance count")
+                .arg(Arg::new("explainClassName").help("class name (e.g. com.acme.Order)").required(true))
+                .arg(Arg::new("explainFile").help("binary hprof input file").required(true)),
+        )
+        .subcommand(
+            Command::new("what-if")
+                .about("report how much heap would become unreachable if the matching objects were removed, needs the `graph` feature")
+                .arg(Arg::new("whatIfFile").help("binary hprof input file").required(true))
+                .arg(
+                    Arg::new("removeClass")
+                        .help("remove the instances of this class and of its subclasses, can be repeated")
+                        .long("remove-class")
+                        .num_args(1)
+                        .action(clap::ArgAction::Append),
+                )
+                .arg(
+                    Arg::new("removeObject")
+                        .help("remove the object with this id (e.g. 0x7f3a0010), can be repeated")
+                        .long("remove-object")
+                        .num_args(1)
+                        .value_parser(parse_object_id)
+                        .action(clap::ArgAction::Append),
+                )
+                .group(
+                    ArgGroup::new("removed")
+                        .args(["removeClass", "removeObject"])
+                        .multiple(true)
+                        .required(true),
+                ),
+        )
+        .subcommand(
+            Command::new("export-class")
+                .about("write the fields of the instances of a class as CSV, or JSON for a `.json` output with the `exports` feature")
+                .arg(Arg::new("exportClassName").help("class name (e.g. com.acme.Order), subclasses included").required(true))
+                .arg(Arg::new("exportClassFile").help("binary hprof input file").required(true))
+                .arg(
+                    Arg::new("fields")
+                        .help("comma separated fields to export, dotted paths follow references (e.g. id,customer.name)")
+                        .long("fields")
+                        .num_args(1)
+                        .value_delimiter(',')
+                        .required(true),
+                )
+                .arg(
+                    Arg::new("output")
+                        .help("output file, stdout if missing")
+                        .long("output")
+                        .short('o')
+                        .num_args(1),
+                ),
+        )
         .arg(
             Arg::new("inputFile")
-                .help("binary hprof input file")
+                .help("binary hprof input file, `-` reads from stdin, HTTP(S) and S3 URLs need the `remote` feature, several files or a glob pattern for dumps split in parts")
                 .long("inputFile")
                 .short('i')
-                .num_args(1)
+                .num_args(1..)
                 .required(true),
         )
         .arg(
@@ -41,19 +120,377 @@ fn command() -> Command {
                 .short('l')
                 .action(clap::ArgAction::SetTrue),
         )
+        .arg(
+            Arg::new("stringsOutput")
+                .help("write the Strings listed with `--listStrings` to this file as they are sorted instead of the report")
+                .long("strings-output")
+                .num_args(1)
+                .requires("listStrings")
+                .required(false),
+        )
+        .arg(
+            Arg::new("sortBy")
+                .help("the sort key of the allocated classes")
+                .long("sort-by")
+                .num_args(1)
+                .default_value("total-size")
+                .value_parser([
+                    "total-size",
+                    "instances",
+                    "largest",
+                    "name",
+                    #[cfg(feature = "graph")]
+                    "retained-size",
+                ])
+                .required(false),
+        )
+        .arg(
+            Arg::new("minTotalSize")
+                .help("hide the classes and arrays smaller than this total size (e.g. 10MiB)")
+                .long("min-total-size")
+                .num_args(1)
+                .value_parser(parse_bytes_size)
+                .required(false),
+        )
+        .arg(
+            Arg::new("minInstances")
+                .help("hide the classes and arrays with fewer instances")
+                .long("min-instances")
+                .num_args(1)
+                .value_parser(clap::value_parser!(u64))
+                .required(false),
+        )
+        .arg(
+            Arg::new("effectiveOwnership")
+                .help("also list the classes with the char[] and byte[] attributed to the strings and buffers owning them")
+                .long("effective-ownership")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("splitArraysByOwner")
+                .help("split the char[] and byte[] of the allocated classes by the class of the object solely referencing them, e.g. `byte[] (owned by io.netty.buffer.UnpooledHeapByteBuf)`")
+                .long("split-arrays-by-owner")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("groupNames")
+                .help("collapse generated class names into stable groups, or nested classes into their outer class: comma separated list of `hidden`, `lambdas`, `proxies`, `enhancers`, `accessors`, `nested`, `all` or `none`")
+                .long("group-names")
+                .num_args(1)
+                .default_value("none")
+                .value_parser(|s: &str| s.parse::<NameGrouping>())
+                .required(false),
+        )
+        .arg(
+            Arg::new("baseline")
+                .help("subtract a baseline profile from the allocated classes, `jdk` uses the shipped JDK baseline")
+                .long("baseline")
+                .num_args(1)
+                .required(false),
+        )
+        .arg(
+            Arg::new("saveBaseline")
+                .help("save the allocated classes as a baseline profile")
+                .long("save-baseline")
+                .num_args(1)
+                .required(false),
+        )
+        .arg(
+            Arg::new("checkpoint")
+                .help("periodically save the progress to this file and resume from it, needs the `checkpoint` feature")
+                .long("checkpoint")
+                .num_args(1)
+                .required(false),
+        )
+        .arg(
+            Arg::new("checkpointInterval")
+                .help("the input size processed between two checkpoints")
+                .long("checkpoint-interval")
+                .num_args(1)
+                .default_value("1GiB")
+                .value_parser(parse_bytes_size)
+                .required(false),
+        )
+        .arg(
+            Arg::new("maxMemory")
+                .help("stop retaining instance fields and array elements once the estimated memory crosses this size (e.g. 8GiB)")
+                .long("max-memory")
+                .num_args(1)
+                .value_parser(parse_bytes_size)
+                .required(false),
+        )
+        .arg(
+            Arg::new("maxRecordSize")
+                .help("reject the records declaring a length above this size as corrupt (default 1GiB)")
+                .long("max-record-size")
+                .num_args(1)
+                .value_parser(parse_bytes_size)
+                .required(false),
+        )
+        .arg(
+            Arg::new("skipOversizedRecords")
+                .help("skip the records declaring an oversized length instead of failing")
+                .long("skip-oversized-records")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("dumpIndex")
+                .help("only analyze the heap dump at this index (starting at 0) when the file holds several")
+                .long("dump-index")
+                .num_args(1)
+                .value_parser(clap::value_parser!(usize))
+                .required(false),
+        )
+        .arg(
+            Arg::new("readahead")
+                .help("the number of input buffers read ahead of the parser, more of them help on network filesystems")
+                .long("readahead")
+                .num_args(1)
+                .default_value("2")
+                .value_parser(clap::value_parser!(u64).range(2..))
+                .required(false),
+        )
+        .arg(
+            Arg::new("directIo")
+                .help("read the input with direct I/O to bypass the page cache, Linux only")
+                .long("direct-io")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("matIndex")
+                .help("also write the Eclipse MAT identifier and offset indexes next to the dump")
+                .long("mat-index")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("graphMetrics")
+                .help("also report the most referenced objects and the strongly connected components of the reference graph, needs the `graph` feature")
+                .long("graph-metrics")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("cycles")
+                .help("also report the largest reference cycles of at least this many objects with their classes, needs the `graph` feature")
+                .long("cycles")
+                .num_args(1)
+                .value_parser(clap::value_parser!(u64).range(2..))
+                .required(false),
+        )
+        .arg(
+            Arg::new("heaviestFields")
+                .help("also report the reference fields pointing to the most bytes across the instances of their class")
+                .long("heaviest-fields")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("referencingFields")
+                .help("also report the fields holding the most references to the instances of this class, e.g. `byte[]`")
+                .long("referencing-fields")
+                .num_args(1)
+                .value_name("class")
+                .required(false),
+        )
+        .arg(
+            Arg::new("auto")
+                .help("only render the sections likely to matter for this heap, e.g. the duplicated strings when the strings hold a large share of it")
+                .long("auto")
+                .conflicts_with("listStrings")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("anonymize")
+                .help("replace the application class and package names with pseudonyms in all outputs, the JDK names are kept, the pseudonyms are stable across runs only for a given salt, a random one by default")
+                .long("anonymize")
+                // the contents of the strings are not anonymized
+                .conflicts_with("listStrings")
+                .num_args(0..=1)
+                .value_name("salt")
+                .default_missing_value("")
+                .required(false),
+        )
+        .arg(
+            Arg::new("section")
+                .help("also report this section after the default ones, can be repeated")
+                .long("section")
+                .num_args(1)
+                .value_parser(SectionKind::OPTIONAL.map(SectionKind::id))
+                .action(clap::ArgAction::Append),
+        )
+        .arg(
+            Arg::new("memoryReport")
+                .help("report the memory used by hprof-slurp itself after the run")
+                .long("memory-report")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("width")
+                .help("the maximum width of the tables truncated with --truncate, 120 by default")
+                .long("width")
+                .short('w')
+                .requires("truncate")
+                .num_args(1)
+                .value_parser(clap::value_parser!(usize))
+                .required(false),
+        )
+        .arg(
+            Arg::new("ascii")
+                .help("render tables with ASCII characters only")
+                .long("ascii")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("truncate")
+                .help("truncate long class names to fit the table width")
+                .long("truncate")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("siUnits")
+                .help("render sizes in powers of 1000 (kB, MB, GB) instead of 1024 (KiB, MiB, GiB)")
+                .long("si-units")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("quiet")
+                .help("do not display the progress bar")
+                .long("quiet")
+                .short('q')
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("color")
+                .help("colorize the output")
+                .long("color")
+                .num_args(1)
+                .default_value("auto")
+                .value_parser(["auto", "always", "never"])
+                .required(false),
+        )
+        .arg(
+            Arg::new("format")
+                .help("the output format")
+                .long("format")
+                .num_args(1)
+                .default_value("text")
+                .value_parser([
+                    "text",
+                    #[cfg(feature = "exports")]
+                    "md",
+                    #[cfg(feature = "exports")]
+                    "html",
+                    #[cfg(feature = "exports")]
+                    "json",
+                    "jmap-histo",
+                    #[cfg(feature = "exports")]
+                    "visualvm-json",
+                    #[cfg(feature = "exports")]
+                    "visualvm-xml",
+                ])
+                .required(false),
+        )
 }
 
-pub fn get_args() -> Result<(String, usize, bool, bool), HprofSlurpError> {
+pub struct Args {
+    // several files for dumps split in parts
+    pub input_files: Vec<String>,
+    // only write the sidecar index of `input_files`
+    pub index: bool,
+    // only describe `input_files` from their header and first records
+    pub info: bool,
+    // only check the structural invariants of `input_files`
+    pub verify: bool,
+    // only explain the size of the instances of this class
+    pub explain: Option<String>,
+    // only report what removing these objects would free
+    pub what_if: Option<WhatIfQuery>,
+    // only export the fields of the instances of a class
+    pub export_class: Option<ClassExportOptions>,
+    pub top: usize,
+    pub debug: bool,
+    pub list_strings: bool,
+    // the captured strings are streamed to this file instead of the report
+    pub strings_output: Option<PathBuf>,
+    pub histogram_options: HistogramOptions,
+    // baseline profile path or `jdk`
+    pub baseline: Option<String>,
+    pub save_baseline: Option<String>,
+    pub checkpoint: Option<CheckpointOptions>,
+    // estimated memory budget in bytes
+    pub max_memory: Option<u64>,
+    // sanity limits on the declared lengths of the records
+    pub record_limits: RecordLimits,
+    pub dump_index: Option<usize>,
+    // input buffers read ahead of the parser
+    pub readahead_buffers: usize,
+    // bypass the page cache when reading local files
+    pub direct_io: bool,
+    // `dump.idx.index` and `dump.o2hprof.index`, needs the record offsets
+    pub mat_index: bool,
+    // appends the `GraphMetrics` section to the report
+    pub graph_metrics: bool,
+    // appends the `ReferenceCycles` section with this minimum cycle size
+    pub cycles: Option<usize>,
+    // appends the `HeaviestFields` section to the report
+    pub heaviest_fields: bool,
+    // appends the `ReferencingFields` section for this target class
+    pub referencing_fields: Option<String>,
+    // renders `report::auto::auto_report` instead of the default sections
+    pub auto: bool,
+    // salt of the pseudonyms replacing the application class names
+    pub anonymize: Option<String>,
+    // appended after the default sections
+    pub sections: Vec<SectionKind>,
+    pub memory_report: bool,
+    pub table_options: TableOptions,
+    pub color: ColorChoice,
+    pub size_units: SizeUnits,
+    pub format: OutputFormat,
+    // rendered instead of the report, selected with `--format` as well
+    pub export: Option<HeapExport>,
+    pub quiet: bool,
+}
+
+pub fn get_args() -> Result<Args, HprofSlurpError> {
     let matches = command().get_matches();
 
-    let input_file = matches
-        .get_one::<String>("inputFile")
-        .expect("impossible")
-        .trim();
-    if !Path::new(&input_file).is_file() {
-        return Err(InputFileNotFound {
-            name: input_file.to_string(),
-        });
+    let index_matches = matches.subcommand_matches("index");
+    let info_matches = matches.subcommand_matches("info");
+    let verify_matches = matches.subcommand_matches("verify");
+    let explain_matches = matches.subcommand_matches("explain");
+    let what_if_matches = matches.subcommand_matches("what-if");
+    let export_class_matches = matches.subcommand_matches("export-class");
+    let input_arguments = match (
+        index_matches,
+        info_matches,
+        verify_matches,
+        explain_matches,
+        what_if_matches,
+        export_class_matches,
+    ) {
+        (Some(index_matches), _, _, _, _, _) => index_matches.get_many::<String>("indexFile"),
+        (_, Some(info_matches), _, _, _, _) => info_matches.get_many::<String>("infoFile"),
+        (_, _, Some(verify_matches), _, _, _) => verify_matches.get_many::<String>("verifyFile"),
+        (_, _, _, Some(explain_matches), _, _) => explain_matches.get_many::<String>("explainFile"),
+        (_, _, _, _, Some(what_if_matches), _) => what_if_matches.get_many::<String>("whatIfFile"),
+        (_, _, _, _, _, Some(export_matches)) => {
+            export_matches.get_many::<String>("exportClassFile")
+        }
+        _ => matches.get_many::<String>("inputFile"),
+    };
+    let mut input_files = Vec::new();
+    for input_file in input_arguments.expect("impossible") {
+        let input_file = input_file.trim();
+        if is_glob_pattern(input_file) {
+            input_files.extend(expand_glob_pattern(input_file)?);
+            continue;
+        }
+        let local_file = input_file != STDIN_PATH && !is_remote_path(input_file);
+        if local_file && !Path::new(&input_file).is_file() {
+            return Err(InputFileNotFound {
+                name: input_file.to_string(),
+            });
+        }
+        input_files.push(input_file.to_string());
     }
 
     let top: usize = *matches.get_one("top").expect("impossible");
@@ -63,15 +500,359 @@ pub fn get_args() -> Result<(String, usize, bool, bool), HprofSlurpError> {
 
     let debug = matches.get_flag("debug");
     let list_strings = matches.get_flag("listStrings");
-    Ok((input_file.to_string(), top, debug, list_strings))
+    let histogram_options = HistogramOptions {
+        sort: matches
+            .get_one::<String>("sortBy")
+            .expect("impossible")
+            .parse::<HistogramSort>()
+            .expect("impossible"),
+        min_total_size: matches
+            .get_one::<u64>("minTotalSize")
+            .copied()
+            .unwrap_or_default(),
+        min_instances: matches
+            .get_one::<u64>("minInstances")
+            .copied()
+            .unwrap_or_default(),
+        names: *matches
+            .get_one::<NameGrouping>("groupNames")
+            .expect("impossible"),
+        effective_ownership: matches.get_flag("effectiveOwnership"),
+        split_arrays_by_owner: matches.get_flag("splitArraysByOwner"),
+    };
+    let table_options = TableOptions {
+        max_width: matches.get_one::<usize>("width").copied(),
+        ascii_only: matches.get_flag("ascii"),
+        truncate_names: matches.get_flag("truncate"),
+    };
+    let checkpoint = matches
+        .get_one::<String>("checkpoint")
+        .map(|path| CheckpointOptions {
+            path: path.into(),
+            interval: *matches
+                .get_one::<u64>("checkpointInterval")
+                .expect("impossible") as usize,
+        });
+    let default_limits = RecordLimits::default();
+    let record_limits = RecordLimits {
+        // the declared lengths fit in 32 bits
+        max_record_length: matches
+            .get_one::<u64>("maxRecordSize")
+            .map_or(default_limits.max_record_length, |size| {
+                (*size).min(u32::MAX as u64) as u32
+            }),
+        skip_oversized: matches.get_flag("skipOversizedRecords"),
+        ..default_limits
+    };
+    let what_if = what_if_matches.map(|what_if_matches| WhatIfQuery {
+        classes: what_if_matches
+            .get_many::<String>("removeClass")
+            .unwrap_or_default()
+            .cloned()
+            .collect(),
+        object_ids: what_if_matches
+            .get_many::<u64>("removeObject")
+            .unwrap_or_default()
+            .copied()
+            .collect(),
+    });
+    let export_class = export_class_matches.map(|export_matches| ClassExportOptions {
+        class_name: export_matches
+            .get_one::<String>("exportClassName")
+            .expect("impossible")
+            .clone(),
+        fields: export_matches
+            .get_many::<String>("fields")
+            .expect("impossible")
+            .map(|field| field.trim().to_string())
+            .collect(),
+        output: export_matches
+            .get_one::<String>("output")
+            .map(|path| path.into()),
+    });
+    let format = matches.get_one::<String>("format").expect("impossible");
+    let export = format.parse::<HeapExport>().ok();
+    let color = matches
+        .get_one::<String>("color")
+        .expect("impossible")
+        .parse::<ColorChoice>()
+        .expect("impossible");
+    Ok(Args {
+        input_files,
+        index: index_matches.is_some(),
+        info: info_matches.is_some(),
+        verify: verify_matches.is_some(),
+        explain: explain_matches.map(|explain_matches| {
+            explain_matches
+                .get_one::<String>("explainClassName")
+                .expect("impossible")
+                .clone()
+        }),
+        what_if,
+        export_class,
+        top,
+        debug,
+        list_strings,
+        strings_output: matches
+            .get_one::<String>("stringsOutput")
+            .map(|path| path.into()),
+        histogram_options,
+        baseline: matches.get_one::<String>("baseline").cloned(),
+        save_baseline: matches.get_one::<String>("saveBaseline").cloned(),
+        checkpoint,
+        max_memory: matches.get_one::<u64>("maxMemory").copied(),
+        record_limits,
+        dump_index: matches.get_one::<usize>("dumpIndex").copied(),
+        readahead_buffers: *matches.get_one::<u64>("readahead").expect("impossible") as usize,
+        direct_io: matches.get_flag("directIo"),
+        mat_index: matches.get_flag("matIndex"),
+        graph_metrics: matches.get_flag("graphMetrics"),
+        cycles: matches
+            .get_one::<u64>("cycles")
+            .map(|min_size| *min_size as usize),
+        heaviest_fields: matches.get_flag("heaviestFields"),
+        referencing_fields: matches.get_one::<String>("referencingFields").cloned(),
+        auto: matches.get_flag("auto"),
+        anonymize: matches.get_one::<String>("anonymize").map(|salt| {
+            if salt.is_empty() {
+                random_salt()
+            } else {
+                salt.clone()
+            }
+        }),
+        sections: matches
+            .get_many::<String>("section")
+            .unwrap_or_default()
+            .map(|id| {
+                SectionKind::OPTIONAL
+                    .into_iter()
+                    .find(|kind| kind.id() == id)
+                    .expect("impossible")
+            })
+            .collect(),
+        memory_report: matches.get_flag("memoryReport"),
+        table_options,
+        color,
+        size_units: if matches.get_flag("siUnits") {
+            SizeUnits::Si
+        } else {
+            SizeUnits::Binary
+        },
+        format: export.map_or_else(
+            || format.parse::<OutputFormat>().expect("impossible"),
+            |_| OutputFormat::Text,
+        ),
+        export,
+        quiet: matches.get_flag("quiet"),
+    })
+}
+
+fn is_glob_pattern(input: &str) -> bool {
+    input.contains(['*', '?'])
+}
+
+// only the file name may contain wildcards, the parts are sorted in natural order (part2 before part10)
+fn expand_glob_pattern(pattern: &str) -> Result<Vec<String>, HprofSlurpError> {
+    let path = Path::new(pattern);
+    let directory = match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+    let file_pattern = path
+        .file_name()
+        .and_then(|name| name.to_str())
+        .unwrap_or_default();
+    let mut matches: Vec<String> = std::fs::read_dir(directory)?
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.path().is_file())
+        .filter(|entry| {
+            entry
+                .file_name()
+                .to_str()
+                .map(|name| wildcard_match(file_pattern, name))
+                .unwrap_or(false)
+        })
+        .map(|entry| directory.join(entry.file_name()).display().to_string())
+        .collect();
+    if matches.is_empty() {
+        return Err(InputFileNotFound {
+            name: pattern.to_string(),
+        });
+    }
+    matches.sort_by(|a, b| natural_cmp(a, b));
+    Ok(matches)
+}
+
+fn wildcard_match(pattern: &str, name: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let name: Vec<char> = name.chars().collect();
+    // backtrack to the last `*` on mismatch
+    let (mut p, mut n) = (0, 0);
+    let mut star: Option<(usize, usize)> = None;
+    while n < name.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                star = Some((p, n));
+                p += 1;
+            }
+            Some(c) if *c == '?' || *c == name[n] => {
+                p += 1;
+                n += 1;
+            }
+            _ => match star {
+                Some((star_p, star_n)) => {
+                    p = star_p + 1;
+                    n = star_n + 1;
+                    star = Some((star_p, star_n + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|c| *c == '*')
+}
+
+// compares digit runs by value
+fn natural_cmp(a: &str, b: &str) -> Ordering {
+    let chunks = |s: &str| -> Vec<(bool, String)> {
+        let mut chunks: Vec<(bool, String)> = Vec::new();
+        for c in s.chars() {
+            match chunks.last_mut() {
+                Some((digits, chunk)) if *digits == c.is_ascii_digit() => chunk.push(c),
+                _ => chunks.push((c.is_ascii_digit(), c.to_string())),
+            }
+        }
+        chunks
+    };
+    for (x, y) in chunks(a).iter().zip(chunks(b).iter()) {
+        let ordering = match (x, y) {
+            ((true, x), (true, y)) => x
+                .trim_start_matches('0')
+                .len()
+                .cmp(&y.trim_start_matches('0').len())
+                .then_with(|| x.trim_start_matches('0').cmp(y.trim_start_matches('0'))),
+            ((_, x), (_, y)) => x.cmp(y),
+        };
+        if ordering != Ordering::Equal {
+            return ordering;
+        }
+    }
+    a.len().cmp(&b.len()).then_with(|| a.cmp(b))
 }
 
 #[cfg(test)]
 mod args_tests {
-    use crate::args::command;
+    use crate::args::{command, natural_cmp, wildcard_match};
+    use std::cmp::Ordering;
+
+    #[test]
+    fn match_wildcards() {
+        assert!(wildcard_match("dump.hprof.*", "dump.hprof.part1"));
+        assert!(wildcard_match("dump.hprof.part?", "dump.hprof.part1"));
+        assert!(wildcard_match("*.part*", "dump.hprof.part12"));
+        assert!(!wildcard_match("dump.hprof.part?", "dump.hprof.part12"));
+        assert!(!wildcard_match("*.bin", "dump.hprof"));
+    }
+
+    #[test]
+    fn sort_parts_in_natural_order() {
+        let mut parts = vec!["dump.part10", "dump.part2", "dump.part1"];
+        parts.sort_by(|a, b| natural_cmp(a, b));
+        assert_eq!(parts, vec!["dump.part1", "dump.part2", "dump.part10"]);
+        assert_eq!(natural_cmp("part02", "part2"), Ordering::Greater);
+    }
 
     #[test]
     fn verify_command() {
         command().debug_assert();
     }
+
+    #[test]
+    fn anonymize_with_optional_salt() {
+        let anonymize = |args: &[&str]| {
+            command()
+                .try_get_matches_from(args)
+                .unwrap()
+                .get_one::<String>("anonymize")
+                .cloned()
+        };
+        assert_eq!(anonymize(&["hprof-slurp", "-i", "dump.hprof"]), None);
+        assert_eq!(
+            anonymize(&["hprof-slurp", "-i", "dump.hprof", "--anonymize"]),
+            Some(String::new())
+        );
+        assert_eq!(
+            anonymize(&["hprof-slurp", "--anonymize", "s3cr3t", "-i", "dump.hprof"]),
+            Some("s3cr3t".to_string())
+        );
+        // the string contents would keep the real names
+        assert!(command()
+            .try_get_matches_from(["hprof-slurp", "-i", "dump.hprof", "--anonymize", "-l"])
+            .is_err());
+    }
+
+    #[test]
+    fn width_needs_truncate() {
+        assert!(command()
+            .try_get_matches_from(["hprof-slurp", "-i", "dump.hprof", "--width", "80"])
+            .is_err());
+        let matches = command()
+            .try_get_matches_from(["hprof-slurp", "-i", "dump.hprof", "-w", "80", "--truncate"])
+            .unwrap();
+        assert_eq!(matches.get_one::<usize>("width"), Some(&80));
+    }
+
+    #[test]
+    fn what_if_needs_objects_to_remove() {
+        assert!(command()
+            .try_get_matches_from(["hprof-slurp", "what-if", "dump.hprof"])
+            .is_err());
+        let matches = command()
+            .try_get_matches_from([
+                "hprof-slurp",
+                "what-if",
+                "dump.hprof",
+                "--remove-object",
+                "0x10",
+                "--remove-object",
+                "32",
+            ])
+            .unwrap();
+        let ids: Vec<u64> = matches
+            .subcommand_matches("what-if")
+            .unwrap()
+            .get_many::<u64>("removeObject")
+            .unwrap()
+            .copied()
+            .collect();
+        assert_eq!(ids, vec![16, 32]);
+    }
+
+    #[test]
+    fn export_class_fields() {
+        assert!(command()
+            .try_get_matches_from(["hprof-slurp", "export-class", "com.foo.Order", "dump.hprof"])
+            .is_err());
+        let matches = command()
+            .try_get_matches_from([
+                "hprof-slurp",
+                "export-class",
+                "com.foo.Order",
+                "dump.hprof",
+                "--fields",
+                "id,status,total",
+                "-o",
+                "orders.csv",
+            ])
+            .unwrap();
+        let export_matches = matches.subcommand_matches("export-class").unwrap();
+        let fields: Vec<&String> = export_matches.get_many("fields").unwrap().collect();
+        assert_eq!(fields, ["id", "status", "total"]);
+        assert_eq!(
+            export_matches
+                .get_one::<String>("output")
+                .map(|o| o.as_str()),
+            Some("orders.csv")
+        );
+    }
 }
@@ -0,0 +1,213 @@
+use std::collections::VecDeque;
+use std::future::Future;
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll, Waker};
+use std::thread;
+
+use crate::errors::HprofSlurpError;
+use crate::progress::Progress;
+use crate::slurp::{slurp_file_with_options, SlurpOptions};
+use crate::Heap;
+
+/// Slurps a file on dedicated threads, awaiting the returned future does not block the runtime.
+///
+/// The future does not depend on a specific runtime, it is woken up by the slurping thread.
+pub fn slurp_file_async(file_path: String) -> SlurpFuture {
+    slurp_file_async_with_options(file_path, SlurpOptions::default())
+}
+
+/// Like `slurp_file_async`, the progress is reported by `SlurpFuture::progress` instead of `progress_listener`.
+pub fn slurp_file_async_with_options(file_path: String, mut options: SlurpOptions) -> SlurpFuture {
+    let result = AsyncQueue::default();
+    let progress = AsyncQueue::default();
+    let (send_progress, receive_progress) = crossbeam_channel::unbounded();
+    options.progress_listener = Some(send_progress);
+
+    let forwarded = progress.clone();
+    let forwarder = thread::Builder::new()
+        .name("hprof-progress".to_string())
+        .spawn(move || {
+            // ends once the slurping thread drops the options holding the sender
+            while let Ok(message) = receive_progress.recv() {
+                forwarded.push(message);
+            }
+            forwarded.close();
+        });
+    let slurped = result.clone();
+    let slurper = thread::Builder::new()
+        .name("hprof-slurp".to_string())
+        .spawn(move || {
+            // a panic is reported as an error instead of leaving the future pending forever
+            let heap = catch_unwind(AssertUnwindSafe(|| {
+                slurp_file_with_options(file_path, options)
+            }))
+            .unwrap_or_else(|e| Err(HprofSlurpError::StdThreadError { e }));
+            slurped.push(heap);
+        });
+    match (forwarder, slurper) {
+        (Ok(_), Ok(_)) => (),
+        (Err(e), _) | (_, Err(e)) => {
+            progress.close();
+            result.push(Err(e.into()));
+        }
+    }
+    SlurpFuture {
+        result,
+        progress: Some(ProgressStream { queue: progress }),
+    }
+}
+
+/// Resolves to the slurped `Heap` once the pipeline completes.
+pub struct SlurpFuture {
+    result: AsyncQueue<Result<Heap, HprofSlurpError>>,
+    progress: Option<ProgressStream>,
+}
+
+impl SlurpFuture {
+    /// Takes the progress of the slurping, to await alongside the future.
+    pub fn progress(&mut self) -> Option<ProgressStream> {
+        self.progress.take()
+    }
+}
+
+impl Future for SlurpFuture {
+    type Output = Result<Heap, HprofSlurpError>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        self.result
+            .poll_pop(cx)
+            .map(|result| result.expect("the slurping thread always sends a result"))
+    }
+}
+
+/// Progress of an ongoing `SlurpFuture`, ends once the slurping is done.
+pub struct ProgressStream {
+    queue: AsyncQueue<Progress>,
+}
+
+impl ProgressStream {
+    pub async fn next(&mut self) -> Option<Progress> {
+        std::future::poll_fn(|cx| self.queue.poll_pop(cx)).await
+    }
+}
+
+impl Drop for ProgressStream {
+    fn drop(&mut self) {
+        // later progress is discarded instead of queued
+        self.queue.close();
+    }
+}
+
+// unbounded queue with a single consumer woken up on push
+struct AsyncQueue<T> {
+    shared: Arc<Mutex<QueueState<T>>>,
+}
+
+struct QueueState<T> {
+    items: VecDeque<T>,
+    closed: bool,
+    waker: Option<Waker>,
+}
+
+impl<T> Default for AsyncQueue<T> {
+    fn default() -> Self {
+        AsyncQueue {
+            shared: Arc::new(Mutex::new(QueueState {
+                items: VecDeque::new(),
+                closed: false,
+                waker: None,
+            })),
+        }
+    }
+}
+
+impl<T> Clone for AsyncQueue<T> {
+    fn clone(&self) -> Self {
+        AsyncQueue {
+            shared: self.shared.clone(),
+        }
+    }
+}
+
+impl<T> AsyncQueue<T> {
+    fn push(&self, item: T) {
+        let mut state = self
+            .shared
+            .lock()
+            .expect("queue lock should not be poisoned");
+        if !state.closed {
+            state.items.push_back(item);
+        }
+        if let Some(waker) = state.waker.take() {
+            waker.wake();
+        }
+    }
+
+    fn close(&self) {
+        let mut state = self
+            .shared
+            .lock()
+            .expect("queue lock should not be poisoned");
+        state.closed = true;
+        if let Some(waker) = state.waker.take() {
+            waker.wake();
+        }
+    }
+
+    // `None` once closed and drained
+    fn poll_pop(&self, cx: &mut Context<'_>) -> Poll<Option<T>> {
+        let mut state = self
+            .shared
+            .lock()
+            .expect("queue lock should not be poisoned");
+        match state.items.pop_front() {
+            Some(item) => Poll::Ready(Some(item)),
+            None if state.closed => Poll::Ready(None),
+            None => {
+                state.waker = Some(cx.waker().clone());
+                Poll::Pending
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::task::Wake;
+
+    struct ThreadWaker(thread::Thread);
+
+    impl Wake for ThreadWaker {
+        fn wake(self: Arc<Self>) {
+            self.0.unpark();
+        }
+    }
+
+    // minimal executor parking the test thread between polls
+    fn block_on<F: Future>(future: F) -> F::Output {
+        let mut future = std::pin::pin!(future);
+        let waker = Waker::from(Arc::new(ThreadWaker(thread::current())));
+        let mut cx = Context::from_waker(&waker);
+        loop {
+            match future.as_mut().poll(&mut cx) {
+                Poll::Ready(output) => return output,
+                Poll::Pending => thread::park(),
+            }
+        }
+    }
+
+    #[test]
+    fn await_slurp_failure_and_progress_end() {
+        let mut slurp = slurp_file_async("does-not-exist.hprof".to_string());
+        let mut progress = slurp.progress().expect("progress is taken once");
+        assert!(slurp.progress().is_none());
+        assert!(matches!(
+            block_on(slurp),
+            Err(HprofSlurpError::StdIoError { .. })
+        ));
+        assert_eq!(block_on(progress.next()), None);
+    }
+}
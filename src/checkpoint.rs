@@ -0,0 +1,112 @@
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Write};
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::errors::HprofSlurpError;
+use crate::errors::HprofSlurpError::*;
+use crate::parser::record_stream_parser::StreamPosition;
+use crate::result_recorder::ResultRecorder;
+
+// bumped whenever the recorder state changes shape
+const CHECKPOINT_VERSION: u32 = 6;
+
+// identifies the input the checkpoint was taken from
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq)]
+struct CheckpointHeader {
+    version: u32,
+    input_files: Vec<String>,
+    input_len: Option<usize>,
+    position: StreamPosition,
+}
+
+/// Saves the recorder state reached at `position`, replacing the previous checkpoint atomically.
+pub fn write_checkpoint(
+    path: &Path,
+    input_files: &[String],
+    input_len: Option<usize>,
+    position: StreamPosition,
+    recorder: &ResultRecorder,
+) -> Result<(), HprofSlurpError> {
+    let header = CheckpointHeader {
+        version: CHECKPOINT_VERSION,
+        input_files: input_files.to_vec(),
+        input_len,
+        position,
+    };
+    let partial_path = path.with_extension("partial");
+    let mut writer = BufWriter::new(File::create(&partial_path)?);
+    bincode::serialize_into(&mut writer, &header).map_err(checkpoint_error)?;
+    bincode::serialize_into(&mut writer, recorder).map_err(checkpoint_error)?;
+    writer.flush()?;
+    drop(writer);
+    std::fs::rename(partial_path, path)?;
+    Ok(())
+}
+
+/// Loads the input length, position and recorder state of a previous run on the same input files,
+/// `None` if there is no checkpoint.
+pub fn read_checkpoint(
+    path: &Path,
+    input_files: &[String],
+) -> Result<Option<(Option<usize>, StreamPosition, ResultRecorder)>, HprofSlurpError> {
+    if !path.exists() {
+        return Ok(None);
+    }
+    let mut reader = BufReader::new(File::open(path)?);
+    let header: CheckpointHeader =
+        bincode::deserialize_from(&mut reader).map_err(checkpoint_error)?;
+    if header.version != CHECKPOINT_VERSION {
+        return Err(InvalidCheckpoint {
+            message: format!(
+                "version {} is not supported, expected {}",
+                header.version, CHECKPOINT_VERSION
+            ),
+        });
+    }
+    if header.input_files != input_files {
+        return Err(InvalidCheckpoint {
+            message: format!(
+                "taken from {:?}, delete it to start over",
+                header.input_files
+            ),
+        });
+    }
+    let recorder: ResultRecorder =
+        bincode::deserialize_from(&mut reader).map_err(checkpoint_error)?;
+    Ok(Some((header.input_len, header.position, recorder)))
+}
+
+fn checkpoint_error(e: bincode::Error) -> HprofSlurpError {
+    InvalidCheckpoint {
+        message: e.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::slurp::SlurpOptions;
+
+    #[test]
+    fn checkpoint_round_trip() {
+        let path =
+            std::env::temp_dir().join(format!("hprof-slurp-{}.checkpoint", std::process::id()));
+        let input_files = vec!["dump.hprof".to_string()];
+        let position = StreamPosition {
+            offset: 1024,
+            heap_dump_remaining_len: 42,
+        };
+        let recorder = ResultRecorder::new(8, SlurpOptions::default());
+        write_checkpoint(&path, &input_files, Some(4096), position, &recorder).unwrap();
+
+        let (input_len, resumed_position, _) =
+            read_checkpoint(&path, &input_files).unwrap().unwrap();
+        assert_eq!(input_len, Some(4096));
+        assert_eq!(resumed_position, position);
+        assert!(read_checkpoint(&path, &["other.hprof".to_string()]).is_err());
+        std::fs::remove_file(&path).unwrap();
+        assert!(read_checkpoint(&path, &input_files).unwrap().is_none());
+    }
+}
@@ -0,0 +1,350 @@
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use crate::errors::HprofSlurpError;
+use crate::errors::HprofSlurpError::*;
+use crate::parser::gc_record::FieldValue;
+#[cfg(feature = "exports")]
+use crate::report::json_string;
+use crate::result_recorder::Instance;
+use crate::Heap;
+
+// exported as the primitive they wrap
+const BOXED_CLASSES: &[&str] = &[
+    "java.lang.Boolean",
+    "java.lang.Byte",
+    "java.lang.Character",
+    "java.lang.Short",
+    "java.lang.Integer",
+    "java.lang.Long",
+    "java.lang.Float",
+    "java.lang.Double",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ClassExportFormat {
+    Csv,
+    #[cfg(feature = "exports")]
+    Json,
+}
+
+impl ClassExportFormat {
+    /// JSON for `.json` files, CSV otherwise.
+    pub fn from_path(path: Option<&Path>) -> Self {
+        match path.and_then(|p| p.extension()) {
+            #[cfg(feature = "exports")]
+            Some(extension) if extension.eq_ignore_ascii_case("json") => ClassExportFormat::Json,
+            _ => ClassExportFormat::Csv,
+        }
+    }
+}
+
+/// The instances to export with `export-class`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ClassExportOptions {
+    pub class_name: String,
+    // dotted paths follow references, e.g. `customer.name`
+    pub fields: Vec<String>,
+    // stdout if missing
+    pub output: Option<PathBuf>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Cell {
+    Null,
+    Number(String),
+    Text(String),
+}
+
+/// Writes a row per instance of `class_name` or of its subclasses, ordered by object id, with the object id
+/// and the values of `fields`. Returns the number of rows written.
+///
+/// Strings and enums are exported as their content, boxed primitives as their value and other objects as
+/// `class@id`. Missing fields and null references are empty in CSV and `null` in JSON.
+pub fn export_class<W: Write>(
+    heap: &Heap,
+    class_name: &str,
+    fields: &[String],
+    format: ClassExportFormat,
+    writer: &mut W,
+) -> Result<u64, HprofSlurpError> {
+    if !heap
+        .classes_dump
+        .keys()
+        .any(|class_id| heap.class_name(*class_id).as_deref() == Some(class_name))
+    {
+        return Err(ClassNotFound {
+            name: class_name.to_string(),
+        });
+    }
+    let mut instances: Vec<&Instance> = heap
+        .instances_of(class_name)
+        .map(|instance| instance.as_ref())
+        .collect();
+    instances.sort_unstable_by_key(|instance| instance.object_id);
+    let rows = instances.iter().map(|instance| {
+        let cells: Vec<Cell> = fields
+            .iter()
+            .map(|path| field_cell(heap, instance, path))
+            .collect();
+        (instance.object_id, cells)
+    });
+    match format {
+        ClassExportFormat::Csv => {
+            let header: Vec<String> = std::iter::once("object_id")
+                .chain(fields.iter().map(|f| f.as_str()))
+                .map(csv_field)
+                .collect();
+            writeln!(writer, "{}", header.join(","))?;
+            for (object_id, cells) in rows {
+                let mut line = format!("0x{:x}", object_id);
+                for cell in cells {
+                    line.push(',');
+                    match cell {
+                        Cell::Null => (),
+                        Cell::Number(number) => line.push_str(&number),
+                        Cell::Text(text) => line.push_str(&csv_field(&text)),
+                    }
+                }
+                writeln!(writer, "{}", line)?;
+            }
+        }
+        #[cfg(feature = "exports")]
+        ClassExportFormat::Json => {
+            writeln!(writer, "[")?;
+            let mut first = true;
+            for (object_id, cells) in rows {
+                let mut line = format!("{{\"object_id\":\"0x{:x}\"", object_id);
+                for (field, cell) in fields.iter().zip(cells) {
+                    let value = match cell {
+                        Cell::Null => "null".to_string(),
+                        Cell::Number(number) => number,
+                        Cell::Text(text) => json_string(&text),
+                    };
+                    line.push_str(&format!(",{}:{}", json_string(field), value));
+                }
+                line.push('}');
+                if !first {
+                    writeln!(writer, ",")?;
+                }
+                write!(writer, "{}", line)?;
+                first = false;
+            }
+            if !first {
+                writeln!(writer)?;
+            }
+            writeln!(writer, "]")?;
+        }
+    }
+    Ok(instances.len() as u64)
+}
+
+// the references of a dotted path are followed up to its last field
+fn field_cell(heap: &Heap, instance: &Instance, path: &str) -> Cell {
+    let mut current = instance;
+    let mut names = path.split('.').peekable();
+    while let Some(name) = names.next() {
+        if names.peek().is_none() {
+            return match heap.field_value(current, name) {
+                Some(value) => value_cell(heap, value),
+                None => Cell::Null,
+            };
+        }
+        match heap.field_instance(current, name) {
+            Some(next) => current = next,
+            None => return Cell::Null,
+        }
+    }
+    Cell::Null
+}
+
+// the cell as text, `None` for nulls
+#[cfg(feature = "dataframe")]
+pub(crate) fn field_text(heap: &Heap, instance: &Instance, path: &str) -> Option<String> {
+    match field_cell(heap, instance, path) {
+        Cell::Null => None,
+        Cell::Number(text) | Cell::Text(text) => Some(text),
+    }
+}
+
+fn value_cell(heap: &Heap, value: &FieldValue) -> Cell {
+    match value {
+        FieldValue::Bool(value) => Cell::Number(value.to_string()),
+        FieldValue::Char(value) => Cell::Text(String::from_utf16_lossy(&[*value])),
+        // JSON has no representation for them
+        FieldValue::Float(value) if !value.is_finite() => Cell::Text(value.to_string()),
+        FieldValue::Double(value) if !value.is_finite() => Cell::Text(value.to_string()),
+        FieldValue::Float(value) => Cell::Number(value.to_string()),
+        FieldValue::Double(value) => Cell::Number(value.to_string()),
+        FieldValue::Object(0) => Cell::Null,
+        FieldValue::Object(object_id) => object_cell(heap, *object_id),
+        integral => Cell::Number(integral.as_i64().unwrap_or_default().to_string()),
+    }
+}
+
+fn object_cell(heap: &Heap, object_id: u64) -> Cell {
+    let Some(instance) = heap.instances_pool.get(&object_id) else {
+        return Cell::Text(format!("0x{:x}", object_id));
+    };
+    let class_name = heap.instance_class_name(instance);
+    if class_name == "java.lang.String" {
+        if let Some(text) = heap.string_value(object_id) {
+            return Cell::Text(text);
+        }
+    } else if BOXED_CLASSES.contains(&class_name.as_str()) {
+        if let Some(value) = heap.field_value(instance, "value") {
+            return value_cell(heap, value);
+        }
+    } else if heap.is_subclass_of(instance.class_object_id, "java.lang.Enum") {
+        if let Some(name) = heap.enum_name(instance) {
+            return Cell::Text(name);
+        }
+    }
+    Cell::Text(format!("{}@0x{:x}", class_name, object_id))
+}
+
+// quoted if it holds a separator, a quote or a line break
+fn csv_field(text: &str) -> String {
+    if text.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", text.replace('"', "\"\""))
+    } else {
+        text.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::gc_record::ClassDumpFields;
+    use crate::parser::record::LoadClassData;
+    use crate::tests::{add_instance, reference, single};
+
+    // field name ids
+    const ID: u64 = 1;
+    const NOTE: u64 = 2;
+    const TOTAL: u64 = 3;
+    const CUSTOMER: u64 = 4;
+    const NAME: u64 = 5;
+    const VALUE: u64 = 6;
+
+    #[test]
+    fn export_instance_fields() {
+        let mut heap = Heap::default();
+        for (name_id, name) in [
+            (ID, "id"),
+            (NOTE, "note"),
+            (TOTAL, "total"),
+            (CUSTOMER, "customer"),
+            (NAME, "name"),
+            (VALUE, "value"),
+            (10, "com/acme/Order"),
+            (11, "com/acme/Customer"),
+            (12, "java/lang/Double"),
+        ] {
+            heap.utf8_strings.make_mut().insert(name_id, name.into());
+        }
+        for (serial_number, class_object_id, class_name_id) in
+            [(1, 100, 10), (2, 101, 11), (3, 102, 12)]
+        {
+            heap.class_registry.make_mut().load(LoadClassData {
+                serial_number,
+                class_object_id,
+                stack_trace_serial_number: 0,
+                class_name_id,
+            });
+            heap.classes_dump.make_mut().insert(
+                class_object_id,
+                ClassDumpFields::new(
+                    class_object_id,
+                    0,
+                    0,
+                    0,
+                    0,
+                    Vec::new(),
+                    Vec::new(),
+                    Vec::new(),
+                ),
+            );
+        }
+        add_instance(
+            &mut heap,
+            30,
+            102,
+            vec![single(VALUE, FieldValue::Double(9.5))],
+        );
+        add_instance(&mut heap, 20, 101, vec![reference(NAME, 0)]);
+        add_instance(
+            &mut heap,
+            2,
+            100,
+            vec![
+                single(ID, FieldValue::Long(7)),
+                reference(TOTAL, 30),
+                reference(CUSTOMER, 20),
+            ],
+        );
+        add_instance(
+            &mut heap,
+            1,
+            100,
+            vec![
+                single(ID, FieldValue::Long(6)),
+                reference(NOTE, 20),
+                reference(TOTAL, 0),
+                reference(CUSTOMER, 0),
+            ],
+        );
+
+        let fields: Vec<String> = ["id", "total", "note", "customer.name"]
+            .iter()
+            .map(|f| f.to_string())
+            .collect();
+        let mut csv = Vec::new();
+        let rows = export_class(
+            &heap,
+            "com.acme.Order",
+            &fields,
+            ClassExportFormat::Csv,
+            &mut csv,
+        )
+        .unwrap();
+        assert_eq!(rows, 2);
+        assert_eq!(
+            String::from_utf8(csv).unwrap(),
+            "object_id,id,total,note,customer.name\n0x1,6,,com.acme.Customer@0x14,\n0x2,7,9.5,,\n"
+        );
+
+        #[cfg(feature = "exports")]
+        {
+            let mut json = Vec::new();
+            export_class(
+                &heap,
+                "com.acme.Order",
+                &fields[..2],
+                ClassExportFormat::Json,
+                &mut json,
+            )
+            .unwrap();
+            assert_eq!(
+                String::from_utf8(json).unwrap(),
+                "[\n{\"object_id\":\"0x1\",\"id\":6,\"total\":null},\n{\"object_id\":\"0x2\",\"id\":7,\"total\":9.5}\n]\n"
+            );
+            assert_eq!(
+                ClassExportFormat::from_path(Some(Path::new("orders.json"))),
+                ClassExportFormat::Json
+            );
+        }
+
+        assert!(matches!(
+            export_class(
+                &heap,
+                "com.acme.Missing",
+                &fields,
+                ClassExportFormat::Csv,
+                &mut Vec::new()
+            ),
+            Err(ClassNotFound { .. })
+        ));
+        assert_eq!(csv_field("a,\"b\""), "\"a,\"\"b\"\"\"");
+    }
+}
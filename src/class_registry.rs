@@ -0,0 +1,242 @@
+use std::collections::{HashMap, HashSet};
+
+use serde::{Deserialize, Serialize};
+
+use crate::parser::record::LoadClassData;
+
+/// The classes declared by `LoadClass` records, looked up by object id, serial number or name.
+///
+/// A class object id can be reused by a class loaded after another one was unloaded, the id then
+/// resolves to the latest class while the serial numbers of both keep resolving to their own class.
+/// An id whose latest class was unloaded does not resolve, see `unloaded_by_id`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ClassRegistry {
+    by_serial_number: HashMap<u32, LoadClassData>,
+    // class object id -> serial number of the latest class loaded at this id
+    serial_number_by_id: HashMap<u64, u32>,
+    // class name id -> serial numbers in loading order
+    serial_numbers_by_name_id: HashMap<u64, Vec<u32>>,
+    // serial numbers of the classes unloaded before the heap dump
+    unloaded: HashSet<u32>,
+}
+
+impl ClassRegistry {
+    pub fn load(&mut self, class: LoadClassData) {
+        let serial_number = class.serial_number;
+        let class_object_id = class.class_object_id;
+        let class_name_id = class.class_name_id;
+        if let Some(previous) = self.by_serial_number.insert(serial_number, class) {
+            // a serial number declared twice only keeps its latest class
+            if previous.class_name_id != class_name_id {
+                if let Some(serial_numbers) = self
+                    .serial_numbers_by_name_id
+                    .get_mut(&previous.class_name_id)
+                {
+                    serial_numbers.retain(|serial| *serial != serial_number);
+                }
+            }
+            if self.serial_number_by_id.get(&previous.class_object_id) == Some(&serial_number) {
+                self.serial_number_by_id.remove(&previous.class_object_id);
+            }
+        }
+        self.serial_number_by_id
+            .insert(class_object_id, serial_number);
+        let serial_numbers = self
+            .serial_numbers_by_name_id
+            .entry(class_name_id)
+            .or_default();
+        if !serial_numbers.contains(&serial_number) {
+            serial_numbers.push(serial_number);
+        }
+    }
+
+    /// Marks a class as unloaded, its serial number keeps resolving for the stack frames recorded before.
+    pub fn unload(&mut self, serial_number: u32) {
+        self.unloaded.insert(serial_number);
+    }
+
+    pub fn is_unloaded(&self, serial_number: u32) -> bool {
+        self.unloaded.contains(&serial_number)
+    }
+
+    pub fn unloaded_count(&self) -> usize {
+        self.unloaded.len()
+    }
+
+    /// Returns the latest class loaded with this class object id, unless it was unloaded.
+    pub fn by_id(&self, class_object_id: u64) -> Option<&LoadClassData> {
+        self.latest_by_id(class_object_id)
+            .filter(|class| !self.is_unloaded(class.serial_number))
+    }
+
+    /// Returns the latest class loaded with this class object id if it was unloaded.
+    pub fn unloaded_by_id(&self, class_object_id: u64) -> Option<&LoadClassData> {
+        self.latest_by_id(class_object_id)
+            .filter(|class| self.is_unloaded(class.serial_number))
+    }
+
+    fn latest_by_id(&self, class_object_id: u64) -> Option<&LoadClassData> {
+        self.by_serial_number
+            .get(self.serial_number_by_id.get(&class_object_id)?)
+    }
+
+    pub fn by_serial_number(&self, serial_number: u32) -> Option<&LoadClassData> {
+        self.by_serial_number.get(&serial_number)
+    }
+
+    pub fn serial_number(&self, class_object_id: u64) -> Option<u32> {
+        self.serial_number_by_id.get(&class_object_id).copied()
+    }
+
+    pub fn object_id(&self, serial_number: u32) -> Option<u64> {
+        self.by_serial_number(serial_number)
+            .map(|class| class.class_object_id)
+    }
+
+    /// Classes named by the UTF-8 string `class_name_id`, in loading order.
+    pub fn by_name_id(&self, class_name_id: u64) -> impl Iterator<Item = &LoadClassData> + '_ {
+        self.serial_numbers_by_name_id
+            .get(&class_name_id)
+            .into_iter()
+            .flatten()
+            .filter_map(|serial_number| self.by_serial_number.get(serial_number))
+    }
+
+    pub fn contains_id(&self, class_object_id: u64) -> bool {
+        self.by_id(class_object_id).is_some()
+    }
+
+    /// Number of loaded class object ids, a reused id is counted once.
+    pub fn len(&self) -> usize {
+        self.iter().count()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.iter().next().is_none()
+    }
+
+    /// Iterates over the latest class of each class object id, unloaded classes excluded.
+    pub fn iter(&self) -> impl Iterator<Item = &LoadClassData> + '_ {
+        self.serial_number_by_id
+            .values()
+            .filter(|serial_number| !self.is_unloaded(**serial_number))
+            .map(|serial_number| &self.by_serial_number[serial_number])
+    }
+
+    /// Ids of the strings naming a class, unloaded classes included.
+    pub fn class_name_ids(&self) -> impl Iterator<Item = u64> + '_ {
+        self.serial_numbers_by_name_id.keys().copied()
+    }
+
+    /// Estimates the memory held by the lookup tables.
+    pub fn estimated_bytes(&self) -> u64 {
+        let serial_numbers: usize = self
+            .serial_numbers_by_name_id
+            .values()
+            .map(|serials| serials.capacity() * size_of::<u32>())
+            .sum();
+        (self.by_serial_number.capacity() * (size_of::<(u32, LoadClassData)>() + 1)
+            + self.serial_number_by_id.capacity() * (size_of::<(u64, u32)>() + 1)
+            + self.serial_numbers_by_name_id.capacity() * (size_of::<(u64, Vec<u32>)>() + 1)
+            + self.unloaded.capacity() * (size_of::<u32>() + 1)
+            + serial_numbers) as u64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn class(serial_number: u32, class_object_id: u64, class_name_id: u64) -> LoadClassData {
+        LoadClassData {
+            serial_number,
+            class_object_id,
+            stack_trace_serial_number: 0,
+            class_name_id,
+        }
+    }
+
+    #[test]
+    fn lookup_by_id_serial_and_name() {
+        let mut registry = ClassRegistry::default();
+        registry.load(class(1, 0x100, 10));
+        registry.load(class(2, 0x200, 20));
+        assert_eq!(registry.len(), 2);
+        assert_eq!(registry.serial_number(0x200), Some(2));
+        assert_eq!(registry.object_id(1), Some(0x100));
+        assert_eq!(registry.by_id(0x100).map(|c| c.class_name_id), Some(10));
+        assert_eq!(
+            registry
+                .by_name_id(20)
+                .map(|c| c.serial_number)
+                .collect::<Vec<_>>(),
+            vec![2]
+        );
+        assert!(registry.by_id(0x300).is_none());
+        assert!(registry.by_serial_number(3).is_none());
+    }
+
+    #[test]
+    fn class_reloaded_repeatedly() {
+        let mut registry = ClassRegistry::default();
+        // the same class reloaded at new ids, then another class reusing a freed id
+        registry.load(class(1, 0x100, 10));
+        registry.load(class(2, 0x200, 10));
+        registry.load(class(3, 0x300, 10));
+        registry.load(class(4, 0x100, 20));
+
+        assert_eq!(registry.len(), 3);
+        assert_eq!(registry.by_id(0x100).map(|c| c.serial_number), Some(4));
+        // serial numbers of older loads still resolve to their own class
+        assert_eq!(
+            registry.by_serial_number(1).map(|c| c.class_name_id),
+            Some(10)
+        );
+        assert_eq!(registry.object_id(1), Some(0x100));
+        assert_eq!(
+            registry
+                .by_name_id(10)
+                .map(|c| c.serial_number)
+                .collect::<Vec<_>>(),
+            vec![1, 2, 3]
+        );
+        let mut latest: Vec<u32> = registry.iter().map(|c| c.serial_number).collect();
+        latest.sort_unstable();
+        assert_eq!(latest, vec![2, 3, 4]);
+    }
+
+    #[test]
+    fn serial_number_declared_twice() {
+        let mut registry = ClassRegistry::default();
+        registry.load(class(1, 0x100, 10));
+        registry.load(class(1, 0x100, 20));
+        assert_eq!(registry.by_name_id(10).count(), 0);
+        assert_eq!(registry.by_name_id(20).count(), 1);
+        assert_eq!(registry.by_id(0x100).map(|c| c.class_name_id), Some(20));
+    }
+
+    #[test]
+    fn class_unloaded_then_id_reused() {
+        let mut registry = ClassRegistry::default();
+        registry.load(class(1, 0x100, 10));
+        registry.load(class(2, 0x200, 20));
+        registry.unload(1);
+
+        assert!(registry.by_id(0x100).is_none());
+        assert_eq!(
+            registry.unloaded_by_id(0x100).map(|c| c.serial_number),
+            Some(1)
+        );
+        assert_eq!(
+            registry.by_serial_number(1).map(|c| c.class_name_id),
+            Some(10)
+        );
+        assert_eq!(registry.len(), 1);
+        assert_eq!(registry.unloaded_count(), 1);
+
+        registry.load(class(3, 0x100, 30));
+        assert_eq!(registry.by_id(0x100).map(|c| c.class_name_id), Some(30));
+        assert!(registry.unloaded_by_id(0x100).is_none());
+        assert_eq!(registry.len(), 2);
+    }
+}
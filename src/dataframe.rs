@@ -0,0 +1,164 @@
+use polars::prelude::{Column, DataFrame, PolarsResult};
+
+use crate::analysis::baseline::BaselineDiffReport;
+use crate::analysis::class_names::pretty_class_name;
+use crate::analysis::histogram::ClassHistogram;
+use crate::class_export::field_text;
+use crate::result_recorder::Instance;
+use crate::Heap;
+
+impl ClassHistogram {
+    /// A row per class with the `class_name`, `instances`, `largest`, `total_size` and `retained_size` columns,
+    /// the retained size is null unless computed.
+    pub fn to_dataframe(&self) -> PolarsResult<DataFrame> {
+        let classes = &self.classes;
+        DataFrame::new(
+            classes.len(),
+            vec![
+                Column::new(
+                    "class_name".into(),
+                    classes
+                        .iter()
+                        .map(|c| pretty_class_name(&c.class_name))
+                        .collect::<Vec<_>>(),
+                ),
+                Column::new(
+                    "instances".into(),
+                    classes.iter().map(|c| c.instances).collect::<Vec<_>>(),
+                ),
+                Column::new(
+                    "largest".into(),
+                    classes.iter().map(|c| c.largest).collect::<Vec<_>>(),
+                ),
+                Column::new(
+                    "total_size".into(),
+                    classes.iter().map(|c| c.total_size).collect::<Vec<_>>(),
+                ),
+                Column::new(
+                    "retained_size".into(),
+                    classes.iter().map(|c| c.retained_size).collect::<Vec<_>>(),
+                ),
+            ],
+        )
+    }
+}
+
+impl BaselineDiffReport {
+    /// A row per growing class with the `class_name`, `instances`, `baseline_instances` and `growth` columns.
+    pub fn to_dataframe(&self) -> PolarsResult<DataFrame> {
+        let classes = &self.classes;
+        DataFrame::new(
+            classes.len(),
+            vec![
+                Column::new(
+                    "class_name".into(),
+                    classes
+                        .iter()
+                        .map(|c| pretty_class_name(&c.class_name))
+                        .collect::<Vec<_>>(),
+                ),
+                Column::new(
+                    "instances".into(),
+                    classes.iter().map(|c| c.instances).collect::<Vec<_>>(),
+                ),
+                Column::new(
+                    "baseline_instances".into(),
+                    classes
+                        .iter()
+                        .map(|c| c.baseline_instances)
+                        .collect::<Vec<_>>(),
+                ),
+                Column::new(
+                    "growth".into(),
+                    classes.iter().map(|c| c.growth).collect::<Vec<_>>(),
+                ),
+            ],
+        )
+    }
+}
+
+/// A row per instance of `class_name` or of its subclasses, ordered by object id, with the `object_id` column
+/// and a column per field of `fields`.
+///
+/// The fields are resolved like with `class_export::export_class` into text columns, null for null references
+/// and missing fields, to be cast in polars.
+pub fn instances_dataframe(
+    heap: &Heap,
+    class_name: &str,
+    fields: &[String],
+) -> PolarsResult<DataFrame> {
+    let mut instances: Vec<&Instance> = heap
+        .instances_of(class_name)
+        .map(|instance| instance.as_ref())
+        .collect();
+    instances.sort_unstable_by_key(|instance| instance.object_id);
+    let mut columns = vec![Column::new(
+        "object_id".into(),
+        instances.iter().map(|i| i.object_id).collect::<Vec<_>>(),
+    )];
+    for field in fields {
+        columns.push(Column::new(
+            field.as_str().into(),
+            instances
+                .iter()
+                .map(|instance| field_text(heap, instance, field))
+                .collect::<Vec<_>>(),
+        ));
+    }
+    DataFrame::new(instances.len(), columns)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::analysis::baseline::BaselineDiff;
+    use crate::analysis::histogram::ClassHistogramEntry;
+
+    #[test]
+    fn histogram_and_diff_frames() {
+        let histogram = ClassHistogram {
+            total_size: 48,
+            classes: vec![
+                ClassHistogramEntry {
+                    class_name: "com.acme.Cache$Entry".to_string(),
+                    instances: 2,
+                    largest: 24,
+                    total_size: 40,
+                    retained_size: Some(64),
+                },
+                ClassHistogramEntry {
+                    class_name: "byte[]".to_string(),
+                    instances: 1,
+                    largest: 8,
+                    total_size: 8,
+                    retained_size: None,
+                },
+            ],
+        };
+        let frame = histogram.to_dataframe().unwrap();
+        assert_eq!(frame.shape(), (2, 5));
+        let names = frame.column("class_name").unwrap();
+        assert_eq!(names.str().unwrap().get(0), Some("com.acme.Cache.Entry"));
+        assert_eq!(frame.column("retained_size").unwrap().null_count(), 1);
+        assert_eq!(
+            frame.column("total_size").unwrap().u64().unwrap().get(0),
+            Some(40)
+        );
+
+        let diff = BaselineDiffReport {
+            total_growth: 16,
+            classes: vec![BaselineDiff {
+                class_name: "byte[]".to_string(),
+                instances: 3,
+                baseline_instances: 1,
+                growth: 16,
+            }],
+        };
+        assert_eq!(diff.to_dataframe().unwrap().shape(), (1, 4));
+
+        let heap = Heap::default();
+        let fields = vec!["id".to_string()];
+        let frame = instances_dataframe(&heap, "com.acme.Order", &fields).unwrap();
+        assert_eq!(frame.shape(), (0, 2));
+    }
+}
@@ -0,0 +1,100 @@
+use std::io::{BufRead, BufReader, Read};
+
+use crate::errors::HprofSlurpError;
+
+const GZIP_MAGIC: &[u8] = &[0x1f, 0x8b];
+const ZSTD_MAGIC: &[u8] = &[0x28, 0xb5, 0x2f, 0xfd];
+const XZ_MAGIC: &[u8] = &[0xfd, 0x37, 0x7a, 0x58, 0x5a, 0x00];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Compression {
+    None,
+    Gzip,
+    Zstd,
+    Xz,
+}
+
+impl Compression {
+    /// Detects the compression from the magic bytes, plain dumps start with `JAVA PROFILE`.
+    pub fn detect(prefix: &[u8]) -> Compression {
+        if prefix.starts_with(GZIP_MAGIC) {
+            Compression::Gzip
+        } else if prefix.starts_with(ZSTD_MAGIC) {
+            Compression::Zstd
+        } else if prefix.starts_with(XZ_MAGIC) {
+            Compression::Xz
+        } else {
+            Compression::None
+        }
+    }
+}
+
+/// Wraps the input into a streaming decoder if it is compressed.
+pub fn decompressed_input(
+    input: Box<dyn Read + Send>,
+) -> Result<(Box<dyn Read + Send>, Compression), HprofSlurpError> {
+    let mut reader = BufReader::new(input);
+    let compression = Compression::detect(reader.fill_buf()?);
+    let decoded: Box<dyn Read + Send> = match compression {
+        Compression::None => Box::new(reader),
+        #[cfg(feature = "compression")]
+        Compression::Gzip => Box::new(flate2::bufread::MultiGzDecoder::new(reader)),
+        #[cfg(feature = "compression")]
+        Compression::Zstd => Box::new(zstd::stream::read::Decoder::with_buffer(reader)?),
+        #[cfg(feature = "compression")]
+        Compression::Xz => Box::new(xz2::bufread::XzDecoder::new_multi_decoder(reader)),
+        #[cfg(not(feature = "compression"))]
+        _ => {
+            return Err(HprofSlurpError::InvalidHprofFile {
+                message: format!(
+                    "{:?} compressed input needs the `compression` feature",
+                    compression
+                ),
+            })
+        }
+    };
+    Ok((decoded, compression))
+}
+
+#[cfg(all(test, feature = "compression"))]
+mod tests {
+    use super::*;
+    use std::io::{Cursor, Write};
+
+    const CONTENT: &[u8] = b"JAVA PROFILE 1.0.2\0 some records";
+
+    fn decode(compressed: Vec<u8>) -> (Vec<u8>, Compression) {
+        let (mut reader, compression) =
+            decompressed_input(Box::new(Cursor::new(compressed))).unwrap();
+        let mut decoded = Vec::new();
+        reader.read_to_end(&mut decoded).unwrap();
+        (decoded, compression)
+    }
+
+    #[test]
+    fn plain_input_is_untouched() {
+        assert_eq!(
+            decode(CONTENT.to_vec()),
+            (CONTENT.to_vec(), Compression::None)
+        );
+    }
+
+    #[test]
+    fn decompress_gzip_zstd_and_xz() {
+        let mut gzip = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::fast());
+        gzip.write_all(CONTENT).unwrap();
+        let zstd = zstd::encode_all(CONTENT, 1).unwrap();
+        let mut xz = xz2::write::XzEncoder::new(Vec::new(), 1);
+        xz.write_all(CONTENT).unwrap();
+
+        assert_eq!(
+            decode(gzip.finish().unwrap()),
+            (CONTENT.to_vec(), Compression::Gzip)
+        );
+        assert_eq!(decode(zstd), (CONTENT.to_vec(), Compression::Zstd));
+        assert_eq!(
+            decode(xz.finish().unwrap()),
+            (CONTENT.to_vec(), Compression::Xz)
+        );
+    }
+}
@@ -15,6 +15,31 @@ pub enum HprofSlurpError {
     InvalidHprofFile { message: String },
     #[error("unsupported pointer size - {message:?}")]
     UnsupportedIdSize { message: String },
+    #[error("remote input error - {message:?}")]
+    RemoteInputError { message: String },
+    #[error("invalid baseline profile - {message:?}")]
+    InvalidBaselineProfile { message: String },
+    #[error("invalid checkpoint - {message:?}")]
+    InvalidCheckpoint { message: String },
+    #[error("invalid index - {message:?}")]
+    InvalidIndex { message: String },
+    #[error("invalid annotations - {message:?}")]
+    InvalidAnnotations { message: String },
+    #[error("missing record offsets - slurp the dump with `keep_record_offsets`")]
+    MissingRecordOffsets,
+    #[error("class `{name}` not found in the heap dump")]
+    ClassNotFound { name: String },
+    #[error("oversized record at offset {offset} - {message}")]
+    OversizedRecord { offset: usize, message: String },
+    #[error("invalid heap dump index {index} - the file contains {heap_dumps} heap dumps")]
+    InvalidDumpIndex { index: usize, heap_dumps: u64 },
+    #[error("missing feature - built without the `{feature}` feature")]
+    MissingFeature { feature: &'static str },
+    #[error("{failures} structural problems found in the dump")]
+    CorruptedDump { failures: u64 },
+    #[error("invalid primitive array sample rate {rate} - the value should be between 0 and 1")]
+    InvalidSampleRate { rate: f64 },
+    #[cfg(feature = "cli")]
     #[error("CLI argument error ({e})")]
     ClapError { e: clap::Error },
     #[error("standard I/O error ({e})")]
@@ -29,6 +54,7 @@ impl std::convert::From<std::io::Error> for HprofSlurpError {
     }
 }
 
+#[cfg(feature = "cli")]
 impl std::convert::From<clap::Error> for HprofSlurpError {
     fn from(e: clap::Error) -> Self {
         HprofSlurpError::ClapError { e }
@@ -0,0 +1,732 @@
+use std::collections::{HashMap, VecDeque};
+use std::hash::Hash;
+use std::sync::OnceLock;
+
+use crate::object_index::ObjectIndex;
+use crate::parser::gc_record::{FieldType, FieldValue, Values};
+use crate::progress::{Phase, Progress};
+use crate::Heap;
+
+// marker for vertices without ancestor in the dominator computation
+const NONE: u32 = u32::MAX;
+
+// index of the virtual root pointing to all GC roots
+const ROOT: u32 = 0;
+
+// objects visited between two progress reports
+const REPORT_INTERVAL: usize = 64 * 1024;
+
+/// Object reference graph of a `Heap` with its dominator tree.
+///
+/// Nodes are the class objects and the instances/arrays of the instance pool.
+/// A virtual root references every GC root as well as every object which is not reachable from a GC root,
+/// the latter therefore only retain themselves.
+pub struct HeapGraph {
+    // node `index + 1` for each object index, node 0 is the virtual root
+    objects: ObjectIndex,
+    shallow_sizes: Vec<u64>,
+    // outgoing references in compressed sparse row format
+    edge_offsets: Vec<usize>,
+    edges: Vec<u32>,
+    dominators: Vec<u32>,
+    retained_sizes: Vec<u64>,
+    reachable: Vec<bool>,
+    // breadth first search tree from the GC roots, computed on first use
+    shortest_path_parents: OnceLock<Vec<u32>>,
+}
+
+impl HeapGraph {
+    pub fn new(heap: &Heap) -> Self {
+        Self::with_progress(heap, |_| ())
+    }
+
+    /// Builds the graph, reporting the `Analyzing` phase as three passes over the objects
+    /// (references, dominators, retained sizes).
+    pub fn with_progress(heap: &Heap, report: impl Fn(Progress)) -> Self {
+        let objects = graph_objects(heap);
+        let node_of = |id: u64| objects.index_of(id).map(|index| index + 1);
+        let node_count = objects.len() + 1;
+        let total = Some(3 * objects.len() as u64);
+        let report_done = |done: usize| report(Progress::new(Phase::Analyzing, done as u64, total));
+
+        let mut shallow_sizes = vec![0; node_count];
+        let mut edge_offsets = Vec::with_capacity(node_count + 1);
+        let mut edges = Vec::new();
+
+        // virtual root edges
+        edge_offsets.push(0);
+        let mut root_ids: Vec<u32> = heap.gc_root_ids().filter_map(node_of).collect();
+        root_ids.sort_unstable();
+        root_ids.dedup();
+        edges.extend(root_ids);
+        edge_offsets.push(edges.len());
+
+        for (position, object_id) in objects.ids().iter().enumerate() {
+            let index = position + 1;
+            if position.is_multiple_of(REPORT_INTERVAL) {
+                report_done(position);
+            }
+            let mut push_reference = |id: u64| {
+                if let Some(target) = node_of(id) {
+                    edges.push(target);
+                }
+            };
+            if let Some(class) = heap.classes_dump.get(object_id) {
+                shallow_sizes[index] = class
+                    .static_fields
+                    .iter()
+                    .map(|(field, _)| field_type_size(field.field_type, heap.reference_size()))
+                    .sum();
+                push_reference(class.super_class_object_id);
+                push_reference(class.class_loader_object_id);
+                for (_, value) in &class.static_fields {
+                    if let FieldValue::Object(id) = value {
+                        push_reference(*id);
+                    }
+                }
+            } else if let Some(instance) = heap.instances_pool.get(object_id) {
+                shallow_sizes[index] = heap.shallow_size(instance);
+                match instance.array_element_type() {
+                    Some(FieldType::Object) => {
+                        push_reference(instance.class_object_id);
+                        instance
+                            .object_array()
+                            .unwrap_or_default()
+                            .iter()
+                            .for_each(|id| push_reference(*id));
+                    }
+                    Some(_) => (), // primitive arrays do not reference anything
+                    None => {
+                        push_reference(instance.class_object_id);
+                        for (_, value) in instance.all_fields() {
+                            if let Values::Single(FieldValue::Object(id)) = value {
+                                push_reference(*id);
+                            }
+                        }
+                    }
+                }
+            }
+            edge_offsets.push(edges.len());
+        }
+
+        let mut graph = HeapGraph {
+            objects,
+            shallow_sizes,
+            edge_offsets,
+            edges,
+            dominators: Vec::new(),
+            retained_sizes: Vec::new(),
+            reachable: Vec::new(),
+            shortest_path_parents: OnceLock::new(),
+        };
+        graph.attach_unreachable_to_root();
+        report_done(graph.len());
+        graph.compute_dominators();
+        report_done(2 * graph.len());
+        graph.compute_retained_sizes();
+        report_done(3 * graph.len());
+        graph
+    }
+
+    /// Number of objects in the graph (excluding the virtual root).
+    pub fn len(&self) -> usize {
+        self.objects.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn shallow_size(&self, object_id: u64) -> Option<u64> {
+        self.node(object_id)
+            .map(|index| self.shallow_sizes[index as usize])
+    }
+
+    /// Size of the objects which would be garbage collected if `object_id` was collected.
+    pub fn retained_size(&self, object_id: u64) -> Option<u64> {
+        self.node(object_id)
+            .map(|index| self.retained_sizes[index as usize])
+    }
+
+    /// Returns the objects which would be garbage collected if all of `object_ids` were collected together,
+    /// them included, e.g. the entries of a cache once the cache is cleared.
+    ///
+    /// Unlike summing `retained_size`, the objects kept alive only by several of the objects are counted.
+    pub fn retained_set_of(&self, object_ids: &[u64]) -> Vec<u64> {
+        let mut visited = vec![false; self.node_count()];
+        for node in object_ids.iter().filter_map(|id| self.node(*id)) {
+            // never reached from the root, hence collected
+            visited[node as usize] = true;
+        }
+        let removed = visited.clone();
+        visited[ROOT as usize] = true;
+        let mut stack = vec![ROOT];
+        while let Some(index) = stack.pop() {
+            for target in self.targets(index) {
+                if !visited[*target as usize] {
+                    visited[*target as usize] = true;
+                    stack.push(*target);
+                }
+            }
+        }
+        (1..self.node_count() as u32)
+            .filter(|node| removed[*node as usize] || !visited[*node as usize])
+            .map(|node| self.object_id(node))
+            .collect()
+    }
+
+    /// Size of the objects which would be garbage collected if all of `object_ids` were collected together.
+    pub fn retained_size_of(&self, object_ids: &[u64]) -> u64 {
+        self.retained_set_of(object_ids)
+            .iter()
+            .filter_map(|id| self.shallow_size(*id))
+            .sum()
+    }
+
+    /// Returns the immediate dominator of an object, `None` if it is dominated by the GC roots only.
+    pub fn immediate_dominator(&self, object_id: u64) -> Option<u64> {
+        let index = self.node(object_id)?;
+        match self.dominators[index as usize] {
+            ROOT => None,
+            dominator => Some(self.object_id(dominator)),
+        }
+    }
+
+    /// Returns false for objects which are not reachable from a GC root, or are not in the graph.
+    pub fn is_reachable(&self, object_id: u64) -> bool {
+        self.node(object_id)
+            .map(|index| self.reachable[index as usize])
+            .unwrap_or(false)
+    }
+
+    /// Iterates over the object ids referenced by `object_id`.
+    pub fn references(&self, object_id: u64) -> impl Iterator<Item = u64> + '_ {
+        let targets = match self.node(object_id) {
+            Some(index) => self.targets(index),
+            None => &[],
+        };
+        targets.iter().map(|target| self.object_id(*target))
+    }
+
+    /// Iterates over `(object id, incoming references, outgoing references)` for the objects kept by `keep`,
+    /// only the references between kept objects are counted and the GC roots do not count as references.
+    pub fn degrees(
+        &self,
+        keep: impl Fn(u64) -> bool,
+    ) -> impl Iterator<Item = (u64, u32, u32)> + '_ {
+        let kept = self.kept_nodes(keep);
+        let mut incoming = vec![0u32; self.node_count()];
+        for node in (1..self.node_count() as u32).filter(|node| kept[*node as usize]) {
+            for target in self.targets(node) {
+                if kept[*target as usize] {
+                    incoming[*target as usize] += 1;
+                }
+            }
+        }
+        (1..self.node_count() as u32).filter_map(move |node| {
+            if !kept[node as usize] {
+                return None;
+            }
+            let outgoing = self
+                .targets(node)
+                .iter()
+                .filter(|target| kept[**target as usize])
+                .count();
+            Some((
+                self.object_id(node),
+                incoming[node as usize],
+                outgoing as u32,
+            ))
+        })
+    }
+
+    /// Returns the strongly connected components of at least `min_size` objects among the objects kept by
+    /// `keep`, largest first, each sorted by object id.
+    pub fn strongly_connected_components(
+        &self,
+        min_size: usize,
+        keep: impl Fn(u64) -> bool,
+    ) -> Vec<Vec<u64>> {
+        // iterative Tarjan
+        let kept = self.kept_nodes(keep);
+        let mut order = vec![NONE; self.node_count()];
+        let mut low_link = vec![NONE; self.node_count()];
+        let mut on_stack = vec![false; self.node_count()];
+        let mut stack: Vec<u32> = Vec::new();
+        let mut next_order = 0;
+        let mut components = Vec::new();
+        for start in 1..self.node_count() as u32 {
+            if !kept[start as usize] || order[start as usize] != NONE {
+                continue;
+            }
+            let mut calls: Vec<(u32, usize)> = vec![(start, 0)];
+            order[start as usize] = next_order;
+            low_link[start as usize] = next_order;
+            next_order += 1;
+            stack.push(start);
+            on_stack[start as usize] = true;
+            while let Some((node, cursor)) = calls.last().copied() {
+                if let Some(target) = self.targets(node).get(cursor) {
+                    calls.last_mut().expect("current call").1 += 1;
+                    let target = *target as usize;
+                    if !kept[target] {
+                        continue;
+                    }
+                    if order[target] == NONE {
+                        order[target] = next_order;
+                        low_link[target] = next_order;
+                        next_order += 1;
+                        stack.push(target as u32);
+                        on_stack[target] = true;
+                        calls.push((target as u32, 0));
+                    } else if on_stack[target] {
+                        low_link[node as usize] = low_link[node as usize].min(order[target]);
+                    }
+                } else {
+                    calls.pop();
+                    if let Some((parent, _)) = calls.last() {
+                        low_link[*parent as usize] =
+                            low_link[*parent as usize].min(low_link[node as usize]);
+                    }
+                    if low_link[node as usize] == order[node as usize] {
+                        let mut component = Vec::new();
+                        while let Some(member) = stack.pop() {
+                            on_stack[member as usize] = false;
+                            component.push(self.object_id(member));
+                            if member == node {
+                                break;
+                            }
+                        }
+                        if component.len() >= min_size {
+                            component.sort_unstable();
+                            components.push(component);
+                        }
+                    }
+                }
+            }
+        }
+        components.sort_by(|a: &Vec<u64>, b| b.len().cmp(&a.len()).then_with(|| a[0].cmp(&b[0])));
+        components
+    }
+
+    /// Sums the retained sizes of the objects per group (e.g. per class), an object dominated by an object
+    /// of its own group is already counted through its dominator.
+    pub fn retained_size_by_group<K: Hash + Eq + Clone>(
+        &self,
+        group_of: impl Fn(u64) -> Option<K>,
+    ) -> HashMap<K, u64> {
+        let groups: Vec<Option<K>> = std::iter::once(None)
+            .chain((1..self.node_count() as u32).map(|node| group_of(self.object_id(node))))
+            .collect();
+        // number of dominators of the current node belonging to each group
+        let mut open_groups: HashMap<K, u32> = HashMap::new();
+        let mut sizes: HashMap<K, u64> = HashMap::new();
+        let children = self.dominator_children();
+        let mut stack: Vec<(u32, usize)> = vec![(ROOT, 0)];
+        while let Some((node, cursor)) = stack.last_mut() {
+            let node = *node;
+            if let Some(child) = children[node as usize].get(*cursor) {
+                *cursor += 1;
+                if let Some(group) = &groups[*child as usize] {
+                    let open = open_groups.entry(group.clone()).or_insert(0);
+                    if *open == 0 {
+                        *sizes.entry(group.clone()).or_insert(0) +=
+                            self.retained_sizes[*child as usize];
+                    }
+                    *open += 1;
+                }
+                stack.push((*child, 0));
+            } else {
+                stack.pop();
+                if let Some(group) = &groups[node as usize] {
+                    if let Some(open) = open_groups.get_mut(group) {
+                        *open -= 1;
+                    }
+                }
+            }
+        }
+        sizes
+    }
+
+    /// Returns the shortest reference chain from a GC root (first element) to `object_id` (last element).
+    pub fn path_from_roots(&self, object_id: u64) -> Option<Vec<u64>> {
+        let mut index = self.node(object_id)?;
+        let parents = self
+            .shortest_path_parents
+            .get_or_init(|| self.compute_shortest_path_parents());
+        let mut path = vec![object_id];
+        while parents[index as usize] != ROOT {
+            index = parents[index as usize];
+            path.push(self.object_id(index));
+        }
+        path.reverse();
+        Some(path)
+    }
+
+    fn compute_shortest_path_parents(&self) -> Vec<u32> {
+        let mut parents = vec![NONE; self.node_count()];
+        let mut queue = VecDeque::from([ROOT]);
+        parents[ROOT as usize] = ROOT;
+        while let Some(index) = queue.pop_front() {
+            for target in self.targets(index) {
+                if parents[*target as usize] == NONE {
+                    parents[*target as usize] = index;
+                    queue.push_back(*target);
+                }
+            }
+        }
+        parents
+    }
+
+    fn node(&self, object_id: u64) -> Option<u32> {
+        self.objects.index_of(object_id).map(|index| index + 1)
+    }
+
+    fn object_id(&self, node: u32) -> u64 {
+        self.objects.id_of(node - 1)
+    }
+
+    fn node_count(&self) -> usize {
+        self.objects.len() + 1
+    }
+
+    fn targets(&self, index: u32) -> &[u32] {
+        let index = index as usize;
+        &self.edges[self.edge_offsets[index]..self.edge_offsets[index + 1]]
+    }
+
+    // the virtual root is never kept
+    fn kept_nodes(&self, keep: impl Fn(u64) -> bool) -> Vec<bool> {
+        std::iter::once(false)
+            .chain((1..self.node_count() as u32).map(|node| keep(self.object_id(node))))
+            .collect()
+    }
+
+    // objects not reachable from the GC roots (e.g. awaiting finalization) become children of the virtual root
+    fn attach_unreachable_to_root(&mut self) {
+        let mut visited = vec![false; self.node_count()];
+        visited[ROOT as usize] = true;
+        let mut stack = vec![ROOT];
+        while let Some(index) = stack.pop() {
+            for target in self.targets(index) {
+                if !visited[*target as usize] {
+                    visited[*target as usize] = true;
+                    stack.push(*target);
+                }
+            }
+        }
+        let unreachable: Vec<u32> = (0..self.node_count() as u32)
+            .filter(|index| !visited[*index as usize])
+            .collect();
+        self.reachable = visited;
+        if unreachable.is_empty() {
+            return;
+        }
+        let root_edges_end = self.edge_offsets[1];
+        self.edges
+            .splice(root_edges_end..root_edges_end, unreachable.iter().copied());
+        for offset in self.edge_offsets.iter_mut().skip(1) {
+            *offset += unreachable.len();
+        }
+    }
+
+    // Lengauer-Tarjan with path compression, all vertices are reachable from the virtual root at this point
+    fn compute_dominators(&mut self) {
+        let node_count = self.node_count();
+
+        // iterative depth first search numbering vertices in pre-order
+        let mut dfs_number = vec![NONE; node_count];
+        let mut vertex = Vec::with_capacity(node_count);
+        let mut parent = vec![NONE; node_count];
+        let mut stack: Vec<(u32, usize)> = vec![(ROOT, 0)];
+        dfs_number[ROOT as usize] = 0;
+        vertex.push(ROOT);
+        while let Some((node, cursor)) = stack.last_mut() {
+            let node = *node;
+            let targets = self.targets(node);
+            if *cursor < targets.len() {
+                let target = targets[*cursor];
+                *cursor += 1;
+                if dfs_number[target as usize] == NONE {
+                    let number = vertex.len() as u32;
+                    dfs_number[target as usize] = number;
+                    parent[number as usize] = dfs_number[node as usize];
+                    vertex.push(target);
+                    stack.push((target, 0));
+                }
+            } else {
+                stack.pop();
+            }
+        }
+
+        // predecessors expressed in dfs numbers
+        let mut predecessors: Vec<Vec<u32>> = vec![Vec::new(); node_count];
+        for node in 0..node_count as u32 {
+            for target in self.targets(node) {
+                predecessors[dfs_number[*target as usize] as usize].push(dfs_number[node as usize]);
+            }
+        }
+
+        // all computations below are done in dfs numbers
+        let mut semi: Vec<u32> = (0..node_count as u32).collect();
+        let mut label: Vec<u32> = (0..node_count as u32).collect();
+        let mut ancestor = vec![NONE; node_count];
+        let mut idom = vec![ROOT; node_count];
+        let mut bucket: Vec<Vec<u32>> = vec![Vec::new(); node_count];
+
+        for w in (1..node_count as u32).rev() {
+            for v in &predecessors[w as usize] {
+                let u = eval(*v, &mut ancestor, &mut label, &semi);
+                if semi[u as usize] < semi[w as usize] {
+                    semi[w as usize] = semi[u as usize];
+                }
+            }
+            bucket[semi[w as usize] as usize].push(w);
+            let p = parent[w as usize];
+            ancestor[w as usize] = p;
+            for v in std::mem::take(&mut bucket[p as usize]) {
+                let u = eval(v, &mut ancestor, &mut label, &semi);
+                idom[v as usize] = if semi[u as usize] < semi[v as usize] {
+                    u
+                } else {
+                    p
+                };
+            }
+        }
+        for w in 1..node_count {
+            if idom[w] != semi[w] {
+                idom[w] = idom[idom[w] as usize];
+            }
+        }
+
+        // back to node indexes
+        let mut dominators = vec![ROOT; node_count];
+        for (number, node) in vertex.iter().enumerate().skip(1) {
+            dominators[*node as usize] = vertex[idom[number] as usize];
+        }
+        self.dominators = dominators;
+    }
+
+    fn dominator_children(&self) -> Vec<Vec<u32>> {
+        let mut children: Vec<Vec<u32>> = vec![Vec::new(); self.node_count()];
+        for node in 1..self.node_count() {
+            children[self.dominators[node] as usize].push(node as u32);
+        }
+        children
+    }
+
+    fn compute_retained_sizes(&mut self) {
+        // a dominator always precedes its children in the depth first ordering
+        let mut order: Vec<u32> = Vec::with_capacity(self.node_count());
+        let children = self.dominator_children();
+        let mut stack = vec![ROOT];
+        while let Some(node) = stack.pop() {
+            order.push(node);
+            stack.extend(&children[node as usize]);
+        }
+        let mut retained_sizes = self.shallow_sizes.clone();
+        for node in order.into_iter().rev().filter(|node| *node != ROOT) {
+            let dominator = self.dominators[node as usize] as usize;
+            retained_sizes[dominator] += retained_sizes[node as usize];
+        }
+        self.retained_sizes = retained_sizes;
+    }
+}
+
+fn eval(v: u32, ancestor: &mut [u32], label: &mut [u32], semi: &[u32]) -> u32 {
+    if ancestor[v as usize] == NONE {
+        return v;
+    }
+    // iterative path compression
+    let mut path = Vec::new();
+    let mut current = v;
+    while ancestor[ancestor[current as usize] as usize] != NONE {
+        path.push(current);
+        current = ancestor[current as usize];
+    }
+    while let Some(node) = path.pop() {
+        let a = ancestor[node as usize] as usize;
+        if semi[label[a] as usize] < semi[label[node as usize] as usize] {
+            label[node as usize] = label[a];
+        }
+        ancestor[node as usize] = ancestor[a];
+    }
+    label[v as usize]
+}
+
+// stable node ordering for reproducible results
+fn graph_objects(heap: &Heap) -> ObjectIndex {
+    let mut class_ids: Vec<u64> = heap.classes_dump.keys().copied().collect();
+    class_ids.sort_unstable();
+    let mut instance_ids: Vec<u64> = heap.instances_pool.keys().copied().collect();
+    instance_ids.sort_unstable();
+    ObjectIndex::from_ids(class_ids.into_iter().chain(instance_ids))
+}
+
+fn field_type_size(field_type: FieldType, reference_size: u64) -> u64 {
+    match field_type {
+        FieldType::Bool | FieldType::Byte => 1,
+        FieldType::Char | FieldType::Short => 2,
+        FieldType::Int | FieldType::Float => 4,
+        FieldType::Long | FieldType::Double => 8,
+        FieldType::Object => reference_size,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::result_recorder::{Instance, RootJniGlobal};
+    use std::sync::Arc;
+
+    fn instance(object_id: u64, references: &[u64]) -> Arc<Instance> {
+        Arc::new(Instance {
+            object_id,
+            stack_trace_serial_number: 0,
+            class_object_id: 0,
+            data_size: 8 * references.len() as u64,
+            fields: references
+                .iter()
+                .map(|id| (0, Values::Single(FieldValue::Object(*id))))
+                .collect(),
+            super_fields: Vec::new(),
+        })
+    }
+
+    fn heap(objects: &[(u64, &[u64])], roots: &[u64]) -> Heap {
+        let mut heap = Heap::default();
+        heap.counter.id_size = 8;
+        for (id, references) in objects {
+            heap.instances_pool
+                .make_mut()
+                .insert(*id, instance(*id, references));
+        }
+        for root in roots {
+            heap.root_jni_global.make_mut().insert(
+                *root,
+                RootJniGlobal {
+                    object_id: *root,
+                    jni_global_ref_id: 0,
+                },
+            );
+        }
+        heap
+    }
+
+    #[test]
+    fn retained_size_of_chain() {
+        // 1 -> 2 -> 3
+        let heap = heap(&[(1, &[2]), (2, &[3]), (3, &[])], &[1]);
+        let graph = HeapGraph::new(&heap);
+        assert_eq!(graph.shallow_size(1), Some(24));
+        assert_eq!(graph.shallow_size(3), Some(16));
+        assert_eq!(graph.retained_size(1), Some(24 + 24 + 16));
+        assert_eq!(graph.retained_size(2), Some(24 + 16));
+        assert_eq!(graph.immediate_dominator(3), Some(2));
+        assert_eq!(graph.immediate_dominator(1), None);
+        assert_eq!(graph.path_from_roots(3), Some(vec![1, 2, 3]));
+        assert!(graph.is_reachable(3));
+    }
+
+    #[test]
+    fn shared_object_is_retained_by_common_dominator() {
+        // 1 -> 2 -> 4, 1 -> 3 -> 4
+        let heap = heap(&[(1, &[2, 3]), (2, &[4]), (3, &[4]), (4, &[])], &[1]);
+        let graph = HeapGraph::new(&heap);
+        assert_eq!(graph.immediate_dominator(4), Some(1));
+        assert_eq!(graph.retained_size(2), Some(24));
+        assert_eq!(graph.retained_size(1), Some(32 + 24 + 24 + 16));
+    }
+
+    #[test]
+    fn object_referenced_from_two_roots_is_not_retained() {
+        // 1 -> 3, 2 -> 3, 3 -> 4, 4 -> 3 (cycle)
+        let heap = heap(&[(1, &[3]), (2, &[3]), (3, &[4]), (4, &[3])], &[1, 2]);
+        let graph = HeapGraph::new(&heap);
+        assert_eq!(graph.immediate_dominator(3), None);
+        assert_eq!(graph.immediate_dominator(4), Some(3));
+        assert_eq!(graph.retained_size(1), Some(24));
+        assert_eq!(graph.retained_size(3), Some(48));
+    }
+
+    #[test]
+    fn retained_size_of_several_objects() {
+        // 1 -> 2 -> 4, 1 -> 3 -> 4, 4 -> 5, 6 unreachable
+        let heap = heap(
+            &[
+                (1, &[2, 3]),
+                (2, &[4]),
+                (3, &[4]),
+                (4, &[5]),
+                (5, &[]),
+                (6, &[]),
+            ],
+            &[1],
+        );
+        let graph = HeapGraph::new(&heap);
+        // 4 is kept by 2 or 3, by none of them once both are gone
+        assert_eq!(graph.retained_size(2), Some(24));
+        assert_eq!(graph.retained_size_of(&[2, 3]), 24 + 24 + 24 + 16);
+        let mut retained = graph.retained_set_of(&[3, 2]);
+        retained.sort_unstable();
+        assert_eq!(retained, vec![2, 3, 4, 5]);
+        assert_eq!(
+            graph.retained_size_of(&[4]),
+            graph.retained_size(4).unwrap()
+        );
+        assert_eq!(graph.retained_size_of(&[6, 42]), 16);
+        assert_eq!(graph.retained_size_of(&[]), 0);
+    }
+
+    #[test]
+    fn retained_size_by_group_counts_nested_members_once() {
+        // 1 -> 2 -> 3 with 1 and 3 in the same group
+        let heap = heap(&[(1, &[2]), (2, &[3]), (3, &[])], &[1]);
+        let graph = HeapGraph::new(&heap);
+        let sizes = graph.retained_size_by_group(|id| Some(id % 2));
+        assert_eq!(sizes.get(&1), Some(&(24 + 24 + 16)));
+        assert_eq!(sizes.get(&0), Some(&(24 + 16)));
+    }
+
+    #[test]
+    fn unreachable_objects_retain_themselves() {
+        let heap = heap(&[(1, &[]), (2, &[1])], &[]);
+        let graph = HeapGraph::new(&heap);
+        assert_eq!(graph.len(), 2);
+        assert_eq!(graph.immediate_dominator(1), None);
+        assert_eq!(graph.retained_size(2), Some(24));
+        assert_eq!(graph.references(2).collect::<Vec<_>>(), vec![1]);
+        assert!(!graph.is_reachable(1));
+    }
+
+    #[test]
+    fn strongly_connected_components_and_degrees() {
+        // 1 -> 2 -> 3 -> 1, 3 -> 4 -> 5 -> 4, 4 -> 6 -> 6
+        let heap = heap(
+            &[
+                (1, &[2]),
+                (2, &[3]),
+                (3, &[1, 4]),
+                (4, &[5, 6]),
+                (5, &[4]),
+                (6, &[6]),
+            ],
+            &[1],
+        );
+        let graph = HeapGraph::new(&heap);
+        assert_eq!(
+            graph.strongly_connected_components(2, |_| true),
+            vec![vec![1, 2, 3], vec![4, 5]]
+        );
+        assert_eq!(graph.strongly_connected_components(1, |_| true).len(), 3);
+        // leaving 5 out breaks the second cycle
+        assert_eq!(
+            graph.strongly_connected_components(2, |id| id != 5),
+            vec![vec![1, 2, 3]]
+        );
+        let degrees: Vec<(u64, u32, u32)> = graph.degrees(|id| id != 6).collect();
+        assert_eq!(degrees.len(), 5);
+        assert!(degrees.contains(&(4, 2, 1)));
+        assert!(degrees.contains(&(1, 1, 1)));
+    }
+}
@@ -0,0 +1,295 @@
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+use log::warn;
+use serde::{Deserialize, Serialize};
+
+use crate::class_registry::ClassRegistry;
+use crate::errors::HprofSlurpError;
+use crate::errors::HprofSlurpError::*;
+use crate::parser::gc_record::{ClassDumpFields, GcRecord};
+use crate::parser::record_parser::parse_gc_record;
+use crate::progress::ProgressMode;
+use crate::slurp::{slurp_file_with_options, SlurpOptions};
+use crate::{DumpProvenance, Heap};
+
+// bumped whenever the index changes shape
+const INDEX_VERSION: u32 = 1;
+
+// bytes read ahead of a record, most instances fit
+const RECORD_READ_SIZE: usize = 4096;
+
+// identifies the dump the index was built from, a dump rewritten since then is indexed again
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq)]
+struct IndexHeader {
+    version: u32,
+    dump_len: u64,
+    // seconds since the epoch
+    dump_modified: u64,
+}
+
+/// String table, class table and record offsets of a dump, saved in a sidecar file next to it
+/// to answer queries without parsing the dump again.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct HeapIndex {
+    pub id_size: u32,
+    pub utf8_strings: HashMap<u64, Box<str>>,
+    pub class_registry: ClassRegistry,
+    pub classes_dump: HashMap<u64, ClassDumpFields>,
+    // object id -> offset of its record in the dump, for classes, instances and arrays
+    pub record_offsets: HashMap<u64, u64>,
+    // class object id -> instance ids, sorted
+    pub instances_by_class: HashMap<u64, Vec<u64>>,
+    pub provenance: DumpProvenance,
+}
+
+/// Returns the sidecar path of a dump, e.g. `dump.hprof.idx`.
+pub fn index_path(dump_path: &Path) -> PathBuf {
+    let mut file_name = dump_path.file_name().unwrap_or_default().to_os_string();
+    file_name.push(".idx");
+    dump_path.with_file_name(file_name)
+}
+
+/// Parses the dump and saves its index in the sidecar file.
+pub fn index_dump(dump_path: &Path, progress: ProgressMode) -> Result<HeapIndex, HprofSlurpError> {
+    let index = build_index(dump_path, progress)?;
+    write_index(dump_path, &index)?;
+    Ok(index)
+}
+
+/// Parses the dump keeping only what the index needs, the payloads are read back from the dump on demand.
+pub fn build_index(dump_path: &Path, progress: ProgressMode) -> Result<HeapIndex, HprofSlurpError> {
+    let options = SlurpOptions {
+        keep_instance_fields: false,
+        keep_primitive_arrays: false,
+        keep_object_arrays: false,
+        keep_roots: false,
+        keep_record_offsets: true,
+        progress,
+        ..SlurpOptions::default()
+    };
+    let heap = slurp_file_with_options(dump_path.display().to_string(), options)?;
+    // offsets count in the decompressed stream
+    if heap.provenance.input_size.is_none() {
+        return Err(InvalidIndex {
+            message: "only uncompressed local dumps can be indexed".to_string(),
+        });
+    }
+    Ok(HeapIndex::from(heap))
+}
+
+impl From<Heap> for HeapIndex {
+    fn from(heap: Heap) -> Self {
+        let mut instances_by_class: HashMap<u64, Vec<u64>> = HashMap::new();
+        for instance in heap.instances_pool.values() {
+            if instance.array_element_type().is_none() {
+                instances_by_class
+                    .entry(instance.class_object_id)
+                    .or_default()
+                    .push(instance.object_id);
+            }
+        }
+        for instances in instances_by_class.values_mut() {
+            instances.sort_unstable();
+        }
+        HeapIndex {
+            id_size: heap.counter.id_size,
+            utf8_strings: heap.utf8_strings.into_inner(),
+            class_registry: heap.class_registry.into_inner(),
+            classes_dump: heap.classes_dump.into_inner(),
+            record_offsets: heap.record_offsets.into_inner(),
+            instances_by_class,
+            provenance: heap.provenance,
+        }
+    }
+}
+
+/// Saves the index in the sidecar of the dump, replacing the previous one atomically.
+pub fn write_index(dump_path: &Path, index: &HeapIndex) -> Result<(), HprofSlurpError> {
+    let header = index_header(dump_path)?;
+    let path = index_path(dump_path);
+    let partial_path = path.with_extension("partial");
+    let mut writer = BufWriter::new(File::create(&partial_path)?);
+    bincode::serialize_into(&mut writer, &header).map_err(index_error)?;
+    bincode::serialize_into(&mut writer, index).map_err(index_error)?;
+    writer.flush()?;
+    drop(writer);
+    std::fs::rename(partial_path, path)?;
+    Ok(())
+}
+
+/// Loads the sidecar index of the dump, `None` if there is none or if the dump changed since.
+pub fn read_index(dump_path: &Path) -> Result<Option<HeapIndex>, HprofSlurpError> {
+    let path = index_path(dump_path);
+    if !path.exists() {
+        return Ok(None);
+    }
+    let mut reader = BufReader::new(File::open(&path)?);
+    let header: IndexHeader = bincode::deserialize_from(&mut reader).map_err(index_error)?;
+    if header != index_header(dump_path)? {
+        warn!(
+            "The index `{}` is outdated or from another version, it is ignored.",
+            path.display()
+        );
+        return Ok(None);
+    }
+    let index = bincode::deserialize_from(&mut reader).map_err(index_error)?;
+    Ok(Some(index))
+}
+
+fn index_header(dump_path: &Path) -> Result<IndexHeader, HprofSlurpError> {
+    let metadata = std::fs::metadata(dump_path)?;
+    let dump_modified = metadata
+        .modified()?
+        .duration_since(UNIX_EPOCH)
+        .map(|since| since.as_secs())
+        .unwrap_or_default();
+    Ok(IndexHeader {
+        version: INDEX_VERSION,
+        dump_len: metadata.len(),
+        dump_modified,
+    })
+}
+
+fn index_error(e: bincode::Error) -> HprofSlurpError {
+    InvalidIndex {
+        message: e.to_string(),
+    }
+}
+
+impl HeapIndex {
+    /// Returns the ids of the instances of the classes with this name (e.g. `java.util.HashMap`).
+    pub fn instances_of(&self, class_name: &str) -> Vec<u64> {
+        let name = class_name.replace('.', "/");
+        let mut instances: Vec<u64> = self
+            .class_registry
+            .iter()
+            .filter(|class| {
+                self.utf8_strings
+                    .get(&class.class_name_id)
+                    .map(|n| n.as_ref())
+                    == Some(name.as_str())
+            })
+            .filter_map(|class| self.instances_by_class.get(&class.class_object_id))
+            .flatten()
+            .copied()
+            .collect();
+        instances.sort_unstable();
+        instances
+    }
+
+    /// Reads the record of a class, instance or array back from the dump, with its payload.
+    pub fn read_record(
+        &self,
+        dump_path: &Path,
+        object_id: u64,
+    ) -> Result<Option<GcRecord>, HprofSlurpError> {
+        let Some(offset) = self.record_offsets.get(&object_id) else {
+            return Ok(None);
+        };
+        let mut file = File::open(dump_path)?;
+        file.seek(SeekFrom::Start(*offset))?;
+        let mut buffer = Vec::new();
+        let mut wanted = RECORD_READ_SIZE;
+        loop {
+            let read = (&mut file)
+                .take((wanted - buffer.len()) as u64)
+                .read_to_end(&mut buffer)?;
+            match parse_gc_record(&buffer) {
+                Ok((_, record)) => return Ok(Some(record)),
+                Err(nom::Err::Incomplete(nom::Needed::Size(missing))) if read > 0 => {
+                    wanted = buffer.len() + missing.get();
+                }
+                Err(e) => {
+                    return Err(InvalidHprofFile {
+                        message: format!(
+                            "record of {:#x} at offset {}: {:?}",
+                            object_id, offset, e
+                        ),
+                    })
+                }
+            }
+        }
+    }
+
+    /// Converts the index into a `Heap` holding only the strings and classes, enough for the lookups by name.
+    pub fn into_heap(self) -> Heap {
+        let mut heap = Heap {
+            utf8_strings: self.utf8_strings.into(),
+            class_registry: self.class_registry.into(),
+            classes_dump: self.classes_dump.into(),
+            record_offsets: self.record_offsets.into(),
+            provenance: self.provenance,
+            ..Heap::default()
+        };
+        heap.counter.id_size = self.id_size;
+        heap
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::record::LoadClassData;
+
+    #[test]
+    fn index_round_trip_and_record_lookup() {
+        let dump_path =
+            std::env::temp_dir().join(format!("hprof-slurp-{}.hprof", std::process::id()));
+        // an instance dump record at offset 3
+        let mut dump = vec![0; 3];
+        dump.push(0x21);
+        dump.extend_from_slice(&0x42u64.to_be_bytes());
+        dump.extend_from_slice(&0u32.to_be_bytes());
+        dump.extend_from_slice(&0x1000u64.to_be_bytes());
+        dump.extend_from_slice(&4u32.to_be_bytes());
+        dump.extend_from_slice(&7u32.to_be_bytes());
+        std::fs::write(&dump_path, &dump).unwrap();
+
+        let mut index = HeapIndex {
+            id_size: 8,
+            ..HeapIndex::default()
+        };
+        index.utf8_strings.insert(1, "com/example/Point".into());
+        index.class_registry.load(LoadClassData {
+            serial_number: 1,
+            class_object_id: 0x1000,
+            stack_trace_serial_number: 0,
+            class_name_id: 1,
+        });
+        index.record_offsets.insert(0x42, 3);
+        index.instances_by_class.insert(0x1000, vec![0x42]);
+        write_index(&dump_path, &index).unwrap();
+
+        let index = read_index(&dump_path).unwrap().unwrap();
+        assert_eq!(index.instances_of("com.example.Point"), vec![0x42]);
+        match index.read_record(&dump_path, 0x42).unwrap() {
+            Some(GcRecord::InstanceDump {
+                class_object_id,
+                bytes_ref,
+                ..
+            }) => {
+                assert_eq!(class_object_id, 0x1000);
+                assert_eq!(bytes_ref.as_ref(), &7u32.to_be_bytes());
+            }
+            other => panic!("unexpected record {:?}", other),
+        }
+        assert!(index.read_record(&dump_path, 0x43).unwrap().is_none());
+        let heap = index.into_heap();
+        assert_eq!(
+            heap.class_name(0x1000).as_deref(),
+            Some("com.example.Point")
+        );
+
+        // a rewritten dump no longer matches its index
+        dump.push(0);
+        std::fs::write(&dump_path, &dump).unwrap();
+        assert!(read_index(&dump_path).unwrap().is_none());
+
+        std::fs::remove_file(index_path(&dump_path)).unwrap();
+        std::fs::remove_file(&dump_path).unwrap();
+    }
+}
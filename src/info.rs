@@ -0,0 +1,315 @@
+use std::io::Read;
+
+use serde::Serialize;
+
+use crate::analysis::render_table;
+use crate::decompress::{decompressed_input, Compression};
+use crate::errors::HprofSlurpError;
+use crate::errors::HprofSlurpError::*;
+use crate::parser::gc_record::GcRecord;
+use crate::parser::record::Record;
+use crate::parser::record_parser::HprofRecordParser;
+use crate::sampling::{sample_record_counts, DEFAULT_SAMPLES};
+use crate::slurp::{is_remote_path, open_input, slurp_header, FILE_HEADER_LENGTH, STDIN_PATH};
+use crate::utils::{pretty_bytes_size, pretty_count, pretty_timestamp};
+
+/// Bytes read past the header by `dump_info`, enough for the strings and classes of most dumps.
+pub const INFO_PREFIX_LEN: usize = 64 * 1024 * 1024;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct RecordCounts {
+    pub strings: u64,
+    pub classes: u64,
+    pub stack_frames: u64,
+    pub stack_traces: u64,
+    pub threads: u64,
+    pub heap_dump_segments: u64,
+    pub gc_roots: u64,
+    pub class_dumps: u64,
+    pub instances: u64,
+    pub object_arrays: u64,
+    pub primitive_arrays: u64,
+    pub others: u64,
+}
+
+impl RecordCounts {
+    fn count(&mut self, record: &Record) {
+        match record {
+            Record::Utf8String { .. } => self.strings += 1,
+            Record::LoadClass(_) => self.classes += 1,
+            Record::StackFrame(_) => self.stack_frames += 1,
+            Record::StackTrace(_) => self.stack_traces += 1,
+            Record::StartThread { .. } => self.threads += 1,
+            Record::HeapDumpStart { .. } => self.heap_dump_segments += 1,
+            Record::GcSegment(GcRecord::ClassDump(_)) => self.class_dumps += 1,
+            Record::GcSegment(GcRecord::InstanceDump { .. }) => self.instances += 1,
+            Record::GcSegment(GcRecord::ObjectArrayDump { .. }) => self.object_arrays += 1,
+            Record::GcSegment(GcRecord::PrimitiveArrayDump { .. }) => self.primitive_arrays += 1,
+            Record::GcSegment(_) => self.gc_roots += 1,
+            _ => self.others += 1,
+        }
+    }
+
+    /// Records of all kinds, as reported by the parser.
+    pub fn total(&self) -> u64 {
+        self.rows().iter().map(|(_, count)| count).sum()
+    }
+
+    fn rows(&self) -> [(&'static str, u64); 12] {
+        [
+            ("Strings", self.strings),
+            ("Classes", self.classes),
+            ("Stack frames", self.stack_frames),
+            ("Stack traces", self.stack_traces),
+            ("Threads", self.threads),
+            ("Heap dump segments", self.heap_dump_segments),
+            ("GC roots", self.gc_roots),
+            ("Class dumps", self.class_dumps),
+            ("Instances", self.instances),
+            ("Object arrays", self.object_arrays),
+            ("Primitive arrays", self.primitive_arrays),
+            ("Other records", self.others),
+        ]
+    }
+}
+
+/// What the header and the first records of a dump tell about it, see `dump_info`.
+#[derive(Debug, Clone, Serialize)]
+pub struct DumpInfo {
+    pub format: String,
+    pub id_size: u32,
+    pub timestamp: u64,
+    // unknown for stdin, the decompressed size is unknown for compressed inputs
+    pub file_size: Option<u64>,
+    pub compressed: bool,
+    // bytes read past the header
+    pub scanned_bytes: u64,
+    // the whole dump was scanned, the counts are exact
+    pub complete: bool,
+    pub counted: RecordCounts,
+    // extrapolated to the whole dump, `None` if its size is unknown or the heap dump was not reached
+    pub estimated: Option<RecordCounts>,
+    // the estimate comes from records sampled across the file instead of the scanned prefix
+    pub sampled: bool,
+}
+
+/// Reads the header and at most `prefix_len` bytes of records of a dump to describe it within milliseconds,
+/// to pick which of many dumps to analyze.
+///
+/// The records of a local uncompressed file not scanned entirely are estimated with `sample_record_counts`,
+/// otherwise the heap dump records are extrapolated from their density in the scanned part of the heap dump.
+pub fn dump_info(file_paths: Vec<String>, prefix_len: usize) -> Result<DumpInfo, HprofSlurpError> {
+    let local_file = match &file_paths[..] {
+        [file_path] if file_path != STDIN_PATH && !is_remote_path(file_path) => {
+            Some(file_path.clone())
+        }
+        _ => None,
+    };
+    let (input, file_len) = open_input(file_paths, false)?;
+    let (input, compression) = decompressed_input(input)?;
+    let file_size = match compression {
+        Compression::None => file_len.map(|len| len as u64),
+        _ => None,
+    };
+    let mut info = read_dump_info(
+        input,
+        file_size,
+        prefix_len,
+        compression != Compression::None,
+    )?;
+    if let (false, Compression::None, Some(file_path)) = (info.complete, compression, local_file) {
+        info.estimated = Some(sample_record_counts(&file_path, DEFAULT_SAMPLES)?);
+        info.sampled = true;
+    }
+    Ok(info)
+}
+
+fn read_dump_info<R: Read>(
+    mut reader: R,
+    file_size: Option<u64>,
+    prefix_len: usize,
+    compressed: bool,
+) -> Result<DumpInfo, HprofSlurpError> {
+    let header = slurp_header(&mut reader)?;
+    let mut prefix = Vec::new();
+    // one more byte tells whether the dump goes past the prefix
+    reader
+        .take(prefix_len as u64 + 1)
+        .read_to_end(&mut prefix)?;
+    let truncated = prefix.len() > prefix_len;
+    prefix.truncate(prefix_len);
+
+    let mut parser = HprofRecordParser::new();
+    let mut parse_record = parser.parse_hprof_record();
+    let mut counted = RecordCounts::default();
+    let mut rest = &prefix[..];
+    // position of the first heap dump and length of its first segment
+    let mut heap_dump: Option<(usize, u32)> = None;
+    while !rest.is_empty() {
+        match parse_record(rest) {
+            Ok((next, record)) => {
+                if let Record::HeapDumpStart { length, .. } = record {
+                    heap_dump.get_or_insert((prefix.len() - rest.len(), length));
+                }
+                counted.count(&record);
+                rest = next;
+            }
+            Err(nom::Err::Incomplete(_)) => break,
+            Err(e) => {
+                return Err(InvalidHprofFile {
+                    message: format!("{:?}", e),
+                })
+            }
+        }
+    }
+    let scanned = prefix.len() - rest.len();
+    let complete = !truncated && rest.is_empty();
+
+    let estimated = if complete {
+        Some(counted)
+    } else {
+        file_size.and_then(|file_size| {
+            let records_len = file_size.saturating_sub(FILE_HEADER_LENGTH as u64);
+            match heap_dump {
+                Some((start, segment_len)) if scanned > start => {
+                    let ratio =
+                        records_len.saturating_sub(start as u64) as f64 / (scanned - start) as f64;
+                    let scale = |count: u64| (count as f64 * ratio).round() as u64;
+                    Some(RecordCounts {
+                        // the segments of a dump mostly have the same length
+                        heap_dump_segments: counted.heap_dump_segments.max(
+                            (records_len.saturating_sub(start as u64) as f64
+                                / (segment_len as u64 + 9) as f64)
+                                .round() as u64,
+                        ),
+                        gc_roots: scale(counted.gc_roots),
+                        class_dumps: scale(counted.class_dumps),
+                        instances: scale(counted.instances),
+                        object_arrays: scale(counted.object_arrays),
+                        primitive_arrays: scale(counted.primitive_arrays),
+                        ..counted
+                    })
+                }
+                // nothing to extrapolate the heap dump from
+                _ => None,
+            }
+        })
+    };
+
+    Ok(DumpInfo {
+        format: header.format,
+        id_size: header.size_pointers,
+        timestamp: header.timestamp,
+        file_size,
+        compressed,
+        scanned_bytes: scanned as u64,
+        complete,
+        counted,
+        estimated,
+        sampled: false,
+    })
+}
+
+impl DumpInfo {
+    pub fn render(&self) -> String {
+        let mut info = format!(
+            "\nFormat: {}\nIdentifier size: {} bytes\nTaken at: {}\nFile size: {}\nScanned: {}{}{}\n\n",
+            self.format,
+            self.id_size,
+            pretty_timestamp(self.timestamp),
+            match self.file_size {
+                Some(size) => pretty_bytes_size(size),
+                None if self.compressed => "unknown (compressed)".to_string(),
+                None => "unknown".to_string(),
+            },
+            pretty_bytes_size(self.scanned_bytes),
+            if self.complete { " (whole dump)" } else { "" },
+            if self.sampled {
+                "\nEstimated from records sampled across the heap dump"
+            } else {
+                ""
+            }
+        );
+        let estimated = self.estimated.map(|counts| counts.rows());
+        let rows: Vec<Vec<String>> = self
+            .counted
+            .rows()
+            .iter()
+            .enumerate()
+            .map(|(index, (name, count))| {
+                vec![
+                    name.to_string(),
+                    pretty_count(*count),
+                    estimated
+                        .map(|rows| pretty_count(rows[index].1))
+                        .unwrap_or_else(|| "?".to_string()),
+                ]
+            })
+            .collect();
+        info.push_str(&render_table(&["Records", "Scanned", "Estimated"], &rows));
+        info
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // header, a string, then a heap dump segment holding a root and `instances` empty instances
+    fn dump(instances: usize) -> Vec<u8> {
+        let mut dump = b"JAVA PROFILE 1.0.2\0".to_vec();
+        dump.extend(8u32.to_be_bytes());
+        dump.extend(1608192273831u64.to_be_bytes());
+        dump.push(0x01);
+        dump.extend(0u32.to_be_bytes());
+        dump.extend(13u32.to_be_bytes());
+        dump.extend(1u64.to_be_bytes());
+        dump.extend(b"hello");
+        dump.push(0x1C);
+        dump.extend(0u32.to_be_bytes());
+        dump.extend((9 + 25 * instances as u32).to_be_bytes());
+        dump.push(0xFF);
+        dump.extend(2u64.to_be_bytes());
+        for object_id in 0..instances as u64 {
+            dump.push(0x21);
+            dump.extend((10 + object_id).to_be_bytes());
+            dump.extend(0u32.to_be_bytes());
+            dump.extend(3u64.to_be_bytes());
+            dump.extend(0u32.to_be_bytes());
+        }
+        dump.push(0x2C);
+        dump.extend(0u32.to_be_bytes());
+        dump.extend(0u32.to_be_bytes());
+        dump
+    }
+
+    #[test]
+    fn describe_a_dump_from_its_prefix() {
+        let dump = dump(100);
+        let file_size = Some(dump.len() as u64);
+        let info = read_dump_info(&dump[..], file_size, INFO_PREFIX_LEN, false).unwrap();
+        assert!(info.complete);
+        assert_eq!(info.id_size, 8);
+        assert_eq!(info.counted.strings, 1);
+        assert_eq!(info.counted.instances, 100);
+        assert_eq!(info.counted.gc_roots, 1);
+        assert_eq!(info.estimated, Some(info.counted));
+        assert!(info.render().contains("Taken at: 2020-12-17T08:04:33.831Z"));
+
+        // the string, the segment header, the root and 20 instances
+        let info = read_dump_info(&dump[..], file_size, 22 + 9 + 9 + 20 * 25, false).unwrap();
+        assert!(!info.complete);
+        assert_eq!(info.counted.instances, 20);
+        let estimated = info.estimated.unwrap();
+        assert!((95..=105).contains(&estimated.instances));
+        assert_eq!(estimated.strings, 1);
+        assert_eq!(estimated.heap_dump_segments, 1);
+
+        let info = read_dump_info(&dump[..], file_size, 30, false).unwrap();
+        assert_eq!(info.counted.strings, 1);
+        assert_eq!(info.estimated, None);
+        let info = read_dump_info(&dump[..], None, 100, true).unwrap();
+        assert_eq!(info.estimated, None);
+        assert!(info.render().contains("unknown (compressed)"));
+    }
+}
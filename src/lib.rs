@@ -1,34 +1,324 @@
-use std::{collections::HashMap, sync::Arc};
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap, HashSet},
+    fmt,
+    io::{self, Write},
+    ops::Deref,
+    sync::Arc,
+};
+
+use rayon::prelude::{IntoParallelRefIterator, ParallelIterator, ParallelSliceMut};
+use serde::{Deserialize, Serialize};
 
+use analysis::baseline::{baseline_diff_report, BaselineProfile};
+use analysis::class_names::{
+    anonymized_class_name, anonymized_method_descriptor, pretty_class_name, NameGrouping,
+};
+use analysis::histogram::{
+    array_owner_histogram, array_owners, class_histogram, ownership_histogram, HistogramOptions,
+    HistogramSort,
+};
+use class_registry::ClassRegistry;
+use indoc::formatdoc;
 use parser::{
-    gc_record::ClassDumpFields,
+    gc_record::{ClassDumpFields, FieldType, FieldValue, Values},
     record::{LoadClassData, StackFrameData, StackTraceData},
 };
-use result_recorder::{Instance, ResultRecorder, RootJniGlobal, RootJniLocal, RootThreadObject};
+use report::{
+    render_blocks, write_blocks, Block, OutputFormat, RenderOptions, Report, ReportMetadata,
+};
+use result_recorder::{
+    primitive_byte_size, Instance, MemoryDegradation, RenderedResult, ResultRecorder,
+    RootJavaFrame, RootJniGlobal, RootJniLocal, RootNativeStack, RootThreadBlock, RootThreadObject,
+    SectionKind, SegmentStats,
+};
+use shared::Shared;
+use utils::{pretty_bytes_size, pretty_count};
 
+pub mod analysis;
+pub mod annotations;
+#[cfg(feature = "cli")]
 pub mod args;
+pub mod async_slurp;
+#[cfg(feature = "checkpoint")]
+pub mod checkpoint;
+pub mod class_export;
+pub mod class_registry;
+#[cfg(feature = "dataframe")]
+pub mod dataframe;
+pub mod decompress;
 pub mod errors;
+#[cfg(feature = "graph")]
+pub mod graph;
+#[cfg(feature = "index")]
+pub mod index;
+pub mod info;
+pub mod mat_export;
+pub mod memory;
+pub mod object_index;
 pub mod parser;
 pub mod prefetch_reader;
+pub mod progress;
+#[cfg(feature = "remote")]
+pub mod remote_reader;
+pub mod report;
 pub mod result_recorder;
+pub mod sampling;
+pub mod session;
+pub mod shared;
 pub mod slurp;
 pub mod utils;
+pub mod verify;
 
-#[derive(Debug, Clone, Default)]
+// classes listed in the summary when their instances do not match the field layout
+const MISMATCHES_LISTED: usize = 10;
+
+// real class hierarchies are a few dozen classes deep, a corrupt dump may chain its super classes in a cycle
+pub(crate) const MAX_CLASS_HIERARCHY_DEPTH: usize = 1024;
+
+// 32 bits references scaled by the 8 bytes object alignment address up to 32GiB
+const COMPRESSED_OOPS_MAX_HEAP_BYTES: u64 = 32 * 1024 * 1024 * 1024;
+
+/// The slurped heap, its indexes are shared between clones which makes it cheap to clone and to share across threads.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct Heap {
     pub counter: HeapCounter,
 
-    pub utf8_strings: HashMap<u64, Box<str>>,
-    pub class_data: HashMap<u64, LoadClassData>,
-    pub classes_dump: HashMap<u64, ClassDumpFields>,
-    pub stack_trace_by_serial_number: HashMap<u32, StackTraceData>,
-    pub stack_frame_by_id: HashMap<u64, StackFrameData>,
-    pub instances_pool: HashMap<u64, Arc<Instance>>,
-    pub root_thread_object: HashMap<u64, RootThreadObject>,
-    pub root_jni_local: HashMap<u64, RootJniLocal>,
-    pub root_jni_global: HashMap<u64, RootJniGlobal>,
-}
-#[derive(Debug, Clone, Default)]
+    pub utf8_strings: Shared<HashMap<u64, Box<str>>>,
+    pub class_registry: Shared<ClassRegistry>,
+    pub classes_dump: Shared<HashMap<u64, ClassDumpFields>>,
+    pub stack_trace_by_serial_number: Shared<HashMap<u32, StackTraceData>>,
+    pub stack_frame_by_id: Shared<HashMap<u64, StackFrameData>>,
+    pub instances_pool: Shared<HashMap<u64, Arc<Instance>>>,
+    pub root_thread_object: Shared<HashMap<u64, RootThreadObject>>,
+    pub root_jni_local: Shared<Vec<RootJniLocal>>,
+    pub root_jni_global: Shared<HashMap<u64, RootJniGlobal>>,
+    pub root_java_frame: Shared<Vec<RootJavaFrame>>,
+    pub root_native_stack: Shared<Vec<RootNativeStack>>,
+    pub root_thread_block: Shared<Vec<RootThreadBlock>>,
+    pub root_sticky_class: Shared<Vec<u64>>,
+    pub root_monitor_used: Shared<Vec<u64>>,
+    pub root_unknown: Shared<Vec<u64>>,
+    // payloads dropped to stay within the memory budget, the analyses relying on them are incomplete
+    pub degradation: Option<MemoryDegradation>,
+    // input offset at which `SlurpOptions::stop_when` stopped the run, the later records are missing
+    pub stopped_at: Option<u64>,
+    // object id -> offset of its record in the input, kept with `SlurpOptions::keep_record_offsets`
+    pub record_offsets: Shared<HashMap<u64, u64>>,
+    pub provenance: DumpProvenance,
+    pub reference_sizing: ReferenceSizing,
+}
+/// Where the heap was slurped from, embedded in the reports so that archived ones are self-describing.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DumpProvenance {
+    pub input_files: Vec<String>,
+    // unknown for stdin and compressed inputs
+    pub input_size: Option<u64>,
+    // e.g. `JAVA PROFILE 1.0.2`
+    pub format: String,
+    // milliseconds since the epoch at which the dump was taken
+    pub timestamp: u64,
+}
+
+/// How wide the references were in the JVM memory, the dumps record every object id with the full pointer size.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ReferenceSizing {
+    // 32 bits references, enabled by default on 64-bit JVMs with heaps below 32GiB
+    pub compressed_oops: bool,
+    // heap size with full width references the inference is based on
+    pub estimated_heap_bytes: u64,
+}
+
+/// An object of the heap: an instance or array of the instance pool, or a class object.
+#[derive(Debug, Clone, Copy)]
+pub enum HeapObject<'a> {
+    Instance(&'a Arc<Instance>),
+    Class(&'a ClassDumpFields),
+}
+
+impl HeapObject<'_> {
+    pub fn object_id(&self) -> u64 {
+        match self {
+            HeapObject::Instance(instance) => instance.object_id,
+            HeapObject::Class(class) => class.class_object_id,
+        }
+    }
+
+    pub fn as_instance(&self) -> Option<&Arc<Instance>> {
+        match self {
+            HeapObject::Instance(instance) => Some(instance),
+            HeapObject::Class(_) => None,
+        }
+    }
+}
+
+/// An instance with its class and field names resolved, the primary way to inspect an object.
+///
+/// Arrays have no fields, their elements are available through `Instance::array_value`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ResolvedInstance {
+    pub object_id: u64,
+    pub class_name: String,
+    // declared by the class first, then by each super class up to `java.lang.Object`
+    pub fields: Vec<ResolvedField>,
+    // false if the field values were not retained while slurping
+    pub fields_retained: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ResolvedField {
+    pub name: String,
+    pub declaring_class: String,
+    pub value: FieldValue,
+}
+
+impl ResolvedInstance {
+    /// Returns the value of the field named `name`, fields of subclasses shadow the ones of their super classes.
+    pub fn field(&self, name: &str) -> Option<&FieldValue> {
+        self.fields
+            .iter()
+            .find(|field| field.name == name)
+            .map(|field| &field.value)
+    }
+}
+
+// e.g. `java.util.HashMap$Node@0x7f0012a8 { hash = 42, key = 0x7f0012b0, ... }`
+impl fmt::Display for ResolvedInstance {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}@0x{:x} {{", self.class_name, self.object_id)?;
+        for (i, field) in self.fields.iter().enumerate() {
+            let separator = if i == 0 { " " } else { ", " };
+            write!(f, "{}{} = {}", separator, field.name, field.value)?;
+        }
+        if self.fields.is_empty() {
+            write!(f, "}}")
+        } else {
+            write!(f, " }}")
+        }
+    }
+}
+
+/// Distribution of a numeric field over the instances of a class, see `Heap::field_stats`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FieldStats {
+    // instances holding a numeric value in the field
+    pub count: u64,
+    // instances without the field or whose fields were not retained
+    pub missing: u64,
+    pub min: f64,
+    pub max: f64,
+    pub mean: f64,
+    // nearest rank percentiles
+    pub p50: f64,
+    pub p90: f64,
+    pub p99: f64,
+}
+
+/// Instances sharing a field value, see `Heap::group_by`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FieldGroup {
+    // strings quoted, enums as `Class.CONSTANT`, other objects as `Class@0xid`
+    pub value: String,
+    pub count: u64,
+}
+
+/// How `Heap::sample_instances_with` picks the instances of a class.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SampleMode {
+    // the instances with the lowest object ids
+    First,
+    // pseudo random, the same seed picks the same instances from the same heap
+    Random { seed: u64 },
+}
+
+/// A resolved stack frame, rendered like a Java stack trace line.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FrameView {
+    pub class_name: String,
+    pub method_name: String,
+    pub source_file: String,
+    // >0: normal, -1: unknown, -2: compiled method, -3: native method
+    pub line_number: i32,
+}
+
+impl FrameView {
+    pub fn pretty_line_number(&self) -> String {
+        match self.line_number {
+            -1 => "unknown line number".to_string(),
+            -2 => "compiled method".to_string(),
+            -3 => "native method".to_string(),
+            number => format!("{}", number),
+        }
+    }
+}
+
+impl fmt::Display for FrameView {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "at {}.{} ({}:{})",
+            pretty_class_name(&self.class_name),
+            self.method_name,
+            self.source_file,
+            self.pretty_line_number()
+        )
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub enum GcRootKind {
+    Unknown,
+    ThreadObject,
+    JniGlobal,
+    JniLocal,
+    JavaFrame,
+    NativeStack,
+    StickyClass,
+    ThreadBlock,
+    MonitorUsed,
+}
+
+/// Object, class and root counts of a `Heap`, for programmatic consumers of the summary.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HeapStats {
+    // instances of classes, arrays excluded
+    pub instances: u64,
+    pub object_arrays: u64,
+    pub primitive_arrays: u64,
+    // shallow size of the instances and arrays
+    pub total_shallow_bytes: u64,
+    pub classes: u64,
+    // arrays per element type name, e.g. `int` or `object`
+    pub arrays_by_element_type: BTreeMap<String, u64>,
+    pub roots_by_kind: BTreeMap<GcRootKind, u64>,
+}
+
+/// Instances of a class whose `data_size` differs from the size of the fields declared by the class hierarchy.
+///
+/// Usually a parsing bug or a JVM laying out fields unusually, the field values of these instances are unreliable.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct InstanceSizeMismatch {
+    pub class_object_id: u64,
+    pub class_name: String,
+    // sum of the instance field sizes of the class and its super classes
+    pub expected_data_size: u64,
+    pub mismatched_instances: u64,
+    // distinct data sizes found, in increasing order
+    pub data_sizes: Vec<u64>,
+}
+
+/// A GC root with the thread and stack frame holding it when the dump records them.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GcRootView {
+    pub kind: GcRootKind,
+    pub object_id: u64,
+    pub thread_serial_number: Option<u32>,
+    pub thread_object_id: Option<u64>,
+    pub thread_name: Option<String>,
+    // only for JNI locals and Java frames
+    pub frame: Option<FrameView>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct HeapCounter {
     pub id_size: u32,
     // Tag counters
@@ -43,20 +333,24 @@ pub struct HeapCounter {
     pub control_settings: i32,
     pub cpu_samples: i32,
     // GC tag counters
-    pub heap_dump_segments_all_sub_records: i32,
-    pub heap_dump_segments_gc_root_unknown: i32,
-    pub heap_dump_segments_gc_root_thread_object: i32,
-    pub heap_dump_segments_gc_root_jni_global: i32,
-    pub heap_dump_segments_gc_root_jni_local: i32,
-    pub heap_dump_segments_gc_root_java_frame: i32,
-    pub heap_dump_segments_gc_root_native_stack: i32,
-    pub heap_dump_segments_gc_root_sticky_class: i32,
-    pub heap_dump_segments_gc_root_thread_block: i32,
-    pub heap_dump_segments_gc_root_monitor_used: i32,
-    pub heap_dump_segments_gc_object_array_dump: i32,
-    pub heap_dump_segments_gc_instance_dump: i32,
-    pub heap_dump_segments_gc_primitive_array_dump: i32,
-    pub heap_dump_segments_gc_class_dump: i32,
+    pub heap_dump_segments_all_sub_records: u64,
+    pub heap_dump_segments_gc_root_unknown: u64,
+    pub heap_dump_segments_gc_root_thread_object: u64,
+    pub heap_dump_segments_gc_root_jni_global: u64,
+    pub heap_dump_segments_gc_root_jni_local: u64,
+    pub heap_dump_segments_gc_root_java_frame: u64,
+    pub heap_dump_segments_gc_root_native_stack: u64,
+    pub heap_dump_segments_gc_root_sticky_class: u64,
+    pub heap_dump_segments_gc_root_thread_block: u64,
+    pub heap_dump_segments_gc_root_monitor_used: u64,
+    pub heap_dump_segments_gc_object_array_dump: u64,
+    pub heap_dump_segments_gc_instance_dump: u64,
+    pub heap_dump_segments_gc_primitive_array_dump: u64,
+    pub heap_dump_segments_gc_class_dump: u64,
+    // primitive arrays sharing the payload of an identical array
+    pub deduplicated_primitive_arrays: u64,
+    pub deduplicated_primitive_array_bytes: u64,
+    pub segments: SegmentStats,
 }
 
 impl From<ResultRecorder> for Heap {
@@ -89,18 +383,2226 @@ impl From<ResultRecorder> for Heap {
             heap_dump_segments_gc_primitive_array_dump: value
                 .heap_dump_segments_gc_primitive_array_dump,
             heap_dump_segments_gc_class_dump: value.heap_dump_segments_gc_class_dump,
+            deduplicated_primitive_arrays: value.deduplicated_primitive_arrays,
+            deduplicated_primitive_array_bytes: value.deduplicated_primitive_array_bytes,
+            segments: value.segments,
         };
-        Self {
+        let mut heap = Self {
             counter,
-            utf8_strings: value.utf8_strings_by_id,
-            class_data: value.load_class,
-            classes_dump: value.classes_dump,
-            stack_trace_by_serial_number: value.stack_trace_by_serial_number,
-            stack_frame_by_id: value.stack_frame_by_id,
-            instances_pool: value.instances,
-            root_jni_global: value.root_jni_global,
-            root_jni_local: value.root_jni_local,
-            root_thread_object: value.root_thread_object,
+            utf8_strings: value.utf8_strings_by_id.into(),
+            class_registry: value.class_registry.into(),
+            classes_dump: value.classes_dump.into(),
+            stack_trace_by_serial_number: value.stack_trace_by_serial_number.into(),
+            stack_frame_by_id: value.stack_frame_by_id.into(),
+            instances_pool: value.instances.into(),
+            root_jni_global: value.root_jni_global.into(),
+            root_jni_local: value.root_jni_local.into(),
+            root_thread_object: value.root_thread_object.into(),
+            root_java_frame: value.root_java_frame.into(),
+            root_native_stack: value.root_native_stack.into(),
+            root_thread_block: value.root_thread_block.into(),
+            root_sticky_class: value.root_sticky_class.into(),
+            root_monitor_used: value.root_monitor_used.into(),
+            root_unknown: value.root_unknown.into(),
+            degradation: value.degradation,
+            stopped_at: value.stopped_at.map(|offset| offset as u64),
+            record_offsets: value.record_offsets.into(),
+            provenance: DumpProvenance::default(),
+            reference_sizing: ReferenceSizing::default(),
+        };
+        heap.reference_sizing = heap.infer_reference_sizing();
+        heap
+    }
+}
+
+impl Heap {
+    /// Replaces the names of the application classes, the source files of their stack frames and the class names
+    /// in the method signatures with the pseudonyms of `anonymized_class_name` in every later report and export.
+    /// The JDK classes keep their name, the contents of the `String` instances are not rewritten.
+    pub fn anonymize_class_names(&mut self, salt: &str) {
+        let name_ids: Vec<u64> = self.class_registry.class_name_ids().collect();
+        let mut renamed_serial_numbers = HashSet::new();
+        for name_id in name_ids {
+            let Some(name) = self.utf8_strings.get(&name_id) else {
+                continue;
+            };
+            let anonymized = anonymized_class_name(name, salt);
+            if anonymized != **name {
+                self.utf8_strings
+                    .make_mut()
+                    .insert(name_id, anonymized.into());
+                renamed_serial_numbers.extend(
+                    self.class_registry
+                        .by_name_id(name_id)
+                        .map(|class| class.serial_number),
+                );
+            }
+        }
+        // `Order.java` gets the pseudonym of `Order`
+        let source_file_ids: HashSet<u64> = self
+            .stack_frame_by_id
+            .values()
+            .filter(|frame| renamed_serial_numbers.contains(&frame.class_serial_number))
+            .map(|frame| frame.source_file_name_id)
+            .collect();
+        for source_file_id in source_file_ids {
+            if let Some(source_file) = self.utf8_strings.get(&source_file_id) {
+                let (stem, extension) = source_file.rsplit_once('.').unwrap_or((source_file, ""));
+                let anonymized = format!("{}.{}", anonymized_class_name(stem, salt), extension);
+                self.utf8_strings
+                    .make_mut()
+                    .insert(source_file_id, anonymized.trim_end_matches('.').into());
+            }
+        }
+        let signature_ids: HashSet<u64> = self
+            .stack_frame_by_id
+            .values()
+            .map(|frame| frame.method_signature_id)
+            .collect();
+        for signature_id in signature_ids {
+            if let Some(signature) = self.utf8_strings.get(&signature_id) {
+                let anonymized = anonymized_method_descriptor(signature, salt);
+                if anonymized != **signature {
+                    self.utf8_strings
+                        .make_mut()
+                        .insert(signature_id, anonymized.into());
+                }
+            }
+        }
+    }
+
+    /// Returns the class name (e.g. `java.lang.String`) of a class object id.
+    ///
+    /// Classes unloaded before the heap dump are suffixed with `(unloaded)`, their objects should not exist.
+    pub fn class_name(&self, class_object_id: u64) -> Option<String> {
+        let name = |class_data: &LoadClassData| {
+            self.utf8_strings
+                .get(&class_data.class_name_id)
+                .map(|name| name.replace('/', "."))
+        };
+        match self.class_registry.by_id(class_object_id) {
+            Some(class_data) => name(class_data),
+            None => self
+                .class_registry
+                .unloaded_by_id(class_object_id)
+                .and_then(name)
+                .map(|name| format!("{} (unloaded)", name)),
+        }
+    }
+
+    /// Returns the class name of an instance, primitive arrays are named after their element type (e.g. `byte[]`).
+    pub fn instance_class_name(&self, instance: &Instance) -> String {
+        match instance.array_element_type() {
+            Some(FieldType::Object) | None => self
+                .class_name(instance.class_object_id)
+                .unwrap_or_else(|| "unknown class".to_string()),
+            Some(element_type) => {
+                format!("{}[]", primitive_type_name(element_type))
+            }
+        }
+    }
+
+    /// Returns true if the class or one of its super classes is named `class_name`.
+    pub fn is_subclass_of(&self, class_object_id: u64, class_name: &str) -> bool {
+        let mut current = class_object_id;
+        for _ in 0..MAX_CLASS_HIERARCHY_DEPTH {
+            if current == 0 {
+                return false;
+            }
+            if self.class_name(current).as_deref() == Some(class_name) {
+                return true;
+            }
+            current = match self.classes_dump.get(&current) {
+                Some(class) => class.super_class_object_id,
+                None => return false,
+            };
+        }
+        false
+    }
+
+    /// Returns the value of the field named `field_name`, looking into the fields declared by super classes as well.
+    pub fn field_value<'a>(
+        &self,
+        instance: &'a Instance,
+        field_name: &str,
+    ) -> Option<&'a FieldValue> {
+        instance
+            .all_fields()
+            .find_map(|(name_id, value)| match value {
+                Values::Single(field_value)
+                    if self.utf8_strings.get(name_id).map(|n| n.deref()) == Some(field_name) =>
+                {
+                    Some(field_value)
+                }
+                _ => None,
+            })
+    }
+
+    /// Resolves the class name and the field names of an instance, see `ResolvedInstance`.
+    pub fn resolve_instance(&self, instance: &Instance) -> ResolvedInstance {
+        let class_name = self.instance_class_name(instance);
+        let mut fields = Vec::new();
+        if instance.array_element_type().is_none() {
+            let declaring_classes = self.declaring_classes(instance.class_object_id);
+            fields = instance
+                .all_fields()
+                .zip(declaring_classes)
+                .filter_map(|((name_id, value), class_id)| match value {
+                    Values::Single(value) => Some(ResolvedField {
+                        name: self
+                            .utf8_strings
+                            .get(name_id)
+                            .map_or_else(|| format!("field#{}", name_id), |n| n.to_string()),
+                        declaring_class: self
+                            .class_name(class_id)
+                            .unwrap_or_else(|| "unknown class".to_string()),
+                        value: value.clone(),
+                    }),
+                    _ => None,
+                })
+                .collect();
+        }
+        // dropped payloads leave no fields behind
+        let fields_retained = instance.data_size == 0
+            || !instance.fields.is_empty()
+            || !instance.super_fields.is_empty();
+        ResolvedInstance {
+            object_id: instance.object_id,
+            class_name,
+            fields,
+            fields_retained,
+        }
+    }
+
+    /// Returns the class declaring each of the fields of an instance of `class_object_id`, in the `Instance::all_fields` order.
+    pub fn declaring_classes(&self, class_object_id: u64) -> Vec<u64> {
+        // the super fields are laid out class after class up the hierarchy
+        let mut declaring_classes = Vec::new();
+        let mut class_id = class_object_id;
+        for _ in 0..MAX_CLASS_HIERARCHY_DEPTH {
+            let Some(class) = self.classes_dump.get(&class_id) else {
+                break;
+            };
+            declaring_classes.extend(std::iter::repeat_n(class_id, class.instance_fields.len()));
+            class_id = class.super_class_object_id;
+        }
+        declaring_classes
+    }
+
+    /// Returns the offset of the record of a class, instance or array in the input, to seek there for the bytes not retained.
+    ///
+    /// Offsets count from the start of the file, after decompression for compressed inputs.
+    pub fn record_offset(&self, object_id: u64) -> Option<u64> {
+        self.record_offsets.get(&object_id).copied()
+    }
+
+    /// Looks up an object id among the instances, arrays and class objects.
+    pub fn object(&self, object_id: u64) -> Option<HeapObject<'_>> {
+        match self.instances_pool.get(&object_id) {
+            Some(instance) => Some(HeapObject::Instance(instance)),
+            None => self.classes_dump.get(&object_id).map(HeapObject::Class),
+        }
+    }
+
+    /// Iterates over the objects referenced by an object array in index order, skipping nulls
+    /// and the objects missing from the dump.
+    ///
+    /// Yields nothing for instances, primitive arrays and arrays whose elements were not retained.
+    pub fn array_elements<'a>(
+        &'a self,
+        array: &'a Instance,
+    ) -> impl Iterator<Item = HeapObject<'a>> + 'a {
+        array
+            .object_array()
+            .unwrap_or_default()
+            .iter()
+            .filter(|id| **id != 0)
+            .filter_map(|id| self.object(*id))
+    }
+
+    /// Follows the object reference stored in the field named `field_name`.
+    pub fn field_instance(&self, instance: &Instance, field_name: &str) -> Option<&Arc<Instance>> {
+        match self.field_value(instance, field_name) {
+            Some(FieldValue::Object(object_id)) => self.instances_pool.get(object_id),
+            _ => None,
+        }
+    }
+
+    /// Size of the objects which would be garbage collected if all of `object_ids` were freed together,
+    /// e.g. to estimate the savings of clearing a cache.
+    ///
+    /// Builds the reference graph, use `HeapGraph::retained_size_of` to answer several queries.
+    #[cfg(feature = "graph")]
+    pub fn retained_size_of(&self, object_ids: &[u64]) -> u64 {
+        graph::HeapGraph::new(self).retained_size_of(object_ids)
+    }
+
+    /// Decodes the content of a `java.lang.String` instance (compact strings and `char[]` based layouts).
+    ///
+    /// Returns `None` if the backing array was not retained while slurping.
+    pub fn string_value(&self, object_id: u64) -> Option<String> {
+        let instance = self.instances_pool.get(&object_id)?;
+        let coder = match self.field_value(instance, "coder") {
+            Some(FieldValue::Byte(coder)) => *coder,
+            _ => 0,
+        };
+        let value = self
+            .field_instance(instance, "value")
+            .filter(|value| value.elements_retained())?;
+        // decoded from the raw bytes without materializing the array
+        let array = value.primitive_array()?;
+        let bytes = array.bytes();
+        match array.element_type {
+            FieldType::Char => {
+                let chars: Vec<u16> = bytes
+                    .chunks_exact(2)
+                    .map(|pair| u16::from_be_bytes([pair[0], pair[1]]))
+                    .collect();
+                Some(String::from_utf16_lossy(&chars))
+            }
+            // UTF16 in the native byte order of the dumping JVM (little-endian on mainstream platforms)
+            FieldType::Byte if coder == 1 => {
+                let chars: Vec<u16> = bytes
+                    .chunks_exact(2)
+                    .map(|pair| u16::from_le_bytes([pair[0], pair[1]]))
+                    .collect();
+                Some(String::from_utf16_lossy(&chars))
+            }
+            // LATIN1
+            FieldType::Byte => Some(bytes.iter().map(|b| *b as char).collect()),
+            _ => None,
+        }
+    }
+
+    /// Returns the constant name of a `java.lang.Enum` instance.
+    pub fn enum_name(&self, instance: &Instance) -> Option<String> {
+        match self.field_value(instance, "name") {
+            Some(FieldValue::Object(name_id)) => self.string_value(*name_id),
+            _ => None,
+        }
+    }
+
+    /// Iterates over the instances whose class is `class_name` or one of its subclasses.
+    pub fn instances_of<'a>(
+        &'a self,
+        class_name: &'a str,
+    ) -> impl Iterator<Item = &'a Arc<Instance>> + 'a {
+        let class_ids: HashSet<u64> = self
+            .classes_dump
+            .keys()
+            .filter(|class_id| self.is_subclass_of(**class_id, class_name))
+            .copied()
+            .collect();
+        self.instances_pool.values().filter(move |instance| {
+            instance.array_element_type().is_none() && class_ids.contains(&instance.class_object_id)
+        })
+    }
+
+    /// Resolves up to `n` random instances of `class_name` or of its subclasses, to see what typical instances look like.
+    pub fn sample_instances(&self, class_name: &str, n: usize) -> Vec<ResolvedInstance> {
+        self.sample_instances_with(class_name, n, SampleMode::Random { seed: 0 })
+    }
+
+    /// Like `sample_instances`, the sampled instances are ordered by object id.
+    pub fn sample_instances_with(
+        &self,
+        class_name: &str,
+        n: usize,
+        mode: SampleMode,
+    ) -> Vec<ResolvedInstance> {
+        // the instances pool iterates in a different order on every run
+        let mut candidates: Vec<&Arc<Instance>> = self.instances_of(class_name).collect();
+        candidates.sort_unstable_by_key(|instance| instance.object_id);
+        let n = n.min(candidates.len());
+        if let SampleMode::Random { seed } = mode {
+            // partial Fisher-Yates shuffle moving the sample to the front
+            let mut state = seed;
+            for i in 0..n {
+                let j = i + (splitmix64(&mut state) % (candidates.len() - i) as u64) as usize;
+                candidates.swap(i, j);
+            }
+            candidates[..n].sort_unstable_by_key(|instance| instance.object_id);
+        }
+        candidates[..n]
+            .iter()
+            .map(|instance| self.resolve_instance(instance))
+            .collect()
+    }
+
+    /// Computes the distribution of a numeric `field` (e.g. `size` of `java.util.ArrayList`) over the instances
+    /// of `class_name` and of its subclasses, `None` if no instance holds a numeric value in the field.
+    pub fn field_stats(&self, class_name: &str, field: &str) -> Option<FieldStats> {
+        let mut missing = 0;
+        let mut values: Vec<f64> = self
+            .instances_of(class_name)
+            .filter_map(|instance| {
+                let value = self
+                    .field_value(instance, field)
+                    .and_then(FieldValue::as_f64);
+                if value.is_none() {
+                    missing += 1;
+                }
+                value
+            })
+            .collect();
+        if values.is_empty() {
+            return None;
+        }
+        values.sort_unstable_by(f64::total_cmp);
+        let percentile = |p: f64| {
+            let rank = (p * values.len() as f64 / 100.0).ceil() as usize;
+            values[rank.max(1) - 1]
+        };
+        Some(FieldStats {
+            count: values.len() as u64,
+            missing,
+            min: values[0],
+            max: values[values.len() - 1],
+            mean: values.iter().sum::<f64>() / values.len() as f64,
+            p50: percentile(50.0),
+            p90: percentile(90.0),
+            p99: percentile(99.0),
+        })
+    }
+
+    /// Counts the instances of `class_name` and of its subclasses per distinct value of `field`, most common first.
+    ///
+    /// Strings are grouped by content and enums by constant, other objects by identity.
+    /// Instances without the field or whose fields were not retained are skipped.
+    pub fn group_by(&self, class_name: &str, field: &str) -> Vec<FieldGroup> {
+        let mut counts: HashMap<String, u64> = HashMap::new();
+        for instance in self.instances_of(class_name) {
+            if let Some(value) = self.field_value(instance, field) {
+                *counts.entry(self.group_key(value)).or_default() += 1;
+            }
+        }
+        let mut groups: Vec<FieldGroup> = counts
+            .into_iter()
+            .map(|(value, count)| FieldGroup { value, count })
+            .collect();
+        groups.sort_by(|a, b| b.count.cmp(&a.count).then_with(|| a.value.cmp(&b.value)));
+        groups
+    }
+
+    fn group_key(&self, value: &FieldValue) -> String {
+        let Some(object_id) = value.as_object_id() else {
+            return value.to_string();
+        };
+        let Some(instance) = self.instances_pool.get(&object_id) else {
+            return self.object_class_name(object_id);
+        };
+        let class_name = self.instance_class_name(instance);
+        let decoded = if class_name == "java.lang.String" {
+            self.string_value(object_id)
+                .map(|string| format!("\"{}\"", string))
+        } else if self.is_subclass_of(instance.class_object_id, "java.lang.Enum") {
+            self.enum_name(instance)
+                .map(|name| format!("{}.{}", class_name, name))
+        } else {
+            None
+        };
+        decoded.unwrap_or_else(|| format!("{}@0x{:x}", class_name, object_id))
+    }
+
+    /// Estimates the shallow size of an instance or array on a 64-bit JVM, see `reference_size`.
+    pub fn shallow_size(&self, instance: &Instance) -> u64 {
+        self.shallow_size_with(instance, self.reference_size())
+    }
+
+    fn shallow_size_with(&self, instance: &Instance, reference_size: u64) -> u64 {
+        // object_header = mark(ref_size) + klass(4) + padding_gap(4) = 16 bytes
+        // array_header = mark(ref_size) + klass(4) + array_length(4) = 16 bytes
+        let id_size = self.counter.id_size as u64;
+        let header = id_size + 4 + 4;
+        // `data_size` counts the references with the width of the ids
+        let narrowed_bytes = id_size - reference_size;
+        let references = if narrowed_bytes == 0 {
+            0
+        } else if instance.object_array().is_some() {
+            instance.data_size / id_size
+        } else {
+            // not narrowed for instances whose fields were not retained
+            instance
+                .all_fields()
+                .filter(|(_, value)| matches!(value, Values::Single(FieldValue::Object(_))))
+                .count() as u64
+        };
+        // `data_size` also covers arrays whose elements were not retained, a corrupt one may be too small
+        align_to_8((header + instance.data_size).saturating_sub(references * narrowed_bytes))
+    }
+
+    /// Size of the references in the JVM memory, 4 bytes when compressed oops are inferred.
+    pub fn reference_size(&self) -> u64 {
+        if self.reference_sizing.compressed_oops {
+            4
+        } else {
+            self.counter.id_size as u64
+        }
+    }
+
+    /// Infers compressed oops from the heap size, the JVM enables them by default below 32GiB.
+    pub fn infer_reference_sizing(&self) -> ReferenceSizing {
+        let id_size = self.counter.id_size as u64;
+        let estimated_heap_bytes: u64 = self
+            .instances_pool
+            .par_iter()
+            .map(|(_, instance)| self.shallow_size_with(instance, id_size))
+            .sum();
+        ReferenceSizing {
+            // 32-bit JVMs have nothing to compress
+            compressed_oops: id_size == 8 && estimated_heap_bytes < COMPRESSED_OOPS_MAX_HEAP_BYTES,
+            estimated_heap_bytes,
+        }
+    }
+
+    /// Describes any object id, class objects are prefixed with `class`.
+    pub fn object_class_name(&self, object_id: u64) -> String {
+        if self.classes_dump.contains_key(&object_id) {
+            let name = self
+                .class_name(object_id)
+                .unwrap_or_else(|| "unknown class".to_string());
+            format!("class {}", name)
+        } else if let Some(instance) = self.instances_pool.get(&object_id) {
+            self.instance_class_name(instance)
+        } else {
+            "unknown object".to_string()
+        }
+    }
+
+    /// Names the reference from `from_object_id` to `to_object_id` (field name, static field name or array index).
+    pub fn reference_label(&self, from_object_id: u64, to_object_id: u64) -> Option<String> {
+        let field_name = |name_id: &u64| {
+            self.utf8_strings
+                .get(name_id)
+                .map(|name| name.to_string())
+                .unwrap_or_else(|| "unknown field".to_string())
+        };
+        if let Some(class) = self.classes_dump.get(&from_object_id) {
+            return class
+                .static_fields
+                .iter()
+                .find(|(_, value)| matches!(value, FieldValue::Object(id) if *id == to_object_id))
+                .map(|(field, _)| format!("static {}", field_name(&field.name_id)))
+                .or_else(|| {
+                    if class.super_class_object_id == to_object_id {
+                        Some("<super>".to_string())
+                    } else if class.class_loader_object_id == to_object_id {
+                        Some("<classloader>".to_string())
+                    } else {
+                        None
+                    }
+                });
+        }
+        let instance = self.instances_pool.get(&from_object_id)?;
+        match instance.array_element_type() {
+            Some(FieldType::Object) => instance
+                .object_array()
+                .unwrap_or_default()
+                .iter()
+                .position(|id| *id == to_object_id)
+                .map(|index| format!("[{}]", index)),
+            Some(_) => None,
+            None => instance
+                .all_fields()
+                .find(|(_, value)| {
+                    matches!(value, Values::Single(FieldValue::Object(id)) if *id == to_object_id)
+                })
+                .map(|(name_id, _)| field_name(name_id)),
+        }
+        .or_else(|| (instance.class_object_id == to_object_id).then(|| "<class>".to_string()))
+    }
+
+    /// Returns the `LoadClass` data of the class with the given serial number.
+    pub fn class_by_serial_number(&self, class_serial_number: u32) -> Option<&LoadClassData> {
+        self.class_registry.by_serial_number(class_serial_number)
+    }
+
+    /// Resolves the frames of a stack trace, `None` if the trace is not in the dump.
+    ///
+    /// Frames or classes missing from the dump are rendered as unknown instead of being dropped.
+    pub fn stack_frames(&self, stack_trace_serial_number: u32) -> Option<Vec<FrameView>> {
+        let stack_trace = self
+            .stack_trace_by_serial_number
+            .get(&stack_trace_serial_number)?;
+        let utf8 = |id: &u64, default: &str| {
+            self.utf8_strings
+                .get(id)
+                .map(|b| b.deref())
+                .unwrap_or(default)
+                .to_string()
+        };
+        let frames = stack_trace
+            .stack_frame_ids
+            .iter()
+            .map(
+                |stack_frame_id| match self.stack_frame_by_id.get(stack_frame_id) {
+                    Some(stack_frame) => FrameView {
+                        class_name: self
+                            .class_by_serial_number(stack_frame.class_serial_number)
+                            .and_then(|class_data| self.class_name(class_data.class_object_id))
+                            .unwrap_or_else(|| "unknown class".to_string()),
+                        method_name: utf8(&stack_frame.method_name_id, "unknown method name"),
+                        source_file: utf8(&stack_frame.source_file_name_id, "unknown source file"),
+                        line_number: stack_frame.line_number,
+                    },
+                    None => FrameView {
+                        class_name: "unknown class".to_string(),
+                        method_name: "unknown method name".to_string(),
+                        source_file: "unknown source file".to_string(),
+                        line_number: -1,
+                    },
+                },
+            )
+            .collect();
+        Some(frames)
+    }
+
+    /// Renders a stack trace with one indented `at` line per frame.
+    pub fn format_stack_trace(&self, stack_trace_serial_number: u32) -> Option<String> {
+        let frames = self.stack_frames(stack_trace_serial_number)?;
+        Some(
+            frames
+                .iter()
+                .map(|frame| format!("  {}\n", frame))
+                .collect(),
+        )
+    }
+
+    /// Renders the summary, thread info, memory usage and optionally all the UTF-8 strings as sections.
+    pub fn render(
+        &self,
+        top: usize,
+        list_strings: bool,
+        histogram_options: HistogramOptions,
+    ) -> RenderedResult {
+        self.report(top, list_strings, histogram_options)
+            .rendered(OutputFormat::Text, &RenderOptions::default())
+    }
+
+    /// Structured content of the sections of `render`, to render in other formats.
+    pub fn report(
+        &self,
+        top: usize,
+        list_strings: bool,
+        histogram_options: HistogramOptions,
+    ) -> Report {
+        self.report_with_sections(top, list_strings, histogram_options, &[])
+    }
+
+    /// The sections of `report` followed by `sections`, e.g. some of `SectionKind::OPTIONAL`.
+    pub fn report_with_sections(
+        &self,
+        top: usize,
+        list_strings: bool,
+        histogram_options: HistogramOptions,
+        sections: &[SectionKind],
+    ) -> Report {
+        let mut report = Report {
+            metadata: Some(ReportMetadata::new(
+                self.provenance.clone(),
+                top,
+                list_strings,
+                histogram_options,
+            )),
+            ..Default::default()
+        };
+        let mut kinds = vec![
+            SectionKind::Summary,
+            SectionKind::ThreadInfo,
+            SectionKind::MemoryUsage,
+        ];
+        if list_strings {
+            kinds.push(SectionKind::CapturedStrings);
+        }
+        for kind in sections {
+            if !kinds.contains(kind) {
+                kinds.push(*kind);
+            }
+        }
+        // the sections are independent, the order is kept by the indexed collect
+        let sections: Vec<Vec<Block>> = kinds
+            .par_iter()
+            .map(|kind| self.section_blocks(*kind, top, histogram_options))
+            .collect();
+        for (kind, blocks) in kinds.into_iter().zip(sections) {
+            report.push(kind, blocks);
+        }
+        report
+    }
+
+    pub fn section_blocks(
+        &self,
+        kind: SectionKind,
+        top: usize,
+        histogram_options: HistogramOptions,
+    ) -> Vec<Block> {
+        match kind {
+            SectionKind::Summary => self.summary_blocks(),
+            SectionKind::ThreadInfo => self.thread_info_blocks(),
+            SectionKind::MemoryUsage => self.memory_usage_blocks(top, histogram_options),
+            SectionKind::CapturedStrings => self.captured_strings_blocks(),
+            SectionKind::GraphMetrics => self.graph_metrics_blocks(top),
+            SectionKind::ReferenceCycles => {
+                self.reference_cycles_blocks(top, analysis::DEFAULT_CYCLE_MIN_SIZE)
+            }
+            SectionKind::DuplicateStrings => {
+                analysis::strings::string_table_report(self, top).blocks()
+            }
+            SectionKind::DuplicateArrays => {
+                analysis::duplicate_arrays::duplicate_array_report(self, top).blocks()
+            }
+            SectionKind::HeaviestFields => {
+                analysis::heaviest_fields::heaviest_fields_report(self, top).blocks()
+            }
+            SectionKind::Locks => analysis::locks::lock_report(self).blocks(),
+            SectionKind::LeakSuspects => self.leak_suspects_blocks(),
+            SectionKind::Caches => self.caches_blocks(),
+            SectionKind::Netty => analysis::netty::netty_report(self).blocks(),
+            SectionKind::AllocationSites => {
+                analysis::allocation_sites::allocation_site_report(self, top).blocks()
+            }
+            SectionKind::RootRetention => self.root_retention_blocks(),
+            SectionKind::Metaspace => analysis::metaspace::metaspace_report(self).blocks(top),
+            SectionKind::ValueTypes => analysis::value_types::value_type_report(self).blocks(),
+            SectionKind::GeneratedClasses => {
+                analysis::generated_classes::generated_class_report(self, top).blocks()
+            }
+            SectionKind::Exceptions => self.exceptions_blocks(top),
+            SectionKind::StaticFields => self.static_fields_blocks(top),
+            SectionKind::SparseArrays => analysis::sparse_arrays::sparse_array_report(
+                self,
+                analysis::sparse_arrays::DEFAULT_MIN_NULL_RATIO,
+                top,
+            )
+            .blocks(),
+            SectionKind::MapCollisions => {
+                analysis::map_collisions::map_collision_report(self, top).blocks()
+            }
+            SectionKind::StructuralDuplicates => {
+                analysis::structural_duplicates::structural_duplicate_report(self, top).blocks()
+            }
+            SectionKind::AllocationAge => {
+                analysis::allocation_age::allocation_age_report(self, top).blocks()
+            }
+            SectionKind::ReferencingFields => {
+                analysis::referencing_fields::referencing_fields_report(
+                    self,
+                    analysis::referencing_fields::DEFAULT_TARGET_CLASS,
+                    top,
+                )
+                .blocks()
+            }
+            SectionKind::BaselineDiff => {
+                self.baseline_diff_blocks(&BaselineProfile::jdk(), top, histogram_options)
+            }
+            SectionKind::ToolMemory => memory::memory_report(self).blocks(),
+        }
+    }
+
+    /// The `top` classes growing the most beyond `baseline`, not part of the default report.
+    pub fn baseline_diff_blocks(
+        &self,
+        baseline: &BaselineProfile,
+        top: usize,
+        histogram_options: HistogramOptions,
+    ) -> Vec<Block> {
+        let mut histogram = class_histogram(self);
+        histogram.group_names(histogram_options.names);
+        let mut baseline = baseline.clone();
+        baseline.group_names(histogram_options.names);
+        baseline_diff_report(&histogram, &baseline, top).blocks()
+    }
+
+    /// The allocated classes as a baseline profile, their names grouped like the histogram.
+    pub fn baseline_profile(&self, names: NameGrouping) -> BaselineProfile {
+        let mut histogram = class_histogram(self);
+        histogram.group_names(names);
+        BaselineProfile::from_histogram(&histogram)
+    }
+
+    /// Hubs and strongly connected components of the reference graph, not part of the default report.
+    pub fn graph_metrics_blocks(&self, top: usize) -> Vec<Block> {
+        #[cfg(feature = "graph")]
+        {
+            analysis::graph_metrics::graph_metrics_report(self, &graph::HeapGraph::new(self), top)
+                .blocks()
+        }
+        #[cfg(not(feature = "graph"))]
+        {
+            let _ = top;
+            vec![Block::Warning(
+                "The graph metrics need the `graph` feature.".to_string(),
+            )]
+        }
+    }
+
+    /// The `top` largest reference cycles of at least `min_size` objects, not part of the default report.
+    pub fn reference_cycles_blocks(&self, top: usize, min_size: usize) -> Vec<Block> {
+        #[cfg(feature = "graph")]
+        {
+            analysis::cycles::cycle_report(self, &graph::HeapGraph::new(self), min_size, top)
+                .blocks()
+        }
+        #[cfg(not(feature = "graph"))]
+        {
+            let _ = (top, min_size);
+            vec![Block::Warning(
+                "The reference cycles need the `graph` feature.".to_string(),
+            )]
+        }
+    }
+
+    /// Connections and class loaders flagged as leak suspects, not part of the default report.
+    pub fn leak_suspects_blocks(&self) -> Vec<Block> {
+        #[cfg(feature = "graph")]
+        {
+            analysis::leak_suspects::leak_suspects_report(self, &graph::HeapGraph::new(self))
+                .blocks()
+        }
+        #[cfg(not(feature = "graph"))]
+        {
+            vec![Block::Warning(
+                "The leak suspects need the `graph` feature.".to_string(),
+            )]
+        }
+    }
+
+    /// Guava, Caffeine and Ehcache caches with their retained size, not part of the default report.
+    pub fn caches_blocks(&self) -> Vec<Block> {
+        #[cfg(feature = "graph")]
+        {
+            analysis::caches::cache_report(self, &graph::HeapGraph::new(self)).blocks()
+        }
+        #[cfg(not(feature = "graph"))]
+        {
+            vec![Block::Warning(
+                "The caches need the `graph` feature.".to_string(),
+            )]
+        }
+    }
+
+    /// Retained size per GC root kind, not part of the default report.
+    pub fn root_retention_blocks(&self) -> Vec<Block> {
+        #[cfg(feature = "graph")]
+        {
+            analysis::root_retention::root_retention_report(self, &graph::HeapGraph::new(self))
+                .blocks()
+        }
+        #[cfg(not(feature = "graph"))]
+        {
+            vec![Block::Warning(
+                "The retained sizes by root kind need the `graph` feature.".to_string(),
+            )]
+        }
+    }
+
+    /// The `top` exception groups by class and message with their retained size, not part of the default report.
+    pub fn exceptions_blocks(&self, top: usize) -> Vec<Block> {
+        #[cfg(feature = "graph")]
+        {
+            analysis::exceptions::exception_report(self, &graph::HeapGraph::new(self), top).blocks()
+        }
+        #[cfg(not(feature = "graph"))]
+        {
+            let _ = top;
+            vec![Block::Warning(
+                "The exceptions need the `graph` feature.".to_string(),
+            )]
+        }
+    }
+
+    /// The `top` static fields by retained size, not part of the default report.
+    pub fn static_fields_blocks(&self, top: usize) -> Vec<Block> {
+        #[cfg(feature = "graph")]
+        {
+            analysis::static_fields::static_field_report(self, &graph::HeapGraph::new(self), top)
+                .blocks()
+        }
+        #[cfg(not(feature = "graph"))]
+        {
+            let _ = top;
+            vec![Block::Warning(
+                "The static fields need the `graph` feature.".to_string(),
+            )]
+        }
+    }
+
+    pub fn memory_usage_blocks(
+        &self,
+        top: usize,
+        histogram_options: HistogramOptions,
+    ) -> Vec<Block> {
+        let owners = histogram_options
+            .split_arrays_by_owner
+            .then(|| array_owners(self));
+        let mut histogram = match &owners {
+            Some(owners) => array_owner_histogram(self, owners),
+            None => class_histogram(self),
+        };
+        histogram.group_names(histogram_options.names);
+        #[cfg(feature = "graph")]
+        if histogram_options.sort == HistogramSort::RetainedSize {
+            histogram.add_retained_sizes(
+                self,
+                &graph::HeapGraph::new(self),
+                histogram_options.names,
+                owners.as_ref(),
+            );
+        }
+        #[cfg(not(feature = "graph"))]
+        let histogram_options = match histogram_options.sort {
+            HistogramSort::RetainedSize => {
+                log::warn!("Sorting by total size, retained sizes need the `graph` feature.");
+                HistogramOptions {
+                    sort: HistogramSort::TotalSize,
+                    ..histogram_options
+                }
+            }
+            _ => histogram_options,
+        };
+        histogram.sort_by(histogram_options.sort);
+        histogram.retain_at_least(
+            histogram_options.min_total_size,
+            histogram_options.min_instances,
+        );
+        let mut blocks = histogram.blocks(top);
+        if histogram_options.effective_ownership {
+            let mut ownership = ownership_histogram(self);
+            ownership.histogram.group_names(histogram_options.names);
+            // retained sizes are only computed for the raw histogram
+            if histogram_options.sort != HistogramSort::RetainedSize {
+                ownership.histogram.sort_by(histogram_options.sort);
+            }
+            ownership.histogram.retain_at_least(
+                histogram_options.min_total_size,
+                histogram_options.min_instances,
+            );
+            blocks.extend(ownership.blocks(top));
+        }
+        blocks
+    }
+
+    pub fn render_summary(&self) -> String {
+        render_blocks(&self.summary_blocks())
+    }
+
+    pub fn summary_blocks(&self) -> Vec<Block> {
+        let counter = &self.counter;
+        let top_summary = formatdoc!(
+            "UTF-8 Strings: {}
+            Classes loaded: {}
+            Classes unloaded: {}
+            Stack traces: {}
+            Stack frames: {}
+            Start threads: {}
+            Allocation sites: {}
+            End threads: {}
+            Control settings: {}
+            CPU samples: {}",
+            pretty_count(self.utf8_strings.len() as u64),
+            pretty_count(self.class_registry.len() as u64),
+            pretty_count(counter.classes_unloaded as u64),
+            pretty_count(counter.stack_traces as u64),
+            pretty_count(counter.stack_frames as u64),
+            pretty_count(counter.start_threads as u64),
+            pretty_count(counter.allocation_sites as u64),
+            pretty_count(counter.end_threads as u64),
+            pretty_count(counter.control_settings as u64),
+            pretty_count(counter.cpu_samples as u64)
+        );
+
+        let heap_summary = formatdoc!(
+            "Heap summaries: {}
+            {} heap dumps containing in total {} segments:
+            ..GC root unknown: {}
+            ..GC root thread objects: {}
+            ..GC root JNI global: {}
+            ..GC root JNI local: {}
+            ..GC root Java frame: {}
+            ..GC root native stack: {}
+            ..GC root sticky class: {}
+            ..GC root thread block: {}
+            ..GC root monitor used: {}
+            ..GC primitive array dump: {}
+            ..GC object array dump: {}
+            ..GC class dump: {}
+            ..GC instance dump: {}
+            ..Primitive arrays deduplicated: {} ({} saved)",
+            pretty_count(counter.heap_summaries as u64),
+            pretty_count(counter.heap_dumps as u64),
+            pretty_count(counter.heap_dump_segments_all_sub_records),
+            pretty_count(counter.heap_dump_segments_gc_root_unknown),
+            pretty_count(counter.heap_dump_segments_gc_root_thread_object),
+            pretty_count(counter.heap_dump_segments_gc_root_jni_global),
+            pretty_count(counter.heap_dump_segments_gc_root_jni_local),
+            pretty_count(counter.heap_dump_segments_gc_root_java_frame),
+            pretty_count(counter.heap_dump_segments_gc_root_native_stack),
+            pretty_count(counter.heap_dump_segments_gc_root_sticky_class),
+            pretty_count(counter.heap_dump_segments_gc_root_thread_block),
+            pretty_count(counter.heap_dump_segments_gc_root_monitor_used),
+            pretty_count(counter.heap_dump_segments_gc_primitive_array_dump),
+            pretty_count(counter.heap_dump_segments_gc_object_array_dump),
+            pretty_count(counter.heap_dump_segments_gc_class_dump),
+            pretty_count(counter.heap_dump_segments_gc_instance_dump),
+            pretty_count(counter.deduplicated_primitive_arrays),
+            pretty_bytes_size(counter.deduplicated_primitive_array_bytes),
+        );
+
+        let sizing = &self.reference_sizing;
+        let references = if sizing.compressed_oops {
+            format!(
+                "References: {} bytes, compressed oops inferred from the {} heap below {}",
+                self.reference_size(),
+                pretty_bytes_size(sizing.estimated_heap_bytes),
+                pretty_bytes_size(COMPRESSED_OOPS_MAX_HEAP_BYTES)
+            )
+        } else {
+            format!(
+                "References: {} bytes, no compressed oops for the {} heap",
+                self.reference_size(),
+                pretty_bytes_size(sizing.estimated_heap_bytes)
+            )
+        };
+        let segments = &counter.segments;
+        let mut blocks = vec![
+            Block::heading("File content summary:"),
+            Block::Text(format!(
+                "{}\n{}\nHeap dump segments: {} in {} heap dumps ({} in total, largest {}), heap dump ends: {}\n{}",
+                top_summary,
+                heap_summary,
+                pretty_count(segments.segments),
+                pretty_count(segments.heap_dumps),
+                pretty_bytes_size(segments.segment_bytes),
+                pretty_bytes_size(segments.largest_segment_bytes),
+                pretty_count(segments.heap_dump_ends),
+                references
+            )),
+        ];
+        for anomaly in segments.anomalies() {
+            blocks.push(Block::Warning(format!("..{}", anomaly)));
+        }
+        let mismatches = self.instance_size_mismatches();
+        if !mismatches.is_empty() {
+            blocks.push(Block::Warning(format!(
+                "Instance sizes not matching the field layout of {} classes, their field values are unreliable:",
+                pretty_count(mismatches.len() as u64)
+            )));
+            let mut lines: Vec<String> = mismatches
+                .iter()
+                .take(MISMATCHES_LISTED)
+                .map(|mismatch| {
+                    let data_sizes: Vec<String> =
+                        mismatch.data_sizes.iter().map(u64::to_string).collect();
+                    format!(
+                        "..{}: {} instances of {} bytes instead of {}",
+                        mismatch.class_name,
+                        pretty_count(mismatch.mismatched_instances),
+                        data_sizes.join(", "),
+                        mismatch.expected_data_size
+                    )
+                })
+                .collect();
+            if mismatches.len() > MISMATCHES_LISTED {
+                lines.push(format!(
+                    "..and {} more classes",
+                    pretty_count((mismatches.len() - MISMATCHES_LISTED) as u64)
+                ));
+            }
+            blocks.push(Block::Lines(lines));
+        }
+        if let Some(stopped_at) = self.stopped_at {
+            blocks.push(Block::Warning(format!(
+                "Stopped early at {} of the input, the records after it are missing.",
+                pretty_bytes_size(stopped_at)
+            )));
+        }
+        if let Some(degradation) = &self.degradation {
+            blocks.push(Block::Warning(format!(
+                "Degraded: the estimated memory reached {}, later payloads were not retained:",
+                pretty_bytes_size(degradation.estimated_bytes)
+            )));
+            blocks.push(Block::Lines(vec![
+                format!(
+                    "..Instance fields dropped: {}",
+                    pretty_count(degradation.dropped_instance_fields)
+                ),
+                format!(
+                    "..Primitive arrays dropped: {}",
+                    pretty_count(degradation.dropped_primitive_arrays)
+                ),
+                format!(
+                    "..Object arrays dropped: {}",
+                    pretty_count(degradation.dropped_object_arrays)
+                ),
+                format!(
+                    "..Payloads dropped: {}",
+                    pretty_bytes_size(degradation.dropped_bytes)
+                ),
+            ]));
+        }
+        blocks
+    }
+
+    pub fn render_captured_strings(&self) -> String {
+        let mut rendered = Vec::new();
+        self.write_captured_strings(&mut rendered)
+            .expect("writing to memory cannot fail");
+        String::from_utf8(rendered).expect("the strings are valid UTF-8")
+    }
+
+    /// Writes the section of `render_captured_strings` string after string, only references to the strings
+    /// are held to sort them.
+    pub fn write_captured_strings<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        let mut strings: Vec<&str> = self.utf8_strings.values().map(|s| s.as_ref()).collect();
+        strings.par_sort_unstable();
+        write_blocks(
+            &[Block::heading("List of Strings")],
+            &RenderOptions::default(),
+            writer,
+        )?;
+        for string in strings {
+            writeln!(writer, "{}", string)?;
+        }
+        Ok(())
+    }
+
+    pub fn captured_strings_blocks(&self) -> Vec<Block> {
+        let mut strings: Vec<String> = self
+            .utf8_strings
+            .par_iter()
+            .map(|(_, s)| s.to_string())
+            .collect();
+        strings.par_sort_unstable();
+        vec![Block::heading("List of Strings"), Block::Lines(strings)]
+    }
+
+    pub fn render_thread_info(&self) -> String {
+        render_blocks(&self.thread_info_blocks())
+    }
+
+    pub fn thread_info_blocks(&self) -> Vec<Block> {
+        // for each stacktrace
+        let mut stack_traces: Vec<_> = self
+            .stack_trace_by_serial_number
+            .iter()
+            .filter(|(_, stack)| !stack.stack_frame_ids.is_empty()) // omit empty stacktraces
+            .collect();
+
+        stack_traces.sort_by_key(|(serial_number, _)| **serial_number);
+
+        let mut blocks = vec![Block::Heading(format!(
+            "Found {} threads with stacktraces:",
+            stack_traces.len()
+        ))];
+        for (index, (serial_number, _)) in stack_traces.iter().enumerate() {
+            blocks.push(Block::Heading(format!("Thread {}", index + 1)));
+            if let Some(frames) = self.stack_frames(**serial_number) {
+                blocks.push(Block::Lines(
+                    frames.iter().map(|frame| format!("  {}", frame)).collect(),
+                ));
+            }
+        }
+        blocks
+    }
+
+    /// Iterates over all GC roots with their kind, resolving the owning thread and frame where possible.
+    ///
+    /// The roots are grouped by kind and sorted by object id within a kind.
+    pub fn gc_roots(&self) -> impl Iterator<Item = GcRootView> + '_ {
+        // thread serial number -> thread root
+        let threads: HashMap<u32, &RootThreadObject> = self
+            .root_thread_object
+            .values()
+            .map(|thread| (thread.thread_sequence_number, thread))
+            .collect();
+        let thread_root =
+            |kind, object_id, thread_serial_number: u32, frame_number: Option<u32>| {
+                let thread = threads.get(&thread_serial_number);
+                GcRootView {
+                    kind,
+                    object_id,
+                    thread_serial_number: Some(thread_serial_number),
+                    thread_object_id: thread.map(|t| t.thread_object_id),
+                    thread_name: thread.and_then(|t| self.thread_name(t.thread_object_id)),
+                    // the frame number is the index in the thread's stack trace, -1 if unknown
+                    frame: thread.zip(frame_number).and_then(|(t, frame_number)| {
+                        self.stack_frames(t.stack_sequence_number)?
+                            .into_iter()
+                            .nth(frame_number as usize)
+                    }),
+                }
+            };
+        let global_root = |kind, object_id| GcRootView {
+            kind,
+            object_id,
+            thread_serial_number: None,
+            thread_object_id: None,
+            thread_name: None,
+            frame: None,
+        };
+
+        let mut roots = Vec::new();
+        roots.extend(values_by_key(&self.root_thread_object).map(|t| {
+            thread_root(
+                GcRootKind::ThreadObject,
+                t.thread_object_id,
+                t.thread_sequence_number,
+                None,
+            )
+        }));
+        roots.extend(self.root_jni_local.iter().map(|r| {
+            thread_root(
+                GcRootKind::JniLocal,
+                r.object_id,
+                r.thread_serial_number,
+                Some(r.frame_number_in_stack_trace),
+            )
+        }));
+        roots.extend(self.root_java_frame.iter().map(|r| {
+            thread_root(
+                GcRootKind::JavaFrame,
+                r.object_id,
+                r.thread_serial_number,
+                Some(r.frame_number_in_stack_trace),
+            )
+        }));
+        roots.extend(self.root_native_stack.iter().map(|r| {
+            thread_root(
+                GcRootKind::NativeStack,
+                r.object_id,
+                r.thread_serial_number,
+                None,
+            )
+        }));
+        roots.extend(self.root_thread_block.iter().map(|r| {
+            thread_root(
+                GcRootKind::ThreadBlock,
+                r.object_id,
+                r.thread_serial_number,
+                None,
+            )
+        }));
+        roots.extend(
+            values_by_key(&self.root_jni_global)
+                .map(|r| global_root(GcRootKind::JniGlobal, r.object_id)),
+        );
+        roots.extend(
+            self.root_sticky_class
+                .iter()
+                .map(|id| global_root(GcRootKind::StickyClass, *id)),
+        );
+        roots.extend(
+            self.root_monitor_used
+                .iter()
+                .map(|id| global_root(GcRootKind::MonitorUsed, *id)),
+        );
+        roots.extend(
+            self.root_unknown
+                .iter()
+                .map(|id| global_root(GcRootKind::Unknown, *id)),
+        );
+        roots.into_iter()
+    }
+
+    /// Returns the `name` of a `java.lang.Thread` instance.
+    pub fn thread_name(&self, thread_object_id: u64) -> Option<String> {
+        let thread = self.instances_pool.get(&thread_object_id)?;
+        match self.field_value(thread, "name") {
+            Some(FieldValue::Object(name_id)) => self.string_value(*name_id),
+            _ => None,
+        }
+    }
+
+    /// Counts the objects, classes and roots without rendering them.
+    pub fn stats(&self) -> HeapStats {
+        let mut stats = HeapStats {
+            classes: self.classes_dump.len() as u64,
+            ..Default::default()
+        };
+        for instance in self.instances_pool.values() {
+            stats.total_shallow_bytes += self.shallow_size(instance);
+            match instance.array_element_type() {
+                Some(FieldType::Object) => {
+                    stats.object_arrays += 1;
+                    *stats
+                        .arrays_by_element_type
+                        .entry("object".to_string())
+                        .or_default() += 1;
+                }
+                Some(element_type) => {
+                    stats.primitive_arrays += 1;
+                    *stats
+                        .arrays_by_element_type
+                        .entry(primitive_type_name(element_type).to_string())
+                        .or_default() += 1;
+                }
+                None => stats.instances += 1,
+            }
+        }
+        let root_counts = [
+            (GcRootKind::ThreadObject, self.root_thread_object.len()),
+            (GcRootKind::JniGlobal, self.root_jni_global.len()),
+            (GcRootKind::JniLocal, self.root_jni_local.len()),
+            (GcRootKind::JavaFrame, self.root_java_frame.len()),
+            (GcRootKind::NativeStack, self.root_native_stack.len()),
+            (GcRootKind::ThreadBlock, self.root_thread_block.len()),
+            (GcRootKind::StickyClass, self.root_sticky_class.len()),
+            (GcRootKind::MonitorUsed, self.root_monitor_used.len()),
+            (GcRootKind::Unknown, self.root_unknown.len()),
+        ];
+        stats.roots_by_kind = root_counts
+            .into_iter()
+            .filter(|(_, count)| *count > 0)
+            .map(|(kind, count)| (kind, count as u64))
+            .collect();
+        stats
+    }
+
+    /// Size of the instance fields declared by the class and its super classes, `None` if a class of the hierarchy is
+    /// missing or the hierarchy is cyclic.
+    pub fn field_layout_size(&self, class_object_id: u64) -> Option<u64> {
+        let mut size = 0;
+        let mut current = class_object_id;
+        for _ in 0..MAX_CLASS_HIERARCHY_DEPTH {
+            if current == 0 {
+                return Some(size);
+            }
+            let class = self.classes_dump.get(&current)?;
+            size += class
+                .instance_fields
+                .iter()
+                .map(|field| match field.field_type {
+                    FieldType::Object => self.counter.id_size as u64,
+                    field_type => primitive_byte_size(&field_type),
+                })
+                .sum::<u64>();
+            current = class.super_class_object_id;
+        }
+        // cyclic super classes
+        None
+    }
+
+    /// Classes with instances not matching their field layout, most affected classes first.
+    pub fn instance_size_mismatches(&self) -> Vec<InstanceSizeMismatch> {
+        let mut layout_sizes: HashMap<u64, Option<u64>> = HashMap::new();
+        let mut mismatches: HashMap<u64, (u64, u64, BTreeSet<u64>)> = HashMap::new();
+        for instance in self.instances_pool.values() {
+            // arrays have no field layout
+            if instance.primitive_array().is_some() || instance.object_array().is_some() {
+                continue;
+            }
+            let class_object_id = instance.class_object_id;
+            let expected = *layout_sizes
+                .entry(class_object_id)
+                .or_insert_with(|| self.field_layout_size(class_object_id));
+            match expected {
+                Some(expected) if expected != instance.data_size => {
+                    let (_, count, data_sizes) = mismatches
+                        .entry(class_object_id)
+                        .or_insert_with(|| (expected, 0, BTreeSet::new()));
+                    *count += 1;
+                    data_sizes.insert(instance.data_size);
+                }
+                _ => (),
+            }
+        }
+        let mut result: Vec<InstanceSizeMismatch> = mismatches
+            .into_iter()
+            .map(
+                |(class_object_id, (expected_data_size, mismatched_instances, data_sizes))| {
+                    InstanceSizeMismatch {
+                        class_object_id,
+                        class_name: self
+                            .class_name(class_object_id)
+                            .unwrap_or_else(|| "unknown class".to_string()),
+                        expected_data_size,
+                        mismatched_instances,
+                        data_sizes: data_sizes.into_iter().collect(),
+                    }
+                },
+            )
+            .collect();
+        result.sort_by(|a, b| {
+            b.mismatched_instances
+                .cmp(&a.mismatched_instances)
+                .then_with(|| a.class_name.cmp(&b.class_name))
+                .then_with(|| a.class_object_id.cmp(&b.class_object_id))
+        });
+        result
+    }
+
+    /// Iterates over all GC root object ids, regardless of their kind.
+    pub fn gc_root_ids(&self) -> impl Iterator<Item = u64> + '_ {
+        self.root_thread_object
+            .keys()
+            .chain(self.root_jni_global.keys())
+            .chain(self.root_jni_local.iter().map(|r| &r.object_id))
+            .chain(self.root_java_frame.iter().map(|r| &r.object_id))
+            .chain(self.root_native_stack.iter().map(|r| &r.object_id))
+            .chain(self.root_thread_block.iter().map(|r| &r.object_id))
+            .chain(self.root_sticky_class.iter())
+            .chain(self.root_monitor_used.iter())
+            .chain(self.root_unknown.iter())
+            .copied()
+    }
+}
+
+// values in the order of their keys, so that the output does not depend on the hashing
+fn values_by_key<T>(map: &HashMap<u64, T>) -> impl Iterator<Item = &T> {
+    let mut entries: Vec<(&u64, &T)> = map.iter().collect();
+    entries.sort_unstable_by_key(|(key, _)| **key);
+    entries.into_iter().map(|(_, value)| value)
+}
+
+// small seeded generator, good enough to sample instances, its output spreads consecutive states uniformly
+pub(crate) fn splitmix64(state: &mut u64) -> u64 {
+    *state = state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    let mut z = *state;
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
+pub(crate) fn align_to_8(size: u64) -> u64 {
+    size.div_ceil(8) * 8
+}
+
+pub(crate) fn primitive_type_name(element_type: FieldType) -> &'static str {
+    match element_type {
+        FieldType::Bool => "bool",
+        FieldType::Byte => "byte",
+        FieldType::Char => "char",
+        FieldType::Short => "short",
+        FieldType::Int => "int",
+        FieldType::Long => "long",
+        FieldType::Float => "float",
+        FieldType::Double => "double",
+        FieldType::Object => "object",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::gc_record::{ArrayValue, PrimitiveArray};
+
+    // stack trace 1 with frames 10 (class serial 1, known), 11 (class serial 2, not loaded) and 12 (missing)
+    fn heap_with_partial_class_info() -> Heap {
+        let mut heap = Heap::default();
+        heap.utf8_strings
+            .make_mut()
+            .insert(100, "com/example/Main".into());
+        heap.utf8_strings.make_mut().insert(101, "main".into());
+        heap.utf8_strings.make_mut().insert(102, "Main.java".into());
+        heap.class_registry.make_mut().load(LoadClassData {
+            serial_number: 1,
+            class_object_id: 1000,
+            stack_trace_serial_number: 0,
+            class_name_id: 100,
+        });
+        for (stack_frame_id, class_serial_number, line_number) in [(10, 1, 42), (11, 2, -3)] {
+            heap.stack_frame_by_id.make_mut().insert(
+                stack_frame_id,
+                StackFrameData {
+                    stack_frame_id,
+                    method_name_id: 101,
+                    method_signature_id: 0,
+                    source_file_name_id: 102,
+                    class_serial_number,
+                    line_number,
+                },
+            );
+        }
+        heap.stack_trace_by_serial_number.make_mut().insert(
+            1,
+            StackTraceData {
+                serial_number: 1,
+                thread_serial_number: 1,
+                number_of_frames: 3,
+                stack_frame_ids: vec![10, 11, 12],
+            },
+        );
+        heap
+    }
+
+    // class fixture shared by the analyses tests, the name is stored under the `class_object_id` string id
+    pub(crate) fn add_class<'a>(
+        heap: &'a mut Heap,
+        class_object_id: u64,
+        super_class_object_id: u64,
+        class_name: &str,
+    ) -> &'a mut ClassDumpFields {
+        heap.utf8_strings
+            .make_mut()
+            .insert(class_object_id, class_name.into());
+        heap.class_registry.make_mut().load(LoadClassData {
+            serial_number: class_object_id as u32,
+            class_object_id,
+            stack_trace_serial_number: 0,
+            class_name_id: class_object_id,
+        });
+        heap.classes_dump.make_mut().insert(
+            class_object_id,
+            ClassDumpFields::new(
+                class_object_id,
+                0,
+                super_class_object_id,
+                0,
+                0,
+                Vec::new(),
+                Vec::new(),
+                Vec::new(),
+            ),
+        );
+        heap.classes_dump
+            .make_mut()
+            .get_mut(&class_object_id)
+            .expect("class just inserted")
+    }
+
+    // instance fixture shared by the analyses tests, each field counts as 8 bytes of instance data
+    pub(crate) fn add_instance(
+        heap: &mut Heap,
+        object_id: u64,
+        class_object_id: u64,
+        fields: Vec<(u64, Values)>,
+    ) {
+        heap.instances_pool.make_mut().insert(
+            object_id,
+            Arc::new(Instance {
+                object_id,
+                stack_trace_serial_number: 0,
+                class_object_id,
+                data_size: 8 * fields.len() as u64,
+                fields,
+                super_fields: Vec::new(),
+            }),
+        );
+    }
+
+    pub(crate) fn single(name_id: u64, value: FieldValue) -> (u64, Values) {
+        (name_id, Values::Single(value))
+    }
+
+    pub(crate) fn reference(name_id: u64, object_id: u64) -> (u64, Values) {
+        single(name_id, FieldValue::Object(object_id))
+    }
+
+    #[test]
+    fn stack_frames_resolve_classes_by_serial_number() {
+        let heap = heap_with_partial_class_info();
+        assert_eq!(
+            heap.class_by_serial_number(1).unwrap().class_object_id,
+            1000
+        );
+        assert!(heap.class_by_serial_number(2).is_none());
+        let frames = heap.stack_frames(1).unwrap();
+        assert_eq!(frames.len(), 3);
+        assert_eq!(frames[0].class_name, "com.example.Main");
+        assert_eq!(frames[1].class_name, "unknown class");
+        assert_eq!(frames[2].method_name, "unknown method name");
+        assert!(heap.stack_frames(2).is_none());
+    }
+
+    #[test]
+    fn write_captured_strings_as_rendered() {
+        let heap = heap_with_partial_class_info();
+        let mut written = Vec::new();
+        heap.write_captured_strings(&mut written).unwrap();
+        let written = String::from_utf8(written).unwrap();
+        assert_eq!(
+            written,
+            "\nList of Strings\nMain.java\ncom/example/Main\nmain\n"
+        );
+        assert_eq!(written, render_blocks(&heap.captured_strings_blocks()));
+        assert_eq!(written, heap.render_captured_strings());
+    }
+
+    #[test]
+    fn format_stack_trace_with_partial_class_info() {
+        let heap = heap_with_partial_class_info();
+        assert_eq!(
+            heap.format_stack_trace(1).unwrap(),
+            "  at com.example.Main.main (Main.java:42)\n\
+             \x20 at unknown class.main (Main.java:native method)\n\
+             \x20 at unknown class.unknown method name (unknown source file:unknown line number)\n"
+        );
+        assert!(heap
+            .render_thread_info()
+            .contains("Thread 1\n  at com.example.Main.main"));
+    }
+
+    #[test]
+    fn gc_roots_resolve_thread_and_frame() {
+        let mut heap = heap_with_partial_class_info();
+        heap.root_thread_object.make_mut().insert(
+            500,
+            RootThreadObject {
+                thread_object_id: 500,
+                thread_sequence_number: 7,
+                stack_sequence_number: 1,
+            },
+        );
+        heap.root_java_frame.make_mut().push(RootJavaFrame {
+            object_id: 600,
+            thread_serial_number: 7,
+            frame_number_in_stack_trace: 0,
+        });
+        heap.root_sticky_class.make_mut().push(700);
+        let mut roots: Vec<GcRootView> = heap.gc_roots().collect();
+        roots.sort_by_key(|root| root.object_id);
+        assert_eq!(roots.len(), 3);
+        assert_eq!(roots[0].kind, GcRootKind::ThreadObject);
+        assert_eq!(roots[1].kind, GcRootKind::JavaFrame);
+        assert_eq!(roots[1].thread_object_id, Some(500));
+        assert_eq!(
+            roots[1].frame.as_ref().map(|f| f.class_name.as_str()),
+            Some("com.example.Main")
+        );
+        assert_eq!(roots[2].kind, GcRootKind::StickyClass);
+        assert_eq!(roots[2].thread_serial_number, None);
+    }
+
+    #[test]
+    fn same_object_rooted_by_two_threads() {
+        let mut heap = heap_with_partial_class_info();
+        for (thread_object_id, thread_serial_number) in [(500, 7), (501, 8)] {
+            heap.root_thread_object.make_mut().insert(
+                thread_object_id,
+                RootThreadObject {
+                    thread_object_id,
+                    thread_sequence_number: thread_serial_number,
+                    stack_sequence_number: 1,
+                },
+            );
+            heap.root_java_frame.make_mut().push(RootJavaFrame {
+                object_id: 600,
+                thread_serial_number,
+                frame_number_in_stack_trace: 0,
+            });
+        }
+        let frame_threads: Vec<Option<u64>> = heap
+            .gc_roots()
+            .filter(|root| root.kind == GcRootKind::JavaFrame)
+            .map(|root| root.thread_object_id)
+            .collect();
+        assert_eq!(frame_threads, vec![Some(500), Some(501)]);
+        assert_eq!(heap.stats().roots_by_kind[&GcRootKind::JavaFrame], 2);
+        assert_eq!(heap.gc_root_ids().filter(|id| *id == 600).count(), 2);
+    }
+
+    #[test]
+    fn resolve_instance_fields_up_the_hierarchy() {
+        let mut heap = Heap::default();
+        for (name_id, name) in [
+            (100, "com/example/Base"),
+            (101, "com/example/Child"),
+            (200, "id"),
+            (201, "name"),
+        ] {
+            heap.utf8_strings.make_mut().insert(name_id, name.into());
+        }
+        for (serial_number, class_object_id, class_name_id, super_class_object_id, field) in [
+            (1, 1000, 100, 0, (200, FieldType::Int)),
+            (2, 2000, 101, 1000, (201, FieldType::Object)),
+        ] {
+            heap.class_registry.make_mut().load(LoadClassData {
+                serial_number,
+                class_object_id,
+                stack_trace_serial_number: 0,
+                class_name_id,
+            });
+            let (name_id, field_type) = field;
+            heap.classes_dump.make_mut().insert(
+                class_object_id,
+                ClassDumpFields::new(
+                    class_object_id,
+                    0,
+                    super_class_object_id,
+                    0,
+                    12,
+                    Vec::new(),
+                    Vec::new(),
+                    vec![parser::gc_record::FieldInfo {
+                        name_id,
+                        field_type,
+                    }],
+                ),
+            );
+        }
+        let instance = Instance {
+            object_id: 0x10,
+            stack_trace_serial_number: 0,
+            class_object_id: 2000,
+            data_size: 12,
+            fields: vec![(201, Values::Single(FieldValue::Object(0)))],
+            super_fields: vec![(200, Values::Single(FieldValue::Int(7)))],
+        };
+
+        let resolved = heap.resolve_instance(&instance);
+        assert_eq!(resolved.class_name, "com.example.Child");
+        assert_eq!(resolved.fields[1].declaring_class, "com.example.Base");
+        assert_eq!(resolved.field("id").and_then(FieldValue::as_i64), Some(7));
+        assert!(resolved.fields_retained);
+        assert_eq!(
+            resolved.to_string(),
+            "com.example.Child@0x10 { name = null, id = 7 }"
+        );
+    }
+
+    #[test]
+    fn array_elements_skip_nulls() {
+        let mut heap = Heap::default();
+        for object_id in [0x20, 0x30] {
+            heap.instances_pool.make_mut().insert(
+                object_id,
+                Arc::new(Instance {
+                    object_id,
+                    stack_trace_serial_number: 0,
+                    class_object_id: 1000,
+                    data_size: 0,
+                    fields: Vec::new(),
+                    super_fields: Vec::new(),
+                }),
+            );
+        }
+        let array = Instance {
+            object_id: 0x10,
+            stack_trace_serial_number: 0,
+            class_object_id: 1001,
+            data_size: 40,
+            // null, missing from the dump, then two instances
+            fields: vec![(
+                0,
+                Values::Array(parser::gc_record::ArrayValue::Object(vec![
+                    0, 0x99, 0x30, 0x20,
+                ])),
+            )],
+            super_fields: Vec::new(),
+        };
+        let ids: Vec<u64> = heap
+            .array_elements(&array)
+            .map(|object| object.object_id())
+            .collect();
+        assert_eq!(ids, vec![0x30, 0x20]);
+        assert_eq!(heap.array_elements(&heap.instances_pool[&0x20]).count(), 0);
+    }
+
+    #[test]
+    fn gc_roots_in_stable_order() {
+        let mut heap = Heap::default();
+        for object_id in [0x30, 0x10, 0x20, 0x50, 0x40] {
+            heap.root_jni_global.make_mut().insert(
+                object_id,
+                RootJniGlobal {
+                    object_id,
+                    jni_global_ref_id: 0,
+                },
+            );
+        }
+        heap.root_sticky_class.make_mut().push(0x5);
+        let roots: Vec<(GcRootKind, u64)> = heap
+            .gc_roots()
+            .map(|root| (root.kind, root.object_id))
+            .collect();
+        let mut expected: Vec<(GcRootKind, u64)> = [0x10, 0x20, 0x30, 0x40, 0x50]
+            .into_iter()
+            .map(|object_id| (GcRootKind::JniGlobal, object_id))
+            .collect();
+        expected.push((GcRootKind::StickyClass, 0x5));
+        assert_eq!(roots, expected);
+    }
+
+    // instances 0x100 + x of `com.example.Point` with an int field `x`
+    fn point_heap(xs: impl IntoIterator<Item = i32>) -> Heap {
+        let mut heap = Heap::default();
+        heap.counter.id_size = 8;
+        heap.utf8_strings
+            .make_mut()
+            .insert(100, "com/example/Point".into());
+        heap.utf8_strings.make_mut().insert(200, "x".into());
+        heap.class_registry.make_mut().load(LoadClassData {
+            serial_number: 1,
+            class_object_id: 1000,
+            stack_trace_serial_number: 0,
+            class_name_id: 100,
+        });
+        let fields = vec![parser::gc_record::FieldInfo {
+            name_id: 200,
+            field_type: FieldType::Int,
+        }];
+        heap.classes_dump.make_mut().insert(
+            1000,
+            ClassDumpFields::new(1000, 0, 0, 0, 20, Vec::new(), Vec::new(), fields),
+        );
+        for x in xs {
+            let object_id = 0x100 + x as u64;
+            heap.instances_pool.make_mut().insert(
+                object_id,
+                Arc::new(Instance {
+                    object_id,
+                    stack_trace_serial_number: 0,
+                    class_object_id: 1000,
+                    data_size: 4,
+                    fields: vec![(200, Values::Single(FieldValue::Int(x)))],
+                    super_fields: Vec::new(),
+                }),
+            );
+        }
+        heap
+    }
+
+    #[test]
+    fn sample_instances_modes() {
+        let heap = point_heap(1..=20);
+        let ids = |sample: Vec<ResolvedInstance>| -> Vec<u64> {
+            sample.iter().map(|instance| instance.object_id).collect()
+        };
+
+        let first = heap.sample_instances_with("com.example.Point", 3, SampleMode::First);
+        assert_eq!(first[0].field("x").and_then(FieldValue::as_i64), Some(1));
+        assert_eq!(ids(first), vec![0x101, 0x102, 0x103]);
+
+        let random = ids(heap.sample_instances("com.example.Point", 5));
+        assert_eq!(random.len(), 5);
+        assert!(random.windows(2).all(|pair| pair[0] < pair[1]));
+        assert_eq!(random, ids(heap.sample_instances("com.example.Point", 5)));
+        assert_ne!(
+            random,
+            ids(heap.sample_instances_with("com.example.Point", 5, SampleMode::Random { seed: 7 }))
+        );
+        assert_eq!(heap.sample_instances("com.example.Point", 50).len(), 20);
+        assert!(heap.sample_instances("com.example.Line", 5).is_empty());
+    }
+
+    #[test]
+    fn numeric_field_stats() {
+        let mut heap = point_heap(1..=100);
+        heap.instances_pool.make_mut().insert(
+            0x1000,
+            Arc::new(Instance {
+                object_id: 0x1000,
+                stack_trace_serial_number: 0,
+                class_object_id: 1000,
+                data_size: 4,
+                fields: Vec::new(),
+                super_fields: Vec::new(),
+            }),
+        );
+        assert_eq!(
+            heap.field_stats("com.example.Point", "x"),
+            Some(FieldStats {
+                count: 100,
+                missing: 1,
+                min: 1.0,
+                max: 100.0,
+                mean: 50.5,
+                p50: 50.0,
+                p90: 90.0,
+                p99: 99.0,
+            })
+        );
+        assert_eq!(heap.field_stats("com.example.Point", "y"), None);
+    }
+
+    #[test]
+    fn group_by_string_field() {
+        let mut heap = Heap::default();
+        heap.counter.id_size = 8;
+        for (name_id, name) in [
+            (100, "com/example/Session"),
+            (101, "java/lang/String"),
+            (200, "tenantId"),
+            (201, "value"),
+            (202, "coder"),
+        ] {
+            heap.utf8_strings.make_mut().insert(name_id, name.into());
+        }
+        for (serial_number, class_object_id, class_name_id, fields) in [
+            (1, 1000, 100, vec![(200, FieldType::Object)]),
+            (
+                2,
+                2000,
+                101,
+                vec![(201, FieldType::Object), (202, FieldType::Byte)],
+            ),
+        ] {
+            heap.class_registry.make_mut().load(LoadClassData {
+                serial_number,
+                class_object_id,
+                stack_trace_serial_number: 0,
+                class_name_id,
+            });
+            let fields = fields
+                .into_iter()
+                .map(|(name_id, field_type)| parser::gc_record::FieldInfo {
+                    name_id,
+                    field_type,
+                })
+                .collect();
+            heap.classes_dump.make_mut().insert(
+                class_object_id,
+                ClassDumpFields::new(class_object_id, 0, 0, 0, 0, Vec::new(), Vec::new(), fields),
+            );
+        }
+        let mut insert = |object_id: u64, class_object_id: u64, fields: Vec<(u64, Values)>| {
+            heap.instances_pool.make_mut().insert(
+                object_id,
+                Arc::new(Instance {
+                    object_id,
+                    stack_trace_serial_number: 0,
+                    class_object_id,
+                    data_size: 8,
+                    fields,
+                    super_fields: Vec::new(),
+                }),
+            );
+        };
+        // two distinct strings with the same content
+        for (string_id, bytes_id, content) in [
+            (0x20, 0x30, "acme"),
+            (0x21, 0x31, "acme"),
+            (0x22, 0x32, "zeta"),
+        ] {
+            let bytes: Arc<[u8]> = Arc::from(content.as_bytes());
+            let array = PrimitiveArray::new(FieldType::Byte, bytes.len() as u32, bytes);
+            insert(bytes_id, 0, vec![(0, Values::PrimitiveArray(array))]);
+            insert(
+                string_id,
+                2000,
+                vec![
+                    (201, Values::Single(FieldValue::Object(bytes_id))),
+                    (202, Values::Single(FieldValue::Byte(0))),
+                ],
+            );
+        }
+        for (session_id, tenant_id) in [
+            (0x10, 0x20),
+            (0x11, 0x21),
+            (0x12, 0x22),
+            (0x13, 0),
+            (0x14, 0x20),
+        ] {
+            insert(
+                session_id,
+                1000,
+                vec![(200, Values::Single(FieldValue::Object(tenant_id)))],
+            );
+        }
+
+        let group = |value: &str, count: u64| FieldGroup {
+            value: value.to_string(),
+            count,
+        };
+        assert_eq!(
+            heap.group_by("com.example.Session", "tenantId"),
+            vec![group("\"acme\"", 3), group("\"zeta\"", 1), group("null", 1)]
+        );
+        assert_eq!(
+            heap.group_by("java.lang.String", "coder"),
+            vec![group("0", 3)]
+        );
+        assert!(heap.group_by("com.example.Session", "userId").is_empty());
+    }
+
+    #[test]
+    fn unloaded_class_names() {
+        let mut heap = point_heap(1..=2);
+        assert_eq!(heap.class_name(1000).as_deref(), Some("com.example.Point"));
+        heap.class_registry.make_mut().unload(1);
+        assert_eq!(
+            heap.class_name(1000).as_deref(),
+            Some("com.example.Point (unloaded)")
+        );
+        assert_eq!(heap.instances_of("com.example.Point").count(), 0);
+        // stack frames keep resolving the serial number
+        assert!(heap.class_by_serial_number(1).is_some());
+    }
+
+    #[test]
+    fn anonymize_class_names() {
+        let mut heap = point_heap(1..=2);
+        heap.utf8_strings
+            .make_mut()
+            .insert(101, "java/lang/String".into());
+        heap.utf8_strings
+            .make_mut()
+            .insert(102, "Point.java".into());
+        heap.utf8_strings
+            .make_mut()
+            .insert(103, "(Lcom/example/Point;Ljava/lang/String;)V".into());
+        heap.class_registry.make_mut().load(LoadClassData {
+            serial_number: 2,
+            class_object_id: 2000,
+            stack_trace_serial_number: 0,
+            class_name_id: 101,
+        });
+        heap.stack_frame_by_id.make_mut().insert(
+            1,
+            StackFrameData {
+                stack_frame_id: 1,
+                method_name_id: 200,
+                method_signature_id: 103,
+                source_file_name_id: 102,
+                class_serial_number: 1,
+                line_number: 12,
+            },
+        );
+        heap.anonymize_class_names("salt");
+        let point = heap.class_name(1000).unwrap();
+        assert_eq!(
+            point,
+            pretty_class_name(&anonymized_class_name("com/example/Point", "salt"))
+        );
+        assert_eq!(heap.class_name(2000).as_deref(), Some("java.lang.String"));
+        assert_eq!(heap.instances_of(&point).count(), 2);
+        assert_eq!(
+            heap.utf8_strings[&102].to_string(),
+            format!("{}.java", anonymized_class_name("Point", "salt"))
+        );
+        assert_eq!(
+            heap.utf8_strings[&103].to_string(),
+            format!(
+                "(L{};Ljava/lang/String;)V",
+                anonymized_class_name("com/example/Point", "salt")
+            )
+        );
+        // field names are kept
+        assert_eq!(&*heap.utf8_strings[&200], "x");
+    }
+
+    #[test]
+    fn instance_size_mismatches() {
+        let mut heap = Heap::default();
+        heap.counter.id_size = 8;
+        heap.utf8_strings
+            .make_mut()
+            .insert(100, "com/example/Point".into());
+        heap.class_registry.make_mut().load(LoadClassData {
+            serial_number: 1,
+            class_object_id: 1000,
+            stack_trace_serial_number: 0,
+            class_name_id: 100,
+        });
+        let fields = [FieldType::Int, FieldType::Object]
+            .into_iter()
+            .map(|field_type| parser::gc_record::FieldInfo {
+                name_id: 0,
+                field_type,
+            })
+            .collect();
+        heap.classes_dump.make_mut().insert(
+            1000,
+            ClassDumpFields::new(1000, 0, 0, 0, 28, Vec::new(), Vec::new(), fields),
+        );
+        for (object_id, data_size) in [(0x10, 12), (0x20, 16), (0x30, 16), (0x40, 8)] {
+            heap.instances_pool.make_mut().insert(
+                object_id,
+                Arc::new(Instance {
+                    object_id,
+                    stack_trace_serial_number: 0,
+                    class_object_id: 1000,
+                    data_size,
+                    fields: Vec::new(),
+                    super_fields: Vec::new(),
+                }),
+            );
+        }
+        assert_eq!(heap.field_layout_size(1000), Some(12));
+        assert_eq!(heap.field_layout_size(2000), None);
+        // cyclic super classes
+        heap.classes_dump.make_mut().insert(
+            3000,
+            ClassDumpFields::new(3000, 0, 3000, 0, 0, Vec::new(), Vec::new(), Vec::new()),
+        );
+        assert_eq!(heap.field_layout_size(3000), None);
+        assert_eq!(
+            heap.instance_size_mismatches(),
+            vec![InstanceSizeMismatch {
+                class_object_id: 1000,
+                class_name: "com.example.Point".to_string(),
+                expected_data_size: 12,
+                mismatched_instances: 3,
+                data_sizes: vec![8, 16],
+            }]
+        );
+        assert!(heap
+            .render_summary()
+            .contains("..com.example.Point: 3 instances of 8, 16 bytes instead of 12"));
+    }
+
+    #[test]
+    fn compressed_oops_narrow_references() {
+        let mut heap = Heap::default();
+        heap.counter.id_size = 8;
+        let objects = [
+            (
+                0x10,
+                12,
+                vec![
+                    (0, Values::Single(FieldValue::Object(0x20))),
+                    (0, Values::Single(FieldValue::Int(1))),
+                ],
+            ),
+            (
+                0x20,
+                24,
+                vec![(0, Values::Array(ArrayValue::Object(vec![0x10, 0, 0])))],
+            ),
+        ];
+        for (object_id, data_size, fields) in objects {
+            heap.instances_pool.make_mut().insert(
+                object_id,
+                Arc::new(Instance {
+                    object_id,
+                    stack_trace_serial_number: 0,
+                    class_object_id: 0,
+                    data_size,
+                    fields,
+                    super_fields: Vec::new(),
+                }),
+            );
+        }
+        let shallow_sizes = |heap: &Heap| {
+            [0x10, 0x20].map(|object_id| heap.shallow_size(&heap.instances_pool[&object_id]))
+        };
+        assert_eq!(shallow_sizes(&heap), [32, 40]);
+
+        heap.reference_sizing = heap.infer_reference_sizing();
+        assert_eq!(
+            heap.reference_sizing,
+            ReferenceSizing {
+                compressed_oops: true,
+                estimated_heap_bytes: 72,
+            }
+        );
+        assert_eq!(heap.reference_size(), 4);
+        assert_eq!(shallow_sizes(&heap), [24, 32]);
+        assert!(heap
+            .render_summary()
+            .contains("References: 4 bytes, compressed oops inferred from the 72.00bytes heap"));
+    }
+
+    #[test]
+    fn heap_stats_counts() {
+        let mut heap = Heap::default();
+        heap.counter.id_size = 8;
+        let objects = [
+            (0x10, 8, Values::Single(FieldValue::Object(0x20))),
+            (
+                0x20,
+                16,
+                Values::Array(ArrayValue::Object(vec![0x10, 0x30])),
+            ),
+            (
+                0x30,
+                12,
+                Values::PrimitiveArray(PrimitiveArray::new(FieldType::Int, 0, Arc::from([]))),
+            ),
+        ];
+        for (object_id, data_size, value) in objects {
+            heap.instances_pool.make_mut().insert(
+                object_id,
+                Arc::new(Instance {
+                    object_id,
+                    stack_trace_serial_number: 0,
+                    class_object_id: 0,
+                    data_size,
+                    fields: vec![(0, value)],
+                    super_fields: Vec::new(),
+                }),
+            );
+        }
+        heap.root_sticky_class.make_mut().push(0x5);
+        heap.root_sticky_class.make_mut().push(0x6);
+        heap.root_jni_global.make_mut().insert(
+            0x10,
+            RootJniGlobal {
+                object_id: 0x10,
+                jni_global_ref_id: 0,
+            },
+        );
+        let stats = heap.stats();
+        assert_eq!(stats.instances, 1);
+        assert_eq!(stats.object_arrays, 1);
+        assert_eq!(stats.primitive_arrays, 1);
+        // 24 + 32 + 32 with 16 bytes headers aligned to 8
+        assert_eq!(stats.total_shallow_bytes, 88);
+        assert_eq!(stats.classes, 0);
+        assert_eq!(
+            stats.arrays_by_element_type,
+            BTreeMap::from([("int".to_string(), 1), ("object".to_string(), 1)])
+        );
+        assert_eq!(
+            stats.roots_by_kind,
+            BTreeMap::from([(GcRootKind::JniGlobal, 1), (GcRootKind::StickyClass, 2)])
+        );
+    }
+
+    #[test]
+    fn clones_share_the_indexes() {
+        fn assert_send_sync<T: Send + Sync>() {}
+        assert_send_sync::<Heap>();
+
+        let heap = heap_with_partial_class_info();
+        let mut clone = heap.clone();
+        assert!(Shared::ptr_eq(&heap.utf8_strings, &clone.utf8_strings));
+        clone.utf8_strings.make_mut().insert(999, "other".into());
+        assert!(!Shared::ptr_eq(&heap.utf8_strings, &clone.utf8_strings));
+        assert!(!heap.utf8_strings.contains_key(&999));
+        assert!(Shared::ptr_eq(&heap.class_registry, &clone.class_registry));
+    }
+
+    #[test]
+    fn shallow_sizes_of_extreme_instances() {
+        let mut heap = Heap::default();
+        heap.counter.id_size = 8;
+        heap.reference_sizing.compressed_oops = true;
+        // long[] of 2 billion elements, 16GB past the 32 bits of the element count
+        let array = Instance {
+            object_id: 1,
+            stack_trace_serial_number: 0,
+            class_object_id: FieldType::Long.to_u64(),
+            data_size: 2_000_000_000 * 8,
+            fields: vec![(
+                0,
+                Values::PrimitiveArray(PrimitiveArray::new(FieldType::Long, 0, Arc::from([]))),
+            )],
+            super_fields: Vec::new(),
+        };
+        assert_eq!(heap.shallow_size(&array), 16_000_000_016);
+        // corrupt instance declaring fewer bytes than its narrowed references
+        let instance = Instance {
+            object_id: 2,
+            stack_trace_serial_number: 0,
+            class_object_id: 100,
+            data_size: 0,
+            fields: (0..5)
+                .map(|name_id| (name_id, Values::Single(FieldValue::Object(1))))
+                .collect(),
+            super_fields: Vec::new(),
+        };
+        assert_eq!(heap.shallow_size(&instance), 0);
+    }
+
+    #[test]
+    fn cyclic_super_classes_end_the_walk() {
+        let mut heap = Heap::default();
+        for (class_object_id, super_class_object_id) in [(1, 2), (2, 1)] {
+            heap.classes_dump.make_mut().insert(
+                class_object_id,
+                ClassDumpFields::new(
+                    class_object_id,
+                    0,
+                    super_class_object_id,
+                    0,
+                    0,
+                    Vec::new(),
+                    Vec::new(),
+                    Vec::new(),
+                ),
+            );
         }
+        assert!(!heap.is_subclass_of(1, "java.lang.Enum"));
     }
 }
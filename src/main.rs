@@ -0,0 +1,274 @@
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+use hprof_slurp::analysis::baseline::BaselineProfile;
+use hprof_slurp::analysis::explain_size::explain_class_size;
+use hprof_slurp::analysis::referencing_fields::referencing_fields_report;
+#[cfg(feature = "graph")]
+use hprof_slurp::analysis::what_if::what_if_report;
+use hprof_slurp::analysis::what_if::WhatIfQuery;
+use hprof_slurp::args::{get_args, Args};
+use hprof_slurp::class_export::{export_class, ClassExportFormat};
+use hprof_slurp::errors::HprofSlurpError;
+use hprof_slurp::errors::HprofSlurpError::*;
+#[cfg(feature = "graph")]
+use hprof_slurp::graph::HeapGraph;
+#[cfg(feature = "index")]
+use hprof_slurp::index::{index_dump, index_path};
+use hprof_slurp::info::{dump_info, INFO_PREFIX_LEN};
+use hprof_slurp::mat_export::write_mat_indexes;
+use hprof_slurp::progress::ProgressMode;
+use hprof_slurp::report::auto::{auto_report, AutoThresholds};
+use hprof_slurp::report::{write_blocks, Block, RenderOptions, ReportMetadata, SectionKind};
+use hprof_slurp::slurp::{slurp_file_parts, SlurpOptions};
+#[cfg(feature = "index")]
+use hprof_slurp::utils::pretty_count;
+use hprof_slurp::utils::set_size_units;
+use hprof_slurp::verify::verify_dump;
+use hprof_slurp::Heap;
+
+fn main() {
+    std::process::exit(match main_result() {
+        Ok(()) => 0,
+        Err(err) => {
+            eprintln!("error: {}", err);
+            1
+        }
+    });
+}
+
+fn main_result() -> Result<(), HprofSlurpError> {
+    let args = get_args()?;
+    set_size_units(args.size_units);
+    let stdout = io::stdout();
+    let mut writer = BufWriter::new(stdout.lock());
+    let render_options = RenderOptions {
+        table: args.table_options,
+        colors: args.color.enabled(),
+    };
+
+    let options = slurp_options(&args);
+
+    if args.index {
+        let summary = index_dumps(&args.input_files, options.progress)?;
+        writer.write_all(summary.as_bytes())?;
+        writer.flush()?;
+        return Ok(());
+    }
+
+    if args.info {
+        let info = dump_info(args.input_files, INFO_PREFIX_LEN)?;
+        writer.write_all(info.render().as_bytes())?;
+        writer.flush()?;
+        return Ok(());
+    }
+
+    if args.verify {
+        let verification = verify_dump(args.input_files)?;
+        writer.write_all(verification.render().as_bytes())?;
+        writer.flush()?;
+        if !verification.is_valid() {
+            return Err(CorruptedDump {
+                failures: verification.failures(),
+            });
+        }
+        return Ok(());
+    }
+
+    let mut heap = slurp_file_parts(args.input_files.clone(), options)?;
+    if args.mat_index {
+        // the offsets of the parts follow each other as in a single dump
+        write_mat_indexes(&heap, Path::new(&args.input_files[0]))?;
+    }
+    if let Some(salt) = &args.anonymize {
+        heap.anonymize_class_names(salt);
+    }
+
+    if let Some(class_name) = &args.explain {
+        for explanation in explain_class_size(&heap, class_name)? {
+            write_blocks(&explanation.blocks(), &render_options, &mut writer)?;
+        }
+        writer.flush()?;
+        return Ok(());
+    }
+
+    if let Some(export) = &args.export_class {
+        let format = ClassExportFormat::from_path(export.output.as_deref());
+        match &export.output {
+            Some(path) => {
+                let mut file = BufWriter::new(File::create(path)?);
+                export_class(&heap, &export.class_name, &export.fields, format, &mut file)?;
+                file.flush()?;
+            }
+            None => {
+                export_class(
+                    &heap,
+                    &export.class_name,
+                    &export.fields,
+                    format,
+                    &mut writer,
+                )?;
+                writer.flush()?;
+            }
+        }
+        return Ok(());
+    }
+
+    if let Some(query) = &args.what_if {
+        let blocks = what_if_blocks(&heap, query, args.top)?;
+        write_blocks(&blocks, &render_options, &mut writer)?;
+        writer.flush()?;
+        return Ok(());
+    }
+
+    if let Some(export) = args.export {
+        writer.write_all(export.render(&heap).as_bytes())?;
+        writer.flush()?;
+        return Ok(());
+    }
+
+    let sections = optional_sections(&args);
+    let mut report = if args.auto {
+        // the summary is part of the first look
+        let mut report = auto_report(
+            &heap,
+            args.top,
+            args.histogram_options,
+            &AutoThresholds::default(),
+        );
+        for kind in &sections {
+            if !report.sections.iter().any(|section| section.kind == *kind) {
+                let blocks = heap.section_blocks(*kind, args.top, args.histogram_options);
+                report.push(*kind, blocks);
+            }
+        }
+        report
+    } else {
+        let mut report = heap.report_with_sections(
+            args.top,
+            // streamed to their own file instead
+            args.list_strings && args.strings_output.is_none(),
+            args.histogram_options,
+            &sections,
+        );
+        // the record counters are debug info
+        if !args.debug {
+            report
+                .sections
+                .retain(|section| section.kind != SectionKind::Summary);
+        }
+        report
+    };
+    if let Some(path) = &args.strings_output {
+        let mut file = BufWriter::new(File::create(path)?);
+        heap.write_captured_strings(&mut file)?;
+        file.flush()?;
+    }
+    if let Some(baseline) = &args.baseline {
+        let baseline = BaselineProfile::load(baseline)?;
+        let blocks = heap.baseline_diff_blocks(&baseline, args.top, args.histogram_options);
+        report.push(SectionKind::BaselineDiff, blocks);
+    }
+    if let Some(path) = &args.save_baseline {
+        let metadata = ReportMetadata::new(
+            heap.provenance.clone(),
+            args.top,
+            args.list_strings,
+            args.histogram_options,
+        );
+        let profile = heap.baseline_profile(args.histogram_options.names);
+        std::fs::write(path, profile.render_with_metadata(&metadata))?;
+    }
+    if let Some(min_size) = args.cycles {
+        let blocks = heap.reference_cycles_blocks(args.top, min_size);
+        report.push(SectionKind::ReferenceCycles, blocks);
+    }
+    if let Some(target_class) = &args.referencing_fields {
+        let blocks = referencing_fields_report(&heap, target_class, args.top).blocks();
+        report.push(SectionKind::ReferencingFields, blocks);
+    }
+    if args.memory_report {
+        let blocks = heap.section_blocks(SectionKind::ToolMemory, args.top, args.histogram_options);
+        report.push(SectionKind::ToolMemory, blocks);
+    }
+    report.write(args.format, &render_options, &mut writer)?;
+    writer.flush()?;
+    Ok(())
+}
+
+// `--section` and the flags appending a section
+fn optional_sections(args: &Args) -> Vec<SectionKind> {
+    let mut sections = args.sections.clone();
+    if args.graph_metrics {
+        sections.push(SectionKind::GraphMetrics);
+    }
+    if args.heaviest_fields {
+        sections.push(SectionKind::HeaviestFields);
+    }
+    // appended with its own minimum size
+    if args.cycles.is_some() {
+        sections.retain(|kind| *kind != SectionKind::ReferenceCycles);
+    }
+    sections
+}
+
+fn slurp_options(args: &Args) -> SlurpOptions {
+    SlurpOptions {
+        progress: if args.quiet {
+            ProgressMode::Never
+        } else {
+            ProgressMode::Auto
+        },
+        max_memory: args.max_memory,
+        checkpoint: args.checkpoint.clone(),
+        dump_index: args.dump_index,
+        keep_record_offsets: args.mat_index,
+        readahead_buffers: args.readahead_buffers,
+        direct_io: args.direct_io,
+        record_limits: args.record_limits,
+        ..SlurpOptions::default()
+    }
+}
+
+#[cfg(feature = "graph")]
+fn what_if_blocks(
+    heap: &Heap,
+    query: &WhatIfQuery,
+    top: usize,
+) -> Result<Vec<Block>, HprofSlurpError> {
+    Ok(what_if_report(heap, &HeapGraph::new(heap), query, top).blocks())
+}
+
+#[cfg(not(feature = "graph"))]
+fn what_if_blocks(
+    _heap: &Heap,
+    _query: &WhatIfQuery,
+    _top: usize,
+) -> Result<Vec<Block>, HprofSlurpError> {
+    Err(MissingFeature { feature: "graph" })
+}
+
+#[cfg(feature = "index")]
+fn index_dumps(input_files: &[String], progress: ProgressMode) -> Result<String, HprofSlurpError> {
+    let mut summary = String::new();
+    for input_file in input_files {
+        let dump_path = Path::new(input_file);
+        let index = index_dump(dump_path, progress)?;
+        summary.push_str(&format!(
+            "Wrote {} with {} classes and {} record offsets\n",
+            index_path(dump_path).display(),
+            pretty_count(index.classes_dump.len() as u64),
+            pretty_count(index.record_offsets.len() as u64)
+        ));
+    }
+    Ok(summary)
+}
+
+#[cfg(not(feature = "index"))]
+fn index_dumps(
+    _input_files: &[String],
+    _progress: ProgressMode,
+) -> Result<String, HprofSlurpError> {
+    Err(MissingFeature { feature: "index" })
+}
@@ -0,0 +1,147 @@
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+use crate::errors::HprofSlurpError;
+use crate::errors::HprofSlurpError::*;
+use crate::Heap;
+
+// values per page of a long index, as written by MAT
+const MAT_PAGE_SIZE_LONG: usize = 500_000;
+
+/// Writes the identifier and offset indexes of Eclipse MAT next to the dump, e.g. `dump.idx.index` and
+/// `dump.o2hprof.index` for `dump.hprof`, returning their paths.
+///
+/// The identifier index lists the object ids in ascending order, the position of an id is the object index
+/// of MAT, and the offset index gives the position of the record of each object in the dump. The heap must
+/// be slurped with `SlurpOptions::keep_record_offsets` from an uncompressed dump.
+pub fn write_mat_indexes(heap: &Heap, dump_path: &Path) -> Result<Vec<PathBuf>, HprofSlurpError> {
+    if heap.record_offsets.is_empty() {
+        return Err(MissingRecordOffsets);
+    }
+    let mut objects: Vec<(u64, u64)> = heap
+        .record_offsets
+        .iter()
+        .map(|(object_id, offset)| (*object_id, *offset))
+        .collect();
+    objects.sort_unstable();
+    let identifiers: Vec<u64> = objects.iter().map(|(object_id, _)| *object_id).collect();
+    let offsets: Vec<u64> = objects.iter().map(|(_, offset)| *offset).collect();
+
+    let mut paths = Vec::new();
+    for (kind, values) in [("idx", identifiers), ("o2hprof", offsets)] {
+        let path = mat_index_path(dump_path, kind);
+        let mut writer = BufWriter::new(File::create(&path)?);
+        write_long_index(&mut writer, &values)?;
+        writer.flush()?;
+        paths.push(path);
+    }
+    Ok(paths)
+}
+
+// MAT names its indexes after the dump without its extension
+fn mat_index_path(dump_path: &Path, kind: &str) -> PathBuf {
+    let prefix = dump_path.file_stem().unwrap_or_default().to_string_lossy();
+    dump_path.with_file_name(format!("{}.{}.index", prefix, kind))
+}
+
+// compressed pages, then the start of each page and the end of the last one, the page size and the value count
+fn write_long_index<W: Write>(writer: &mut W, values: &[u64]) -> std::io::Result<()> {
+    let mut page_starts = vec![0u64];
+    for page in values.chunks(MAT_PAGE_SIZE_LONG) {
+        let compressed = compress_longs(page);
+        writer.write_all(&compressed)?;
+        page_starts.push(page_starts[page_starts.len() - 1] + compressed.len() as u64);
+    }
+    for page_start in page_starts {
+        writer.write_all(&page_start.to_be_bytes())?;
+    }
+    writer.write_all(&(MAT_PAGE_SIZE_LONG as u32).to_be_bytes())?;
+    writer.write_all(&(values.len() as u32).to_be_bytes())?;
+    Ok(())
+}
+
+// `ArrayLongCompressed` of MAT: the varying bits of each value packed big-endian after a two bytes header
+// holding the number of varying bits and of trailing bits cleared in all values
+fn compress_longs(values: &[u64]) -> Vec<u8> {
+    let mask = values.iter().fold(0, |mask, value| mask | value);
+    let (varying_bits, trailing_clear_bits) = if mask == 0 {
+        (1, 0)
+    } else {
+        let trailing = mask.trailing_zeros();
+        (64 - mask.leading_zeros() - trailing, trailing)
+    };
+    let bits = values.len() as u64 * varying_bits as u64;
+    let mut data = vec![0u8; 2 + (bits.max(1) as usize - 1) / 8 + 1];
+    data[0] = varying_bits as u8;
+    data[1] = trailing_clear_bits as u8;
+    for (index, value) in values.iter().enumerate() {
+        let value = value >> trailing_clear_bits;
+        let position = index as u64 * varying_bits as u64;
+        let mut idx = 2 + (position >> 3) as usize;
+        let mut off = (position & 0x7) as u32 + varying_bits;
+        if off > 8 {
+            off -= 8;
+            data[idx] |= (value >> off) as u8;
+            idx += 1;
+            while off > 8 {
+                off -= 8;
+                data[idx] = (value >> off) as u8;
+                idx += 1;
+            }
+        }
+        data[idx] |= (value << (8 - off)) as u8;
+    }
+    data
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // mirrors `ArrayLongCompressed.get` of MAT
+    fn decompress_longs(data: &[u8], len: usize) -> Vec<u64> {
+        let (varying_bits, trailing_clear_bits) = (data[0] as u64, data[1] as u64);
+        (0..len as u64)
+            .map(|index| {
+                let mut value = 0u64;
+                for bit in index * varying_bits..(index + 1) * varying_bits {
+                    let byte = data[2 + (bit / 8) as usize];
+                    value = (value << 1) | ((byte >> (7 - bit % 8)) & 1) as u64;
+                }
+                value << trailing_clear_bits
+            })
+            .collect()
+    }
+
+    #[test]
+    fn compressed_longs_round_trip() {
+        let values = vec![0xfeb1_a040, 0xfeb1_a058, 0xfeb4_77d8, 0x8];
+        let data = compress_longs(&values);
+        // 8 bytes aligned addresses share 3 trailing clear bits
+        assert_eq!(data[1], 3);
+        assert_eq!(data[0], 32 - 3);
+        assert_eq!(decompress_longs(&data, values.len()), values);
+
+        assert_eq!(decompress_longs(&compress_longs(&[0, 0]), 2), vec![0, 0]);
+        let wide = vec![u64::MAX, 1];
+        assert_eq!(decompress_longs(&compress_longs(&wide), 2), wide);
+    }
+
+    #[test]
+    fn long_index_trailer() {
+        let mut bytes = Vec::new();
+        write_long_index(&mut bytes, &[16, 32, 48]).unwrap();
+        let len = bytes.len();
+        assert_eq!(&bytes[len - 4..], &3u32.to_be_bytes());
+        assert_eq!(&bytes[len - 8..len - 4], &500_000u32.to_be_bytes());
+        // one page: its start and its end
+        let page_end = u64::from_be_bytes(bytes[len - 16..len - 8].try_into().unwrap());
+        assert_eq!(page_end as usize, len - 24);
+        assert_eq!(decompress_longs(&bytes, 3), vec![16, 32, 48]);
+        assert_eq!(
+            mat_index_path(Path::new("/tmp/dump.hprof"), "idx"),
+            PathBuf::from("/tmp/dump.idx.index")
+        );
+    }
+}
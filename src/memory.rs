@@ -0,0 +1,189 @@
+use std::collections::{HashMap, HashSet};
+use std::mem::size_of;
+use std::sync::Arc;
+
+use serde::Serialize;
+
+use crate::parser::gc_record::{ArrayValue, ConstFieldInfo, FieldInfo, FieldValue, Values};
+use crate::report::{render_blocks, Block};
+use crate::result_recorder::Instance;
+use crate::utils::pretty_bytes_size;
+use crate::Heap;
+
+// the `Arc` strong and weak counts in front of a shared value
+const ARC_COUNTS_BYTES: u64 = 2 * size_of::<usize>() as u64;
+
+/// Estimated memory held by one part of the `Heap`.
+#[derive(Debug, Clone, Serialize)]
+pub struct SubsystemMemory {
+    pub name: &'static str,
+    pub estimated_bytes: u64,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct MemoryReport {
+    // high water mark of the resident set, `None` where the platform does not expose it
+    pub peak_rss: Option<u64>,
+    // sorted by estimated size
+    pub subsystems: Vec<SubsystemMemory>,
+}
+
+/// Reports the peak RSS of the process and estimates the memory held by each part of the `Heap`,
+/// to pick the retention options fitting a machine.
+///
+/// Estimates count the container capacities and payloads, not the allocator overhead.
+pub fn memory_report(heap: &Heap) -> MemoryReport {
+    let strings = map_bytes(&heap.utf8_strings)
+        + heap
+            .utf8_strings
+            .values()
+            .map(|s| s.len() as u64)
+            .sum::<u64>();
+
+    let mut instances = map_bytes(&heap.instances_pool);
+    let mut primitive_arrays = 0;
+    let mut object_arrays = 0;
+    // deduplicated payloads are shared by several arrays
+    let mut payloads: HashSet<*const u8> = HashSet::new();
+    for instance in heap.instances_pool.values() {
+        let fields = instance_bytes(instance);
+        match instance.fields.first() {
+            Some((_, Values::PrimitiveArray(array))) => {
+                primitive_arrays += fields;
+                if payloads.insert(array.bytes().as_ptr()) {
+                    primitive_arrays += ARC_COUNTS_BYTES + array.bytes().len() as u64;
+                }
+            }
+            Some((_, Values::Array(ArrayValue::Object(elements)))) => {
+                object_arrays += fields + (elements.capacity() * size_of::<u64>()) as u64;
+            }
+            _ => instances += fields,
+        }
+    }
+
+    let classes = heap.class_registry.estimated_bytes()
+        + map_bytes(&heap.classes_dump)
+        + heap
+            .classes_dump
+            .values()
+            .map(|class| {
+                (class.const_fields.capacity() * size_of::<(ConstFieldInfo, FieldValue)>()
+                    + class.static_fields.capacity() * size_of::<(FieldInfo, FieldValue)>()
+                    + class.instance_fields.capacity() * size_of::<FieldInfo>())
+                    as u64
+            })
+            .sum::<u64>();
+    let stack_traces = map_bytes(&heap.stack_trace_by_serial_number)
+        + map_bytes(&heap.stack_frame_by_id)
+        + heap
+            .stack_trace_by_serial_number
+            .values()
+            .map(|trace| (trace.stack_frame_ids.capacity() * size_of::<u64>()) as u64)
+            .sum::<u64>();
+    let gc_roots = map_bytes(&heap.root_thread_object)
+        + vec_bytes(&heap.root_jni_local)
+        + map_bytes(&heap.root_jni_global)
+        + vec_bytes(&heap.root_java_frame)
+        + vec_bytes(&heap.root_native_stack)
+        + vec_bytes(&heap.root_thread_block)
+        + ((heap.root_sticky_class.capacity()
+            + heap.root_monitor_used.capacity()
+            + heap.root_unknown.capacity())
+            * size_of::<u64>()) as u64;
+
+    let mut subsystems: Vec<SubsystemMemory> = [
+        ("UTF-8 strings", strings),
+        ("Instances", instances),
+        ("Primitive arrays", primitive_arrays),
+        ("Object arrays", object_arrays),
+        ("Classes", classes),
+        ("Stack traces", stack_traces),
+        ("GC roots", gc_roots),
+        ("Record offsets", map_bytes(&heap.record_offsets)),
+    ]
+    .into_iter()
+    .map(|(name, estimated_bytes)| SubsystemMemory {
+        name,
+        estimated_bytes,
+    })
+    .collect();
+    subsystems.sort_by(|a, b| {
+        b.estimated_bytes
+            .cmp(&a.estimated_bytes)
+            .then_with(|| a.name.cmp(b.name))
+    });
+    MemoryReport {
+        peak_rss: peak_rss(),
+        subsystems,
+    }
+}
+
+/// High water mark of the resident set of the process, only available on Linux.
+pub fn peak_rss() -> Option<u64> {
+    parse_peak_rss(&std::fs::read_to_string("/proc/self/status").ok()?)
+}
+
+// e.g. `VmHWM:	  123456 kB` in `/proc/self/status`
+fn parse_peak_rss(status: &str) -> Option<u64> {
+    let line = status.lines().find(|l| l.starts_with("VmHWM:"))?;
+    let kilobytes: u64 = line.split_whitespace().nth(1)?.parse().ok()?;
+    Some(kilobytes * 1024)
+}
+
+// buckets of a hash map, the entries are stored inline with one control byte each
+fn map_bytes<K, V>(map: &HashMap<K, V>) -> u64 {
+    (map.capacity() * (size_of::<(K, V)>() + 1)) as u64
+}
+
+fn vec_bytes<T>(vec: &Vec<T>) -> u64 {
+    (vec.capacity() * size_of::<T>()) as u64
+}
+
+// the shared instance and its field vectors, array payloads excluded
+fn instance_bytes(instance: &Arc<Instance>) -> u64 {
+    let fields = (instance.fields.capacity() + instance.super_fields.capacity())
+        * size_of::<(u64, Values)>();
+    ARC_COUNTS_BYTES + (size_of::<Instance>() + fields) as u64
+}
+
+impl MemoryReport {
+    pub fn estimated_bytes(&self) -> u64 {
+        self.subsystems.iter().map(|s| s.estimated_bytes).sum()
+    }
+
+    pub fn blocks(&self) -> Vec<Block> {
+        let peak_rss = self
+            .peak_rss
+            .map_or_else(|| "unknown".to_string(), pretty_bytes_size);
+        let rows: Vec<Vec<String>> = self
+            .subsystems
+            .iter()
+            .map(|s| vec![pretty_bytes_size(s.estimated_bytes), s.name.to_string()])
+            .collect();
+        vec![
+            Block::Heading(format!(
+                "Memory used by hprof-slurp: peak RSS {}, estimated {} for the heap:",
+                peak_rss,
+                pretty_bytes_size(self.estimated_bytes())
+            )),
+            Block::table(&["Estimated", "Subsystem"], rows),
+        ]
+    }
+
+    pub fn render(&self) -> String {
+        render_blocks(&self.blocks())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_proc_status_peak_rss() {
+        let status =
+            "Name:\thprof-slurp\nVmPeak:\t  300000 kB\nVmHWM:\t    2048 kB\nVmRSS:\t    1024 kB\n";
+        assert_eq!(parse_peak_rss(status), Some(2 * 1024 * 1024));
+        assert_eq!(parse_peak_rss("Name:\thprof-slurp\n"), None);
+    }
+}
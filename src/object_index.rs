@@ -0,0 +1,102 @@
+use std::collections::HashMap;
+
+/// Dense `u32` indices assigned to the sparse `u64` object ids in insertion order.
+///
+/// Built from a slurped `Heap` by the structures indexed by object (e.g. the `HeapGraph`), which then use
+/// plain vectors of these indices instead of keying on the 64 bits ids. The recorder and the `Heap` only
+/// know the 64 bits ids.
+#[derive(Debug, Clone, Default)]
+pub struct ObjectIndex {
+    ids: Vec<u64>,
+    index_by_id: HashMap<u64, u32>,
+}
+
+impl ObjectIndex {
+    pub fn from_ids(ids: impl IntoIterator<Item = u64>) -> Self {
+        let mut index = ObjectIndex::default();
+        ids.into_iter().for_each(|id| {
+            index.insert(id);
+        });
+        index
+    }
+
+    /// Returns the index of `object_id`, assigning the next one if it is not indexed yet.
+    ///
+    /// Panics once more than `u32::MAX - 1` objects are indexed.
+    pub fn insert(&mut self, object_id: u64) -> u32 {
+        let next = next_index(self.ids.len());
+        let index = *self.index_by_id.entry(object_id).or_insert(next);
+        if index == next {
+            self.ids.push(object_id);
+        }
+        index
+    }
+
+    pub fn index_of(&self, object_id: u64) -> Option<u32> {
+        self.index_by_id.get(&object_id).copied()
+    }
+
+    pub fn id_of(&self, index: u32) -> u64 {
+        self.ids[index as usize]
+    }
+
+    pub fn contains(&self, object_id: u64) -> bool {
+        self.index_by_id.contains_key(&object_id)
+    }
+
+    pub fn len(&self) -> usize {
+        self.ids.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.ids.is_empty()
+    }
+
+    /// Object ids in index order.
+    pub fn ids(&self) -> &[u64] {
+        &self.ids
+    }
+}
+
+const MAX_OBJECTS: u32 = u32::MAX - 1;
+
+fn next_index(len: usize) -> u32 {
+    // the graph numbers its nodes `index + 1` and keeps `u32::MAX` as a sentinel
+    u32::try_from(len)
+        .ok()
+        .filter(|index| *index < MAX_OBJECTS)
+        .expect("too many objects to index")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn indices_follow_insertion_order() {
+        let mut index = ObjectIndex::from_ids([30, 10, 20]);
+        assert_eq!(index.len(), 3);
+        assert_eq!(index.ids(), &[30, 10, 20]);
+        assert_eq!(index.index_of(10), Some(1));
+        assert_eq!(index.id_of(2), 20);
+        assert_eq!(index.index_of(40), None);
+
+        // an indexed object keeps its index
+        assert_eq!(index.insert(30), 0);
+        assert_eq!(index.insert(40), 3);
+        assert_eq!(index.len(), 4);
+        assert!(index.contains(40));
+    }
+
+    #[test]
+    fn next_index_up_to_the_limit() {
+        assert_eq!(next_index(0), 0);
+        assert_eq!(next_index(MAX_OBJECTS as usize - 1), MAX_OBJECTS - 1);
+    }
+
+    #[test]
+    #[should_panic(expected = "too many objects to index")]
+    fn next_index_overflow() {
+        next_index(MAX_OBJECTS as usize);
+    }
+}
@@ -1,4 +1,11 @@
-#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash)]
+use std::fmt;
+use std::sync::{Arc, OnceLock};
+
+use serde::{Deserialize, Serialize};
+
+use crate::parser::record_parser::parse_array_value;
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash, Serialize, Deserialize)]
 pub enum FieldType {
     Object = 2,
     Bool = 4,
@@ -39,25 +46,120 @@ impl FieldType {
             FieldType::Long => 11,
         }
     }
+
+    /// Returns the JVM descriptor of the type (e.g. `B` for `byte`).
+    pub fn descriptor(&self) -> char {
+        match self {
+            FieldType::Object => 'L',
+            FieldType::Bool => 'Z',
+            FieldType::Char => 'C',
+            FieldType::Float => 'F',
+            FieldType::Double => 'D',
+            FieldType::Byte => 'B',
+            FieldType::Short => 'S',
+            FieldType::Int => 'I',
+            FieldType::Long => 'J',
+        }
+    }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ConstFieldInfo {
     pub const_pool_idx: u16,
     pub const_type: FieldType,
 }
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct FieldInfo {
     pub name_id: u64,
     pub field_type: FieldType,
 }
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum Values {
     Single(FieldValue),
     Array(ArrayValue),
+    PrimitiveArray(PrimitiveArray),
 }
 
-#[derive(Debug, Clone)]
+impl Values {
+    pub fn as_single(&self) -> Option<&FieldValue> {
+        match self {
+            Values::Single(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    /// The elements of an object or primitive array, primitive arrays are decoded on first access.
+    pub fn as_array(&self) -> Option<&ArrayValue> {
+        match self {
+            Values::Single(_) => None,
+            Values::Array(value) => Some(value),
+            Values::PrimitiveArray(array) => Some(array.decoded()),
+        }
+    }
+
+    pub fn as_i64(&self) -> Option<i64> {
+        self.as_single()?.as_i64()
+    }
+
+    pub fn as_f64(&self) -> Option<f64> {
+        self.as_single()?.as_f64()
+    }
+
+    pub fn as_object_id(&self) -> Option<u64> {
+        self.as_single()?.as_object_id()
+    }
+
+    pub fn as_bool(&self) -> Option<bool> {
+        self.as_single()?.as_bool()
+    }
+}
+
+impl fmt::Display for Values {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Values::Single(value) => value.fmt(f),
+            Values::Array(value) => value.fmt(f),
+            Values::PrimitiveArray(array) => array.decoded().fmt(f),
+        }
+    }
+}
+
+/// Primitive array elements kept as the big-endian bytes of the dump and decoded on first access.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PrimitiveArray {
+    pub element_type: FieldType,
+    // elements retained in `bytes`, zero if they were dropped while slurping
+    pub number_of_elements: u32,
+    bytes: Arc<[u8]>,
+    #[serde(skip)]
+    decoded: OnceLock<ArrayValue>,
+}
+
+impl PrimitiveArray {
+    pub fn new(element_type: FieldType, number_of_elements: u32, bytes: Arc<[u8]>) -> Self {
+        PrimitiveArray {
+            element_type,
+            number_of_elements,
+            bytes,
+            decoded: OnceLock::new(),
+        }
+    }
+
+    /// Raw big-endian elements.
+    pub fn bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+
+    pub fn decoded(&self) -> &ArrayValue {
+        self.decoded.get_or_init(|| {
+            parse_array_value(self.element_type, self.number_of_elements)(&self.bytes)
+                .map(|(_, value)| value)
+                .expect("primitive array bytes were checked by the parser")
+        })
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum FieldValue {
     Bool(bool),
     Byte(i8),
@@ -70,7 +172,75 @@ pub enum FieldValue {
     Object(u64),
 }
 
-#[derive(Debug, Clone)]
+impl FieldValue {
+    /// Integral values widened to `i64`, chars as their UTF-16 code unit.
+    pub fn as_i64(&self) -> Option<i64> {
+        match self {
+            FieldValue::Byte(value) => Some(*value as i64),
+            FieldValue::Char(value) => Some(*value as i64),
+            FieldValue::Short(value) => Some(*value as i64),
+            FieldValue::Int(value) => Some(*value as i64),
+            FieldValue::Long(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    /// Numeric values as `f64`, integral ones included.
+    pub fn as_f64(&self) -> Option<f64> {
+        match self {
+            FieldValue::Float(value) => Some(*value as f64),
+            FieldValue::Double(value) => Some(*value),
+            _ => self.as_i64().map(|value| value as f64),
+        }
+    }
+
+    /// The referenced object id, `None` for null references and primitives.
+    pub fn as_object_id(&self) -> Option<u64> {
+        match self {
+            FieldValue::Object(id) if *id != 0 => Some(*id),
+            _ => None,
+        }
+    }
+
+    pub fn as_bool(&self) -> Option<bool> {
+        match self {
+            FieldValue::Bool(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    pub fn is_null(&self) -> bool {
+        matches!(self, FieldValue::Object(0))
+    }
+}
+
+// Java syntax: chars are quoted, references are hex ids or `null`
+impl fmt::Display for FieldValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FieldValue::Bool(value) => write!(f, "{}", value),
+            FieldValue::Byte(value) => write!(f, "{}", value),
+            FieldValue::Char(value) => write!(f, "'{}'", escape_char(*value)),
+            FieldValue::Short(value) => write!(f, "{}", value),
+            FieldValue::Int(value) => write!(f, "{}", value),
+            FieldValue::Long(value) => write!(f, "{}L", value),
+            FieldValue::Float(value) => write!(f, "{}f", value),
+            FieldValue::Double(value) => write!(f, "{}", value),
+            FieldValue::Object(0) => write!(f, "null"),
+            FieldValue::Object(id) => write!(f, "0x{:x}", id),
+        }
+    }
+}
+
+// lone surrogates and control characters are rendered as `\uXXXX`
+fn escape_char(code_unit: u16) -> String {
+    match char::from_u32(code_unit as u32) {
+        Some(c) if !c.is_control() => c.to_string(),
+        _ => format!("\\u{:04x}", code_unit),
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum ArrayValue {
     Bool(Vec<bool>),
     Byte(Vec<i8>),
@@ -83,7 +253,71 @@ pub enum ArrayValue {
     Object(Vec<u64>),
 }
 
-#[derive(Debug)]
+impl ArrayValue {
+    pub fn len(&self) -> usize {
+        match self {
+            ArrayValue::Bool(values) => values.len(),
+            ArrayValue::Byte(values) => values.len(),
+            ArrayValue::Char(values) => values.len(),
+            ArrayValue::Short(values) => values.len(),
+            ArrayValue::Int(values) => values.len(),
+            ArrayValue::Long(values) => values.len(),
+            ArrayValue::Float(values) => values.len(),
+            ArrayValue::Double(values) => values.len(),
+            ArrayValue::Object(values) => values.len(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+// char arrays are rendered as a string literal, other arrays element by element
+impl fmt::Display for ArrayValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ArrayValue::Char(values) => {
+                write!(f, "\"")?;
+                for c in char::decode_utf16(values.iter().copied()) {
+                    match c {
+                        Ok(c) => write!(f, "{}", c.escape_debug())?,
+                        Err(e) => write!(f, "\\u{:04x}", e.unpaired_surrogate())?,
+                    }
+                }
+                write!(f, "\"")
+            }
+            ArrayValue::Bool(values) => write_list(f, values.iter()),
+            ArrayValue::Byte(values) => write_list(f, values.iter()),
+            ArrayValue::Short(values) => write_list(f, values.iter()),
+            ArrayValue::Int(values) => write_list(f, values.iter()),
+            ArrayValue::Long(values) => write_list(f, values.iter().map(|v| FieldValue::Long(*v))),
+            ArrayValue::Float(values) => {
+                write_list(f, values.iter().map(|v| FieldValue::Float(*v)))
+            }
+            ArrayValue::Double(values) => write_list(f, values.iter()),
+            ArrayValue::Object(values) => {
+                write_list(f, values.iter().map(|v| FieldValue::Object(*v)))
+            }
+        }
+    }
+}
+
+fn write_list<T: fmt::Display>(
+    f: &mut fmt::Formatter<'_>,
+    values: impl Iterator<Item = T>,
+) -> fmt::Result {
+    write!(f, "[")?;
+    for (i, value) in values.enumerate() {
+        if i > 0 {
+            write!(f, ", ")?;
+        }
+        write!(f, "{}", value)?;
+    }
+    write!(f, "]")
+}
+
+#[derive(Debug, Serialize, Deserialize)]
 pub enum GcRecord {
     RootUnknown {
         object_id: u64,
@@ -140,17 +374,18 @@ pub enum GcRecord {
         stack_trace_serial_number: u32,
         number_of_elements: u32,
         element_type: FieldType,
-        bytes_ref: Box<[u8]>,
+        // shared with the `Instance` without further copies
+        bytes_ref: Arc<[u8]>,
     },
     ClassDump(Box<ClassDumpFields>), // rare enough to be boxed to avoid large variant cost
 }
 
-
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ClassDumpFields {
     pub class_object_id: u64,
     pub stack_trace_serial_number: u32,
     pub super_class_object_id: u64,
+    pub class_loader_object_id: u64,
     pub instance_size: u32,
     pub const_fields: Vec<(ConstFieldInfo, FieldValue)>,
     pub static_fields: Vec<(FieldInfo, FieldValue)>,
@@ -158,10 +393,12 @@ pub struct ClassDumpFields {
 }
 
 impl ClassDumpFields {
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         class_object_id: u64,
         stack_trace_serial_number: u32,
         super_class_object_id: u64,
+        class_loader_object_id: u64,
         instance_size: u32,
         const_fields: Vec<(ConstFieldInfo, FieldValue)>,
         static_fields: Vec<(FieldInfo, FieldValue)>,
@@ -171,6 +408,7 @@ impl ClassDumpFields {
             class_object_id,
             stack_trace_serial_number,
             super_class_object_id,
+            class_loader_object_id,
             instance_size,
             const_fields,
             static_fields,
@@ -178,3 +416,30 @@ impl ClassDumpFields {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn field_value_accessors_and_display() {
+        assert_eq!(FieldValue::Char(65).as_i64(), Some(65));
+        assert_eq!(FieldValue::Int(-3).as_f64(), Some(-3.0));
+        assert_eq!(FieldValue::Object(0).as_object_id(), None);
+        assert_eq!(FieldValue::Object(0x10).as_object_id(), Some(0x10));
+        assert_eq!(Values::Single(FieldValue::Bool(true)).as_bool(), Some(true));
+        assert_eq!(FieldValue::Bool(true).as_i64(), None);
+
+        assert_eq!(FieldValue::Char('a' as u16).to_string(), "'a'");
+        assert_eq!(FieldValue::Char(0xd800).to_string(), "'\\ud800'");
+        assert_eq!(FieldValue::Byte(-1).to_string(), "-1");
+        assert_eq!(FieldValue::Long(7).to_string(), "7L");
+        assert_eq!(FieldValue::Object(0).to_string(), "null");
+        assert_eq!(FieldValue::Object(255).to_string(), "0xff");
+        assert_eq!(
+            ArrayValue::Char("hi\n".encode_utf16().collect()).to_string(),
+            "\"hi\\n\""
+        );
+        assert_eq!(ArrayValue::Object(vec![0, 16]).to_string(), "[null, 0x10]");
+    }
+}
@@ -4,3 +4,4 @@ mod primitive_parsers;
 pub mod record;
 pub mod record_parser;
 pub mod record_stream_parser;
+pub mod record_visitor;
@@ -1,3 +1,5 @@
+use serde::{Deserialize, Serialize};
+
 use crate::parser::gc_record::GcRecord;
 
 #[derive(Debug, PartialEq, Eq)]
@@ -23,7 +25,7 @@ pub struct CpuSample {
     pub stack_trace_serial_number: u32,
 }
 
-#[derive(Debug, Default, Clone)]
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
 pub struct StackFrameData {
     pub stack_frame_id: u64,
     pub method_name_id: u64,
@@ -33,7 +35,7 @@ pub struct StackFrameData {
     pub line_number: i32,
 }
 
-#[derive(Debug, Default, Clone)]
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
 pub struct StackTraceData {
     pub serial_number: u32,
     pub thread_serial_number: u32,
@@ -41,7 +43,7 @@ pub struct StackTraceData {
     pub stack_frame_ids: Vec<u64>,
 }
 
-#[derive(Debug, Default, Clone)]
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
 pub struct LoadClassData {
     pub serial_number: u32,
     pub class_object_id: u64,
@@ -49,7 +51,7 @@ pub struct LoadClassData {
     pub class_name_id: u64,
 }
 
-#[derive(Debug, Default, Clone)]
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
 pub struct ThreadStartData {
     pub thread_serial_number: u32,
     pub thread_object_id: u64,
@@ -59,7 +61,7 @@ pub struct ThreadStartData {
     pub thread_group_parent_name_id: u64,
 }
 
-#[derive(Debug, Default, Clone)]
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
 pub struct ThreadEndData {
     pub thread_serial_number: u32,
 }
@@ -113,6 +115,8 @@ pub enum Record {
     },
     HeapDumpStart {
         length: u32,
+        // `HEAP DUMP SEGMENT` closed by a `HEAP DUMP END`, as opposed to a single `HEAP DUMP`
+        segment: bool,
     },
     HeapDumpEnd {
         length: u32,
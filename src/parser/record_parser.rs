@@ -7,61 +7,219 @@ use crate::parser::record::{
 };
 use crate::parser::record_parser::GcRecord::*;
 use crate::parser::record_parser::Record::*;
+use log::warn;
 use nom::combinator::{flat_map, map};
 use nom::error::{ErrorKind, ParseError};
 use nom::multi::count;
 use nom::sequence::{preceded, tuple};
+use nom::Needed;
 use nom::Parser;
 use nom::{bytes, IResult};
+use std::sync::Arc;
 
-const TAG_STRING: u8 = 0x01;
-const TAG_LOAD_CLASS: u8 = 0x02;
-const TAG_UNLOAD_CLASS: u8 = 0x03;
-const TAG_STACK_FRAME: u8 = 0x04;
-const TAG_STACK_TRACE: u8 = 0x05;
-const TAG_ALLOC_SITES: u8 = 0x06;
-const TAG_HEAP_SUMMARY: u8 = 0x07;
-const TAG_START_THREAD: u8 = 0x0A;
-const TAG_END_THREAD: u8 = 0x0B;
-const TAG_HEAP_DUMP: u8 = 0x0C;
-const TAG_HEAP_DUMP_SEGMENT: u8 = 0x1C;
-const TAG_HEAP_DUMP_END: u8 = 0x2C;
-const TAG_CONTROL_SETTING: u8 = 0x0E;
-const TAG_CPU_SAMPLES: u8 = 0x0D;
-
-const TAG_GC_ROOT_UNKNOWN: u8 = 0xFF;
-const TAG_GC_ROOT_JNI_GLOBAL: u8 = 0x01;
-const TAG_GC_ROOT_JNI_LOCAL: u8 = 0x02;
-const TAG_GC_ROOT_JAVA_FRAME: u8 = 0x03;
-const TAG_GC_ROOT_NATIVE_STACK: u8 = 0x04;
-const TAG_GC_ROOT_STICKY_CLASS: u8 = 0x05;
-const TAG_GC_ROOT_THREAD_BLOCK: u8 = 0x06;
-const TAG_GC_ROOT_MONITOR_USED: u8 = 0x07;
-const TAG_GC_ROOT_THREAD_OBJ: u8 = 0x08;
-const TAG_GC_CLASS_DUMP: u8 = 0x20;
-const TAG_GC_INSTANCE_DUMP: u8 = 0x21;
-const TAG_GC_OBJ_ARRAY_DUMP: u8 = 0x22;
-const TAG_GC_PRIM_ARRAY_DUMP: u8 = 0x23;
+pub(crate) const TAG_STRING: u8 = 0x01;
+pub(crate) const TAG_LOAD_CLASS: u8 = 0x02;
+pub(crate) const TAG_UNLOAD_CLASS: u8 = 0x03;
+pub(crate) const TAG_STACK_FRAME: u8 = 0x04;
+pub(crate) const TAG_STACK_TRACE: u8 = 0x05;
+pub(crate) const TAG_ALLOC_SITES: u8 = 0x06;
+pub(crate) const TAG_HEAP_SUMMARY: u8 = 0x07;
+pub(crate) const TAG_START_THREAD: u8 = 0x0A;
+pub(crate) const TAG_END_THREAD: u8 = 0x0B;
+pub(crate) const TAG_HEAP_DUMP: u8 = 0x0C;
+pub(crate) const TAG_HEAP_DUMP_SEGMENT: u8 = 0x1C;
+pub(crate) const TAG_HEAP_DUMP_END: u8 = 0x2C;
+pub(crate) const TAG_CONTROL_SETTING: u8 = 0x0E;
+pub(crate) const TAG_CPU_SAMPLES: u8 = 0x0D;
+
+pub(crate) const TAG_GC_ROOT_UNKNOWN: u8 = 0xFF;
+pub(crate) const TAG_GC_ROOT_JNI_GLOBAL: u8 = 0x01;
+pub(crate) const TAG_GC_ROOT_JNI_LOCAL: u8 = 0x02;
+pub(crate) const TAG_GC_ROOT_JAVA_FRAME: u8 = 0x03;
+pub(crate) const TAG_GC_ROOT_NATIVE_STACK: u8 = 0x04;
+pub(crate) const TAG_GC_ROOT_STICKY_CLASS: u8 = 0x05;
+pub(crate) const TAG_GC_ROOT_THREAD_BLOCK: u8 = 0x06;
+pub(crate) const TAG_GC_ROOT_MONITOR_USED: u8 = 0x07;
+pub(crate) const TAG_GC_ROOT_THREAD_OBJ: u8 = 0x08;
+pub(crate) const TAG_GC_CLASS_DUMP: u8 = 0x20;
+pub(crate) const TAG_GC_INSTANCE_DUMP: u8 = 0x21;
+pub(crate) const TAG_GC_OBJ_ARRAY_DUMP: u8 = 0x22;
+pub(crate) const TAG_GC_PRIM_ARRAY_DUMP: u8 = 0x23;
 
 // TODO currently defaults to 64 bits - use instead caller's `id_size` to change impl. of parse_id to support 32 bits dump.
 const ID_SIZE: u32 = 8;
 
+/// Bitmask of the record tags to decode, the other records are skipped without being decoded.
+///
+/// Excluding `HEAP_DUMP` skips the content of the heap dump segments, their start and end are still reported.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RecordFilter(u64);
+
+impl RecordFilter {
+    pub const ALL: RecordFilter = RecordFilter(u64::MAX);
+    pub const NONE: RecordFilter = RecordFilter(0);
+    pub const STRINGS: RecordFilter = RecordFilter::tags(&[TAG_STRING]);
+    pub const CLASSES: RecordFilter = RecordFilter::tags(&[TAG_LOAD_CLASS, TAG_UNLOAD_CLASS]);
+    pub const STACK_TRACES: RecordFilter = RecordFilter::tags(&[TAG_STACK_FRAME, TAG_STACK_TRACE]);
+    pub const THREADS: RecordFilter = RecordFilter::tags(&[TAG_START_THREAD, TAG_END_THREAD]);
+    pub const HEAP_DUMP: RecordFilter =
+        RecordFilter::tags(&[TAG_HEAP_DUMP, TAG_HEAP_DUMP_SEGMENT, TAG_HEAP_DUMP_END]);
+
+    /// Filter decoding the records with these tags, tags are below 64.
+    pub const fn tags(tags: &[u8]) -> RecordFilter {
+        let mut mask = 0;
+        let mut i = 0;
+        while i < tags.len() {
+            mask |= 1 << tags[i];
+            i += 1;
+        }
+        RecordFilter(mask)
+    }
+
+    pub const fn union(self, other: RecordFilter) -> RecordFilter {
+        RecordFilter(self.0 | other.0)
+    }
+
+    pub fn contains(self, tag: u8) -> bool {
+        // unknown tags are reported by the parser
+        tag >= 64 || self.0 & (1 << tag) != 0
+    }
+
+    fn skips_heap_dump(self) -> bool {
+        !self.contains(TAG_HEAP_DUMP)
+    }
+}
+
+impl Default for RecordFilter {
+    fn default() -> Self {
+        RecordFilter::ALL
+    }
+}
+
+impl std::ops::BitOr for RecordFilter {
+    type Output = RecordFilter;
+
+    fn bitor(self, rhs: RecordFilter) -> RecordFilter {
+        self.union(rhs)
+    }
+}
+
+/// Sanity limits on the lengths declared in the input, a corrupt length would otherwise make the parser
+/// buffer or allocate gigabytes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RecordLimits {
+    // records outside of the heap dump segments, e.g. strings and stack traces
+    pub max_record_length: u32,
+    // elements of a primitive or object array
+    pub max_array_length: u32,
+    // skip the oversized records instead of failing, the rest of the heap dump segment for a sub-record
+    pub skip_oversized: bool,
+}
+
+impl Default for RecordLimits {
+    fn default() -> Self {
+        RecordLimits {
+            max_record_length: DEFAULT_MAX_RECORD_LENGTH,
+            // the JVM caps the array lengths below `Integer.MAX_VALUE`
+            max_array_length: i32::MAX as u32,
+            skip_oversized: false,
+        }
+    }
+}
+
+// the largest records outside of the heap dump are a few MiB long
+const DEFAULT_MAX_RECORD_LENGTH: u32 = 1 << 30;
+
+// initial capacity of the vectors sized by a count read from the input
+const MAX_INITIAL_CAPACITY: usize = 1024;
+
 pub struct HprofRecordParser {
     heap_dump_remaining_len: u32,
+    filter: RecordFilter,
+    limits: RecordLimits,
+    // bytes of oversized records left to skip
+    skip_len: usize,
+    // why the last parsing failed on an oversized record
+    oversized: Option<String>,
+}
+
+// bytes skipped by the filter before the next record to decode
+enum Skipped {
+    Complete(usize),
+    // an excluded record is not fully available yet
+    Incomplete { skipped: usize, missing: usize },
 }
 
 impl HprofRecordParser {
     pub fn new() -> Self {
+        Self::resume(0)
+    }
+
+    /// Resumes parsing with `heap_dump_remaining_len` bytes left in the current heap dump segment.
+    pub fn resume(heap_dump_remaining_len: u32) -> Self {
         HprofRecordParser {
-            heap_dump_remaining_len: 0,
+            heap_dump_remaining_len,
+            filter: RecordFilter::ALL,
+            limits: RecordLimits::default(),
+            skip_len: 0,
+            oversized: None,
         }
     }
 
+    /// Fails or skips the records declaring lengths above `limits`.
+    pub fn with_limits(mut self, limits: RecordLimits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    /// Describes the oversized record which made the parsing fail, if any.
+    pub fn take_oversized(&mut self) -> Option<String> {
+        self.oversized.take()
+    }
+
+    /// Only decodes the records selected by `filter`.
+    pub fn with_filter(mut self, filter: RecordFilter) -> Self {
+        self.filter = filter;
+        self
+    }
+
+    pub fn heap_dump_remaining_len(&self) -> u32 {
+        self.heap_dump_remaining_len
+    }
+
     // TODO use nom combinators (instead of Result's)
     pub fn parse_hprof_record(&mut self) -> impl FnMut(&[u8]) -> IResult<&[u8], Record> + '_ {
         |i| {
+            if let Some((message, skip_len)) = self.check_limits(i) {
+                if self.limits.skip_oversized {
+                    warn!("Skipping {} bytes for {}.", skip_len, message);
+                    // left to `skip_filtered`
+                    self.skip_len = skip_len;
+                    return Err(nom::Err::Error(nom::error::Error::new(
+                        i,
+                        ErrorKind::TooLarge,
+                    )));
+                }
+                self.oversized = Some(message);
+                return Err(nom::Err::Failure(nom::error::Error::new(
+                    i,
+                    ErrorKind::TooLarge,
+                )));
+            }
             if self.heap_dump_remaining_len == 0 {
                 parse_u8(i).and_then(|(r1, tag)| {
+                    if !self.filter.contains(tag)
+                        && !matches!(
+                            tag,
+                            TAG_HEAP_DUMP | TAG_HEAP_DUMP_SEGMENT | TAG_HEAP_DUMP_END
+                        )
+                    {
+                        // left to `skip_filtered`
+                        return Err(nom::Err::Error(nom::error::Error::new(
+                            i,
+                            ErrorKind::Verify,
+                        )));
+                    }
                     match tag {
                         TAG_STRING => parse_utf8_string(r1),
                         TAG_LOAD_CLASS => parse_load_class(r1),
@@ -79,12 +237,21 @@ impl HprofRecordParser {
                             map(parse_header_record, |hr| {
                                 // record expected GC segments length
                                 self.heap_dump_remaining_len = hr.length;
-                                HeapDumpStart { length: hr.length }
+                                HeapDumpStart {
+                                    length: hr.length,
+                                    segment: tag == TAG_HEAP_DUMP_SEGMENT,
+                                }
                             })(r1)
                         }
                         x => panic!("{}", format!("unhandled record tag {}", x)),
                     }
                 })
+            } else if self.filter.skips_heap_dump() {
+                // left to `skip_filtered`
+                Err(nom::Err::Error(nom::error::Error::new(
+                    i,
+                    ErrorKind::Verify,
+                )))
             } else {
                 // GC record mode
                 parse_gc_record(i).map(|(r1, gc_sub)| {
@@ -96,12 +263,196 @@ impl HprofRecordParser {
         }
     }
 
+    // describes the next record and the bytes to skip if it declares a length above the limits,
+    // `None` until enough of it is available to tell
+    fn check_limits(&self, i: &[u8]) -> Option<(String, usize)> {
+        let limits = &self.limits;
+        if self.heap_dump_remaining_len == 0 {
+            let (r1, tag) = parse_u8(i).ok()?;
+            // the heap dump segments are not buffered, their sub-records are checked one by one
+            if matches!(tag, TAG_HEAP_DUMP | TAG_HEAP_DUMP_SEGMENT) {
+                return None;
+            }
+            let (_, header) = parse_header_record(r1).ok()?;
+            return (header.length > limits.max_record_length).then(|| {
+                (
+                    format!(
+                        "record with tag 0x{:02x} of {} bytes, above the limit of {} bytes",
+                        tag, header.length, limits.max_record_length
+                    ),
+                    9 + header.length as usize,
+                )
+            });
+        }
+        let remaining = self.heap_dump_remaining_len as u64;
+        let skip_rest_of_segment = |message: String| Some((message, remaining as usize));
+        let (r1, tag) = parse_u8(i).ok()?;
+        let (elements, data_size) = match tag {
+            TAG_GC_INSTANCE_DUMP => {
+                let (_, (_, _, _, data_size)) =
+                    tuple((parse_id, parse_u32, parse_id, parse_u32))(r1).ok()?;
+                (None, data_size as u64)
+            }
+            TAG_GC_OBJ_ARRAY_DUMP => {
+                let (_, (_, _, elements)) = tuple((parse_id, parse_u32, parse_u32))(r1).ok()?;
+                (Some(elements), elements as u64 * ID_SIZE as u64)
+            }
+            TAG_GC_PRIM_ARRAY_DUMP => {
+                let (_, (_, _, elements, element_type)) =
+                    tuple((parse_id, parse_u32, parse_u32, parse_i8))(r1).ok()?;
+                // unknown types are reported by the parser
+                let element_size = match element_type {
+                    4 | 8 => 1,
+                    5 | 9 => 2,
+                    6 | 10 => 4,
+                    7 | 11 => 8,
+                    _ => return None,
+                };
+                (Some(elements), elements as u64 * element_size)
+            }
+            _ => return None,
+        };
+        match elements {
+            Some(elements) if elements > limits.max_array_length => skip_rest_of_segment(format!(
+                "array of {} elements, above the limit of {} elements",
+                elements, limits.max_array_length
+            )),
+            _ if data_size > remaining => skip_rest_of_segment(format!(
+                "heap dump sub-record with tag 0x{:02x} of {} bytes, past the {} bytes left in its segment",
+                tag, data_size, remaining
+            )),
+            _ => None,
+        }
+    }
+
     pub fn parse_streaming<'a>(
         &mut self,
         i: &'a [u8],
         pooled_vec: &mut Vec<Record>,
     ) -> IResult<&'a [u8], ()> {
-        lazy_many1(self.parse_hprof_record(), pooled_vec)(i)
+        if self.filter == RecordFilter::ALL && !self.limits.skip_oversized {
+            return lazy_many1(self.parse_hprof_record(), pooled_vec)(i);
+        }
+        self.parse_filtered(i, |parser, rest| {
+            lazy_many1(parser.parse_hprof_record(), &mut *pooled_vec)(rest)
+        })
+    }
+
+    /// Same as `parse_streaming`, also pushing the offset of each record within `i` to `offsets`.
+    pub fn parse_streaming_with_offsets<'a>(
+        &mut self,
+        i: &'a [u8],
+        pooled_vec: &mut Vec<Record>,
+        offsets: &mut Vec<usize>,
+    ) -> IResult<&'a [u8], ()> {
+        let input_len = i.len();
+        self.parse_filtered(i, |parser, rest| {
+            let mut parse_record = parser.parse_hprof_record();
+            let offsets = &mut *offsets;
+            let parse_with_offset = move |rest: &'a [u8]| {
+                parse_record(rest).map(|(remaining, record)| {
+                    offsets.push(input_len - rest.len());
+                    (remaining, record)
+                })
+            };
+            lazy_many1(parse_with_offset, &mut *pooled_vec)(rest)
+        })
+    }
+
+    // alternates between skipping the excluded records and decoding the others with `parse`
+    fn parse_filtered<'a, F>(&mut self, mut i: &'a [u8], mut parse: F) -> IResult<&'a [u8], ()>
+    where
+        F: FnMut(&mut Self, &'a [u8]) -> IResult<&'a [u8], ()>,
+    {
+        let mut progressed = false;
+        loop {
+            let start_len = i.len();
+            match self.skip_filtered(i) {
+                Skipped::Complete(skipped) => i = &i[skipped..],
+                Skipped::Incomplete { skipped, missing } => {
+                    i = &i[skipped..];
+                    return if progressed || skipped > 0 {
+                        Ok((i, ()))
+                    } else {
+                        Err(nom::Err::Incomplete(Needed::new(missing)))
+                    };
+                }
+            }
+            progressed |= i.len() < start_len;
+            if i.is_empty() && progressed {
+                return Ok((i, ()));
+            }
+            match parse(self, i) {
+                Ok((rest, ())) => i = rest,
+                // the next record is incomplete or excluded
+                Err(nom::Err::Incomplete(_) | nom::Err::Error(_)) if progressed => {}
+                // the next record is oversized
+                Err(nom::Err::Error(_)) if self.skip_len > 0 => {}
+                Err(e) => return Err(e),
+            }
+            if i.len() == start_len && self.skip_len == 0 {
+                return Ok((i, ()));
+            }
+            progressed = true;
+        }
+    }
+
+    fn skip_filtered(&mut self, i: &[u8]) -> Skipped {
+        let mut skipped = 0;
+        loop {
+            let rest = &i[skipped..];
+            if self.skip_len > 0 {
+                let len = rest.len().min(self.skip_len);
+                self.skip_len -= len;
+                // a skipped sub-record takes the rest of its segment with it
+                self.heap_dump_remaining_len =
+                    self.heap_dump_remaining_len.saturating_sub(len as u32);
+                skipped += len;
+                if self.skip_len > 0 {
+                    return Skipped::Complete(skipped);
+                }
+                continue;
+            }
+            if self.heap_dump_remaining_len > 0 {
+                if !self.filter.skips_heap_dump() {
+                    return Skipped::Complete(skipped);
+                }
+                let len = rest.len().min(self.heap_dump_remaining_len as usize);
+                self.heap_dump_remaining_len -= len as u32;
+                skipped += len;
+                if self.heap_dump_remaining_len > 0 {
+                    return Skipped::Complete(skipped);
+                }
+                continue;
+            }
+            let Some(&tag) = rest.first() else {
+                return Skipped::Complete(skipped);
+            };
+            if self.filter.contains(tag)
+                || matches!(
+                    tag,
+                    TAG_HEAP_DUMP | TAG_HEAP_DUMP_SEGMENT | TAG_HEAP_DUMP_END
+                )
+            {
+                return Skipped::Complete(skipped);
+            }
+            // tag, timestamp and length
+            let record_len = match parse_header_record(&rest[1..]) {
+                // left to the limits check of `parse_hprof_record` instead of buffering it
+                Ok((_, header)) if header.length > self.limits.max_record_length => {
+                    return Skipped::Complete(skipped);
+                }
+                Ok((_, header)) => 9 + header.length as usize,
+                Err(_) => 9,
+            };
+            if rest.len() < record_len {
+                return Skipped::Incomplete {
+                    skipped,
+                    missing: record_len - rest.len(),
+                };
+            }
+            skipped += record_len;
+        }
     }
 }
 
@@ -149,7 +500,27 @@ where
     }
 }
 
-fn parse_gc_record(i: &[u8]) -> IResult<&[u8], GcRecord> {
+// copy of nom's count but the initial capacity is capped, the count comes from the input and may be corrupt
+fn capped_count<'a, O, F>(
+    mut f: F,
+    count: usize,
+) -> impl FnMut(&'a [u8]) -> IResult<&'a [u8], Vec<O>>
+where
+    F: Parser<&'a [u8], O, nom::error::Error<&'a [u8]>>,
+{
+    move |mut i: &'a [u8]| {
+        let mut res = Vec::with_capacity(count.min(MAX_INITIAL_CAPACITY));
+        for _ in 0..count {
+            let (rest, o) = f.parse(i)?;
+            res.push(o);
+            i = rest;
+        }
+        Ok((i, res))
+    }
+}
+
+/// Parses a single sub-record of a heap dump segment, starting with its tag.
+pub fn parse_gc_record(i: &[u8]) -> IResult<&[u8], GcRecord> {
     flat_map(parse_u8, |tag| match tag {
         TAG_GC_ROOT_UNKNOWN => parse_gc_root_unknown,
         TAG_GC_ROOT_JNI_GLOBAL => parse_gc_root_jni_global,
@@ -265,33 +636,57 @@ pub fn parse_array_value(
     number_of_elements: u32,
 ) -> impl Fn(&[u8]) -> IResult<&[u8], ArrayValue> {
     move |i| match element_type {
-        FieldType::Object => map(count(parse_u64, number_of_elements as usize), |res| {
-            ArrayValue::Object(res)
-        })(i),
-        FieldType::Bool => map(count(parse_u8, number_of_elements as usize), |res| {
-            ArrayValue::Bool(res.iter().map(|b| *b != 0).collect())
-        })(i),
-        FieldType::Char => map(count(parse_u16, number_of_elements as usize), |res| {
-            ArrayValue::Char(res)
-        })(i),
-        FieldType::Float => map(count(parse_f32, number_of_elements as usize), |res| {
-            ArrayValue::Float(res)
-        })(i),
-        FieldType::Double => map(count(parse_f64, number_of_elements as usize), |res| {
-            ArrayValue::Double(res)
-        })(i),
-        FieldType::Byte => map(count(parse_i8, number_of_elements as usize), |res| {
-            ArrayValue::Byte(res)
-        })(i),
-        FieldType::Short => map(count(parse_i16, number_of_elements as usize), |res| {
-            ArrayValue::Short(res)
-        })(i),
-        FieldType::Int => map(count(parse_i32, number_of_elements as usize), |res| {
-            ArrayValue::Int(res)
-        })(i),
-        FieldType::Long => map(count(parse_i64, number_of_elements as usize), |res| {
-            ArrayValue::Long(res)
-        })(i),
+        FieldType::Object => map(
+            parse_be_array(number_of_elements, u64::from_be_bytes),
+            ArrayValue::Object,
+        )(i),
+        FieldType::Bool => map(
+            parse_be_array(number_of_elements, |[b]: [u8; 1]| b != 0),
+            ArrayValue::Bool,
+        )(i),
+        FieldType::Char => map(
+            parse_be_array(number_of_elements, u16::from_be_bytes),
+            ArrayValue::Char,
+        )(i),
+        FieldType::Float => map(
+            parse_be_array(number_of_elements, f32::from_be_bytes),
+            ArrayValue::Float,
+        )(i),
+        FieldType::Double => map(
+            parse_be_array(number_of_elements, f64::from_be_bytes),
+            ArrayValue::Double,
+        )(i),
+        FieldType::Byte => map(
+            parse_be_array(number_of_elements, i8::from_be_bytes),
+            ArrayValue::Byte,
+        )(i),
+        FieldType::Short => map(
+            parse_be_array(number_of_elements, i16::from_be_bytes),
+            ArrayValue::Short,
+        )(i),
+        FieldType::Int => map(
+            parse_be_array(number_of_elements, i32::from_be_bytes),
+            ArrayValue::Int,
+        )(i),
+        FieldType::Long => map(
+            parse_be_array(number_of_elements, i64::from_be_bytes),
+            ArrayValue::Long,
+        )(i),
+    }
+}
+
+// decodes the whole array at once, the fixed size chunks let the compiler vectorize the byte swaps
+fn parse_be_array<const N: usize, T>(
+    number_of_elements: u32,
+    from_be_bytes: fn([u8; N]) -> T,
+) -> impl Fn(&[u8]) -> IResult<&[u8], Vec<T>> {
+    move |i| {
+        let (rest, bytes) = bytes::streaming::take(number_of_elements as usize * N)(i)?;
+        let values = bytes
+            .chunks_exact(N)
+            .map(|chunk| from_be_bytes(chunk.try_into().expect("chunks have the exact size")))
+            .collect();
+        Ok((rest, values))
     }
 }
 
@@ -301,14 +696,14 @@ fn skip_array_value(
 ) -> impl Fn(&[u8]) -> IResult<&[u8], &[u8]> {
     move |i| match element_type {
         FieldType::Object => panic!("object type in primitive array"),
-        FieldType::Bool => bytes::streaming::take(number_of_elements)(i),
-        FieldType::Char => bytes::streaming::take(number_of_elements * 2)(i),
-        FieldType::Float => bytes::streaming::take(number_of_elements * 4)(i),
-        FieldType::Double => bytes::streaming::take(number_of_elements * 8)(i),
-        FieldType::Byte => bytes::streaming::take(number_of_elements)(i),
-        FieldType::Short => bytes::streaming::take(number_of_elements * 2)(i),
-        FieldType::Int => bytes::streaming::take(number_of_elements * 4)(i),
-        FieldType::Long => bytes::streaming::take(number_of_elements * 8)(i),
+        FieldType::Bool => bytes::streaming::take(number_of_elements as usize)(i),
+        FieldType::Char => bytes::streaming::take(number_of_elements as usize * 2)(i),
+        FieldType::Float => bytes::streaming::take(number_of_elements as usize * 4)(i),
+        FieldType::Double => bytes::streaming::take(number_of_elements as usize * 8)(i),
+        FieldType::Byte => bytes::streaming::take(number_of_elements as usize)(i),
+        FieldType::Short => bytes::streaming::take(number_of_elements as usize * 2)(i),
+        FieldType::Int => bytes::streaming::take(number_of_elements as usize * 4)(i),
+        FieldType::Long => bytes::streaming::take(number_of_elements as usize * 8)(i),
     }
 }
 
@@ -364,7 +759,7 @@ fn parse_gc_class_dump(i: &[u8]) -> IResult<&[u8], GcRecord> {
             class_object_id,
             stack_trace_serial_number,
             super_class_object_id,
-            _class_loader_object_id,
+            class_loader_object_id,
             _signers_object_id,
             _protection_domain_object_id,
             _reserved_1,
@@ -388,6 +783,7 @@ fn parse_gc_class_dump(i: &[u8]) -> IResult<&[u8], GcRecord> {
                                     class_object_id,
                                     stack_trace_serial_number,
                                     super_class_object_id,
+                                    class_loader_object_id,
                                     instance_size,
                                     const_fields,
                                     static_fields,
@@ -433,7 +829,7 @@ fn parse_gc_object_array_dump(i: &[u8]) -> IResult<&[u8], GcRecord> {
         tuple((parse_id, parse_u32, parse_u32, parse_id)),
         |(object_id, stack_trace_serial_number, number_of_elements, array_class_id)| {
             map(
-                bytes::streaming::take(number_of_elements * ID_SIZE),
+                bytes::streaming::take(number_of_elements as usize * ID_SIZE as usize),
                 move |byte_array_elements: &[u8]| {
                     // Do not parse the array of object references as it is not needed for any analyses so far.
                     // see `count(parse_id, number_of_elements as usize)(byte_array_elements)`
@@ -463,7 +859,7 @@ fn parse_gc_primitive_array_dump(i: &[u8]) -> IResult<&[u8], GcRecord> {
                     stack_trace_serial_number,
                     number_of_elements,
                     element_type,
-                    bytes_ref: data_array_elements.into(),
+                    bytes_ref: Arc::from(data_array_elements),
                 },
             )
         },
@@ -544,13 +940,13 @@ fn parse_stack_frame(i: &[u8]) -> IResult<&[u8], Record> {
 fn parse_stack_trace(i: &[u8]) -> IResult<&[u8], Record> {
     flat_map(parse_header_record, |header_record| {
         // (header_record.length - (3 * parse_u32)) / id_size = (header_record.length - 12) / 8
-        let stack_frame_ids_len = (header_record.length - 12) / ID_SIZE;
+        let stack_frame_ids_len = header_record.length.saturating_sub(12) / ID_SIZE;
         map(
             tuple((
                 parse_u32,
                 parse_u32,
                 parse_u32,
-                count(parse_id, stack_frame_ids_len as usize),
+                capped_count(parse_id, stack_frame_ids_len as usize),
             )),
             |(serial_number, thread_serial_number, number_of_frames, stack_frame_ids)| {
                 StackTrace(StackTraceData {
@@ -662,7 +1058,7 @@ fn parse_allocation_sites(i: &[u8]) -> IResult<&[u8], Record> {
             number_of_sites,
         )| {
             map(
-                count(parse_allocation_site, number_of_sites as usize),
+                capped_count(parse_allocation_site, number_of_sites as usize),
                 move |allocation_sites| AllocationSites {
                     flags,
                     cutoff_ratio,
@@ -710,7 +1106,7 @@ fn parse_cpu_samples(i: &[u8]) -> IResult<&[u8], Record> {
         preceded(parse_header_record, tuple((parse_u32, parse_u32))),
         |(total_number_of_samples, number_of_traces)| {
             map(
-                count(parse_cpu_sample, total_number_of_samples as usize),
+                capped_count(parse_cpu_sample, total_number_of_samples as usize),
                 move |cpu_samples| CpuSamples {
                     total_number_of_samples,
                     number_of_traces,
@@ -720,3 +1116,179 @@ fn parse_cpu_samples(i: &[u8]) -> IResult<&[u8], Record> {
         },
     )(i)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_primitive_arrays_in_bulk() {
+        let bytes = [0x00, 0x00, 0x00, 0x01, 0xff, 0xff, 0xff, 0xfe, 0x2a];
+        let (rest, value) = parse_array_value(FieldType::Int, 2)(&bytes).unwrap();
+        assert_eq!(value, ArrayValue::Int(vec![1, -2]));
+        assert_eq!(rest, &[0x2a]);
+
+        let (_, value) = parse_array_value(FieldType::Char, 2)(&[0x00, 0x41, 0x00, 0xe9]).unwrap();
+        assert_eq!(value, ArrayValue::Char(vec![0x41, 0xe9]));
+
+        let (_, value) = parse_array_value(FieldType::Double, 1)(&1.5f64.to_be_bytes()).unwrap();
+        assert_eq!(value, ArrayValue::Double(vec![1.5]));
+
+        let (_, value) = parse_array_value(FieldType::Bool, 2)(&[0x00, 0x01]).unwrap();
+        assert_eq!(value, ArrayValue::Bool(vec![false, true]));
+    }
+
+    #[test]
+    fn parse_incomplete_primitive_array() {
+        let result = parse_array_value(FieldType::Long, 2)(&[0x00; 12]);
+        assert!(matches!(result, Err(nom::Err::Incomplete(_))));
+    }
+
+    #[test]
+    fn parse_streaming_reports_record_offsets() {
+        // heap dump segment header holding a single GC root unknown, then a truncated record
+        let mut bytes = vec![TAG_HEAP_DUMP_SEGMENT, 0, 0, 0, 0, 0, 0, 0, 9];
+        bytes.push(TAG_GC_ROOT_UNKNOWN);
+        bytes.extend_from_slice(&42u64.to_be_bytes());
+        bytes.extend_from_slice(&[TAG_STRING, 0, 0]);
+        let mut parser = HprofRecordParser::new();
+        let mut records = Vec::new();
+        let mut offsets = Vec::new();
+        let (rest, _) = parser
+            .parse_streaming_with_offsets(&bytes, &mut records, &mut offsets)
+            .unwrap();
+        assert_eq!(records.len(), 2);
+        assert_eq!(offsets, vec![0, 9]);
+        assert_eq!(rest.len(), 3);
+    }
+
+    fn utf8_string_record(id: u64, str: &str) -> Vec<u8> {
+        let mut bytes = vec![TAG_STRING, 0, 0, 0, 0];
+        bytes.extend_from_slice(&(8 + str.len() as u32).to_be_bytes());
+        bytes.extend_from_slice(&id.to_be_bytes());
+        bytes.extend_from_slice(str.as_bytes());
+        bytes
+    }
+
+    #[test]
+    fn filter_skips_excluded_records_across_buffers() {
+        let mut bytes = utf8_string_record(1, "abc");
+        // excluded stack frame, its content is never decoded
+        bytes.extend_from_slice(&[TAG_STACK_FRAME, 0, 0, 0, 0, 0, 0, 0, 5, 1, 2, 3, 4, 5]);
+        // heap dump segment holding two GC roots unknown
+        bytes.extend_from_slice(&[TAG_HEAP_DUMP_SEGMENT, 0, 0, 0, 0, 0, 0, 0, 18]);
+        for object_id in [42u64, 43] {
+            bytes.push(TAG_GC_ROOT_UNKNOWN);
+            bytes.extend_from_slice(&object_id.to_be_bytes());
+        }
+        bytes.extend_from_slice(&[TAG_HEAP_DUMP_END, 0, 0, 0, 0, 0, 0, 0, 0]);
+        bytes.extend(utf8_string_record(2, "de"));
+
+        // feeds the input in chunks like the stream parser does
+        for chunk_len in 1..=bytes.len() {
+            let mut parser = HprofRecordParser::new().with_filter(RecordFilter::STRINGS);
+            let mut records = Vec::new();
+            let mut buffer = Vec::new();
+            for chunk in bytes.chunks(chunk_len) {
+                buffer.extend_from_slice(chunk);
+                match parser.parse_streaming(&buffer, &mut records) {
+                    Ok((rest, _)) => {
+                        let processed = buffer.len() - rest.len();
+                        buffer.drain(..processed);
+                    }
+                    Err(nom::Err::Incomplete(_)) => {}
+                    Err(e) => panic!("chunks of {}: {:?}", chunk_len, e),
+                }
+            }
+            assert!(buffer.is_empty(), "chunks of {}", chunk_len);
+            let decoded: Vec<String> = records
+                .iter()
+                .map(|record| match record {
+                    Utf8String { id, str } => format!("{}:{}", id, str),
+                    HeapDumpStart { length, .. } => format!("start:{}", length),
+                    HeapDumpEnd { .. } => "end".to_string(),
+                    other => panic!("unexpected record {:?}", other),
+                })
+                .collect();
+            assert_eq!(decoded, vec!["1:abc", "start:18", "end", "2:de"]);
+        }
+    }
+
+    #[test]
+    fn oversized_record_fails_before_buffering() {
+        // string record declaring 4 GiB, only its header is available
+        let bytes = [TAG_STRING, 0, 0, 0, 0, 0xff, 0xff, 0xff, 0xff];
+        let mut parser = HprofRecordParser::new();
+        let mut records = Vec::new();
+        let result = parser.parse_streaming(&bytes, &mut records);
+        assert!(matches!(result, Err(nom::Err::Failure(_))));
+        let message = parser.take_oversized().unwrap();
+        assert!(message.contains("4294967295 bytes"), "{}", message);
+    }
+
+    #[test]
+    fn skip_oversized_records_across_buffers() {
+        let mut bytes = utf8_string_record(1, "abc");
+        bytes.extend(utf8_string_record(2, "too long"));
+        // heap dump segment with a GC root unknown and a primitive array of 3 elements
+        bytes.extend_from_slice(&[TAG_HEAP_DUMP_SEGMENT, 0, 0, 0, 0, 0, 0, 0, 30]);
+        bytes.push(TAG_GC_ROOT_UNKNOWN);
+        bytes.extend_from_slice(&42u64.to_be_bytes());
+        bytes.push(TAG_GC_PRIM_ARRAY_DUMP);
+        bytes.extend_from_slice(&43u64.to_be_bytes());
+        bytes.extend_from_slice(&0u32.to_be_bytes());
+        bytes.extend_from_slice(&3u32.to_be_bytes());
+        bytes.extend_from_slice(&[8, 1, 2, 3]);
+        bytes.extend_from_slice(&[TAG_HEAP_DUMP_END, 0, 0, 0, 0, 0, 0, 0, 0]);
+        bytes.extend(utf8_string_record(3, "de"));
+
+        let limits = RecordLimits {
+            max_record_length: 12,
+            max_array_length: 2,
+            skip_oversized: true,
+        };
+        for chunk_len in 1..=bytes.len() {
+            let mut parser = HprofRecordParser::new().with_limits(limits);
+            let mut records = Vec::new();
+            let mut buffer = Vec::new();
+            for chunk in bytes.chunks(chunk_len) {
+                buffer.extend_from_slice(chunk);
+                match parser.parse_streaming(&buffer, &mut records) {
+                    Ok((rest, _)) => {
+                        let processed = buffer.len() - rest.len();
+                        buffer.drain(..processed);
+                    }
+                    Err(nom::Err::Incomplete(_)) => {}
+                    Err(e) => panic!("chunks of {}: {:?}", chunk_len, e),
+                }
+            }
+            assert!(buffer.is_empty(), "chunks of {}", chunk_len);
+            let decoded: Vec<String> = records
+                .iter()
+                .map(|record| match record {
+                    Utf8String { id, str } => format!("{}:{}", id, str),
+                    HeapDumpStart { length, .. } => format!("start:{}", length),
+                    GcSegment(RootUnknown { object_id }) => format!("root:{}", object_id),
+                    HeapDumpEnd { .. } => "end".to_string(),
+                    other => panic!("unexpected record {:?}", other),
+                })
+                .collect();
+            assert_eq!(
+                decoded,
+                vec!["1:abc", "start:30", "root:42", "end", "3:de"],
+                "chunks of {}",
+                chunk_len
+            );
+        }
+    }
+
+    #[test]
+    fn filter_union_of_tags() {
+        let filter = RecordFilter::STRINGS | RecordFilter::THREADS;
+        assert!(filter.contains(TAG_STRING));
+        assert!(filter.contains(TAG_END_THREAD));
+        assert!(!filter.contains(TAG_LOAD_CLASS));
+        assert!(filter.skips_heap_dump());
+        assert!(!RecordFilter::ALL.skips_heap_dump());
+    }
+}
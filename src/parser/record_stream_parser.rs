@@ -1,48 +1,104 @@
+use crate::errors::HprofSlurpError;
 use crate::parser::record::Record;
-use crate::parser::record_parser::HprofRecordParser;
+use crate::parser::record_parser::{HprofRecordParser, RecordFilter, RecordLimits};
 
 use nom::Err;
 use nom::Needed::Size;
 use nom::Needed::Unknown;
 
+use crate::progress::{Phase, Progress, ThroughputTracker};
 use crate::slurp::READ_BUFFER_SIZE;
 use crossbeam_channel::{Receiver, Sender};
+use serde::{Deserialize, Serialize};
 use std::thread;
 use std::thread::JoinHandle;
 
+/// Position of the parser in the input, enough to resume the parsing from there.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StreamPosition {
+    // bytes processed including the file header
+    pub offset: usize,
+    // the parser is within a heap dump segment if not zero
+    pub heap_dump_remaining_len: u32,
+}
+
+/// Records parsed from the input up to `position`.
+pub struct RecordBatch {
+    pub records: Vec<Record>,
+    // offset of each record in the input, empty unless requested
+    pub offsets: Vec<usize>,
+    pub position: StreamPosition,
+}
+
 pub struct HprofRecordStreamParser {
     parser: HprofRecordParser,
-    file_len: usize,
+    // unknown when streaming from stdin
+    file_len: Option<usize>,
     processed_len: usize,
     loop_buffer: Vec<u8>,
     pooled_vec: Vec<Record>,
+    record_offsets: Option<Vec<usize>>,
+    // sampled beforehand to estimate the remaining time
+    expected_records: Option<u64>,
     needed: usize,
 }
 
 impl HprofRecordStreamParser {
-    pub fn new(file_len: usize, processed_len: usize, initial_loop_buffer: Vec<u8>) -> Self {
-        let parser = HprofRecordParser::new();
+    pub fn new(
+        file_len: Option<usize>,
+        start: StreamPosition,
+        initial_loop_buffer: Vec<u8>,
+    ) -> Self {
+        let parser = HprofRecordParser::resume(start.heap_dump_remaining_len);
         HprofRecordStreamParser {
             parser,
             file_len,
-            processed_len,
+            processed_len: start.offset,
             loop_buffer: initial_loop_buffer,
             pooled_vec: Vec::new(),
+            record_offsets: None,
+            expected_records: None,
             needed: 0,
         }
     }
 
+    /// Skips the records excluded by `filter` without decoding them.
+    pub fn with_record_filter(mut self, filter: RecordFilter) -> Self {
+        self.parser = self.parser.with_filter(filter);
+        self
+    }
+
+    /// Fails or skips the records declaring lengths above `limits`.
+    pub fn with_record_limits(mut self, limits: RecordLimits) -> Self {
+        self.parser = self.parser.with_limits(limits);
+        self
+    }
+
+    /// Reports the offset of each record in the batches.
+    pub fn with_record_offsets(mut self) -> Self {
+        self.record_offsets = Some(Vec::new());
+        self
+    }
+
+    /// Estimates the remaining time from the records expected in the whole input.
+    pub fn with_expected_records(mut self, expected_records: u64) -> Self {
+        self.expected_records = Some(expected_records);
+        self
+    }
+
     pub fn start(
         mut self,
         receive_data: Receiver<Vec<u8>>,
         send_pooled_data: Sender<Vec<u8>>,
-        send_progress: Sender<usize>,
+        send_progress: Sender<Progress>,
         receive_pooled_vec: Receiver<Vec<Record>>,
-        send_records: Sender<Vec<Record>>,
-    ) -> std::io::Result<JoinHandle<()>> {
+        send_records: Sender<RecordBatch>,
+    ) -> std::io::Result<JoinHandle<Result<(), HprofSlurpError>>> {
         thread::Builder::new()
             .name("hprof-parser".to_string())
             .spawn(move || {
+                let mut throughput =
+                    ThroughputTracker::new().with_expected_records(self.expected_records);
                 loop {
                     match receive_data.recv() {
                         Err(_) => break,
@@ -55,32 +111,77 @@ impl HprofRecordStreamParser {
                                 // need more data for the ongoing object
                                 continue;
                             }
-                            let iteration_res = self
-                                .parser
-                                .parse_streaming(&self.loop_buffer, &mut self.pooled_vec);
+                            let iteration_res = match &mut self.record_offsets {
+                                Some(offsets) => self.parser.parse_streaming_with_offsets(
+                                    &self.loop_buffer,
+                                    &mut self.pooled_vec,
+                                    offsets,
+                                ),
+                                None => self
+                                    .parser
+                                    .parse_streaming(&self.loop_buffer, &mut self.pooled_vec),
+                            };
                             match iteration_res {
                                 Ok((rest, _)) => {
                                     let rest_len = rest.len();
+                                    // offsets within the working buffer become offsets in the input
+                                    let offsets = match &mut self.record_offsets {
+                                        Some(offsets) => offsets
+                                            .drain(..)
+                                            .map(|offset| self.processed_len + offset)
+                                            .collect(),
+                                        None => Vec::new(),
+                                    };
                                     let iteration_processed = self.loop_buffer.len() - rest_len;
                                     self.processed_len += iteration_processed;
                                     self.loop_buffer.drain(0..iteration_processed);
-                                    assert!(
-                                        self.processed_len <= self.file_len,
-                                        "Can't process more than the file length (processed:{} vs file:{})",
-                                        self.processed_len,
-                                        self.file_len
+                                    if let Some(file_len) = self.file_len {
+                                        assert!(
+                                            self.processed_len <= file_len,
+                                            "Can't process more than the file length (processed:{} vs file:{})",
+                                            self.processed_len,
+                                            file_len
+                                        );
+                                    }
+                                    throughput.record(
+                                        self.pooled_vec.len() as u64,
+                                        iteration_processed as u64,
                                     );
+                                    let eta = self.file_len.and_then(|len| {
+                                        throughput.eta((len - self.processed_len) as u64)
+                                    });
+                                    // progress is optional for the consumer
+                                    let total = self.file_len.map(|len| len as u64);
                                     send_progress
-                                        .send(self.processed_len)
-                                        .expect("channel should not be closed");
-                                    let mut next_pooled_vec = receive_pooled_vec
-                                        .recv()
-                                        .expect("channel should not be closed");
+                                        .send(
+                                            Progress::new(
+                                                Phase::Parsing,
+                                                self.processed_len as u64,
+                                                total,
+                                            )
+                                            .with_estimate(throughput.throughput(), eta),
+                                        )
+                                        .unwrap_or_default();
+                                    // the consumer closing its channels stops the parsing early
+                                    let mut next_pooled_vec = match receive_pooled_vec.recv() {
+                                        Ok(pooled_vec) => pooled_vec,
+                                        Err(_) => break,
+                                    };
                                     // next_pooled_vec contains the records result after the swap
                                     std::mem::swap(&mut next_pooled_vec, &mut self.pooled_vec);
-                                    send_records
-                                        .send(next_pooled_vec)
-                                        .expect("channel should not be closed");
+                                    let batch = RecordBatch {
+                                        records: next_pooled_vec,
+                                        offsets,
+                                        position: StreamPosition {
+                                            offset: self.processed_len,
+                                            heap_dump_remaining_len: self
+                                                .parser
+                                                .heap_dump_remaining_len(),
+                                        },
+                                    };
+                                    if send_records.send(batch).is_err() {
+                                        break;
+                                    }
                                     if self.needed > 0 {
                                         // Multi-buffer object successfully parsed
                                         // Do not hold on too much working memory
@@ -97,6 +198,14 @@ impl HprofRecordStreamParser {
                                     panic!("Unexpected Incomplete with unknown size")
                                 }
                                 Err(Err::Failure(e)) => {
+                                    let offset =
+                                        self.processed_len + self.loop_buffer.len() - e.input.len();
+                                    if let Some(message) = self.parser.take_oversized() {
+                                        return Err(HprofSlurpError::OversizedRecord {
+                                            offset,
+                                            message,
+                                        });
+                                    }
                                     panic!("parsing failed with {:?}", e)
                                 }
                                 Err(Err::Error(e)) => {
@@ -106,6 +215,7 @@ impl HprofRecordStreamParser {
                         }
                     }
                 }
+                Ok(())
             })
     }
 }
@@ -0,0 +1,301 @@
+use std::ops::ControlFlow;
+
+use crate::parser::gc_record::{ClassDumpFields, FieldType, GcRecord};
+use crate::parser::record::{LoadClassData, Record, StackFrameData, StackTraceData};
+use crate::parser::record_stream_parser::StreamPosition;
+
+/// Receives the records of the stream parser one after the other, for custom aggregations without building a `Heap`.
+///
+/// All methods do nothing by default, implement the ones of interest. `visit_record` dispatches each record to
+/// the other methods, the built-in `ResultRecorder` overrides it to take the payloads out of the records.
+pub trait RecordVisitor {
+    fn visit_string(&mut self, _id: u64, _text: &str) {}
+
+    fn visit_class(&mut self, _class: &LoadClassData) {}
+
+    fn visit_stack_frame(&mut self, _frame: &StackFrameData) {}
+
+    fn visit_stack_trace(&mut self, _trace: &StackTraceData) {}
+
+    fn visit_class_dump(&mut self, _class: &ClassDumpFields) {}
+
+    /// `fields` holds the raw field values, laid out class after class up the hierarchy.
+    fn visit_instance(&mut self, _object_id: u64, _class_object_id: u64, _fields: &[u8]) {}
+
+    /// `elements` holds the raw object ids.
+    fn visit_object_array(&mut self, _object_id: u64, _array_class_id: u64, _elements: &[u8]) {}
+
+    fn visit_primitive_array(
+        &mut self,
+        _object_id: u64,
+        _element_type: FieldType,
+        _elements: &[u8],
+    ) {
+    }
+
+    /// Called for the garbage collection roots of the heap dumps.
+    fn visit_gc_root(&mut self, _root: &GcRecord) {}
+
+    /// Called for the records without a method of their own, e.g. threads and heap dump boundaries.
+    fn visit_other(&mut self, _record: &Record) {}
+
+    /// `offset` is the position of the record in the input, only known with `SlurpOptions::keep_record_offsets`.
+    fn visit_record(&mut self, record: &mut Record, _offset: Option<usize>) {
+        match record {
+            Record::Utf8String { id, str } => self.visit_string(*id, str),
+            Record::LoadClass(class) => self.visit_class(class),
+            Record::StackFrame(frame) => self.visit_stack_frame(frame),
+            Record::StackTrace(trace) => self.visit_stack_trace(trace),
+            Record::GcSegment(gc_record) => match gc_record {
+                GcRecord::ClassDump(class) => self.visit_class_dump(class),
+                GcRecord::InstanceDump {
+                    object_id,
+                    class_object_id,
+                    bytes_ref,
+                    ..
+                } => self.visit_instance(*object_id, *class_object_id, bytes_ref),
+                GcRecord::ObjectArrayDump {
+                    object_id,
+                    array_class_id,
+                    bytes_ref,
+                    ..
+                } => self.visit_object_array(*object_id, *array_class_id, bytes_ref),
+                GcRecord::PrimitiveArrayDump {
+                    object_id,
+                    element_type,
+                    bytes_ref,
+                    ..
+                } => self.visit_primitive_array(*object_id, *element_type, bytes_ref),
+                root => self.visit_gc_root(root),
+            },
+            other => self.visit_other(other),
+        }
+    }
+
+    /// Called after each batch of records, returning `ControlFlow::Break` stops reading the input.
+    fn visit_batch_end(&mut self, _position: StreamPosition) -> ControlFlow<()> {
+        ControlFlow::Continue(())
+    }
+
+    /// True if `visit_record` takes the payloads (strings, field bytes...) out of the records, leaving
+    /// empty ones behind for the visitors chained with it.
+    fn takes_payloads(&self) -> bool {
+        false
+    }
+
+    /// Feeds the same records to both visitors in a single pass over the input, chains can be chained again.
+    ///
+    /// The visitor taking the payloads, if any, sees the records last. Panics if both take them.
+    fn chain<V: RecordVisitor>(self, other: V) -> RecordChain<Self, V>
+    where
+        Self: Sized,
+    {
+        assert!(
+            !(self.takes_payloads() && other.takes_payloads()),
+            "at most one chained visitor can take the payloads of the records"
+        );
+        RecordChain {
+            first: self,
+            second: other,
+            first_done: false,
+            second_done: false,
+        }
+    }
+}
+
+impl<V: RecordVisitor + ?Sized> RecordVisitor for &mut V {
+    fn visit_record(&mut self, record: &mut Record, offset: Option<usize>) {
+        (**self).visit_record(record, offset)
+    }
+
+    fn visit_batch_end(&mut self, position: StreamPosition) -> ControlFlow<()> {
+        (**self).visit_batch_end(position)
+    }
+
+    fn takes_payloads(&self) -> bool {
+        (**self).takes_payloads()
+    }
+}
+
+impl<V: RecordVisitor + ?Sized> RecordVisitor for Box<V> {
+    fn visit_record(&mut self, record: &mut Record, offset: Option<usize>) {
+        (**self).visit_record(record, offset)
+    }
+
+    fn visit_batch_end(&mut self, position: StreamPosition) -> ControlFlow<()> {
+        (**self).visit_batch_end(position)
+    }
+
+    fn takes_payloads(&self) -> bool {
+        (**self).takes_payloads()
+    }
+}
+
+/// Two visitors fed the same records, see `RecordVisitor::chain`.
+///
+/// A visitor stopping early no longer sees records, the input is read until both stopped.
+pub struct RecordChain<A, B> {
+    first: A,
+    second: B,
+    first_done: bool,
+    second_done: bool,
+}
+
+impl<A, B> RecordChain<A, B> {
+    pub fn into_parts(self) -> (A, B) {
+        (self.first, self.second)
+    }
+}
+
+impl<A: RecordVisitor, B: RecordVisitor> RecordVisitor for RecordChain<A, B> {
+    fn visit_record(&mut self, record: &mut Record, offset: Option<usize>) {
+        if self.first.takes_payloads() {
+            if !self.second_done {
+                self.second.visit_record(record, offset);
+            }
+            if !self.first_done {
+                self.first.visit_record(record, offset);
+            }
+        } else {
+            if !self.first_done {
+                self.first.visit_record(record, offset);
+            }
+            if !self.second_done {
+                self.second.visit_record(record, offset);
+            }
+        }
+    }
+
+    fn visit_batch_end(&mut self, position: StreamPosition) -> ControlFlow<()> {
+        if !self.first_done {
+            self.first_done = self.first.visit_batch_end(position).is_break();
+        }
+        if !self.second_done {
+            self.second_done = self.second.visit_batch_end(position).is_break();
+        }
+        if self.first_done && self.second_done {
+            ControlFlow::Break(())
+        } else {
+            ControlFlow::Continue(())
+        }
+    }
+
+    fn takes_payloads(&self) -> bool {
+        self.first.takes_payloads() || self.second.takes_payloads()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Default)]
+    struct Counter {
+        strings: Vec<String>,
+        instance_bytes: usize,
+        others: usize,
+    }
+
+    impl RecordVisitor for Counter {
+        fn visit_string(&mut self, _id: u64, text: &str) {
+            self.strings.push(text.to_string());
+        }
+
+        fn visit_instance(&mut self, _object_id: u64, _class_object_id: u64, fields: &[u8]) {
+            self.instance_bytes += fields.len();
+        }
+
+        fn visit_other(&mut self, _record: &Record) {
+            self.others += 1;
+        }
+    }
+
+    #[test]
+    fn dispatch_records() {
+        let mut records = vec![
+            Record::Utf8String {
+                id: 1,
+                str: "hello".into(),
+            },
+            Record::GcSegment(GcRecord::InstanceDump {
+                object_id: 2,
+                stack_trace_serial_number: 0,
+                class_object_id: 3,
+                data_size: 4,
+                bytes_ref: vec![0; 4].into_boxed_slice(),
+            }),
+            Record::GcSegment(GcRecord::RootUnknown { object_id: 2 }),
+            Record::EndThread {
+                thread_serial_number: 1,
+            },
+        ];
+        let mut counter = Counter::default();
+        for record in &mut records {
+            counter.visit_record(record, None);
+        }
+        assert_eq!(counter.strings, vec!["hello"]);
+        assert_eq!(counter.instance_bytes, 4);
+        assert_eq!(counter.others, 1);
+    }
+
+    // empties the strings like the result recorder
+    #[derive(Default)]
+    struct Taker {
+        strings: Vec<String>,
+    }
+
+    impl RecordVisitor for Taker {
+        fn visit_record(&mut self, record: &mut Record, _offset: Option<usize>) {
+            if let Record::Utf8String { str, .. } = record {
+                self.strings.push(std::mem::take(str).into());
+            }
+        }
+
+        fn takes_payloads(&self) -> bool {
+            true
+        }
+    }
+
+    // stops after the first batch
+    struct Once(usize);
+
+    impl RecordVisitor for Once {
+        fn visit_string(&mut self, _id: u64, _text: &str) {
+            self.0 += 1;
+        }
+
+        fn visit_batch_end(&mut self, _position: StreamPosition) -> ControlFlow<()> {
+            ControlFlow::Break(())
+        }
+    }
+
+    #[test]
+    fn chained_visitors() {
+        let position = StreamPosition {
+            offset: 0,
+            heap_dump_remaining_len: 0,
+        };
+        let mut chain = Taker::default().chain(Counter::default()).chain(Once(0));
+        assert!(chain.takes_payloads());
+        for _ in 0..2 {
+            let mut record = Record::Utf8String {
+                id: 1,
+                str: "hello".into(),
+            };
+            chain.visit_record(&mut record, None);
+            assert!(chain.visit_batch_end(position).is_continue());
+        }
+        let (first, once) = chain.into_parts();
+        let (taker, counter) = first.into_parts();
+        // the counter saw the strings before the taker emptied them
+        assert_eq!(taker.strings, vec!["hello", "hello"]);
+        assert_eq!(counter.strings, vec!["hello", "hello"]);
+        assert_eq!(once.0, 1);
+    }
+
+    #[test]
+    #[should_panic(expected = "at most one chained visitor")]
+    fn two_visitors_taking_payloads() {
+        let _ = Taker::default().chain(Taker::default());
+    }
+}
@@ -1,23 +1,20 @@
+use crate::progress::{Phase, Progress};
 use crossbeam_channel::{Receiver, Sender};
 use std::fs::File;
-use std::io::{BufReader, Read};
+use std::io::{ErrorKind, Read};
 use std::thread;
 use std::thread::JoinHandle;
 
-pub struct PrefetchReader {
-    reader: BufReader<File>,
-    file_len: usize,
+pub struct PrefetchReader<R> {
+    reader: R,
+    // unknown for streamed inputs such as stdin, read until EOF
+    file_len: Option<usize>,
     processed_len: usize,
     read_size: usize,
 }
 
-impl PrefetchReader {
-    pub fn new(
-        reader: BufReader<File>,
-        file_len: usize,
-        processed_len: usize,
-        read_size: usize,
-    ) -> Self {
+impl<R: Read + Send + 'static> PrefetchReader<R> {
+    pub fn new(reader: R, file_len: Option<usize>, processed_len: usize, read_size: usize) -> Self {
         PrefetchReader {
             reader,
             file_len,
@@ -30,43 +27,335 @@ impl PrefetchReader {
         mut self,
         send_data: Sender<Vec<u8>>,
         receive_pooled_data: Receiver<Vec<u8>>,
+        send_progress: Sender<Progress>,
     ) -> std::io::Result<JoinHandle<()>> {
         thread::Builder::new()
             .name("hprof-prefetch".to_string())
-            .spawn(move || {
-                while self.processed_len != self.file_len {
-                    let remaining = self.file_len - self.processed_len;
-                    let next_size = if remaining > self.read_size {
-                        self.read_size
-                    } else {
-                        remaining
-                    };
-                    let mut pooled_buffer = receive_pooled_data
-                        .recv()
-                        .expect("channel should not be closed");
-                    pooled_buffer.resize(next_size, 0);
-                    self.reader
-                        .read_exact(&mut pooled_buffer)
-                        .unwrap_or_else(|e| {
-                            panic!(
-                                "Fail to read buffer for incomplete input:\n
-                                error->{}\n
-                                next->{}\n
-                                processed->{}\n
-                                file_len->{}\n
-                                remaining->{}",
-                                e,
-                                next_size,
-                                self.processed_len,
-                                self.file_len,
-                                self.file_len - self.processed_len
-                            )
-                        });
-                    send_data
-                        .send(pooled_buffer)
-                        .expect("Channel should not be closed");
-                    self.processed_len += next_size
+            .spawn(move || loop {
+                let next_size = match self.file_len {
+                    Some(file_len) if self.processed_len == file_len => break,
+                    Some(file_len) => self.read_size.min(file_len - self.processed_len),
+                    None => self.read_size,
+                };
+                // the parser stopped early
+                let mut pooled_buffer = match receive_pooled_data.recv() {
+                    Ok(pooled_buffer) => pooled_buffer,
+                    Err(_) => break,
+                };
+                pooled_buffer.resize(next_size, 0);
+                match self.file_len {
+                    Some(file_len) => {
+                        self.reader
+                            .read_exact(&mut pooled_buffer)
+                            .unwrap_or_else(|e| {
+                                panic!(
+                                    "Fail to read buffer for incomplete input:\n
+                                    error->{}\n
+                                    next->{}\n
+                                    processed->{}\n
+                                    file_len->{}\n
+                                    remaining->{}",
+                                    e,
+                                    next_size,
+                                    self.processed_len,
+                                    file_len,
+                                    file_len - self.processed_len
+                                )
+                            });
+                    }
+                    None => {
+                        let read = fill_buffer(&mut self.reader, &mut pooled_buffer)
+                            .unwrap_or_else(|e| {
+                                panic!(
+                                    "Fail to read input stream after {} bytes: {}",
+                                    self.processed_len, e
+                                )
+                            });
+                        if read == 0 {
+                            break;
+                        }
+                        pooled_buffer.truncate(read);
+                    }
                 }
+                let read = pooled_buffer.len();
+                if send_data.send(pooled_buffer).is_err() {
+                    break;
+                }
+                self.processed_len += read;
+                // progress is optional for the consumer
+                let total = self.file_len.map(|len| len as u64);
+                send_progress
+                    .send(Progress::new(
+                        Phase::Reading,
+                        self.processed_len as u64,
+                        total,
+                    ))
+                    .unwrap_or_default();
             })
     }
 }
+
+/// Hints the OS that the file is read sequentially, enlarging its readahead window.
+///
+/// Failures are ignored as the hint only affects the throughput.
+#[cfg(any(target_os = "linux", target_os = "android", target_os = "freebsd"))]
+pub fn advise_sequential(file: &File) {
+    use std::os::unix::io::AsRawFd;
+    // SAFETY: the descriptor stays open for the duration of the call
+    unsafe {
+        libc::posix_fadvise(file.as_raw_fd(), 0, 0, libc::POSIX_FADV_SEQUENTIAL);
+    }
+}
+
+#[cfg(any(target_os = "macos", target_os = "ios"))]
+pub fn advise_sequential(file: &File) {
+    use std::os::unix::io::AsRawFd;
+    // SAFETY: the descriptor stays open for the duration of the call
+    unsafe {
+        libc::fcntl(file.as_raw_fd(), libc::F_RDAHEAD, 1);
+    }
+}
+
+#[cfg(not(any(
+    target_os = "linux",
+    target_os = "android",
+    target_os = "freebsd",
+    target_os = "macos",
+    target_os = "ios"
+)))]
+pub fn advise_sequential(_file: &File) {}
+
+#[cfg(target_os = "linux")]
+const DIRECT_IO_ALIGNMENT: usize = 4096;
+
+#[cfg(target_os = "linux")]
+const DIRECT_IO_BUFFER_SIZE: usize = 8 * 1024 * 1024;
+
+// `O_DIRECT` requires the buffer address, file offset and read length to be block aligned
+#[cfg(target_os = "linux")]
+struct AlignedBuffer {
+    ptr: std::ptr::NonNull<u8>,
+    layout: std::alloc::Layout,
+}
+
+#[cfg(target_os = "linux")]
+impl AlignedBuffer {
+    fn new(size: usize) -> Self {
+        let layout = std::alloc::Layout::from_size_align(size, DIRECT_IO_ALIGNMENT)
+            .expect("valid direct I/O buffer layout");
+        // SAFETY: the layout has a non-zero size
+        let ptr = unsafe { std::alloc::alloc_zeroed(layout) };
+        let ptr =
+            std::ptr::NonNull::new(ptr).unwrap_or_else(|| std::alloc::handle_alloc_error(layout));
+        AlignedBuffer { ptr, layout }
+    }
+
+    fn as_slice(&self) -> &[u8] {
+        // SAFETY: the allocation is initialized and lives as long as `self`
+        unsafe { std::slice::from_raw_parts(self.ptr.as_ptr(), self.layout.size()) }
+    }
+
+    fn as_mut_slice(&mut self) -> &mut [u8] {
+        // SAFETY: the allocation is initialized and uniquely borrowed through `self`
+        unsafe { std::slice::from_raw_parts_mut(self.ptr.as_ptr(), self.layout.size()) }
+    }
+}
+
+#[cfg(target_os = "linux")]
+impl Drop for AlignedBuffer {
+    fn drop(&mut self) {
+        // SAFETY: allocated with the same layout in `new`
+        unsafe { std::alloc::dealloc(self.ptr.as_ptr(), self.layout) }
+    }
+}
+
+// SAFETY: the buffer owns its allocation
+#[cfg(target_os = "linux")]
+unsafe impl Send for AlignedBuffer {}
+
+/// Reads a file with `O_DIRECT`, bypassing the page cache to not evict the data of other processes.
+#[cfg(target_os = "linux")]
+pub struct DirectFileReader {
+    file: File,
+    buffer: AlignedBuffer,
+    // unread bytes of `buffer`
+    start: usize,
+    end: usize,
+    eof: bool,
+}
+
+#[cfg(target_os = "linux")]
+impl DirectFileReader {
+    /// Fails if the filesystem does not support direct I/O (e.g. tmpfs).
+    pub fn open<P: AsRef<std::path::Path>>(path: P) -> std::io::Result<Self> {
+        use std::os::unix::fs::OpenOptionsExt;
+        let file = std::fs::OpenOptions::new()
+            .read(true)
+            .custom_flags(libc::O_DIRECT)
+            .open(path)?;
+        Ok(DirectFileReader {
+            file,
+            buffer: AlignedBuffer::new(DIRECT_IO_BUFFER_SIZE),
+            start: 0,
+            end: 0,
+            eof: false,
+        })
+    }
+
+    pub fn file_len(&self) -> std::io::Result<u64> {
+        Ok(self.file.metadata()?.len())
+    }
+
+    fn refill(&mut self) -> std::io::Result<()> {
+        self.start = 0;
+        self.end = 0;
+        loop {
+            match self.file.read(self.buffer.as_mut_slice()) {
+                Ok(read) => {
+                    // the next offset would not be aligned after a partial block, only the file end has one
+                    if read == 0 || read % DIRECT_IO_ALIGNMENT != 0 {
+                        self.eof = true;
+                    }
+                    self.end = read;
+                    return Ok(());
+                }
+                Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e),
+            }
+        }
+    }
+}
+
+#[cfg(target_os = "linux")]
+impl Read for DirectFileReader {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        if self.start == self.end {
+            if self.eof {
+                return Ok(0);
+            }
+            self.refill()?;
+        }
+        let read = buf.len().min(self.end - self.start);
+        buf[..read].copy_from_slice(&self.buffer.as_slice()[self.start..self.start + read]);
+        self.start += read;
+        Ok(read)
+    }
+}
+
+// pipes return short reads, fill the buffer until EOF
+fn fill_buffer<R: Read>(reader: &mut R, buffer: &mut [u8]) -> std::io::Result<usize> {
+    let mut filled = 0;
+    while filled < buffer.len() {
+        match reader.read(&mut buffer[filled..]) {
+            Ok(0) => break,
+            Ok(read) => filled += read,
+            Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(filled)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // returns at most `chunk` bytes per read and is interrupted before the first one, like a pipe
+    struct ShortReads {
+        data: Vec<u8>,
+        position: usize,
+        chunk: usize,
+        interrupted: bool,
+    }
+
+    impl ShortReads {
+        fn new(data: Vec<u8>, chunk: usize) -> Self {
+            ShortReads {
+                data,
+                position: 0,
+                chunk,
+                interrupted: false,
+            }
+        }
+    }
+
+    impl Read for ShortReads {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            if !self.interrupted {
+                self.interrupted = true;
+                return Err(ErrorKind::Interrupted.into());
+            }
+            let read = buf
+                .len()
+                .min(self.chunk)
+                .min(self.data.len() - self.position);
+            buf[..read].copy_from_slice(&self.data[self.position..self.position + read]);
+            self.position += read;
+            Ok(read)
+        }
+    }
+
+    #[test]
+    fn fill_buffer_across_short_reads() {
+        let data: Vec<u8> = (0..25).collect();
+        let mut reader = ShortReads::new(data.clone(), 3);
+        let mut buffer = vec![0; 10];
+        assert_eq!(fill_buffer(&mut reader, &mut buffer).unwrap(), 10);
+        assert_eq!(buffer, data[..10]);
+        assert_eq!(fill_buffer(&mut reader, &mut buffer).unwrap(), 10);
+        // the last buffer is partially filled at EOF
+        assert_eq!(fill_buffer(&mut reader, &mut buffer).unwrap(), 5);
+        assert_eq!(buffer[..5], data[20..]);
+        assert_eq!(fill_buffer(&mut reader, &mut buffer).unwrap(), 0);
+    }
+
+    #[test]
+    fn streamed_input_read_until_eof() {
+        let data: Vec<u8> = (0..25).collect();
+        let (send_data, receive_data) = crossbeam_channel::unbounded();
+        let (send_pooled_data, receive_pooled_data) = crossbeam_channel::unbounded();
+        let (send_progress, receive_progress) = crossbeam_channel::unbounded();
+        for _ in 0..2 {
+            send_pooled_data.send(Vec::new()).unwrap();
+        }
+        let handle = PrefetchReader::new(ShortReads::new(data.clone(), 3), None, 0, 10)
+            .start(send_data, receive_pooled_data, send_progress)
+            .unwrap();
+
+        let mut read = Vec::new();
+        let mut buffer_lengths = Vec::new();
+        for buffer in receive_data.iter() {
+            buffer_lengths.push(buffer.len());
+            read.extend_from_slice(&buffer);
+            send_pooled_data.send(buffer).unwrap();
+        }
+        handle.join().unwrap();
+        assert_eq!(buffer_lengths, vec![10, 10, 5]);
+        assert_eq!(read, data);
+        let last = receive_progress.try_iter().last().unwrap();
+        assert_eq!((last.done, last.total), (25, None));
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn direct_file_reader_reads_unaligned_length() {
+        let path = std::env::temp_dir().join(format!("hprof-slurp-direct-{}", std::process::id()));
+        let data: Vec<u8> = (0..DIRECT_IO_BUFFER_SIZE + 3 * DIRECT_IO_ALIGNMENT + 17)
+            .map(|i| (i % 251) as u8)
+            .collect();
+        std::fs::write(&path, &data).unwrap();
+        let reader = DirectFileReader::open(&path);
+        let mut read = Vec::new();
+        let result = reader.map(|mut reader| {
+            assert_eq!(reader.file_len().unwrap(), data.len() as u64);
+            reader.read_to_end(&mut read)
+        });
+        std::fs::remove_file(&path).unwrap();
+        match result {
+            Ok(Ok(_)) => assert_eq!(read, data),
+            // the temporary directory does not support direct I/O
+            Err(e) if e.kind() == ErrorKind::InvalidInput => {}
+            Ok(Err(e)) | Err(e) => panic!("direct read failed: {}", e),
+        }
+    }
+}
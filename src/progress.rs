@@ -0,0 +1,430 @@
+use std::collections::VecDeque;
+#[cfg(feature = "cli")]
+use std::io::IsTerminal;
+#[cfg(feature = "cli")]
+use std::sync::OnceLock;
+use std::time::{Duration, Instant};
+
+use crossbeam_channel::Sender;
+#[cfg(feature = "cli")]
+use indicatif::{HumanDuration, MultiProgress, ProgressBar, ProgressDrawTarget, ProgressStyle};
+
+#[cfg(feature = "cli")]
+use crate::utils::pretty_count;
+
+/// The stages of building and analyzing a `Heap`, the first three run concurrently.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Phase {
+    // bytes read from the input
+    Reading,
+    // bytes turned into records
+    Parsing,
+    // bytes of records stored by the recorder
+    Recording,
+    // instances and arrays decoded into the `Heap`
+    Materializing,
+    // graph passes over the objects
+    Analyzing,
+}
+
+impl Phase {
+    pub const ALL: [Phase; 5] = [
+        Phase::Reading,
+        Phase::Parsing,
+        Phase::Recording,
+        Phase::Materializing,
+        Phase::Analyzing,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Phase::Reading => "reading",
+            Phase::Parsing => "parsing",
+            Phase::Recording => "recording",
+            Phase::Materializing => "materializing",
+            Phase::Analyzing => "analyzing",
+        }
+    }
+
+    // the streaming phases count bytes, the later ones count objects
+    #[cfg(feature = "cli")]
+    fn counts_bytes(self) -> bool {
+        matches!(self, Phase::Reading | Phase::Parsing | Phase::Recording)
+    }
+}
+
+/// Progress message: `done` units out of `total` within a phase.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Progress {
+    pub phase: Phase,
+    pub done: u64,
+    // unknown when streaming from stdin or decompressing
+    pub total: Option<u64>,
+    // only measured while parsing
+    pub throughput: Option<Throughput>,
+    // remaining time of the phase, unknown without a total
+    pub eta: Option<Duration>,
+}
+
+/// Rates measured over the latest parsed buffers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Throughput {
+    pub bytes_per_sec: u64,
+    pub records_per_sec: u64,
+}
+
+impl Progress {
+    pub fn new(phase: Phase, done: u64, total: Option<u64>) -> Self {
+        Progress {
+            phase,
+            done,
+            total,
+            throughput: None,
+            eta: None,
+        }
+    }
+
+    pub fn with_estimate(mut self, throughput: Option<Throughput>, eta: Option<Duration>) -> Self {
+        self.throughput = throughput;
+        self.eta = eta;
+        self
+    }
+
+    pub fn percentage(&self) -> Option<f64> {
+        match self.total {
+            Some(0) => Some(100.0),
+            Some(total) => Some((self.done as f64 * 100.0 / total as f64).min(100.0)),
+            None => None,
+        }
+    }
+}
+
+// parsed buffers kept to measure the throughput
+const THROUGHPUT_WINDOW: usize = 16;
+
+/// Estimates the remaining parsing time from the latest parsed buffers.
+///
+/// The time of a buffer is modelled as a cost per record plus a cost per byte, the remaining bytes are
+/// expected to hold records at the density seen so far. Regions of small records (strings) and of
+/// large arrays then weigh on the estimate according to their share of the dump.
+///
+/// A record count sampled across the dump beforehand replaces the density for the remaining records.
+pub(crate) struct ThroughputTracker {
+    last: Instant,
+    // (seconds, records, bytes) per parsed buffer, latest last
+    window: VecDeque<(f64, f64, f64)>,
+    total_records: u64,
+    total_bytes: u64,
+    expected_records: Option<u64>,
+}
+
+impl ThroughputTracker {
+    pub fn new() -> Self {
+        ThroughputTracker {
+            last: Instant::now(),
+            window: VecDeque::with_capacity(THROUGHPUT_WINDOW),
+            total_records: 0,
+            total_bytes: 0,
+            expected_records: None,
+        }
+    }
+
+    pub fn with_expected_records(mut self, expected_records: Option<u64>) -> Self {
+        self.expected_records = expected_records;
+        self
+    }
+
+    /// Records a buffer parsed since the previous one.
+    pub fn record(&mut self, records: u64, bytes: u64) {
+        let now = Instant::now();
+        self.record_interval(now.duration_since(self.last), records, bytes);
+        self.last = now;
+    }
+
+    fn record_interval(&mut self, elapsed: Duration, records: u64, bytes: u64) {
+        if self.window.len() == THROUGHPUT_WINDOW {
+            self.window.pop_front();
+        }
+        self.window
+            .push_back((elapsed.as_secs_f64(), records as f64, bytes as f64));
+        self.total_records += records;
+        self.total_bytes += bytes;
+    }
+
+    pub fn throughput(&self) -> Option<Throughput> {
+        let (seconds, records, bytes) = self
+            .window
+            .iter()
+            .fold((0.0, 0.0, 0.0), |(t, r, b), (dt, dr, db)| {
+                (t + dt, r + dr, b + db)
+            });
+        if seconds <= 0.0 {
+            return None;
+        }
+        Some(Throughput {
+            bytes_per_sec: (bytes / seconds) as u64,
+            records_per_sec: (records / seconds) as u64,
+        })
+    }
+
+    pub fn eta(&self, remaining_bytes: u64) -> Option<Duration> {
+        if self.total_bytes == 0 {
+            return None;
+        }
+        let (per_record, per_byte) = self.costs()?;
+        let remaining_bytes = remaining_bytes as f64;
+        let remaining_records = match self.expected_records {
+            Some(expected) if expected > self.total_records => {
+                (expected - self.total_records) as f64
+            }
+            // the sampled count fell short
+            _ => remaining_bytes * self.total_records as f64 / self.total_bytes as f64,
+        };
+        let seconds = per_record * remaining_records + per_byte * remaining_bytes;
+        Duration::try_from_secs_f64(seconds).ok()
+    }
+
+    // least squares fit of `seconds = per_record * records + per_byte * bytes` over the window
+    fn costs(&self) -> Option<(f64, f64)> {
+        let (mut rr, mut rb, mut bb, mut tr, mut tb) = (0.0, 0.0, 0.0, 0.0, 0.0);
+        for (t, r, b) in &self.window {
+            rr += r * r;
+            rb += r * b;
+            bb += b * b;
+            tr += t * r;
+            tb += t * b;
+        }
+        if bb <= 0.0 {
+            return None;
+        }
+        let bytes_only = (0.0, tb / bb);
+        let det = rr * bb - rb * rb;
+        // a constant record density cannot tell the two costs apart
+        if det <= 1e-9 * rr * bb {
+            return Some(bytes_only);
+        }
+        let per_record = (tr * bb - tb * rb) / det;
+        let per_byte = (tb * rr - tr * rb) / det;
+        match (per_record >= 0.0, per_byte >= 0.0) {
+            (true, true) => Some((per_record, per_byte)),
+            (false, _) => Some(bytes_only),
+            (true, false) => Some((tr / rr, 0.0)),
+        }
+    }
+}
+
+/// When to draw the progress bars on stderr, they are never drawn without the `cli` feature.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ProgressMode {
+    // only when stderr is a terminal, keeps the logs of cron and CI jobs free of redraws
+    #[default]
+    Auto,
+    Always,
+    Never,
+}
+
+impl ProgressMode {
+    // whether the bars are drawn
+    pub(crate) fn is_visible(self) -> bool {
+        #[cfg(feature = "cli")]
+        {
+            match self {
+                ProgressMode::Always => true,
+                ProgressMode::Never => false,
+                ProgressMode::Auto => std::io::stderr().is_terminal(),
+            }
+        }
+        #[cfg(not(feature = "cli"))]
+        {
+            false
+        }
+    }
+}
+
+/// Draws one bar per phase on stderr and forwards the progress to an optional listener.
+///
+/// Reporting is thread safe, the bar of a phase appears with its first report.
+pub struct ProgressBars {
+    #[cfg(feature = "cli")]
+    bars: MultiProgress,
+    #[cfg(feature = "cli")]
+    by_phase: [OnceLock<ProgressBar>; 5],
+    listener: Option<Sender<Progress>>,
+}
+
+impl ProgressBars {
+    #[cfg(feature = "cli")]
+    pub fn new(mode: ProgressMode, listener: Option<Sender<Progress>>) -> Self {
+        let target = if mode.is_visible() {
+            ProgressDrawTarget::stderr()
+        } else {
+            ProgressDrawTarget::hidden()
+        };
+        ProgressBars {
+            bars: MultiProgress::with_draw_target(target),
+            by_phase: Default::default(),
+            listener,
+        }
+    }
+
+    // only the listener is notified
+    #[cfg(not(feature = "cli"))]
+    pub fn new(_mode: ProgressMode, listener: Option<Sender<Progress>>) -> Self {
+        ProgressBars { listener }
+    }
+
+    pub fn report(&self, progress: Progress) {
+        #[cfg(feature = "cli")]
+        {
+            let bar = self.by_phase[progress.phase as usize]
+                .get_or_init(|| self.bars.add(phase_bar(progress.phase, progress.total)));
+            bar.set_position(progress.done);
+            if let Some(throughput) = progress.throughput {
+                bar.set_message(estimate_message(throughput, progress.eta));
+            }
+        }
+        if let Some(listener) = &self.listener {
+            // the listener may stop listening
+            listener.send(progress).unwrap_or_default();
+        }
+    }
+
+    /// Completes the bar of `phase` if it was started.
+    #[cfg_attr(not(feature = "cli"), allow(unused_variables))]
+    pub fn finish_phase(&self, phase: Phase) {
+        #[cfg(feature = "cli")]
+        if let Some(bar) = self.by_phase[phase as usize].get() {
+            bar.finish();
+        }
+    }
+
+    pub fn clear(&self) {
+        #[cfg(feature = "cli")]
+        for phase in Phase::ALL {
+            if let Some(bar) = self.by_phase[phase as usize].get() {
+                bar.finish_and_clear();
+            }
+        }
+    }
+}
+
+#[cfg(feature = "cli")]
+fn estimate_message(throughput: Throughput, eta: Option<Duration>) -> String {
+    let records = format!("records:{}/s", pretty_count(throughput.records_per_sec));
+    match eta {
+        Some(eta) => format!("(eta:{}, {})", HumanDuration(eta), records),
+        None => format!("({})", records),
+    }
+}
+
+// unknown totals only report the processed units
+#[cfg(feature = "cli")]
+fn phase_bar(phase: Phase, total: Option<u64>) -> ProgressBar {
+    let (units, total_units, speed) = if phase.counts_bytes() {
+        ("{bytes}", "{total_bytes}", " (speed:{bytes_per_sec})")
+    } else {
+        ("{human_pos}", "{human_len}", "")
+    };
+    // the parser reports its own estimate, based on the record density
+    let (eta, extra) = if phase == Phase::Parsing {
+        ("{msg}", " {msg}")
+    } else {
+        ("(eta:{eta})", "")
+    };
+    let pb = match total {
+        Some(total) => {
+            let pb = ProgressBar::new(total);
+            pb.set_style(
+                ProgressStyle::default_bar()
+                    .template(&format!(
+                        "{{prefix:>13}} [{{elapsed_precise}}] [{{bar:40.cyan/blue}}] {{percent:>3}}% {}/{}{} {}",
+                        units, total_units, speed, eta
+                    ))
+                    .expect("templating should never fail")
+                    .progress_chars("#>-"),
+            );
+            pb
+        }
+        None => {
+            let pb = ProgressBar::new_spinner();
+            pb.set_style(
+                ProgressStyle::default_spinner()
+                    .template(&format!(
+                        "{{prefix:>13}} [{{elapsed_precise}}] {{spinner}} {}{}{}",
+                        units, speed, extra
+                    ))
+                    .expect("templating should never fail"),
+            );
+            pb
+        }
+    };
+    pb.set_prefix(phase.name());
+    pb
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn phase_percentage() {
+        assert_eq!(
+            Progress::new(Phase::Parsing, 25, Some(200)).percentage(),
+            Some(12.5)
+        );
+        assert_eq!(
+            Progress::new(Phase::Analyzing, 0, Some(0)).percentage(),
+            Some(100.0)
+        );
+        assert_eq!(Progress::new(Phase::Reading, 10, None).percentage(), None);
+    }
+
+    #[test]
+    fn eta_follows_record_density() {
+        let mut tracker = ThroughputTracker::new();
+        assert_eq!(tracker.eta(100), None);
+        // 1ms per record and 1ns per byte
+        for (records, bytes) in [(1000, 1_000_000), (10, 10_000_000), (500, 2_000_000)] {
+            let seconds = records as f64 * 1e-3 + bytes as f64 * 1e-9;
+            tracker.record_interval(Duration::from_secs_f64(seconds), records, bytes);
+        }
+        let throughput = tracker.throughput().unwrap();
+        assert_eq!(throughput.records_per_sec, 1510 * 1000 / 1523);
+        // 13M bytes to go at the density seen so far hold 1510 records
+        let eta = tracker.eta(13_000_000).unwrap().as_secs_f64();
+        assert!((eta - (1.51 + 0.013)).abs() < 1e-6, "eta {}", eta);
+    }
+
+    #[test]
+    fn eta_with_constant_density() {
+        let mut tracker = ThroughputTracker::new();
+        for _ in 0..3 {
+            tracker.record_interval(Duration::from_secs(2), 100, 1000);
+        }
+        assert_eq!(
+            tracker.throughput(),
+            Some(Throughput {
+                bytes_per_sec: 500,
+                records_per_sec: 50,
+            })
+        );
+        assert_eq!(tracker.eta(3000), Some(Duration::from_secs(6)));
+    }
+
+    #[test]
+    fn eta_with_expected_records() {
+        let mut tracker = ThroughputTracker::new().with_expected_records(Some(1610));
+        // 1ms per record and 1ns per byte
+        for (records, bytes) in [(1000, 1_000_000), (10, 10_000_000), (500, 2_000_000)] {
+            let seconds = records as f64 * 1e-3 + bytes as f64 * 1e-9;
+            tracker.record_interval(Duration::from_secs_f64(seconds), records, bytes);
+        }
+        // 100 records left instead of the 1510 of the density seen so far
+        let eta = tracker.eta(13_000_000).unwrap().as_secs_f64();
+        assert!((eta - (0.1 + 0.013)).abs() < 1e-6, "eta {}", eta);
+        // past the expected records
+        tracker.record_interval(Duration::from_secs_f64(0.2), 200, 0);
+        let eta = tracker.eta(13_000_000).unwrap().as_secs_f64();
+        assert!(eta > 1.0, "eta {}", eta);
+    }
+}
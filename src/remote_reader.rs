@@ -0,0 +1,140 @@
+use std::io::{Read, Result as IoResult};
+
+use log::warn;
+
+use crate::errors::HprofSlurpError;
+use crate::errors::HprofSlurpError::*;
+
+// size of each range request, the prefetch reader asks for larger buffers in several reads
+const RANGE_SIZE: u64 = 32 * 1024 * 1024;
+const MAX_ATTEMPTS: usize = 3;
+
+/// Streams a remote dump with HTTP range requests, one `RANGE_SIZE` chunk at a time.
+///
+/// `s3://bucket/key` URLs are fetched without signing through the bucket endpoint (region from `AWS_REGION`,
+/// endpoint override from `AWS_ENDPOINT_URL`), private objects need a presigned HTTPS URL.
+pub struct RangeReader {
+    agent: ureq::Agent,
+    url: String,
+    len: u64,
+    position: u64,
+    chunk: Vec<u8>,
+    chunk_position: usize,
+}
+
+impl RangeReader {
+    pub fn open(path: &str) -> Result<Self, HprofSlurpError> {
+        let url = resolve_url(path);
+        let agent = ureq::AgentBuilder::new().build();
+        let response = agent.head(&url).call().map_err(|e| RemoteInputError {
+            message: format!("HEAD {} failed: {}", url, e),
+        })?;
+        let len = response
+            .header("Content-Length")
+            .and_then(|len| len.parse::<u64>().ok())
+            .ok_or_else(|| RemoteInputError {
+                message: format!("{} does not report its Content-Length", url),
+            })?;
+        if response.header("Accept-Ranges") == Some("none") {
+            return Err(RemoteInputError {
+                message: format!("{} does not support range requests", url),
+            });
+        }
+        Ok(RangeReader {
+            agent,
+            url,
+            len,
+            position: 0,
+            chunk: Vec::new(),
+            chunk_position: 0,
+        })
+    }
+
+    pub fn len(&self) -> u64 {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    fn fetch_next_chunk(&mut self) -> IoResult<()> {
+        let end = (self.position + RANGE_SIZE).min(self.len) - 1;
+        let range = format!("bytes={}-{}", self.position, end);
+        let mut attempt = 1;
+        loop {
+            match self.fetch_range(&range, end - self.position + 1) {
+                Ok(()) => return Ok(()),
+                Err(e) if attempt < MAX_ATTEMPTS => {
+                    warn!("retrying range {} of {}: {}", range, self.url, e);
+                    attempt += 1;
+                }
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    fn fetch_range(&mut self, range: &str, expected: u64) -> IoResult<()> {
+        let response = self
+            .agent
+            .get(&self.url)
+            .set("Range", range)
+            .call()
+            .map_err(std::io::Error::other)?;
+        // a server ignoring the range answers 200 with the whole content
+        if response.status() != 206 {
+            return Err(std::io::Error::other(format!(
+                "range request answered with status {}",
+                response.status()
+            )));
+        }
+        self.chunk.clear();
+        response
+            .into_reader()
+            .take(expected)
+            .read_to_end(&mut self.chunk)?;
+        if self.chunk.len() as u64 != expected {
+            return Err(std::io::Error::other(format!(
+                "range request returned {} bytes instead of {}",
+                self.chunk.len(),
+                expected
+            )));
+        }
+        self.chunk_position = 0;
+        self.position += expected;
+        Ok(())
+    }
+}
+
+impl Read for RangeReader {
+    fn read(&mut self, buf: &mut [u8]) -> IoResult<usize> {
+        if self.chunk_position == self.chunk.len() {
+            if self.position >= self.len {
+                return Ok(0);
+            }
+            self.fetch_next_chunk()?;
+        }
+        let available = &self.chunk[self.chunk_position..];
+        let read = available.len().min(buf.len());
+        buf[..read].copy_from_slice(&available[..read]);
+        self.chunk_position += read;
+        Ok(read)
+    }
+}
+
+fn resolve_url(path: &str) -> String {
+    let Some(location) = path.strip_prefix("s3://") else {
+        return path.to_string();
+    };
+    let (bucket, key) = location.split_once('/').unwrap_or((location, ""));
+    match std::env::var("AWS_ENDPOINT_URL") {
+        // path style for S3 compatible stores
+        Ok(endpoint) => format!("{}/{}/{}", endpoint.trim_end_matches('/'), bucket, key),
+        Err(_) => {
+            let region = std::env::var("AWS_REGION")
+                .or_else(|_| std::env::var("AWS_DEFAULT_REGION"))
+                .unwrap_or_else(|_| "us-east-1".to_string());
+            format!("https://{}.s3.{}.amazonaws.com/{}", bucket, region, key)
+        }
+    }
+}
@@ -0,0 +1,176 @@
+use serde::{Deserialize, Serialize};
+
+use crate::analysis::duplicate_arrays::duplicate_array_report;
+use crate::analysis::histogram::HistogramOptions;
+#[cfg(feature = "graph")]
+use crate::analysis::leak_suspects::leak_suspects_report;
+use crate::analysis::strings::string_table_report;
+#[cfg(feature = "graph")]
+use crate::graph::HeapGraph;
+use crate::report::{Report, ReportMetadata, SectionKind};
+use crate::Heap;
+
+/// Thresholds deciding which sections `--auto` renders besides the summary and the memory usage.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct AutoThresholds {
+    // share of the heap held by the string backing arrays
+    pub min_strings_share: f64,
+    // share of the heap wasted by identical primitive arrays
+    pub min_duplicate_arrays_share: f64,
+    // the stacktraces of larger applications are noise for a first look
+    pub max_threads: usize,
+}
+
+impl Default for AutoThresholds {
+    fn default() -> Self {
+        AutoThresholds {
+            min_strings_share: 0.1,
+            min_duplicate_arrays_share: 0.05,
+            max_threads: 16,
+        }
+    }
+}
+
+/// Report with the summary, the memory usage, the leak suspects if any and only the other sections crossing their `thresholds`.
+pub fn auto_report(
+    heap: &Heap,
+    top: usize,
+    histogram_options: HistogramOptions,
+    thresholds: &AutoThresholds,
+) -> Report {
+    let mut report = Report {
+        metadata: Some(ReportMetadata::new(
+            heap.provenance.clone(),
+            top,
+            false,
+            histogram_options,
+        )),
+        ..Default::default()
+    };
+    report.push(SectionKind::Summary, heap.summary_blocks());
+    let threads = heap
+        .stack_trace_by_serial_number
+        .values()
+        .filter(|stack| !stack.stack_frame_ids.is_empty())
+        .count();
+    if threads <= thresholds.max_threads {
+        report.push(SectionKind::ThreadInfo, heap.thread_info_blocks());
+    }
+    report.push(
+        SectionKind::MemoryUsage,
+        heap.memory_usage_blocks(top, histogram_options),
+    );
+
+    let heap_bytes = heap.stats().total_shallow_bytes;
+    let strings = string_table_report(heap, top);
+    if exceeds_share(
+        strings.backing_array_bytes,
+        heap_bytes,
+        thresholds.min_strings_share,
+    ) {
+        report.push(SectionKind::DuplicateStrings, strings.blocks());
+    }
+    let arrays = duplicate_array_report(heap, top);
+    if exceeds_share(
+        arrays.wasted_bytes,
+        heap_bytes,
+        thresholds.min_duplicate_arrays_share,
+    ) {
+        report.push(SectionKind::DuplicateArrays, arrays.blocks());
+    }
+    #[cfg(feature = "graph")]
+    {
+        let suspects = leak_suspects_report(heap, &HeapGraph::new(heap));
+        if !suspects.is_empty() {
+            report.push(SectionKind::LeakSuspects, suspects.blocks());
+        }
+    }
+    report
+}
+
+fn exceeds_share(bytes: u64, heap_bytes: u64, min_share: f64) -> bool {
+    heap_bytes > 0 && bytes > 0 && bytes as f64 >= min_share * heap_bytes as f64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn share_of_the_heap() {
+        assert!(exceeds_share(10, 100, 0.1));
+        assert!(!exceeds_share(9, 100, 0.1));
+        assert!(exceeds_share(1, 100, 0.0));
+        assert!(!exceeds_share(0, 100, 0.0));
+        assert!(!exceeds_share(10, 0, 0.1));
+    }
+
+    #[test]
+    fn empty_heap_keeps_the_default_sections() {
+        let report = auto_report(
+            &Heap::default(),
+            20,
+            HistogramOptions::default(),
+            &AutoThresholds::default(),
+        );
+        let kinds: Vec<SectionKind> = report.sections.iter().map(|s| s.kind).collect();
+        assert_eq!(
+            kinds,
+            vec![
+                SectionKind::Summary,
+                SectionKind::ThreadInfo,
+                SectionKind::MemoryUsage
+            ]
+        );
+        let report = auto_report(
+            &Heap::default(),
+            20,
+            HistogramOptions::default(),
+            &AutoThresholds {
+                min_strings_share: 0.0,
+                min_duplicate_arrays_share: 0.0,
+                ..Default::default()
+            },
+        );
+        // nothing to report on without strings nor arrays
+        assert_eq!(report.sections.len(), 3);
+    }
+
+    #[cfg(feature = "graph")]
+    #[test]
+    fn leak_suspects_when_found() {
+        use crate::parser::gc_record::FieldValue;
+        use crate::tests::{add_class, add_instance, single};
+
+        const STARTED: u64 = 1;
+        let mut heap = Heap::default();
+        heap.counter.id_size = 8;
+        heap.utf8_strings
+            .make_mut()
+            .insert(STARTED, "started".into());
+        add_class(&mut heap, 100, 0, "java/lang/ClassLoader");
+        add_class(
+            &mut heap,
+            101,
+            100,
+            "org/apache/catalina/loader/WebappClassLoaderBase",
+        );
+        // an undeployed web application
+        add_instance(
+            &mut heap,
+            2,
+            101,
+            vec![single(STARTED, FieldValue::Bool(false))],
+        );
+        let report = auto_report(
+            &heap,
+            20,
+            HistogramOptions::default(),
+            &AutoThresholds::default(),
+        );
+        assert!(report
+            .sections
+            .iter()
+            .any(|section| section.kind == SectionKind::LeakSuspects));
+    }
+}
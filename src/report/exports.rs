@@ -0,0 +1,300 @@
+use std::io::{self, Write};
+
+use super::{Block, OutputFormat, ReportMetadata, Section};
+use crate::utils::pretty_timestamp;
+
+pub(super) fn render_section(section: &Section, format: OutputFormat) -> String {
+    match format {
+        OutputFormat::Text => unreachable!("text is rendered by the report module"),
+        OutputFormat::Markdown => section.blocks.iter().map(markdown_block).collect(),
+        OutputFormat::Html => format!(
+            "<section id=\"{}\">\n{}</section>\n",
+            section.kind.id(),
+            section.blocks.iter().map(html_block).collect::<String>()
+        ),
+        OutputFormat::Json => format!(
+            "{{\"kind\":{},\"blocks\":[{}]}}",
+            json_string(section.kind.id()),
+            section
+                .blocks
+                .iter()
+                .map(json_block)
+                .collect::<Vec<_>>()
+                .join(",")
+        ),
+    }
+}
+
+// the rendered sections preceded by the metadata
+pub(super) fn render_document(
+    metadata: Option<&ReportMetadata>,
+    format: OutputFormat,
+    sections: Vec<String>,
+) -> String {
+    let (head, separator, tail) = document_parts(metadata, format);
+    head + &sections.join(separator) + &tail
+}
+
+// same as `render_document`, a single section is held in memory at a time
+pub(super) fn write_document<W: Write>(
+    metadata: Option<&ReportMetadata>,
+    format: OutputFormat,
+    sections: &[Section],
+    writer: &mut W,
+) -> io::Result<()> {
+    let (head, separator, tail) = document_parts(metadata, format);
+    writer.write_all(head.as_bytes())?;
+    for (index, section) in sections.iter().enumerate() {
+        if index > 0 {
+            writer.write_all(separator.as_bytes())?;
+        }
+        writer.write_all(render_section(section, format).as_bytes())?;
+    }
+    writer.write_all(tail.as_bytes())
+}
+
+// what comes before, between and after the rendered sections
+fn document_parts(
+    metadata: Option<&ReportMetadata>,
+    format: OutputFormat,
+) -> (String, &'static str, String) {
+    match format {
+        OutputFormat::Text => unreachable!("text is rendered by the report module"),
+        OutputFormat::Markdown => {
+            let metadata: String = metadata
+                .map(|metadata| metadata.blocks().iter().map(markdown_block).collect())
+                .unwrap_or_default();
+            (metadata, "", String::new())
+        }
+        OutputFormat::Html => {
+            let metadata: String = metadata
+                .map(|metadata| {
+                    format!(
+                        "<section id=\"metadata\">\n{}</section>\n",
+                        metadata.blocks().iter().map(html_block).collect::<String>()
+                    )
+                })
+                .unwrap_or_default();
+            (
+                format!(
+                    "<!DOCTYPE html>\n<html>\n<head>\n<meta name=\"generator\" content=\"hprof-slurp {}\">\n</head>\n<body>\n{}",
+                    env!("CARGO_PKG_VERSION"),
+                    metadata
+                ),
+                "",
+                "</body>\n</html>\n".to_string(),
+            )
+        }
+        OutputFormat::Json => (
+            format!(
+                "{{{}\"sections\":[",
+                metadata
+                    .map(|metadata| format!("\"metadata\":{},", metadata_json(metadata)))
+                    .unwrap_or_default()
+            ),
+            ",",
+            "]}\n".to_string(),
+        ),
+    }
+}
+
+fn metadata_json(metadata: &ReportMetadata) -> String {
+    let provenance = &metadata.provenance;
+    let options = &metadata.histogram_options;
+    let input_files: Vec<String> = provenance
+        .input_files
+        .iter()
+        .map(|file| json_string(file))
+        .collect();
+    format!(
+        "{{\"tool_version\":{},\"input_files\":[{}],\"input_size\":{},\"format\":{},\"timestamp\":{},\"taken_at\":{},\"options\":{{\"top\":{},\"list_strings\":{},\"sort_by\":{},\"min_total_size\":{},\"min_instances\":{},\"group_names\":{},\"effective_ownership\":{},\"split_arrays_by_owner\":{}}}}}",
+        json_string(&metadata.tool_version),
+        input_files.join(","),
+        provenance
+            .input_size
+            .map_or("null".to_string(), |size| size.to_string()),
+        json_string(&provenance.format),
+        provenance.timestamp,
+        json_string(&pretty_timestamp(provenance.timestamp)),
+        metadata.top,
+        metadata.list_strings,
+        json_string(&options.sort.to_string()),
+        options.min_total_size,
+        options.min_instances,
+        json_string(&options.names.to_string()),
+        options.effective_ownership,
+        options.split_arrays_by_owner
+    )
+}
+
+fn markdown_block(block: &Block) -> String {
+    match block {
+        Block::Heading(text) => format!("\n### {}\n", text.trim_end_matches(':')),
+        // two trailing spaces break the line
+        Block::Text(text) => format!("\n{}\n", text.lines().collect::<Vec<_>>().join("  \n")),
+        Block::Warning(text) => format!("\n> **Warning:** {}\n", text),
+        Block::Table { headers, rows } => {
+            let row = |cells: &[String]| {
+                let cells: Vec<String> = cells.iter().map(|c| c.replace('|', "\\|")).collect();
+                format!("| {} |\n", cells.join(" | "))
+            };
+            let mut table = format!("\n{}", row(headers));
+            table.push_str(&format!("|{}\n", "---|".repeat(headers.len())));
+            rows.iter().for_each(|r| table.push_str(&row(r)));
+            table
+        }
+        Block::Lines(lines) => format!("\n```\n{}\n```\n", lines.join("\n")),
+    }
+}
+
+fn html_block(block: &Block) -> String {
+    match block {
+        Block::Heading(text) => format!("<h3>{}</h3>\n", html_escape(text)),
+        Block::Text(text) => format!(
+            "<p>{}</p>\n",
+            text.lines()
+                .map(html_escape)
+                .collect::<Vec<_>>()
+                .join("<br>\n")
+        ),
+        Block::Warning(text) => format!("<p class=\"warning\">{}</p>\n", html_escape(text)),
+        Block::Table { headers, rows } => {
+            let mut table = String::from("<table>\n<tr>");
+            headers
+                .iter()
+                .for_each(|h| table.push_str(&format!("<th>{}</th>", html_escape(h))));
+            table.push_str("</tr>\n");
+            for row in rows {
+                table.push_str("<tr>");
+                row.iter()
+                    .for_each(|c| table.push_str(&format!("<td>{}</td>", html_escape(c))));
+                table.push_str("</tr>\n");
+            }
+            table.push_str("</table>\n");
+            table
+        }
+        Block::Lines(lines) => format!(
+            "<pre>\n{}\n</pre>\n",
+            lines
+                .iter()
+                .map(|l| html_escape(l))
+                .collect::<Vec<_>>()
+                .join("\n")
+        ),
+    }
+}
+
+pub(super) fn html_escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+fn json_block(block: &Block) -> String {
+    let strings = |values: &[String]| {
+        format!(
+            "[{}]",
+            values
+                .iter()
+                .map(|v| json_string(v))
+                .collect::<Vec<_>>()
+                .join(",")
+        )
+    };
+    match block {
+        Block::Heading(text) => format!("{{\"heading\":{}}}", json_string(text)),
+        Block::Text(text) => format!("{{\"text\":{}}}", json_string(text)),
+        Block::Warning(text) => format!("{{\"warning\":{}}}", json_string(text)),
+        Block::Table { headers, rows } => format!(
+            "{{\"table\":{{\"headers\":{},\"rows\":[{}]}}}}",
+            strings(headers),
+            rows.iter()
+                .map(|row| strings(row))
+                .collect::<Vec<_>>()
+                .join(",")
+        ),
+        Block::Lines(lines) => format!("{{\"lines\":{}}}", strings(lines)),
+    }
+}
+
+pub fn json_string(text: &str) -> String {
+    let mut json = String::with_capacity(text.len() + 2);
+    json.push('"');
+    for c in text.chars() {
+        match c {
+            '"' => json.push_str("\\\""),
+            '\\' => json.push_str("\\\\"),
+            '\n' => json.push_str("\\n"),
+            '\t' => json.push_str("\\t"),
+            c if c.is_control() => json.push_str(&format!("\\u{:04x}", c as u32)),
+            c => json.push(c),
+        }
+    }
+    json.push('"');
+    json
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::analysis::histogram::HistogramOptions;
+    use crate::report::{tests, RenderOptions, Report};
+    use crate::DumpProvenance;
+
+    #[test]
+    fn render_structured_formats() {
+        let report = tests::report();
+        assert_eq!(
+            report.render(OutputFormat::Markdown, &RenderOptions::default()),
+            "\n### Top 1 allocated classes\n\n| Instances | Class name |\n|---|---|\n| 2 | a<b>\\|\"c\" |\n"
+        );
+        assert!(report
+            .render(OutputFormat::Html, &RenderOptions::default())
+            .contains("<td>a&lt;b&gt;|&quot;c&quot;</td>"));
+        assert_eq!(
+            report.render(OutputFormat::Json, &RenderOptions::default()),
+            "{\"sections\":[{\"kind\":\"memory-usage\",\"blocks\":[{\"heading\":\"Top 1 allocated classes:\"},{\"table\":{\"headers\":[\"Instances\",\"Class name\"],\"rows\":[[\"2\",\"a<b>|\\\"c\\\"\"]]}}]}]}\n"
+        );
+    }
+
+    #[test]
+    fn render_report_metadata() {
+        let mut report = tests::report();
+        report.metadata = Some(ReportMetadata::new(
+            DumpProvenance {
+                input_files: vec!["heap.hprof".to_string()],
+                input_size: Some(2048),
+                format: "JAVA PROFILE 1.0.2".to_string(),
+                timestamp: 1608192273831,
+            },
+            1,
+            false,
+            HistogramOptions::default(),
+        ));
+        let json = report.render(OutputFormat::Json, &RenderOptions::default());
+        assert!(json.starts_with("{\"metadata\":{\"tool_version\":"));
+        assert!(json.contains("\"input_files\":[\"heap.hprof\"],\"input_size\":2048,\"format\":\"JAVA PROFILE 1.0.2\",\"timestamp\":1608192273831,\"taken_at\":\"2020-12-17T08:04:33.831Z\",\"options\":{\"top\":1,"));
+        assert!(report
+            .render(OutputFormat::Markdown, &RenderOptions::default())
+            .starts_with("\n### Report metadata\n"));
+        assert!(report
+            .render(OutputFormat::Html, &RenderOptions::default())
+            .contains("<td>Taken at</td><td>2020-12-17T08:04:33.831Z</td>"));
+        // the terminal output is unchanged
+        assert_eq!(
+            report.render(OutputFormat::Text, &RenderOptions::default()),
+            Report {
+                metadata: None,
+                ..report.clone()
+            }
+            .render(OutputFormat::Text, &RenderOptions::default())
+        );
+        assert_eq!(
+            report
+                .rendered(OutputFormat::Text, &RenderOptions::default())
+                .metadata,
+            report.metadata
+        );
+    }
+}
@@ -0,0 +1,89 @@
+use std::collections::HashMap;
+
+use crate::parser::gc_record::FieldType;
+use crate::Heap;
+
+// rows of `jmap -histo`: count, bytes and class name as named by the JVM
+struct JmapRow {
+    class_name: String,
+    instances: u64,
+    bytes: u64,
+}
+
+/// Renders the instances and arrays per class like `jmap -histo` of JDK 8, for the scripts parsing its output.
+///
+/// Classes are named as in the JVM, arrays included (e.g. `[B` or `[Ljava.lang.Object;`), and sorted by bytes.
+pub fn render_jmap_histo(heap: &Heap) -> String {
+    let mut rows: HashMap<String, JmapRow> = HashMap::new();
+    for instance in heap.instances_pool.values() {
+        let class_name = match instance.array_element_type() {
+            Some(FieldType::Object) | None => heap
+                .class_name(instance.class_object_id)
+                .unwrap_or_else(|| "unknown class".to_string()),
+            Some(element_type) => format!("[{}", element_type.descriptor()),
+        };
+        let row = rows.entry(class_name).or_insert_with_key(|name| JmapRow {
+            class_name: name.clone(),
+            instances: 0,
+            bytes: 0,
+        });
+        row.instances += 1;
+        row.bytes += heap.shallow_size(instance);
+    }
+    let mut rows: Vec<JmapRow> = rows.into_values().collect();
+    rows.sort_by(|a, b| {
+        b.bytes
+            .cmp(&a.bytes)
+            .then_with(|| a.class_name.cmp(&b.class_name))
+    });
+    format_jmap_histo(&rows)
+}
+
+fn format_jmap_histo(rows: &[JmapRow]) -> String {
+    let mut histo = String::from("\n num     #instances         #bytes  class name\n");
+    histo.push_str("----------------------------------------------\n");
+    for (num, row) in rows.iter().enumerate() {
+        histo.push_str(&format!(
+            "{:4}: {:13}  {:13}  {}\n",
+            num + 1,
+            row.instances,
+            row.bytes,
+            row.class_name
+        ));
+    }
+    histo.push_str(&format!(
+        "Total {:13}  {:13}\n",
+        rows.iter().map(|row| row.instances).sum::<u64>(),
+        rows.iter().map(|row| row.bytes).sum::<u64>()
+    ));
+    histo
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn jmap_histo_layout() {
+        let rows = [
+            JmapRow {
+                class_name: "[C".to_string(),
+                instances: 20347,
+                bytes: 2357880,
+            },
+            JmapRow {
+                class_name: "java.lang.String".to_string(),
+                instances: 20134,
+                bytes: 483216,
+            },
+        ];
+        let expected = "
+ num     #instances         #bytes  class name
+----------------------------------------------
+   1:         20347        2357880  [C
+   2:         20134         483216  java.lang.String
+Total         40481        2841096
+";
+        assert_eq!(format_jmap_histo(&rows), expected);
+    }
+}
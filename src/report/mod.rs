@@ -0,0 +1,541 @@
+use std::fmt;
+use std::io::{self, Write};
+use std::str::FromStr;
+
+use rayon::prelude::{IntoParallelRefIterator, ParallelIterator};
+use serde::{Deserialize, Serialize};
+
+use crate::analysis::histogram::HistogramOptions;
+use crate::analysis::{render_table_with, TableOptions};
+use crate::utils::{
+    is_pretty_bytes_size, paint_size, paint_warning, pretty_bytes_size, pretty_count,
+    pretty_timestamp,
+};
+use crate::{DumpProvenance, Heap};
+
+pub mod auto;
+#[cfg(feature = "exports")]
+mod exports;
+#[cfg(feature = "exports")]
+pub use exports::json_string;
+pub mod jmap;
+#[cfg(feature = "exports")]
+pub mod visualvm;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SectionKind {
+    Summary,
+    ThreadInfo,
+    MemoryUsage,
+    CapturedStrings,
+    GraphMetrics,
+    ReferenceCycles,
+    DuplicateStrings,
+    DuplicateArrays,
+    HeaviestFields,
+    Locks,
+    LeakSuspects,
+    Caches,
+    Netty,
+    AllocationSites,
+    RootRetention,
+    Metaspace,
+    ValueTypes,
+    GeneratedClasses,
+    Exceptions,
+    StaticFields,
+    SparseArrays,
+    MapCollisions,
+    StructuralDuplicates,
+    AllocationAge,
+    // fields referencing the instances of a target class
+    ReferencingFields,
+    // classes growing beyond a baseline profile
+    BaselineDiff,
+    // memory used by hprof-slurp itself, last so that its peak covers the other sections
+    ToolMemory,
+}
+
+impl SectionKind {
+    // rendered on demand after the default sections, selected by their id with `--section`
+    pub const OPTIONAL: [SectionKind; 20] = [
+        SectionKind::GraphMetrics,
+        SectionKind::ReferenceCycles,
+        SectionKind::DuplicateStrings,
+        SectionKind::DuplicateArrays,
+        SectionKind::HeaviestFields,
+        SectionKind::Locks,
+        SectionKind::LeakSuspects,
+        SectionKind::Caches,
+        SectionKind::Netty,
+        SectionKind::AllocationSites,
+        SectionKind::RootRetention,
+        SectionKind::Metaspace,
+        SectionKind::ValueTypes,
+        SectionKind::GeneratedClasses,
+        SectionKind::Exceptions,
+        SectionKind::StaticFields,
+        SectionKind::SparseArrays,
+        SectionKind::MapCollisions,
+        SectionKind::StructuralDuplicates,
+        SectionKind::AllocationAge,
+    ];
+
+    // stable identifier used by the structured formats
+    pub fn id(self) -> &'static str {
+        match self {
+            SectionKind::Summary => "summary",
+            SectionKind::ThreadInfo => "thread-info",
+            SectionKind::MemoryUsage => "memory-usage",
+            SectionKind::CapturedStrings => "captured-strings",
+            SectionKind::GraphMetrics => "graph-metrics",
+            SectionKind::ReferenceCycles => "reference-cycles",
+            SectionKind::DuplicateStrings => "duplicate-strings",
+            SectionKind::DuplicateArrays => "duplicate-arrays",
+            SectionKind::HeaviestFields => "heaviest-fields",
+            SectionKind::Locks => "locks",
+            SectionKind::LeakSuspects => "leak-suspects",
+            SectionKind::Caches => "caches",
+            SectionKind::Netty => "netty",
+            SectionKind::AllocationSites => "allocation-sites",
+            SectionKind::RootRetention => "root-retention",
+            SectionKind::Metaspace => "metaspace",
+            SectionKind::ValueTypes => "value-types",
+            SectionKind::GeneratedClasses => "generated-classes",
+            SectionKind::Exceptions => "exceptions",
+            SectionKind::StaticFields => "static-fields",
+            SectionKind::SparseArrays => "sparse-arrays",
+            SectionKind::MapCollisions => "map-collisions",
+            SectionKind::StructuralDuplicates => "structural-duplicates",
+            SectionKind::AllocationAge => "allocation-age",
+            SectionKind::ReferencingFields => "referencing-fields",
+            SectionKind::BaselineDiff => "baseline-diff",
+            SectionKind::ToolMemory => "tool-memory",
+        }
+    }
+}
+
+/// Formats a `Report` can be rendered in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum OutputFormat {
+    #[default]
+    Text,
+    #[cfg(feature = "exports")]
+    Markdown,
+    #[cfg(feature = "exports")]
+    Html,
+    #[cfg(feature = "exports")]
+    Json,
+}
+
+impl FromStr for OutputFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "text" => Ok(OutputFormat::Text),
+            #[cfg(feature = "exports")]
+            "md" | "markdown" => Ok(OutputFormat::Markdown),
+            #[cfg(feature = "exports")]
+            "html" => Ok(OutputFormat::Html),
+            #[cfg(feature = "exports")]
+            "json" => Ok(OutputFormat::Json),
+            #[cfg(not(feature = "exports"))]
+            "md" | "markdown" | "html" | "json" => {
+                Err(format!("output format `{}` needs the `exports` feature", s))
+            }
+            other => Err(format!("unknown output format `{}`", other)),
+        }
+    }
+}
+
+/// Formats mirroring other tools, rendered from the heap instead of the report sections.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum HeapExport {
+    // `jmap -histo` text
+    JmapHisto,
+    // VisualVM heap summary and classes view
+    #[cfg(feature = "exports")]
+    VisualVmJson,
+    #[cfg(feature = "exports")]
+    VisualVmXml,
+}
+
+impl FromStr for HeapExport {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "jmap-histo" => Ok(HeapExport::JmapHisto),
+            #[cfg(feature = "exports")]
+            "visualvm-json" => Ok(HeapExport::VisualVmJson),
+            #[cfg(feature = "exports")]
+            "visualvm-xml" => Ok(HeapExport::VisualVmXml),
+            #[cfg(not(feature = "exports"))]
+            "visualvm-json" | "visualvm-xml" => {
+                Err(format!("export format `{}` needs the `exports` feature", s))
+            }
+            other => Err(format!("unknown export format `{}`", other)),
+        }
+    }
+}
+
+impl HeapExport {
+    pub fn render(self, heap: &Heap) -> String {
+        match self {
+            HeapExport::JmapHisto => jmap::render_jmap_histo(heap),
+            #[cfg(feature = "exports")]
+            HeapExport::VisualVmJson => visualvm::visualvm_summary(heap).render_json(),
+            #[cfg(feature = "exports")]
+            HeapExport::VisualVmXml => visualvm::visualvm_summary(heap).render_xml(),
+        }
+    }
+}
+
+/// Layout and colors of the text format, given to each render call.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct RenderOptions {
+    pub table: TableOptions,
+    // ANSI colors on the sizes and warnings, see `ColorChoice::enabled`
+    pub colors: bool,
+}
+
+/// A piece of report content, each `OutputFormat` renders it its own way.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Block {
+    // e.g. `Top 20 allocated classes:`
+    Heading(String),
+    // paragraph of one or more lines
+    Text(String),
+    Warning(String),
+    // cells are pre-formatted, the last column is left aligned
+    Table {
+        headers: Vec<String>,
+        rows: Vec<Vec<String>>,
+    },
+    // preformatted lines such as stack frames
+    Lines(Vec<String>),
+}
+
+impl Block {
+    pub fn heading(text: impl Into<String>) -> Self {
+        Block::Heading(text.into())
+    }
+
+    pub fn table(headers: &[&str], rows: Vec<Vec<String>>) -> Self {
+        Block::Table {
+            headers: headers.iter().map(|h| h.to_string()).collect(),
+            rows,
+        }
+    }
+}
+
+/// Version of `hprof-slurp`, input and analysis options a report was produced with.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ReportMetadata {
+    pub tool_version: String,
+    pub provenance: DumpProvenance,
+    pub top: usize,
+    pub list_strings: bool,
+    pub histogram_options: HistogramOptions,
+}
+
+impl ReportMetadata {
+    pub fn new(
+        provenance: DumpProvenance,
+        top: usize,
+        list_strings: bool,
+        histogram_options: HistogramOptions,
+    ) -> Self {
+        ReportMetadata {
+            tool_version: env!("CARGO_PKG_VERSION").to_string(),
+            provenance,
+            top,
+            list_strings,
+            histogram_options,
+        }
+    }
+
+    /// Field names and rendered values, in display order.
+    pub fn fields(&self) -> Vec<(&'static str, String)> {
+        let provenance = &self.provenance;
+        let options = &self.histogram_options;
+        vec![
+            ("Tool", format!("hprof-slurp {}", self.tool_version)),
+            ("Input files", provenance.input_files.join(", ")),
+            (
+                "Input size",
+                provenance
+                    .input_size
+                    .map_or("unknown".to_string(), pretty_bytes_size),
+            ),
+            ("Format", provenance.format.clone()),
+            ("Taken at", pretty_timestamp(provenance.timestamp)),
+            ("Top", self.top.to_string()),
+            ("List strings", self.list_strings.to_string()),
+            ("Sort by", options.sort.to_string()),
+            ("Min total size", pretty_bytes_size(options.min_total_size)),
+            ("Min instances", pretty_count(options.min_instances)),
+            ("Group names", options.names.to_string()),
+            (
+                "Effective ownership",
+                options.effective_ownership.to_string(),
+            ),
+            (
+                "Split arrays by owner",
+                options.split_arrays_by_owner.to_string(),
+            ),
+        ]
+    }
+
+    pub fn blocks(&self) -> Vec<Block> {
+        vec![
+            Block::heading("Report metadata:"),
+            Block::table(
+                &["Field", "Value"],
+                self.fields()
+                    .into_iter()
+                    .map(|(field, value)| vec![field.to_string(), value])
+                    .collect(),
+            ),
+        ]
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Section {
+    pub kind: SectionKind,
+    pub blocks: Vec<Block>,
+}
+
+/// Structured model of the analysis output, rendered in any `OutputFormat`.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Report {
+    // rendered ahead of the sections by the structured formats
+    pub metadata: Option<ReportMetadata>,
+    pub sections: Vec<Section>,
+}
+
+impl Report {
+    pub fn push(&mut self, kind: SectionKind, blocks: Vec<Block>) {
+        self.sections.push(Section { kind, blocks });
+    }
+
+    /// Renders each section on its own, the sections are concatenated by `RenderedResult`.
+    pub fn rendered(&self, format: OutputFormat, options: &RenderOptions) -> RenderedResult {
+        let mut result = RenderedResult {
+            metadata: self.metadata.clone(),
+            ..Default::default()
+        };
+        let contents: Vec<String> = self
+            .sections
+            .par_iter()
+            .map(|section| render_section(section, format, options))
+            .collect();
+        for (section, content) in self.sections.iter().zip(contents) {
+            result.push(section.kind, content);
+        }
+        result
+    }
+
+    /// Renders the whole report as a single document, the structured formats start with the metadata.
+    pub fn render(&self, format: OutputFormat, options: &RenderOptions) -> String {
+        let sections: Vec<String> = self
+            .sections
+            .par_iter()
+            .map(|section| render_section(section, format, options))
+            .collect();
+        match format {
+            OutputFormat::Text => sections.concat(),
+            #[cfg(feature = "exports")]
+            _ => exports::render_document(self.metadata.as_ref(), format, sections),
+        }
+    }
+
+    /// Writes the same document as `render` section after section, the sections are rendered one at a time
+    /// and the lines of the text ones written as they go.
+    pub fn write<W: Write>(
+        &self,
+        format: OutputFormat,
+        options: &RenderOptions,
+        writer: &mut W,
+    ) -> io::Result<()> {
+        match format {
+            OutputFormat::Text => self
+                .sections
+                .iter()
+                .try_for_each(|section| write_blocks(&section.blocks, options, writer)),
+            #[cfg(feature = "exports")]
+            _ => exports::write_document(self.metadata.as_ref(), format, &self.sections, writer),
+        }
+    }
+}
+
+// the layout and colors only apply to the text format
+pub fn render_section(section: &Section, format: OutputFormat, options: &RenderOptions) -> String {
+    match format {
+        OutputFormat::Text => render_blocks_with(&section.blocks, options),
+        #[cfg(feature = "exports")]
+        _ => exports::render_section(section, format),
+    }
+}
+
+/// Renders blocks as plain text with the default layout and without colors.
+pub fn render_blocks(blocks: &[Block]) -> String {
+    render_blocks_with(blocks, &RenderOptions::default())
+}
+
+/// Renders blocks as plain text, the format of the terminal output.
+pub fn render_blocks_with(blocks: &[Block], options: &RenderOptions) -> String {
+    blocks
+        .iter()
+        .map(|block| text_block(block, options))
+        .collect()
+}
+
+/// Writes blocks as plain text like `render_blocks_with`, line by line for the `Lines` blocks.
+pub fn write_blocks<W: Write>(
+    blocks: &[Block],
+    options: &RenderOptions,
+    writer: &mut W,
+) -> io::Result<()> {
+    for block in blocks {
+        match block {
+            Block::Lines(lines) => {
+                for line in lines {
+                    writeln!(writer, "{}", line)?;
+                }
+            }
+            block => writer.write_all(text_block(block, options).as_bytes())?,
+        }
+    }
+    Ok(())
+}
+
+fn text_block(block: &Block, options: &RenderOptions) -> String {
+    match block {
+        Block::Heading(text) => format!("\n{}\n", paint_sizes(text, options.colors)),
+        Block::Text(text) => format!("\n{}\n", paint_sizes(text, options.colors)),
+        Block::Warning(text) => format!("{}\n", paint_warning(text, options.colors)),
+        Block::Table { headers, rows } => {
+            let headers: Vec<&str> = headers.iter().map(|h| h.as_str()).collect();
+            format!("\n{}", render_table_with(options, &headers, rows))
+        }
+        Block::Lines(lines) => lines.iter().map(|line| format!("{}\n", line)).collect(),
+    }
+}
+
+// sizes stand out in the text like in the tables
+fn paint_sizes(text: &str, colors: bool) -> String {
+    text.split(' ')
+        .map(|word| {
+            if is_pretty_bytes_size(word) {
+                paint_size(word, colors)
+            } else {
+                word.to_string()
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RenderedSection {
+    pub kind: SectionKind,
+    pub content: String,
+}
+
+/// Rendered output split in sections so that front-ends can pick the ones to display.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct RenderedResult {
+    pub metadata: Option<ReportMetadata>,
+    pub sections: Vec<RenderedSection>,
+}
+
+impl RenderedResult {
+    pub fn push(&mut self, kind: SectionKind, content: String) {
+        self.sections.push(RenderedSection { kind, content });
+    }
+
+    pub fn section(&self, kind: SectionKind) -> Option<&str> {
+        self.sections
+            .iter()
+            .find(|section| section.kind == kind)
+            .map(|section| section.content.as_str())
+    }
+
+    /// Concatenates the sections of the given kinds in rendering order.
+    pub fn render(&self, kinds: &[SectionKind]) -> String {
+        self.sections
+            .iter()
+            .filter(|section| kinds.contains(&section.kind))
+            .map(|section| section.content.as_str())
+            .collect()
+    }
+}
+
+impl fmt::Display for RenderedResult {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.sections
+            .iter()
+            .try_for_each(|section| f.write_str(&section.content))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    pub(super) fn report() -> Report {
+        let mut report = Report::default();
+        report.push(
+            SectionKind::MemoryUsage,
+            vec![
+                Block::heading("Top 1 allocated classes:"),
+                Block::table(
+                    &["Instances", "Class name"],
+                    vec![vec!["2".to_string(), "a<b>|\"c\"".to_string()]],
+                ),
+            ],
+        );
+        report
+    }
+
+    #[test]
+    fn render_report_text() {
+        assert_eq!(
+            report().render(OutputFormat::Text, &RenderOptions::default()),
+            "\nTop 1 allocated classes:\n\nInstances | Class name\n----------------------\n        2 | a<b>|\"c\"\n"
+        );
+    }
+
+    #[test]
+    fn write_report_as_rendered() {
+        let mut report = report();
+        report.push(
+            SectionKind::CapturedStrings,
+            vec![
+                Block::heading("List of Strings"),
+                Block::Lines(vec!["a".to_string(), "b".to_string()]),
+            ],
+        );
+        let formats = [
+            OutputFormat::Text,
+            #[cfg(feature = "exports")]
+            OutputFormat::Markdown,
+            #[cfg(feature = "exports")]
+            OutputFormat::Html,
+            #[cfg(feature = "exports")]
+            OutputFormat::Json,
+        ];
+        for format in formats {
+            let mut written = Vec::new();
+            let options = RenderOptions::default();
+            report.write(format, &options, &mut written).unwrap();
+            assert_eq!(
+                String::from_utf8(written).unwrap(),
+                report.render(format, &options)
+            );
+        }
+    }
+}
@@ -0,0 +1,174 @@
+use serde::Serialize;
+
+use super::exports::{html_escape, json_string};
+use crate::analysis::class_names::pretty_class_name;
+use crate::analysis::histogram::class_histogram;
+use crate::Heap;
+
+/// Basic info of the VisualVM heap summary.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct VisualVmBasicInfo {
+    pub total_bytes: u64,
+    pub total_classes: u64,
+    // instances and arrays
+    pub total_instances: u64,
+    // instances of `java.lang.ClassLoader` subclasses
+    pub classloaders: u64,
+    pub gc_roots: u64,
+    // `java.lang.ref.Finalizer` instances, the ones already finalized included
+    pub objects_pending_finalization: u64,
+}
+
+/// Row of the VisualVM classes view.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct VisualVmClass {
+    pub name: String,
+    pub instances: u64,
+    pub instances_percent: f64,
+    pub size: u64,
+    pub size_percent: f64,
+}
+
+/// Heap summary and classes view structured like VisualVM, for the dashboards built on its exports.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct VisualVmSummary {
+    pub basic_info: VisualVmBasicInfo,
+    // sorted by size
+    pub classes: Vec<VisualVmClass>,
+}
+
+pub fn visualvm_summary(heap: &Heap) -> VisualVmSummary {
+    let stats = heap.stats();
+    let total_instances = stats.instances + stats.object_arrays + stats.primitive_arrays;
+    let basic_info = VisualVmBasicInfo {
+        total_bytes: stats.total_shallow_bytes,
+        total_classes: stats.classes,
+        total_instances,
+        classloaders: heap.instances_of("java.lang.ClassLoader").count() as u64,
+        gc_roots: stats.roots_by_kind.values().sum(),
+        objects_pending_finalization: heap.instances_of("java.lang.ref.Finalizer").count() as u64,
+    };
+    let percent = |part: u64, total: u64| {
+        if total == 0 {
+            0.0
+        } else {
+            part as f64 * 100.0 / total as f64
+        }
+    };
+    let classes = class_histogram(heap)
+        .classes
+        .into_iter()
+        .map(|entry| VisualVmClass {
+            instances_percent: percent(entry.instances, total_instances),
+            size_percent: percent(entry.total_size, stats.total_shallow_bytes),
+            name: pretty_class_name(&entry.class_name),
+            instances: entry.instances,
+            size: entry.total_size,
+        })
+        .collect();
+    VisualVmSummary {
+        basic_info,
+        classes,
+    }
+}
+
+impl VisualVmSummary {
+    pub fn render_json(&self) -> String {
+        let info = &self.basic_info;
+        let classes: Vec<String> = self
+            .classes
+            .iter()
+            .map(|class| {
+                format!(
+                    "{{\"name\":{},\"instances\":{},\"instancesPercent\":{:.1},\"size\":{},\"sizePercent\":{:.1}}}",
+                    json_string(&class.name),
+                    class.instances,
+                    class.instances_percent,
+                    class.size,
+                    class.size_percent
+                )
+            })
+            .collect();
+        format!(
+            "{{\"generator\":{},\"basicInfo\":{{\"totalBytes\":{},\"totalClasses\":{},\"totalInstances\":{},\"classloaders\":{},\"gcRoots\":{},\"objectsPendingFinalization\":{}}},\"classes\":[{}]}}\n",
+            json_string(&format!("hprof-slurp {}", env!("CARGO_PKG_VERSION"))),
+            info.total_bytes,
+            info.total_classes,
+            info.total_instances,
+            info.classloaders,
+            info.gc_roots,
+            info.objects_pending_finalization,
+            classes.join(",")
+        )
+    }
+
+    pub fn render_xml(&self) -> String {
+        let info = &self.basic_info;
+        let mut xml = format!(
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<heapSummary generator=\"hprof-slurp {}\">\n",
+            env!("CARGO_PKG_VERSION")
+        );
+        xml.push_str(&format!(
+            "  <basicInfo>\n    <totalBytes>{}</totalBytes>\n    <totalClasses>{}</totalClasses>\n    <totalInstances>{}</totalInstances>\n    <classloaders>{}</classloaders>\n    <gcRoots>{}</gcRoots>\n    <objectsPendingFinalization>{}</objectsPendingFinalization>\n  </basicInfo>\n",
+            info.total_bytes,
+            info.total_classes,
+            info.total_instances,
+            info.classloaders,
+            info.gc_roots,
+            info.objects_pending_finalization
+        ));
+        xml.push_str("  <classes>\n");
+        for class in &self.classes {
+            xml.push_str(&format!(
+                "    <class name=\"{}\" instances=\"{}\" instancesPercent=\"{:.1}\" size=\"{}\" sizePercent=\"{:.1}\"/>\n",
+                html_escape(&class.name),
+                class.instances,
+                class.instances_percent,
+                class.size,
+                class.size_percent
+            ));
+        }
+        xml.push_str("  </classes>\n</heapSummary>\n");
+        xml
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn summary() -> VisualVmSummary {
+        VisualVmSummary {
+            basic_info: VisualVmBasicInfo {
+                total_bytes: 300,
+                total_classes: 2,
+                total_instances: 4,
+                classloaders: 1,
+                gc_roots: 3,
+                objects_pending_finalization: 0,
+            },
+            classes: vec![VisualVmClass {
+                name: "java.util.Map<K>".to_string(),
+                instances: 1,
+                instances_percent: 25.0,
+                size: 100,
+                size_percent: 100.0 / 3.0,
+            }],
+        }
+    }
+
+    #[test]
+    fn render_visualvm_json() {
+        let json = summary().render_json();
+        assert!(json.contains("\"basicInfo\":{\"totalBytes\":300,\"totalClasses\":2,\"totalInstances\":4,\"classloaders\":1,\"gcRoots\":3,\"objectsPendingFinalization\":0}"));
+        assert!(json.ends_with("\"classes\":[{\"name\":\"java.util.Map<K>\",\"instances\":1,\"instancesPercent\":25.0,\"size\":100,\"sizePercent\":33.3}]}\n"));
+    }
+
+    #[test]
+    fn render_visualvm_xml() {
+        let xml = summary().render_xml();
+        assert!(xml.starts_with("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<heapSummary"));
+        assert!(xml.contains("    <gcRoots>3</gcRoots>\n"));
+        assert!(xml.contains("<class name=\"java.util.Map&lt;K&gt;\" instances=\"1\" instancesPercent=\"25.0\" size=\"100\" sizePercent=\"33.3\"/>"));
+    }
+}
@@ -1,34 +1,29 @@
 use crossbeam_channel::{Receiver, Sender};
-use indoc::formatdoc;
-use log::info;
+use serde::{Deserialize, Serialize};
 
-use std::collections::HashMap;
-use std::ops::Deref;
+use std::collections::{HashMap, HashSet};
+use std::ops::ControlFlow;
 use std::sync::Arc;
 use std::thread::JoinHandle;
 use std::{mem, thread};
 
+use crate::class_registry::ClassRegistry;
 use crate::parser::gc_record::*;
-use crate::parser::record::{LoadClassData, Record, StackFrameData, StackTraceData};
-use crate::parser::record::{Record::*, ThreadEndData, ThreadStartData};
+use crate::parser::record::Record::*;
+use crate::parser::record::{Record, StackFrameData, StackTraceData};
+use crate::parser::record_stream_parser::{RecordBatch, StreamPosition};
+use crate::parser::record_visitor::RecordVisitor;
+use crate::progress::{Phase, Progress};
+pub use crate::report::{RenderedResult, RenderedSection, SectionKind};
+#[cfg(feature = "checkpoint")]
+use crate::slurp::CheckpointOptions;
+use crate::slurp::SlurpOptions;
 use crate::utils::pretty_bytes_size;
+#[cfg(feature = "checkpoint")]
+use log::info;
+use log::warn;
 
-#[derive(Debug, Copy, Clone)]
-pub struct ClassInfo {
-    super_class_object_id: u64,
-    instance_size: u32,
-}
-
-impl ClassInfo {
-    fn new(super_class_object_id: u64, instance_size: u32) -> Self {
-        Self {
-            super_class_object_id,
-            instance_size,
-        }
-    }
-}
-
-#[derive(Debug, Copy, Clone)]
+#[derive(Debug, Copy, Clone, Serialize, Deserialize)]
 pub struct ClassInstanceCounter {
     number_of_instances: u64,
 }
@@ -45,7 +40,7 @@ impl ClassInstanceCounter {
     }
 }
 
-#[derive(Debug, Copy, Clone)]
+#[derive(Debug, Copy, Clone, Serialize, Deserialize)]
 pub struct ArrayCounter {
     number_of_arrays: u64,
     max_size_seen: u32,
@@ -69,41 +64,224 @@ impl ArrayCounter {
         }
     }
 }
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct RootThreadObject {
     pub thread_object_id: u64,
     pub thread_sequence_number: u32,
     pub stack_sequence_number: u32,
 }
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct RootJniGlobal {
     pub object_id: u64,
     pub jni_global_ref_id: u64,
 }
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct RootJniLocal {
     pub object_id: u64,
     pub thread_serial_number: u32,
     pub frame_number_in_stack_trace: u32,
 }
-pub struct RenderedResult {
-    pub summary: String,
-    pub thread_info: String,
-    pub memory_usage: String,
-    pub captured_strings: Option<String>,
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RootJavaFrame {
+    pub object_id: u64,
+    pub thread_serial_number: u32,
+    pub frame_number_in_stack_trace: u32,
 }
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RootNativeStack {
+    pub object_id: u64,
+    pub thread_serial_number: u32,
+}
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RootThreadBlock {
+    pub object_id: u64,
+    pub thread_serial_number: u32,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Instance {
     pub object_id: u64,
     pub stack_trace_serial_number: u32,
     pub class_object_id: u64,
-    pub data_size: u32,
+    pub data_size: u64,
     pub fields: Vec<(u64, Values)>,
     pub super_fields: Vec<(u64, Values)>,
 }
 
+impl Instance {
+    /// Returns the elements if the instance is a primitive or object array, decoding primitive arrays on first access.
+    pub fn array_value(&self) -> Option<&ArrayValue> {
+        match self.fields.first() {
+            Some((_, Values::Array(array))) => Some(array),
+            Some((_, Values::PrimitiveArray(array))) => Some(array.decoded()),
+            _ => None,
+        }
+    }
+
+    /// Element type of arrays, `None` for instances.
+    pub fn array_element_type(&self) -> Option<FieldType> {
+        match self.fields.first() {
+            Some((_, Values::Array(ArrayValue::Object(_)))) => Some(FieldType::Object),
+            Some((_, Values::Array(_))) => None,
+            Some((_, Values::PrimitiveArray(array))) => Some(array.element_type),
+            _ => None,
+        }
+    }
+
+    pub fn object_array(&self) -> Option<&[u64]> {
+        match self.fields.first() {
+            Some((_, Values::Array(ArrayValue::Object(ids)))) => Some(ids),
+            _ => None,
+        }
+    }
+
+    /// Returns the undecoded elements of primitive arrays.
+    pub fn primitive_array(&self) -> Option<&PrimitiveArray> {
+        match self.fields.first() {
+            Some((_, Values::PrimitiveArray(array))) => Some(array),
+            _ => None,
+        }
+    }
+
+    /// Returns false for arrays whose elements were dropped or not sampled while slurping.
+    pub fn elements_retained(&self) -> bool {
+        match (self.primitive_array(), self.object_array()) {
+            (Some(array), _) => array.number_of_elements > 0 || self.data_size == 0,
+            (_, Some(ids)) => !ids.is_empty() || self.data_size == 0,
+            _ => true,
+        }
+    }
+
+    /// Iterates over all field values, including the ones declared by super classes.
+    pub fn all_fields(&self) -> impl Iterator<Item = &(u64, Values)> {
+        self.fields.iter().chain(self.super_fields.iter())
+    }
+}
+
+/// Heap dump segments found in the file and the anomalies in their sequence.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SegmentStats {
+    // a segmented heap dump or a single `HEAP DUMP` record, each taken at a different time
+    pub heap_dumps: u64,
+    // `HEAP DUMP` and `HEAP DUMP SEGMENT` records
+    pub segments: u64,
+    pub segment_bytes: u64,
+    pub largest_segment_bytes: u64,
+    pub heap_dump_ends: u64,
+    // a segmented heap dump was started and not ended yet
+    pub open: bool,
+    // `HEAP DUMP` records while a segmented heap dump is open
+    pub nested_starts: u64,
+    // `HEAP DUMP END` records without a segmented heap dump open
+    pub unmatched_ends: u64,
+}
+
+impl SegmentStats {
+    // returns the anomaly the first time it is found, later ones are only counted
+    pub(crate) fn record_start(&mut self, length: u32, segment: bool) -> Option<&'static str> {
+        if !segment || !self.open {
+            self.heap_dumps += 1;
+        }
+        self.segments += 1;
+        self.segment_bytes += length as u64;
+        self.largest_segment_bytes = self.largest_segment_bytes.max(length as u64);
+        let anomaly = if !segment && self.open {
+            self.nested_starts += 1;
+            (self.nested_starts == 1)
+                .then_some("heap dump started within an open segmented heap dump")
+        } else {
+            None
+        };
+        if segment {
+            self.open = true;
+        }
+        anomaly
+    }
+
+    // same as `record_start`
+    pub(crate) fn record_end(&mut self) -> Option<&'static str> {
+        self.heap_dump_ends += 1;
+        if self.open {
+            self.open = false;
+            None
+        } else {
+            self.unmatched_ends += 1;
+            (self.unmatched_ends == 1).then_some("heap dump end found without a heap dump segment")
+        }
+    }
+
+    /// Describes the anomalies found, a heap dump still open means a truncated file.
+    pub fn anomalies(&self) -> Vec<String> {
+        let mut anomalies = Vec::new();
+        if self.nested_starts > 0 {
+            anomalies.push(format!(
+                "{} heap dumps nested in a segmented heap dump",
+                self.nested_starts
+            ));
+        }
+        if self.unmatched_ends > 0 {
+            anomalies.push(format!(
+                "{} heap dump ends without segments",
+                self.unmatched_ends
+            ));
+        }
+        if self.open {
+            anomalies.push("segmented heap dump not ended, the file may be truncated".to_string());
+        }
+        anomalies
+    }
+}
+
+// decoded instance fields take about four times their dump size once materialized
+const INSTANCE_FIELDS_EXPANSION: u64 = 4;
+
+/// Payloads dropped once the estimated memory crossed `SlurpOptions::max_memory`.
+///
+/// Dropped payloads are still counted and sized like with the retention options.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct MemoryDegradation {
+    // estimated memory when the budget was crossed
+    pub estimated_bytes: u64,
+    pub dropped_instance_fields: u64,
+    pub dropped_primitive_arrays: u64,
+    pub dropped_object_arrays: u64,
+    pub dropped_bytes: u64,
+}
+
+/// Checked by the recorder after each batch of records, returning `true` stops reading the input.
+///
+/// Targeted queries stop once the records they need are recorded, e.g. a class and its class dump.
+#[derive(Clone)]
+pub struct StopCondition(Arc<dyn Fn(&ResultRecorder) -> bool + Send + Sync>);
+
+impl StopCondition {
+    pub fn new<F>(condition: F) -> Self
+    where
+        F: Fn(&ResultRecorder) -> bool + Send + Sync + 'static,
+    {
+        StopCondition(Arc::new(condition))
+    }
+
+    pub fn is_met(&self, recorder: &ResultRecorder) -> bool {
+        (self.0)(recorder)
+    }
+}
+
+impl std::fmt::Debug for StopCondition {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("StopCondition")
+    }
+}
+
+/// Aggregates the records, serializable to checkpoint an ongoing run.
+#[derive(Serialize, Deserialize)]
 pub struct ResultRecorder {
     pub id_size: u32,
+    #[serde(skip)]
+    pub options: SlurpOptions,
+    #[cfg(feature = "checkpoint")]
+    #[serde(skip)]
+    checkpoint: Option<CheckpointState>,
     // Tag counters
     pub classes_unloaded: i32,
     pub stack_frames: i32,
@@ -116,27 +294,35 @@ pub struct ResultRecorder {
     pub control_settings: i32,
     pub cpu_samples: i32,
     // GC tag counters
-    pub heap_dump_segments_all_sub_records: i32,
-    pub heap_dump_segments_gc_root_unknown: i32,
-    pub heap_dump_segments_gc_root_thread_object: i32,
-    pub heap_dump_segments_gc_root_jni_global: i32,
-    pub heap_dump_segments_gc_root_jni_local: i32,
-    pub heap_dump_segments_gc_root_java_frame: i32,
-    pub heap_dump_segments_gc_root_native_stack: i32,
-    pub heap_dump_segments_gc_root_sticky_class: i32,
-    pub heap_dump_segments_gc_root_thread_block: i32,
-    pub heap_dump_segments_gc_root_monitor_used: i32,
-    pub heap_dump_segments_gc_object_array_dump: i32,
-    pub heap_dump_segments_gc_instance_dump: i32,
-    pub heap_dump_segments_gc_primitive_array_dump: i32,
-    pub heap_dump_segments_gc_class_dump: i32,
+    pub heap_dump_segments_all_sub_records: u64,
+    pub heap_dump_segments_gc_root_unknown: u64,
+    pub heap_dump_segments_gc_root_thread_object: u64,
+    pub heap_dump_segments_gc_root_jni_global: u64,
+    pub heap_dump_segments_gc_root_jni_local: u64,
+    pub heap_dump_segments_gc_root_java_frame: u64,
+    pub heap_dump_segments_gc_root_native_stack: u64,
+    pub heap_dump_segments_gc_root_sticky_class: u64,
+    pub heap_dump_segments_gc_root_thread_block: u64,
+    pub heap_dump_segments_gc_root_monitor_used: u64,
+    pub heap_dump_segments_gc_object_array_dump: u64,
+    pub heap_dump_segments_gc_instance_dump: u64,
+    pub heap_dump_segments_gc_primitive_array_dump: u64,
+    pub heap_dump_segments_gc_class_dump: u64,
+    // primitive arrays sharing the payload of an identical array recorded before
+    pub deduplicated_primitive_arrays: u64,
+    pub deduplicated_primitive_array_bytes: u64,
+    // rough size of the captured state, checked against `SlurpOptions::max_memory`
+    pub estimated_memory: u64,
+    pub degradation: Option<MemoryDegradation>,
+    // input offset at which `SlurpOptions::stop_when` stopped the run, only set once it is over
+    #[serde(skip)]
+    pub stopped_at: Option<usize>,
+    pub segments: SegmentStats,
+    // object id -> offset of its record in the input, only with `SlurpOptions::keep_record_offsets`
+    pub record_offsets: HashMap<u64, u64>,
     // Captured state
     // "object_id" -> "class_id" -> "class_name_id" -> "utf8_string"
     pub utf8_strings_by_id: HashMap<u64, Box<str>>,
-    pub class_data: Vec<LoadClassData>,        // holds class_data
-    pub class_data_by_id: HashMap<u64, usize>, // value is index into class_data
-    pub class_data_by_serial_number: HashMap<u32, usize>, // value is index into class_data
-    pub classes_single_instance_size_by_id: HashMap<u64, ClassInfo>,
     pub classes_dump: HashMap<u64, ClassDumpFields>,
     pub classes_all_instance_total_size_by_id: HashMap<u64, ClassInstanceCounter>,
     pub primitive_array_counters: HashMap<FieldType, ArrayCounter>,
@@ -147,20 +333,41 @@ pub struct ResultRecorder {
     //add
     pub dump_instances: Vec<GcRecord>,
     pub dump_primitive_array_dump: Vec<GcRecord>,
+    // rebuilt when resuming from a checkpoint
+    #[serde(skip)]
+    primitive_array_payloads: HashSet<Arc<[u8]>>,
     pub dump_object_array_dump: Vec<GcRecord>,
     pub instances: HashMap<u64, Arc<Instance>>,
 
-    pub load_class: HashMap<u64, LoadClassData>,
+    pub class_registry: ClassRegistry,
 
     pub root_thread_object: HashMap<u64, RootThreadObject>,
-    pub root_jni_local: HashMap<u64, RootJniLocal>,
+    pub root_jni_local: Vec<RootJniLocal>,
     pub root_jni_global: HashMap<u64, RootJniGlobal>,
+    pub root_java_frame: Vec<RootJavaFrame>,
+    pub root_native_stack: Vec<RootNativeStack>,
+    pub root_thread_block: Vec<RootThreadBlock>,
+    pub root_sticky_class: Vec<u64>,
+    pub root_monitor_used: Vec<u64>,
+    pub root_unknown: Vec<u64>,
+}
+
+// where the recorder saves its state and the input it is taken from
+#[cfg(feature = "checkpoint")]
+struct CheckpointState {
+    options: CheckpointOptions,
+    input_files: Vec<String>,
+    input_len: Option<usize>,
+    last_offset: usize,
 }
 
 impl ResultRecorder {
-    pub fn new(id_size: u32) -> Self {
+    pub fn new(id_size: u32, options: SlurpOptions) -> Self {
         ResultRecorder {
             id_size,
+            options,
+            #[cfg(feature = "checkpoint")]
+            checkpoint: None,
             classes_unloaded: 0,
             stack_frames: 0,
             stack_traces: 0,
@@ -185,11 +392,14 @@ impl ResultRecorder {
             heap_dump_segments_gc_primitive_array_dump: 0,
             heap_dump_segments_gc_instance_dump: 0,
             heap_dump_segments_gc_class_dump: 0,
+            deduplicated_primitive_arrays: 0,
+            deduplicated_primitive_array_bytes: 0,
+            estimated_memory: 0,
+            degradation: None,
+            stopped_at: None,
+            segments: SegmentStats::default(),
+            record_offsets: HashMap::new(),
             utf8_strings_by_id: HashMap::new(),
-            class_data: vec![],
-            class_data_by_id: HashMap::new(),
-            class_data_by_serial_number: HashMap::default(),
-            classes_single_instance_size_by_id: HashMap::new(),
             classes_all_instance_total_size_by_id: HashMap::new(),
             primitive_array_counters: HashMap::new(),
             object_array_counters: HashMap::new(),
@@ -198,73 +408,221 @@ impl ResultRecorder {
             stack_frame_by_id: HashMap::default(),
             dump_instances: Vec::default(),
             dump_primitive_array_dump: Vec::default(),
+            primitive_array_payloads: HashSet::default(),
             instances: HashMap::default(),
-            load_class: HashMap::default(),
+            class_registry: ClassRegistry::default(),
             root_jni_global: HashMap::default(),
-            root_jni_local: HashMap::default(),
+            root_jni_local: Vec::new(),
             root_thread_object: HashMap::default(),
+            root_java_frame: Vec::new(),
+            root_native_stack: Vec::new(),
+            root_thread_block: Vec::new(),
+            root_sticky_class: Vec::default(),
+            root_monitor_used: Vec::default(),
+            root_unknown: Vec::default(),
             dump_object_array_dump: Vec::default(),
         }
     }
 
-    fn get_class_name_string(&self, class_id: &u64) -> String {
-        self.class_data_by_id
-            .get(class_id)
-            .and_then(|data_index| self.class_data.get(*data_index))
-            .and_then(|class_data| self.utf8_strings_by_id.get(&class_data.class_name_id))
-            .expect("class_id must have an UTF-8 string representation available")
-            .replace('/', ".")
+    // identical payloads (e.g. zeroed buffers or constant tables) share a single allocation
+    fn deduplicate(&mut self, bytes: Arc<[u8]>) -> Arc<[u8]> {
+        if bytes.is_empty() || !self.options.deduplicate_primitive_arrays {
+            return bytes;
+        }
+        if let Some(shared) = self.primitive_array_payloads.get(&bytes) {
+            self.deduplicated_primitive_arrays += 1;
+            self.deduplicated_primitive_array_bytes += bytes.len() as u64;
+            return shared.clone();
+        }
+        self.primitive_array_payloads.insert(bytes.clone());
+        bytes
+    }
+
+    // grows the estimate, payloads are dropped from now on if it crosses the budget
+    fn account(&mut self, bytes: u64) {
+        self.estimated_memory += bytes;
+        match self.options.max_memory {
+            Some(max_memory)
+                if self.estimated_memory > max_memory && self.degradation.is_none() =>
+            {
+                warn!(
+                    "Estimated memory {} exceeds the budget of {}, instance fields and array elements are no longer retained.",
+                    pretty_bytes_size(self.estimated_memory),
+                    pretty_bytes_size(max_memory)
+                );
+                self.degradation = Some(MemoryDegradation {
+                    estimated_bytes: self.estimated_memory,
+                    ..Default::default()
+                });
+            }
+            _ => (),
+        }
+    }
+
+    // the retention option still applies while within budget, dropping a wanted payload is reported
+    fn keep_payload(
+        &mut self,
+        wanted: bool,
+        len: usize,
+        dropped: fn(&mut MemoryDegradation) -> &mut u64,
+    ) -> bool {
+        match &mut self.degradation {
+            Some(degradation) if wanted => {
+                *dropped(degradation) += 1;
+                degradation.dropped_bytes += len as u64;
+                false
+            }
+            _ => wanted,
+        }
+    }
+
+    /// Restores the options and the state not saved in checkpoints.
+    pub fn resume(mut self, options: SlurpOptions) -> Self {
+        self.options = options;
+        if self.options.deduplicate_primitive_arrays {
+            for record in &self.dump_primitive_array_dump {
+                if let GcRecord::PrimitiveArrayDump { bytes_ref, .. } = record {
+                    if !bytes_ref.is_empty() {
+                        self.primitive_array_payloads.insert(bytes_ref.clone());
+                    }
+                }
+            }
+        }
+        self
+    }
+
+    /// Saves the state every `options.interval` bytes of input after `offset`.
+    #[cfg(feature = "checkpoint")]
+    pub fn with_checkpoints(
+        mut self,
+        options: CheckpointOptions,
+        input_files: Vec<String>,
+        input_len: Option<usize>,
+        offset: usize,
+    ) -> Self {
+        self.checkpoint = Some(CheckpointState {
+            options,
+            input_files,
+            input_len,
+            last_offset: offset,
+        });
+        self
+    }
+
+    #[cfg(feature = "checkpoint")]
+    fn checkpoint_if_due(&mut self, position: StreamPosition) {
+        let Some(checkpoint) = &self.checkpoint else {
+            return;
+        };
+        if position.offset < checkpoint.last_offset + checkpoint.options.interval {
+            return;
+        }
+        match crate::checkpoint::write_checkpoint(
+            &checkpoint.options.path,
+            &checkpoint.input_files,
+            checkpoint.input_len,
+            position,
+            self,
+        ) {
+            Ok(()) => info!("Checkpoint saved at offset {}.", position.offset),
+            // the run itself can still complete
+            Err(e) => warn!("Failed to save checkpoint: {}", e),
+        }
+        if let Some(checkpoint) = &mut self.checkpoint {
+            checkpoint.last_offset = position.offset;
+        }
     }
 
     pub fn start(
         mut self,
-        receive_records: Receiver<Vec<Record>>,
+        receive_records: Receiver<RecordBatch>,
         send_result: Sender<Self>,
         send_pooled_vec: Sender<Vec<Record>>,
+        send_progress: Sender<Progress>,
+        input_len: Option<usize>,
     ) -> std::io::Result<JoinHandle<()>> {
         thread::Builder::new()
             .name("hprof-recorder".to_string())
             .spawn(move || {
-                loop {
-                    match receive_records.recv() {
-                        Ok(mut records) => {
-                            self.record_records(&mut records);
-                            // clear values but retain underlying storage
-                            records.clear();
-                            // send back pooled vec (swallow errors as it is possible the receiver was already dropped)
-                            send_pooled_vec.send(records).unwrap_or_default();
-                        }
-                        Err(_) => {
-                            // no more Record to pull, generate and send back results
-
-                            send_result
-                                .send(self)
-                                .expect("channel should not be closed");
-                            break;
-                        }
+                while let Ok(mut batch) = receive_records.recv() {
+                    self.record_records(&mut batch.records, &batch.offsets);
+                    // clear values but retain underlying storage
+                    batch.records.clear();
+                    // send back pooled vec (swallow errors as it is possible the receiver was already dropped)
+                    send_pooled_vec.send(batch.records).unwrap_or_default();
+                    send_progress
+                        .send(Progress::new(
+                            Phase::Recording,
+                            batch.position.offset as u64,
+                            input_len.map(|len| len as u64),
+                        ))
+                        .unwrap_or_default();
+                    if self.visit_batch_end(batch.position).is_break() {
+                        // dropping the receiver stops the parser, which stops the pre-fetcher
+                        break;
                     }
                 }
+                // no more Record to pull, generate and send back results
+                drop(receive_records);
+                send_result
+                    .send(self)
+                    .expect("channel should not be closed");
             })
     }
 
-    fn record_records(&mut self, records: &mut [Record]) {
-        records.iter_mut().for_each(|record| match record {
+    // classes, instances and arrays only
+    fn record_offset(&mut self, gc_record: &GcRecord, offset: usize) {
+        let object_id = match gc_record {
+            GcRecord::InstanceDump { object_id, .. }
+            | GcRecord::ObjectArrayDump { object_id, .. }
+            | GcRecord::PrimitiveArrayDump { object_id, .. } => *object_id,
+            GcRecord::ClassDump(class) => class.class_object_id,
+            _ => return,
+        };
+        self.record_offsets.insert(object_id, offset as u64);
+    }
+
+    fn in_selected_dump(&self) -> bool {
+        match self.options.dump_index {
+            Some(index) => index as u64 + 1 == self.segments.heap_dumps,
+            None => true,
+        }
+    }
+
+    fn before_analyzed_dump(&self) -> bool {
+        match self.options.dump_index {
+            Some(index) => self.segments.heap_dumps <= index as u64,
+            None => self.segments.heap_dumps == 0,
+        }
+    }
+
+    fn record_records(&mut self, records: &mut [Record], offsets: &[usize]) {
+        for (i, record) in records.iter_mut().enumerate() {
+            self.visit_record(record, offsets.get(i).copied());
+        }
+    }
+}
+
+// the built-in visitor, see `RecordVisitor`
+impl RecordVisitor for ResultRecorder {
+    // the payloads are taken out of the records instead of being copied
+    fn visit_record(&mut self, record: &mut Record, offset: Option<usize>) {
+        match record {
             Utf8String { id, str } => {
+                self.account((mem::size_of::<(u64, Box<str>)>() + 1 + str.len()) as u64);
                 self.utf8_strings_by_id.insert(*id, mem::take(str));
             }
             LoadClass(load_class_data) => {
-                let class_object_id = load_class_data.class_object_id;
-                // let class_serial_number = load_class_data.serial_number;
-                // self.class_data.push(mem::take(load_class_data));
-                // let data_index = self.class_data.len() - 1;
-                // self.class_data_by_id.insert(class_object_id, data_index);
-                // self.class_data_by_serial_number
-                //     .insert(class_serial_number, data_index);
-
-                self.load_class
-                    .insert(class_object_id, load_class_data.clone());
+                // stack frames reference classes by serial number, resolved through `Heap::class_by_serial_number`
+                self.class_registry.load(mem::take(load_class_data));
+            }
+            UnloadClass { serial_number } => {
+                self.classes_unloaded += 1;
+                // classes unloaded after the analyzed heap dump still describe its objects
+                if self.before_analyzed_dump() {
+                    self.class_registry.unload(*serial_number);
+                }
             }
-            UnloadClass { .. } => self.classes_unloaded += 1,
             StackFrame(stack_frame_data) => {
                 self.stack_frames += 1;
                 self.stack_frame_by_id
@@ -275,14 +633,7 @@ impl ResultRecorder {
                 self.stack_trace_by_serial_number
                     .insert(stack_trace_data.serial_number, mem::take(stack_trace_data));
             }
-            StartThread {
-                thread_serial_number,
-                thread_object_id,
-                stack_trace_serial_number,
-                thread_name_id,
-                thread_group_name_id,
-                thread_group_parent_name_id,
-            } => {
+            StartThread { .. } => {
                 // self.thread_start.insert(
                 //     *thread_serial_number,
                 //     ThreadStartData {
@@ -295,9 +646,7 @@ impl ResultRecorder {
                 //     },
                 // );
             }
-            EndThread {
-                thread_serial_number,
-            } => {
+            EndThread { .. } => {
                 // self.thread_end.insert(
                 //     *thread_serial_number,
                 //     ThreadEndData {
@@ -314,39 +663,62 @@ impl ResultRecorder {
             } => self.heap_summaries += 1,
             ControlSettings { .. } => self.control_settings += 1,
             CpuSamples { .. } => self.cpu_samples += 1,
-            HeapDumpEnd { .. } => (),
-            HeapDumpStart { .. } => self.heap_dumps += 1,
+            HeapDumpEnd { .. } => {
+                if let Some(anomaly) = self.segments.record_end() {
+                    warn!("Unexpected segment sequence: {}", anomaly);
+                }
+            }
+            HeapDumpStart { length, segment } => {
+                self.heap_dumps += 1;
+                if let Some(anomaly) = self.segments.record_start(*length, *segment) {
+                    warn!("Unexpected segment sequence: {}", anomaly);
+                }
+            }
+            // sub-records of the heap dumps not selected are skipped
+            GcSegment(_) if !self.in_selected_dump() => (),
             GcSegment(gc_record) => {
                 self.heap_dump_segments_all_sub_records += 1;
+                if let Some(offset) = offset {
+                    self.record_offset(gc_record, offset);
+                }
                 match gc_record {
-                    GcRecord::RootUnknown { .. } => self.heap_dump_segments_gc_root_unknown += 1,
+                    GcRecord::RootUnknown { object_id } => {
+                        self.heap_dump_segments_gc_root_unknown += 1;
+                        if self.options.keep_roots {
+                            self.root_unknown.push(*object_id);
+                        }
+                    }
                     GcRecord::RootThreadObject {
                         thread_object_id,
                         thread_sequence_number,
                         stack_sequence_number,
                     } => {
                         self.heap_dump_segments_gc_root_thread_object += 1;
-                        self.root_thread_object.insert(
-                            *thread_object_id,
-                            RootThreadObject {
-                                thread_object_id: *thread_object_id,
-                                thread_sequence_number: *thread_sequence_number,
-                                stack_sequence_number: *stack_sequence_number,
-                            },
-                        );
+                        if self.options.keep_roots {
+                            self.root_thread_object.insert(
+                                *thread_object_id,
+                                RootThreadObject {
+                                    thread_object_id: *thread_object_id,
+                                    thread_sequence_number: *thread_sequence_number,
+                                    stack_sequence_number: *stack_sequence_number,
+                                },
+                            );
+                        }
                     }
                     GcRecord::RootJniGlobal {
                         object_id,
                         jni_global_ref_id,
                     } => {
                         self.heap_dump_segments_gc_root_jni_global += 1;
-                        self.root_jni_global.insert(
-                            *object_id,
-                            RootJniGlobal {
-                                object_id: *object_id,
-                                jni_global_ref_id: *jni_global_ref_id,
-                            },
-                        );
+                        if self.options.keep_roots {
+                            self.root_jni_global.insert(
+                                *object_id,
+                                RootJniGlobal {
+                                    object_id: *object_id,
+                                    jni_global_ref_id: *jni_global_ref_id,
+                                },
+                            );
+                        }
                     }
                     GcRecord::RootJniLocal {
                         object_id,
@@ -354,29 +726,63 @@ impl ResultRecorder {
                         frame_number_in_stack_trace,
                     } => {
                         self.heap_dump_segments_gc_root_jni_local += 1;
-                        self.root_jni_local.insert(
-                            *object_id,
-                            RootJniLocal {
+                        if self.options.keep_roots {
+                            self.root_jni_local.push(RootJniLocal {
                                 object_id: *object_id,
                                 thread_serial_number: *thread_serial_number,
                                 frame_number_in_stack_trace: *frame_number_in_stack_trace,
-                            },
-                        );
+                            });
+                        }
                     }
-                    GcRecord::RootJavaFrame { .. } => {
-                        self.heap_dump_segments_gc_root_java_frame += 1
+                    GcRecord::RootJavaFrame {
+                        object_id,
+                        thread_serial_number,
+                        frame_number_in_stack_trace,
+                    } => {
+                        self.heap_dump_segments_gc_root_java_frame += 1;
+                        if self.options.keep_roots {
+                            self.root_java_frame.push(RootJavaFrame {
+                                object_id: *object_id,
+                                thread_serial_number: *thread_serial_number,
+                                frame_number_in_stack_trace: *frame_number_in_stack_trace,
+                            });
+                        }
                     }
-                    GcRecord::RootNativeStack { .. } => {
-                        self.heap_dump_segments_gc_root_native_stack += 1
+                    GcRecord::RootNativeStack {
+                        object_id,
+                        thread_serial_number,
+                    } => {
+                        self.heap_dump_segments_gc_root_native_stack += 1;
+                        if self.options.keep_roots {
+                            self.root_native_stack.push(RootNativeStack {
+                                object_id: *object_id,
+                                thread_serial_number: *thread_serial_number,
+                            });
+                        }
                     }
-                    GcRecord::RootStickyClass { .. } => {
-                        self.heap_dump_segments_gc_root_sticky_class += 1
+                    GcRecord::RootStickyClass { object_id } => {
+                        self.heap_dump_segments_gc_root_sticky_class += 1;
+                        if self.options.keep_roots {
+                            self.root_sticky_class.push(*object_id);
+                        }
                     }
-                    GcRecord::RootThreadBlock { .. } => {
-                        self.heap_dump_segments_gc_root_thread_block += 1
+                    GcRecord::RootThreadBlock {
+                        object_id,
+                        thread_serial_number,
+                    } => {
+                        self.heap_dump_segments_gc_root_thread_block += 1;
+                        if self.options.keep_roots {
+                            self.root_thread_block.push(RootThreadBlock {
+                                object_id: *object_id,
+                                thread_serial_number: *thread_serial_number,
+                            });
+                        }
                     }
-                    GcRecord::RootMonitorUsed { .. } => {
-                        self.heap_dump_segments_gc_root_monitor_used += 1
+                    GcRecord::RootMonitorUsed { object_id } => {
+                        self.heap_dump_segments_gc_root_monitor_used += 1;
+                        if self.options.keep_roots {
+                            self.root_monitor_used.push(*object_id);
+                        }
                     }
                     GcRecord::InstanceDump {
                         object_id,
@@ -391,12 +797,22 @@ impl ResultRecorder {
                             .add_instance();
 
                         self.heap_dump_segments_gc_instance_dump += 1;
+                        let keep = self.keep_payload(
+                            self.options.keep_instance_fields,
+                            bytes_ref.len(),
+                            |d| &mut d.dropped_instance_fields,
+                        );
+                        let bytes_ref = retained_bytes(bytes_ref, keep);
+                        self.account(
+                            mem::size_of::<GcRecord>() as u64
+                                + bytes_ref.len() as u64 * INSTANCE_FIELDS_EXPANSION,
+                        );
                         self.dump_instances.push(GcRecord::InstanceDump {
                             object_id: *object_id,
                             stack_trace_serial_number: *stack_trace_serial_number,
                             class_object_id: *class_object_id,
                             data_size: *data_size,
-                            bytes_ref: bytes_ref.clone(),
+                            bytes_ref,
                         });
                     }
                     GcRecord::ObjectArrayDump {
@@ -411,12 +827,22 @@ impl ResultRecorder {
                             .or_insert_with(ArrayCounter::empty)
                             .add_elements_from_array(*number_of_elements);
 
+                        let keep = self.keep_payload(
+                            self.options.keep_object_arrays,
+                            bytes_ref.len(),
+                            |d| &mut d.dropped_object_arrays,
+                        );
+                        let bytes_ref = retained_bytes(bytes_ref, keep);
+                        // the elements are decoded into a vector of ids once materialized
+                        self.account(
+                            mem::size_of::<GcRecord>() as u64 + 2 * bytes_ref.len() as u64,
+                        );
                         self.dump_object_array_dump.push(GcRecord::ObjectArrayDump {
                             number_of_elements: *number_of_elements,
                             array_class_id: *array_class_id,
                             object_id: *object_id,
                             stack_trace_serial_number: *stack_trace_serial_number,
-                            bytes_ref: bytes_ref.clone(),
+                            bytes_ref,
                         });
                         self.heap_dump_segments_gc_object_array_dump += 1
                     }
@@ -434,448 +860,273 @@ impl ResultRecorder {
 
                         self.heap_dump_segments_gc_primitive_array_dump += 1;
 
+                        let keep = self.keep_payload(
+                            self.options.keep_primitive_arrays
+                                && is_sampled(*object_id, self.options.primitive_array_sample_rate),
+                            bytes_ref.len(),
+                            |d| &mut d.dropped_primitive_arrays,
+                        );
+                        let bytes = retained_bytes(bytes_ref, keep);
+                        let deduplicated_bytes = self.deduplicated_primitive_array_bytes;
+                        let bytes_ref = self.deduplicate(bytes);
+                        // shared payloads are only counted once
+                        let stored =
+                            if self.deduplicated_primitive_array_bytes == deduplicated_bytes {
+                                bytes_ref.len() as u64
+                            } else {
+                                0
+                            };
+                        self.account(mem::size_of::<GcRecord>() as u64 + stored);
                         self.dump_primitive_array_dump
                             .push(GcRecord::PrimitiveArrayDump {
                                 number_of_elements: *number_of_elements,
                                 element_type: *element_type,
                                 object_id: *object_id,
                                 stack_trace_serial_number: *stack_trace_serial_number,
-                                bytes_ref: bytes_ref.clone(),
+                                bytes_ref,
                             });
                     }
                     GcRecord::ClassDump(class_dump_fields) => {
                         let class_object_id = class_dump_fields.class_object_id;
                         self.classes_dump
                             .insert(class_object_id, *(*class_dump_fields).clone());
-                        self.classes_single_instance_size_by_id
-                            .entry(class_object_id)
-                            .or_insert_with(|| {
-                                let instance_size = class_dump_fields.instance_size;
-                                let super_class_object_id = class_dump_fields.super_class_object_id;
-                                ClassInfo::new(super_class_object_id, instance_size)
-                            });
-
                         self.heap_dump_segments_gc_class_dump += 1
                     }
                 }
             }
-        });
-    }
-
-    fn render_captured_strings(&self) -> String {
-        let mut strings: Vec<_> = self.utf8_strings_by_id.values().collect();
-        strings.sort();
-        let mut result = String::new();
-        result.push_str("\nList of Strings\n");
-        strings.iter().for_each(|s| {
-            result.push_str(s);
-            result.push('\n')
-        });
-        result
+        }
     }
 
-    fn render_thread_info(&self) -> String {
-        let mut thread_info = String::new();
-
-        // for each stacktrace
-        let mut stack_traces: Vec<_> = self
-            .stack_trace_by_serial_number
-            .iter()
-            .filter(|(_, stack)| !stack.stack_frame_ids.is_empty()) // omit empty stacktraces
-            .collect();
-
-        stack_traces.sort_by_key(|(serial_number, _)| **serial_number);
-
-        thread_info.push_str(&format!(
-            "\nFound {} threads with stacktraces:\n",
-            stack_traces.len()
-        ));
-
-        for (index, (_id, stack_data)) in stack_traces.iter().enumerate() {
-            thread_info.push_str(&format!("\nThread {}\n", index + 1));
-
-            //  for each stack frames
-            for stack_frame_id in &stack_data.stack_frame_ids {
-                let stack_frame = self.stack_frame_by_id.get(stack_frame_id).unwrap();
-                let class_object_id = self
-                    .class_data_by_serial_number
-                    .get(&stack_frame.class_serial_number)
-                    .and_then(|index| self.class_data.get(*index))
-                    .expect("Class not found")
-                    .class_object_id;
-                let class_name = self.get_class_name_string(&class_object_id);
-                let method_name = self
-                    .utf8_strings_by_id
-                    .get(&stack_frame.method_name_id)
-                    .map(|b| b.deref())
-                    .unwrap_or("unknown method name");
-                let file_name = self
-                    .utf8_strings_by_id
-                    .get(&stack_frame.source_file_name_id)
-                    .map(|b| b.deref())
-                    .unwrap_or("unknown source file");
-
-                // >0: normal
-                // -1: unknown
-                // -2: compiled method
-                // -3: native method
-                let pretty_line_number = match stack_frame.line_number {
-                    -1 => "unknown line number".to_string(),
-                    -2 => "compiled method".to_string(),
-                    -3 => "native method".to_string(),
-                    number => format!("{}", number),
-                };
-
-                // pretty frame output
-                let stack_frame_pretty = format!(
-                    "  at {}.{} ({}:{})\n",
-                    class_name, method_name, file_name, pretty_line_number
-                );
-                thread_info.push_str(&stack_frame_pretty);
-            }
+    fn visit_batch_end(&mut self, position: StreamPosition) -> ControlFlow<()> {
+        #[cfg(feature = "checkpoint")]
+        self.checkpoint_if_due(position);
+        let stop_when = self.options.stop_when.clone();
+        if stop_when.is_some_and(|condition| condition.is_met(self)) {
+            self.stopped_at = Some(position.offset);
+            return ControlFlow::Break(());
         }
-        thread_info
+        ControlFlow::Continue(())
     }
 
-    fn render_memory_usage(&self) -> String {
-        // https://www.baeldung.com/java-memory-layout
-        // total_size = object_header + data
-        // on a 64-bit arch.
-        // object_header = mark(ref_size) + klass(4) + padding_gap(4) = 16 bytes
-        // data = instance_size + padding_next(??)
-        let object_header = self.id_size + 4 + 4;
-
-        let mut classes_dump_vec: Vec<_> = self
-            .classes_all_instance_total_size_by_id
-            .iter()
-            .map(|(class_id, v)| {
-                let class_name = self.get_class_name_string(class_id);
-                let mut size = 0;
-
-                let ClassInfo {
-                    super_class_object_id,
-                    instance_size,
-                } = self
-                    .classes_single_instance_size_by_id
-                    .get(class_id)
-                    .unwrap();
-                let mut parent_class_id = *super_class_object_id;
-                size += instance_size;
-
-                // recursively add sizes from parent classes
-                while parent_class_id != 0 {
-                    let ClassInfo {
-                        super_class_object_id,
-                        instance_size,
-                    } = self
-                        .classes_single_instance_size_by_id
-                        .get(&parent_class_id)
-                        .unwrap();
-                    size += instance_size;
-                    parent_class_id = *super_class_object_id;
-                }
-                // add object header
-                size += object_header;
-                // add extra padding if any
-                size += size.rem_euclid(8);
-                let total_size = size as u64 * v.number_of_instances;
-                (
-                    class_name,
-                    v.number_of_instances,
-                    size as u64, // all instances have the same size
-                    total_size,
-                )
-            })
-            .collect();
-
-        // https://www.baeldung.com/java-memory-layout
-        // the array's `elements` size is already accounted for via `GcInstanceDump` for objects
-        // unlike primitives which are packed in the array itself
-        // array headers already aligned for 64-bit arch - no need for padding
-        // array_header = mark(ref_size) + klass(4) + array_length(4) = 16 bytes
-        // data_primitive = primitive_size * length + padding(??)
-        // data_object = ref_size * length (no padding because the ref size is already aligned!)
-        let ref_size = self.id_size as u64;
-        let array_header_size = ref_size + 4 + 4;
-
-        let array_primitives_dump_vec = self.primitive_array_counters.iter().map(|(ft, &ac)| {
-            let primitive_type = format!("{:?}", ft).to_lowercase();
-            let primitive_array_label = format!("{}[]", primitive_type);
-            let primitive_size = primitive_byte_size(ft);
-
-            let cost_of_all_array_headers = array_header_size * ac.number_of_arrays;
-            let cost_of_all_values = primitive_size * ac.total_number_of_elements;
-            // info lost at this point to compute the real padding for each array
-            // assume mid value of 4 bytes per array for an estimation
-            let estimated_cost_of_all_padding = ac.number_of_arrays * 4;
-
-            let cost_data_largest_array = primitive_size * ac.max_size_seen as u64;
-            let cost_padding_largest_array =
-                (array_header_size + cost_data_largest_array).rem_euclid(8);
-            (
-                primitive_array_label,
-                ac.number_of_arrays,
-                array_header_size + cost_data_largest_array + cost_padding_largest_array,
-                cost_of_all_array_headers + cost_of_all_values + estimated_cost_of_all_padding,
-            )
-        });
-
-        // For array of objects we are interested in the total size of the array headers and outgoing elements references
-        let array_objects_dump_vec = self.object_array_counters.iter().map(|(class_id, &ac)| {
-            let raw_class_name = self.get_class_name_string(class_id);
-            let cleaned_class_name: String = if raw_class_name.starts_with("[L") {
-                // remove '[L' prefix and ';' suffix
-                raw_class_name
-                    .chars()
-                    .skip(2)
-                    .take(raw_class_name.chars().count() - 3)
-                    .collect()
-            } else if raw_class_name.starts_with("[[L") {
-                // remove '[[L' prefix and ';' suffix
-                raw_class_name
-                    .chars()
-                    .skip(3)
-                    .take(raw_class_name.chars().count() - 4)
-                    .collect()
-            } else {
-                // TODO: what are those ([[C, [[D, [[B, [[S ...)? boxed primitives are already present
-                raw_class_name
-            };
-
-            let object_array_label = format!("{}[]", cleaned_class_name);
-
-            let cost_of_all_refs = ref_size * ac.total_number_of_elements;
-            let cost_of_all_array_headers = array_header_size * ac.number_of_arrays;
-            let cost_of_largest_array_refs = ref_size * ac.max_size_seen as u64;
-            (
-                object_array_label,
-                ac.number_of_arrays,
-                array_header_size + cost_of_largest_array_refs,
-                cost_of_all_array_headers + cost_of_all_refs,
-            )
-        });
-
-        // Merge results
-        classes_dump_vec.extend(array_primitives_dump_vec);
-        classes_dump_vec.extend(array_objects_dump_vec);
+    fn takes_payloads(&self) -> bool {
+        true
+    }
+}
 
-        // Holds the final result
-        let mut analysis = String::new();
+// payloads not retained are replaced by an empty slice, the heap then only knows the object's size
+fn retained_bytes<B: Default>(bytes_ref: &mut B, keep: bool) -> B {
+    if keep {
+        mem::take(bytes_ref)
+    } else {
+        B::default()
+    }
+}
 
-        // Total heap size found banner
-        let total_size = classes_dump_vec.iter().map(|(_, _, _, s)| *s).sum();
-        let display_total_size = pretty_bytes_size(total_size);
-        let allocation_classes_title = format!(
-            "Found a total of {} of instances allocated on the heap.\n",
-            display_total_size
-        );
-        analysis.push_str(&allocation_classes_title);
+// deterministic per object id so that repeated runs retain the same arrays
+fn is_sampled(mut object_id: u64, sample_rate: f64) -> bool {
+    if sample_rate >= 1.0 {
+        return true;
+    }
+    // hashed to spread the aligned object ids uniformly
+    let hash = crate::splitmix64(&mut object_id);
+    (hash as f64) < sample_rate * u64::MAX as f64
+}
 
-        // Sort by class name first for stability in test results :s
-        classes_dump_vec.sort_by(|a, b| b.0.cmp(&a.0));
+pub fn primitive_byte_size(field_type: &FieldType) -> u64 {
+    match field_type {
+        FieldType::Byte | FieldType::Bool => 1,
+        FieldType::Char | FieldType::Short => 2,
+        FieldType::Float | FieldType::Int => 4,
+        FieldType::Double | FieldType::Long => 8,
+        FieldType::Object => panic!("object type in primitive array"),
+    }
+}
 
-        // Top allocated classes analysis
-        // let allocation_classes_title = format!("\nTop {} allocated classes:\n\n", top);
-        analysis.push_str(&allocation_classes_title);
-        classes_dump_vec.sort_by(|a, b| b.3.cmp(&a.3));
-        // ResultRecorder::render_table(self.top, &mut analysis, classes_dump_vec.as_slice());
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-        // Top largest instances analysis
-        // let allocation_largest_title = format!("\nTop {} largest instances:\n\n", top);
-        // analysis.push_str(&allocation_largest_title);
-        classes_dump_vec.sort_by(|a, b| b.2.cmp(&a.2));
-        // ResultRecorder::render_table(self.top, &mut analysis, classes_dump_vec.as_slice());
+    #[test]
+    fn array_counter_beyond_32_bits() {
+        let mut counter = ArrayCounter::empty();
+        for _ in 0..3 {
+            counter.add_elements_from_array(u32::MAX);
+        }
+        assert_eq!(counter.number_of_arrays, 3);
+        assert_eq!(counter.total_number_of_elements, 3 * u32::MAX as u64);
+    }
 
-        analysis
+    #[test]
+    fn segment_sequence_anomalies() {
+        let mut stats = SegmentStats::default();
+        assert_eq!(stats.record_start(100, true), None);
+        assert_eq!(stats.record_start(300, true), None);
+        assert!(stats.record_start(10, false).is_some());
+        assert_eq!(stats.record_end(), None);
+        assert!(stats.record_end().is_some());
+        // a segment after an end starts the next heap dump
+        assert_eq!(stats.record_start(50, true), None);
+        assert_eq!(stats.heap_dumps, 3);
+        assert_eq!(stats.segments, 4);
+        assert_eq!(stats.segment_bytes, 460);
+        assert_eq!(stats.largest_segment_bytes, 300);
+        assert_eq!(stats.anomalies().len(), 3);
+
+        let mut balanced = SegmentStats::default();
+        balanced.record_start(100, true);
+        balanced.record_end();
+        assert!(balanced.anomalies().is_empty());
     }
 
-    // Render table from [(class_name, count, largest_allocation, instance_size)]
-    fn render_table(top: usize, analysis: &mut String, rows: &[(String, u64, u64, u64)]) {
-        let rows_formatted: Vec<_> = rows
-            .iter()
-            .take(top)
-            .map(|(class_name, count, largest_allocation, allocation_size)| {
-                let display_allocation = pretty_bytes_size(*allocation_size);
-                let largest_display_allocation = pretty_bytes_size(*largest_allocation);
-                (
-                    display_allocation,
-                    *count,
-                    largest_display_allocation,
-                    class_name,
-                )
+    #[test]
+    fn classes_unloaded_before_the_heap_dump() {
+        let mut recorder = ResultRecorder::new(8, SlurpOptions::default());
+        let load_class = |serial_number: u32| {
+            LoadClass(crate::parser::record::LoadClassData {
+                serial_number,
+                class_object_id: 0x100 * serial_number as u64,
+                stack_trace_serial_number: 0,
+                class_name_id: serial_number as u64,
             })
-            .collect();
-
-        let total_size_header = "Total size";
-        let total_size_header_padding = ResultRecorder::padding_for_header(
-            rows_formatted.as_slice(),
-            |r| r.0.to_string(),
-            total_size_header,
-        );
-        let total_size_len =
-            total_size_header.chars().count() + total_size_header_padding.chars().count();
-
-        let instance_count_header = "Instances";
-        let instance_count_header_padding = ResultRecorder::padding_for_header(
-            rows_formatted.as_slice(),
-            |r| r.1.to_string(),
-            instance_count_header,
-        );
-        let instance_len =
-            instance_count_header.chars().count() + instance_count_header_padding.chars().count();
-
-        let largest_instance_header = "Largest";
-        let largest_instance_padding = ResultRecorder::padding_for_header(
-            rows_formatted.as_slice(),
-            |r| r.2.to_string(),
-            largest_instance_header,
-        );
-        let largest_len =
-            largest_instance_header.chars().count() + largest_instance_padding.chars().count();
-
-        let class_name_header = "Class name";
-        let class_name_padding = ResultRecorder::padding_for_header(
-            rows_formatted.as_slice(),
-            |r| r.3.to_string(),
-            class_name_header,
-        );
-
-        let header = format!(
-            "{}{} | {}{} | {}{} | {}{}\n",
-            total_size_header_padding,
-            total_size_header,
-            instance_count_header_padding,
-            instance_count_header,
-            largest_instance_padding,
-            largest_instance_header,
-            class_name_header,
-            class_name_padding
-        );
-        let header_len = header.chars().count();
-        analysis.push_str(&header);
-        analysis.push_str(&("-".repeat(header_len)));
-        analysis.push('\n');
-
-        rows_formatted.into_iter().for_each(
-            |(allocation_size, count, largest_allocation_size, class_name)| {
-                let padding_size_str =
-                    ResultRecorder::column_padding(&allocation_size, total_size_len);
-                let padding_count_str =
-                    ResultRecorder::column_padding(&count.to_string(), instance_len);
-                let padding_largest_size_str =
-                    ResultRecorder::column_padding(&largest_allocation_size, largest_len);
-
-                let row = format!(
-                    "{}{} | {}{} | {}{} | {}\n",
-                    padding_size_str,
-                    allocation_size,
-                    padding_count_str,
-                    count,
-                    padding_largest_size_str,
-                    largest_allocation_size,
-                    class_name
-                );
-                analysis.push_str(&row);
+        };
+        let mut records = vec![
+            load_class(1),
+            load_class(2),
+            UnloadClass { serial_number: 1 },
+            HeapDumpStart {
+                length: 0,
+                segment: true,
             },
-        );
+            UnloadClass { serial_number: 2 },
+        ];
+        recorder.record_records(&mut records, &[]);
+        assert_eq!(recorder.classes_unloaded, 2);
+        assert!(recorder.class_registry.is_unloaded(1));
+        assert!(!recorder.class_registry.is_unloaded(2));
+        assert_eq!(recorder.class_registry.len(), 1);
     }
 
-    fn padding_for_header<F>(
-        rows: &[(String, u64, String, &String)],
-        field_selector: F,
-        header_label: &str,
-    ) -> String
-    where
-        F: Fn(&(String, u64, String, &String)) -> String,
-    {
-        let max_elem_size = rows
-            .iter()
-            .map(|d| field_selector(d).chars().count())
-            .max_by(|x, y| x.cmp(y))
-            .expect("Results can't be empty");
+    #[test]
+    fn payloads_dropped_above_the_memory_budget() {
+        let record_bytes = mem::size_of::<GcRecord>() as u64;
+        let instance_bytes = record_bytes + 16 * INSTANCE_FIELDS_EXPANSION;
+        let options = SlurpOptions {
+            max_memory: Some(instance_bytes),
+            ..SlurpOptions::default()
+        };
+        let mut recorder = ResultRecorder::new(8, options);
+        let mut records: Vec<Record> = (1..=4)
+            .map(|object_id| {
+                GcSegment(GcRecord::InstanceDump {
+                    object_id,
+                    stack_trace_serial_number: 0,
+                    class_object_id: 100,
+                    data_size: 16,
+                    bytes_ref: vec![0; 16].into_boxed_slice(),
+                })
+            })
+            .collect();
+        recorder.record_records(&mut records, &[]);
 
-        ResultRecorder::column_padding(header_label, max_elem_size)
+        // the second instance crosses the budget, the later ones lose their fields
+        let retained: Vec<usize> = recorder
+            .dump_instances
+            .iter()
+            .map(|record| match record {
+                GcRecord::InstanceDump { bytes_ref, .. } => bytes_ref.len(),
+                _ => unreachable!(),
+            })
+            .collect();
+        assert_eq!(retained, vec![16, 16, 0, 0]);
+        let degradation = recorder.degradation.unwrap();
+        assert_eq!(degradation.estimated_bytes, 2 * instance_bytes);
+        assert_eq!(degradation.dropped_instance_fields, 2);
+        assert_eq!(degradation.dropped_bytes, 2 * 16);
+        assert_eq!(degradation.dropped_primitive_arrays, 0);
+        assert_eq!(recorder.heap_dump_segments_gc_instance_dump, 4);
+
+        // unbounded by default
+        let mut recorder = ResultRecorder::new(8, SlurpOptions::default());
+        recorder.record_records(&mut records, &[]);
+        assert!(recorder.degradation.is_none());
     }
 
-    fn column_padding(column_name: &str, max_item_length: usize) -> String {
-        let column_label_len = column_name.chars().count();
-        let padding_size = if max_item_length > column_label_len {
-            max_item_length - column_label_len
-        } else {
-            0
+    #[test]
+    fn stop_once_the_condition_is_met() {
+        use crate::parser::record_stream_parser::StreamPosition;
+
+        let options = SlurpOptions {
+            stop_when: Some(StopCondition::new(|recorder| {
+                recorder.utf8_strings_by_id.contains_key(&2)
+            })),
+            ..SlurpOptions::default()
         };
-        " ".repeat(padding_size)
-    }
-
-    pub fn render_summary(&self) -> String {
-        let top_summary = formatdoc!(
-            "\nFile content summary:\n
-            UTF-8 Strings: {}
-            Classes loaded: {}
-            Classes unloaded: {}
-            Stack traces: {}
-            Stack frames: {}
-            Start threads: {}
-            Allocation sites: {}
-            End threads: {}
-            Control settings: {}
-            CPU samples: {}",
-            self.utf8_strings_by_id.len(),
-            self.class_data_by_id.len(),
-            self.classes_unloaded,
-            self.stack_traces,
-            self.stack_frames,
-            self.start_threads,
-            self.allocation_sites,
-            self.end_threads,
-            self.control_settings,
-            self.cpu_samples
-        );
-
-        let heap_summary = formatdoc!(
-            "Heap summaries: {}
-            {} heap dumps containing in total {} segments:
-            ..GC root unknown: {}
-            ..GC root thread objects: {}
-            ..GC root JNI global: {}
-            ..GC root JNI local: {}
-            ..GC root Java frame: {}
-            ..GC root native stack: {}
-            ..GC root sticky class: {}
-            ..GC root thread block: {}
-            ..GC root monitor used: {}
-            ..GC primitive array dump: {}
-            ..GC object array dump: {}
-            ..GC class dump: {}
-            ..GC instance dump: {}",
-            self.heap_summaries,
-            self.heap_dumps,
-            self.heap_dump_segments_all_sub_records,
-            self.heap_dump_segments_gc_root_unknown,
-            self.heap_dump_segments_gc_root_thread_object,
-            self.heap_dump_segments_gc_root_jni_global,
-            self.heap_dump_segments_gc_root_jni_local,
-            self.heap_dump_segments_gc_root_java_frame,
-            self.heap_dump_segments_gc_root_native_stack,
-            self.heap_dump_segments_gc_root_sticky_class,
-            self.heap_dump_segments_gc_root_thread_block,
-            self.heap_dump_segments_gc_root_monitor_used,
-            self.heap_dump_segments_gc_primitive_array_dump,
-            self.heap_dump_segments_gc_object_array_dump,
-            self.heap_dump_segments_gc_class_dump,
-            self.heap_dump_segments_gc_instance_dump,
-        );
-
-        format!("{}\n{}", top_summary, heap_summary)
+        let (send_records, receive_records) = crossbeam_channel::unbounded();
+        let (send_result, receive_result) = crossbeam_channel::unbounded();
+        let (send_pooled_vec, _receive_pooled_vec) = crossbeam_channel::unbounded();
+        let (send_progress, _receive_progress) = crossbeam_channel::unbounded();
+        for (id, offset) in [(1, 100), (2, 200), (3, 300)] {
+            send_records
+                .send(RecordBatch {
+                    records: vec![Utf8String {
+                        id,
+                        str: "s".into(),
+                    }],
+                    offsets: Vec::new(),
+                    position: StreamPosition {
+                        offset,
+                        heap_dump_remaining_len: 0,
+                    },
+                })
+                .unwrap();
+        }
+        ResultRecorder::new(8, options)
+            .start(
+                receive_records,
+                send_result,
+                send_pooled_vec,
+                send_progress,
+                None,
+            )
+            .unwrap()
+            .join()
+            .unwrap();
+
+        let recorder = receive_result.recv().unwrap();
+        assert_eq!(recorder.stopped_at, Some(200));
+        assert_eq!(recorder.utf8_strings_by_id.len(), 2);
+        // the parser sees the recorder is gone
+        assert!(send_records
+            .send(RecordBatch {
+                records: Vec::new(),
+                offsets: Vec::new(),
+                position: StreamPosition {
+                    offset: 400,
+                    heap_dump_remaining_len: 0,
+                },
+            })
+            .is_err());
     }
-}
 
-fn primitive_byte_size(field_type: &FieldType) -> u64 {
-    match field_type {
-        FieldType::Byte | FieldType::Bool => 1,
-        FieldType::Char | FieldType::Short => 2,
-        FieldType::Float | FieldType::Int => 4,
-        FieldType::Double | FieldType::Long => 8,
-        FieldType::Object => panic!("object type in primitive array"),
+    #[test]
+    fn sampled_fraction_follows_the_rate() {
+        // aligned object ids as allocated by the JVM
+        let object_ids = (1..=10_000u64).map(|i| 0x7_0000_0000 + i * 8);
+        for rate in [0.0, 0.1, 0.5, 0.9] {
+            let sampled = object_ids
+                .clone()
+                .filter(|object_id| is_sampled(*object_id, rate))
+                .count();
+            let fraction = sampled as f64 / 10_000.0;
+            assert!(
+                (fraction - rate).abs() < 0.02,
+                "{} sampled at {}",
+                fraction,
+                rate
+            );
+        }
+        assert!(object_ids
+            .clone()
+            .all(|object_id| is_sampled(object_id, 1.0)));
     }
 }
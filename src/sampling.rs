@@ -0,0 +1,423 @@
+use std::fs::File;
+use std::io::{BufReader, Read, Seek, SeekFrom};
+
+use crate::errors::HprofSlurpError;
+use crate::errors::HprofSlurpError::*;
+use crate::info::RecordCounts;
+use crate::parser::record_parser::*;
+use crate::slurp::{slurp_header, FILE_HEADER_LENGTH};
+
+/// Heap dump offsets parsed by `sample_record_counts`.
+pub const DEFAULT_SAMPLES: usize = 64;
+// bytes parsed at each sampled offset
+const SAMPLE_WINDOW: usize = 64 * 1024;
+// records parsed from a candidate offset before trusting it as a record boundary
+const SYNC_RECORDS: usize = 16;
+// instances hold at most 65535 fields of 8 bytes
+const MAX_INSTANCE_LEN: u32 = 65535 * 8;
+
+enum SubRecordError {
+    // the record goes past the sampled bytes
+    Incomplete,
+    Malformed,
+}
+
+/// Estimates the records of a local uncompressed dump without parsing it fully.
+///
+/// The top level records are counted exactly by hopping from record header to record header, the heap
+/// dump records are projected from their density at `samples` offsets spread over the heap dump.
+pub fn sample_record_counts(
+    file_path: &str,
+    samples: usize,
+) -> Result<RecordCounts, HprofSlurpError> {
+    let mut reader = BufReader::new(File::open(file_path)?);
+    sample_records(&mut reader, samples.max(1), SAMPLE_WINDOW)
+}
+
+fn sample_records<R: Read + Seek>(
+    reader: &mut BufReader<R>,
+    samples: usize,
+    window: usize,
+) -> Result<RecordCounts, HprofSlurpError> {
+    let file_len = reader.seek(SeekFrom::End(0))?;
+    reader.rewind()?;
+    let header = slurp_header(reader)?;
+    let id_size = header.size_pointers as usize;
+
+    let mut counts = RecordCounts::default();
+    // (offset, length) of the body of each heap dump segment
+    let mut segments: Vec<(u64, u64)> = Vec::new();
+    let mut offset = FILE_HEADER_LENGTH as u64;
+    let mut record_header = [0u8; 9];
+    while offset + 9 <= file_len {
+        reader.read_exact(&mut record_header)?;
+        offset += 9;
+        let length = u32::from_be_bytes([
+            record_header[5],
+            record_header[6],
+            record_header[7],
+            record_header[8],
+        ]) as u64;
+        // a truncated dump ends within its last record
+        let length = length.min(file_len - offset);
+        match record_header[0] {
+            TAG_STRING => counts.strings += 1,
+            TAG_LOAD_CLASS => counts.classes += 1,
+            TAG_STACK_FRAME => counts.stack_frames += 1,
+            TAG_STACK_TRACE => counts.stack_traces += 1,
+            TAG_START_THREAD => counts.threads += 1,
+            TAG_HEAP_DUMP | TAG_HEAP_DUMP_SEGMENT => {
+                counts.heap_dump_segments += 1;
+                segments.push((offset, length));
+            }
+            TAG_UNLOAD_CLASS | TAG_ALLOC_SITES | TAG_HEAP_SUMMARY | TAG_END_THREAD
+            | TAG_HEAP_DUMP_END | TAG_CONTROL_SETTING | TAG_CPU_SAMPLES => counts.others += 1,
+            tag => {
+                return Err(InvalidHprofFile {
+                    message: format!("unknown record tag 0x{:x} at offset {}", tag, offset - 9),
+                })
+            }
+        }
+        reader.seek_relative(length as i64)?;
+        offset += length;
+    }
+
+    let heap_dump_len: u64 = segments.iter().map(|(_, length)| length).sum();
+    let mut buffer = Vec::new();
+    if heap_dump_len <= (samples * window) as u64 {
+        // small enough to be counted exactly
+        for (start, length) in segments {
+            read_at(reader, start, length as usize, &mut buffer)?;
+            count_sub_records(&buffer, 0, id_size, &mut counts);
+        }
+        return Ok(counts);
+    }
+
+    let mut sampled = RecordCounts::default();
+    let mut sampled_len = 0;
+    for sample in 0..samples as u64 {
+        // middle of the sample-th slice of the heap dump
+        let mut position = (2 * sample + 1) * heap_dump_len / (2 * samples as u64);
+        let (start, length) = segments
+            .iter()
+            .find(|(_, length)| {
+                let within = position < *length;
+                if !within {
+                    position -= length;
+                }
+                within
+            })
+            .copied()
+            .expect("the position is within the heap dump");
+        let sample_len = (window as u64).min(length - position) as usize;
+        read_at(reader, start + position, sample_len, &mut buffer)?;
+        // the records ending with the segment must end with the sampled bytes
+        let segment_end = position + sample_len as u64 == length;
+        let sync =
+            (0..buffer.len()).find(|&at| is_record_boundary(&buffer, at, id_size, segment_end));
+        if let Some(sync) = sync {
+            count_sub_records(&buffer, sync, id_size, &mut sampled);
+        }
+        sampled_len += sample_len as u64;
+    }
+
+    let ratio = heap_dump_len as f64 / sampled_len as f64;
+    let scale = |count: u64| (count as f64 * ratio).round() as u64;
+    Ok(RecordCounts {
+        gc_roots: scale(sampled.gc_roots),
+        class_dumps: scale(sampled.class_dumps),
+        instances: scale(sampled.instances),
+        object_arrays: scale(sampled.object_arrays),
+        primitive_arrays: scale(sampled.primitive_arrays),
+        ..counts
+    })
+}
+
+fn read_at<R: Read + Seek>(
+    reader: &mut BufReader<R>,
+    offset: u64,
+    length: usize,
+    buffer: &mut Vec<u8>,
+) -> Result<(), HprofSlurpError> {
+    reader.seek(SeekFrom::Start(offset))?;
+    buffer.resize(length, 0);
+    reader.read_exact(buffer)?;
+    Ok(())
+}
+
+// counts the records starting within `data` from the record boundary `at`
+fn count_sub_records(data: &[u8], mut at: usize, id_size: usize, counts: &mut RecordCounts) {
+    while at < data.len() {
+        count_sub_record(data[at], counts);
+        match sub_record_len(&data[at..], id_size) {
+            Ok(length) => at += length,
+            // the record crosses the end of the sampled bytes
+            Err(_) => return,
+        }
+    }
+}
+
+fn count_sub_record(tag: u8, counts: &mut RecordCounts) {
+    match tag {
+        TAG_GC_CLASS_DUMP => counts.class_dumps += 1,
+        TAG_GC_INSTANCE_DUMP => counts.instances += 1,
+        TAG_GC_OBJ_ARRAY_DUMP => counts.object_arrays += 1,
+        TAG_GC_PRIM_ARRAY_DUMP => counts.primitive_arrays += 1,
+        _ => counts.gc_roots += 1,
+    }
+}
+
+// random bytes rarely chain `SYNC_RECORDS` well-formed records
+fn is_record_boundary(data: &[u8], mut at: usize, id_size: usize, segment_end: bool) -> bool {
+    for _ in 0..SYNC_RECORDS {
+        if at == data.len() {
+            return true;
+        }
+        match sub_record_len(&data[at..], id_size) {
+            Ok(length) => at += length,
+            Err(SubRecordError::Incomplete) => return !segment_end,
+            Err(SubRecordError::Malformed) => return false,
+        }
+    }
+    true
+}
+
+// length of the heap dump record at the start of `data`, following `parse_gc_record` without decoding it
+fn sub_record_len(data: &[u8], id_size: usize) -> Result<usize, SubRecordError> {
+    let mut cursor = Cursor {
+        data,
+        at: 1,
+        id_size,
+    };
+    match data[0] {
+        TAG_GC_ROOT_UNKNOWN | TAG_GC_ROOT_STICKY_CLASS | TAG_GC_ROOT_MONITOR_USED => {
+            cursor.id()?;
+        }
+        TAG_GC_ROOT_JNI_GLOBAL => {
+            cursor.id()?;
+            cursor.id()?;
+        }
+        TAG_GC_ROOT_JNI_LOCAL | TAG_GC_ROOT_JAVA_FRAME | TAG_GC_ROOT_THREAD_OBJ => {
+            cursor.id()?;
+            cursor.skip(8)?;
+        }
+        TAG_GC_ROOT_NATIVE_STACK | TAG_GC_ROOT_THREAD_BLOCK => {
+            cursor.id()?;
+            cursor.skip(4)?;
+        }
+        TAG_GC_CLASS_DUMP => {
+            cursor.object_id()?;
+            cursor.skip(4)?;
+            // super class, class loader, signers, protection domain and 2 reserved ids
+            for _ in 0..6 {
+                cursor.id()?;
+            }
+            cursor.skip(4)?;
+            for _ in 0..cursor.u16()? {
+                cursor.skip(2)?;
+                let value_len = cursor.value_len()?;
+                cursor.skip(value_len)?;
+            }
+            for _ in 0..cursor.u16()? {
+                cursor.id()?;
+                let value_len = cursor.value_len()?;
+                cursor.skip(value_len)?;
+            }
+            for _ in 0..cursor.u16()? {
+                cursor.id()?;
+                cursor.value_len()?;
+            }
+        }
+        TAG_GC_INSTANCE_DUMP => {
+            cursor.object_id()?;
+            cursor.skip(4)?;
+            cursor.object_id()?;
+            let data_len = cursor.u32()?;
+            if data_len > MAX_INSTANCE_LEN {
+                return Err(SubRecordError::Malformed);
+            }
+            cursor.skip(data_len as usize)?;
+        }
+        TAG_GC_OBJ_ARRAY_DUMP => {
+            cursor.object_id()?;
+            cursor.skip(4)?;
+            let elements = cursor.u32()? as usize;
+            cursor.object_id()?;
+            cursor.skip(
+                elements
+                    .checked_mul(id_size)
+                    .ok_or(SubRecordError::Malformed)?,
+            )?;
+        }
+        TAG_GC_PRIM_ARRAY_DUMP => {
+            cursor.object_id()?;
+            cursor.skip(4)?;
+            let elements = cursor.u32()? as usize;
+            // object elements only exist in object arrays
+            let element_len = match cursor.bytes(1)?[0] {
+                2 => return Err(SubRecordError::Malformed),
+                element_type => type_len(element_type, id_size)?,
+            };
+            cursor.skip(
+                elements
+                    .checked_mul(element_len)
+                    .ok_or(SubRecordError::Malformed)?,
+            )?;
+        }
+        _ => return Err(SubRecordError::Malformed),
+    }
+    Ok(cursor.at)
+}
+
+struct Cursor<'a> {
+    data: &'a [u8],
+    at: usize,
+    id_size: usize,
+}
+
+impl Cursor<'_> {
+    fn skip(&mut self, length: usize) -> Result<(), SubRecordError> {
+        self.at += length;
+        if self.at > self.data.len() {
+            return Err(SubRecordError::Incomplete);
+        }
+        Ok(())
+    }
+
+    fn bytes(&mut self, length: usize) -> Result<&[u8], SubRecordError> {
+        let start = self.at;
+        self.skip(length)?;
+        Ok(&self.data[start..self.at])
+    }
+
+    fn u16(&mut self) -> Result<u16, SubRecordError> {
+        let bytes = self.bytes(2)?;
+        Ok(u16::from_be_bytes([bytes[0], bytes[1]]))
+    }
+
+    fn u32(&mut self) -> Result<u32, SubRecordError> {
+        let bytes = self.bytes(4)?;
+        Ok(u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    }
+
+    fn id(&mut self) -> Result<(), SubRecordError> {
+        self.skip(self.id_size)
+    }
+
+    // objects and classes never have the null id
+    fn object_id(&mut self) -> Result<(), SubRecordError> {
+        let id_size = self.id_size;
+        if self.bytes(id_size)?.iter().all(|byte| *byte == 0) {
+            return Err(SubRecordError::Malformed);
+        }
+        Ok(())
+    }
+
+    // length of a value of the field type read
+    fn value_len(&mut self) -> Result<usize, SubRecordError> {
+        let id_size = self.id_size;
+        type_len(self.bytes(1)?[0], id_size)
+    }
+}
+
+fn type_len(field_type: u8, id_size: usize) -> Result<usize, SubRecordError> {
+    match field_type {
+        2 => Ok(id_size),
+        4 | 8 => Ok(1),
+        5 | 9 => Ok(2),
+        6 | 10 => Ok(4),
+        7 | 11 => Ok(8),
+        _ => Err(SubRecordError::Malformed),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor as IoCursor;
+
+    // header, 3 strings, then 2 heap dump segments of roots, instances and arrays
+    fn dump() -> (Vec<u8>, RecordCounts) {
+        let mut dump = b"JAVA PROFILE 1.0.2\0".to_vec();
+        dump.extend(8u32.to_be_bytes());
+        dump.extend(1608192273831u64.to_be_bytes());
+        for id in 1..=3u64 {
+            dump.push(TAG_STRING);
+            dump.extend(0u32.to_be_bytes());
+            dump.extend(13u32.to_be_bytes());
+            dump.extend(id.to_be_bytes());
+            dump.extend(b"hello");
+        }
+        let mut expected = RecordCounts {
+            strings: 3,
+            heap_dump_segments: 2,
+            others: 1,
+            ..RecordCounts::default()
+        };
+        let mut object_id = 0x1000u64;
+        for _ in 0..2 {
+            let mut segment = Vec::new();
+            segment.push(TAG_GC_ROOT_UNKNOWN);
+            segment.extend(object_id.to_be_bytes());
+            expected.gc_roots += 1;
+            for index in 0..300 {
+                object_id += 8;
+                if index % 10 == 0 {
+                    // a long array breaking the sampled windows
+                    segment.push(TAG_GC_PRIM_ARRAY_DUMP);
+                    segment.extend(object_id.to_be_bytes());
+                    segment.extend(0u32.to_be_bytes());
+                    segment.extend(100u32.to_be_bytes());
+                    segment.push(8);
+                    segment.extend([b'#'; 100]);
+                    expected.primitive_arrays += 1;
+                } else if index % 10 == 5 {
+                    segment.push(TAG_GC_OBJ_ARRAY_DUMP);
+                    segment.extend(object_id.to_be_bytes());
+                    segment.extend(0u32.to_be_bytes());
+                    segment.extend(2u32.to_be_bytes());
+                    segment.extend(0x20u64.to_be_bytes());
+                    segment.extend([0; 16]);
+                    expected.object_arrays += 1;
+                } else {
+                    segment.push(TAG_GC_INSTANCE_DUMP);
+                    segment.extend(object_id.to_be_bytes());
+                    segment.extend(0u32.to_be_bytes());
+                    segment.extend(0x20u64.to_be_bytes());
+                    segment.extend(8u32.to_be_bytes());
+                    segment.extend((object_id - 8).to_be_bytes());
+                    expected.instances += 1;
+                }
+            }
+            dump.push(TAG_HEAP_DUMP_SEGMENT);
+            dump.extend(0u32.to_be_bytes());
+            dump.extend((segment.len() as u32).to_be_bytes());
+            dump.extend(segment);
+        }
+        dump.push(TAG_HEAP_DUMP_END);
+        dump.extend(0u32.to_be_bytes());
+        dump.extend(0u32.to_be_bytes());
+        (dump, expected)
+    }
+
+    #[test]
+    fn project_record_counts_from_samples() {
+        let (dump, expected) = dump();
+        let mut reader = BufReader::new(IoCursor::new(dump));
+        // the whole heap dump fits in the samples
+        let counts = sample_records(&mut reader, 4, 64 * 1024).unwrap();
+        assert_eq!(counts, expected);
+
+        let counts = sample_records(&mut reader, 20, 400).unwrap();
+        assert_eq!(counts.strings, 3);
+        assert_eq!(counts.heap_dump_segments, 2);
+        for (estimated, exact) in [
+            (counts.instances, expected.instances),
+            (counts.object_arrays, expected.object_arrays),
+            (counts.primitive_arrays, expected.primitive_arrays),
+        ] {
+            let error = estimated.abs_diff(exact) as f64 / exact as f64;
+            assert!(error < 0.2, "estimated {} for {}", estimated, exact);
+        }
+    }
+}
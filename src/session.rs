@@ -0,0 +1,164 @@
+use std::collections::HashSet;
+#[cfg(feature = "graph")]
+use std::sync::OnceLock;
+use std::sync::{Arc, RwLock};
+
+use crate::annotations::HeapAnnotations;
+
+#[cfg(feature = "graph")]
+use crate::graph::HeapGraph;
+use crate::Heap;
+
+/// A loaded heap answering queries from several threads at once, e.g. the requests of a front-end.
+///
+/// The session is `Send + Sync` and cheap to clone: clones share the heap, which is never written once loaded,
+/// and the reference graph built by the first query needing it. Each query gets its own scratch space.
+#[derive(Clone)]
+pub struct HeapSession {
+    heap: Arc<Heap>,
+    #[cfg(feature = "graph")]
+    graph: Arc<OnceLock<HeapGraph>>,
+    annotations: Arc<RwLock<HeapAnnotations>>,
+}
+
+/// Scratch space of a single query, reused between the steps of the query but never shared with another one.
+#[derive(Debug, Default)]
+pub struct QueryScratch {
+    // objects already visited by a traversal
+    pub visited: HashSet<u64>,
+    // objects waiting to be visited
+    pub pending: Vec<u64>,
+}
+
+impl QueryScratch {
+    pub fn clear(&mut self) {
+        self.visited.clear();
+        self.pending.clear();
+    }
+}
+
+impl HeapSession {
+    pub fn new(heap: Heap) -> Self {
+        Self::from(Arc::new(heap))
+    }
+
+    pub fn heap(&self) -> &Heap {
+        &self.heap
+    }
+
+    /// Returns the reference graph, built on first use while the other queries needing it wait.
+    #[cfg(feature = "graph")]
+    pub fn graph(&self) -> &HeapGraph {
+        self.graph.get_or_init(|| HeapGraph::new(&self.heap))
+    }
+
+    /// Returns the annotations shared by the clones of the session, e.g. the objects tagged while investigating.
+    pub fn annotations(&self) -> &RwLock<HeapAnnotations> {
+        &self.annotations
+    }
+
+    /// Runs a query with a scratch space of its own, queries can run concurrently on clones of the session.
+    pub fn query<R>(&self, query: impl FnOnce(&Heap, &mut QueryScratch) -> R) -> R {
+        let mut scratch = QueryScratch::default();
+        query(&self.heap, &mut scratch)
+    }
+}
+
+impl From<Arc<Heap>> for HeapSession {
+    fn from(heap: Arc<Heap>) -> Self {
+        HeapSession {
+            heap,
+            #[cfg(feature = "graph")]
+            graph: Arc::new(OnceLock::new()),
+            annotations: Arc::default(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::gc_record::{FieldValue, Values};
+    use crate::result_recorder::{Instance, RootJniGlobal};
+
+    fn assert_send_sync<T: Send + Sync>() {}
+
+    #[test]
+    fn concurrent_queries() {
+        assert_send_sync::<HeapSession>();
+
+        // 1 -> 2 -> 3, 4 alone
+        let mut heap = Heap::default();
+        heap.counter.id_size = 8;
+        for (object_id, references) in [(1, vec![2]), (2, vec![3]), (3, vec![]), (4, vec![])] {
+            heap.instances_pool.make_mut().insert(
+                object_id,
+                Arc::new(Instance {
+                    object_id,
+                    stack_trace_serial_number: 0,
+                    class_object_id: 0,
+                    data_size: 8 * references.len() as u64,
+                    fields: references
+                        .iter()
+                        .map(|id| (0, Values::Single(FieldValue::Object(*id))))
+                        .collect(),
+                    super_fields: Vec::new(),
+                }),
+            );
+        }
+        heap.root_jni_global.make_mut().insert(
+            1,
+            RootJniGlobal {
+                object_id: 1,
+                jni_global_ref_id: 0,
+            },
+        );
+        let session = HeapSession::new(heap);
+        let reachable = |object_id: u64| {
+            session.query(|heap, scratch| {
+                scratch.pending.push(object_id);
+                while let Some(id) = scratch.pending.pop() {
+                    if !scratch.visited.insert(id) {
+                        continue;
+                    }
+                    if let Some(instance) = heap.instances_pool.get(&id) {
+                        for (_, value) in instance.all_fields() {
+                            if let Values::Single(FieldValue::Object(next)) = value {
+                                scratch.pending.push(*next);
+                            }
+                        }
+                    }
+                }
+                scratch.visited.len()
+            })
+        };
+        let counts: Vec<usize> = std::thread::scope(|scope| {
+            let handles: Vec<_> = [1, 2, 3, 4]
+                .into_iter()
+                .map(|object_id| scope.spawn(move || reachable(object_id)))
+                .collect();
+            handles.into_iter().map(|h| h.join().unwrap()).collect()
+        });
+        assert_eq!(counts, vec![3, 2, 1, 1]);
+
+        #[cfg(feature = "graph")]
+        {
+            let clone = session.clone();
+            assert_eq!(clone.graph().retained_size(1), Some(24 + 24 + 16));
+            assert!(std::ptr::eq(clone.graph(), session.graph()));
+        }
+
+        let clone = session.clone();
+        clone
+            .annotations()
+            .write()
+            .unwrap()
+            .tag(2, "suspect")
+            .unwrap();
+        assert!(session
+            .annotations()
+            .read()
+            .unwrap()
+            .has_label(2, "suspect"));
+    }
+}
@@ -1,45 +1,616 @@
 use std::collections::HashMap;
 use std::fs::File;
 use std::io::{BufReader, Read};
+use std::ops::ControlFlow;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Arc;
-
-use indicatif::{ProgressBar, ProgressStyle};
+use std::thread::JoinHandle;
+use std::time::Instant;
 
 use crossbeam_channel::{Receiver, Sender};
-use log::info;
-use rayon::prelude::{IntoParallelIterator, IntoParallelRefIterator, ParallelIterator};
+use log::{info, warn};
+use rayon::prelude::{IntoParallelIterator, ParallelIterator};
 
+use crate::decompress::{decompressed_input, Compression};
 use crate::errors::HprofSlurpError;
 use crate::errors::HprofSlurpError::*;
 use crate::parser::file_header_parser::{parse_file_header, FileHeader};
-use crate::parser::gc_record::{ClassDumpFields, GcRecord, Values};
+use crate::parser::gc_record::{ClassDumpFields, GcRecord, PrimitiveArray, Values};
 use crate::parser::record::Record;
-use crate::parser::record_parser::{parse_array_value, parse_field_value};
-use crate::parser::record_stream_parser::HprofRecordStreamParser;
-use crate::prefetch_reader::PrefetchReader;
-use crate::result_recorder::{Instance, ResultRecorder};
-use crate::utils::pretty_bytes_size;
-use crate::{Heap, HeapCounter};
+use crate::parser::record_parser::{
+    parse_array_value, parse_field_value, RecordFilter, RecordLimits,
+};
+use crate::parser::record_stream_parser::{HprofRecordStreamParser, RecordBatch, StreamPosition};
+use crate::parser::record_visitor::RecordVisitor;
+#[cfg(target_os = "linux")]
+use crate::prefetch_reader::DirectFileReader;
+use crate::prefetch_reader::{advise_sequential, PrefetchReader};
+use crate::progress::{Phase, Progress, ProgressBars};
+use crate::result_recorder::{primitive_byte_size, Instance, ResultRecorder, StopCondition};
+use crate::sampling::{sample_record_counts, DEFAULT_SAMPLES};
+use crate::utils::{pretty_bytes_size, PrettyDuration};
+use crate::{DumpProvenance, Heap, HeapCounter, MAX_CLASS_HIERARCHY_DEPTH};
+
+// instance values keyed by field name id
+type InstanceFields = Vec<(u64, Values)>;
 
 // the exact size of the file header (31 bytes)
-const FILE_HEADER_LENGTH: usize = 31;
+pub(crate) const FILE_HEADER_LENGTH: usize = 31;
+
+pub use crate::progress::ProgressMode;
+
+// materialized objects between two progress reports
+const MATERIALIZING_REPORT_INTERVAL: u64 = 64 * 1024;
+
+/// Input path reading the dump from stdin.
+pub const STDIN_PATH: &str = "-";
+
+/// Remote inputs are only readable with the `remote` feature.
+pub fn is_remote_path(path: &str) -> bool {
+    ["http://", "https://", "s3://"]
+        .iter()
+        .any(|scheme| path.starts_with(scheme))
+}
 
 // 64 MB buffer performs nicely (higher is faster but increases the memory consumption)
 pub const READ_BUFFER_SIZE: usize = 128 * 1024 * 1024;
 
+// the reader needs more than one buffer to make progress independently of the parser
+pub const DEFAULT_READAHEAD_BUFFERS: usize = 2;
+
+/// Selects which parts of the dump are retained in memory while building the `Heap`.
+///
+/// Dropped payloads are still counted and sized, but their content (field values or array elements) is not available.
+/// Object arrays and instance fields are needed to build the reference graph.
+#[derive(Debug, Clone)]
+pub struct SlurpOptions {
+    pub keep_instance_fields: bool,
+    pub keep_primitive_arrays: bool,
+    // fraction of the primitive arrays keeping their elements, all of them are still counted and sized
+    pub primitive_array_sample_rate: f64,
+    pub keep_object_arrays: bool,
+    pub keep_roots: bool,
+    // identical primitive array payloads share one allocation
+    pub deduplicate_primitive_arrays: bool,
+    // estimated bytes above which instance fields and array elements are no longer retained
+    pub max_memory: Option<u64>,
+    pub progress: ProgressMode,
+    // receives the progress of every phase, in addition to the bars
+    pub progress_listener: Option<Sender<Progress>>,
+    // needs the `checkpoint` feature
+    pub checkpoint: Option<CheckpointOptions>,
+    // only record the objects of this heap dump, files may hold several dumps taken at different times
+    pub dump_index: Option<usize>,
+    // offset of the record of each class and object, to seek in the input for the data not retained
+    pub keep_record_offsets: bool,
+    // input buffers of `READ_BUFFER_SIZE` read ahead of the parser (at least 2), more of them help on network filesystems
+    pub readahead_buffers: usize,
+    // read local files with `O_DIRECT` on Linux, bypassing the page cache
+    pub direct_io: bool,
+    // records decoded by the parser, e.g. `STRINGS | CLASSES | THREADS` skips the heap dump content
+    pub record_filter: RecordFilter,
+    // stops reading the input once met, the `Heap` then only holds the records before `Heap::stopped_at`
+    pub stop_when: Option<StopCondition>,
+    // sanity limits on the declared lengths, a corrupt dump fails with `OversizedRecord` unless skipping
+    pub record_limits: RecordLimits,
+}
+
+/// Periodically saves the recorder state to `path`, a later run on the same input resumes from it.
+///
+/// The checkpoint is deleted once the run completes.
+#[derive(Debug, Clone)]
+pub struct CheckpointOptions {
+    pub path: PathBuf,
+    // bytes of input between two checkpoints
+    pub interval: usize,
+}
+
+impl Default for SlurpOptions {
+    fn default() -> Self {
+        Self {
+            keep_instance_fields: true,
+            keep_primitive_arrays: true,
+            primitive_array_sample_rate: 1.0,
+            keep_object_arrays: true,
+            keep_roots: true,
+            deduplicate_primitive_arrays: true,
+            max_memory: None,
+            progress: ProgressMode::Auto,
+            progress_listener: None,
+            checkpoint: None,
+            dump_index: None,
+            keep_record_offsets: false,
+            readahead_buffers: DEFAULT_READAHEAD_BUFFERS,
+            direct_io: false,
+            record_filter: RecordFilter::ALL,
+            stop_when: None,
+            record_limits: RecordLimits::default(),
+        }
+    }
+}
+
+impl SlurpOptions {
+    // rejects the values the recorder cannot honor before reading the input
+    fn validate(&self) -> Result<(), HprofSlurpError> {
+        let rate = self.primitive_array_sample_rate;
+        if !(0.0..=1.0).contains(&rate) {
+            return Err(InvalidSampleRate { rate });
+        }
+        Ok(())
+    }
+}
+
 pub fn slurp_file(file_path: String) -> Result<Heap, HprofSlurpError> {
+    slurp_file_with_options(file_path, SlurpOptions::default())
+}
+
+pub fn slurp_file_with_options(
+    file_path: String,
+    options: SlurpOptions,
+) -> Result<Heap, HprofSlurpError> {
+    slurp_file_parts(vec![file_path], options)
+}
+
+/// Slurps a dump split in several local files, the parts are read one after the other as a single dump.
+pub fn slurp_file_parts(
+    file_paths: Vec<String>,
+    options: SlurpOptions,
+) -> Result<Heap, HprofSlurpError> {
+    options.validate()?;
+    let started = Instant::now();
+    let resumed = resume_from_checkpoint(&file_paths, &options)?;
+    let start = resumed
+        .as_ref()
+        .map_or(START_POSITION, |(_, position, _)| *position);
+    let RecordStream {
+        header,
+        file_len,
+        receive_records,
+        send_pooled_vec,
+        receive_progress,
+        send_progress,
+        prefetch_thread,
+        parser_thread,
+    } = start_record_stream(file_paths.clone(), start, &options)?;
+    let provenance = DumpProvenance {
+        input_files: file_paths.clone(),
+        input_size: file_len.map(|len| len as u64),
+        format: header.format,
+        timestamp: header.timestamp,
+    };
+
+    // Communication channel from recorder to main
+    let (send_result, receive_result): (Sender<ResultRecorder>, Receiver<ResultRecorder>) =
+        crossbeam_channel::unbounded();
+
+    // Init progress bars
+    let bars = ProgressBars::new(options.progress, options.progress_listener.clone());
+
+    // Init result recorder
+    let checkpoint = options.checkpoint.clone();
+    let result_recorder = match resumed {
+        Some((input_len, _, _)) if input_len != file_len => {
+            return Err(InvalidCheckpoint {
+                message: format!(
+                    "taken from an input of {:?} bytes instead of {:?}",
+                    input_len, file_len
+                ),
+            })
+        }
+        Some((_, position, recorder)) => {
+            info!("Resuming from checkpoint at offset {}.", position.offset);
+            recorder.resume(options)
+        }
+        None => ResultRecorder::new(header.size_pointers, options),
+    };
+    #[cfg(feature = "checkpoint")]
+    let result_recorder = match &checkpoint {
+        Some(checkpoint) => {
+            result_recorder.with_checkpoints(checkpoint.clone(), file_paths, file_len, start.offset)
+        }
+        None => result_recorder,
+    };
+    let recorder_thread = result_recorder.start(
+        receive_records,
+        send_result,
+        send_pooled_vec,
+        send_progress,
+        file_len,
+    )?;
+
+    // Feed progress bars until the pre-fetcher, parser and recorder are done
+    while let Ok(progress) = receive_progress.recv() {
+        bars.report(progress);
+    }
+    prefetch_thread
+        .join()
+        .map_err(|e| HprofSlurpError::StdThreadError { e })?;
+
+    // Blocks until parser is done
+    parser_thread
+        .join()
+        .map_err(|e| HprofSlurpError::StdThreadError { e })??;
+
+    // Blocks until recorder is done
+    recorder_thread
+        .join()
+        .map_err(|e| HprofSlurpError::StdThreadError { e })?;
+
+    for phase in [Phase::Reading, Phase::Parsing, Phase::Recording] {
+        bars.finish_phase(phase);
+    }
+    // Wait for final result
+    let result = receive_result
+        .recv()
+        .expect("result channel should be alive");
+    check_recorded(&result)?;
+    if let Some(checkpoint) = checkpoint {
+        if checkpoint.path.exists() {
+            std::fs::remove_file(checkpoint.path)?;
+        }
+    }
+
+    let heap = build_heap(result, provenance, &bars);
+    bars.clear();
+    info!("Slurped the heap in {}.", PrettyDuration(started.elapsed()));
+    Ok(heap)
+}
+
+/// Slurps the heap like `slurp_file_parts` while `visitor` receives the same records, in a single pass over the input.
+///
+/// The records reach `visitor`, which must not take their payloads, before the recorder. Checkpoints are not supported
+/// and the progress is not reported.
+pub fn slurp_file_parts_with_visitor<V: RecordVisitor>(
+    file_paths: Vec<String>,
+    options: SlurpOptions,
+    visitor: V,
+) -> Result<(Heap, V), HprofSlurpError> {
+    options.validate()?;
+    if options.checkpoint.is_some() {
+        return Err(InvalidCheckpoint {
+            message: "not supported with a record visitor".to_string(),
+        });
+    }
+    let stream = start_record_stream(file_paths.clone(), START_POSITION, &options)?;
+    let provenance = DumpProvenance {
+        input_files: file_paths,
+        input_size: stream.file_len.map(|len| len as u64),
+        format: stream.header.format.clone(),
+        timestamp: stream.header.timestamp,
+    };
+    let bars = ProgressBars::new(ProgressMode::Never, None);
+    let recorder = ResultRecorder::new(stream.header.size_pointers, options);
+    let mut chain = visitor.chain(recorder);
+    drain_record_stream(stream, |batch| visit_batch(&mut chain, batch))?;
+    let (visitor, result) = chain.into_parts();
+    check_recorded(&result)?;
+    Ok((build_heap(result, provenance, &bars), visitor))
+}
+
+// the selected dump must exist, several dumps are merged otherwise
+fn check_recorded(result: &ResultRecorder) -> Result<(), HprofSlurpError> {
+    if result.segments.open && result.stopped_at.is_none() {
+        warn!("The last heap dump segment is not ended, the file may be truncated.");
+    }
+    let heap_dumps = result.segments.heap_dumps;
+    match result.options.dump_index {
+        // a stopped run may not have reached the selected dump yet
+        Some(index) if index as u64 >= heap_dumps && result.stopped_at.is_none() => {
+            return Err(InvalidDumpIndex { index, heap_dumps })
+        }
+        None if heap_dumps > 1 => warn!(
+            "The file contains {} heap dumps, their objects are merged (select one with `--dump-index`).",
+            heap_dumps
+        ),
+        _ => (),
+    }
+    Ok(())
+}
+
+fn build_heap(result: ResultRecorder, provenance: DumpProvenance, bars: &ProgressBars) -> Heap {
+    let mut heap = parse_instance(result, bars);
+    heap.provenance = provenance;
+    heap.reference_sizing = heap.infer_reference_sizing();
+    heap
+}
+
+// parsing starts right after the file header
+const START_POSITION: StreamPosition = StreamPosition {
+    offset: FILE_HEADER_LENGTH,
+    heap_dump_remaining_len: 0,
+};
+
+// input length, position and recorder state saved by a previous run
+type Resumed = (Option<usize>, StreamPosition, ResultRecorder);
+
+#[cfg(feature = "checkpoint")]
+fn resume_from_checkpoint(
+    file_paths: &[String],
+    options: &SlurpOptions,
+) -> Result<Option<Resumed>, HprofSlurpError> {
+    match &options.checkpoint {
+        Some(checkpoint) => crate::checkpoint::read_checkpoint(&checkpoint.path, file_paths),
+        None => Ok(None),
+    }
+}
+
+#[cfg(not(feature = "checkpoint"))]
+fn resume_from_checkpoint(
+    _file_paths: &[String],
+    options: &SlurpOptions,
+) -> Result<Option<Resumed>, HprofSlurpError> {
+    match options.checkpoint {
+        Some(_) => Err(InvalidCheckpoint {
+            message: "built without the `checkpoint` feature".to_string(),
+        }),
+        None => Ok(None),
+    }
+}
+
+/// Streams the parsed records to `on_record` without building a `Heap`.
+///
+/// Returning `ControlFlow::Break` stops the parsing early.
+pub fn slurp_records<F>(file_path: String, on_record: F) -> Result<(), HprofSlurpError>
+where
+    F: FnMut(Record) -> ControlFlow<()>,
+{
+    slurp_records_filtered(file_path, RecordFilter::ALL, on_record)
+}
+
+/// Same as `slurp_records`, only the records selected by `filter` are decoded and streamed.
+pub fn slurp_records_filtered<F>(
+    file_path: String,
+    filter: RecordFilter,
+    mut on_record: F,
+) -> Result<(), HprofSlurpError>
+where
+    F: FnMut(Record) -> ControlFlow<()>,
+{
+    let options = SlurpOptions {
+        record_filter: filter,
+        ..SlurpOptions::default()
+    };
+    let stream = start_record_stream(vec![file_path], START_POSITION, &options)?;
+    drain_record_stream(stream, |batch| {
+        for record in batch.records.drain(..) {
+            on_record(record)?;
+        }
+        ControlFlow::Continue(())
+    })
+}
+
+/// Streams the records to `visitor` without building a `Heap`, returning it once the input is consumed
+/// or `RecordVisitor::visit_batch_end` stopped the parsing.
+///
+/// The record filter, record offsets and input options of `options` apply, the retention ones do not.
+pub fn slurp_file_with_visitor<V: RecordVisitor>(
+    file_paths: Vec<String>,
+    options: SlurpOptions,
+    mut visitor: V,
+) -> Result<V, HprofSlurpError> {
+    let stream = start_record_stream(file_paths, START_POSITION, &options)?;
+    drain_record_stream(stream, |batch| visit_batch(&mut visitor, batch))?;
+    Ok(visitor)
+}
+
+fn visit_batch<V: RecordVisitor>(visitor: &mut V, batch: &mut RecordBatch) -> ControlFlow<()> {
+    for (i, record) in batch.records.iter_mut().enumerate() {
+        visitor.visit_record(record, batch.offsets.get(i).copied());
+    }
+    visitor.visit_batch_end(batch.position)
+}
+
+// hands the batches to `on_batch` on the calling thread, progress is not reported
+fn drain_record_stream<F>(stream: RecordStream, mut on_batch: F) -> Result<(), HprofSlurpError>
+where
+    F: FnMut(&mut RecordBatch) -> ControlFlow<()>,
+{
+    let RecordStream {
+        receive_records,
+        send_pooled_vec,
+        receive_progress,
+        send_progress,
+        prefetch_thread,
+        parser_thread,
+        ..
+    } = stream;
+
+    // progress is not reported
+    drop(receive_progress);
+    drop(send_progress);
+
+    while let Ok(mut batch) = receive_records.recv() {
+        let flow = on_batch(&mut batch);
+        // give back the pooled vec, fails only once the parser is done
+        batch.records.clear();
+        send_pooled_vec.send(batch.records).unwrap_or_default();
+        if flow.is_break() {
+            break;
+        }
+    }
+
+    // closing the channels stops the parser and pre-fetcher if the stream was interrupted
+    drop(receive_records);
+    drop(send_pooled_vec);
+
+    prefetch_thread
+        .join()
+        .map_err(|e| HprofSlurpError::StdThreadError { e })?;
+    parser_thread
+        .join()
+        .map_err(|e| HprofSlurpError::StdThreadError { e })??;
+    Ok(())
+}
+
+// Pre-fetcher and stream parser threads producing batches of records
+struct RecordStream {
+    header: FileHeader,
+    // unknown when reading from stdin
+    file_len: Option<usize>,
+    receive_records: Receiver<RecordBatch>,
+    send_pooled_vec: Sender<Vec<Record>>,
+    receive_progress: Receiver<Progress>,
+    // for the recorder, the progress channel closes once all senders are dropped
+    send_progress: Sender<Progress>,
+    prefetch_thread: JoinHandle<()>,
+    parser_thread: JoinHandle<Result<(), HprofSlurpError>>,
+}
+
+// the length is unknown for stdin
+pub(crate) fn open_input(
+    mut file_paths: Vec<String>,
+    direct_io: bool,
+) -> Result<(Box<dyn Read + Send>, Option<usize>), HprofSlurpError> {
+    if file_paths.len() > 1 {
+        return open_file_parts(file_paths, direct_io);
+    }
+    let file_path = file_paths.pop().ok_or(InvalidHprofFile {
+        message: "no input file".to_string(),
+    })?;
+    if file_path == STDIN_PATH {
+        return Ok((Box::new(std::io::stdin()), None));
+    }
+    if is_remote_path(&file_path) {
+        #[cfg(feature = "remote")]
+        {
+            let reader = crate::remote_reader::RangeReader::open(&file_path)?;
+            let len = reader.len() as usize;
+            return Ok((Box::new(reader), Some(len)));
+        }
+        #[cfg(not(feature = "remote"))]
+        return Err(RemoteInputError {
+            message: "built without the `remote` feature".to_string(),
+        });
+    }
+    open_local_file(&file_path, direct_io)
+}
+
+// local files concatenated in order
+fn open_file_parts(
+    file_paths: Vec<String>,
+    direct_io: bool,
+) -> Result<(Box<dyn Read + Send>, Option<usize>), HprofSlurpError> {
+    let mut input: Box<dyn Read + Send> = Box::new(std::io::empty());
+    let mut file_len = 0;
+    for file_path in file_paths {
+        if file_path == STDIN_PATH || is_remote_path(&file_path) {
+            return Err(InvalidHprofFile {
+                message: format!("dump part `{}` must be a local file", file_path),
+            });
+        }
+        let (file, len) = open_local_file(&file_path, direct_io)?;
+        file_len += len.unwrap_or_default();
+        input = Box::new(input.chain(file));
+    }
+    Ok((input, Some(file_len)))
+}
+
+fn open_local_file(
+    file_path: &str,
+    direct_io: bool,
+) -> Result<(Box<dyn Read + Send>, Option<usize>), HprofSlurpError> {
+    if direct_io {
+        #[cfg(target_os = "linux")]
+        match DirectFileReader::open(file_path) {
+            Ok(reader) => {
+                let file_len = reader.file_len()? as usize;
+                return Ok((Box::new(reader), Some(file_len)));
+            }
+            // e.g. tmpfs does not support direct I/O
+            Err(e) => warn!(
+                "Direct I/O is not available for `{}`, reading through the page cache: {}",
+                file_path, e
+            ),
+        }
+        #[cfg(not(target_os = "linux"))]
+        warn!("Direct I/O is only supported on Linux, reading through the page cache.");
+    }
     let file = File::open(file_path)?;
     let file_len = file.metadata()?.len() as usize;
-    let mut reader = BufReader::new(file);
+    advise_sequential(&file);
+    Ok((Box::new(file), Some(file_len)))
+}
+
+// where the records are read from, for the logs
+fn input_source(file_paths: &[String]) -> String {
+    match file_paths {
+        [file_path] if file_path == STDIN_PATH => "stdin".to_string(),
+        [file_path] => format!("'{}'", file_path),
+        parts => format!("{} file parts", parts.len()),
+    }
+}
+
+// the sampling costs a few seeks, only worth it when the bars or a listener show the ETA
+fn eta_wanted(options: &SlurpOptions) -> bool {
+    options.progress.is_visible() || options.progress_listener.is_some()
+}
+
+fn start_record_stream(
+    file_paths: Vec<String>,
+    start: StreamPosition,
+    options: &SlurpOptions,
+) -> Result<RecordStream, HprofSlurpError> {
+    // records sampled from a local file beforehand make the ETA independent of where the small records are
+    let sampled_file = match &file_paths[..] {
+        [file_path]
+            if file_path != STDIN_PATH
+                && !is_remote_path(file_path)
+                && start == START_POSITION
+                && options.record_filter == RecordFilter::ALL
+                && eta_wanted(options) =>
+        {
+            Some(file_path.clone())
+        }
+        _ => None,
+    };
+    let source = input_source(&file_paths);
+    let (input, file_len) = open_input(file_paths, options.direct_io)?;
+    // the decompressed length is unknown
+    let (input, file_len, expected_records) = match decompressed_input(input)? {
+        (input, Compression::None) => {
+            let expected_records = sampled_file.and_then(|file_path| {
+                match sample_record_counts(&file_path, DEFAULT_SAMPLES) {
+                    Ok(counts) => Some(counts.total()),
+                    Err(e) => {
+                        warn!("Could not sample the records of '{}': {}", file_path, e);
+                        None
+                    }
+                }
+            });
+            (input, file_len, expected_records)
+        }
+        (input, compression) => {
+            info!("Decompressing {:?} input on the fly.", compression);
+            (input, None, None)
+        }
+    };
+    let mut reader = BufReader::new(input);
 
     // Parse file header
     let header = slurp_header(&mut reader)?;
-    let id_size = header.size_pointers;
-    info!(
-        "Processing {} binary hprof file in '{}' format.",
-        pretty_bytes_size(file_len as u64),
-        header.format
-    );
+    match file_len {
+        Some(file_len) => info!(
+            "Processing {} binary hprof file in '{}' format.",
+            pretty_bytes_size(file_len as u64),
+            header.format
+        ),
+        None => info!(
+            "Processing binary hprof stream from {} in '{}' format.",
+            source, header.format
+        ),
+    }
+
+    // resumed runs skip the records already processed, by reading as the input may be compressed or streamed
+    if start.offset > FILE_HEADER_LENGTH {
+        let skip = (start.offset - FILE_HEADER_LENGTH) as u64;
+        let skipped = std::io::copy(&mut (&mut reader).take(skip), &mut std::io::sink())?;
+        if skipped != skip {
+            return Err(InvalidHprofFile {
+                message: format!("input ends before the resumed offset {}", start.offset),
+            });
+        }
+    }
 
     // Communication channel from pre-fetcher to parser
     let (send_data, receive_data): (Sender<Vec<u8>>, Receiver<Vec<u8>>) =
@@ -50,31 +621,28 @@ pub fn slurp_file(file_path: String) -> Result<Heap, HprofSlurpError> {
         crossbeam_channel::unbounded();
 
     // Init pooled binary data with more than 1 element to enable the reader to make progress interdependently
-    for _ in 0..2 {
+    for _ in 0..options.readahead_buffers.max(DEFAULT_READAHEAD_BUFFERS) {
         send_pooled_data
             .send(Vec::with_capacity(READ_BUFFER_SIZE))
             .expect("pre-fetcher channel should be alive");
     }
 
     // Communication channel from parser to recorder
-    let (send_records, receive_records): (Sender<Vec<Record>>, Receiver<Vec<Record>>) =
+    let (send_records, receive_records): (Sender<RecordBatch>, Receiver<RecordBatch>) =
         crossbeam_channel::unbounded();
 
     // Communication channel from recorder to parser (pooled record buffers)
     let (send_pooled_vec, receive_pooled_vec): (Sender<Vec<Record>>, Receiver<Vec<Record>>) =
         crossbeam_channel::unbounded();
 
-    // Communication channel from recorder to main
-    let (send_result, receive_result): (Sender<ResultRecorder>, Receiver<ResultRecorder>) =
-        crossbeam_channel::unbounded();
-
-    // Communication channel from parser to main
-    let (send_progress, receive_progress): (Sender<usize>, Receiver<usize>) =
+    // Communication channel from pre-fetcher, parser and recorder to main
+    let (send_progress, receive_progress): (Sender<Progress>, Receiver<Progress>) =
         crossbeam_channel::unbounded();
 
     // Init pre-fetcher
-    let prefetcher = PrefetchReader::new(reader, file_len, FILE_HEADER_LENGTH, READ_BUFFER_SIZE);
-    let prefetch_thread = prefetcher.start(send_data, receive_pooled_data)?;
+    let prefetcher = PrefetchReader::new(reader, file_len, start.offset, READ_BUFFER_SIZE);
+    let prefetch_thread =
+        prefetcher.start(send_data, receive_pooled_data, send_progress.clone())?;
 
     // Init pooled result vec
     send_pooled_vec
@@ -83,67 +651,42 @@ pub fn slurp_file(file_path: String) -> Result<Heap, HprofSlurpError> {
 
     // Init stream parser
     let initial_loop_buffer = Vec::with_capacity(READ_BUFFER_SIZE); // will be added to the data pool after the first chunk
-    let stream_parser =
-        HprofRecordStreamParser::new(file_len, FILE_HEADER_LENGTH, initial_loop_buffer);
+    let stream_parser = HprofRecordStreamParser::new(file_len, start, initial_loop_buffer)
+        .with_record_filter(options.record_filter)
+        .with_record_limits(options.record_limits);
+    let stream_parser = match expected_records {
+        Some(expected_records) => stream_parser.with_expected_records(expected_records),
+        None => stream_parser,
+    };
+    let stream_parser = if options.keep_record_offsets {
+        stream_parser.with_record_offsets()
+    } else {
+        stream_parser
+    };
 
     // Start stream parser
     let parser_thread = stream_parser.start(
         receive_data,
         send_pooled_data,
-        send_progress,
+        send_progress.clone(),
         receive_pooled_vec,
         send_records,
     )?;
 
-    // Init result recorder
-    let result_recorder = ResultRecorder::new(id_size);
-    let recorder_thread = result_recorder.start(receive_records, send_result, send_pooled_vec)?;
-
-    // Init progress bar
-    let pb = ProgressBar::new(file_len as u64);
-    pb.set_style(ProgressStyle::default_bar()
-        .template("[{elapsed_precise}] [{bar:40.cyan/blue}] {bytes}/{total_bytes} (speed:{bytes_per_sec}) (eta:{eta})")
-        .expect("templating should never fail")
-        .progress_chars("#>-"));
-
-    // Feed progress bar
-    while let Ok(processed) = receive_progress.recv() {
-        pb.set_position(processed as u64)
-    }
-    prefetch_thread
-        .join()
-        .map_err(|e| HprofSlurpError::StdThreadError { e })?;
-
-    // Blocks until parser is done
-    parser_thread
-        .join()
-        .map_err(|e| HprofSlurpError::StdThreadError { e })?;
-
-    // Blocks until recorder is done
-    recorder_thread
-        .join()
-        .map_err(|e| HprofSlurpError::StdThreadError { e })?;
-
-    pb.set_position(99);
-    pb.finish_and_clear();
-    // Wait for final result
-    let result = receive_result
-        .recv()
-        .expect("result channel should be alive");
-
-    let result = parse_instance(result);
-
-    // parser_vm_overview(&result);
-
-    // Blocks until pre-fetcher is done
-
-    // Finish and remove progress bar
-
-    Ok(result)
+    Ok(RecordStream {
+        header,
+        file_len,
+        receive_records,
+        send_pooled_vec,
+        receive_progress,
+        send_progress,
+        prefetch_thread,
+        parser_thread,
+    })
 }
 
 //TODO: support 32bits
-pub fn slurp_header(reader: &mut BufReader<File>) -> Result<FileHeader, HprofSlurpError> {
+pub fn slurp_header<R: Read>(reader: &mut R) -> Result<FileHeader, HprofSlurpError> {
     let mut header_buffer = vec![0; FILE_HEADER_LENGTH];
     reader.read_exact(&mut header_buffer)?;
     let (rest, header) = parse_file_header(&header_buffer).map_err(|e| InvalidHprofFile {
@@ -165,27 +708,20 @@ pub fn slurp_header(reader: &mut BufReader<File>) -> Result<FileHeader, HprofSlu
     Ok(header)
 }
 
-fn parser_vm_overview(result: &ResultRecorder) {
-    //start up time
-    if let Some(str_id) = search_str("sun.management.ManagementFactoryHelper", result) {
-        if let Some(cdf) = search_dump_class(str_id, result) {
-            println!("{:?}", cdf);
-        } else {
-            println!("UNKNOWN: sun.management.ManagementFactoryHelper");
-        }
-    }
+fn parse_instance(value: ResultRecorder, bars: &ProgressBars) -> Heap {
+    let mut heap = Heap::default();
+    let id_size = value.id_size;
 
-    if let Some(str_id) = search_str("sun.management.ManagementFactory", result) {
-        if let Some(cdf) = search_dump_class(str_id, result) {
-            println!("{:?}", cdf);
-        } else {
-            println!("UNKNOWN: sun.management.ManagementFactory");
+    let total = (value.dump_instances.len()
+        + value.dump_primitive_array_dump.len()
+        + value.dump_object_array_dump.len()) as u64;
+    let materialized = AtomicU64::new(0);
+    let report = || {
+        let done = materialized.fetch_add(1, Ordering::Relaxed) + 1;
+        if done.is_multiple_of(MATERIALIZING_REPORT_INTERVAL) || done == total {
+            bars.report(Progress::new(Phase::Materializing, done, Some(total)));
         }
-    }
-}
-
-fn parse_instance(value: ResultRecorder) -> Heap {
-    let mut heap = Heap::default();
+    };
 
     let counter = HeapCounter {
         id_size: value.id_size,
@@ -214,14 +750,21 @@ fn parse_instance(value: ResultRecorder) -> Heap {
         heap_dump_segments_gc_primitive_array_dump: value
             .heap_dump_segments_gc_primitive_array_dump,
         heap_dump_segments_gc_class_dump: value.heap_dump_segments_gc_class_dump,
+        deduplicated_primitive_arrays: value.deduplicated_primitive_arrays,
+        deduplicated_primitive_array_bytes: value.deduplicated_primitive_array_bytes,
+        segments: value.segments,
     };
 
     heap.counter = counter;
+    heap.degradation = value.degradation;
+    heap.stopped_at = value.stopped_at.map(|offset| offset as u64);
+    heap.record_offsets = value.record_offsets.into();
 
     let instance: HashMap<u64, Arc<Instance>> = value
         .dump_instances
         .into_par_iter()
         .map(|ele| {
+            report();
             if let GcRecord::InstanceDump {
                 object_id,
                 stack_trace_serial_number,
@@ -231,18 +774,23 @@ fn parse_instance(value: ResultRecorder) -> Heap {
             } = ele
             {
                 if let Some(class) = value.classes_dump.get(&class_object_id) {
-                    let (a, b) = parse_instance_data(
-                        class,
-                        &bytes_ref,
-                        &value.utf8_strings_by_id,
-                        &value.classes_dump,
-                    );
+                    // field values were not retained
+                    let (a, b) = if bytes_ref.is_empty() {
+                        (Vec::new(), Vec::new())
+                    } else {
+                        parse_instance_data(
+                            class,
+                            &bytes_ref,
+                            &value.utf8_strings_by_id,
+                            &value.classes_dump,
+                        )
+                    };
 
                     let instance = Instance {
                         object_id,
                         stack_trace_serial_number,
                         class_object_id,
-                        data_size,
+                        data_size: data_size as u64,
                         fields: a,
                         super_fields: b,
                     };
@@ -262,6 +810,7 @@ fn parse_instance(value: ResultRecorder) -> Heap {
         .dump_primitive_array_dump
         .into_par_iter()
         .map(|ele| {
+            report();
             if let GcRecord::PrimitiveArrayDump {
                 object_id,
                 stack_trace_serial_number,
@@ -270,21 +819,30 @@ fn parse_instance(value: ResultRecorder) -> Heap {
                 bytes_ref,
             } = ele
             {
-                let (_, value) =
-                    parse_array_value(element_type.clone(), number_of_elements)(&bytes_ref)
-                        .unwrap();
-                let mut fields = Vec::default();
-                fields.push((0, Values::Array(value)));
+                // elements were not retained
+                let retained_elements = if bytes_ref.is_empty() {
+                    0
+                } else {
+                    number_of_elements
+                };
+                let fields = vec![(
+                    0,
+                    Values::PrimitiveArray(PrimitiveArray::new(
+                        element_type,
+                        retained_elements,
+                        bytes_ref,
+                    )),
+                )];
 
                 let instance = Instance {
                     object_id,
                     stack_trace_serial_number,
                     class_object_id: element_type.to_u64(),
-                    data_size: bytes_ref.len() as u32,
+                    // the dump holds the number of elements, the size exceeds 32 bits past 4GiB
+                    data_size: number_of_elements as u64 * primitive_byte_size(&element_type),
                     fields,
                     super_fields: Vec::default(),
                 };
-                drop(bytes_ref);
                 Some((object_id, Arc::new(instance)))
             } else {
                 None
@@ -298,6 +856,7 @@ fn parse_instance(value: ResultRecorder) -> Heap {
         .dump_object_array_dump
         .into_par_iter()
         .map(|ele| {
+            report();
             if let GcRecord::ObjectArrayDump {
                 object_id,
                 stack_trace_serial_number,
@@ -306,19 +865,24 @@ fn parse_instance(value: ResultRecorder) -> Heap {
                 bytes_ref,
             } = ele
             {
+                // elements were not retained
+                let retained_elements = if bytes_ref.is_empty() {
+                    0
+                } else {
+                    number_of_elements
+                };
                 let (_, value) = parse_array_value(
                     crate::parser::gc_record::FieldType::Object,
-                    number_of_elements,
+                    retained_elements,
                 )(&bytes_ref)
                 .unwrap();
-                let mut fields = Vec::default();
-                fields.push((0, Values::Array(value)));
+                let fields = vec![(0, Values::Array(value))];
 
                 let instance = Instance {
                     object_id,
                     stack_trace_serial_number,
                     class_object_id: array_class_id,
-                    data_size: bytes_ref.len() as u32,
+                    data_size: number_of_elements as u64 * id_size as u64,
                     fields,
                     super_fields: Vec::with_capacity(0),
                 };
@@ -332,83 +896,267 @@ fn parse_instance(value: ResultRecorder) -> Heap {
         .filter(|e| e.is_some())
         .map(|e| e.unwrap())
         .collect();
-    heap.instances_pool.extend(instance);
-    heap.instances_pool.extend(instance_primitive_array_dump);
-    heap.instances_pool.extend(instance_object_array_dump);
-
-    heap.utf8_strings = value.utf8_strings_by_id;
-    heap.class_data = value.load_class;
-    heap.classes_dump = value.classes_dump;
-    heap.stack_frame_by_id = value.stack_frame_by_id;
-    heap.stack_trace_by_serial_number = value.stack_trace_by_serial_number;
-    heap.root_jni_global = value.root_jni_global;
-    heap.root_jni_local = value.root_jni_local;
-    heap.root_thread_object = value.root_thread_object;
+    bars.finish_phase(Phase::Materializing);
+    let instances_pool = heap.instances_pool.make_mut();
+    instances_pool.extend(instance);
+    instances_pool.extend(instance_primitive_array_dump);
+    instances_pool.extend(instance_object_array_dump);
+
+    heap.utf8_strings = value.utf8_strings_by_id.into();
+    heap.class_registry = value.class_registry.into();
+    heap.classes_dump = value.classes_dump.into();
+    heap.stack_frame_by_id = value.stack_frame_by_id.into();
+    heap.stack_trace_by_serial_number = value.stack_trace_by_serial_number.into();
+    heap.root_jni_global = value.root_jni_global.into();
+    heap.root_jni_local = value.root_jni_local.into();
+    heap.root_thread_object = value.root_thread_object.into();
+    heap.root_java_frame = value.root_java_frame.into();
+    heap.root_native_stack = value.root_native_stack.into();
+    heap.root_thread_block = value.root_thread_block.into();
+    heap.root_sticky_class = value.root_sticky_class.into();
+    heap.root_monitor_used = value.root_monitor_used.into();
+    heap.root_unknown = value.root_unknown.into();
 
     heap
 }
 
+// Instance values are laid out for the class first, followed by its super class and so on.
 fn parse_instance_data(
     class: &ClassDumpFields,
     data_bytes: &[u8],
     _utf8_strings_by_id: &HashMap<u64, Box<str>>,
-    _classes_dump: &HashMap<u64, ClassDumpFields>,
-) -> (Vec<(u64, Values)>, Vec<(u64, Values)>) {
+    classes_dump: &HashMap<u64, ClassDumpFields>,
+) -> (InstanceFields, InstanceFields) {
+    let (mut data_pt, fields_with_name) = parse_class_fields(class, data_bytes);
+    let mut super_fields_with_name: InstanceFields = Vec::new();
+
+    // super classes, merged
+    let mut super_class_id = class.super_class_object_id;
+    for _ in 0..MAX_CLASS_HIERARCHY_DEPTH {
+        let Some(super_class) = classes_dump.get(&super_class_id) else {
+            break;
+        };
+        let (remaining, fields) = parse_class_fields(super_class, data_pt);
+        data_pt = remaining;
+        super_fields_with_name.extend(fields);
+        super_class_id = super_class.super_class_object_id;
+    }
+
+    (fields_with_name, super_fields_with_name)
+}
+
+fn parse_class_fields<'a>(
+    class: &ClassDumpFields,
+    data_bytes: &'a [u8],
+) -> (&'a [u8], InstanceFields) {
     let mut data_pt = data_bytes;
-    let mut fields_with_name: Vec<(u64, Values)> = Vec::with_capacity(class.instance_fields.len());
-    let super_fields_with_name: Vec<(u64, Values)> = Vec::new();
+    let mut fields_with_name: InstanceFields = Vec::with_capacity(class.instance_fields.len());
     for field in &class.instance_fields {
-        // let name = if let Some(field_name) = utf8_strings_by_id.get(&fields.name_id) {
-        //     field_name.to_string()
-        // } else {
-        //     "UNKNOWN".to_string()
-        // };
-
-        let parser = parse_field_value(field.field_type);
-        let (remaining, value) = parser(data_pt).unwrap();
+        // instances shorter than the layout of their class keep the fields decoded so far
+        let Ok((remaining, value)) = parse_field_value(field.field_type)(data_pt) else {
+            break;
+        };
         data_pt = remaining;
         fields_with_name.push((field.name_id, Values::Single(value)));
     }
+    (data_pt, fields_with_name)
+}
 
-    //super class, merged
-    // if let Some(super_class) = classes_dump.get(&class.super_class_object_id) {
-    //     let (this, s) = parse_instance_data(super_class, data_pt, utf8_strings_by_id, classes_dump);
-    //     super_fields_with_name.extend(this);
-    //     super_fields_with_name.extend(s);
-    // }
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::gc_record::{FieldInfo, FieldType, FieldValue};
 
-    (fields_with_name, super_fields_with_name)
-}
+    fn class(class_object_id: u64, super_class_object_id: u64, fields: &[u64]) -> ClassDumpFields {
+        ClassDumpFields::new(
+            class_object_id,
+            0,
+            super_class_object_id,
+            0,
+            0,
+            Vec::new(),
+            Vec::new(),
+            fields
+                .iter()
+                .map(|name_id| FieldInfo {
+                    name_id: *name_id,
+                    field_type: FieldType::Int,
+                })
+                .collect(),
+        )
+    }
 
-fn search_str(str: &str, result: &ResultRecorder) -> Option<u64> {
-    if let Some((id, _)) = result
-        .utf8_strings_by_id
-        .par_iter()
-        .find_first(|(_, v)| v.contains(str))
-    {
-        Some(*id)
-    } else {
-        None
+    #[test]
+    fn instance_shorter_than_its_layout() {
+        let classes_dump = HashMap::from([(1, class(1, 2, &[10, 11])), (2, class(2, 0, &[12]))]);
+        // the last int of the super class is missing
+        let data = [0, 0, 0, 1, 0, 0, 0, 2, 0, 0];
+        let (fields, super_fields) =
+            parse_instance_data(&classes_dump[&1], &data, &HashMap::new(), &classes_dump);
+        let names: Vec<u64> = fields.iter().map(|(name_id, _)| *name_id).collect();
+        assert_eq!(names, [10, 11]);
+        assert!(matches!(fields[1].1, Values::Single(FieldValue::Int(2))));
+        assert!(super_fields.is_empty());
     }
-}
 
-fn search_dump_class(name_str_id: u64, result: &ResultRecorder) -> Option<ClassDumpFields> {
-    if let Some((_k, v)) = result
-        .load_class
-        .par_iter()
-        .find_first(|(_k, v)| v.class_name_id == name_str_id)
-    {
-        let obj_id = v.class_object_id;
-        if let Some((_, cdf)) = result
-            .classes_dump
-            .par_iter()
-            .find_first(|(_k, v)| v.class_object_id == obj_id)
-        {
-            Some(cdf.clone())
-        } else {
-            None
+    #[test]
+    fn cyclic_super_classes_without_fields() {
+        let classes_dump = HashMap::from([(1, class(1, 2, &[10])), (2, class(2, 1, &[]))]);
+        let (fields, super_fields) = parse_instance_data(
+            &classes_dump[&1],
+            &[0, 0, 0, 1],
+            &HashMap::new(),
+            &classes_dump,
+        );
+        assert_eq!(fields.len(), 1);
+        assert!(super_fields.is_empty());
+    }
+
+    #[test]
+    fn short_instance_reaches_the_size_mismatches() {
+        let mut recorder = ResultRecorder::new(8, SlurpOptions::default());
+        recorder.classes_dump.insert(1, class(1, 0, &[10, 11]));
+        for (object_id, bytes) in [(100, vec![0; 8]), (101, vec![0; 6])] {
+            recorder.dump_instances.push(GcRecord::InstanceDump {
+                object_id,
+                stack_trace_serial_number: 0,
+                class_object_id: 1,
+                data_size: bytes.len() as u32,
+                bytes_ref: bytes.into(),
+            });
         }
-    } else {
-        None
+        let heap = parse_instance(recorder, &ProgressBars::new(ProgressMode::Never, None));
+        assert_eq!(heap.instances_pool[&101].fields.len(), 1);
+        let mismatches = heap.instance_size_mismatches();
+        assert_eq!(mismatches.len(), 1);
+        assert_eq!(
+            (
+                mismatches[0].expected_data_size,
+                mismatches[0].data_sizes.clone()
+            ),
+            (8, vec![6])
+        );
+    }
+
+    #[test]
+    fn input_sources() {
+        let paths = |paths: &[&str]| paths.iter().map(|p| p.to_string()).collect::<Vec<_>>();
+        assert_eq!(input_source(&paths(&[STDIN_PATH])), "stdin");
+        // a compressed local file is streamed as well
+        assert_eq!(input_source(&paths(&["dump.hprof.gz"])), "'dump.hprof.gz'");
+        assert_eq!(
+            input_source(&paths(&["a.part1", "a.part2"])),
+            "2 file parts"
+        );
+    }
+
+    #[test]
+    fn only_the_selected_dump_is_recorded() {
+        use crate::parser::record::Record::{GcSegment, HeapDumpEnd, HeapDumpStart};
+
+        // two heap dumps of one instance each
+        let two_dumps = || {
+            [1, 2].into_iter().flat_map(|object_id| {
+                [
+                    HeapDumpStart {
+                        length: 0,
+                        segment: true,
+                    },
+                    GcSegment(GcRecord::InstanceDump {
+                        object_id,
+                        stack_trace_serial_number: 0,
+                        class_object_id: 100,
+                        data_size: 0,
+                        bytes_ref: Box::default(),
+                    }),
+                    HeapDumpEnd { length: 0 },
+                ]
+            })
+        };
+        let record = |dump_index: Option<usize>| {
+            let options = SlurpOptions {
+                dump_index,
+                ..SlurpOptions::default()
+            };
+            let mut recorder = ResultRecorder::new(8, options);
+            for mut record in two_dumps() {
+                recorder.visit_record(&mut record, None);
+            }
+            recorder
+        };
+        let object_ids = |recorder: &ResultRecorder| -> Vec<u64> {
+            recorder
+                .dump_instances
+                .iter()
+                .filter_map(|record| match record {
+                    GcRecord::InstanceDump { object_id, .. } => Some(*object_id),
+                    _ => None,
+                })
+                .collect()
+        };
+
+        let second = record(Some(1));
+        assert_eq!(object_ids(&second), vec![2]);
+        assert!(check_recorded(&second).is_ok());
+        assert_eq!(object_ids(&record(Some(0))), vec![1]);
+        // merged without a selection
+        let merged = record(None);
+        assert_eq!(object_ids(&merged), vec![1, 2]);
+        assert!(check_recorded(&merged).is_ok());
+
+        assert!(matches!(
+            check_recorded(&record(Some(2))),
+            Err(InvalidDumpIndex {
+                index: 2,
+                heap_dumps: 2
+            })
+        ));
+    }
+
+    #[test]
+    fn records_sampled_only_for_an_eta() {
+        let hidden = SlurpOptions {
+            progress: ProgressMode::Never,
+            ..SlurpOptions::default()
+        };
+        assert!(!eta_wanted(&hidden));
+        let (sender, _receiver) = crossbeam_channel::unbounded();
+        let listened = SlurpOptions {
+            progress_listener: Some(sender),
+            ..hidden
+        };
+        assert!(eta_wanted(&listened));
+        let drawn = SlurpOptions {
+            progress: ProgressMode::Always,
+            ..SlurpOptions::default()
+        };
+        // the bars are never drawn without the `cli` feature
+        assert_eq!(eta_wanted(&drawn), cfg!(feature = "cli"));
+    }
+
+    #[test]
+    fn sample_rate_between_0_and_1() {
+        for rate in [0.0, 0.5, 1.0] {
+            let options = SlurpOptions {
+                primitive_array_sample_rate: rate,
+                ..SlurpOptions::default()
+            };
+            assert!(options.validate().is_ok());
+        }
+        for rate in [-0.1, 1.5, f64::NAN] {
+            let options = SlurpOptions {
+                primitive_array_sample_rate: rate,
+                ..SlurpOptions::default()
+            };
+            assert!(matches!(options.validate(), Err(InvalidSampleRate { .. })));
+        }
+        let options = SlurpOptions {
+            primitive_array_sample_rate: 2.0,
+            ..SlurpOptions::default()
+        };
+        assert!(matches!(
+            slurp_file_with_options("missing.hprof".to_string(), options),
+            Err(InvalidSampleRate { .. })
+        ));
     }
 }
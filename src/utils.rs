@@ -1,24 +1,214 @@
+use std::fmt;
+use std::io::IsTerminal;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+
+use serde::Serialize;
+
 const KILOBYTE: f64 = 1024.0;
 const MEGABYTE: f64 = KILOBYTE * KILOBYTE;
 const GIGABYTE: f64 = KILOBYTE * MEGABYTE;
 
+// powers of 1000 for the SI units
+const BINARY_UNITS: [(&str, f64); 3] = [("GiB", GIGABYTE), ("MiB", MEGABYTE), ("KiB", KILOBYTE)];
+const SI_UNITS: [(&str, f64); 3] = [("GB", 1e9), ("MB", 1e6), ("kB", 1e3)];
+
+/// Units of the sizes rendered by `pretty_bytes_size`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize)]
+pub enum SizeUnits {
+    // KiB, MiB and GiB
+    #[default]
+    Binary,
+    // kB, MB and GB
+    Si,
+}
+
+static SI_UNITS_ENABLED: AtomicBool = AtomicBool::new(false);
+
+/// Sets the units of all subsequent sizes rendered, binary by default.
+pub fn set_size_units(units: SizeUnits) {
+    SI_UNITS_ENABLED.store(units == SizeUnits::Si, Ordering::Relaxed);
+}
+
+pub fn size_units() -> SizeUnits {
+    if SI_UNITS_ENABLED.load(Ordering::Relaxed) {
+        SizeUnits::Si
+    } else {
+        SizeUnits::Binary
+    }
+}
+
 pub fn pretty_bytes_size(len: u64) -> String {
+    pretty_bytes_size_in(len, size_units())
+}
+
+pub fn pretty_bytes_size_in(len: u64, units: SizeUnits) -> String {
     let float_len = len as f64;
-    let (unit, value) = if float_len > GIGABYTE {
-        ("GiB", float_len / GIGABYTE)
-    } else if float_len > MEGABYTE {
-        ("MiB", float_len / MEGABYTE)
-    } else if float_len > KILOBYTE {
-        ("KiB", float_len / KILOBYTE)
-    } else {
-        ("bytes", float_len)
+    let units = match units {
+        SizeUnits::Binary => BINARY_UNITS,
+        SizeUnits::Si => SI_UNITS,
     };
+    let (unit, value) = units
+        .iter()
+        .find(|(_, factor)| float_len > *factor)
+        .map_or(("bytes", float_len), |(unit, factor)| {
+            (*unit, float_len / factor)
+        });
     format!("{:.2}{}", value, unit)
 }
 
+/// Renders a count with `,` separating the thousands whatever the locale, e.g. `1,234,567`.
+pub fn pretty_count(count: u64) -> String {
+    let digits = count.to_string();
+    let mut pretty = String::with_capacity(digits.len() + digits.len() / 3);
+    for (i, digit) in digits.chars().enumerate() {
+        if i > 0 && (digits.len() - i).is_multiple_of(3) {
+            pretty.push(',');
+        }
+        pretty.push(digit);
+    }
+    pretty
+}
+
+/// Renders a duration with the two most significant units, e.g. `1h02m`, `3m05s`, `2.35s` or `120ms`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PrettyDuration(pub Duration);
+
+impl fmt::Display for PrettyDuration {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let seconds = self.0.as_secs();
+        if seconds >= 3600 {
+            write!(f, "{}h{:02}m", seconds / 3600, seconds % 3600 / 60)
+        } else if seconds >= 60 {
+            write!(f, "{}m{:02}s", seconds / 60, seconds % 60)
+        } else if seconds >= 1 {
+            write!(f, "{:.2}s", self.0.as_secs_f64())
+        } else {
+            write!(f, "{}ms", self.0.as_millis())
+        }
+    }
+}
+
+/// Renders milliseconds since the epoch as a UTC date, e.g. `2020-12-17T08:04:33.831Z`.
+pub fn pretty_timestamp(millis: u64) -> String {
+    let days = millis / 86_400_000;
+    let millis_of_day = millis % 86_400_000;
+    // civil date from the days since 1970-01-01, valid for all the u64 days
+    let z = days + 719_468;
+    let era = z / 146_097;
+    let day_of_era = z - era * 146_097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 {
+        month_index + 3
+    } else {
+        month_index - 9
+    };
+    let year = year_of_era + era * 400 + u64::from(month <= 2);
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}.{:03}Z",
+        year,
+        month,
+        day,
+        millis_of_day / 3_600_000,
+        millis_of_day / 60_000 % 60,
+        millis_of_day / 1000 % 60,
+        millis_of_day % 1000
+    )
+}
+
+/// Parses sizes such as `512`, `64KiB`, `10MB` or `1.5g`, units are powers of 1024.
+pub fn parse_bytes_size(input: &str) -> Result<u64, String> {
+    let input = input.trim();
+    let split = input
+        .find(|c: char| !c.is_ascii_digit() && c != '.')
+        .unwrap_or(input.len());
+    let (value, unit) = input.split_at(split);
+    let value: f64 = value
+        .parse()
+        .map_err(|_| format!("invalid size `{}`", input))?;
+    let multiplier = match unit.trim().to_ascii_lowercase().as_str() {
+        "" | "b" | "bytes" => 1.0,
+        "k" | "kb" | "kib" => KILOBYTE,
+        "m" | "mb" | "mib" => MEGABYTE,
+        "g" | "gb" | "gib" => GIGABYTE,
+        _ => return Err(format!("unknown size unit in `{}`", input)),
+    };
+    Ok((value * multiplier) as u64)
+}
+
+/// When to emit ANSI colors in the rendered output.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize)]
+pub enum ColorChoice {
+    // only when stdout is a terminal and `NO_COLOR` is not set
+    #[default]
+    Auto,
+    Always,
+    Never,
+}
+
+impl FromStr for ColorChoice {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "auto" => Ok(ColorChoice::Auto),
+            "always" => Ok(ColorChoice::Always),
+            "never" => Ok(ColorChoice::Never),
+            other => Err(format!("unknown color choice `{}`", other)),
+        }
+    }
+}
+
+impl ColorChoice {
+    /// Whether the output written to stdout is colorized.
+    pub fn enabled(self) -> bool {
+        match self {
+            ColorChoice::Always => true,
+            ColorChoice::Never => false,
+            ColorChoice::Auto => {
+                std::env::var_os("NO_COLOR").is_none() && std::io::stdout().is_terminal()
+            }
+        }
+    }
+}
+
+fn paint(code: &str, text: &str, colors: bool) -> String {
+    if colors {
+        format!("\x1b[{}m{}\x1b[0m", code, text)
+    } else {
+        text.to_string()
+    }
+}
+
+pub fn paint_size(text: &str, colors: bool) -> String {
+    paint("36", text, colors)
+}
+
+pub fn paint_warning(text: &str, colors: bool) -> String {
+    paint("33", text, colors)
+}
+
+// cells produced by `pretty_bytes_size`
+pub fn is_pretty_bytes_size(text: &str) -> bool {
+    let trimmed = text.trim();
+    ["GiB", "MiB", "KiB", "GB", "MB", "kB", "bytes"]
+        .iter()
+        .any(|unit| {
+            trimmed
+                .strip_suffix(unit)
+                .map(|value| value.parse::<f64>().is_ok())
+                .unwrap_or(false)
+        })
+}
+
 #[cfg(test)]
 mod tests {
-    use super::pretty_bytes_size;
+    use super::*;
 
     #[test]
     fn pretty_size_gb() {
@@ -37,4 +227,48 @@ mod tests {
         let size: u64 = 1_200;
         assert_eq!(pretty_bytes_size(size), "1.17KiB");
     }
+
+    #[test]
+    fn parse_sizes_with_units() {
+        assert_eq!(parse_bytes_size("512"), Ok(512));
+        assert_eq!(parse_bytes_size("64KiB"), Ok(64 * 1024));
+        assert_eq!(parse_bytes_size("1.5g"), Ok(3 * 512 * 1024 * 1024));
+        assert!(parse_bytes_size("10 parsecs").is_err());
+    }
+
+    #[test]
+    fn pretty_size_si_units() {
+        assert_eq!(pretty_bytes_size_in(1_200_000, SizeUnits::Si), "1.20MB");
+        assert_eq!(pretty_bytes_size_in(999, SizeUnits::Si), "999.00bytes");
+        assert!(is_pretty_bytes_size("1.20MB"));
+    }
+
+    #[test]
+    fn pretty_counts_and_durations() {
+        assert_eq!(pretty_count(0), "0");
+        assert_eq!(pretty_count(999), "999");
+        assert_eq!(pretty_count(1_234_567), "1,234,567");
+        assert_eq!(
+            PrettyDuration(Duration::from_millis(120)).to_string(),
+            "120ms"
+        );
+        assert_eq!(
+            PrettyDuration(Duration::from_millis(2_346)).to_string(),
+            "2.35s"
+        );
+        assert_eq!(
+            PrettyDuration(Duration::from_secs(185)).to_string(),
+            "3m05s"
+        );
+        assert_eq!(
+            PrettyDuration(Duration::from_secs(3_720)).to_string(),
+            "1h02m"
+        );
+        assert_eq!(pretty_timestamp(0), "1970-01-01T00:00:00.000Z");
+        assert_eq!(pretty_timestamp(1608192273831), "2020-12-17T08:04:33.831Z");
+        assert_eq!(
+            pretty_timestamp(951_782_400_000),
+            "2000-02-29T00:00:00.000Z"
+        );
+    }
 }
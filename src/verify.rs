@@ -0,0 +1,529 @@
+use std::collections::{HashMap, HashSet};
+use std::ops::ControlFlow;
+
+use serde::Serialize;
+
+use crate::analysis::render_table;
+use crate::decompress::{decompressed_input, Compression};
+use crate::errors::HprofSlurpError;
+use crate::parser::gc_record::{ClassDumpFields, FieldType, GcRecord};
+use crate::parser::record::{LoadClassData, Record, StackFrameData, StackTraceData};
+use crate::parser::record_stream_parser::StreamPosition;
+use crate::parser::record_visitor::RecordVisitor;
+use crate::result_recorder::{primitive_byte_size, SegmentStats};
+use crate::slurp::{open_input, slurp_file_with_visitor, slurp_header, SlurpOptions};
+use crate::utils::pretty_count;
+use crate::MAX_CLASS_HIERARCHY_DEPTH;
+
+// failures described per check
+const MAX_EXAMPLES: usize = 5;
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct VerificationCheck {
+    pub name: &'static str,
+    pub checked: u64,
+    pub failures: u64,
+    // the first failures found
+    pub examples: Vec<String>,
+}
+
+impl VerificationCheck {
+    fn new(name: &'static str) -> Self {
+        VerificationCheck {
+            name,
+            ..VerificationCheck::default()
+        }
+    }
+
+    fn check(&mut self, valid: bool, describe: impl FnOnce() -> String) {
+        self.checked += 1;
+        if !valid {
+            self.fail(describe);
+        }
+    }
+
+    fn fail(&mut self, describe: impl FnOnce() -> String) {
+        self.fail_many(1, describe);
+    }
+
+    // failures sharing one description
+    fn fail_many(&mut self, failures: u64, describe: impl FnOnce() -> String) {
+        self.failures += failures;
+        if failures > 0 && self.examples.len() < MAX_EXAMPLES {
+            self.examples.push(describe());
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct VerificationReport {
+    pub records: u64,
+    pub checks: Vec<VerificationCheck>,
+}
+
+/// Parses a whole dump without building a `Heap` and checks its structural invariants: records laid out one
+/// after the other up to the end of the input, heap dump segments balanced, referenced class, string and
+/// stack frame ids defined, and instance sizes matching the field layout of their class.
+///
+/// Meant to confirm that a dump is not corrupted before a long analysis.
+pub fn verify_dump(file_paths: Vec<String>) -> Result<VerificationReport, HprofSlurpError> {
+    let (input, file_len) = open_input(file_paths.clone(), false)?;
+    let (mut input, compression) = decompressed_input(input)?;
+    let header = slurp_header(&mut input)?;
+    let input_len = match compression {
+        Compression::None => file_len.map(|len| len as u64),
+        _ => None,
+    };
+    let options = SlurpOptions {
+        keep_record_offsets: true,
+        ..SlurpOptions::default()
+    };
+    let verifier = Verifier::new(header.size_pointers, input_len);
+    let verifier = slurp_file_with_visitor(file_paths, options, verifier)?;
+    Ok(verifier.report())
+}
+
+/// Collects what `verify_dump` checks once all the records are known, ids can be used before being defined.
+struct Verifier {
+    id_size: u32,
+    // unknown for stdin and compressed inputs
+    input_len: Option<u64>,
+    records: u64,
+    offsets: VerificationCheck,
+    last_offset: Option<usize>,
+    end_offset: usize,
+    segments: SegmentStats,
+    string_ids: HashSet<u64>,
+    classes: Vec<LoadClassData>,
+    stack_frames: Vec<StackFrameData>,
+    stack_traces: Vec<StackTraceData>,
+    class_dumps: HashMap<u64, ClassDumpFields>,
+    // (class id, data size) -> (instances, first object id)
+    instances: HashMap<(u64, u32), (u64, u64)>,
+    // array class id -> (arrays, first object id)
+    object_arrays: HashMap<u64, (u64, u64)>,
+}
+
+impl Verifier {
+    fn new(id_size: u32, input_len: Option<u64>) -> Self {
+        Verifier {
+            id_size,
+            input_len,
+            records: 0,
+            offsets: VerificationCheck::new("Record offsets increasing up to the end of the input"),
+            last_offset: None,
+            end_offset: 0,
+            segments: SegmentStats::default(),
+            string_ids: HashSet::new(),
+            classes: Vec::new(),
+            stack_frames: Vec::new(),
+            stack_traces: Vec::new(),
+            class_dumps: HashMap::new(),
+            instances: HashMap::new(),
+            object_arrays: HashMap::new(),
+        }
+    }
+
+    // `None` if a class of the hierarchy has no class dump or the hierarchy is cyclic
+    fn field_layout_size(&self, class_object_id: u64) -> Option<u64> {
+        let mut size = 0;
+        let mut current = class_object_id;
+        for _ in 0..MAX_CLASS_HIERARCHY_DEPTH {
+            if current == 0 {
+                return Some(size);
+            }
+            let class = self.class_dumps.get(&current)?;
+            size += class
+                .instance_fields
+                .iter()
+                .map(|field| match field.field_type {
+                    FieldType::Object => self.id_size as u64,
+                    field_type => primitive_byte_size(&field_type),
+                })
+                .sum::<u64>();
+            current = class.super_class_object_id;
+        }
+        // cyclic super classes
+        None
+    }
+
+    fn report(mut self) -> VerificationReport {
+        if let Some(input_len) = self.input_len {
+            if self.end_offset as u64 != input_len {
+                let end_offset = self.end_offset;
+                self.offsets.fail(|| {
+                    format!(
+                        "records end at offset {} of a {} bytes input",
+                        end_offset, input_len
+                    )
+                });
+            }
+        }
+
+        let mut segments = VerificationCheck::new("Heap dump segments balanced");
+        segments.checked = self.segments.segments + self.segments.heap_dump_ends;
+        for anomaly in self.segments.anomalies() {
+            segments.fail(|| anomaly);
+        }
+
+        let strings = &self.string_ids;
+        let mut class_names = VerificationCheck::new("Class names resolved");
+        let mut loaded_ids = HashSet::new();
+        for class in &self.classes {
+            loaded_ids.insert(class.class_object_id);
+            class_names.check(strings.contains(&class.class_name_id), || {
+                format!(
+                    "class 0x{:x} named by missing string 0x{:x}",
+                    class.class_object_id, class.class_name_id
+                )
+            });
+        }
+
+        let mut frame_strings = VerificationCheck::new("Stack frame strings resolved");
+        let mut frame_ids = HashSet::new();
+        for frame in &self.stack_frames {
+            frame_ids.insert(frame.stack_frame_id);
+            // the source file is unknown for some frames
+            let missing = [
+                frame.method_name_id,
+                frame.method_signature_id,
+                frame.source_file_name_id,
+            ]
+            .into_iter()
+            .find(|id| *id != 0 && !strings.contains(id));
+            frame_strings.check(missing.is_none(), || {
+                format!(
+                    "stack frame 0x{:x} using missing string 0x{:x}",
+                    frame.stack_frame_id,
+                    missing.unwrap_or_default()
+                )
+            });
+        }
+
+        let mut trace_frames = VerificationCheck::new("Stack trace frames defined");
+        for trace in &self.stack_traces {
+            let missing = trace
+                .stack_frame_ids
+                .iter()
+                .find(|id| !frame_ids.contains(id));
+            trace_frames.check(missing.is_none(), || {
+                format!(
+                    "stack trace {} using missing frame 0x{:x}",
+                    trace.serial_number,
+                    missing.copied().unwrap_or_default()
+                )
+            });
+        }
+
+        let mut loaded = VerificationCheck::new("Class dumps loaded");
+        let mut super_classes = VerificationCheck::new("Super classes defined");
+        let mut field_names = VerificationCheck::new("Field names resolved");
+        let mut class_dumps: Vec<&ClassDumpFields> = self.class_dumps.values().collect();
+        class_dumps.sort_by_key(|class| class.class_object_id);
+        for class in class_dumps {
+            let class_object_id = class.class_object_id;
+            loaded.check(loaded_ids.contains(&class_object_id), || {
+                format!("class dump 0x{:x} without a loaded class", class_object_id)
+            });
+            let super_class_object_id = class.super_class_object_id;
+            super_classes.check(
+                super_class_object_id == 0 || self.class_dumps.contains_key(&super_class_object_id),
+                || {
+                    format!(
+                        "class 0x{:x} extending missing class 0x{:x}",
+                        class_object_id, super_class_object_id
+                    )
+                },
+            );
+            let missing = class
+                .static_fields
+                .iter()
+                .map(|(field, _)| field)
+                .chain(class.instance_fields.iter())
+                .map(|field| field.name_id)
+                .find(|id| !strings.contains(id));
+            field_names.check(missing.is_none(), || {
+                format!(
+                    "class 0x{:x} with a field named by missing string 0x{:x}",
+                    class_object_id,
+                    missing.unwrap_or_default()
+                )
+            });
+        }
+
+        let mut instance_classes = VerificationCheck::new("Instance classes defined");
+        let mut instance_sizes = VerificationCheck::new("Instance sizes matching their class");
+        let mut instances: Vec<_> = self.instances.iter().collect();
+        instances.sort();
+        for ((class_object_id, data_size), (count, object_id)) in instances {
+            instance_classes.checked += count;
+            instance_sizes.checked += count;
+            if !self.class_dumps.contains_key(class_object_id) {
+                instance_classes.fail_many(*count, || {
+                    format!(
+                        "instance 0x{:x} of missing class 0x{:x}",
+                        object_id, class_object_id
+                    )
+                });
+                continue;
+            }
+            match self.field_layout_size(*class_object_id) {
+                Some(expected) if expected != *data_size as u64 => {
+                    instance_sizes.fail_many(*count, || {
+                        format!(
+                            "instance 0x{:x} of {} bytes, its class 0x{:x} lays out {} bytes",
+                            object_id, data_size, class_object_id, expected
+                        )
+                    });
+                }
+                // a missing super class is already reported
+                _ => (),
+            }
+        }
+
+        let mut array_classes = VerificationCheck::new("Object array classes defined");
+        let mut object_arrays: Vec<_> = self.object_arrays.iter().collect();
+        object_arrays.sort();
+        for (array_class_id, (count, object_id)) in object_arrays {
+            array_classes.checked += count;
+            if !self.class_dumps.contains_key(array_class_id) {
+                array_classes.fail_many(*count, || {
+                    format!(
+                        "object array 0x{:x} of missing class 0x{:x}",
+                        object_id, array_class_id
+                    )
+                });
+            }
+        }
+
+        VerificationReport {
+            records: self.records,
+            checks: vec![
+                self.offsets,
+                segments,
+                class_names,
+                frame_strings,
+                trace_frames,
+                loaded,
+                super_classes,
+                field_names,
+                instance_classes,
+                instance_sizes,
+                array_classes,
+            ],
+        }
+    }
+}
+
+impl RecordVisitor for Verifier {
+    fn visit_record(&mut self, record: &mut Record, offset: Option<usize>) {
+        self.records += 1;
+        if let Some(offset) = offset {
+            let last_offset = self.last_offset;
+            self.offsets
+                .check(last_offset.is_none_or(|last| offset > last), || {
+                    format!(
+                        "record at offset {} after a record at offset {}",
+                        offset,
+                        last_offset.unwrap_or_default()
+                    )
+                });
+            self.last_offset = Some(offset);
+        }
+        match record {
+            Record::Utf8String { id, .. } => {
+                self.string_ids.insert(*id);
+            }
+            Record::LoadClass(class) => self.classes.push(class.clone()),
+            Record::StackFrame(frame) => self.stack_frames.push(frame.clone()),
+            Record::StackTrace(trace) => self.stack_traces.push(trace.clone()),
+            Record::HeapDumpStart { length, segment } => {
+                self.segments.record_start(*length, *segment);
+            }
+            Record::HeapDumpEnd { .. } => {
+                self.segments.record_end();
+            }
+            Record::GcSegment(GcRecord::ClassDump(class)) => {
+                self.class_dumps
+                    .insert(class.class_object_id, (**class).clone());
+            }
+            Record::GcSegment(GcRecord::InstanceDump {
+                object_id,
+                class_object_id,
+                data_size,
+                ..
+            }) => {
+                self.instances
+                    .entry((*class_object_id, *data_size))
+                    .or_insert((0, *object_id))
+                    .0 += 1;
+            }
+            Record::GcSegment(GcRecord::ObjectArrayDump {
+                object_id,
+                array_class_id,
+                ..
+            }) => {
+                self.object_arrays
+                    .entry(*array_class_id)
+                    .or_insert((0, *object_id))
+                    .0 += 1;
+            }
+            _ => (),
+        }
+    }
+
+    fn visit_batch_end(&mut self, position: StreamPosition) -> ControlFlow<()> {
+        self.end_offset = position.offset;
+        ControlFlow::Continue(())
+    }
+}
+
+impl VerificationReport {
+    pub fn failures(&self) -> u64 {
+        self.checks.iter().map(|check| check.failures).sum()
+    }
+
+    pub fn is_valid(&self) -> bool {
+        self.failures() == 0
+    }
+
+    pub fn render(&self) -> String {
+        let mut report = format!("\nVerified {} records:\n\n", pretty_count(self.records));
+        let rows: Vec<Vec<String>> = self
+            .checks
+            .iter()
+            .map(|check| {
+                vec![
+                    check.name.to_string(),
+                    pretty_count(check.checked),
+                    pretty_count(check.failures),
+                ]
+            })
+            .collect();
+        report.push_str(&render_table(&["Check", "Checked", "Failures"], &rows));
+        for check in self.checks.iter().filter(|check| check.failures > 0) {
+            report.push_str(&format!("\n{}:\n", check.name));
+            for example in &check.examples {
+                report.push_str(&format!("  {}\n", example));
+            }
+            if check.failures > check.examples.len() as u64 {
+                report.push_str(&format!(
+                    "  and {} more\n",
+                    pretty_count(check.failures - check.examples.len() as u64)
+                ));
+            }
+        }
+        if self.is_valid() {
+            report.push_str("\nNo structural problem found.\n");
+        } else {
+            report.push_str(&format!(
+                "\n{} structural problems found, the dump may be corrupted.\n",
+                pretty_count(self.failures())
+            ));
+        }
+        report
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::gc_record::FieldInfo;
+
+    fn instance(object_id: u64, class_object_id: u64, data_size: u32) -> Record {
+        Record::GcSegment(GcRecord::InstanceDump {
+            object_id,
+            stack_trace_serial_number: 0,
+            class_object_id,
+            data_size,
+            bytes_ref: vec![0; data_size as usize].into(),
+        })
+    }
+
+    #[test]
+    fn report_broken_invariants() {
+        let int_field = FieldInfo {
+            name_id: 2,
+            field_type: FieldType::Int,
+        };
+        let mut records = vec![
+            Record::Utf8String {
+                id: 1,
+                str: "com/acme/Point".into(),
+            },
+            Record::Utf8String {
+                id: 2,
+                str: "x".into(),
+            },
+            Record::LoadClass(LoadClassData {
+                serial_number: 1,
+                class_object_id: 100,
+                stack_trace_serial_number: 0,
+                class_name_id: 1,
+            }),
+            // named by a string missing from the dump
+            Record::LoadClass(LoadClassData {
+                serial_number: 2,
+                class_object_id: 101,
+                stack_trace_serial_number: 0,
+                class_name_id: 9,
+            }),
+            Record::HeapDumpStart {
+                length: 0,
+                segment: true,
+            },
+            Record::GcSegment(GcRecord::ClassDump(Box::new(ClassDumpFields::new(
+                100,
+                0,
+                0,
+                0,
+                4,
+                Vec::new(),
+                Vec::new(),
+                vec![int_field],
+            )))),
+            instance(1, 100, 4),
+            instance(2, 100, 4),
+            instance(3, 100, 8),
+            instance(4, 102, 4),
+        ];
+        let mut verifier = Verifier::new(8, Some(100));
+        for (offset, record) in [10, 20, 30, 40, 50, 60, 70, 80, 80, 90]
+            .into_iter()
+            .zip(records.iter_mut())
+        {
+            verifier.visit_record(record, Some(offset));
+        }
+        let _ = verifier.visit_batch_end(StreamPosition {
+            offset: 100,
+            heap_dump_remaining_len: 0,
+        });
+
+        let report = verifier.report();
+        assert_eq!(report.records, 10);
+        let failures: Vec<(&str, u64, u64)> = report
+            .checks
+            .iter()
+            .filter(|check| check.failures > 0)
+            .map(|check| (check.name, check.checked, check.failures))
+            .collect();
+        assert_eq!(
+            failures,
+            vec![
+                (
+                    "Record offsets increasing up to the end of the input",
+                    10,
+                    1
+                ),
+                ("Heap dump segments balanced", 1, 1),
+                ("Class names resolved", 2, 1),
+                ("Instance classes defined", 4, 1),
+                ("Instance sizes matching their class", 4, 1),
+            ]
+        );
+        assert_eq!(report.failures(), 5);
+        let rendered = report.render();
+        assert!(rendered.contains("instance 0x3 of 8 bytes, its class 0x64 lays out 4 bytes"));
+        assert!(rendered.contains("5 structural problems found"));
+    }
+}
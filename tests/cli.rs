@@ -0,0 +1,638 @@
+// End-to-end runs of the `hprof-slurp` binary on the dumps of `tests/dumps`.
+
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
+
+fn dump_path(name: &str) -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/dumps")
+        .join(name)
+}
+
+fn hprof_slurp(args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_hprof-slurp"))
+        .args(args)
+        .output()
+        .expect("hprof-slurp should run")
+}
+
+// stdout of a successful run
+fn run(args: &[&str]) -> String {
+    let output = hprof_slurp(args);
+    assert!(
+        output.status.success(),
+        "hprof-slurp {:?} failed: {}",
+        args,
+        String::from_utf8_lossy(&output.stderr)
+    );
+    String::from_utf8(output.stdout).expect("the output should be UTF-8")
+}
+
+fn mini_64() -> String {
+    dump_path("mini-64.hprof.gz").display().to_string()
+}
+
+// `mini-64.hprof` decompressed in a temporary directory of its own, for the outputs written next to the dump
+fn uncompressed_mini_64(test_name: &str) -> (PathBuf, PathBuf) {
+    let dir =
+        std::env::temp_dir().join(format!("hprof-slurp-{}-{}", test_name, std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let dump = dir.join("mini-64.hprof");
+    let mut decoder = flate2::read::GzDecoder::new(std::fs::File::open(mini_64()).unwrap());
+    std::io::copy(&mut decoder, &mut std::fs::File::create(&dump).unwrap()).unwrap();
+    (dir, dump)
+}
+
+// cells of the rows of the table following `heading`
+fn table_rows(output: &str, heading: &str) -> Vec<Vec<String>> {
+    let start = output
+        .find(heading)
+        .expect("the heading should be rendered");
+    output[start..]
+        .lines()
+        .skip_while(|line| !line.starts_with("---"))
+        .skip(1)
+        .take_while(|line| line.contains(" | "))
+        .map(|line| {
+            line.split(" | ")
+                .map(|cell| cell.trim().to_string())
+                .collect()
+        })
+        .collect()
+}
+
+fn golden(name: &str) -> String {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/golden")
+        .join(name);
+    std::fs::read_to_string(&path)
+        .unwrap_or_else(|e| panic!("missing golden file {}: {}", path.display(), e))
+}
+
+#[test]
+fn default_report() {
+    let output = run(&["-i", &mini_64()]);
+    assert!(output.contains("Top 20 allocated classes:"));
+    assert!(output.contains("  at Mini.main (Mini.java:15)"));
+    // the record counters are only printed with `--debug`
+    assert!(!output.contains("File content summary:"));
+    assert!(run(&["-i", &mini_64(), "--debug"]).contains("File content summary:"));
+}
+
+#[test]
+fn truncated_tables() {
+    let output = run(&["-i", &mini_64(), "--truncate", "-w", "60"]);
+    let table_lines: Vec<&str> = output.lines().filter(|line| line.contains(" | ")).collect();
+    assert!(!table_lines.is_empty());
+    assert!(table_lines.iter().all(|line| line.chars().count() <= 60));
+    assert!(output.contains("…"));
+
+    let ascii = run(&["-i", &mini_64(), "--truncate", "-w", "60", "--ascii"]);
+    assert!(ascii.contains("..."));
+    assert!(!ascii.contains("…"));
+}
+
+#[test]
+fn histogram_sort() {
+    let heading = "Top 20 allocated classes:";
+    let output = run(&["-i", &mini_64(), "--sort-by", "name"]);
+    let names: Vec<String> = table_rows(&output, heading)
+        .into_iter()
+        .map(|row| row[3].clone())
+        .collect();
+    assert_eq!(names.len(), 20);
+    assert!(names.windows(2).all(|pair| pair[0] <= pair[1]));
+
+    let output = run(&["-i", &mini_64(), "--sort-by", "largest"]);
+    assert_eq!(table_rows(&output, heading)[0][3], "char[]");
+}
+
+#[test]
+fn histogram_filters() {
+    let heading = "Top 20 allocated classes:";
+    let count = |cell: &str| cell.replace(',', "").parse::<u64>().unwrap();
+    let output = run(&["-i", &mini_64(), "--min-instances", "1000"]);
+    let rows = table_rows(&output, heading);
+    assert!(!rows.is_empty());
+    assert!(rows.iter().all(|row| count(&row[1]) >= 1000));
+    assert!(rows.iter().all(|row| row[3] != "char[]"));
+
+    let output = run(&["-i", &mini_64(), "--min-total-size", "30KiB"]);
+    let names: Vec<String> = table_rows(&output, heading)
+        .into_iter()
+        .map(|row| row[3].clone())
+        .collect();
+    assert_eq!(
+        names,
+        [
+            "byte[]",
+            "java.lang.String",
+            "java.lang.Object[]",
+            "java.util.concurrent.ConcurrentHashMap.Node",
+            "char[]",
+            "java.util.HashMap.Node",
+        ]
+    );
+}
+
+#[test]
+fn nested_class_groups() {
+    let heading = "Top 20 allocated classes:";
+    let count = |cell: &str| cell.replace(',', "").parse::<u64>().unwrap();
+    let output = run(&["-i", &mini_64()]);
+    assert!(table_rows(&output, heading)
+        .iter()
+        .any(|row| row[3] == "java.util.HashMap.Node"));
+
+    let output = run(&["-i", &mini_64(), "--group-names", "nested"]);
+    let rows = table_rows(&output, heading);
+    assert!(rows.iter().all(|row| row[3] != "java.util.HashMap.Node"));
+    // the 264 maps and their 1,024 nodes
+    let maps = rows
+        .iter()
+        .find(|row| row[3] == "java.util.HashMap")
+        .unwrap();
+    assert!(count(&maps[1]) >= 1_288);
+}
+
+#[test]
+fn effective_ownership() {
+    let heading = "owned by strings and buffers, top ";
+    assert!(!run(&["-i", &mini_64()]).contains(heading));
+    assert!(run(&["-i", &mini_64(), "--effective-ownership"]).contains(heading));
+}
+
+#[test]
+fn arrays_split_by_owner() {
+    let output = run(&["-i", &mini_64(), "--split-arrays-by-owner"]);
+    // the strings solely own their backing arrays
+    assert!(output.contains("byte[] (owned by java.lang.String)"));
+}
+
+#[test]
+fn si_units() {
+    let output = run(&["-i", &mini_64(), "--si-units"]);
+    assert!(output.contains("kB"));
+    assert!(!output.contains("KiB"));
+    assert!(run(&["-i", &mini_64()]).contains("KiB"));
+}
+
+#[test]
+fn colors() {
+    // stdout is not a terminal
+    assert!(!run(&["-i", &mini_64()]).contains('\x1b'));
+    assert!(run(&["-i", &mini_64(), "--color", "always"]).contains("\x1b[36m"));
+}
+
+#[test]
+fn optional_sections() {
+    let output = run(&["-i", &mini_64()]);
+    assert!(!output.contains("strings backed by"));
+    let output = run(&[
+        "-i",
+        &mini_64(),
+        "--section",
+        "duplicate-strings",
+        "--section",
+        "duplicate-arrays",
+    ]);
+    assert!(output.contains("strings backed by"));
+    let strings = output.find("strings backed by").unwrap();
+    assert!(output.find("Top 20 allocated classes:").unwrap() < strings);
+}
+
+#[test]
+fn auto_report() {
+    let output = run(&["-i", &mini_64(), "--auto"]);
+    assert!(output.contains("File content summary:"));
+    assert!(output.contains("Top 20 allocated classes:"));
+    let output = run(&["-i", &mini_64(), "--auto", "--section", "duplicate-arrays"]);
+    assert!(output.contains("duplicated primitive arrays wasting"));
+}
+
+#[cfg(feature = "graph")]
+#[test]
+fn graph_metrics() {
+    let heading = "Most referenced objects:";
+    assert!(!run(&["-i", &mini_64()]).contains(heading));
+    assert!(run(&["-i", &mini_64(), "--graph-metrics"]).contains(heading));
+}
+
+#[test]
+fn heaviest_fields() {
+    let heading = "reference fields pointing";
+    assert!(!run(&["-i", &mini_64()]).contains(heading));
+    assert!(run(&["-i", &mini_64(), "--heaviest-fields"]).contains(heading));
+}
+
+#[test]
+fn locks_section() {
+    let heading = "monitors and locks in use";
+    assert!(!run(&["-i", &mini_64()]).contains(heading));
+    assert!(run(&["-i", &mini_64(), "--section", "locks"]).contains(heading));
+}
+
+#[cfg(feature = "graph")]
+#[test]
+fn leak_suspects_section() {
+    // only the built-in class loaders and no connections
+    let heading = "No leak suspects found.";
+    assert!(!run(&["-i", &mini_64()]).contains(heading));
+    assert!(!run(&["-i", &mini_64(), "--auto"]).contains("Leak suspects:"));
+    let output = run(&["-i", &mini_64(), "--section", "leak-suspects"]);
+    assert!(output.contains(heading));
+}
+
+#[cfg(feature = "graph")]
+#[test]
+fn caches_section() {
+    let heading = "Found 0 cache instances:";
+    assert!(!run(&["-i", &mini_64()]).contains(heading));
+    assert!(run(&["-i", &mini_64(), "--section", "caches"]).contains(heading));
+}
+
+#[test]
+fn netty_section() {
+    let heading = "Found 0 Netty pool arenas holding";
+    assert!(!run(&["-i", &mini_64()]).contains(heading));
+    assert!(run(&["-i", &mini_64(), "--section", "netty"]).contains(heading));
+}
+
+#[test]
+fn allocation_sites_section() {
+    // HotSpot records no allocation stack traces
+    let heading = "No allocation stack traces found (";
+    assert!(!run(&["-i", &mini_64()]).contains(heading));
+    assert!(run(&["-i", &mini_64(), "--section", "allocation-sites"]).contains(heading));
+}
+
+#[test]
+fn referencing_fields() {
+    let heading = "instances of byte[]:";
+    assert!(!run(&["-i", &mini_64()]).contains(heading));
+    let output = run(&["-i", &mini_64(), "--referencing-fields", "byte[]"]);
+    assert!(output.contains(" references to 2894 instances of byte[]:"));
+    // the backing arrays of the strings
+    assert_eq!(table_rows(&output, heading)[0][2], "java.lang.String.value");
+}
+
+#[cfg(feature = "graph")]
+#[test]
+fn root_retention_section() {
+    let heading = "Retained size by GC root kind (";
+    assert!(!run(&["-i", &mini_64()]).contains(heading));
+    let output = run(&["-i", &mini_64(), "--section", "root-retention"]);
+    let kinds: Vec<String> = table_rows(&output, heading)
+        .into_iter()
+        .map(|row| row[2].clone())
+        .collect();
+    assert!(kinds.iter().any(|kind| kind == "StickyClass"));
+    assert_eq!(kinds.last().unwrap(), "unreachable");
+}
+
+#[test]
+fn metaspace_section() {
+    let heading = "of class metadata for ";
+    assert!(!run(&["-i", &mini_64()]).contains(heading));
+    let output = run(&["-i", &mini_64(), "--section", "metaspace"]);
+    let count = |cell: &str| cell.replace(',', "").parse::<u64>().unwrap();
+    // the 820 classes of the dump
+    let classes: u64 = table_rows(&output, heading)
+        .iter()
+        .map(|row| count(&row[1]))
+        .sum();
+    assert_eq!(classes, 820);
+}
+
+#[test]
+fn value_types_section() {
+    // no big numbers, dates nor UUIDs in the dump
+    let heading = "Found 0 value types, ";
+    assert!(!run(&["-i", &mini_64()]).contains(heading));
+    assert!(run(&["-i", &mini_64(), "--section", "value-types"]).contains(heading));
+}
+
+#[test]
+fn generated_classes_section() {
+    let heading = " generated classes, top ";
+    assert!(!run(&["-i", &mini_64()]).contains(heading));
+    assert!(run(&["-i", &mini_64(), "--section", "generated-classes"]).contains(heading));
+}
+
+#[cfg(feature = "graph")]
+#[test]
+fn exceptions_section() {
+    let heading = " exceptions, top ";
+    assert!(!run(&["-i", &mini_64()]).contains(heading));
+    let output = run(&["-i", &mini_64(), "--section", "exceptions"]);
+    // the preallocated errors of the JVM
+    assert!(output.contains("Found 15 exceptions, top "));
+    assert!(table_rows(&output, heading)
+        .iter()
+        .any(|row| row[2] == "java.lang.OutOfMemoryError"));
+}
+
+#[cfg(feature = "graph")]
+#[test]
+fn static_fields_section() {
+    let heading = " static fields by retained size (";
+    assert!(!run(&["-i", &mini_64()]).contains(heading));
+    let output = run(&["-i", &mini_64(), "--section", "static-fields"]);
+    // the classes of the boot class loader
+    assert!(output.contains("(705 sticky classes, "));
+    assert_eq!(table_rows(&output, heading).len(), 20);
+}
+
+#[test]
+fn sparse_arrays_section() {
+    let heading = " object arrays with at least 50% of null elements, ";
+    assert!(!run(&["-i", &mini_64()]).contains(heading));
+    assert!(run(&["-i", &mini_64(), "--section", "sparse-arrays"]).contains(heading));
+}
+
+#[test]
+fn map_collisions_section() {
+    let heading = " hash maps, the 20 largest:";
+    assert!(!run(&["-i", &mini_64()]).contains(heading));
+    let output = run(&["-i", &mini_64(), "--section", "map-collisions"]);
+    assert_eq!(table_rows(&output, heading).len(), 20);
+    assert!(output.contains("Buckets per chain length: 1: "));
+}
+
+#[test]
+fn structural_duplicates_section() {
+    let heading = " instances equal to another instance of their class, wasting ";
+    assert!(!run(&["-i", &mini_64()]).contains(heading));
+    assert!(run(&["-i", &mini_64(), "--section", "structural-duplicates"]).contains(heading));
+}
+
+#[test]
+fn allocation_age_section() {
+    // the instances of HotSpot dumps all share an empty stack trace
+    let heading = "No allocation stack traces found (";
+    assert!(!run(&["-i", &mini_64()]).contains(heading));
+    let output = run(&["-i", &mini_64(), "--section", "allocation-age"]);
+    assert_eq!(output.matches(heading).count(), 1);
+}
+
+#[cfg(feature = "graph")]
+#[test]
+fn reference_cycles() {
+    assert!(!run(&["-i", &mini_64()]).contains("reference cycles of at least"));
+    let output = run(&[
+        "-i",
+        &mini_64(),
+        "--cycles",
+        "3",
+        "--section",
+        "reference-cycles",
+    ]);
+    assert!(output.contains("reference cycles of at least 3 objects"));
+    assert_eq!(output.matches("reference cycles of at least").count(), 1);
+}
+
+#[test]
+fn strings_output() {
+    let heading = "List of Strings";
+    assert!(run(&["-i", &mini_64(), "-l"]).contains(heading));
+    let path = std::env::temp_dir().join(format!("hprof-slurp-{}.strings", std::process::id()));
+    let path_name = path.display().to_string();
+    let output = run(&["-i", &mini_64(), "-l", "--strings-output", &path_name]);
+    assert!(!output.contains(heading));
+    assert!(output.contains("Top 20 allocated classes:"));
+    let strings = std::fs::read_to_string(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    assert!(strings.contains(heading));
+    assert!(strings.lines().any(|line| line == "Mini"));
+}
+
+#[test]
+fn baselines() {
+    let heading = "above the baseline, top ";
+    assert!(!run(&["-i", &mini_64()]).contains(heading));
+    assert!(run(&["-i", &mini_64(), "--baseline", "jdk"]).contains(heading));
+
+    let path = std::env::temp_dir().join(format!("hprof-slurp-{}.baseline", std::process::id()));
+    let path_name = path.display().to_string();
+    run(&["-i", &mini_64(), "--save-baseline", &path_name]);
+    assert!(std::fs::read_to_string(&path)
+        .unwrap()
+        .contains(" java.lang.String\n"));
+    // nothing grows beyond the profile of the same dump
+    let output = run(&["-i", &mini_64(), "--baseline", &path_name]);
+    std::fs::remove_file(&path).unwrap();
+    assert!(output.contains("above the baseline, top 0 growing classes:"));
+}
+
+#[test]
+fn memory_report() {
+    let heading = "Memory used by hprof-slurp: peak RSS ";
+    assert!(!run(&["-i", &mini_64()]).contains(heading));
+    let output = run(&["-i", &mini_64(), "--memory-report"]);
+    assert!(output.contains(heading));
+    assert!(output.find(heading).unwrap() > output.find("Top 20 allocated classes:").unwrap());
+}
+
+#[test]
+fn quiet() {
+    let output = hprof_slurp(&["-i", &mini_64(), "--quiet"]);
+    assert!(output.status.success());
+    assert!(output.stderr.is_empty());
+}
+
+#[cfg(feature = "exports")]
+#[test]
+fn structured_formats() {
+    let json = run(&["-i", &mini_64(), "--format", "json"]);
+    assert!(json.starts_with('{'));
+    assert!(json.ends_with("]}\n"));
+    assert!(json.contains("{\"kind\":\"thread-info\""));
+    let html = run(&["-i", &mini_64(), "--format", "html"]);
+    assert!(html.starts_with("<!DOCTYPE html>"));
+    assert!(html.contains("<section id=\"memory-usage\">"));
+    let markdown = run(&["-i", &mini_64(), "--format", "md"]);
+    assert!(markdown.contains("Top 20 allocated classes:"));
+}
+
+#[test]
+fn jmap_histo_format() {
+    let output = run(&["-i", &mini_64(), "--format", "jmap-histo"]);
+    assert_eq!(output, golden("mini-64.jmap-histo.txt"));
+}
+
+#[cfg(feature = "exports")]
+#[test]
+fn visualvm_formats() {
+    let json = run(&["-i", &mini_64(), "--format", "visualvm-json"]);
+    assert!(json.starts_with('{'));
+    assert!(json.contains("\"name\":\"java.lang.String\""));
+    let xml = run(&["-i", &mini_64(), "--format", "visualvm-xml"]);
+    assert!(xml.starts_with("<?xml"));
+    assert!(xml.contains("<totalInstances>14488</totalInstances>"));
+}
+
+#[test]
+fn anonymized_class_names() {
+    assert!(run(&["-i", &mini_64()]).contains("Mini"));
+    let output = run(&["-i", &mini_64(), "--anonymize", "salt"]);
+    assert!(output.contains("  at "));
+    assert!(!output.contains("Mini"));
+}
+
+#[test]
+fn checkpoint() {
+    let path = std::env::temp_dir().join(format!("hprof-slurp-{}.checkpoint", std::process::id()));
+    let path_name = path.display().to_string();
+    let args = ["-i", &mini_64(), "--checkpoint", &path_name];
+    if cfg!(feature = "checkpoint") {
+        assert!(run(&args).contains("Top 20 allocated classes:"));
+        // deleted once the run completes
+        assert!(!path.exists());
+    } else {
+        let output = hprof_slurp(&args);
+        assert_eq!(output.status.code(), Some(1));
+        assert!(String::from_utf8_lossy(&output.stderr).contains("`checkpoint` feature"));
+    }
+}
+
+#[test]
+fn max_memory() {
+    let degraded = "Degraded: the estimated memory reached";
+    assert!(!run(&["-i", &mini_64(), "--debug"]).contains(degraded));
+    let output = run(&["-i", &mini_64(), "--debug", "--max-memory", "64KiB"]);
+    assert!(output.contains(degraded));
+    assert!(output.contains("Top 20 allocated classes:"));
+}
+
+#[test]
+fn record_limits() {
+    // two UTF-8 records of the dump are longer
+    let output = hprof_slurp(&["-i", &mini_64(), "--max-record-size", "512"]);
+    assert_eq!(output.status.code(), Some(1));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("oversized record at offset"));
+    let output = run(&[
+        "-i",
+        &mini_64(),
+        "--max-record-size",
+        "512",
+        "--skip-oversized-records",
+    ]);
+    assert!(output.contains("Top 20 allocated classes:"));
+}
+
+#[test]
+fn readahead() {
+    let output = run(&["-i", &mini_64(), "--readahead", "8"]);
+    assert!(output.contains("Top 20 allocated classes:"));
+    assert!(!hprof_slurp(&["-i", &mini_64(), "--readahead", "1"])
+        .status
+        .success());
+}
+
+#[test]
+fn direct_io() {
+    // falls back to the page cache where direct I/O is not available
+    let output = run(&["-i", &mini_64(), "--direct-io"]);
+    assert!(output.contains("Top 20 allocated classes:"));
+}
+
+#[test]
+fn dump_index() {
+    assert!(run(&["-i", &mini_64(), "--dump-index", "0"]).contains("Top 20 allocated classes:"));
+    let output = hprof_slurp(&["-i", &mini_64(), "--dump-index", "1"]);
+    assert_eq!(output.status.code(), Some(1));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("invalid heap dump index 1"));
+}
+
+#[test]
+fn unsupported_dump_fails() {
+    let output = hprof_slurp(&["-i", &dump_path("mini-32.hprof").display().to_string()]);
+    assert_eq!(output.status.code(), Some(1));
+    assert!(String::from_utf8_lossy(&output.stderr).starts_with("error: "));
+}
+
+#[test]
+fn info_command() {
+    let output = run(&["info", &mini_64()]);
+    assert!(output.contains("Format: JAVA PROFILE 1.0.2"));
+    assert!(output.contains("Identifier size: 8 bytes"));
+    assert!(output.contains("File size: unknown (compressed)"));
+}
+
+#[test]
+fn verify_command() {
+    let output = run(&["verify", &mini_64()]);
+    assert!(output.contains("Verified "));
+    assert!(output.contains("No structural problem found."));
+}
+
+#[test]
+fn explain_command() {
+    let output = run(&["explain", "java.lang.String", &mini_64()]);
+    assert!(output.contains("java.lang.String (0x"));
+    assert!(output.contains("header"));
+
+    let output = hprof_slurp(&["explain", "com.acme.Missing", &mini_64()]);
+    assert_eq!(output.status.code(), Some(1));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("com.acme.Missing"));
+}
+
+#[cfg(feature = "graph")]
+#[test]
+fn what_if_command() {
+    let output = run(&["what-if", &mini_64(), "--remove-class", "java.lang.String"]);
+    assert!(output.contains("would free"));
+    assert!(!output.contains("Removing 0 objects"));
+}
+
+#[test]
+fn export_class_command() {
+    let output = run(&[
+        "export-class",
+        "java.lang.String",
+        &mini_64(),
+        "--fields",
+        "hash",
+    ]);
+    let mut lines = output.lines();
+    assert_eq!(lines.next(), Some("object_id,hash"));
+    assert!(lines.next().unwrap().starts_with("0x"));
+
+    let path = std::env::temp_dir().join(format!("hprof-slurp-strings-{}.csv", std::process::id()));
+    let path_name = path.display().to_string();
+    let output = run(&[
+        "export-class",
+        "java.lang.String",
+        &mini_64(),
+        "--fields",
+        "hash",
+        "-o",
+        &path_name,
+    ]);
+    assert!(output.is_empty());
+    let exported = std::fs::read_to_string(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    assert!(exported.starts_with("object_id,hash\n0x"));
+}
+
+#[cfg(feature = "index")]
+#[test]
+fn index_command() {
+    // only uncompressed dumps can be indexed
+    let (dir, dump) = uncompressed_mini_64("index");
+    let output = run(&["index", &dump.display().to_string()]);
+    let sidecar = dir.join("mini-64.hprof.idx");
+    assert!(output.starts_with(&format!("Wrote {} with ", sidecar.display())));
+    assert!(sidecar.is_file());
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn mat_indexes() {
+    let (dir, dump) = uncompressed_mini_64("mat");
+    let output = run(&["-i", &dump.display().to_string(), "--mat-index"]);
+    assert!(output.contains("Top 20 allocated classes:"));
+    assert!(dir.join("mini-64.idx.index").is_file());
+    assert!(dir.join("mini-64.o2hprof.index").is_file());
+    std::fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,19 @@
+// source of mini-64.hprof.gz, regenerated with:
+// javac Mini.java && java -Xshare:off -XX:+UseSerialGC -Xmx16m Mini mini-64.hprof && gzip -9 mini-64.hprof
+import java.lang.management.ManagementFactory;
+import java.util.ArrayList;
+import java.util.List;
+import com.sun.management.HotSpotDiagnosticMXBean;
+
+public class Mini {
+    static final List<Object> HELD = new ArrayList<>();
+
+    public static void main(String[] args) throws Exception {
+        for (int i = 0; i < 100; i++) {
+            HELD.add("item-" + (i % 10));
+        }
+        HELD.add(new int[1024]);
+        HotSpotDiagnosticMXBean bean = ManagementFactory.getPlatformMXBean(HotSpotDiagnosticMXBean.class);
+        bean.dumpHeap(args[0], true);
+    }
+}
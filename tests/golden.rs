@@ -0,0 +1,82 @@
+// End-to-end runs of `slurp_file` on the dumps of `tests/dumps`, compared against `tests/golden`.
+//
+// `UPDATE_GOLDEN=1 cargo test --test golden` rewrites the golden files after an intended change.
+
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+use hprof_slurp::analysis::histogram::class_histogram;
+use hprof_slurp::decompress::decompressed_input;
+use hprof_slurp::errors::HprofSlurpError;
+use hprof_slurp::slurp::{slurp_file_with_options, ProgressMode, SlurpOptions};
+use hprof_slurp::Heap;
+
+// cuts the heap dump segment of mini-64.hprof in the middle
+const TRUNCATED_LENGTH: u64 = 2_000_000;
+
+fn dump_path(name: &str) -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/dumps")
+        .join(name)
+}
+
+fn slurp(path: &Path) -> Result<Heap, HprofSlurpError> {
+    let options = SlurpOptions {
+        progress: ProgressMode::Never,
+        ..Default::default()
+    };
+    slurp_file_with_options(path.to_string_lossy().into_owned(), options)
+}
+
+fn assert_golden(name: &str, actual: &str) {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/golden")
+        .join(name);
+    if std::env::var_os("UPDATE_GOLDEN").is_some() {
+        std::fs::write(&path, actual).unwrap();
+        return;
+    }
+    let expected = std::fs::read_to_string(&path)
+        .unwrap_or_else(|e| panic!("missing golden file {}: {}", path.display(), e));
+    assert!(
+        expected == actual,
+        "{} differs from the output, run with UPDATE_GOLDEN=1 to accept it:\n{}",
+        path.display(),
+        actual
+    );
+}
+
+#[test]
+fn gzip_64_bits_dump() {
+    let heap = slurp(&dump_path("mini-64.hprof.gz")).unwrap();
+    assert_golden("mini-64.summary.txt", &heap.render_summary());
+    assert_golden("mini-64.threads.txt", &heap.render_thread_info());
+    assert_golden("mini-64.histogram.txt", &class_histogram(&heap).render(20));
+}
+
+#[test]
+fn truncated_dump() {
+    let compressed = std::fs::File::open(dump_path("mini-64.hprof.gz")).unwrap();
+    let (input, _) = decompressed_input(Box::new(compressed)).unwrap();
+    let mut truncated = Vec::new();
+    input
+        .take(TRUNCATED_LENGTH)
+        .read_to_end(&mut truncated)
+        .unwrap();
+    let path = Path::new(env!("CARGO_TARGET_TMPDIR")).join("mini-64-truncated.hprof");
+    std::fs::write(&path, truncated).unwrap();
+
+    let heap = slurp(&path).unwrap();
+    assert_golden("mini-64-truncated.summary.txt", &heap.render_summary());
+}
+
+#[test]
+fn unsupported_dumps() {
+    for (dump, golden) in [
+        ("mini-32.hprof", "mini-32.error.txt"),
+        ("android-mini.hprof", "android-mini.error.txt"),
+    ] {
+        let error = slurp(&dump_path(dump)).expect_err("dump is not supported");
+        assert_golden(golden, &format!("{}\n", error));
+    }
+}